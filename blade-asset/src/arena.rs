@@ -29,6 +29,7 @@ impl<T> PartialEq for Handle<T> {
         self.0 == other.0
     }
 }
+impl<T> Eq for Handle<T> {}
 impl<T> hash::Hash for Handle<T> {
     fn hash<H: hash::Hasher>(&self, hasher: &mut H) {
         self.0.hash(hasher);
@@ -0,0 +1,226 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Component, Path, PathBuf},
+    str,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::SystemTime,
+};
+
+const PACK_MAGIC: [u8; 4] = *b"BLPK";
+const PACK_VERSION: u32 = 1;
+
+/// Provider of the source files of assets.
+pub trait AssetSource: Send + Sync + 'static {
+    /// Identity of the source, which is a part of the cache keys.
+    ///
+    /// Different sources providing the same paths need different identities.
+    fn identity(&self) -> String;
+    /// Read the contents at the given path.
+    fn read(&self, path: &Path) -> Option<Vec<u8>>;
+    /// Return a stamp that changes whenever the contents at the path change,
+    /// such as the modification time.
+    ///
+    /// It's only used to detect the changes for hot reloading and watching,
+    /// while the validity of the cooked assets is decided by the contents.
+    fn stamp(&self, path: &Path) -> Option<u64>;
+}
+
+pub(super) fn modified_nanos(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_nanos() as u64)
+}
+
+/// Strip the `.` components, so that the paths in memory match
+/// regardless of how they are joined.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|&component| component != Component::CurDir)
+        .collect()
+}
+
+/// Source reading the files from the file system.
+#[derive(Default)]
+pub struct FileSource;
+
+impl AssetSource for FileSource {
+    fn identity(&self) -> String {
+        "file".to_string()
+    }
+    fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        fs::read(path).ok()
+    }
+    fn stamp(&self, path: &Path) -> Option<u64> {
+        fs::metadata(path)
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| modified_nanos(&metadata))
+    }
+}
+
+/// Source with the contents provided in memory, for example generated at runtime.
+///
+/// Replacing the contents of a path is picked up by the watching.
+pub struct MemorySource {
+    name: String,
+    files: Mutex<HashMap<PathBuf, (Vec<u8>, u64)>>,
+    counter: AtomicU64,
+}
+
+impl MemorySource {
+    /// Create an empty source.
+    ///
+    /// The `name` distinguishes the cooked assets of different sources.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            files: Mutex::default(),
+            counter: AtomicU64::new(1),
+        }
+    }
+
+    /// Put the contents at the given path, replacing any previous ones.
+    pub fn insert(&self, path: impl AsRef<Path>, data: Vec<u8>) {
+        let stamp = self.counter.fetch_add(1, Ordering::Relaxed);
+        self.files
+            .lock()
+            .unwrap()
+            .insert(normalize(path.as_ref()), (data, stamp));
+    }
+
+    pub fn remove(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        self.files
+            .lock()
+            .unwrap()
+            .remove(&normalize(path.as_ref()))
+            .map(|(data, _)| data)
+    }
+}
+
+impl AssetSource for MemorySource {
+    fn identity(&self) -> String {
+        format!("memory:{}", self.name)
+    }
+    fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        let files = self.files.lock().unwrap();
+        files.get(&normalize(path)).map(|entry| entry.0.clone())
+    }
+    fn stamp(&self, path: &Path) -> Option<u64> {
+        let files = self.files.lock().unwrap();
+        files.get(&normalize(path)).map(|&(_, stamp)| stamp)
+    }
+}
+
+/// Source reading the files from a pack, which is loaded into memory as a whole.
+///
+/// A pack starts with the "BLPK" magic, the format version, and the number
+/// of the files, followed by the files. Each file has the length of its path,
+/// the path in UTF-8, the length of the contents, and the contents.
+/// All the numbers are little-endian, with the lengths of contents being 64-bit
+/// and the rest 32-bit.
+pub struct PackSource {
+    name: String,
+    files: HashMap<PathBuf, Vec<u8>>,
+    stamp: u64,
+}
+
+impl PackSource {
+    /// Open a pack file.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let bytes = fs::read(path)?;
+        let mut pack = Self::from_bytes(&path.display().to_string(), &bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        pack.stamp = modified_nanos(&metadata);
+        Ok(pack)
+    }
+
+    /// Create a pack from the bytes in memory.
+    ///
+    /// The `name` distinguishes the cooked assets of different packs.
+    pub fn from_bytes(name: &str, bytes: &[u8]) -> Result<Self, &'static str> {
+        let mut reader = PackReader(bytes);
+        if reader.take(4)? != PACK_MAGIC {
+            return Err("Not an asset pack");
+        }
+        if reader.read_u32()? != PACK_VERSION {
+            return Err("Unsupported pack version");
+        }
+        let count = reader.read_u32()?;
+        let mut files = HashMap::new();
+        for _ in 0..count {
+            let path_size = reader.read_u32()? as usize;
+            let path = str::from_utf8(reader.take(path_size)?).map_err(|_| "Malformed path")?;
+            let data_size = reader.read_u64()? as usize;
+            let data = reader.take(data_size)?;
+            files.insert(normalize(Path::new(path)), data.to_vec());
+        }
+        if !reader.0.is_empty() {
+            return Err("Trailing data after the files");
+        }
+        Ok(Self {
+            name: name.to_string(),
+            files,
+            stamp: 0,
+        })
+    }
+
+    /// Serialize the given files into a pack.
+    pub fn build<'a>(files: impl IntoIterator<Item = (&'a Path, &'a [u8])>) -> Vec<u8> {
+        let files = files.into_iter().collect::<Vec<_>>();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&PACK_MAGIC);
+        bytes.extend_from_slice(&PACK_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(files.len() as u32).to_le_bytes());
+        for (path, data) in files {
+            let path = path.to_str().expect("Pack paths have to be UTF-8");
+            bytes.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(path.as_bytes());
+            bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+}
+
+impl AssetSource for PackSource {
+    fn identity(&self) -> String {
+        format!("pack:{}", self.name)
+    }
+    fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        self.files.get(&normalize(path)).cloned()
+    }
+    fn stamp(&self, path: &Path) -> Option<u64> {
+        // The pack doesn't change after opening
+        self.files.get(&normalize(path)).map(|_| self.stamp)
+    }
+}
+
+struct PackReader<'a>(&'a [u8]);
+
+impl<'a> PackReader<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8], &'static str> {
+        if self.0.len() < size {
+            return Err("Truncated pack");
+        }
+        let (head, tail) = self.0.split_at(size);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, &'static str> {
+        self.take(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, &'static str> {
+        self.take(8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     path::PathBuf,
     sync::{
         Arc,
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
     },
 };
 
@@ -70,3 +70,666 @@ fn test_flatten() {
     flat_roundtrip(&[2u32, 4u32, 6u32][..]);
     flat_roundtrip(vec![1u32, 2, 3]);
 }
+
+#[test]
+fn test_replace() {
+    let choir = choir::Choir::new();
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let am = blade_asset::AssetManager::<Baker>::new(
+        &root.join("cooked"),
+        &choir,
+        Baker {
+            allow_cooking: AtomicBool::new(false),
+        },
+    );
+    let handle = am.insert(5);
+    assert_eq!(am.replace(handle, 7), 5);
+    assert_eq!(am[handle], 7);
+}
+
+/// Parses the number written in the source file.
+#[derive(Default)]
+struct NumberBaker {
+    cook_count: AtomicUsize,
+}
+impl blade_asset::Baker for NumberBaker {
+    type Meta = u32;
+    type Data<'a> = u32;
+    type Output = u32;
+    fn cook(
+        &self,
+        source: &[u8],
+        _extension: &str,
+        _meta: u32,
+        cooker: Arc<blade_asset::Cooker<Self>>,
+        _exe_context: &choir::ExecutionContext,
+    ) {
+        self.cook_count.fetch_add(1, Ordering::SeqCst);
+        let text = std::str::from_utf8(source).unwrap();
+        if text.trim().is_empty() {
+            cooker.fail("Nothing to parse");
+            return;
+        }
+        match text.trim().parse() {
+            Ok(number) => cooker.finish(number),
+            Err(e) => panic!("Unable to parse {text:?}: {e}"),
+        }
+    }
+    fn serve(&self, cooked: u32, _exe_context: &choir::ExecutionContext) -> u32 {
+        cooked
+    }
+    fn delete(&self, _output: u32) {}
+}
+
+#[test]
+fn test_watch() {
+    use std::{
+        fs,
+        time::{Duration, Instant, SystemTime},
+    };
+
+    let choir = choir::Choir::new();
+    let _w1 = choir.add_worker("main");
+    let root = std::env::temp_dir().join(format!("blade-asset-watch-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let path = root.join("number.txt");
+    // Set the modification times explicitly, in case the file system is coarse.
+    let write = |text: &str, seconds: u64| {
+        fs::write(&path, text).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(seconds))
+            .unwrap();
+    };
+    let wait_for_reload = |am: &blade_asset::AssetManager<NumberBaker>, timeout: Duration| {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            let reloaded = am.take_reloaded();
+            if !reloaded.is_empty() {
+                return reloaded;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        Vec::new()
+    };
+
+    write("1", 0);
+    let am = blade_asset::AssetManager::new(&root.join("cooked"), &choir, NumberBaker::default());
+    am.set_watching(Some(Duration::from_millis(50)));
+    let (handle, task) = am.load(&path, 0);
+    task.join();
+    assert_eq!(am[handle], 1);
+    assert!(wait_for_reload(&am, Duration::from_millis(300)).is_empty());
+
+    write("2", 10);
+    let reloaded = wait_for_reload(&am, Duration::from_secs(5));
+    assert_eq!(reloaded, [(handle, 1)]);
+    assert_eq!(am[handle], 2);
+
+    // A broken source keeps the old asset
+    write("two", 20);
+    assert!(wait_for_reload(&am, Duration::from_secs(1)).is_empty());
+    assert_eq!(am[handle], 2);
+
+    write("3", 30);
+    let reloaded = wait_for_reload(&am, Duration::from_secs(5));
+    assert_eq!(reloaded, [(handle, 2)]);
+    assert_eq!(am[handle], 3);
+
+    // Touching the file without changing the contents doesn't reload
+    write("3", 40);
+    assert!(wait_for_reload(&am, Duration::from_secs(1)).is_empty());
+    assert_eq!(am.baker.cook_count.load(Ordering::SeqCst), 4);
+
+    am.set_watching(None);
+    am.clear();
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_dependency_contents() {
+    use std::{
+        fs,
+        time::{Duration, SystemTime},
+    };
+
+    let choir = choir::Choir::new();
+    let _w1 = choir.add_worker("main");
+    let root = std::env::temp_dir().join(format!("blade-asset-deps-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let path = root.join("number.txt");
+    let write = |text: &str, seconds: u64| {
+        fs::write(&path, text).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(seconds))
+            .unwrap();
+    };
+    // Every manager starts with no loaded assets, but shares the cooked files
+    let load = || {
+        let am =
+            blade_asset::AssetManager::new(&root.join("cooked"), &choir, NumberBaker::default());
+        let (handle, task) = am.load(&path, 0);
+        task.join();
+        let value = am[handle];
+        let cook_count = am.baker.cook_count.load(Ordering::SeqCst);
+        am.clear();
+        (value, cook_count)
+    };
+
+    write("5", 0);
+    assert_eq!(load(), (5, 1));
+    assert_eq!(load(), (5, 0));
+    // A newer modification time with the same contents
+    write("5", 10);
+    assert_eq!(load(), (5, 0));
+    write("6", 20);
+    assert_eq!(load(), (6, 1));
+    assert_eq!(load(), (6, 0));
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_branch_switch() {
+    use std::{
+        fs,
+        time::{Duration, SystemTime},
+    };
+
+    let choir = choir::Choir::new();
+    let _w1 = choir.add_worker("main");
+    let root = std::env::temp_dir().join(format!("blade-asset-branch-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let paths = ["a.txt", "b.txt", "c.txt"].map(|name| root.join(name));
+    let write = |path: &std::path::Path, text: &str, time: SystemTime| {
+        fs::write(path, text).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    };
+    let load_all = |force: bool| {
+        let am =
+            blade_asset::AssetManager::new(&root.join("cooked"), &choir, NumberBaker::default());
+        am.set_force_recook(force);
+        let values = paths.clone().map(|path| {
+            let (handle, task) = am.load(&path, 0);
+            task.join();
+            am[handle]
+        });
+        let cook_count = am.baker.cook_count.load(Ordering::SeqCst);
+        am.clear();
+        (values, cook_count)
+    };
+
+    let now = SystemTime::now();
+    for (i, path) in paths.iter().enumerate() {
+        write(path, &i.to_string(), now);
+    }
+    assert_eq!(load_all(false), ([0, 1, 2], 3));
+
+    // Switching to a branch and back rewrites the files with newer times
+    let later = now + Duration::from_secs(10);
+    for (i, path) in paths.iter().enumerate() {
+        write(path, &i.to_string(), later);
+    }
+    assert_eq!(load_all(false), ([0, 1, 2], 0));
+
+    // A different branch may have other contents with the same time
+    write(&paths[1], "7", later);
+    assert_eq!(load_all(false), ([0, 7, 2], 1));
+    assert_eq!(load_all(false), ([0, 7, 2], 0));
+
+    assert_eq!(load_all(true), ([0, 7, 2], 3));
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_progress() {
+    use blade_asset::AssetStatus;
+    use std::fs;
+
+    let choir = choir::Choir::new();
+    let root = std::env::temp_dir().join(format!("blade-asset-progress-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("good.txt"), "4").unwrap();
+    fs::write(root.join("bad.txt"), "four").unwrap();
+
+    let am = blade_asset::AssetManager::new(&root.join("cooked"), &choir, NumberBaker::default());
+    am.set_force_recook(true);
+    let (good, good_task) = am.load(root.join("good.txt"), 0);
+    let good_task = good_task.clone();
+    let (bad, bad_task) = am.load(root.join("bad.txt"), 0);
+    let bad_task = bad_task.clone();
+    // Nothing runs without workers
+    assert_eq!(am.status(good), AssetStatus::Loading);
+    let mut progress = blade_asset::Progress::default();
+    am.collect_progress(&mut progress);
+    assert_eq!((progress.total, progress.completed), (2, 0));
+    progress.running.sort();
+    assert_eq!(progress.running, ["bad.txt", "good.txt"]);
+
+    let _w1 = choir.add_worker("main");
+    good_task.join();
+    bad_task.join();
+    assert_eq!(am.status(good), AssetStatus::Ready);
+    assert_eq!(am[good], 4);
+    match am.status(bad) {
+        AssetStatus::Failed(error) => assert!(error.contains("four"), "{error}"),
+        other => panic!("Unexpected {other:?}"),
+    }
+    let mut progress = blade_asset::Progress::default();
+    am.collect_progress(&mut progress);
+    assert!(progress.is_done());
+    assert_eq!(progress.total, 2);
+    assert!(progress.running.is_empty());
+    assert_eq!(progress.failed.len(), 1);
+    assert_eq!(progress.failed[0].0, "bad.txt");
+
+    am.clear();
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_cook_error() {
+    use blade_asset::AssetStatus;
+    use std::fs;
+
+    let choir = choir::Choir::new();
+    let _w1 = choir.add_worker("main");
+    let root = std::env::temp_dir().join(format!("blade-asset-error-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let path = root.join("empty.txt");
+    fs::write(&path, "").unwrap();
+
+    let am = blade_asset::AssetManager::new(&root.join("cooked"), &choir, NumberBaker::default());
+    let (handle, task) = am.load(&path, 0);
+    task.clone().join();
+    assert_eq!(
+        am.status(handle),
+        AssetStatus::Failed("Nothing to parse".to_string())
+    );
+
+    // Nothing is cached for the failed cook, so the fixed source is picked up
+    fs::write(&path, "3").unwrap();
+    let am = blade_asset::AssetManager::new(&root.join("cooked"), &choir, NumberBaker::default());
+    let (handle, task) = am.load(&path, 0);
+    task.clone().join();
+    assert_eq!(am.status(handle), AssetStatus::Ready);
+    assert_eq!(am[handle], 3);
+    assert_eq!(am.baker.cook_count.load(Ordering::SeqCst), 1);
+
+    am.clear();
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_eviction() {
+    use blade_asset::{AssetStatus, Footprint};
+    use std::fs;
+
+    let choir = choir::Choir::new();
+    let _w1 = choir.add_worker("main");
+    let root = std::env::temp_dir().join(format!("blade-asset-evict-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let path = root.join("number.txt");
+    fs::write(&path, "3").unwrap();
+
+    let am = blade_asset::AssetManager::new(&root.join("cooked"), &choir, NumberBaker::default());
+    let (handle, task) = am.load(&path, 0);
+    task.join();
+    let (same, _) = am.load(&path, 0);
+    assert_eq!(same, handle);
+    assert_eq!(am.ref_count(handle), 2);
+    assert_eq!(am.footprint(handle), Footprint { cpu: 4, gpu: 0 });
+
+    am.release(handle);
+    assert!(am.evict().is_empty());
+    am.release(handle);
+    assert_eq!(am.evict(), [(handle, 3)]);
+    assert_eq!(am.status(handle), AssetStatus::Unloaded);
+    assert_eq!(am.footprint(handle), Footprint::default());
+
+    // Loading again goes through the cache
+    let (reloaded, task) = am.load(&path, 0);
+    task.join();
+    assert_ne!(reloaded, handle);
+    assert_eq!(am[reloaded], 3);
+    assert_eq!(am.ref_count(reloaded), 1);
+    assert_eq!(am.baker.cook_count.load(Ordering::SeqCst), 1);
+
+    am.clear();
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_sources() {
+    use blade_asset::{MemorySource, PackSource};
+    use std::{fs, path::Path, time::Duration};
+
+    let choir = choir::Choir::new();
+    let _w1 = choir.add_worker("main");
+    let root = std::env::temp_dir().join(format!("blade-asset-sources-{}", std::process::id()));
+    let target = root.join("cooked");
+
+    let memory = Arc::new(MemorySource::new("generated"));
+    memory.insert("numbers/one.txt", b"1".to_vec());
+    let am = blade_asset::AssetManager::with_source(
+        &target,
+        &choir,
+        NumberBaker::default(),
+        Arc::clone(&memory) as Arc<dyn blade_asset::AssetSource>,
+    );
+    am.set_watching(Some(Duration::ZERO));
+    let (handle, task) = am.load("numbers/one.txt", 0);
+    task.join();
+    assert_eq!(am[handle], 1);
+
+    // Replacing the contents in memory is picked up by the watching
+    memory.insert("numbers/one.txt", b"11".to_vec());
+    let mut reloaded = Vec::new();
+    for _ in 0..500 {
+        reloaded = am.take_reloaded();
+        if !reloaded.is_empty() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(reloaded, [(handle, 1)]);
+    assert_eq!(am[handle], 11);
+    am.set_watching(None);
+    am.clear();
+
+    // The same path in a pack is cooked separately, sharing the target
+    let pack_bytes = PackSource::build([(Path::new("numbers/one.txt"), &b"100"[..])]);
+    let pack = PackSource::from_bytes("test", &pack_bytes).unwrap();
+    let am = blade_asset::AssetManager::with_source(
+        &target,
+        &choir,
+        NumberBaker::default(),
+        Arc::new(pack),
+    );
+    let (handle, task) = am.load("numbers/one.txt", 0);
+    task.join();
+    assert_eq!(am[handle], 100);
+    assert_eq!(am.baker.cook_count.load(Ordering::SeqCst), 1);
+    am.clear();
+    assert!(PackSource::from_bytes("broken", &pack_bytes[..10]).is_err());
+
+    // The cooked asset of the memory source is kept intact
+    let memory = MemorySource::new("generated");
+    memory.insert("numbers/one.txt", b"11".to_vec());
+    let am = blade_asset::AssetManager::with_source(
+        &target,
+        &choir,
+        NumberBaker::default(),
+        Arc::new(memory),
+    );
+    let (handle, task) = am.load("numbers/one.txt", 0);
+    task.join();
+    assert_eq!(am[handle], 11);
+    assert_eq!(am.baker.cook_count.load(Ordering::SeqCst), 0);
+    am.clear();
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+#[test]
+fn test_compression() {
+    use blade_asset::Compression;
+    use std::fs;
+
+    let choir = choir::Choir::new();
+    let _w1 = choir.add_worker("main");
+    let root = std::env::temp_dir().join(format!("blade-asset-compress-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let path = root.join("number.txt");
+    fs::write(&path, "12345").unwrap();
+    let target = root.join("cooked");
+    let load = |compression: Compression| {
+        let am = blade_asset::AssetManager::new(&target, &choir, NumberBaker::default());
+        am.set_compression(compression);
+        let (handle, task) = am.load(&path, 0);
+        task.join();
+        let value = am[handle];
+        let cook_count = am.baker.cook_count.load(Ordering::SeqCst);
+        am.clear();
+        (value, cook_count)
+    };
+    let cooked_size = || {
+        let entry = fs::read_dir(&target).unwrap().next().unwrap().unwrap();
+        entry.metadata().unwrap().len()
+    };
+
+    assert_eq!(load(Compression::None), (12345, 1));
+    let plain_size = cooked_size();
+    // The uncompressed files keep the layout of the older versions, ending with the data
+    let entry = fs::read_dir(&target).unwrap().next().unwrap().unwrap();
+    let bytes = fs::read(entry.path()).unwrap();
+    let data_offset = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+    assert_eq!(data_offset, bytes.len() as u64 - 4);
+    assert_eq!(bytes[bytes.len() - 4..], 12345u32.to_le_bytes());
+    // Either kind of the files is readable with any setting
+    assert_eq!(load(Compression::Zstd), (12345, 0));
+    fs::remove_dir_all(&target).unwrap();
+    assert_eq!(load(Compression::Zstd), (12345, 1));
+    assert_ne!(cooked_size(), plain_size);
+    assert_eq!(load(Compression::None), (12345, 0));
+
+    // Corrupted data gets cooked again
+    let entry = fs::read_dir(&target).unwrap().next().unwrap().unwrap();
+    let mut bytes = fs::read(entry.path()).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xFF;
+    fs::write(entry.path(), bytes).unwrap();
+    assert_eq!(load(Compression::None), (12345, 1));
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Sums up the numbers in the files listed by the source,
+/// reading them in parallel forks.
+#[derive(Default)]
+struct SumBaker {
+    running: Arc<AtomicUsize>,
+    max_running: Arc<AtomicUsize>,
+}
+impl blade_asset::Baker for SumBaker {
+    type Meta = u32;
+    type Data<'a> = u32;
+    type Output = u32;
+    fn cook(
+        &self,
+        source: &[u8],
+        _extension: &str,
+        _meta: u32,
+        cooker: Arc<blade_asset::Cooker<Self>>,
+        exe_context: &choir::ExecutionContext,
+    ) {
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_running.fetch_max(running, Ordering::SeqCst);
+        let names = std::str::from_utf8(source)
+            .unwrap()
+            .split_whitespace()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>();
+        let sum = Arc::new(AtomicUsize::new(0));
+        let sum_arg = Arc::clone(&sum);
+        let cooker_arg = Arc::clone(&cooker);
+        let read_task = exe_context
+            .fork("read")
+            .init_iter(names.into_iter(), move |_, name| {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                let data = cooker_arg.add_dependency(&name);
+                let number = std::str::from_utf8(&data).unwrap().trim().parse::<usize>();
+                sum_arg.fetch_add(number.unwrap(), Ordering::SeqCst);
+            });
+        let running = Arc::clone(&self.running);
+        exe_context
+            .fork("sum")
+            .init(move |_| {
+                cooker.finish(sum.load(Ordering::SeqCst) as u32);
+                running.fetch_sub(1, Ordering::SeqCst);
+            })
+            .depend_on(&read_task);
+    }
+    fn serve(&self, cooked: u32, _exe_context: &choir::ExecutionContext) -> u32 {
+        cooked
+    }
+    fn delete(&self, _output: u32) {}
+}
+
+#[test]
+fn test_cook_limit() {
+    use std::fs;
+
+    let choir = choir::Choir::new();
+    let _workers = (0..4)
+        .map(|i| choir.add_worker(&format!("worker{i}")))
+        .collect::<Vec<_>>();
+    let root = std::env::temp_dir().join(format!("blade-asset-limit-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    for i in 0..4 {
+        fs::write(root.join(format!("{i}.txt")), i.to_string()).unwrap();
+    }
+    let lists = (0..6)
+        .map(|i| {
+            let path = root.join(format!("list{i}.sum"));
+            fs::write(&path, "3.txt 1.txt 2.txt 0.txt").unwrap();
+            path
+        })
+        .collect::<Vec<_>>();
+    let cook_all = |target: &str, limit: Option<usize>| {
+        let am = blade_asset::AssetManager::new(&root.join(target), &choir, SumBaker::default());
+        let cook_limit = Arc::new(blade_asset::CookLimit::new(limit));
+        am.set_cook_limit(&cook_limit);
+        am.set_force_recook(true);
+        let loads = lists
+            .iter()
+            .map(|path| {
+                let (handle, task) = am.load(path, 0);
+                (handle, task.clone())
+            })
+            .collect::<Vec<_>>();
+        let values = loads
+            .into_iter()
+            .map(|(handle, task)| {
+                task.join();
+                am[handle]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(cook_limit.pending_count(), 0);
+        let max_running = am.baker.max_running.load(Ordering::SeqCst);
+        am.clear();
+        (values, max_running)
+    };
+    let read_cooked = |target: &str| {
+        let mut files = fs::read_dir(root.join(target))
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                (entry.file_name(), fs::read(entry.path()).unwrap())
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    };
+
+    let (values, max_running) = cook_all("limited", Some(2));
+    assert_eq!(values, [6; 6]);
+    assert!(max_running <= 2, "{max_running} cooks at once");
+    let (values, _) = cook_all("unlimited", None);
+    assert_eq!(values, [6; 6]);
+    // The dependencies are read in any order, but written sorted
+    assert_eq!(read_cooked("limited"), read_cooked("unlimited"));
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Serves the source as is, to measure the cooked files of real assets.
+struct CopyBaker;
+impl blade_asset::Baker for CopyBaker {
+    type Meta = u32;
+    type Data<'a> = &'a [u8];
+    type Output = usize;
+    fn cook(
+        &self,
+        source: &[u8],
+        _extension: &str,
+        _meta: u32,
+        cooker: Arc<blade_asset::Cooker<Self>>,
+        _exe_context: &choir::ExecutionContext,
+    ) {
+        cooker.finish(source);
+    }
+    fn serve(&self, cooked: &[u8], _exe_context: &choir::ExecutionContext) -> usize {
+        cooked.len()
+    }
+    fn delete(&self, _output: usize) {}
+}
+
+#[test]
+#[ignore = "benchmark, run with `--release -- --ignored --nocapture`"]
+fn bench_compression() {
+    use blade_asset::Compression;
+    use std::{fs, time::Instant};
+    const LOAD_COUNT: u32 = 20;
+
+    let choir = choir::Choir::new();
+    let _w1 = choir.add_worker("main");
+    let examples = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../examples");
+    let sources = [
+        "scene/data/monkey.bin",
+        "vehicle/data/raceFuture-body.glb",
+        "vehicle/data/wheelRacing.glb",
+        "vehicle/data/orange_light_grid.png",
+    ]
+    .map(|name| examples.join(name));
+    let source_size = sources
+        .iter()
+        .map(|path| fs::metadata(path).unwrap().len())
+        .sum::<u64>();
+    println!("Sources: {source_size} bytes");
+
+    for compression in [Compression::None, Compression::Zstd] {
+        let target = std::env::temp_dir().join(format!(
+            "blade-asset-bench-{:?}-{}",
+            compression,
+            std::process::id()
+        ));
+        let load_all = || {
+            let am = blade_asset::AssetManager::new(&target, &choir, CopyBaker);
+            am.set_compression(compression);
+            let start = Instant::now();
+            for path in sources.iter() {
+                let (_, task) = am.load(path, 0);
+                task.join();
+            }
+            let elapsed = start.elapsed();
+            am.clear();
+            elapsed
+        };
+
+        let cook_time = load_all();
+        let cooked_size = fs::read_dir(&target)
+            .unwrap()
+            .map(|entry| entry.unwrap().metadata().unwrap().len())
+            .sum::<u64>();
+        let load_time = (0..LOAD_COUNT)
+            .map(|_| load_all())
+            .sum::<std::time::Duration>()
+            / LOAD_COUNT;
+        println!(
+            "{compression:?}: cooked {cooked_size} bytes in {cook_time:?}, loaded in {load_time:?}"
+        );
+        let _ = fs::remove_dir_all(&target);
+    }
+}
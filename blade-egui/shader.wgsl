@@ -4,10 +4,14 @@ struct VertexOutput {
     @builtin(position) position: vec4<f32>,
 };
 
+// Has to match `UiColorMode`
+const COLOR_MODE_GAMMA: u32 = 0u;
+
 struct Uniforms {
     screen_size: vec2<f32>,
-    convert_to_linear: f32,
-    padding: f32,
+    color_mode: u32,
+    is_linear_target: u32,
+    dithering: u32,
 };
 var<uniform> r_uniforms: Uniforms;
 
@@ -45,14 +49,95 @@ fn vs_main(
     return out;
 }
 
+// Has to match `TextureEncoding`
+const TEXTURE_ENCODING_SRGB: u32 = 0u;
+const TEXTURE_ENCODING_LINEAR_TONE_MAPPED: u32 = 2u;
+
+struct TextureParams {
+    encoding: u32,
+};
+
 var r_texture: texture_2d<f32>;
 var r_sampler: sampler;
+var<uniform> r_texture_params: TextureParams;
+
+fn gamma_from_linear(rgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = rgb < vec3<f32>(0.0031308);
+    let lower = rgb * vec3<f32>(12.92);
+    let higher = vec3<f32>(1.055) * pow(rgb, vec3<f32>(1.0 / 2.4)) - vec3<f32>(0.055);
+    return select(higher, lower, cutoff);
+}
+
+// Convert a premultiplied color from gamma into linear space.
+fn linear_from_gamma_premultiplied(color: vec4<f32>) -> vec4<f32> {
+    if (color.a <= 0.0) {
+        return vec4<f32>(0.0);
+    }
+    return vec4<f32>(linear_from_gamma(color.rgb / color.a) * color.a, color.a);
+}
+
+// Convert a premultiplied color from linear into gamma space.
+fn gamma_from_linear_premultiplied(color: vec4<f32>) -> vec4<f32> {
+    if (color.a <= 0.0) {
+        return vec4<f32>(0.0);
+    }
+    return vec4<f32>(gamma_from_linear(color.rgb / color.a) * color.a, color.a);
+}
+
+// Offset of the 4x4 ordered dither at the given pixel, in (-0.5, 0.5).
+fn bayer_offset(pixel: vec2<f32>) -> f32 {
+    let x = u32(pixel.x) & 3u;
+    let y = u32(pixel.y) & 3u;
+    let v = x ^ y;
+    let index = ((v & 1u) << 3u) | ((y & 1u) << 2u) | (v & 2u) | ((y & 2u) >> 1u);
+    return (f32(index) + 0.5) / 16.0 - 0.5;
+}
+
+// Dither a color in gamma space before it gets quantized to 8 bits.
+// The amplitude stays below half of a step, so the flat colors are not affected.
+fn dither(color: vec4<f32>, pixel: vec2<f32>) -> vec4<f32> {
+    if (r_uniforms.dithering == 0u) {
+        return color;
+    }
+    let offset = bayer_offset(pixel) / 255.0;
+    return vec4<f32>(clamp(color.rgb + vec3<f32>(offset), vec3<f32>(0.0), vec3<f32>(color.a)), color.a);
+}
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    //Note: we always assume rendering to linear color space,
-    // but Egui wants to blend in gamma space, see
-    // https://github.com/emilk/egui/pull/2071
-    let blended = in.color * textureSample(r_texture, r_sampler, in.tex_coord);
-    return vec4f(linear_from_gamma(blended.xyz), blended.a);
+    var texel = textureSample(r_texture, r_sampler, in.tex_coord);
+    let is_gamma_texel = r_texture_params.encoding == TEXTURE_ENCODING_SRGB;
+    if (!is_gamma_texel) {
+        var color = max(texel.rgb, vec3<f32>(0.0));
+        if (r_texture_params.encoding == TEXTURE_ENCODING_LINEAR_TONE_MAPPED) {
+            color = color / (color + vec3<f32>(1.0));
+        }
+        texel = vec4<f32>(min(color, vec3<f32>(1.0)), texel.a);
+    }
+
+    if (r_uniforms.color_mode == COLOR_MODE_GAMMA) {
+        // Egui wants to blend in gamma space, see
+        // https://github.com/emilk/egui/pull/2071
+        if (!is_gamma_texel) {
+            texel = vec4<f32>(gamma_from_linear(texel.rgb), texel.a);
+        }
+        let blended = dither(in.color * texel, in.position.xy);
+        if (r_uniforms.is_linear_target != 0u) {
+            return vec4<f32>(linear_from_gamma(blended.rgb), blended.a);
+        }
+        return blended;
+    } else {
+        if (is_gamma_texel) {
+            texel = linear_from_gamma_premultiplied(texel);
+        }
+        let blended = linear_from_gamma_premultiplied(in.color) * texel;
+        if (r_uniforms.dithering == 0u && r_uniforms.is_linear_target != 0u) {
+            return blended;
+        }
+        let output = dither(gamma_from_linear_premultiplied(blended), in.position.xy);
+        if (r_uniforms.is_linear_target != 0u) {
+            return linear_from_gamma_premultiplied(output);
+        }
+        return output;
+    }
 }
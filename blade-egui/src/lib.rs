@@ -16,13 +16,19 @@
 const SHADER_SOURCE: &str = include_str!("../shader.wgsl");
 
 use blade_util::{BufferBelt, BufferBeltDescriptor};
-use std::collections::hash_map::{Entry, HashMap};
+use std::{
+    collections::hash_map::{Entry, HashMap},
+    fmt,
+};
 
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
 struct Uniforms {
     screen_size: [f32; 2],
-    padding: [f32; 2],
+    color_mode: u32,
+    is_linear_target: u32,
+    dithering: u32,
+    pad: u32,
 }
 
 #[derive(blade_macros::ShaderData)]
@@ -30,13 +36,103 @@ struct Globals {
     r_uniforms: Uniforms,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct TextureParams {
+    encoding: u32,
+}
+
 #[derive(blade_macros::ShaderData)]
 struct Locals {
     r_vertex_data: blade_graphics::BufferPiece,
     r_texture: blade_graphics::TextureView,
     r_sampler: blade_graphics::Sampler,
+    r_texture_params: TextureParams,
+}
+
+/// Color space, in which the GUI colors are blended.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UiColorMode {
+    /// Blend in the sRGB space, matching the reference look of egui.
+    /// This requires a target that isn't sRGB, otherwise the hardware
+    /// blending happens in linear space, making the translucent parts darker.
+    #[default]
+    Gamma = 0,
+    /// Convert the colors into linear space, and blend them there.
+    /// This requires an sRGB or a floating point target, otherwise
+    /// the hardware blending happens in sRGB space.
+    Linear = 1,
+}
+
+/// Return true if the target expects linear colors in the fragment output.
+fn is_linear_format(format: blade_graphics::TextureFormat) -> bool {
+    format.is_srgb()
+        || matches!(
+            format,
+            blade_graphics::TextureFormat::R16Float
+                | blade_graphics::TextureFormat::Rg16Float
+                | blade_graphics::TextureFormat::Rgba16Float
+                | blade_graphics::TextureFormat::R32Float
+                | blade_graphics::TextureFormat::Rg32Float
+                | blade_graphics::TextureFormat::Rgba32Float
+                | blade_graphics::TextureFormat::Rg11b10Ufloat
+                | blade_graphics::TextureFormat::Rgb9e5Ufloat
+        )
+}
+
+/// Return true if the target has 8 bits per channel, which makes the gradients band.
+fn is_8bit_format(format: blade_graphics::TextureFormat) -> bool {
+    matches!(
+        format,
+        blade_graphics::TextureFormat::R8Unorm
+            | blade_graphics::TextureFormat::Rg8Unorm
+            | blade_graphics::TextureFormat::Rg8Snorm
+            | blade_graphics::TextureFormat::Rgba8Unorm
+            | blade_graphics::TextureFormat::Rgba8UnormSrgb
+            | blade_graphics::TextureFormat::Bgra8Unorm
+            | blade_graphics::TextureFormat::Bgra8UnormSrgb
+            | blade_graphics::TextureFormat::Rgba8Snorm
+    )
+}
+
+/// Color encoding of a user texture.
+///
+/// egui blends the colors in the sRGB space, so the linear
+/// textures get encoded into sRGB when painted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextureEncoding {
+    /// Colors are already encoded in sRGB, like in the textures managed by egui.
+    /// This is the case for a render target that went through the post-processing
+    /// into a non-sRGB format.
+    #[default]
+    Srgb = 0,
+    /// Linear colors in the range from 0 to 1.
+    /// This is also the case for any sRGB format, since it's decoded by the sampler.
+    Linear = 1,
+    /// Linear HDR colors, compressed by the Reinhard tone mapping.
+    LinearToneMapped = 2,
+}
+
+/// Error indicating an invalid operation on a user texture.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TextureError {
+    /// The texture is managed by egui, and only changed by `update_textures`.
+    Managed(egui::TextureId),
+    /// The texture isn't registered.
+    Unknown(egui::TextureId),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Managed(id) => write!(f, "texture {id:?} is managed by egui"),
+            Self::Unknown(id) => write!(f, "texture {id:?} is not registered"),
+        }
+    }
 }
 
+impl std::error::Error for TextureError {}
+
 #[derive(Debug, PartialEq)]
 pub struct ScreenDescriptor {
     pub physical_size: (u32, u32),
@@ -49,12 +145,90 @@ impl ScreenDescriptor {
         let logical_height = self.physical_size.1 as f32 / self.scale_factor;
         (logical_width, logical_height)
     }
+
+    /// Convert a clip rectangle in points into a scissor rectangle in pixels.
+    /// Returns `None` if nothing is visible.
+    fn clip_to_scissor(&self, clip_rect: &egui::Rect) -> Option<blade_graphics::ScissorRect> {
+        // Make sure clip rect can fit within an `u32`.
+        let clip_min_x = (self.scale_factor * clip_rect.min.x)
+            .clamp(0.0, self.physical_size.0 as f32)
+            .trunc() as i32;
+        let clip_min_y = (self.scale_factor * clip_rect.min.y)
+            .clamp(0.0, self.physical_size.1 as f32)
+            .trunc() as i32;
+        let clip_max_x = (self.scale_factor * clip_rect.max.x)
+            .clamp(0.0, self.physical_size.0 as f32)
+            .ceil() as i32;
+        let clip_max_y = (self.scale_factor * clip_rect.max.y)
+            .clamp(0.0, self.physical_size.1 as f32)
+            .ceil() as i32;
+
+        if clip_max_x <= clip_min_x || clip_max_y <= clip_min_y {
+            return None;
+        }
+        Some(blade_graphics::ScissorRect {
+            x: clip_min_x,
+            y: clip_min_y,
+            w: (clip_max_x - clip_min_x) as u32,
+            h: (clip_max_y - clip_min_y) as u32,
+        })
+    }
+}
+
+/// Custom rendering inside of an egui widget.
+///
+/// It's invoked by `GuiPainter` for the `egui::PaintCallback` primitives
+/// created by `Callback::new_paint_callback`.
+pub trait CallbackTrait: Send + Sync {
+    /// Record the work needed for painting, such as the uploads of the data.
+    ///
+    /// Called by `GuiPainter::prepare_callbacks`, before the render pass starts.
+    fn prepare(
+        &self,
+        _command_encoder: &mut blade_graphics::CommandEncoder,
+        _sd: &ScreenDescriptor,
+        _context: &blade_graphics::Context,
+    ) {
+    }
+
+    /// Paint into the render pass of the GUI.
+    ///
+    /// The viewport is set to the rectangle of the callback,
+    /// and the scissor rectangle to the visible part of it.
+    /// The pipeline and the bindings are up to the callback.
+    fn paint(
+        &self,
+        info: egui::epaint::PaintCallbackInfo,
+        pass: &mut blade_graphics::RenderCommandEncoder,
+        sd: &ScreenDescriptor,
+    );
+}
+
+/// Paint callback recognized by `GuiPainter`.
+pub struct Callback(Box<dyn CallbackTrait>);
+
+impl Callback {
+    /// Create a paint callback covering the given rectangle, to be added as a shape.
+    pub fn new_paint_callback(
+        rect: egui::Rect,
+        callback: impl CallbackTrait + 'static,
+    ) -> egui::epaint::PaintCallback {
+        egui::epaint::PaintCallback {
+            rect,
+            callback: std::sync::Arc::new(Self(Box::new(callback))),
+        }
+    }
 }
 
 struct GuiTexture {
-    allocation: blade_graphics::Texture,
+    /// The texture object, unless it's owned by the user.
+    allocation: Option<blade_graphics::Texture>,
     view: blade_graphics::TextureView,
+    /// Size of the texture object, or zero if it's owned by the user.
+    size: blade_graphics::Extent,
+    options: egui::TextureOptions,
     sampler: blade_graphics::Sampler,
+    encoding: TextureEncoding,
 }
 
 #[inline]
@@ -65,6 +239,32 @@ const fn egui_texture_filter_to_blade(filter: egui::TextureFilter) -> blade_grap
     }
 }
 
+fn create_sampler(
+    context: &blade_graphics::Context,
+    name: &str,
+    options: egui::TextureOptions,
+) -> blade_graphics::Sampler {
+    context.create_sampler(blade_graphics::SamplerDesc {
+        name,
+        address_modes: {
+            let mode = match options.wrap_mode {
+                egui::TextureWrapMode::ClampToEdge => blade_graphics::AddressMode::ClampToEdge,
+                egui::TextureWrapMode::Repeat => blade_graphics::AddressMode::Repeat,
+                egui::TextureWrapMode::MirroredRepeat => blade_graphics::AddressMode::MirrorRepeat,
+            };
+            [mode; 3]
+        },
+        mag_filter: egui_texture_filter_to_blade(options.magnification),
+        min_filter: egui_texture_filter_to_blade(options.minification),
+        mipmap_filter: options
+            .mipmap_mode
+            .map(egui_texture_filter_to_blade)
+            .unwrap_or_default(),
+
+        ..Default::default()
+    })
+}
+
 impl GuiTexture {
     fn create(
         context: &blade_graphics::Context,
@@ -93,51 +293,83 @@ impl GuiTexture {
                 subresources: &blade_graphics::TextureSubresources::default(),
             },
         );
-        let sampler = context.create_sampler(blade_graphics::SamplerDesc {
-            name,
-            address_modes: {
-                let mode = match options.wrap_mode {
-                    egui::TextureWrapMode::ClampToEdge => blade_graphics::AddressMode::ClampToEdge,
-                    egui::TextureWrapMode::Repeat => blade_graphics::AddressMode::Repeat,
-                    egui::TextureWrapMode::MirroredRepeat => {
-                        blade_graphics::AddressMode::MirrorRepeat
-                    }
-                };
-                [mode; 3]
-            },
-            mag_filter: egui_texture_filter_to_blade(options.magnification),
-            min_filter: egui_texture_filter_to_blade(options.minification),
-            mipmap_filter: options
-                .mipmap_mode
-                .map(egui_texture_filter_to_blade)
-                .unwrap_or_default(),
-
-            ..Default::default()
-        });
         Self {
-            allocation,
+            allocation: Some(allocation),
             view,
-            sampler,
+            size,
+            options,
+            sampler: create_sampler(context, name, options),
+            encoding: TextureEncoding::Srgb,
         }
     }
 
+    /// Wrap a view owned by the user.
+    fn wrap(
+        view: blade_graphics::TextureView,
+        options: egui::TextureOptions,
+        encoding: TextureEncoding,
+        index: u64,
+        context: &blade_graphics::Context,
+    ) -> Self {
+        let label = format!("egui_user_image_{}", index);
+        Self {
+            allocation: None,
+            view,
+            size: blade_graphics::Extent::default(),
+            options,
+            sampler: create_sampler(context, &label, options),
+            encoding,
+        }
+    }
+
+    /// GPU memory of the texture object, if it's owned by the painter.
+    fn memory(&self) -> u64 {
+        // Every texel is a `Color32`
+        self.size.width as u64 * self.size.height as u64 * size_of::<egui::Color32>() as u64
+    }
+
     fn delete(self, context: &blade_graphics::Context) {
-        context.destroy_texture(self.allocation);
-        context.destroy_texture_view(self.view);
+        if let Some(allocation) = self.allocation {
+            context.destroy_texture(allocation);
+            context.destroy_texture_view(self.view);
+        }
         context.destroy_sampler(self.sampler);
     }
 }
 
-//TODO: scissor test
+fn create_belt() -> BufferBelt {
+    BufferBelt::new(BufferBeltDescriptor {
+        memory: blade_graphics::Memory::Shared,
+        min_chunk_size: 0x1000,
+        alignment: blade_graphics::limits::STORAGE_BUFFER_ALIGNMENT,
+    })
+}
 
 /// GUI painter based on egui.
 ///
 /// It can render egui primitives into a render pass.
+/// The textures are shared between all the viewports,
+/// while each viewport gets its own buffers for the geometry.
 pub struct GuiPainter {
     pipeline: blade_graphics::RenderPipeline,
+    color_mode: UiColorMode,
+    /// The target is sRGB or floating point, expecting linear colors.
+    is_linear_target: bool,
+    /// The target has 8 bits per channel, so the gradients can band.
+    is_8bit_target: bool,
+    /// Apply an ordered dither to break up the gradient banding.
+    dithering: bool,
     //TODO: find a better way to allocate temporary buffers.
+    /// Staging area for the texture updates.
     belt: BufferBelt,
+    /// Geometry of the viewports.
+    viewport_belts: HashMap<egui::ViewportId, BufferBelt>,
+    viewport_belts_dropped: Vec<BufferBelt>,
+    viewport_belts_to_delete: Vec<(BufferBelt, blade_graphics::SyncPoint)>,
     textures: HashMap<egui::TextureId, GuiTexture>,
+    next_user_texture_id: u64,
+    /// Textures freed by egui, which can still be painted in the current frame.
+    textures_to_free: Vec<egui::TextureId>,
     //TODO: this could also look better
     textures_dropped: Vec<GuiTexture>,
     textures_to_delete: Vec<(GuiTexture, blade_graphics::SyncPoint)>,
@@ -148,6 +380,15 @@ impl GuiPainter {
     pub fn destroy(&mut self, context: &blade_graphics::Context) {
         context.destroy_render_pipeline(&mut self.pipeline);
         self.belt.destroy(context);
+        for (_, mut belt) in self.viewport_belts.drain() {
+            belt.destroy(context);
+        }
+        for mut belt in self.viewport_belts_dropped.drain(..) {
+            belt.destroy(context);
+        }
+        for (mut belt, _) in self.viewport_belts_to_delete.drain(..) {
+            belt.destroy(context);
+        }
         for (_, gui_texture) in self.textures.drain() {
             gui_texture.delete(context);
         }
@@ -163,6 +404,8 @@ impl GuiPainter {
     ///
     /// It supports renderpasses with only a color attachment,
     /// and this attachment format must be The `output_format`.
+    /// The colors are blended in `UiColorMode::Gamma` by default.
+    /// sRGB and floating point formats are considered to be linear.
     #[profiling::function]
     pub fn new(info: blade_graphics::SurfaceInfo, context: &blade_graphics::Context) -> Self {
         let shader = context.create_shader(blade_graphics::ShaderDesc {
@@ -197,25 +440,50 @@ impl GuiPainter {
                     },
                 }),
                 write_mask: blade_graphics::ColorWrites::all(),
+                logic_op: None,
             }],
             multisample_state: Default::default(),
-        });
-
-        let belt = BufferBelt::new(BufferBeltDescriptor {
-            memory: blade_graphics::Memory::Shared,
-            min_chunk_size: 0x1000,
-            alignment: blade_graphics::limits::STORAGE_BUFFER_ALIGNMENT,
+            multiview: None,
         });
 
         Self {
             pipeline,
-            belt,
+            color_mode: UiColorMode::default(),
+            is_linear_target: is_linear_format(info.format),
+            is_8bit_target: is_8bit_format(info.format),
+            dithering: false,
+            belt: create_belt(),
+            viewport_belts: HashMap::default(),
+            viewport_belts_dropped: Vec::new(),
+            viewport_belts_to_delete: Vec::new(),
             textures: Default::default(),
+            next_user_texture_id: 0,
+            textures_to_free: Vec::new(),
             textures_dropped: Vec::new(),
             textures_to_delete: Vec::new(),
         }
     }
 
+    pub fn color_mode(&self) -> UiColorMode {
+        self.color_mode
+    }
+
+    /// Change the color space, in which the colors are blended.
+    pub fn set_color_mode(&mut self, color_mode: UiColorMode) {
+        self.color_mode = color_mode;
+    }
+
+    pub fn dithering(&self) -> bool {
+        self.dithering
+    }
+
+    /// Dither the output to reduce the banding of the smooth gradients.
+    /// The dither is too weak to change any flat colors,
+    /// and it's only applied to 8-bit targets.
+    pub fn set_dithering(&mut self, dithering: bool) {
+        self.dithering = dithering;
+    }
+
     #[profiling::function]
     fn triage_deletions(&mut self, context: &blade_graphics::Context) {
         let valid_pos = self
@@ -224,15 +492,106 @@ impl GuiPainter {
             .position(|&(_, ref sp)| !context.wait_for(sp, 0).unwrap_or(true))
             .unwrap_or(self.textures_to_delete.len());
         for (texture, _) in self.textures_to_delete.drain(..valid_pos) {
-            context.destroy_texture_view(texture.view);
-            context.destroy_texture(texture.allocation);
-            context.destroy_sampler(texture.sampler);
+            texture.delete(context);
+        }
+        let valid_pos = self
+            .viewport_belts_to_delete
+            .iter()
+            .position(|&(_, ref sp)| !context.wait_for(sp, 0).unwrap_or(true))
+            .unwrap_or(self.viewport_belts_to_delete.len());
+        for (mut belt, _) in self.viewport_belts_to_delete.drain(..valid_pos) {
+            belt.destroy(context);
+        }
+        for belt in self.viewport_belts.values_mut() {
+            belt.trim(4, context);
+        }
+    }
+
+    /// GPU memory of the textures created for egui, including the freed ones
+    /// that are waiting for the frames in flight. User textures aren't counted.
+    pub fn texture_memory(&self) -> u64 {
+        self.textures.values().map(GuiTexture::memory).sum::<u64>()
+            + self
+                .textures_dropped
+                .iter()
+                .map(GuiTexture::memory)
+                .sum::<u64>()
+            + self
+                .textures_to_delete
+                .iter()
+                .map(|&(ref texture, _)| texture.memory())
+                .sum::<u64>()
+    }
+
+    /// Release the resources of a viewport that is closed.
+    /// They are destroyed once the work submitted for it is done.
+    pub fn remove_viewport(&mut self, viewport_id: egui::ViewportId) {
+        if let Some(belt) = self.viewport_belts.remove(&viewport_id) {
+            self.viewport_belts_dropped.push(belt);
         }
     }
 
+    /// Register a texture view owned by the user, to be shown by `egui::Image`.
+    ///
+    /// The view has to stay alive for as long as it's registered,
+    /// and the painter never destroys it.
+    pub fn register_texture(
+        &mut self,
+        view: blade_graphics::TextureView,
+        options: egui::TextureOptions,
+        encoding: TextureEncoding,
+        context: &blade_graphics::Context,
+    ) -> egui::TextureId {
+        let index = self.next_user_texture_id;
+        self.next_user_texture_id += 1;
+        let texture = GuiTexture::wrap(view, options, encoding, index, context);
+        let id = egui::TextureId::User(index);
+        self.textures.insert(id, texture);
+        id
+    }
+
+    /// Replace the view behind a registered user texture,
+    /// for example after the texture got resized.
+    pub fn update_texture(
+        &mut self,
+        id: egui::TextureId,
+        view: blade_graphics::TextureView,
+        options: egui::TextureOptions,
+        encoding: TextureEncoding,
+        context: &blade_graphics::Context,
+    ) -> Result<(), TextureError> {
+        let index = match id {
+            egui::TextureId::Managed(_) => return Err(TextureError::Managed(id)),
+            egui::TextureId::User(index) => index,
+        };
+        let Some(texture) = self.textures.get_mut(&id) else {
+            return Err(TextureError::Unknown(id));
+        };
+        let old = std::mem::replace(
+            texture,
+            GuiTexture::wrap(view, options, encoding, index, context),
+        );
+        self.textures_dropped.push(old);
+        Ok(())
+    }
+
+    /// Unregister a user texture.
+    /// The view can be destroyed after the current submission is done.
+    pub fn unregister_texture(&mut self, id: egui::TextureId) -> Result<(), TextureError> {
+        if let egui::TextureId::Managed(_) = id {
+            return Err(TextureError::Managed(id));
+        }
+        let texture = self.textures.remove(&id).ok_or(TextureError::Unknown(id))?;
+        self.textures_dropped.push(texture);
+        Ok(())
+    }
+
     /// Updates the texture used by egui for the fonts etc.
     /// New textures should be added before the call to `execute()`,
     /// and old textures should be removed after.
+    ///
+    /// The freed textures stay available for painting the current frame,
+    /// and are only removed by `after_submit`.
     #[profiling::function]
     pub fn update_textures(
         &mut self,
@@ -240,6 +599,7 @@ impl GuiPainter {
         textures_delta: &egui::TexturesDelta,
         context: &blade_graphics::Context,
     ) {
+        self.triage_deletions(context);
         if textures_delta.set.is_empty() && textures_delta.free.is_empty() {
             return;
         }
@@ -262,26 +622,46 @@ impl GuiPainter {
                 egui::TextureId::User(u) => format!("egui_user_image_{}", u),
             };
 
-            let texture = match self.textures.entry(texture_id) {
-                Entry::Occupied(mut o) => {
-                    if image_delta.pos.is_none() {
-                        let texture =
-                            GuiTexture::create(context, &label, extent, image_delta.options);
-                        command_encoder.init_texture(texture.allocation);
-                        let old = o.insert(texture);
-                        self.textures_dropped.push(old);
-                    }
+            let texture = match (self.textures.entry(texture_id), image_delta.pos) {
+                (Entry::Occupied(o), Some([x, y])) => {
+                    let texture = o.into_mut();
+                    assert!(
+                        x + image_size[0] <= texture.size.width as usize
+                            && y + image_size[1] <= texture.size.height as usize,
+                        "Update of {texture_id:?} at {:?} with size {image_size:?} is outside of {:?}",
+                        image_delta.pos,
+                        texture.size,
+                    );
+                    texture
+                }
+                (Entry::Vacant(_), Some(_)) => {
+                    panic!("Partial update of an unknown texture {texture_id:?}")
+                }
+                // The full image is reallocated only if it changes the size, e.g. when
+                // the font atlas grows. The old texture may still be used by the frames in flight.
+                (Entry::Occupied(o), None)
+                    if o.get().size == extent && o.get().options == image_delta.options =>
+                {
+                    o.into_mut()
+                }
+                (Entry::Occupied(mut o), None) => {
+                    let texture = GuiTexture::create(context, &label, extent, image_delta.options);
+                    command_encoder.init_texture(texture.allocation.unwrap());
+                    let old = o.insert(texture);
+                    self.textures_dropped.push(old);
                     o.into_mut()
                 }
-                Entry::Vacant(v) => {
+                (Entry::Vacant(v), None) => {
                     let texture = GuiTexture::create(context, &label, extent, image_delta.options);
-                    command_encoder.init_texture(texture.allocation);
+                    command_encoder.init_texture(texture.allocation.unwrap());
                     v.insert(texture)
                 }
             };
 
             let dst = blade_graphics::TexturePiece {
-                texture: texture.allocation,
+                texture: texture
+                    .allocation
+                    .expect("User textures can't be updated by egui"),
                 mip_level: 0,
                 array_layer: 0,
                 origin: match image_delta.pos {
@@ -294,22 +674,38 @@ impl GuiPainter {
 
         if let mut transfer = command_encoder.transfer("update egui textures") {
             for (src, dst, extent) in copies {
-                transfer.copy_buffer_to_texture(src, 4 * extent.width, dst, extent);
+                // The source is tightly packed, with one `Color32` per texel
+                let bytes_per_row = extent.width * size_of::<egui::Color32>() as u32;
+                transfer.copy_buffer_to_texture(src, bytes_per_row, dst, extent);
             }
         }
 
-        for texture_id in textures_delta.free.iter() {
-            let texture = self.textures.remove(texture_id).unwrap();
-            self.textures_dropped.push(texture);
-        }
-
-        self.triage_deletions(context);
+        self.textures_to_free
+            .extend_from_slice(&textures_delta.free);
         self.belt.trim(4, context);
     }
 
-    /// Render the set of clipped primitives into a render pass.
-    /// The `sd` must contain dimensions of the render target.
+    /// Prepare the resources of the paint callbacks among the primitives.
+    /// Has to be called before the render pass, in which they are painted, starts.
     #[profiling::function]
+    pub fn prepare_callbacks(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        paint_jobs: &[egui::epaint::ClippedPrimitive],
+        sd: &ScreenDescriptor,
+        context: &blade_graphics::Context,
+    ) {
+        for clipped_prim in paint_jobs {
+            if let egui::epaint::Primitive::Callback(ref paint_callback) = clipped_prim.primitive
+                && let Some(callback) = paint_callback.callback.downcast_ref::<Callback>()
+            {
+                callback.0.prepare(command_encoder, sd, context);
+            }
+        }
+    }
+
+    /// Render the set of clipped primitives of the root viewport into a render pass.
+    /// The `sd` must contain dimensions of the render target.
     pub fn paint(
         &mut self,
         pass: &mut blade_graphics::RenderCommandEncoder,
@@ -317,80 +713,150 @@ impl GuiPainter {
         sd: &ScreenDescriptor,
         context: &blade_graphics::Context,
     ) {
+        self.paint_viewport(pass, paint_jobs, egui::ViewportId::ROOT, sd, context);
+    }
+
+    /// Render the set of clipped primitives of a viewport into a render pass.
+    /// The `sd` must contain dimensions of the render target of the viewport,
+    /// which has to be in the format the painter was created with.
+    /// Meshes with unknown textures are skipped.
+    #[profiling::function]
+    pub fn paint_viewport(
+        &mut self,
+        pass: &mut blade_graphics::RenderCommandEncoder,
+        paint_jobs: &[egui::epaint::ClippedPrimitive],
+        viewport_id: egui::ViewportId,
+        sd: &ScreenDescriptor,
+        context: &blade_graphics::Context,
+    ) {
+        let belt = self
+            .viewport_belts
+            .entry(viewport_id)
+            .or_insert_with(create_belt);
         let logical_size = sd.logical_size();
-        let mut pc = pass.with(&self.pipeline);
-        pc.bind(
-            0,
-            &Globals {
-                r_uniforms: Uniforms {
-                    screen_size: [logical_size.0, logical_size.1],
-                    padding: [0.0; 2],
-                },
+        let globals = Globals {
+            r_uniforms: Uniforms {
+                screen_size: [logical_size.0, logical_size.1],
+                color_mode: self.color_mode as u32,
+                is_linear_target: self.is_linear_target as u32,
+                dithering: (self.dithering && self.is_8bit_target) as u32,
+                pad: 0,
             },
-        );
+        };
+
+        let mut primitives = paint_jobs.iter().peekable();
+        while primitives.peek().is_some() {
+            // The pipeline has to be bound again after every callback
+            if let mut pc = pass.with(&self.pipeline) {
+                pc.bind(0, &globals);
+                while let Some(clipped_prim) =
+                    primitives.next_if(|p| matches!(p.primitive, egui::epaint::Primitive::Mesh(_)))
+                {
+                    let egui::epaint::Primitive::Mesh(ref mesh) = clipped_prim.primitive else {
+                        unreachable!()
+                    };
+                    let Some(scissor) = sd.clip_to_scissor(&clipped_prim.clip_rect) else {
+                        continue;
+                    };
+                    pc.set_scissor_rect(&scissor);
+
+                    let Some(texture) = self.textures.get(&mesh.texture_id) else {
+                        log::warn!("Skipping a mesh with unknown texture {:?}", mesh.texture_id);
+                        continue;
+                    };
+                    let index_buf = belt.alloc_pod(&mesh.indices, context);
+                    let vertex_buf = belt.alloc_pod(&mesh.vertices, context);
+
+                    pc.bind(
+                        1,
+                        &Locals {
+                            r_vertex_data: vertex_buf,
+                            r_texture: texture.view,
+                            r_sampler: texture.sampler,
+                            r_texture_params: TextureParams {
+                                encoding: texture.encoding as u32,
+                            },
+                        },
+                    );
+
+                    pc.draw_indexed(
+                        index_buf,
+                        blade_graphics::IndexType::U32,
+                        mesh.indices.len() as u32,
+                        0,
+                        0,
+                        1,
+                    );
+                }
+            }
 
-        for clipped_prim in paint_jobs {
-            let clip_rect = &clipped_prim.clip_rect;
-
-            // Make sure clip rect can fit within an `u32`.
-            let clip_min_x = (sd.scale_factor * clip_rect.min.x)
-                .clamp(0.0, sd.physical_size.0 as f32)
-                .trunc() as i32;
-            let clip_min_y = (sd.scale_factor * clip_rect.min.y)
-                .clamp(0.0, sd.physical_size.1 as f32)
-                .trunc() as i32;
-            let clip_max_x = (sd.scale_factor * clip_rect.max.x)
-                .clamp(0.0, sd.physical_size.0 as f32)
-                .ceil() as i32;
-            let clip_max_y = (sd.scale_factor * clip_rect.max.y)
-                .clamp(0.0, sd.physical_size.1 as f32)
-                .ceil() as i32;
-
-            if clip_max_x <= clip_min_x || clip_max_y == clip_min_y {
+            let Some(clipped_prim) = primitives.next() else {
+                break;
+            };
+            let egui::epaint::Primitive::Callback(ref paint_callback) = clipped_prim.primitive
+            else {
+                unreachable!()
+            };
+            let Some(callback) = paint_callback.callback.downcast_ref::<Callback>() else {
+                continue;
+            };
+            let info = egui::epaint::PaintCallbackInfo {
+                viewport: paint_callback.rect,
+                clip_rect: clipped_prim.clip_rect,
+                pixels_per_point: sd.scale_factor,
+                screen_size_px: [sd.physical_size.0, sd.physical_size.1],
+            };
+            let viewport = info.viewport_in_pixels();
+            let Some(scissor) = sd.clip_to_scissor(&info.clip_rect.intersect(info.viewport)) else {
+                continue;
+            };
+            if viewport.width_px <= 0 || viewport.height_px <= 0 {
                 continue;
             }
-
-            pc.set_scissor_rect(&blade_graphics::ScissorRect {
-                x: clip_min_x,
-                y: clip_min_y,
-                w: (clip_max_x - clip_min_x) as u32,
-                h: (clip_max_y - clip_min_y) as u32,
+            pass.set_viewport(&blade_graphics::Viewport {
+                x: viewport.left_px as f32,
+                y: viewport.top_px as f32,
+                w: viewport.width_px as f32,
+                h: viewport.height_px as f32,
+                depth: 0.0..1.0,
+            });
+            pass.set_scissor_rect(&scissor);
+            callback.0.paint(info, pass, sd);
+            pass.set_viewport(&blade_graphics::Viewport {
+                x: 0.0,
+                y: 0.0,
+                w: sd.physical_size.0 as f32,
+                h: sd.physical_size.1 as f32,
+                depth: 0.0..1.0,
             });
-
-            if let egui::epaint::Primitive::Mesh(ref mesh) = clipped_prim.primitive {
-                let texture = self.textures.get(&mesh.texture_id).unwrap();
-                let index_buf = self.belt.alloc_pod(&mesh.indices, context);
-                let vertex_buf = self.belt.alloc_pod(&mesh.vertices, context);
-
-                pc.bind(
-                    1,
-                    &Locals {
-                        r_vertex_data: vertex_buf,
-                        r_texture: texture.view,
-                        r_sampler: texture.sampler,
-                    },
-                );
-
-                pc.draw_indexed(
-                    index_buf,
-                    blade_graphics::IndexType::U32,
-                    mesh.indices.len() as u32,
-                    0,
-                    0,
-                    1,
-                );
-            }
         }
     }
 
     /// Call this after submitting work at the given `sync_point`.
+    ///
+    /// With multiple viewports, it can be called either once after all of them
+    /// are submitted, or after each submission. The textures freed by egui
+    /// are only removed here, so they have to be painted before.
     #[profiling::function]
     pub fn after_submit(&mut self, sync_point: &blade_graphics::SyncPoint) {
+        for texture_id in self.textures_to_free.drain(..) {
+            if let Some(texture) = self.textures.remove(&texture_id) {
+                self.textures_dropped.push(texture);
+            }
+        }
         self.textures_to_delete.extend(
             self.textures_dropped
                 .drain(..)
                 .map(|texture| (texture, sync_point.clone())),
         );
         self.belt.flush(sync_point);
+        for belt in self.viewport_belts.values_mut() {
+            belt.flush(sync_point);
+        }
+        self.viewport_belts_to_delete
+            .extend(self.viewport_belts_dropped.drain(..).map(|mut belt| {
+                belt.flush(sync_point);
+                (belt, sync_point.clone())
+            }));
     }
 }
@@ -111,13 +111,15 @@ fn default_time_step() -> f32 {
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
 pub enum RenderBackend {
+    /// Ray tracing if the GPU supports ray queries, rasterization otherwise.
     #[default]
+    Auto,
     RayTracer,
     Rasterizer,
 }
 
 fn default_render_backend() -> RenderBackend {
-    RenderBackend::RayTracer
+    RenderBackend::Auto
 }
 
 fn default_gui_enabled() -> bool {
@@ -140,4 +142,8 @@ pub struct Engine {
     pub render_backend: RenderBackend,
     #[serde(default = "default_gui_enabled")]
     pub gui_enabled: bool,
+    /// GPU memory for streaming the texture mips, in megabytes.
+    /// All the mips are loaded upfront when it's zero.
+    #[serde(default)]
+    pub texture_budget_mb: u32,
 }
@@ -15,7 +15,7 @@
 )]
 
 use blade_graphics as gpu;
-use std::{ops, path::PathBuf, sync::Arc};
+use std::{ops, path::PathBuf, sync::Arc, time};
 
 pub mod config;
 mod trimesh;
@@ -193,6 +193,8 @@ pub struct ContactEvent {
 }
 
 const MAX_DEPTH: f32 = 1e9;
+/// Time for the asset sources to settle before they are reloaded.
+const ASSET_RELOAD_DEBOUNCE: time::Duration = time::Duration::from_millis(300);
 
 #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Hash)]
 pub struct ObjectHandle(usize);
@@ -353,12 +355,17 @@ struct Object {
     colliders: Vec<rapier3d::geometry::ColliderHandle>,
     visuals: Vec<Visual>,
     color_tint: [f32; 4],
+    layers: u32,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct FrameCamera {
     pub transform: Transform,
     pub fov_y: f32,
+    /// Lens for the depth of field of the ray tracer.
+    pub lens: blade_render::Lens,
+    /// Projection onto the screen.
+    pub projection: blade_render::Projection,
 }
 
 impl From<blade_render::Camera> for FrameCamera {
@@ -369,17 +376,26 @@ impl From<blade_render::Camera> for FrameCamera {
                 orientation: cam.rot,
             },
             fov_y: cam.fov_y,
+            lens: cam.lens,
+            projection: cam.projection,
         }
     }
 }
 
+/// Procedural sky, which can be used as the environment instead of a map.
+struct Sky {
+    model: blade_render::SkyModel,
+    texture: blade_asset::Handle<blade_render::Texture>,
+    /// The texture and the sun light need an update.
+    is_dirty: bool,
+}
+
 #[allow(clippy::large_enum_variant)]
 enum Renderer {
     RayTracer {
         inner: blade_render::RayTracer,
         frame_config: blade_render::FrameConfig,
         ray_config: blade_render::RayConfig,
-        denoiser_enabled: bool,
         denoiser_config: blade_render::DenoiserConfig,
         post_proc_config: blade_render::PostProcConfig,
     },
@@ -419,6 +435,7 @@ pub struct Engine {
     target_surface: TargetSurface,
     gpu_context: Arc<gpu::Context>,
     environment_map: Option<blade_asset::Handle<blade_render::Texture>>,
+    sky: Option<Sky>,
     objects: slab::Slab<Object>,
     selected_object_handle: Option<ObjectHandle>,
     selected_collider: Option<rapier3d::geometry::ColliderHandle>,
@@ -501,12 +518,53 @@ impl Engine {
                     },
                     model: visual.model,
                     color_tint: object.color_tint,
+                    joints: Vec::new(),
+                    layers: object.layers,
+                    material_overrides: blade_render::MaterialOverrides::default(),
                 });
             }
             object.prev_isometry = isometry;
         }
     }
 
+    /// Regenerate the sky if needed, and light the scene by its sun while it's active.
+    fn update_sky(
+        sky: &mut Option<Sky>,
+        environment_map: Option<blade_asset::Handle<blade_render::Texture>>,
+        asset_hub: &blade_render::AssetHub,
+        renderer: &mut Renderer,
+        command_encoder: &mut gpu::CommandEncoder,
+        gpu_context: &gpu::Context,
+        temp: &mut blade_render::FrameResources,
+    ) {
+        let sky = match *sky {
+            Some(ref mut sky) if sky.is_dirty => sky,
+            _ => return,
+        };
+        sky.is_dirty = false;
+        let sun = if environment_map == Some(sky.texture) {
+            asset_hub.update_sky(sky.texture, &sky.model, temp);
+            Some(sky.model.sun_light())
+        } else {
+            None
+        };
+        match *renderer {
+            Renderer::RayTracer { ref mut inner, .. } => {
+                inner.set_lights(command_encoder, sun.as_slice(), gpu_context, temp);
+            }
+            Renderer::Rasterizer {
+                ref mut raster_config,
+                ..
+            } => {
+                if let Some(sun) = sun {
+                    raster_config.light_dir = sun.direction;
+                    raster_config.light_color =
+                        (glam::Vec3::from(sun.color) * sun.intensity).into();
+                }
+            }
+        }
+    }
+
     fn flush_assets_and_check_ready(
         asset_hub: &blade_render::AssetHub,
         load_tasks: &mut Vec<choir::RunningTask>,
@@ -537,12 +595,26 @@ impl Engine {
         let context_desc = gpu::ContextDesc {
             presentation: xr.is_none(),
             xr,
-            ray_tracing: matches!(config.render_backend, config::RenderBackend::RayTracer),
+            ray_tracing: config.render_backend != config::RenderBackend::Rasterizer,
             validation: cfg!(debug_assertions),
             timing: true,
             ..Default::default()
         };
         let gpu_context = Arc::new(unsafe { gpu::Context::init(context_desc).unwrap() });
+        let use_ray_tracing = match config.render_backend {
+            config::RenderBackend::Auto => {
+                let supported = gpu_context
+                    .capabilities()
+                    .ray_query
+                    .contains(gpu::ShaderVisibility::COMPUTE);
+                if !supported {
+                    log::info!("Ray queries are not supported, falling back to rasterization");
+                }
+                supported
+            }
+            config::RenderBackend::RayTracer => true,
+            config::RenderBackend::Rasterizer => false,
+        };
 
         let (surface_size, surface_info, target_surface) = match presentation {
             #[cfg(not(target_os = "android"))]
@@ -589,11 +661,14 @@ impl Engine {
 
         let asset_cache_path = Self::asset_cache_path(config);
         let asset_hub = blade_render::AssetHub::new(&asset_cache_path, &choir, &gpu_context);
-        let (shaders, shader_task) = blade_render::Shaders::load(
-            config.shader_path.as_ref(),
-            &asset_hub,
-            matches!(config.render_backend, config::RenderBackend::RayTracer),
-        );
+        if config.texture_budget_mb != 0 {
+            asset_hub.set_texture_streaming(Some(blade_render::texture::StreamingConfig {
+                budget: (config.texture_budget_mb as u64) << 20,
+                ..Default::default()
+            }));
+        }
+        let (shaders, shader_task) =
+            blade_render::Shaders::load(config.shader_path.as_ref(), &asset_hub, use_ray_tracing);
 
         log::info!("Spinning up the renderer");
         shader_task.join();
@@ -605,8 +680,8 @@ impl Engine {
             surface_info,
             max_debug_lines: 1 << 14,
         };
-        let renderer = match config.render_backend {
-            config::RenderBackend::RayTracer => Renderer::RayTracer {
+        let renderer = if use_ray_tracing {
+            Renderer::RayTracer {
                 inner: blade_render::RayTracer::new(
                     command_encoder,
                     &gpu_context,
@@ -619,20 +694,22 @@ impl Engine {
                     debug_draw: true,
                     reset_variance: false,
                     reset_reservoirs: true,
+                    accumulate: false,
+                    visible_layers: blade_render::ALL_LAYERS,
+                    culling: blade_render::CullingConfig::default(),
                 },
                 ray_config: blade_helpers::default_ray_config(),
-                denoiser_enabled: true,
                 denoiser_config: blade_render::DenoiserConfig {
-                    num_passes: 4,
-                    temporal_weight: 0.1,
+                    spatial_passes: 4,
+                    ..Default::default()
                 },
                 post_proc_config: blade_render::PostProcConfig {
-                    average_luminocity: 0.5,
-                    exposure_key_value: 1.0 / 9.6,
-                    white_level: 1.0,
+                    exposure_ev: -2.25,
+                    ..Default::default()
                 },
-            },
-            config::RenderBackend::Rasterizer => Renderer::Rasterizer {
+            }
+        } else {
+            Renderer::Rasterizer {
                 inner: blade_render::Rasterizer::new(
                     command_encoder,
                     &gpu_context,
@@ -641,13 +718,15 @@ impl Engine {
                     &render_config,
                 ),
                 raster_config: blade_render::RasterConfig::default(),
-            },
+            }
         };
 
         pacer.end_frame(&gpu_context);
 
         let gui_painter = if config.gui_enabled {
-            Some(blade_egui::GuiPainter::new(surface_info, &gpu_context))
+            let mut gui_painter = blade_egui::GuiPainter::new(surface_info, &gpu_context);
+            gui_painter.set_dithering(true);
+            Some(gui_painter)
         } else {
             None
         };
@@ -665,6 +744,7 @@ impl Engine {
             target_surface,
             gpu_context,
             environment_map: None,
+            sky: None,
             objects: slab::Slab::new(),
             selected_object_handle: None,
             selected_collider: None,
@@ -853,7 +933,41 @@ impl Engine {
         }
         if let Some(ref mut painter) = self.gui_painter {
             painter.update_textures(command_encoder, gui_textures, &self.gpu_context);
+            let screen_desc = blade_egui::ScreenDescriptor {
+                physical_size: (physical_size.width, physical_size.height),
+                scale_factor,
+            };
+            painter.prepare_callbacks(
+                command_encoder,
+                gui_primitives,
+                &screen_desc,
+                &self.gpu_context,
+            );
+        }
+        let reloaded = self.asset_hub.take_reloaded(temp);
+        if !reloaded.is_empty() {
+            log::info!(
+                "Reloaded {} textures and {} models",
+                reloaded.textures.len(),
+                reloaded.models.len()
+            );
+            if let Renderer::RayTracer {
+                ref mut frame_config,
+                ..
+            } = self.renderer
+            {
+                frame_config.reset_reservoirs = true;
+            }
         }
+        Self::update_sky(
+            &mut self.sky,
+            self.environment_map,
+            &self.asset_hub,
+            &mut self.renderer,
+            command_encoder,
+            &self.gpu_context,
+            temp,
+        );
         let can_render = Self::flush_assets_and_check_ready(
             &self.asset_hub,
             &mut self.load_tasks,
@@ -870,42 +984,72 @@ impl Engine {
                 &mut self.render_objects,
                 self.time_ahead,
             );
-            if let Renderer::RayTracer {
-                ref mut inner,
-                ref mut frame_config,
-                ref mut ray_config,
-                ref mut denoiser_enabled,
-                ref mut denoiser_config,
-                ..
-            } = self.renderer
-            {
-                inner.build_scene(
-                    command_encoder,
-                    &self.render_objects,
-                    self.environment_map,
-                    &self.asset_hub,
-                    &self.gpu_context,
-                    temp,
-                );
-                inner.prepare(
-                    command_encoder,
-                    &blade_render::Camera {
-                        pos: camera.transform.position,
-                        rot: camera.transform.orientation,
-                        fov_y: camera.fov_y,
-                        depth: MAX_DEPTH,
-                        fov: None,
-                    },
-                    *frame_config,
-                );
-                frame_config.reset_reservoirs = false;
+            let render_camera = blade_render::Camera {
+                pos: camera.transform.position,
+                rot: camera.transform.orientation,
+                fov_y: camera.fov_y,
+                depth: MAX_DEPTH,
+                fov: None,
+                lens: camera.lens,
+                projection: camera.projection,
+            };
+            self.asset_hub.request_texture_mips(
+                &render_camera,
+                &self.render_objects,
+                new_render_size.height,
+            );
+            self.asset_hub.stream_textures(command_encoder, temp);
+            match self.renderer {
+                Renderer::RayTracer {
+                    ref mut inner,
+                    ref mut frame_config,
+                    ref mut ray_config,
+                    ref mut denoiser_config,
+                    ref post_proc_config,
+                    ..
+                } => {
+                    inner.build_scene(
+                        command_encoder,
+                        &self.render_objects,
+                        self.environment_map,
+                        &self.asset_hub,
+                        &self.gpu_context,
+                        temp,
+                    );
+                    inner.prepare(command_encoder, &render_camera, *frame_config);
+                    frame_config.reset_reservoirs = false;
 
-                if !self.render_objects.is_empty() {
-                    inner.ray_trace(command_encoder, self.debug, *ray_config);
-                    if *denoiser_enabled {
+                    if !self.render_objects.is_empty() {
+                        inner.ray_trace(command_encoder, self.debug, *ray_config);
                         inner.denoise(command_encoder, *denoiser_config);
+                        inner.render_bloom(command_encoder, post_proc_config.bloom);
                     }
                 }
+                Renderer::Rasterizer {
+                    ref mut inner,
+                    raster_config,
+                } => {
+                    inner.prepare(
+                        command_encoder,
+                        &blade_render::Camera {
+                            lens: blade_render::Lens::default(),
+                            ..render_camera
+                        },
+                        &self.render_objects,
+                        &self.asset_hub,
+                        self.environment_map,
+                        raster_config,
+                        &self.gpu_context,
+                    );
+                }
+            }
+        }
+        match self.renderer {
+            Renderer::RayTracer { ref mut inner, .. } => {
+                inner.flush_debug_draw(&self.gpu_context, temp);
+            }
+            Renderer::Rasterizer { ref mut inner, .. } => {
+                inner.flush_debug_draw(&self.gpu_context, temp);
             }
         }
 
@@ -979,6 +1123,8 @@ impl Engine {
                             finish_op: gpu::FinishOp::Store,
                         }],
                         depth_stencil: None,
+                        depth_stencil_read_only: gpu::TexelAspects::empty(),
+                        multiview: None,
                     },
                 ) {
                     let screen_desc = blade_egui::ScreenDescriptor {
@@ -1009,6 +1155,8 @@ impl Engine {
                     fov_y: camera.fov_y,
                     depth: MAX_DEPTH,
                     fov: None,
+                    lens: blade_render::Lens::default(),
+                    projection: camera.projection,
                 };
                 command_encoder.init_texture(inner.depth_texture());
                 if let mut pass = command_encoder.render(
@@ -1024,6 +1172,8 @@ impl Engine {
                             init_op: gpu::InitOp::Clear(gpu::TextureColor::White),
                             finish_op: gpu::FinishOp::Store,
                         }),
+                        depth_stencil_read_only: gpu::TexelAspects::empty(),
+                        multiview: None,
                     },
                 ) && can_render
                 {
@@ -1048,6 +1198,8 @@ impl Engine {
                                 finish_op: gpu::FinishOp::Store,
                             }],
                             depth_stencil: None,
+                            depth_stencil_read_only: gpu::TexelAspects::empty(),
+                            multiview: None,
                         },
                     )
                 {
@@ -1109,12 +1261,47 @@ impl Engine {
             }
         }
 
+        Self::update_sky(
+            &mut self.sky,
+            self.environment_map,
+            &self.asset_hub,
+            &mut self.renderer,
+            command_encoder,
+            &self.gpu_context,
+            temp,
+        );
         let can_render = Self::flush_assets_and_check_ready(
             &self.asset_hub,
             &mut self.load_tasks,
             command_encoder,
             temp,
         );
+        let render_cameras = (0..view_count)
+            .map(|eye| {
+                let xr_view = frame.xr_view(eye as u32);
+                blade_render::Camera {
+                    pos: xr_view.pose.position.into(),
+                    rot: mint::Quaternion {
+                        s: xr_view.pose.orientation[3],
+                        v: mint::Vector3 {
+                            x: xr_view.pose.orientation[0],
+                            y: xr_view.pose.orientation[1],
+                            z: xr_view.pose.orientation[2],
+                        },
+                    },
+                    fov_y: xr_view.fov.angle_up - xr_view.fov.angle_down,
+                    depth: MAX_DEPTH,
+                    fov: Some(blade_render::Fov {
+                        left: (-xr_view.fov.angle_left).max(0.0),
+                        right: xr_view.fov.angle_right.max(0.0),
+                        up: xr_view.fov.angle_up.max(0.0),
+                        down: (-xr_view.fov.angle_down).max(0.0),
+                    }),
+                    lens: blade_render::Lens::default(),
+                    projection: blade_render::Projection::default(),
+                }
+            })
+            .collect::<Vec<_>>();
 
         if can_render {
             Self::update_render_objects(
@@ -1123,6 +1310,14 @@ impl Engine {
                 &mut self.render_objects,
                 self.time_ahead,
             );
+            for render_camera in render_cameras.iter() {
+                self.asset_hub.request_texture_mips(
+                    render_camera,
+                    &self.render_objects,
+                    target_size.height,
+                );
+            }
+            self.asset_hub.stream_textures(command_encoder, temp);
         }
 
         // Update particle systems (compute passes)
@@ -1139,7 +1334,6 @@ impl Engine {
                 ref mut inner,
                 ray_config,
                 ref mut frame_config,
-                denoiser_enabled,
                 denoiser_config,
                 post_proc_config,
             } => {
@@ -1155,34 +1349,14 @@ impl Engine {
                 }
                 // Current ray path is monoscopic in engine terms. For XR we render
                 // each eye independently by re-preparing camera state per-eye.
-                for eye in 0..view_count {
-                    let xr_view = frame.xr_view(eye as u32);
-                    let render_camera = blade_render::Camera {
-                        pos: xr_view.pose.position.into(),
-                        rot: mint::Quaternion {
-                            s: xr_view.pose.orientation[3],
-                            v: mint::Vector3 {
-                                x: xr_view.pose.orientation[0],
-                                y: xr_view.pose.orientation[1],
-                                z: xr_view.pose.orientation[2],
-                            },
-                        },
-                        fov_y: xr_view.fov.angle_up - xr_view.fov.angle_down,
-                        depth: MAX_DEPTH,
-                        fov: Some(blade_render::Fov {
-                            left: (-xr_view.fov.angle_left).max(0.0),
-                            right: xr_view.fov.angle_right.max(0.0),
-                            up: xr_view.fov.angle_up.max(0.0),
-                            down: (-xr_view.fov.angle_down).max(0.0),
-                        }),
-                    };
-                    inner.prepare(command_encoder, &render_camera, *frame_config);
+                // The debug lines are not drawn over the ray-traced views
+                inner.debug_draw().clear();
+                for (eye, render_camera) in render_cameras.iter().enumerate() {
+                    inner.prepare(command_encoder, render_camera, *frame_config);
                     frame_config.reset_reservoirs = false;
                     if !self.render_objects.is_empty() {
                         inner.ray_trace(command_encoder, self.debug, ray_config);
-                        if denoiser_enabled {
-                            inner.denoise(command_encoder, denoiser_config);
-                        }
+                        inner.denoise(command_encoder, denoiser_config);
                     }
                     if let mut pass = command_encoder.render(
                         "xr-draw",
@@ -1193,6 +1367,8 @@ impl Engine {
                                 finish_op: gpu::FinishOp::Store,
                             }],
                             depth_stencil: None,
+                            depth_stencil_read_only: gpu::TexelAspects::empty(),
+                            multiview: None,
                         },
                     ) {
                         if can_render {
@@ -1205,28 +1381,21 @@ impl Engine {
                 ref mut inner,
                 raster_config,
             } => {
+                // The eyes are close enough to share the shadow cascades
+                if can_render && let Some(shadow_camera) = render_cameras.first() {
+                    inner.prepare(
+                        command_encoder,
+                        shadow_camera,
+                        &self.render_objects,
+                        &self.asset_hub,
+                        self.environment_map,
+                        raster_config,
+                        &self.gpu_context,
+                    );
+                }
+                inner.flush_debug_draw(&self.gpu_context, temp);
                 command_encoder.init_texture(inner.depth_texture());
-                for eye in 0..view_count {
-                    let xr_view = frame.xr_view(eye as u32);
-                    let render_camera = blade_render::Camera {
-                        pos: xr_view.pose.position.into(),
-                        rot: mint::Quaternion {
-                            s: xr_view.pose.orientation[3],
-                            v: mint::Vector3 {
-                                x: xr_view.pose.orientation[0],
-                                y: xr_view.pose.orientation[1],
-                                z: xr_view.pose.orientation[2],
-                            },
-                        },
-                        fov_y: xr_view.fov.angle_up - xr_view.fov.angle_down,
-                        depth: MAX_DEPTH,
-                        fov: Some(blade_render::Fov {
-                            left: (-xr_view.fov.angle_left).max(0.0),
-                            right: xr_view.fov.angle_right.max(0.0),
-                            up: xr_view.fov.angle_up.max(0.0),
-                            down: (-xr_view.fov.angle_down).max(0.0),
-                        }),
-                    };
+                for (eye, render_camera) in render_cameras.iter().enumerate() {
                     if let mut pass = command_encoder.render(
                         "xr-raster",
                         gpu::RenderTargetSet {
@@ -1240,27 +1409,25 @@ impl Engine {
                                 init_op: gpu::InitOp::Clear(gpu::TextureColor::White),
                                 finish_op: gpu::FinishOp::Store,
                             }),
+                            depth_stencil_read_only: gpu::TexelAspects::empty(),
+                            multiview: None,
                         },
                     ) {
                         if can_render {
                             inner.render(
                                 &mut pass,
-                                &render_camera,
+                                render_camera,
                                 &self.render_objects,
                                 &self.asset_hub,
                                 self.environment_map,
                                 raster_config,
                             );
                         }
-                        inner.render_debug_lines(
-                            &mut pass,
-                            &render_camera,
-                            &self.extra_debug_lines,
-                        );
+                        inner.render_debug_lines(&mut pass, render_camera, &self.extra_debug_lines);
                         // Draw particle systems
                         if let Some(ref pipeline) = self.particle_pipeline {
                             let particle_camera =
-                                Self::make_particle_camera(&render_camera, target_size);
+                                Self::make_particle_camera(render_camera, target_size);
                             for (_, system) in self.particle_systems.iter() {
                                 system.draw(pipeline, &mut pass, &particle_camera);
                             }
@@ -1322,6 +1489,16 @@ impl Engine {
         self.gpu_context.xr_locate_space(action_space)
     }
 
+    /// Return the batch of debug shapes to be rendered this frame.
+    ///
+    /// The shapes are cleared after rendering.
+    pub fn debug_draw(&mut self) -> &mut blade_render::DebugDraw {
+        match self.renderer {
+            Renderer::RayTracer { ref mut inner, .. } => inner.debug_draw(),
+            Renderer::Rasterizer { ref mut inner, .. } => inner.debug_draw(),
+        }
+    }
+
     /// Add debug lines to be rendered this frame (consumed after rendering).
     pub fn add_debug_lines(&mut self, lines: &[blade_render::DebugLine]) {
         self.extra_debug_lines.extend_from_slice(lines);
@@ -1335,30 +1512,8 @@ impl Engine {
         let pos = glam::Vec3::from(camera.pos);
         let rot = glam::Quat::from(camera.rot);
         let view = glam::Mat4::from_rotation_translation(rot, pos).inverse();
-        let near = 0.01f32;
-        let far = camera.depth;
-        let proj = if let Some(fov) = camera.fov {
-            let left = -fov.left.tan() * near;
-            let right = fov.right.tan() * near;
-            let bottom = -fov.down.tan() * near;
-            let top = fov.up.tan() * near;
-            let w = right - left;
-            let h = top - bottom;
-            glam::Mat4::from_cols(
-                glam::Vec4::new(2.0 * near / w, 0.0, 0.0, 0.0),
-                glam::Vec4::new(0.0, 2.0 * near / h, 0.0, 0.0),
-                glam::Vec4::new(
-                    (right + left) / w,
-                    (top + bottom) / h,
-                    far / (near - far),
-                    -1.0,
-                ),
-                glam::Vec4::new(0.0, 0.0, far * near / (near - far), 0.0),
-            )
-        } else {
-            let aspect = target_size.width as f32 / target_size.height.max(1) as f32;
-            glam::Mat4::perspective_rh(camera.fov_y, aspect, near, far)
-        };
+        let aspect = target_size.width as f32 / target_size.height.max(1) as f32;
+        let proj = glam::Mat4::from(camera.projection_matrix(aspect, 0.01));
         let view_proj = proj * view;
         // Camera right and up in world space for billboarding
         let right = rot * glam::Vec3::X;
@@ -1427,14 +1582,19 @@ impl Engine {
             self.selected_object_handle = self.find_object(selection.custom_index);
         }
 
-        ui.checkbox(&mut self.track_hot_reloads, "Hot reloading");
+        if ui
+            .checkbox(&mut self.track_hot_reloads, "Hot reloading")
+            .changed()
+        {
+            self.asset_hub
+                .set_watching(self.track_hot_reloads.then_some(ASSET_RELOAD_DEBOUNCE));
+        }
 
         egui::CollapsingHeader::new("Rendering")
             .default_open(false)
             .show(ui, |ui| match self.renderer {
                 Renderer::RayTracer {
                     ref mut ray_config,
-                    ref mut denoiser_enabled,
                     ref mut denoiser_config,
                     ref mut post_proc_config,
                     ref mut frame_config,
@@ -1442,7 +1602,6 @@ impl Engine {
                 } => {
                     ray_config.populate_hud(ui);
                     frame_config.reset_reservoirs |= ui.button("Reset Accumulation").clicked();
-                    ui.checkbox(denoiser_enabled, "Enable Denoiser");
                     denoiser_config.populate_hud(ui);
                     post_proc_config.populate_hud(ui);
                 }
@@ -1643,6 +1802,7 @@ impl Engine {
                         config::FrontFace::Cw => blade_render::model::FrontFace::Clockwise,
                         config::FrontFace::Ccw => blade_render::model::FrontFace::CounterClockwise,
                     },
+                    ..Default::default()
                 },
             );
             visuals.push(Visual {
@@ -1756,6 +1916,7 @@ impl Engine {
             colliders,
             visuals,
             color_tint: [1.0; 4],
+            layers: blade_render::DEFAULT_LAYERS,
         });
         self.physics.rigid_bodies[rb_handle].user_data = raw_handle as u128;
         ObjectHandle(raw_handle)
@@ -1799,6 +1960,7 @@ impl Engine {
             colliders: Vec::new(),
             visuals: vec![visual],
             color_tint: [1.0; 4],
+            layers: blade_render::DEFAULT_LAYERS,
         });
         // Store ObjectHandle index in rigid body user_data for fast lookup in contacts
         self.physics.rigid_bodies[rb_handle].user_data = raw_handle as u128;
@@ -2018,6 +2180,25 @@ impl Engine {
         self.objects[handle.0].color_tint = tint;
     }
 
+    /// Assign an object to a set of visibility layers, see `blade_render::Object::layers`.
+    pub fn set_layers(&mut self, handle: ObjectHandle, layers: u32) {
+        self.objects[handle.0].layers = layers;
+    }
+
+    /// Only show the objects on any of the given layers.
+    pub fn set_visible_layers(&mut self, visible_layers: u32) {
+        match self.renderer {
+            Renderer::RayTracer {
+                ref mut frame_config,
+                ..
+            } => frame_config.visible_layers = visible_layers,
+            Renderer::Rasterizer {
+                ref mut raster_config,
+                ..
+            } => raster_config.visible_layers = visible_layers,
+        }
+    }
+
     pub fn set_joint_motor(
         &mut self,
         handle: JointHandle,
@@ -2038,6 +2219,9 @@ impl Engine {
     }
 
     pub fn set_environment_map(&mut self, path: &str) {
+        if let Some(ref mut sky) = self.sky {
+            sky.is_dirty = true;
+        }
         if path.is_empty() {
             self.environment_map = None;
         } else {
@@ -2048,6 +2232,7 @@ impl Engine {
                     format: gpu::TextureFormat::Rgba32Float,
                     generate_mips: false,
                     y_flip: false,
+                    basis: None,
                 },
             );
             self.environment_map = Some(handle);
@@ -2070,6 +2255,29 @@ impl Engine {
             .create_texture(name, width, height, data);
         let handle = self.asset_hub.textures.insert(texture);
         self.environment_map = Some(handle);
+        if let Some(ref mut sky) = self.sky {
+            sky.is_dirty = true;
+        }
+    }
+
+    /// Use a procedural sky as the environment, lighting the scene by its sun.
+    ///
+    /// The sky is regenerated at the next frame, if the parameters have changed.
+    pub fn set_sky(&mut self, model: blade_render::SkyModel) {
+        match self.sky {
+            Some(ref mut sky) => {
+                sky.is_dirty |= sky.model != model || self.environment_map != Some(sky.texture);
+                sky.model = model;
+            }
+            None => {
+                self.sky = Some(Sky {
+                    model,
+                    texture: self.asset_hub.create_sky(&model),
+                    is_dirty: true,
+                });
+            }
+        }
+        self.environment_map = self.sky.as_ref().map(|sky| sky.texture);
     }
 
     pub fn set_gravity(&mut self, force: f32) {
@@ -2096,13 +2304,13 @@ impl Engine {
         }
     }
 
-    pub fn set_average_luminosity(&mut self, avg_lum: f32) {
+    pub fn set_exposure(&mut self, exposure_ev: f32) {
         if let Renderer::RayTracer {
             ref mut post_proc_config,
             ..
         } = self.renderer
         {
-            post_proc_config.average_luminocity = avg_lum;
+            post_proc_config.exposure_ev = exposure_ev;
         }
     }
 
@@ -0,0 +1,123 @@
+use std::{collections::HashMap, sync::Mutex};
+
+/// Max number of distinct live samplers in the cache.
+/// Past it, the samplers are created without deduplication.
+const MAX_CACHED_SAMPLERS: usize = 1024;
+
+/// Sampler descriptor without the name, in a hashable form.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct SamplerKey {
+    address_modes: [crate::AddressMode; 3],
+    mag_filter: crate::FilterMode,
+    min_filter: crate::FilterMode,
+    mipmap_filter: crate::FilterMode,
+    lod_min_clamp: u32,
+    lod_max_clamp: Option<u32>,
+    lod_bias: u32,
+    compare: Option<crate::CompareFunction>,
+    anisotropy_clamp: u32,
+    border_color: Option<crate::TextureColor>,
+}
+
+impl SamplerKey {
+    fn new(desc: &crate::SamplerDesc) -> Self {
+        Self {
+            address_modes: desc.address_modes,
+            mag_filter: desc.mag_filter,
+            min_filter: desc.min_filter,
+            mipmap_filter: desc.mipmap_filter,
+            lod_min_clamp: desc.lod_min_clamp.to_bits(),
+            lod_max_clamp: desc.lod_max_clamp.map(f32::to_bits),
+            lod_bias: desc.lod_bias.to_bits(),
+            compare: desc.compare,
+            anisotropy_clamp: desc.anisotropy_clamp,
+            border_color: desc.border_color,
+        }
+    }
+}
+
+struct CachedSampler {
+    sampler: crate::Sampler,
+    ref_count: u32,
+}
+
+#[derive(Default)]
+struct SamplerEntries {
+    cached: HashMap<SamplerKey, CachedSampler>,
+    keys: HashMap<crate::Sampler, SamplerKey>,
+    stats: crate::SamplerCacheStats,
+}
+
+/// Samplers shared between the identical descriptors.
+///
+/// Every `create_sampler` of a cached sampler adds a reference,
+/// and every `destroy_sampler` removes one, so a sampler is only
+/// destroyed when the last of its users is done with it.
+#[derive(Default)]
+pub(crate) struct SamplerCache {
+    entries: Mutex<SamplerEntries>,
+}
+
+impl SamplerCache {
+    /// Return the cached sampler matching the descriptor,
+    /// or the one produced by `create`.
+    pub(crate) fn get_or_create(
+        &self,
+        desc: &crate::SamplerDesc,
+        create: impl FnOnce() -> crate::Sampler,
+    ) -> crate::Sampler {
+        let key = SamplerKey::new(desc);
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.cached.get_mut(&key) {
+            entry.ref_count += 1;
+            let sampler = entry.sampler;
+            entries.stats.hits += 1;
+            return sampler;
+        }
+
+        let sampler = create();
+        entries.stats.misses += 1;
+        if entries.cached.len() < MAX_CACHED_SAMPLERS {
+            entries.cached.insert(
+                key,
+                CachedSampler {
+                    sampler,
+                    ref_count: 1,
+                },
+            );
+            entries.keys.insert(sampler, key);
+        }
+        sampler
+    }
+
+    /// Remove a reference to the sampler.
+    /// Return true if it's no longer used and needs to be destroyed.
+    pub(crate) fn release(&self, sampler: crate::Sampler) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(&key) = entries.keys.get(&sampler) else {
+            return true;
+        };
+        let entry = entries.cached.get_mut(&key).unwrap();
+        entry.ref_count -= 1;
+        if entry.ref_count != 0 {
+            return false;
+        }
+        entries.cached.remove(&key);
+        entries.keys.remove(&sampler);
+        true
+    }
+}
+
+impl crate::Context {
+    /// Statistics of the sampler deduplication.
+    ///
+    /// Identical sampler descriptors, ignoring the names, share a sampler,
+    /// which is only destroyed after a `destroy_sampler` for each `create_sampler`.
+    pub fn sampler_cache_stats(&self) -> crate::SamplerCacheStats {
+        let entries = self.sampler_cache.entries.lock().unwrap();
+        crate::SamplerCacheStats {
+            cached_count: entries.cached.len() as u32,
+            ..entries.stats
+        }
+    }
+}
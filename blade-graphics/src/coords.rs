@@ -0,0 +1,69 @@
+//! Coordinate conventions, which are the same on all the backends.
+//!
+//! - Normalized device coordinates (NDC) have X pointing right and Y pointing up,
+//!   with (-1, -1) at the bottom left corner of the viewport.
+//! - Depth in NDC goes from 0 at the near plane to 1 at the far plane.
+//! - Framebuffer coordinates, including `@builtin(position)`, `Viewport`, and `ScissorRect`,
+//!   start at the top left corner, with Y pointing down.
+//! - Texture coordinates start at the top left corner of the texture,
+//!   which is the first row of texels in memory, with V pointing down.
+//! - `FrontFace` is the winding of the triangles as seen on the screen,
+//!   which matches the winding in NDC.
+//!
+//! Backends insert the flips that their native API needs, so the same shaders
+//! and pipeline states produce the same images everywhere.
+
+/// Range of the depth in NDC.
+pub const NDC_DEPTH_RANGE: std::ops::Range<f32> = 0.0..1.0;
+
+const fn column_matrix(columns: [[f32; 4]; 4]) -> mint::ColumnMatrix4<f32> {
+    const fn vector(v: [f32; 4]) -> mint::Vector4<f32> {
+        mint::Vector4 {
+            x: v[0],
+            y: v[1],
+            z: v[2],
+            w: v[3],
+        }
+    }
+    mint::ColumnMatrix4 {
+        x: vector(columns[0]),
+        y: vector(columns[1]),
+        z: vector(columns[2]),
+        w: vector(columns[3]),
+    }
+}
+
+/// Transform from the NDC XY to the texture coordinates of the rendered image.
+pub const NDC_TO_UV: mint::ColumnMatrix4<f32> = column_matrix([
+    [0.5, 0.0, 0.0, 0.0],
+    [0.0, -0.5, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.5, 0.5, 0.0, 1.0],
+]);
+
+/// Transform from the texture coordinates to the NDC XY, inverse of `NDC_TO_UV`.
+pub const UV_TO_NDC: mint::ColumnMatrix4<f32> = column_matrix([
+    [2.0, 0.0, 0.0, 0.0],
+    [0.0, -2.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0, 1.0],
+]);
+
+/// Transform from the GL clip space, where the depth is in -1..1,
+/// to the clip space of Blade. Multiply a GL-style projection matrix by it.
+pub const GL_CLIP_TO_CLIP: mint::ColumnMatrix4<f32> = column_matrix([
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 0.5, 0.0],
+    [0.0, 0.0, 0.5, 1.0],
+]);
+
+/// Convert a point from the NDC XY to the texture coordinates, see `NDC_TO_UV`.
+pub fn ndc_to_uv(ndc: [f32; 2]) -> [f32; 2] {
+    [0.5 * ndc[0] + 0.5, 0.5 - 0.5 * ndc[1]]
+}
+
+/// Convert a point from the texture coordinates to the NDC XY, see `UV_TO_NDC`.
+pub fn uv_to_ndc(uv: [f32; 2]) -> [f32; 2] {
+    [2.0 * uv[0] - 1.0, 1.0 - 2.0 * uv[1]]
+}
@@ -0,0 +1,142 @@
+use std::sync::Mutex;
+
+enum Resource {
+    Buffer(crate::Buffer),
+    Texture(crate::Texture),
+    TextureView(crate::TextureView),
+    Sampler(crate::Sampler),
+    AccelerationStructure(crate::AccelerationStructure),
+    ComputePipeline(crate::ComputePipeline),
+    RenderPipeline(crate::RenderPipeline),
+}
+
+/// Resources waiting for the GPU to be done with them before destruction.
+#[derive(Default)]
+pub(crate) struct DeferredDestructions {
+    pending: Mutex<Vec<(Resource, crate::SyncPoint)>>,
+}
+
+impl DeferredDestructions {
+    fn push(&self, resource: Resource, sync_point: &crate::SyncPoint) {
+        self.pending
+            .lock()
+            .unwrap()
+            .push((resource, sync_point.clone()));
+    }
+
+    /// Destroy the resources that are no longer used by the GPU.
+    ///
+    /// Checking the sync points waits on them, which drains again,
+    /// so the nested calls are skipped.
+    pub(crate) fn drain(&self, context: &crate::Context) {
+        let Ok(mut pending) = self.pending.try_lock() else {
+            return;
+        };
+        pending.retain_mut(|entry| {
+            let done = context.wait_for(&entry.1, 0).unwrap_or(false);
+            if done {
+                destroy(&mut entry.0, context);
+            }
+            !done
+        });
+    }
+
+    /// Destroy all the resources, once the GPU is idle.
+    pub(crate) fn destroy_all(&self, context: &crate::Context) {
+        let mut pending = self.pending.lock().unwrap();
+        for (mut resource, _) in pending.drain(..) {
+            destroy(&mut resource, context);
+        }
+    }
+}
+
+fn destroy(resource: &mut Resource, context: &crate::Context) {
+    match *resource {
+        Resource::Buffer(buffer) => context.destroy_buffer(buffer),
+        Resource::Texture(texture) => context.destroy_texture(texture),
+        Resource::TextureView(view) => context.destroy_texture_view(view),
+        Resource::Sampler(sampler) => context.destroy_sampler(sampler),
+        Resource::AccelerationStructure(acceleration_structure) => {
+            context.destroy_acceleration_structure(acceleration_structure)
+        }
+        Resource::ComputePipeline(ref mut pipeline) => context.destroy_compute_pipeline(pipeline),
+        Resource::RenderPipeline(ref mut pipeline) => context.destroy_render_pipeline(pipeline),
+    }
+}
+
+impl crate::Context {
+    /// Destroy a buffer that may still be used by the submitted work.
+    ///
+    /// It's destroyed during one of the later submissions or waits,
+    /// once the GPU reaches the sync point, or when the context is dropped.
+    /// This allows replacing resources on the fly without stalling on the GPU.
+    pub fn destroy_buffer_after(&self, buffer: crate::Buffer, sync_point: &crate::SyncPoint) {
+        self.deferred_destructions
+            .push(Resource::Buffer(buffer), sync_point);
+    }
+
+    /// Destroy a texture once the GPU reaches the sync point,
+    /// see `destroy_buffer_after`.
+    pub fn destroy_texture_after(&self, texture: crate::Texture, sync_point: &crate::SyncPoint) {
+        self.deferred_destructions
+            .push(Resource::Texture(texture), sync_point);
+    }
+
+    /// Destroy a texture view once the GPU reaches the sync point,
+    /// see `destroy_buffer_after`.
+    pub fn destroy_texture_view_after(
+        &self,
+        view: crate::TextureView,
+        sync_point: &crate::SyncPoint,
+    ) {
+        self.deferred_destructions
+            .push(Resource::TextureView(view), sync_point);
+    }
+
+    /// Destroy a sampler once the GPU reaches the sync point,
+    /// see `destroy_buffer_after`.
+    pub fn destroy_sampler_after(&self, sampler: crate::Sampler, sync_point: &crate::SyncPoint) {
+        self.deferred_destructions
+            .push(Resource::Sampler(sampler), sync_point);
+    }
+
+    /// Destroy an acceleration structure once the GPU reaches the sync point,
+    /// see `destroy_buffer_after`.
+    pub fn destroy_acceleration_structure_after(
+        &self,
+        acceleration_structure: crate::AccelerationStructure,
+        sync_point: &crate::SyncPoint,
+    ) {
+        self.deferred_destructions.push(
+            Resource::AccelerationStructure(acceleration_structure),
+            sync_point,
+        );
+    }
+
+    /// Destroy a compute pipeline once the GPU reaches the sync point,
+    /// see `destroy_buffer_after`.
+    pub fn destroy_compute_pipeline_after(
+        &self,
+        pipeline: crate::ComputePipeline,
+        sync_point: &crate::SyncPoint,
+    ) {
+        self.deferred_destructions
+            .push(Resource::ComputePipeline(pipeline), sync_point);
+    }
+
+    /// Destroy a render pipeline once the GPU reaches the sync point,
+    /// see `destroy_buffer_after`.
+    pub fn destroy_render_pipeline_after(
+        &self,
+        pipeline: crate::RenderPipeline,
+        sync_point: &crate::SyncPoint,
+    ) {
+        self.deferred_destructions
+            .push(Resource::RenderPipeline(pipeline), sync_point);
+    }
+
+    /// Number of resources waiting to be destroyed.
+    pub fn pending_destruction_count(&self) -> usize {
+        self.deferred_destructions.pending.lock().unwrap().len()
+    }
+}
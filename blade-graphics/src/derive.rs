@@ -19,6 +19,12 @@ impl HasShaderBinding for super::Sampler {
 impl HasShaderBinding for super::BufferPiece {
     const TYPE: ShaderBinding = ShaderBinding::Buffer;
 }
+impl HasShaderBinding for super::Nullable<super::TextureView> {
+    const TYPE: ShaderBinding = ShaderBinding::Texture;
+}
+impl HasShaderBinding for super::Nullable<super::BufferPiece> {
+    const TYPE: ShaderBinding = ShaderBinding::Buffer;
+}
 impl<'a, const N: ResourceIndex> HasShaderBinding for &'a super::BufferArray<N> {
     const TYPE: ShaderBinding = ShaderBinding::BufferArray { count: N };
 }
@@ -0,0 +1,183 @@
+use std::{
+    sync::{Mutex, OnceLock},
+    thread,
+};
+
+/// Size in bytes of the zero buffer.
+const BUFFER_SIZE: u64 = 1024;
+
+/// Handles of the default resources of a context.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DummySet {
+    textures: [crate::Texture; 3],
+    pub(crate) white: crate::TextureView,
+    pub(crate) black: crate::TextureView,
+    pub(crate) normal: crate::TextureView,
+    pub(crate) buffer: crate::Buffer,
+}
+
+impl DummySet {
+    fn new(context: &crate::Context) -> Self {
+        let size = crate::Extent {
+            width: 1,
+            height: 1,
+            depth: 1,
+        };
+        let format = crate::TextureFormat::Rgba8Unorm;
+        // Texels of white, black, and a normal facing +Z,
+        // which are the same in linear and sRGB except for the normal,
+        // so a linear format is used.
+        let texels = [[!0u8; 4], [0; 4], [0x80, 0x80, !0, !0]];
+        let names = ["dummy/white", "dummy/black", "dummy/normal"];
+        let textures = names.map(|name| {
+            context.create_texture(crate::TextureDesc {
+                name,
+                format,
+                size,
+                array_layer_count: 1,
+                mip_level_count: 1,
+                dimension: crate::TextureDimension::D2,
+                usage: crate::TextureUsage::RESOURCE
+                    | crate::TextureUsage::STORAGE
+                    | crate::TextureUsage::COPY,
+                sample_count: 1,
+                external: None,
+            })
+        });
+        let mut views = textures.iter().zip(names).map(|(&texture, name)| {
+            context.create_texture_view(
+                texture,
+                crate::TextureViewDesc {
+                    name,
+                    format,
+                    dimension: crate::ViewDimension::D2,
+                    subresources: &crate::TextureSubresources::default(),
+                },
+            )
+        });
+        let (white, black, normal) = (
+            views.next().unwrap(),
+            views.next().unwrap(),
+            views.next().unwrap(),
+        );
+        let buffer = context.create_buffer(crate::BufferDesc {
+            name: "dummy/buffer",
+            size: BUFFER_SIZE,
+            memory: crate::Memory::Device,
+        });
+
+        let staging = context.create_buffer(crate::BufferDesc {
+            name: "dummy/staging",
+            size: 4 * texels.len() as u64,
+            memory: crate::Memory::Upload,
+        });
+        staging.write_slice(0, &texels);
+        let mut encoder = context.create_command_encoder(crate::CommandEncoderDesc {
+            name: "dummy",
+            buffer_count: 1,
+        });
+        encoder.start();
+        for &texture in textures.iter() {
+            encoder.init_texture(texture);
+        }
+        {
+            let mut transfer = encoder.transfer("init dummy");
+            for (index, &texture) in textures.iter().enumerate() {
+                transfer.copy_buffer_to_texture(
+                    staging.at(4 * index as u64),
+                    4,
+                    texture.into(),
+                    size,
+                );
+            }
+            transfer.fill_buffer(buffer.into(), BUFFER_SIZE, 0);
+        }
+        let sync_point = context.submit(&mut encoder);
+        let _ = context.wait_for(&sync_point, !0);
+        context.destroy_command_encoder(&mut encoder);
+        context.destroy_buffer(staging);
+
+        Self {
+            textures,
+            white,
+            black,
+            normal,
+            buffer,
+        }
+    }
+}
+
+/// Default resources of a context, created on first use.
+#[derive(Default)]
+pub(crate) struct DummyResources {
+    set: OnceLock<DummySet>,
+    /// Thread that is creating the resources.
+    creator: Mutex<Option<thread::ThreadId>>,
+}
+
+impl DummyResources {
+    fn get(&self, context: &crate::Context) -> &DummySet {
+        self.set.get_or_init(|| {
+            *self.creator.lock().unwrap() = Some(thread::current().id());
+            let set = DummySet::new(context);
+            *self.creator.lock().unwrap() = None;
+            set
+        })
+    }
+
+    /// Resources bound in place of `Nullable` bindings of `None`,
+    /// for a new command encoder.
+    ///
+    /// They are created here if needed, except for the encoder uploading them,
+    /// which doesn't bind anything.
+    pub(crate) fn for_encoder(&self, context: &crate::Context) -> Option<DummySet> {
+        if *self.creator.lock().unwrap() == Some(thread::current().id()) {
+            None
+        } else {
+            Some(*self.get(context))
+        }
+    }
+
+    pub(crate) fn destroy(&self, context: &crate::Context) {
+        if let Some(set) = self.set.get() {
+            for view in [set.white, set.black, set.normal] {
+                context.destroy_texture_view(view);
+            }
+            for &texture in set.textures.iter() {
+                context.destroy_texture(texture);
+            }
+            context.destroy_buffer(set.buffer);
+        }
+    }
+}
+
+impl crate::Context {
+    /// 1x1 white texture, created on first use and destroyed with the context.
+    ///
+    /// The default textures are `Rgba8Unorm`, filterable, and usable
+    /// as both sampled and storage textures. White and black are the same
+    /// in linear and sRGB, while the normal map is meant to be linear.
+    pub fn dummy_white(&self) -> crate::TextureView {
+        self.dummy_resources.get(self).white
+    }
+
+    /// 1x1 texture of zeros, including the alpha, see `dummy_white`.
+    ///
+    /// It's bound for the `Nullable` textures of `None` without `Capabilities::robustness`.
+    pub fn dummy_black(&self) -> crate::TextureView {
+        self.dummy_resources.get(self).black
+    }
+
+    /// 1x1 normal map texture with the normal facing +Z, see `dummy_white`.
+    pub fn dummy_normal(&self) -> crate::TextureView {
+        self.dummy_resources.get(self).normal
+    }
+
+    /// Small buffer of zeros, created on first use and destroyed with the context.
+    ///
+    /// It's bound for the `Nullable` buffers of `None` without `Capabilities::robustness`,
+    /// so it has to stay zeroed: shaders should not write to it.
+    pub fn dummy_buffer(&self) -> crate::Buffer {
+        self.dummy_resources.get(self).buffer
+    }
+}
@@ -1,4 +1,4 @@
-use std::{str, time::Duration};
+use std::{mem, str, time::Duration};
 
 const COLOR_ATTACHMENTS: &[u32] = &[
     glow::COLOR_ATTACHMENT0,
@@ -40,6 +40,15 @@ impl crate::ShaderBindable for super::TextureView {
         }
     }
 }
+impl crate::ShaderBindable for crate::Nullable<super::TextureView> {
+    fn bind_to(&self, ctx: &mut super::PipelineContext, index: u32) {
+        let view = match self.0 {
+            Some(view) => view,
+            None => ctx.dummies.expect("No dummy resources").black,
+        };
+        view.bind_to(ctx, index);
+    }
+}
 impl<'a, const N: crate::ResourceIndex> crate::ShaderBindable for &'a crate::TextureArray<N> {
     fn bind_to(&self, _ctx: &mut super::PipelineContext, _index: u32) {
         unimplemented!()
@@ -57,6 +66,9 @@ impl crate::ShaderBindable for super::Sampler {
 }
 impl crate::ShaderBindable for crate::BufferPiece {
     fn bind_to(&self, ctx: &mut super::PipelineContext, index: u32) {
+        if ctx.dynamic_mask & (1 << index) != 0 {
+            ctx.dynamic_buffers.push((index, *self));
+        }
         for &slot in ctx.targets[index as usize].iter() {
             ctx.commands.push(super::Command::BindBuffer {
                 target: glow::SHADER_STORAGE_BUFFER,
@@ -67,6 +79,15 @@ impl crate::ShaderBindable for crate::BufferPiece {
         }
     }
 }
+impl crate::ShaderBindable for crate::Nullable<crate::BufferPiece> {
+    fn bind_to(&self, ctx: &mut super::PipelineContext, index: u32) {
+        let piece = match self.0 {
+            Some(piece) => piece,
+            None => ctx.dummies.expect("No dummy resources").buffer.into(),
+        };
+        piece.bind_to(ctx, index);
+    }
+}
 impl<'a, const N: crate::ResourceIndex> crate::ShaderBindable for &'a crate::BufferArray<N> {
     fn bind_to(&self, _ctx: &mut super::PipelineContext, _index: u32) {
         unimplemented!()
@@ -90,6 +111,13 @@ impl<'a, const N: crate::ResourceIndex> crate::ShaderBindable
 
 impl super::CommandEncoder {
     fn begin_pass(&mut self, label: &str) {
+        debug_assert!(
+            matches!(
+                self.recording,
+                crate::RecordingState::Transient | crate::RecordingState::Reusable
+            ),
+            "Command encoder is not recording"
+        );
         if self.needs_scopes {
             let start = self.string_data.len();
             self.string_data.extend_from_slice(label.as_bytes());
@@ -103,19 +131,36 @@ impl super::CommandEncoder {
             self.commands.push(super::Command::QueryCounter {
                 query: td.queries[id],
             });
-            td.pass_names.push(label.to_string());
+            td.pass_names.push(label);
         }
     }
 
+    fn retained_bytes(&self) -> usize {
+        self.commands.capacity() * mem::size_of::<super::Command>()
+            + self.plain_data.capacity()
+            + self.string_data.capacity()
+            + self.invalidate_attachments.capacity() * mem::size_of::<u32>()
+            + self.resolve_attachments.capacity()
+                * mem::size_of::<(super::TextureView, super::TextureView)>()
+            + crate::timings_retained_bytes(&self.timings)
+            + self.timing_datas.as_ref().map_or(0, |tds| {
+                tds.iter().map(|td| td.pass_names.retained_bytes()).sum()
+            })
+    }
+
     fn pass<P>(&mut self, kind: super::PassKind) -> super::PassEncoder<'_, P> {
         super::PassEncoder {
             commands: &mut self.commands,
             plain_data: &mut self.plain_data,
+            binding_stats: &mut self.binding_stats,
             kind,
-            invalidate_attachments: Vec::new(),
+            invalidate_attachments: &mut self.invalidate_attachments,
+            resolve_attachments: &mut self.resolve_attachments,
             pipeline: Default::default(),
             limits: &self.limits,
             has_scope: self.needs_scopes,
+            depth_stencil_read_only: crate::TexelAspects::empty(),
+            dummies: self.dummies,
         }
     }
 
@@ -132,9 +177,11 @@ impl super::CommandEncoder {
             }
 
             timing_datas.rotate_left(1);
-            self.timings.clear();
             let td = timing_datas.first_mut().unwrap();
-            if !td.pass_names.is_empty() {
+            if td.pass_names.is_empty() {
+                self.timings.clear();
+                self.timings_recording_index = None;
+            } else {
                 let mut prev = 0;
                 unsafe {
                     gl.get_query_parameter_u64_with_offset(
@@ -143,7 +190,7 @@ impl super::CommandEncoder {
                         &mut prev as *mut _ as usize,
                     );
                 }
-                for (pass_name, &query) in td.pass_names.drain(..).zip(td.queries[1..].iter()) {
+                let durations = td.queries[1..].iter().map(|&query| {
                     let mut result: u64 = 0;
                     unsafe {
                         gl.get_query_parameter_u64_with_offset(
@@ -153,9 +200,59 @@ impl super::CommandEncoder {
                         );
                     }
                     let time = Duration::from_nanos(result - prev);
-                    self.timings.push((pass_name, time));
-                    prev = result
-                }
+                    prev = result;
+                    time
+                });
+                crate::fill_timings(&mut self.timings, td.pass_names.iter().zip(durations));
+                self.timings_recording_index = Some(td.pass_names.recording);
+                td.pass_names.clear();
+            }
+        }
+    }
+
+    /// Finish the encoder and execute its commands.
+    pub(super) fn execute(&mut self, gl: &glow::Context) {
+        use glow::HasContext as _;
+        match self.recording {
+            crate::RecordingState::Transient => self.finish(gl),
+            crate::RecordingState::Reusable => {
+                self.finish(gl);
+                self.recording = crate::RecordingState::Recorded;
+            }
+            crate::RecordingState::Recorded => {}
+            crate::RecordingState::Invalid => {
+                panic!("Command encoder is invalidated, it needs to be started again")
+            }
+        }
+
+        let push_group = !self.name.is_empty() && gl.supports_debug();
+        let ec = unsafe {
+            if push_group {
+                gl.push_debug_group(glow::DEBUG_SOURCE_APPLICATION, super::DEBUG_ID, &self.name);
+            }
+            let framebuf = gl.create_framebuffer().unwrap();
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuf));
+            let plain_buffer = gl.create_buffer().unwrap();
+            if !self.plain_data.is_empty() {
+                log::trace!("Allocating plain data of size {}", self.plain_data.len());
+                gl.bind_buffer(glow::UNIFORM_BUFFER, Some(plain_buffer));
+                gl.buffer_data_u8_slice(glow::UNIFORM_BUFFER, &self.plain_data, glow::STATIC_DRAW);
+            }
+            super::ExecutionContext {
+                framebuf,
+                plain_buffer,
+                string_data: &self.string_data,
+            }
+        };
+        for command in self.commands.iter() {
+            log::trace!("{:?}", command);
+            unsafe { command.execute(gl, &ec) };
+        }
+        unsafe {
+            gl.delete_framebuffer(ec.framebuf);
+            gl.delete_buffer(ec.plain_buffer);
+            if push_group {
+                gl.pop_debug_group();
             }
         }
     }
@@ -182,7 +279,14 @@ impl super::CommandEncoder {
         self.begin_pass(label);
 
         let mut target_size = [0u16; 2];
-        let mut invalidate_attachments = Vec::new();
+        assert!(
+            !targets.colors.is_empty() || targets.depth_stencil.is_some(),
+            "Render pass '{label}' has neither color nor depth targets"
+        );
+        assert!(targets.multiview.is_none(), "Multiview is not supported");
+        let invalidate_attachments = &mut self.invalidate_attachments;
+        invalidate_attachments.clear();
+        self.resolve_attachments.clear();
         for (i, rt) in targets.colors.iter().enumerate() {
             let attachment = glow::COLOR_ATTACHMENT0 + i as u32;
             target_size = rt.view.target_size;
@@ -193,12 +297,13 @@ impl super::CommandEncoder {
             if let crate::FinishOp::Discard = rt.finish_op {
                 invalidate_attachments.push(attachment);
             }
-            if let crate::FinishOp::ResolveTo(to) = rt.finish_op {
-                self.commands
-                    .push(super::Command::BlitFramebuffer { from: rt.view, to });
+            if let Some((to, _)) = rt.finish_op.resolve() {
+                self.resolve_attachments.push((rt.view, to));
             }
         }
         if let Some(ref rt) = targets.depth_stencil {
+            // Read-only aspects stay attached, GL doesn't store them differently
+            let _ = targets.depth_stencil_writes(rt.view.aspects);
             let attachment = match rt.view.aspects {
                 crate::TexelAspects::DEPTH => glow::DEPTH_ATTACHMENT,
                 crate::TexelAspects::STENCIL => glow::STENCIL_ATTACHMENT,
@@ -212,11 +317,28 @@ impl super::CommandEncoder {
             if let crate::FinishOp::Discard = rt.finish_op {
                 invalidate_attachments.push(attachment);
             }
+            // Blitting picks an unspecified sample of the depth, so only `SampleZero` is accepted
+            match rt.finish_op.resolve() {
+                Some((to, crate::DepthResolveMode::SampleZero)) => {
+                    self.resolve_attachments.push((rt.view, to));
+                }
+                Some((_, mode)) => panic!("Depth resolve mode {mode:?} is not supported"),
+                None => {}
+            }
         }
 
         self.commands.push(super::Command::SetDrawColorBuffers(
             targets.colors.len() as _
         ));
+        if self
+            .capabilities
+            .contains(super::Capabilities::FRAMEBUFFER_SRGB)
+        {
+            // The switch is global, so linear views of sRGB textures
+            // are only written raw when none of the targets are sRGB
+            let srgb = targets.colors.iter().any(|rt| rt.view.format.is_srgb());
+            self.commands.push(super::Command::SetFramebufferSrgb(srgb));
+        }
         self.commands
             .push(super::Command::SetViewport(crate::Viewport {
                 x: 0.0,
@@ -235,33 +357,26 @@ impl super::CommandEncoder {
 
         // issue the clears
         for (i, rt) in targets.colors.iter().enumerate() {
-            if let crate::InitOp::Clear(color) = rt.init_op {
+            if let Some(color) = rt.color_clear_value(rt.view.format) {
                 self.commands.push(super::Command::ClearColor {
                     draw_buffer: i as u32,
                     color,
-                    ty: super::ColorType::Float, //TODO: get from the format
                 });
             }
         }
-        if let Some(ref rt) = targets.depth_stencil
-            && let crate::InitOp::Clear(color) = rt.init_op
-        {
-            self.commands.push(super::Command::ClearDepthStencil {
-                depth: if rt.view.aspects.contains(crate::TexelAspects::DEPTH) {
-                    Some(color.depth_clear_value())
-                } else {
-                    None
-                },
-                stencil: if rt.view.aspects.contains(crate::TexelAspects::STENCIL) {
-                    Some(color.stencil_clear_value())
-                } else {
-                    None
-                },
-            });
+        if let Some(ref rt) = targets.depth_stencil {
+            let (depth, stencil) = rt.depth_stencil_clear_values(rt.view.format);
+            let depth = depth.filter(|_| rt.view.aspects.contains(crate::TexelAspects::DEPTH));
+            let stencil =
+                stencil.filter(|_| rt.view.aspects.contains(crate::TexelAspects::STENCIL));
+            if depth.is_some() || stencil.is_some() {
+                self.commands
+                    .push(super::Command::ClearDepthStencil { depth, stencil });
+            }
         }
 
         let mut pass = self.pass(super::PassKind::Render);
-        pass.invalidate_attachments = invalidate_attachments;
+        pass.depth_stencil_read_only = targets.depth_stencil_read_only;
         pass
     }
 }
@@ -272,21 +387,71 @@ impl crate::traits::CommandEncoder for super::CommandEncoder {
     type Frame = super::Frame;
 
     fn start(&mut self) {
+        self.peak_retained_bytes = self.peak_retained_bytes.max(self.retained_bytes());
         self.commands.clear();
         self.plain_data.clear();
         self.string_data.clear();
         self.present_frames.clear();
+        self.binding_stats = Default::default();
+        self.recording = crate::RecordingState::Transient;
+        self.recording_index += 1;
+        if let Some(ref mut timing_datas) = self.timing_datas {
+            timing_datas.first_mut().unwrap().pass_names.recording = self.recording_index;
+        }
     }
 
-    fn init_texture(&mut self, _texture: super::Texture) {}
+    fn start_reusable(&mut self) {
+        self.start();
+        self.recording = crate::RecordingState::Reusable;
+    }
+
+    fn invalidate(&mut self) {
+        self.commands.clear();
+        self.plain_data.clear();
+        self.string_data.clear();
+        self.recording = crate::RecordingState::Invalid;
+    }
+
+    fn init_texture(&mut self, _texture: super::Texture) {
+        debug_assert_ne!(
+            self.recording,
+            crate::RecordingState::Reusable,
+            "Textures can't be initialized by a reusable encoder"
+        );
+    }
 
     fn present(&mut self, frame: super::Frame) {
+        debug_assert_ne!(
+            self.recording,
+            crate::RecordingState::Reusable,
+            "Frames can't be presented by a reusable encoder"
+        );
         self.present_frames.push(frame.platform);
     }
 
     fn timings(&self) -> &crate::Timings {
         &self.timings
     }
+
+    fn recording_index(&self) -> u64 {
+        self.recording_index
+    }
+
+    fn timings_recording_index(&self) -> Option<u64> {
+        self.timings_recording_index
+    }
+
+    fn binding_stats(&self) -> crate::BindingStats {
+        self.binding_stats
+    }
+
+    fn memory_stats(&self) -> crate::EncoderMemoryStats {
+        let retained_bytes = self.retained_bytes();
+        crate::EncoderMemoryStats {
+            retained_bytes,
+            peak_retained_bytes: self.peak_retained_bytes.max(retained_bytes),
+        }
+    }
 }
 
 impl super::PassEncoder<'_, super::ComputePipeline> {
@@ -304,10 +469,13 @@ impl super::PassEncoder<'_, super::ComputePipeline> {
         super::PipelineEncoder {
             commands: self.commands,
             plain_data: self.plain_data,
+            binding_stats: self.binding_stats,
             group_mappings: &pipeline.inner.group_mappings,
             topology: 0,
             limits: self.limits,
             vertex_attributes: &[],
+            dynamic_buffers: Vec::new(),
+            dummies: self.dummies,
         }
     }
 }
@@ -333,8 +501,20 @@ impl super::PassEncoder<'_, super::RenderPipeline> {
         &'b mut self,
         pipeline: &'b super::RenderPipeline,
     ) -> super::PipelineEncoder<'b> {
+        debug_assert!(
+            !pipeline
+                .depth_stencil_writes
+                .intersects(self.depth_stencil_read_only),
+            "Pipeline writes {:?} of the depth-stencil target, which is read-only in the pass",
+            pipeline.depth_stencil_writes & self.depth_stencil_read_only,
+        );
         self.commands
             .push(super::Command::SetProgram(pipeline.inner.program));
+        self.commands.push(super::Command::SetPrimitive {
+            front_face: pipeline.front_face,
+            cull_face: pipeline.cull_face,
+        });
+        self.commands.push(super::Command::SetDepth(pipeline.depth));
 
         match &pipeline.inner.color_targets[..] {
             &[(blend_state, write_masks)] => self
@@ -349,12 +529,23 @@ impl super::PassEncoder<'_, super::RenderPipeline> {
         super::PipelineEncoder {
             commands: self.commands,
             plain_data: self.plain_data,
+            binding_stats: self.binding_stats,
             group_mappings: &pipeline.inner.group_mappings,
             topology: map_primitive_topology(pipeline.topology),
             limits: self.limits,
             vertex_attributes: &pipeline.inner.vertex_attribute_infos,
+            dynamic_buffers: Vec::new(),
+            dummies: self.dummies,
         }
     }
+
+    pub fn begin_conditional(&mut self, _predicate: crate::BufferPiece) {
+        unimplemented!("Conditional rendering")
+    }
+
+    pub fn end_conditional(&mut self) {
+        unimplemented!("Conditional rendering")
+    }
 }
 
 impl<T> Drop for super::PassEncoder<'_, T> {
@@ -374,6 +565,10 @@ impl<T> Drop for super::PassEncoder<'_, T> {
                 self.commands.push(super::Command::ResetFramebuffer);
             }
         }
+        for (from, to) in self.resolve_attachments.drain(..) {
+            self.commands
+                .push(super::Command::BlitFramebuffer { from, to });
+        }
         if self.has_scope {
             self.commands.push(super::Command::PopScope);
         }
@@ -411,6 +606,14 @@ impl crate::traits::TransferEncoder for super::PassEncoder<'_, ()> {
         dst: crate::TexturePiece,
         size: crate::Extent,
     ) {
+        crate::debug_check_region(&src, size);
+        crate::debug_check_region(&dst, size);
+        assert!(
+            src.texture.format.is_copy_compatible(dst.texture.format),
+            "Texels can't be copied from {:?} to {:?}",
+            src.texture.format,
+            dst.texture.format
+        );
         self.commands.push(super::Command::CopyTextureToTexture {
             src: src.into(),
             dst: dst.into(),
@@ -418,6 +621,39 @@ impl crate::traits::TransferEncoder for super::PassEncoder<'_, ()> {
         });
     }
 
+    fn blit(
+        &mut self,
+        src: crate::TexturePiece,
+        src_size: crate::Extent,
+        dst: crate::TexturePiece,
+        dst_size: crate::Extent,
+        filter: crate::FilterMode,
+    ) {
+        crate::debug_check_region(&src, src_size);
+        crate::debug_check_region(&dst, dst_size);
+        assert!(
+            src.texture.format.aspects() == crate::TexelAspects::COLOR
+                && dst.texture.format.aspects() == crate::TexelAspects::COLOR,
+            "Only color textures can be blitted, not {:?} to {:?}",
+            src.texture.format,
+            dst.texture.format
+        );
+        assert!(
+            src_size.depth == 1 && dst_size.depth == 1,
+            "Only 2D regions can be blitted"
+        );
+        self.commands.push(super::Command::BlitTexture {
+            src: src.into(),
+            src_size,
+            dst: dst.into(),
+            dst_size,
+            filter: match filter {
+                crate::FilterMode::Nearest => glow::NEAREST,
+                crate::FilterMode::Linear => glow::LINEAR,
+            },
+        });
+    }
+
     fn copy_buffer_to_texture(
         &mut self,
         src: crate::BufferPiece,
@@ -425,6 +661,7 @@ impl crate::traits::TransferEncoder for super::PassEncoder<'_, ()> {
         dst: crate::TexturePiece,
         size: crate::Extent,
     ) {
+        crate::debug_check_region(&dst, size);
         self.commands.push(super::Command::CopyBufferToTexture {
             src: src.into(),
             bytes_per_row,
@@ -440,6 +677,7 @@ impl crate::traits::TransferEncoder for super::PassEncoder<'_, ()> {
         bytes_per_row: u32,
         size: crate::Extent,
     ) {
+        crate::debug_check_region(&src, size);
         self.commands.push(super::Command::CopyTextureToBuffer {
             src: src.into(),
             dst: dst.into(),
@@ -452,12 +690,52 @@ impl crate::traits::TransferEncoder for super::PassEncoder<'_, ()> {
 #[hidden_trait::expose]
 impl crate::traits::PipelineEncoder for super::PipelineEncoder<'_> {
     fn bind<D: crate::ShaderData>(&mut self, group: u32, data: &D) {
+        self.binding_stats.issued += 1;
+        let mapping = &self.group_mappings[group as usize];
+        let group_index = group as usize;
+        if self.dynamic_buffers.len() <= group_index {
+            self.dynamic_buffers.resize_with(group_index + 1, Vec::new);
+        }
+        let dynamic_buffers = &mut self.dynamic_buffers[group_index];
+        dynamic_buffers.clear();
         data.fill(super::PipelineContext {
             commands: self.commands,
             plain_data: self.plain_data,
-            targets: &self.group_mappings[group as usize].targets,
+            targets: &mapping.targets,
             limits: self.limits,
+            dynamic_mask: mapping.dynamic_mask,
+            dynamic_buffers,
+            dummies: self.dummies,
         });
+        dynamic_buffers.sort_by_key(|&(index, _)| index);
+    }
+
+    fn set_dynamic_offsets(&mut self, group: u32, offsets: &[u32]) {
+        let mapping = &self.group_mappings[group as usize];
+        let dynamic_buffers = self
+            .dynamic_buffers
+            .get(group as usize)
+            .map_or(&[][..], Vec::as_slice);
+        assert_eq!(
+            offsets.len(),
+            dynamic_buffers.len(),
+            "Group {group} has {} bound dynamic buffers",
+            dynamic_buffers.len()
+        );
+        for (&(index, base), &offset) in dynamic_buffers.iter().zip(offsets) {
+            let piece = crate::BufferPiece {
+                buffer: base.buffer,
+                offset: base.offset + offset as u64,
+            };
+            for &slot in mapping.targets[index as usize].iter() {
+                self.commands.push(super::Command::BindBuffer {
+                    target: glow::SHADER_STORAGE_BUFFER,
+                    slot,
+                    buffer: piece.into(),
+                    size: (piece.buffer.size - piece.offset) as u32,
+                });
+            }
+        }
     }
 }
 
@@ -470,6 +748,7 @@ impl crate::traits::ComputePipelineEncoder for super::PipelineEncoder<'_> {
     }
 
     fn dispatch_indirect(&mut self, indirect_buf: crate::BufferPiece) {
+        crate::indirect::debug_check_indirect::<crate::DispatchIndirectArgs>(&indirect_buf);
         self.commands.push(super::Command::DispatchIndirect {
             indirect_buf: indirect_buf.into(),
         });
@@ -552,17 +831,32 @@ impl crate::traits::RenderPipelineEncoder for super::PipelineEncoder<'_> {
         });
     }
 
-    fn draw_indirect(&mut self, _indirect_buf: crate::BufferPiece) {
-        unimplemented!()
+    fn draw_indirect(&mut self, indirect_buf: crate::BufferPiece) {
+        crate::indirect::debug_check_indirect::<crate::DrawIndirectArgs>(&indirect_buf);
+        self.commands.push(super::Command::DrawIndirect {
+            topology: self.topology,
+            indirect_buf: indirect_buf.into(),
+        });
     }
 
     fn draw_indexed_indirect(
         &mut self,
-        _index_buf: crate::BufferPiece,
-        _index_type: crate::IndexType,
-        _indirect_buf: crate::BufferPiece,
+        index_buf: crate::BufferPiece,
+        index_type: crate::IndexType,
+        indirect_buf: crate::BufferPiece,
     ) {
-        unimplemented!()
+        // The first index comes from the arguments, there is no room for the offset
+        assert_eq!(
+            index_buf.offset, 0,
+            "Index buffer offsets are not supported"
+        );
+        crate::indirect::debug_check_indirect::<crate::DrawIndexedIndirectArgs>(&indirect_buf);
+        self.commands.push(super::Command::DrawIndexedIndirect {
+            topology: self.topology,
+            raw_index_buf: index_buf.buffer.raw,
+            index_type: map_index_type(index_type),
+            indirect_buf: indirect_buf.into(),
+        });
     }
 }
 
@@ -770,6 +1064,36 @@ impl super::Command {
                         None,
                     );
                 }
+                Self::BlitTexture {
+                    ref src,
+                    ref src_size,
+                    ref dst,
+                    ref dst_size,
+                    filter,
+                } => {
+                    // The execution framebuffer may have other attachments to draw into
+                    let framebufs = [(); 2].map(|()| gl.create_framebuffer().unwrap());
+                    gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(framebufs[0]));
+                    attach_texture_part(gl, glow::READ_FRAMEBUFFER, src);
+                    gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(framebufs[1]));
+                    attach_texture_part(gl, glow::DRAW_FRAMEBUFFER, dst);
+                    gl.blit_framebuffer(
+                        src.origin[0] as i32,
+                        src.origin[1] as i32,
+                        (src.origin[0] + src_size.width) as i32,
+                        (src.origin[1] + src_size.height) as i32,
+                        dst.origin[0] as i32,
+                        dst.origin[1] as i32,
+                        (dst.origin[0] + dst_size.width) as i32,
+                        (dst.origin[1] + dst_size.height) as i32,
+                        glow::COLOR_BUFFER_BIT,
+                        filter,
+                    );
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(ec.framebuf));
+                    for framebuf in framebufs {
+                        gl.delete_framebuffer(framebuf);
+                    }
+                }
                 Self::CopyBufferToTexture {
                     ref src,
                     ref dst,
@@ -909,56 +1233,52 @@ impl super::Command {
                     );
                 }
 
-                Self::BlitFramebuffer { from, to } => {
-                    /*
-                        TODO: Framebuffers could be re-used instead of being created on the fly.
-                              Currently deleted down below
-                    */
-                    let framebuf_from = gl.create_framebuffer().unwrap();
-                    let framebuf_to = gl.create_framebuffer().unwrap();
-
-                    gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(framebuf_from));
-                    match from.inner {
-                        super::TextureInner::Renderbuffer { raw } => {
-                            gl.framebuffer_renderbuffer(
-                                glow::READ_FRAMEBUFFER,
-                                glow::COLOR_ATTACHMENT0, // NOTE: Assuming color attachment
-                                glow::RENDERBUFFER,
-                                Some(raw),
-                            );
+                Self::BlitFramebuffer { ref from, ref to } => {
+                    let (attachment, mask) = match from.aspects {
+                        crate::TexelAspects::COLOR => {
+                            (glow::COLOR_ATTACHMENT0, glow::COLOR_BUFFER_BIT)
                         }
-                        super::TextureInner::Texture { raw, target } => {
-                            gl.framebuffer_texture_2d(
-                                glow::READ_FRAMEBUFFER,
-                                glow::COLOR_ATTACHMENT0,
-                                target,
-                                Some(raw),
-                                0,
-                            );
+                        crate::TexelAspects::DEPTH => {
+                            (glow::DEPTH_ATTACHMENT, glow::DEPTH_BUFFER_BIT)
                         }
-                    }
-
-                    gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(framebuf_to));
-                    match to.inner {
-                        super::TextureInner::Renderbuffer { raw } => {
-                            gl.framebuffer_renderbuffer(
-                                glow::DRAW_FRAMEBUFFER,
-                                glow::COLOR_ATTACHMENT0, // NOTE: Assuming color attachment
-                                glow::RENDERBUFFER,
-                                Some(raw),
-                            );
+                        crate::TexelAspects::STENCIL => {
+                            (glow::STENCIL_ATTACHMENT, glow::STENCIL_BUFFER_BIT)
                         }
-                        super::TextureInner::Texture { raw, target } => {
-                            gl.framebuffer_texture_2d(
-                                glow::DRAW_FRAMEBUFFER,
-                                glow::COLOR_ATTACHMENT0,
-                                target,
-                                Some(raw),
-                                0,
-                            );
+                        _ => (
+                            glow::DEPTH_STENCIL_ATTACHMENT,
+                            glow::DEPTH_BUFFER_BIT | glow::STENCIL_BUFFER_BIT,
+                        ),
+                    };
+                    // The execution framebuffer may have other attachments to draw into
+                    let framebufs = [(); 2].map(|()| gl.create_framebuffer().unwrap());
+                    for (target, framebuf, view) in [
+                        (glow::READ_FRAMEBUFFER, framebufs[0], from),
+                        (glow::DRAW_FRAMEBUFFER, framebufs[1], to),
+                    ] {
+                        gl.bind_framebuffer(target, Some(framebuf));
+                        match view.inner {
+                            super::TextureInner::Renderbuffer { raw } => {
+                                gl.framebuffer_renderbuffer(
+                                    target,
+                                    attachment,
+                                    glow::RENDERBUFFER,
+                                    Some(raw),
+                                );
+                            }
+                            super::TextureInner::Texture {
+                                raw,
+                                target: tex_target,
+                            } => {
+                                gl.framebuffer_texture_2d(
+                                    target,
+                                    attachment,
+                                    tex_target,
+                                    Some(raw),
+                                    0,
+                                );
+                            }
                         }
                     }
-
                     debug_assert_eq!(
                         gl.check_framebuffer_status(glow::DRAW_FRAMEBUFFER),
                         glow::FRAMEBUFFER_COMPLETE,
@@ -974,15 +1294,13 @@ impl super::Command {
                         0,
                         to.target_size[0] as _,
                         to.target_size[1] as _,
-                        glow::COLOR_BUFFER_BIT, // NOTE: Assuming color
+                        mask,
                         glow::NEAREST,
                     );
-
-                    gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
-                    gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, None);
-
-                    gl.delete_framebuffer(framebuf_from);
-                    gl.delete_framebuffer(framebuf_to);
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(ec.framebuf));
+                    for framebuf in framebufs {
+                        gl.delete_framebuffer(framebuf);
+                    }
                 }
 
                 Self::BindAttachment {
@@ -1016,6 +1334,13 @@ impl super::Command {
                 Self::SetDrawColorBuffers(count) => {
                     gl.draw_buffers(&COLOR_ATTACHMENTS[..count as usize]);
                 }
+                Self::SetFramebufferSrgb(enable) => {
+                    if enable {
+                        gl.enable(glow::FRAMEBUFFER_SRGB);
+                    } else {
+                        gl.disable(glow::FRAMEBUFFER_SRGB);
+                    }
+                }
                 Self::SetAllColorTargets(blend, write_mask) => {
                     if let Some(blend_state) = blend {
                         gl.enable(glow::BLEND);
@@ -1058,50 +1383,24 @@ impl super::Command {
                         write_mask.contains(crate::ColorWrites::ALPHA),
                     );
                 }
-                Self::ClearColor {
-                    draw_buffer,
-                    color,
-                    ty,
-                } => match ty {
-                    super::ColorType::Float => {
-                        gl.clear_buffer_f32_slice(
-                            glow::COLOR,
-                            draw_buffer,
-                            &match color {
-                                crate::TextureColor::TransparentBlack => [0.0; 4],
-                                crate::TextureColor::OpaqueBlack => [0.0, 0.0, 0.0, 1.0],
-                                crate::TextureColor::White => [1.0; 4],
-                            },
-                        );
+                Self::ClearColor { draw_buffer, color } => match color {
+                    crate::ClearColor::Float(value) => {
+                        gl.clear_buffer_f32_slice(glow::COLOR, draw_buffer, &value);
                     }
-                    super::ColorType::Uint => {
-                        gl.clear_buffer_u32_slice(
-                            glow::COLOR,
-                            draw_buffer,
-                            &match color {
-                                crate::TextureColor::TransparentBlack => [0; 4],
-                                crate::TextureColor::OpaqueBlack => [0, 0, 0, !0],
-                                crate::TextureColor::White => [!0; 4],
-                            },
-                        );
-                    }
-                    super::ColorType::Sint => {
-                        gl.clear_buffer_i32_slice(
-                            glow::COLOR,
-                            draw_buffer,
-                            &match color {
-                                crate::TextureColor::TransparentBlack => [0; 4],
-                                crate::TextureColor::OpaqueBlack => [0, 0, 0, !0],
-                                crate::TextureColor::White => [!0; 4],
-                            },
-                        );
+                    crate::ClearColor::Uint(value) => {
+                        gl.clear_buffer_u32_slice(glow::COLOR, draw_buffer, &value);
                     }
                 },
                 Self::ClearDepthStencil { depth, stencil } => match (depth, stencil) {
+                    // The depth mask of the last pipeline applies to the clears
                     (Some(d), Some(s)) => {
+                        gl.depth_mask(true);
                         gl.clear_buffer_depth_stencil(glow::DEPTH_STENCIL, 0, d, s as i32)
                     }
-                    (Some(d), None) => gl.clear_buffer_f32_slice(glow::DEPTH, 0, &[d]),
+                    (Some(d), None) => {
+                        gl.depth_mask(true);
+                        gl.clear_buffer_f32_slice(glow::DEPTH, 0, &[d])
+                    }
                     (None, Some(s)) => gl.clear_buffer_i32_slice(glow::STENCIL, 0, &[s as i32]),
                     (None, None) => (),
                 },
@@ -1130,8 +1429,23 @@ impl super::Command {
                     write_mask: _,
                     //ops: crate::StencilOps,
                 } => unimplemented!(),
-                //SetDepth(DepthState),
-                //SetDepthBias(wgt::DepthBiasState),
+                Self::SetDepth(state) => match state {
+                    Some(ds) => {
+                        gl.enable(glow::DEPTH_TEST);
+                        gl.depth_func(ds.function);
+                        gl.depth_mask(ds.write);
+                        if ds.bias.constant != 0 || ds.bias.slope_scale != 0.0 {
+                            gl.enable(glow::POLYGON_OFFSET_FILL);
+                            gl.polygon_offset(ds.bias.slope_scale, ds.bias.constant as f32);
+                        } else {
+                            gl.disable(glow::POLYGON_OFFSET_FILL);
+                        }
+                    }
+                    None => {
+                        gl.disable(glow::DEPTH_TEST);
+                        gl.disable(glow::POLYGON_OFFSET_FILL);
+                    }
+                },
                 //ConfigureDepthStencil(crate::FormatAspects),
                 Self::SetProgram(raw_program) => {
                     gl.use_program(Some(raw_program));
@@ -1139,7 +1453,19 @@ impl super::Command {
                 Self::UnsetProgram => {
                     gl.use_program(None);
                 }
-                //SetPrimitive(PrimitiveState),
+                Self::SetPrimitive {
+                    front_face,
+                    cull_face,
+                } => {
+                    gl.front_face(front_face);
+                    match cull_face {
+                        Some(face) => {
+                            gl.enable(glow::CULL_FACE);
+                            gl.cull_face(face);
+                        }
+                        None => gl.disable(glow::CULL_FACE),
+                    }
+                }
                 Self::SetBlendConstant([r, g, b, a]) => gl.blend_color(r, g, b, a),
                 Self::SetColorTarget {
                     draw_buffer_index: _,
@@ -1231,6 +1557,44 @@ impl super::Command {
     }
 }
 
+/// Attach the mip level and the layer of a texture as the first color attachment.
+unsafe fn attach_texture_part(gl: &glow::Context, target: u32, part: &super::TexturePart) {
+    use glow::HasContext as _;
+    let mip_level = part.mip_level as i32;
+    unsafe {
+        match part.target {
+            glow::TEXTURE_2D_ARRAY => gl.framebuffer_texture_layer(
+                target,
+                glow::COLOR_ATTACHMENT0,
+                Some(part.raw),
+                mip_level,
+                part.array_layer as i32,
+            ),
+            glow::TEXTURE_3D => gl.framebuffer_texture_layer(
+                target,
+                glow::COLOR_ATTACHMENT0,
+                Some(part.raw),
+                mip_level,
+                part.origin[2] as i32,
+            ),
+            glow::TEXTURE_CUBE_MAP => gl.framebuffer_texture_2d(
+                target,
+                glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_CUBE_MAP_POSITIVE_X + part.array_layer,
+                Some(part.raw),
+                mip_level,
+            ),
+            other => gl.framebuffer_texture_2d(
+                target,
+                glow::COLOR_ATTACHMENT0,
+                other,
+                Some(part.raw),
+                mip_level,
+            ),
+        }
+    }
+}
+
 fn map_index_type(ty: crate::IndexType) -> u32 {
     match ty {
         crate::IndexType::U16 => glow::UNSIGNED_SHORT,
@@ -214,6 +214,10 @@ pub(super) struct ContextInner {
     egl: EglContext,
 }
 
+// The context is made current by the thread holding the lock,
+// and released before the lock is, so it can move between the threads.
+unsafe impl Send for ContextInner {}
+
 /// Holds the GBM device and library handle (if GBM-backed display is used).
 struct GbmState {
     device: *mut ffi::c_void,
@@ -227,6 +231,10 @@ struct GbmState {
     _lib: libloading::Library,
 }
 
+// The GBM device is only used with the context lock held.
+unsafe impl Send for GbmState {}
+unsafe impl Sync for GbmState {}
+
 impl Drop for GbmState {
     fn drop(&mut self) {
         unsafe {
@@ -377,6 +385,7 @@ impl super::Context {
             let (glow, capabilities, toggles, device_information, limits) =
                 egl_context.load_functions(&desc);
             egl_context.unmake_current();
+            let reporter = super::init_reporter(&glow, &device_information);
 
             Ok(Self {
                 platform: PlatformContext {
@@ -391,6 +400,10 @@ impl super::Context {
                 toggles,
                 limits,
                 device_information,
+                deferred_destructions: Default::default(),
+                dummy_resources: Default::default(),
+                reporter,
+                sampler_cache: Default::default(),
             })
         }
     }
@@ -508,16 +521,31 @@ impl super::Context {
         } else {
             crate::AlphaMode::Ignored
         };
-        let format = match config.color_space {
-            crate::ColorSpace::Linear => crate::TextureFormat::Rgba8UnormSrgb,
-            crate::ColorSpace::Srgb => crate::TextureFormat::Rgba8Unorm,
-        };
         let swap_interval = match config.display_sync {
             crate::DisplaySync::Block => 1,
             crate::DisplaySync::Recent | crate::DisplaySync::Tear => 0,
         };
 
         let inner = self.platform.inner.lock().unwrap();
+        let format = match config.color_space {
+            crate::ColorSpace::Linear => match inner.egl.srgb_kind {
+                SrgbFrameBufferKind::None => {
+                    log::warn!("sRGB surfaces are not supported, the output needs manual encoding");
+                    crate::TextureFormat::Rgba8Unorm
+                }
+                SrgbFrameBufferKind::Core | SrgbFrameBufferKind::Khr => {
+                    crate::TextureFormat::Rgba8UnormSrgb
+                }
+            },
+            crate::ColorSpace::Srgb => crate::TextureFormat::Rgba8Unorm,
+        };
+        self.reporter.set_surface(crate::SurfaceReport {
+            width: config.size.width,
+            height: config.size.height,
+            format: format!("{format:?}"),
+            alpha: format!("{alpha:?}"),
+            present_mode: format!("swap interval {swap_interval}"),
+        });
 
         // Try GBM-backed DMA-BUF path if available
         if let (Some(gbm), Some(dmabuf_fn), Some(egl1_5)) = (
@@ -764,12 +792,10 @@ impl super::Context {
                 egl::SINGLE_BUFFER
             },
         ];
-        match inner.egl.srgb_kind {
-            SrgbFrameBufferKind::None => {}
-            SrgbFrameBufferKind::Core | SrgbFrameBufferKind::Khr => {
-                attributes.push(egl::GL_COLORSPACE);
-                attributes.push(egl::GL_COLORSPACE_SRGB);
-            }
+        // Only sRGB frames get encoded by the presentation blit
+        if format.is_srgb() {
+            attributes.push(egl::GL_COLORSPACE);
+            attributes.push(egl::GL_COLORSPACE_SRGB);
         }
         attributes.push(egl::ATTRIB_NONE as i32);
 
@@ -887,12 +913,9 @@ impl super::Context {
         };
 
         let mut attributes = vec![egl::RENDER_BUFFER, egl::SINGLE_BUFFER];
-        match pres_egl_context.srgb_kind {
-            SrgbFrameBufferKind::None => {}
-            SrgbFrameBufferKind::Core | SrgbFrameBufferKind::Khr => {
-                attributes.push(egl::GL_COLORSPACE);
-                attributes.push(egl::GL_COLORSPACE_SRGB);
-            }
+        if format.is_srgb() {
+            attributes.push(egl::GL_COLORSPACE);
+            attributes.push(egl::GL_COLORSPACE_SRGB);
         }
         attributes.push(egl::ATTRIB_NONE as i32);
 
@@ -1019,7 +1042,7 @@ impl super::Context {
 }
 
 impl PlatformContext {
-    pub(super) fn present(&self, frame: PlatformFrame) {
+    pub(super) fn present(&self, frame: PlatformFrame, framebuffer_srgb: bool) {
         match frame.present_mode {
             PresentMode::Direct(sc) => {
                 let inner = self.inner.lock().unwrap();
@@ -1040,7 +1063,13 @@ impl PlatformContext {
                     .unwrap();
 
                 unsafe {
-                    super::present_blit(&inner.glow, frame.framebuf, sc.extent);
+                    super::present_blit(
+                        &inner.glow,
+                        frame.framebuf,
+                        sc.extent,
+                        sc.info.format,
+                        framebuffer_srgb,
+                    );
                 }
 
                 inner
@@ -1081,7 +1110,13 @@ impl PlatformContext {
                     .unwrap();
 
                 unsafe {
-                    super::present_blit(&pres.glow, pres.source_framebuf, sc.extent);
+                    super::present_blit(
+                        &pres.glow,
+                        pres.source_framebuf,
+                        sc.extent,
+                        sc.info.format,
+                        framebuffer_srgb,
+                    );
                 }
 
                 pres.egl
@@ -1124,7 +1159,7 @@ impl super::Surface {
                     raw: self.offscreen_texture,
                     target: glow::TEXTURE_2D,
                 },
-                target_size: [extent.width as u16, extent.height as u16],
+                size: extent,
                 format: info.format,
             },
         }
@@ -1530,11 +1565,21 @@ impl EglContext {
                 driver_info: version,
             };
 
-            let mut capabilities = super::Capabilities::empty();
+            // ETC2 is a part of the core GLES 3.0
+            let mut capabilities = super::Capabilities::texture_compression(|names| {
+                names.iter().any(|&name| extensions.contains(name))
+            });
+            if gl.version().is_embedded {
+                capabilities |= super::Capabilities::TEXTURE_COMPRESSION_ETC2;
+            }
             capabilities.set(
                 super::Capabilities::BUFFER_STORAGE,
                 extensions.contains("GL_EXT_buffer_storage"),
             );
+            capabilities.set(
+                super::Capabilities::FRAMEBUFFER_SRGB,
+                !gl.version().is_embedded || extensions.contains("GL_EXT_sRGB_write_control"),
+            );
             capabilities.set(
                 super::Capabilities::DRAW_BUFFERS_INDEXED,
                 if gl.version().is_embedded {
@@ -1563,6 +1608,22 @@ impl EglContext {
                 uniform_buffer_alignment: gl
                     .get_parameter_i32(glow::UNIFORM_BUFFER_OFFSET_ALIGNMENT)
                     as u32,
+                // The query fails without storage buffers, leaving zero
+                storage_buffer_alignment: gl
+                    .get_parameter_i32(glow::SHADER_STORAGE_BUFFER_OFFSET_ALIGNMENT)
+                    as u32,
+                max_uniform_block_size: gl.get_parameter_i32(glow::MAX_UNIFORM_BLOCK_SIZE) as u32,
+                // The query fails without compute support, leaving the minimum from the spec
+                max_compute_work_group_count: [0, 1, 2].map(|i| {
+                    match gl.get_parameter_indexed_i32(glow::MAX_COMPUTE_WORK_GROUP_COUNT, i) {
+                        0 => 65535,
+                        count => count as u32,
+                    }
+                }),
+                max_texture_size: gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE) as u32,
+                max_3d_texture_size: gl.get_parameter_i32(glow::MAX_3D_TEXTURE_SIZE) as u32,
+                max_array_texture_layers: gl.get_parameter_i32(glow::MAX_ARRAY_TEXTURE_LAYERS)
+                    as u32,
             };
             (gl, capabilities, toggles, device_information, limits)
         }
@@ -5,7 +5,7 @@ mod pipeline;
 mod platform;
 mod resource;
 
-use std::{marker::PhantomData, mem, ops::Range};
+use std::{marker::PhantomData, ops::Range};
 
 type BindTarget = u32;
 const DEBUG_ID: u32 = 0;
@@ -13,16 +13,79 @@ const MAX_TIMEOUT: u64 = 1_000_000_000; // MAX_CLIENT_WAIT_TIMEOUT_WEBGL;
 const MAX_QUERIES: usize = crate::limits::PASS_COUNT + 1;
 
 bitflags::bitflags! {
+    #[derive(Clone, Copy, Debug)]
     struct Capabilities: u32 {
         const BUFFER_STORAGE = 1 << 0;
         const DRAW_BUFFERS_INDEXED = 1 << 1;
         const DISJOINT_TIMER_QUERY = 1 << 2;
+        const TEXTURE_COMPRESSION_S3TC = 1 << 3;
+        const TEXTURE_COMPRESSION_RGTC = 1 << 4;
+        const TEXTURE_COMPRESSION_BPTC = 1 << 5;
+        const TEXTURE_COMPRESSION_ETC2 = 1 << 6;
+        const TEXTURE_COMPRESSION_ASTC = 1 << 7;
+        /// The sRGB encoding of the render targets can be toggled.
+        /// Without it, GLES always encodes into sRGB attachments.
+        const FRAMEBUFFER_SRGB = 1 << 8;
+    }
+}
+
+impl Capabilities {
+    /// Detect the supported texture compression, given a predicate
+    /// telling if any of the extension names is supported.
+    fn texture_compression(has_extension: impl Fn(&[&str]) -> bool) -> Self {
+        let mut caps = Self::empty();
+        caps.set(
+            Self::TEXTURE_COMPRESSION_S3TC,
+            has_extension(&[
+                "GL_EXT_texture_compression_s3tc",
+                "WEBGL_compressed_texture_s3tc",
+            ]),
+        );
+        caps.set(
+            Self::TEXTURE_COMPRESSION_RGTC,
+            has_extension(&[
+                "GL_ARB_texture_compression_rgtc",
+                "GL_EXT_texture_compression_rgtc",
+                "EXT_texture_compression_rgtc",
+            ]),
+        );
+        caps.set(
+            Self::TEXTURE_COMPRESSION_BPTC,
+            has_extension(&[
+                "GL_ARB_texture_compression_bptc",
+                "GL_EXT_texture_compression_bptc",
+                "EXT_texture_compression_bptc",
+            ]),
+        );
+        caps.set(
+            Self::TEXTURE_COMPRESSION_ETC2,
+            has_extension(&[
+                "GL_ARB_ES3_compatibility",
+                "GL_OES_compressed_ETC2_RGB8_texture",
+                "WEBGL_compressed_texture_etc",
+            ]),
+        );
+        caps.set(
+            Self::TEXTURE_COMPRESSION_ASTC,
+            has_extension(&[
+                "GL_KHR_texture_compression_astc_ldr",
+                "WEBGL_compressed_texture_astc",
+            ]),
+        );
+        caps
     }
 }
 
 #[derive(Clone, Debug)]
 struct Limits {
     uniform_buffer_alignment: u32,
+    /// Zero without storage buffers.
+    storage_buffer_alignment: u32,
+    max_uniform_block_size: u32,
+    max_compute_work_group_count: [u32; 3],
+    max_texture_size: u32,
+    max_3d_texture_size: u32,
+    max_array_texture_layers: u32,
 }
 
 #[derive(Debug, Default)]
@@ -37,6 +100,10 @@ pub struct Context {
     toggles: Toggles,
     limits: Limits,
     device_information: crate::DeviceInformation,
+    pub(crate) deferred_destructions: crate::deferred::DeferredDestructions,
+    pub(crate) dummy_resources: crate::dummy::DummyResources,
+    pub(crate) reporter: crate::report::Reporter,
+    pub(crate) sampler_cache: crate::cache::SamplerCache,
 }
 
 pub struct Surface {
@@ -63,6 +130,11 @@ impl Buffer {
     pub fn size(&self) -> u64 {
         self.size
     }
+
+    /// GL buffers have no GPU addresses.
+    pub fn device_address(&self) -> u64 {
+        0
+    }
 }
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq)]
@@ -90,18 +162,30 @@ impl TextureInner {
 #[derive(Clone, Copy, Debug, Hash, PartialEq)]
 pub struct Texture {
     inner: TextureInner,
-    target_size: [u16; 2],
+    size: crate::Extent,
     format: crate::TextureFormat,
 }
 
+impl Texture {
+    /// Size of the base mip level.
+    pub fn size(&self) -> crate::Extent {
+        self.size
+    }
+
+    pub fn format(&self) -> crate::TextureFormat {
+        self.format
+    }
+}
+
 #[derive(Clone, Copy, Debug, Hash, PartialEq)]
 pub struct TextureView {
     inner: TextureInner,
     target_size: [u16; 2],
     aspects: crate::TexelAspects,
+    format: crate::TextureFormat,
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Sampler {
     raw: glow::Sampler,
 }
@@ -113,6 +197,8 @@ type SlotList = Vec<u32>;
 
 struct ShaderDataMapping {
     targets: Box<[SlotList]>,
+    /// Bitmask: bit N is set if binding N is a dynamic buffer.
+    dynamic_mask: u64,
 }
 
 struct VertexAttributeInfo {
@@ -132,6 +218,7 @@ struct PipelineInner {
 pub struct ComputePipeline {
     inner: PipelineInner,
     wg_size: [u32; 3],
+    max_group_count: [u32; 3],
 }
 
 #[hidden_trait::expose]
@@ -139,11 +226,25 @@ impl crate::traits::ComputePipelineBase for ComputePipeline {
     fn get_workgroup_size(&self) -> [u32; 3] {
         self.wg_size
     }
+    fn get_max_group_count(&self) -> [u32; 3] {
+        self.max_group_count
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct DepthState {
+    function: u32,
+    write: bool,
+    bias: crate::DepthBiasState,
 }
 
 pub struct RenderPipeline {
     inner: PipelineInner,
     topology: crate::PrimitiveTopology,
+    front_face: u32,
+    cull_face: Option<u32>,
+    depth: Option<DepthState>,
+    depth_stencil_writes: crate::TexelAspects,
 }
 
 #[derive(Debug)]
@@ -160,8 +261,12 @@ impl Frame {
     pub fn texture_view(&self) -> TextureView {
         TextureView {
             inner: self.texture.inner,
-            target_size: self.texture.target_size,
+            target_size: [
+                self.texture.size.width as u16,
+                self.texture.size.height as u16,
+            ],
             aspects: crate::TexelAspects::COLOR,
+            format: self.texture.format,
         }
     }
 }
@@ -172,6 +277,8 @@ struct BufferPart {
     offset: u64,
     data: *mut u8,
 }
+// Same as the `Buffer` it points into
+unsafe impl Send for BufferPart {}
 impl From<crate::BufferPiece> for BufferPart {
     fn from(piece: crate::BufferPiece) -> Self {
         Self {
@@ -215,14 +322,6 @@ struct ImageBinding {
     format: u32,
 }
 
-#[derive(Clone, Copy, Debug)]
-#[allow(unused)]
-enum ColorType {
-    Float,
-    Uint,
-    Sint,
-}
-
 #[derive(Debug)]
 #[allow(unused)]
 enum Command {
@@ -269,6 +368,13 @@ enum Command {
         dst: TexturePart,
         size: crate::Extent,
     },
+    BlitTexture {
+        src: TexturePart,
+        src_size: crate::Extent,
+        dst: TexturePart,
+        dst_size: crate::Extent,
+        filter: u32,
+    },
     CopyBufferToTexture {
         src: BufferPart,
         dst: TexturePart,
@@ -292,12 +398,12 @@ enum Command {
     },
     InvalidateAttachment(u32),
     SetDrawColorBuffers(u8),
+    SetFramebufferSrgb(bool),
     SetAllColorTargets(Option<crate::BlendState>, crate::ColorWrites),
     SetSingleColorTarget(u32, Option<crate::BlendState>, crate::ColorWrites),
     ClearColor {
         draw_buffer: u32,
-        color: crate::TextureColor,
-        ty: ColorType,
+        color: crate::ClearColor,
     },
     ClearDepthStencil {
         depth: Option<f32>,
@@ -317,12 +423,14 @@ enum Command {
         write_mask: u32,
         //ops: crate::StencilOps,
     },
-    //SetDepth(DepthState),
-    //SetDepthBias(wgt::DepthBiasState),
+    SetDepth(Option<DepthState>),
     //ConfigureDepthStencil(crate::FormatAspects),
     SetProgram(glow::Program),
     UnsetProgram,
-    //SetPrimitive(PrimitiveState),
+    SetPrimitive {
+        front_face: u32,
+        cull_face: Option<u32>,
+    },
     SetBlendConstant([f32; 4]),
     SetColorTarget {
         draw_buffer_index: Option<u32>,
@@ -376,7 +484,7 @@ enum Command {
 }
 
 struct TimingData {
-    pass_names: Vec<String>,
+    pass_names: crate::PassNames,
     queries: Box<[glow::Query]>,
 }
 
@@ -388,8 +496,19 @@ pub struct CommandEncoder {
     needs_scopes: bool,
     present_frames: Vec<platform::PlatformFrame>,
     limits: Limits,
+    capabilities: Capabilities,
     timing_datas: Option<Box<[TimingData]>>,
     timings: crate::Timings,
+    timings_recording_index: Option<u64>,
+    recording_index: u64,
+    binding_stats: crate::BindingStats,
+    recording: crate::RecordingState,
+    invalidate_attachments: Vec<u32>,
+    /// Multisampled targets of the current pass, and their resolve targets.
+    resolve_attachments: Vec<(TextureView, TextureView)>,
+    /// Resources bound for `Nullable` bindings of `None`.
+    dummies: Option<crate::dummy::DummySet>,
+    peak_retained_bytes: usize,
 }
 
 enum PassKind {
@@ -401,11 +520,15 @@ enum PassKind {
 pub struct PassEncoder<'a, P> {
     commands: &'a mut Vec<Command>,
     plain_data: &'a mut Vec<u8>,
+    binding_stats: &'a mut crate::BindingStats,
     kind: PassKind,
-    invalidate_attachments: Vec<u32>,
+    invalidate_attachments: &'a mut Vec<u32>,
+    resolve_attachments: &'a mut Vec<(TextureView, TextureView)>,
     pipeline: PhantomData<P>,
     limits: &'a Limits,
     has_scope: bool,
+    depth_stencil_read_only: crate::TexelAspects,
+    dummies: Option<crate::dummy::DummySet>,
 }
 
 pub type ComputeCommandEncoder<'a> = PassEncoder<'a, ComputePipeline>;
@@ -414,10 +537,14 @@ pub type RenderCommandEncoder<'a> = PassEncoder<'a, RenderPipeline>;
 pub struct PipelineEncoder<'a> {
     commands: &'a mut Vec<Command>,
     plain_data: &'a mut Vec<u8>,
+    binding_stats: &'a mut crate::BindingStats,
     group_mappings: &'a [ShaderDataMapping],
     topology: u32,
     limits: &'a Limits,
     vertex_attributes: &'a [VertexAttributeInfo],
+    /// Binding indices and base pieces of the dynamic buffers, per group.
+    dynamic_buffers: Vec<Vec<(u32, crate::BufferPiece)>>,
+    dummies: Option<crate::dummy::DummySet>,
 }
 
 impl Drop for PipelineEncoder<'_> {
@@ -435,29 +562,53 @@ pub struct PipelineContext<'a> {
     plain_data: &'a mut Vec<u8>,
     targets: &'a [SlotList],
     limits: &'a Limits,
+    dynamic_mask: u64,
+    dynamic_buffers: &'a mut Vec<(u32, crate::BufferPiece)>,
+    dummies: Option<crate::dummy::DummySet>,
 }
 
 #[derive(Clone, Debug)]
 pub struct SyncPoint {
     fence: glow::Fence,
 }
+// needed for the deferred destruction, fences are only accessed under the GL lock
+unsafe impl Send for SyncPoint {}
+unsafe impl Sync for SyncPoint {}
 //TODO: destructor
 
-struct ExecutionContext {
+struct ExecutionContext<'a> {
     framebuf: glow::Framebuffer,
     plain_buffer: glow::Buffer,
-    string_data: Box<[u8]>,
+    string_data: &'a [u8],
 }
 
 impl Context {
     pub fn capabilities(&self) -> crate::Capabilities {
         crate::Capabilities {
             binding_array: false,
+            max_binding_array_size: 0,
+            max_plain_data_size: self.limits.max_uniform_block_size,
             ray_query: crate::ShaderVisibility::empty(),
             sample_count_mask: 0x1 | 0x4, //TODO: accurate info
             dual_source_blending: false,
             shader_float16: false,
             cooperative_matrix: crate::CooperativeMatrix::default(),
+            acceleration_structure_motion: false,
+            reusable_command_encoders: true,
+            robustness: false,
+            // Blitting picks one of the depth samples
+            depth_resolve: true,
+            depth_resolve_min_max: false,
+            // GLES has no logic operations, unlike desktop GL
+            logic_op: false,
+            sparse_tile_size: 0,
+            buffer_device_address: false,
+            max_view_count: 0,
+            // Conditional rendering in GL is driven by queries, not buffers
+            conditional_rendering: false,
+            dynamic_offset_alignment: self.limits.storage_buffer_alignment,
+            // `TEXTURE_LOD_BIAS` is not a part of GLES
+            max_sampler_lod_bias: 0.0,
         }
     }
 
@@ -465,6 +616,42 @@ impl Context {
         &self.device_information
     }
 
+    /// Return the usages supported by the textures of the given format,
+    /// which are empty if the format isn't supported at all.
+    pub fn supported_texture_usage(&self, format: crate::TextureFormat) -> crate::TextureUsage {
+        use crate::TextureFormat as Tf;
+        let required = match format {
+            Tf::Bc1Unorm
+            | Tf::Bc1UnormSrgb
+            | Tf::Bc2Unorm
+            | Tf::Bc2UnormSrgb
+            | Tf::Bc3Unorm
+            | Tf::Bc3UnormSrgb => Capabilities::TEXTURE_COMPRESSION_S3TC,
+            Tf::Bc4Unorm | Tf::Bc4Snorm | Tf::Bc5Unorm | Tf::Bc5Snorm => {
+                Capabilities::TEXTURE_COMPRESSION_RGTC
+            }
+            Tf::Bc6hUfloat | Tf::Bc6hFloat | Tf::Bc7Unorm | Tf::Bc7UnormSrgb => {
+                Capabilities::TEXTURE_COMPRESSION_BPTC
+            }
+            Tf::Etc2Rgb8Unorm
+            | Tf::Etc2Rgb8UnormSrgb
+            | Tf::Etc2Rgba8Unorm
+            | Tf::Etc2Rgba8UnormSrgb => Capabilities::TEXTURE_COMPRESSION_ETC2,
+            Tf::Astc4x4Unorm | Tf::Astc4x4UnormSrgb => Capabilities::TEXTURE_COMPRESSION_ASTC,
+            _ => Capabilities::empty(),
+        };
+        if required.is_empty() {
+            //TODO: query the renderability of the uncompressed formats
+            crate::TextureUsage::all()
+                - crate::TextureUsage::HOST_WRITE
+                - crate::TextureUsage::SPARSE
+        } else if self.capabilities.contains(required) {
+            crate::TextureUsage::COPY | crate::TextureUsage::RESOURCE
+        } else {
+            crate::TextureUsage::empty()
+        }
+    }
+
     pub fn enumerate() -> Result<Vec<crate::DeviceReport>, crate::NotSupportedError> {
         let context = unsafe { Self::init(crate::ContextDesc::default())? };
         Ok(context.enumerate_devices())
@@ -484,6 +671,24 @@ impl Context {
     pub fn memory_stats(&self) -> crate::MemoryStats {
         crate::MemoryStats::default()
     }
+
+    /// Release the memory that is no longer needed.
+    /// Shader data is bound directly to the GL state, so there are no pools to trim.
+    pub fn trim(&self) {}
+
+    /// GLES has no sparse textures, see `Capabilities::sparse_tile_size`.
+    pub fn sparse_tile_extent(&self, _format: crate::TextureFormat) -> Option<crate::Extent> {
+        None
+    }
+
+    pub fn update_texture_residency(
+        &self,
+        _texture: Texture,
+        _regions: &[crate::TextureRegion],
+        _residency: crate::Residency,
+    ) -> Result<(), crate::ResourceError> {
+        panic!("Sparse textures are not supported")
+    }
 }
 
 #[hidden_trait::expose]
@@ -501,7 +706,7 @@ impl crate::traits::CommandDevice for Context {
             // in submit() as opposed to start().
             for _ in 0..desc.buffer_count + 1 {
                 array.push(TimingData {
-                    pass_names: Vec::new(),
+                    pass_names: Default::default(),
                     queries: (0..MAX_QUERIES)
                         .map(|_| unsafe { gl.create_query().unwrap() })
                         .collect(),
@@ -519,8 +724,17 @@ impl crate::traits::CommandDevice for Context {
             needs_scopes: self.toggles.scoping,
             present_frames: Vec::new(),
             limits: self.limits.clone(),
+            capabilities: self.capabilities,
             timing_datas,
             timings: Default::default(),
+            timings_recording_index: None,
+            recording_index: 0,
+            binding_stats: Default::default(),
+            recording: Default::default(),
+            invalidate_attachments: Vec::new(),
+            resolve_attachments: Vec::new(),
+            dummies: self.dummy_resources.for_encoder(self),
+            peak_retained_bytes: 0,
         }
     }
 
@@ -538,50 +752,36 @@ impl crate::traits::CommandDevice for Context {
     }
 
     fn submit(&self, encoder: &mut CommandEncoder) -> SyncPoint {
+        self.submit_batch(&mut [encoder])
+    }
+
+    fn submit_batch(&self, encoders: &mut [&mut CommandEncoder]) -> SyncPoint {
         use glow::HasContext as _;
+        assert!(!encoders.is_empty(), "Nothing to submit");
+        assert!(
+            encoders
+                .iter()
+                .filter(|e| !e.present_frames.is_empty())
+                .count()
+                <= 1,
+            "Only one encoder in a batch can present"
+        );
+        self.deferred_destructions.drain(self);
 
         let fence = {
             let gl = self.lock();
-            encoder.finish(&gl);
-
-            let push_group = !encoder.name.is_empty() && gl.supports_debug();
-            let ec = unsafe {
-                if push_group {
-                    gl.push_debug_group(glow::DEBUG_SOURCE_APPLICATION, DEBUG_ID, &encoder.name);
-                }
-                let framebuf = gl.create_framebuffer().unwrap();
-                gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuf));
-                let plain_buffer = gl.create_buffer().unwrap();
-                if !encoder.plain_data.is_empty() {
-                    log::trace!("Allocating plain data of size {}", encoder.plain_data.len());
-                    gl.bind_buffer(glow::UNIFORM_BUFFER, Some(plain_buffer));
-                    gl.buffer_data_u8_slice(
-                        glow::UNIFORM_BUFFER,
-                        &encoder.plain_data,
-                        glow::STATIC_DRAW,
-                    );
-                }
-                ExecutionContext {
-                    framebuf,
-                    plain_buffer,
-                    string_data: mem::take(&mut encoder.string_data).into_boxed_slice(),
-                }
-            };
-            for command in encoder.commands.iter() {
-                log::trace!("{:?}", command);
-                unsafe { command.execute(&gl, &ec) };
-            }
-            unsafe {
-                gl.delete_framebuffer(ec.framebuf);
-                gl.delete_buffer(ec.plain_buffer);
-                if push_group {
-                    gl.pop_debug_group();
-                }
-                gl.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0).unwrap()
+            for encoder in encoders.iter_mut() {
+                encoder.execute(&gl);
             }
+            unsafe { gl.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0).unwrap() }
         };
-        for frame in encoder.present_frames.drain(..) {
-            self.platform.present(frame);
+        for encoder in encoders.iter_mut() {
+            for frame in encoder.present_frames.drain(..) {
+                self.platform.present(
+                    frame,
+                    self.capabilities.contains(Capabilities::FRAMEBUFFER_SRGB),
+                );
+            }
         }
         SyncPoint { fence }
     }
@@ -600,8 +800,12 @@ impl crate::traits::CommandDevice for Context {
 
         let status =
             unsafe { gl.client_wait_sync(sp.fence, glow::SYNC_FLUSH_COMMANDS_BIT, timeout_ns_i32) };
+        drop(gl);
         match status {
-            glow::ALREADY_SIGNALED | glow::CONDITION_SATISFIED => Ok(true),
+            glow::ALREADY_SIGNALED | glow::CONDITION_SATISFIED => {
+                self.deferred_destructions.drain(self);
+                Ok(true)
+            }
             glow::TIMEOUT_EXPIRED => Ok(false),
             glow::WAIT_FAILED => Err(crate::DeviceError::DeviceLost),
             _ => Ok(false),
@@ -609,6 +813,38 @@ impl crate::traits::CommandDevice for Context {
     }
 }
 
+impl Drop for Context {
+    fn drop(&mut self) {
+        self.dummy_resources.destroy(self);
+        self.deferred_destructions.destroy_all(self);
+    }
+}
+
+fn init_reporter(
+    gl: &glow::Context,
+    device_information: &crate::DeviceInformation,
+) -> crate::report::Reporter {
+    use glow::HasContext as _;
+
+    let version = gl.version();
+    let mut extensions = gl
+        .supported_extensions()
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    extensions.sort();
+    crate::report::Reporter::new(crate::InitReport {
+        backend: if version.is_embedded { "GLES" } else { "GL" }.to_string(),
+        api_version: format!("{}.{}", version.major, version.minor),
+        device: device_information.clone(),
+        extensions,
+        features: Vec::new(),
+        // GL doesn't expose the memory heaps
+        memory_heaps: Vec::new(),
+        surface: None,
+    })
+}
+
 // Align the size up to 16 bytes, as expected by GL.
 fn round_up_uniform_size(size: u32) -> u32 {
     if size & 0xF != 0 {
@@ -669,6 +905,12 @@ fn describe_texture_format(format: crate::TextureFormat) -> FormatInfo {
         Tf::Bc6hFloat => (glow::COMPRESSED_RGB_BPTC_SIGNED_FLOAT, glow::RGB, 0),
         Tf::Bc7Unorm => (glow::COMPRESSED_RGBA_BPTC_UNORM, glow::RGBA, 0),
         Tf::Bc7UnormSrgb => (glow::COMPRESSED_SRGB_ALPHA_BPTC_UNORM, glow::RGBA, 0),
+        Tf::Etc2Rgb8Unorm => (glow::COMPRESSED_RGB8_ETC2, glow::RGB, 0),
+        Tf::Etc2Rgb8UnormSrgb => (glow::COMPRESSED_SRGB8_ETC2, glow::RGB, 0),
+        Tf::Etc2Rgba8Unorm => (glow::COMPRESSED_RGBA8_ETC2_EAC, glow::RGBA, 0),
+        Tf::Etc2Rgba8UnormSrgb => (glow::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC, glow::RGBA, 0),
+        Tf::Astc4x4Unorm => (glow::COMPRESSED_RGBA_ASTC_4x4_KHR, glow::RGBA, 0),
+        Tf::Astc4x4UnormSrgb => (glow::COMPRESSED_SRGB8_ALPHA8_ASTC_4x4_KHR, glow::RGBA, 0),
         Tf::Rgb10a2Unorm => (
             glow::RGB10_A2,
             glow::RGBA,
@@ -702,11 +944,28 @@ fn map_compare_func(fun: crate::CompareFunction) -> u32 {
     }
 }
 
-unsafe fn present_blit(gl: &glow::Context, source: glow::Framebuffer, size: crate::Extent) {
+/// Blit the offscreen frame into the window surface.
+///
+/// The window surface is only created with the sRGB colorspace for sRGB frames,
+/// so the encoding is enabled to keep the sRGB values intact, if it can be controlled.
+unsafe fn present_blit(
+    gl: &glow::Context,
+    source: glow::Framebuffer,
+    size: crate::Extent,
+    format: crate::TextureFormat,
+    framebuffer_srgb: bool,
+) {
     unsafe {
         use glow::HasContext as _;
 
         gl.disable(glow::SCISSOR_TEST);
+        if framebuffer_srgb {
+            if format.is_srgb() {
+                gl.enable(glow::FRAMEBUFFER_SRGB);
+            } else {
+                gl.disable(glow::FRAMEBUFFER_SRGB);
+            }
+        }
         gl.color_mask(true, true, true, true);
         gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, None);
         gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(source));
@@ -45,6 +45,14 @@ impl super::Context {
                 .iter()
                 .map(|layout| super::ShaderDataMapping {
                     targets: vec![Vec::new(); layout.bindings.len()].into_boxed_slice(),
+                    dynamic_mask: layout
+                        .bindings
+                        .iter()
+                        .enumerate()
+                        .filter(|&(_, &(_, binding))| {
+                            binding == crate::ShaderBinding::DynamicBuffer
+                        })
+                        .fold(0, |mask, (index, _)| mask | (1 << index)),
                 })
                 .collect::<Box<[_]>>();
             if force_explicit_bindings {
@@ -71,7 +79,7 @@ impl super::Context {
                                 num_samplers += 1;
                                 num_samplers - 1
                             }
-                            crate::ShaderBinding::Buffer => {
+                            crate::ShaderBinding::Buffer | crate::ShaderBinding::DynamicBuffer => {
                                 num_buffers += 1;
                                 num_buffers - 1
                             }
@@ -197,8 +205,36 @@ impl super::Context {
                 baked_shaders.push((shader, reflection));
             }
 
+            // Programs can't be linked without a fragment shader,
+            // so the depth-only pipelines get an empty one.
+            let is_vertex_only = match *shaders {
+                [sf] => {
+                    sf.shader.module.entry_points[sf.entry_point_index()].stage
+                        == naga::ShaderStage::Vertex
+                }
+                _ => false,
+            };
+            let empty_fragment = if is_vertex_only {
+                let shader = gl.create_shader(glow::FRAGMENT_SHADER).unwrap();
+                let source = format!("#version {}\nvoid main() {{}}\n", naga_options.version);
+                gl.shader_source(shader, &source);
+                gl.compile_shader(shader);
+                assert!(
+                    gl.get_shader_compile_status(shader),
+                    "Compile: {}",
+                    gl.get_shader_info_log(shader)
+                );
+                gl.attach_shader(program, shader);
+                Some(shader)
+            } else {
+                None
+            };
+
             gl.link_program(program);
             log::info!("\tLinked program {:?}", program);
+            if let Some(shader) = empty_fragment {
+                gl.delete_shader(shader);
+            }
 
             let linked_ok = gl.get_program_link_status(program);
             let msg = gl.get_program_info_log(program);
@@ -248,7 +284,7 @@ impl super::Context {
                                     targets.push(slots[0] as u32);
                                 }
                             }
-                            crate::ShaderBinding::Buffer => {
+                            crate::ShaderBinding::Buffer | crate::ShaderBinding::DynamicBuffer => {
                                 if let Some(index) =
                                     gl.get_shader_storage_block_index(program, glsl_name)
                                 {
@@ -331,6 +367,21 @@ impl crate::traits::ShaderDevice for super::Context {
     type RenderPipeline = super::RenderPipeline;
 
     fn create_compute_pipeline(&self, desc: crate::ComputePipelineDesc) -> super::ComputePipeline {
+        let name = desc.name;
+        self.try_create_compute_pipeline(desc)
+            .unwrap_or_else(|e| panic!("Unable to create compute pipeline '{name}': {e}"))
+    }
+
+    fn try_create_compute_pipeline(
+        &self,
+        desc: crate::ComputePipelineDesc,
+    ) -> Result<super::ComputePipeline, crate::PipelineError> {
+        // Binding arrays are not supported
+        crate::ShaderDataLayout::check_binding_array_size(desc.data_layouts, 0)?;
+        crate::ShaderDataLayout::check_plain_data_size(
+            desc.data_layouts,
+            self.limits.max_uniform_block_size,
+        )?;
         let wg_size = desc.compute.shader.module.entry_points[desc.compute.entry_point_index()]
             .workgroup_size;
         let inner = unsafe {
@@ -342,7 +393,11 @@ impl crate::traits::ShaderDevice for super::Context {
                 glsl::WriterFlags::empty(),
             )
         };
-        super::ComputePipeline { inner, wg_size }
+        Ok(super::ComputePipeline {
+            inner,
+            wg_size,
+            max_group_count: self.limits.max_compute_work_group_count,
+        })
     }
 
     fn get_compute_pipeline_statistics(
@@ -366,6 +421,24 @@ impl crate::traits::ShaderDevice for super::Context {
     }
 
     fn create_render_pipeline(&self, desc: crate::RenderPipelineDesc) -> super::RenderPipeline {
+        let name = desc.name;
+        self.try_create_render_pipeline(desc)
+            .unwrap_or_else(|e| panic!("Unable to create render pipeline '{name}': {e}"))
+    }
+
+    fn try_create_render_pipeline(
+        &self,
+        desc: crate::RenderPipelineDesc,
+    ) -> Result<super::RenderPipeline, crate::PipelineError> {
+        crate::ShaderDataLayout::check_binding_array_size(desc.data_layouts, 0)?;
+        crate::ShaderDataLayout::check_plain_data_size(
+            desc.data_layouts,
+            self.limits.max_uniform_block_size,
+        )?;
+        desc.check_color_targets()?;
+        desc.check_logic_op(false)?;
+        // `OVR_multiview2` is not exposed by glow
+        desc.check_multiview(0)?;
         let extra_flags = if desc.primitive.topology == crate::PrimitiveTopology::PointList {
             glsl::WriterFlags::FORCE_POINT_SIZE
         } else {
@@ -399,10 +472,29 @@ impl crate::traits::ShaderDevice for super::Context {
             );
         }
 
-        super::RenderPipeline {
+        Ok(super::RenderPipeline {
             inner,
             topology: desc.primitive.topology,
-        }
+            // The vertex positions are Y-flipped by the shader,
+            // which reverses the winding seen by GL.
+            front_face: match desc.primitive.front_face {
+                crate::FrontFace::Ccw => glow::CW,
+                crate::FrontFace::Cw => glow::CCW,
+            },
+            cull_face: desc.primitive.cull_mode.map(|face| match face {
+                crate::Face::Front => glow::FRONT,
+                crate::Face::Back => glow::BACK,
+            }),
+            depth: desc.depth_stencil.as_ref().map(|ds| super::DepthState {
+                function: super::map_compare_func(ds.depth_compare),
+                write: ds.depth_write_enabled,
+                bias: ds.bias,
+            }),
+            depth_stencil_writes: desc
+                .depth_stencil
+                .as_ref()
+                .map_or(crate::TexelAspects::empty(), |ds| ds.write_aspects()),
+        })
     }
 
     fn destroy_render_pipeline(&self, pipeline: &mut super::RenderPipeline) {
@@ -16,6 +16,13 @@ impl super::Context {
         unimplemented!()
     }
 
+    pub fn get_top_level_motion_acceleration_structure_sizes(
+        &self,
+        _instance_count: u32,
+    ) -> Result<crate::AccelerationStructureSizes, crate::ResourceError> {
+        Err(crate::ResourceError::MotionNotSupported)
+    }
+
     pub fn create_acceleration_structure_instance_buffer(
         &self,
         _instances: &[crate::AccelerationStructureInstance],
@@ -23,6 +30,14 @@ impl super::Context {
     ) -> super::Buffer {
         unimplemented!()
     }
+
+    pub fn create_acceleration_structure_motion_instance_buffer(
+        &self,
+        _instances: &[crate::AccelerationStructureMotionInstance],
+        _bottom_level: &[super::AccelerationStructure],
+    ) -> Result<super::Buffer, crate::ResourceError> {
+        Err(crate::ResourceError::MotionNotSupported)
+    }
 }
 
 #[hidden_trait::expose]
@@ -34,6 +49,15 @@ impl crate::traits::ResourceDevice for super::Context {
     type AccelerationStructure = super::AccelerationStructure;
 
     fn create_buffer(&self, desc: crate::BufferDesc) -> super::Buffer {
+        let name = desc.name;
+        self.try_create_buffer(desc)
+            .unwrap_or_else(|e| panic!("Unable to create buffer '{name}': {e}"))
+    }
+
+    fn try_create_buffer(
+        &self,
+        desc: crate::BufferDesc,
+    ) -> Result<super::Buffer, crate::ResourceError> {
         let gl = self.lock();
 
         let raw = unsafe { gl.create_buffer() }.unwrap();
@@ -68,12 +92,22 @@ impl crate::traits::ResourceDevice for super::Context {
                 .contains(super::Capabilities::BUFFER_STORAGE)
             {
                 gl.buffer_storage(glow::ARRAY_BUFFER, desc.size as _, None, storage_flags);
+                if gl.get_error() == glow::OUT_OF_MEMORY {
+                    gl.bind_buffer(glow::ARRAY_BUFFER, None);
+                    gl.delete_buffer(raw);
+                    return Err(crate::ResourceError::OutOfDeviceMemory);
+                }
                 if map_flags != 0 {
                     data = gl.map_buffer_range(glow::ARRAY_BUFFER, 0, desc.size as _, map_flags);
                     assert!(!data.is_null());
                 }
             } else {
                 gl.buffer_data_size(glow::ARRAY_BUFFER, desc.size as _, usage);
+                if gl.get_error() == glow::OUT_OF_MEMORY {
+                    gl.bind_buffer(glow::ARRAY_BUFFER, None);
+                    gl.delete_buffer(raw);
+                    return Err(crate::ResourceError::OutOfDeviceMemory);
+                }
                 let data_vec = vec![0; desc.size as usize];
                 data = Vec::leak(data_vec).as_mut_ptr();
             }
@@ -87,11 +121,11 @@ impl crate::traits::ResourceDevice for super::Context {
                 );
             }
         }
-        super::Buffer {
+        Ok(super::Buffer {
             raw,
             size: desc.size,
             data,
-        }
+        })
     }
 
     fn sync_buffer(&self, buffer: super::Buffer) {
@@ -123,6 +157,37 @@ impl crate::traits::ResourceDevice for super::Context {
     }
 
     fn create_texture(&self, desc: crate::TextureDesc) -> super::Texture {
+        let name = desc.name;
+        self.try_create_texture(desc)
+            .unwrap_or_else(|e| panic!("Unable to create texture '{name}': {e}"))
+    }
+
+    fn try_create_texture(
+        &self,
+        desc: crate::TextureDesc,
+    ) -> Result<super::Texture, crate::ResourceError> {
+        desc.check_usage(self.supported_texture_usage(desc.format))?;
+        let max_size = match desc.dimension {
+            crate::TextureDimension::D3 => self.limits.max_3d_texture_size,
+            crate::TextureDimension::D1 | crate::TextureDimension::D2 => {
+                self.limits.max_texture_size
+            }
+        };
+        let limit = if desc.size.width > max_size
+            || desc.size.height > max_size
+            || desc.size.depth > max_size
+        {
+            Some(max_size)
+        } else if desc.array_layer_count > self.limits.max_array_texture_layers {
+            Some(self.limits.max_array_texture_layers)
+        } else {
+            None
+        };
+        if let Some(limit) = limit {
+            return Err(crate::ResourceError::TooLarge {
+                limit: limit as u64,
+            });
+        }
         let gl = self.lock();
         let format_desc = super::describe_texture_format(desc.format);
 
@@ -261,11 +326,23 @@ impl crate::traits::ResourceDevice for super::Context {
             super::TextureInner::Texture { raw, target }
         };
 
-        super::Texture {
+        if unsafe { gl.get_error() } == glow::OUT_OF_MEMORY {
+            match inner {
+                super::TextureInner::Renderbuffer { raw } => unsafe {
+                    gl.delete_renderbuffer(raw);
+                },
+                super::TextureInner::Texture { raw, .. } => unsafe {
+                    gl.delete_texture(raw);
+                },
+            }
+            return Err(crate::ResourceError::OutOfDeviceMemory);
+        }
+
+        Ok(super::Texture {
             inner,
-            target_size: [desc.size.width as u16, desc.size.height as u16],
+            size: desc.size,
             format: desc.format,
-        }
+        })
     }
 
     fn destroy_texture(&self, texture: super::Texture) {
@@ -280,87 +357,110 @@ impl crate::traits::ResourceDevice for super::Context {
         }
     }
 
+    fn write_texture(
+        &self,
+        _dst: crate::TexturePiece,
+        _data: &[u8],
+        _bytes_per_row: u32,
+        _size: crate::Extent,
+    ) {
+        unimplemented!()
+    }
+
     fn create_texture_view(
         &self,
         texture: super::Texture,
         desc: crate::TextureViewDesc,
     ) -> super::TextureView {
         //TODO: actual reinterpretation
+        if desc.format.is_srgb() && !texture.format.is_srgb() {
+            log::warn!(
+                "View '{}' can't decode {:?} texture as sRGB",
+                desc.name,
+                texture.format
+            );
+        }
         super::TextureView {
             inner: texture.inner,
-            target_size: texture.target_size,
+            target_size: [texture.size.width as u16, texture.size.height as u16],
             aspects: desc.format.aspects(),
+            format: desc.format,
         }
     }
 
     fn destroy_texture_view(&self, _view: super::TextureView) {}
 
     fn create_sampler(&self, desc: crate::SamplerDesc) -> super::Sampler {
-        let gl = self.lock();
+        let _ = desc.check_lod(0.0);
+        self.sampler_cache.get_or_create(&desc, || {
+            let gl = self.lock();
 
-        let wrap_enums = [
-            glow::TEXTURE_WRAP_S,
-            glow::TEXTURE_WRAP_T,
-            glow::TEXTURE_WRAP_R,
-        ];
-        let (min, mag) = map_filter_modes(desc.min_filter, desc.mag_filter, desc.mipmap_filter);
-        let border = match desc.border_color {
-            None | Some(crate::TextureColor::TransparentBlack) => [0.0; 4],
-            Some(crate::TextureColor::OpaqueBlack) => [0.0, 0.0, 0.0, 1.0],
-            Some(crate::TextureColor::White) => [1.0; 4],
-        };
+            let wrap_enums = [
+                glow::TEXTURE_WRAP_S,
+                glow::TEXTURE_WRAP_T,
+                glow::TEXTURE_WRAP_R,
+            ];
+            let (min, mag) = map_filter_modes(desc.min_filter, desc.mag_filter, desc.mipmap_filter);
+            let border = match desc.border_color {
+                None | Some(crate::TextureColor::TransparentBlack) => [0.0; 4],
+                Some(crate::TextureColor::OpaqueBlack) => [0.0, 0.0, 0.0, 1.0],
+                Some(crate::TextureColor::White) => [1.0; 4],
+            };
 
-        let raw = unsafe { gl.create_sampler().unwrap() };
-        unsafe {
-            gl.sampler_parameter_i32(raw, glow::TEXTURE_MIN_FILTER, min as i32);
-            gl.sampler_parameter_i32(raw, glow::TEXTURE_MAG_FILTER, mag as i32);
+            let raw = unsafe { gl.create_sampler().unwrap() };
+            unsafe {
+                gl.sampler_parameter_i32(raw, glow::TEXTURE_MIN_FILTER, min as i32);
+                gl.sampler_parameter_i32(raw, glow::TEXTURE_MAG_FILTER, mag as i32);
 
-            for (&address_mode, wrap_enum) in desc.address_modes.iter().zip(wrap_enums) {
-                gl.sampler_parameter_i32(raw, wrap_enum, map_address_mode(address_mode) as i32)
-            }
-            if desc.border_color.is_some() {
-                gl.sampler_parameter_f32_slice(raw, glow::TEXTURE_BORDER_COLOR, &border)
-            }
-            gl.sampler_parameter_f32(raw, glow::TEXTURE_MIN_LOD, desc.lod_min_clamp);
-            if let Some(clamp) = desc.lod_max_clamp {
-                gl.sampler_parameter_f32(raw, glow::TEXTURE_MAX_LOD, clamp);
-            }
-            if desc.anisotropy_clamp > 1 {
-                gl.sampler_parameter_i32(
-                    raw,
-                    glow::TEXTURE_MAX_ANISOTROPY,
-                    desc.anisotropy_clamp as i32,
-                );
-            }
+                for (&address_mode, wrap_enum) in desc.address_modes.iter().zip(wrap_enums) {
+                    gl.sampler_parameter_i32(raw, wrap_enum, map_address_mode(address_mode) as i32)
+                }
+                if desc.border_color.is_some() {
+                    gl.sampler_parameter_f32_slice(raw, glow::TEXTURE_BORDER_COLOR, &border)
+                }
+                gl.sampler_parameter_f32(raw, glow::TEXTURE_MIN_LOD, desc.lod_min_clamp);
+                if let Some(clamp) = desc.lod_max_clamp {
+                    gl.sampler_parameter_f32(raw, glow::TEXTURE_MAX_LOD, clamp);
+                }
+                if desc.anisotropy_clamp > 1 {
+                    gl.sampler_parameter_i32(
+                        raw,
+                        glow::TEXTURE_MAX_ANISOTROPY,
+                        desc.anisotropy_clamp as i32,
+                    );
+                }
 
-            if let Some(compare) = desc.compare {
-                gl.sampler_parameter_i32(
-                    raw,
-                    glow::TEXTURE_COMPARE_MODE,
-                    glow::COMPARE_REF_TO_TEXTURE as i32,
-                );
-                gl.sampler_parameter_i32(
-                    raw,
-                    glow::TEXTURE_COMPARE_FUNC,
-                    super::map_compare_func(compare) as i32,
-                );
-            }
+                if let Some(compare) = desc.compare {
+                    gl.sampler_parameter_i32(
+                        raw,
+                        glow::TEXTURE_COMPARE_MODE,
+                        glow::COMPARE_REF_TO_TEXTURE as i32,
+                    );
+                    gl.sampler_parameter_i32(
+                        raw,
+                        glow::TEXTURE_COMPARE_FUNC,
+                        super::map_compare_func(compare) as i32,
+                    );
+                }
 
-            #[cfg(not(target_arch = "wasm32"))]
-            if !desc.name.is_empty() && gl.supports_debug() {
-                gl.object_label(
-                    glow::SAMPLER,
-                    std::mem::transmute::<glow::NativeSampler, u32>(raw),
-                    Some(desc.name),
-                );
+                #[cfg(not(target_arch = "wasm32"))]
+                if !desc.name.is_empty() && gl.supports_debug() {
+                    gl.object_label(
+                        glow::SAMPLER,
+                        std::mem::transmute::<glow::NativeSampler, u32>(raw),
+                        Some(desc.name),
+                    );
+                }
             }
-        }
-        super::Sampler { raw }
+            super::Sampler { raw }
+        })
     }
 
     fn destroy_sampler(&self, sampler: super::Sampler) {
-        let gl = self.lock();
-        unsafe { gl.delete_sampler(sampler.raw) };
+        if self.sampler_cache.release(sampler) {
+            let gl = self.lock();
+            unsafe { gl.delete_sampler(sampler.raw) };
+        }
     }
 
     fn create_acceleration_structure(
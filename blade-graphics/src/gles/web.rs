@@ -15,6 +15,7 @@ pub struct PlatformSurface {
 pub struct PlatformFrame {
     framebuf: glow::Framebuffer,
     extent: crate::Extent,
+    format: crate::TextureFormat,
 }
 
 impl super::Surface {
@@ -27,13 +28,14 @@ impl super::Surface {
             platform: PlatformFrame {
                 framebuf: self.framebuf,
                 extent: self.platform.extent,
+                format: self.platform.info.format,
             },
             texture: super::Texture {
                 inner: super::TextureInner::Texture {
                     raw: self.offscreen_texture,
                     target: glow::TEXTURE_2D,
                 },
-                target_size: [size.width as u16, size.height as u16],
+                size,
                 format: self.platform.info.format,
             },
         }
@@ -41,9 +43,15 @@ impl super::Surface {
 }
 
 impl PlatformContext {
-    pub(super) fn present(&self, frame: PlatformFrame) {
+    pub(super) fn present(&self, frame: PlatformFrame, framebuffer_srgb: bool) {
         unsafe {
-            super::present_blit(&self.glow, frame.framebuf, frame.extent);
+            super::present_blit(
+                &self.glow,
+                frame.framebuf,
+                frame.extent,
+                frame.format,
+                framebuffer_srgb,
+            );
         }
     }
 }
@@ -75,11 +83,30 @@ impl super::Context {
 
         let glow = glow::Context::from_webgl2_context(webgl2.clone());
 
-        let capabilities = super::Capabilities::empty();
+        // WebGL extensions have to be enabled to be used
+        let capabilities = super::Capabilities::texture_compression(|names| {
+            names
+                .iter()
+                .any(|&name| matches!(webgl2.get_extension(name), Ok(Some(_))))
+        });
         let limits = super::Limits {
             uniform_buffer_alignment: unsafe {
                 glow.get_parameter_i32(glow::UNIFORM_BUFFER_OFFSET_ALIGNMENT) as u32
             },
+            // WebGL has no storage buffers
+            storage_buffer_alignment: 0,
+            max_uniform_block_size: unsafe {
+                glow.get_parameter_i32(glow::MAX_UNIFORM_BLOCK_SIZE) as u32
+            },
+            // WebGL has no compute shaders
+            max_compute_work_group_count: [0; 3],
+            max_texture_size: unsafe { glow.get_parameter_i32(glow::MAX_TEXTURE_SIZE) as u32 },
+            max_3d_texture_size: unsafe {
+                glow.get_parameter_i32(glow::MAX_3D_TEXTURE_SIZE) as u32
+            },
+            max_array_texture_layers: unsafe {
+                glow.get_parameter_i32(glow::MAX_ARRAY_TEXTURE_LAYERS) as u32
+            },
         };
         let device_information = unsafe {
             crate::DeviceInformation {
@@ -90,12 +117,18 @@ impl super::Context {
             }
         };
 
+        let reporter = super::init_reporter(&glow, &device_information);
+
         Ok(super::Context {
             platform: PlatformContext { webgl2, glow },
             capabilities,
             toggles: super::Toggles::default(),
             limits,
             device_information,
+            deferred_destructions: Default::default(),
+            dummy_resources: Default::default(),
+            reporter,
+            sampler_cache: Default::default(),
         })
     }
 
@@ -150,6 +183,13 @@ impl super::Context {
             gl.bind_texture(glow::TEXTURE_2D, None);
         }
         surface.platform.extent = config.size;
+        self.reporter.set_surface(crate::SurfaceReport {
+            width: config.size.width,
+            height: config.size.height,
+            format: format!("{:?}", surface.platform.info.format),
+            alpha: format!("{:?}", surface.platform.info.alpha),
+            present_mode: "browser".to_string(),
+        });
     }
 
     /// Obtain a lock to the EGL context and get handle to the [`glow::Context`] that can be used to
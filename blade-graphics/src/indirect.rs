@@ -0,0 +1,150 @@
+use std::mem;
+
+/// Required alignment of the indirect arguments in a buffer, on all the backends.
+pub const INDIRECT_ARGS_ALIGNMENT: u64 = 4;
+
+/// Arguments of an indirect command, laid out the way the GPU reads them from a buffer.
+///
+/// Shaders that produce the arguments can declare the matching struct
+/// with `WGSL`, and write it into an array in a storage buffer:
+/// ```
+/// use blade_graphics as gpu;
+///
+/// fn check_layout<T: gpu::IndirectArgs>(name: &str) {
+///     let source = format!(
+///         "{}\n@group(0) @binding(0) var<storage, read_write> args: array<{name}>;",
+///         T::WGSL
+///     );
+///     let module = naga::front::wgsl::parse_str(&source).unwrap();
+///     let mut layouter = naga::proc::Layouter::default();
+///     layouter.update(module.to_ctx()).unwrap();
+///     let (handle, _) = module
+///         .types
+///         .iter()
+///         .find(|&(_, ty)| ty.name.as_deref() == Some(name))
+///         .unwrap();
+///     assert_eq!(layouter[handle].size as usize, size_of::<T>());
+/// }
+///
+/// check_layout::<gpu::DispatchIndirectArgs>("DispatchIndirectArgs");
+/// check_layout::<gpu::DrawIndirectArgs>("DrawIndirectArgs");
+/// check_layout::<gpu::DrawIndexedIndirectArgs>("DrawIndexedIndirectArgs");
+/// ```
+pub trait IndirectArgs: bytemuck::Pod {
+    /// WGSL declaration of the struct with the same layout and name.
+    const WGSL: &'static str;
+}
+
+/// Arguments of `dispatch_indirect`, the number of workgroups in each dimension.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DispatchIndirectArgs {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl IndirectArgs for DispatchIndirectArgs {
+    const WGSL: &'static str = "struct DispatchIndirectArgs {
+    x: u32,
+    y: u32,
+    z: u32,
+}";
+}
+
+/// Arguments of `draw_indirect`, matching the ones of `draw`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawIndirectArgs {
+    pub vertex_count: u32,
+    pub instance_count: u32,
+    pub first_vertex: u32,
+    pub first_instance: u32,
+}
+
+impl IndirectArgs for DrawIndirectArgs {
+    const WGSL: &'static str = "struct DrawIndirectArgs {
+    vertex_count: u32,
+    instance_count: u32,
+    first_vertex: u32,
+    first_instance: u32,
+}";
+}
+
+/// Arguments of `draw_indexed_indirect`, matching the ones of `draw_indexed`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawIndexedIndirectArgs {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub first_instance: u32,
+}
+
+impl IndirectArgs for DrawIndexedIndirectArgs {
+    const WGSL: &'static str = "struct DrawIndexedIndirectArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}";
+}
+
+impl crate::BufferPiece {
+    /// Check that `count` indirect arguments of type `T` fit into the buffer
+    /// at this piece, `stride` bytes apart.
+    pub fn check_indirect<T: IndirectArgs>(
+        &self,
+        count: u32,
+        stride: u64,
+    ) -> Result<(), crate::IndirectError> {
+        let size = mem::size_of::<T>() as u64;
+        if !self.offset.is_multiple_of(INDIRECT_ARGS_ALIGNMENT) {
+            return Err(crate::IndirectError::MisalignedOffset {
+                offset: self.offset,
+            });
+        }
+        if stride < size || !stride.is_multiple_of(INDIRECT_ARGS_ALIGNMENT) {
+            return Err(crate::IndirectError::InvalidStride { stride, size });
+        }
+        let end = match count {
+            0 => self.offset,
+            _ => self.offset + (count as u64 - 1) * stride + size,
+        };
+        let buffer_size = self.buffer.size();
+        if end > buffer_size {
+            return Err(crate::IndirectError::OutOfBounds { end, buffer_size });
+        }
+        Ok(())
+    }
+}
+
+/// Panic on invalid indirect arguments in debug builds,
+/// instead of leaving it to the driver.
+pub(crate) fn debug_check_indirect<T: IndirectArgs>(piece: &crate::BufferPiece) {
+    if cfg!(debug_assertions)
+        && let Err(e) = piece.check_indirect::<T>(1, mem::size_of::<T>() as u64)
+    {
+        panic!(
+            "Invalid indirect arguments in buffer {:?}: {e}",
+            piece.buffer
+        );
+    }
+}
+
+impl crate::Context {
+    /// Create a shared buffer holding the indirect arguments,
+    /// tightly packed, so that entry `i` is at `i * size_of::<T>()`.
+    pub fn create_indirect_buffer<T: IndirectArgs>(&self, name: &str, args: &[T]) -> crate::Buffer {
+        let buffer = self.create_buffer(crate::BufferDesc {
+            name,
+            size: (mem::size_of_val(args) as u64).max(INDIRECT_ARGS_ALIGNMENT),
+            memory: crate::Memory::Shared,
+        });
+        buffer.write_slice(0, args);
+        self.sync_buffer(buffer);
+        buffer
+    }
+}
@@ -49,7 +49,11 @@ pub const IDENTITY_TRANSFORM: Transform = mint::RowMatrix3x4 {
     },
 };
 
+mod cache;
+mod coords;
+mod deferred;
 pub mod derive;
+mod dummy;
 #[cfg_attr(
     all(not(vulkan), not(gles), any(target_os = "ios", target_os = "macos")),
     path = "metal/mod.rs"
@@ -69,6 +73,8 @@ pub mod derive;
 )]
 #[cfg_attr(any(gles, target_arch = "wasm32"), path = "gles/mod.rs")]
 mod hal;
+mod indirect;
+mod report;
 mod shader;
 pub mod traits;
 pub mod util;
@@ -85,12 +91,29 @@ pub mod limits {
     pub const ACCELERATION_STRUCTURE_SCRATCH_ALIGNMENT: u64 = 256;
 }
 
+pub use coords::{GL_CLIP_TO_CLIP, NDC_DEPTH_RANGE, NDC_TO_UV, UV_TO_NDC, ndc_to_uv, uv_to_ndc};
 pub use hal::*;
+pub use indirect::{
+    DispatchIndirectArgs, DrawIndexedIndirectArgs, DrawIndirectArgs, INDIRECT_ARGS_ALIGNMENT,
+    IndirectArgs,
+};
+pub use report::{InitReport, MemoryHeapReport, SurfaceReport};
+
+// Resources can be created and destroyed from any thread, concurrently
+// with the command recording and submission on the other threads.
+// Command encoders can be recorded on different threads.
+#[cfg(not(target_arch = "wasm32"))]
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    const fn assert_send<T: Send>() {}
+    assert_send_sync::<Context>();
+    assert_send::<CommandEncoder>();
+};
 
 #[cfg(target_arch = "wasm32")]
 pub const CANVAS_ID: &str = "blade";
 
-use std::{fmt, num::NonZeroU32};
+use std::{fmt, mem, num::NonZeroU32, ptr};
 
 /// Error from the underlying graphics platform during initialization.
 #[derive(Debug)]
@@ -162,21 +185,44 @@ pub struct ContextDesc {
     pub overlay: bool,
     /// Force selection of a specific Device ID.
     pub device_id: Option<u32>,
+    /// Number of shader data sets in the first descriptor pool of a command buffer,
+    /// or zero for the default. Heavy binders can raise it to grow the pools less often.
+    pub descriptor_pool_size: u32,
+    /// Make the out-of-bounds buffer accesses in shaders return zero,
+    /// and allow binding `None` in place of a buffer or a texture.
+    ///
+    /// Only supported on Vulkan with `VK_EXT_robustness2`, see `Capabilities::robustness`.
+    /// Every buffer access gets a bounds check, which costs up to a few percent
+    /// in shader-heavy workloads, so it's best kept for debugging.
+    pub robustness: bool,
+    /// Render with render pass and framebuffer objects on Vulkan,
+    /// even if dynamic rendering is supported.
+    ///
+    /// This is the path taken by the older drivers without `VK_KHR_dynamic_rendering`,
+    /// forcing it allows testing that path on capable machines.
+    pub legacy_render_passes: bool,
 }
 
 #[derive(Debug)]
 pub enum NotSupportedError {
     Platform(PlatformError),
+    /// The graphics API is there, but has no driver for any device.
+    NoDriverFound,
     NoSupportedDeviceFound,
     PlatformNotSupported,
+    /// `ContextDesc::presentation` was requested, but presenting to windows
+    /// is not supported, like on a system without a display server.
+    PresentationNotSupported,
 }
 
 impl fmt::Display for NotSupportedError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             Self::Platform(ref e) => write!(f, "platform error: {}", e),
+            Self::NoDriverFound => f.write_str("no driver found"),
             Self::NoSupportedDeviceFound => f.write_str("no supported device found"),
             Self::PlatformNotSupported => f.write_str("platform not supported"),
+            Self::PresentationNotSupported => f.write_str("presentation not supported"),
         }
     }
 }
@@ -209,6 +255,189 @@ impl fmt::Display for DeviceError {
 
 impl std::error::Error for DeviceError {}
 
+/// Error indicating an invalid region of a texture.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegionError {
+    /// The region reaches outside of the mip level.
+    OutOfBounds {
+        origin: [u32; 3],
+        extent: Extent,
+        mip_size: Extent,
+    },
+    /// The region splits the blocks of a compressed format
+    /// anywhere but at the edge of the mip level.
+    PartialBlock {
+        origin: [u32; 3],
+        extent: Extent,
+        block_size: (u8, u8),
+    },
+}
+
+impl fmt::Display for RegionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::OutOfBounds {
+                origin,
+                extent,
+                mip_size,
+            } => write!(
+                f,
+                "region {extent} at {origin:?} is out of the mip level bounds {mip_size}"
+            ),
+            Self::PartialBlock {
+                origin,
+                extent,
+                block_size,
+            } => write!(
+                f,
+                "region {extent} at {origin:?} is not aligned to {}x{} blocks",
+                block_size.0, block_size.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RegionError {}
+
+/// Error indicating a failure to create a resource.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResourceError {
+    /// The GPU memory is exhausted.
+    OutOfDeviceMemory,
+    /// The system memory is exhausted.
+    OutOfHostMemory,
+    /// The texture format is not supported by the device.
+    UnsupportedFormat(TextureFormat),
+    /// The texture usage flags are not supported for its format.
+    UnsupportedUsage(TextureUsage),
+    /// The resource exceeds the limit of the device, in texels or bytes.
+    TooLarge { limit: u64 },
+    /// Motion acceleration structures are used,
+    /// see `Capabilities::acceleration_structure_motion`.
+    MotionNotSupported,
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::OutOfDeviceMemory => f.write_str("out of device memory"),
+            Self::OutOfHostMemory => f.write_str("out of host memory"),
+            Self::UnsupportedFormat(format) => write!(f, "format {format:?} is not supported"),
+            Self::UnsupportedUsage(usage) => write!(f, "usage {usage:?} is not supported"),
+            Self::TooLarge { limit } => write!(f, "exceeds the device limit of {limit}"),
+            Self::MotionNotSupported => {
+                f.write_str("motion acceleration structures are not supported")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResourceError {}
+
+/// Error indicating a failure to create a pipeline.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PipelineError {
+    /// The binding arrays hold more textures than the device can access,
+    /// see `Capabilities::max_binding_array_size`.
+    TooManyBindingArrayTextures { count: u32, limit: u32 },
+    /// The fragment shader writes color outputs, but the pipeline
+    /// has no color targets, as used in depth-only passes.
+    ColorOutputsWithoutTargets { fragment: String, count: u32 },
+    /// A color target has a logic operation, see `Capabilities::logic_op`.
+    LogicOpNotSupported,
+    /// A color target with a logic operation doesn't have an integer format.
+    LogicOpOnNonIntegerTarget { index: u32, format: TextureFormat },
+    /// The integer color targets have different logic operations,
+    /// while the operation is shared by the whole pipeline.
+    MismatchedLogicOps,
+    /// A plain data binding is larger than the device can pass inline,
+    /// see `Capabilities::max_plain_data_size`. Large data belongs in a buffer.
+    PlainDataTooLarge {
+        binding: String,
+        size: u32,
+        limit: u32,
+    },
+    /// The pipeline renders more views than the device supports,
+    /// see `Capabilities::max_view_count`.
+    TooManyViews { count: u32, limit: u32 },
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::TooManyBindingArrayTextures { count, limit } => write!(
+                f,
+                "{count} textures in binding arrays exceed the device limit of {limit}"
+            ),
+            Self::ColorOutputsWithoutTargets {
+                ref fragment,
+                count,
+            } => write!(
+                f,
+                "fragment shader '{fragment}' writes {count} color outputs, \
+                 but the pipeline has no color targets"
+            ),
+            Self::LogicOpNotSupported => {
+                write!(f, "logic operations are not supported by the device")
+            }
+            Self::LogicOpOnNonIntegerTarget { index, format } => write!(
+                f,
+                "color target {index} has a logic operation, but its format {format:?} is not integer"
+            ),
+            Self::MismatchedLogicOps => write!(
+                f,
+                "integer color targets have different logic operations, \
+                 but only one can be used by the pipeline"
+            ),
+            Self::PlainDataTooLarge {
+                ref binding,
+                size,
+                limit,
+            } => write!(
+                f,
+                "plain data binding '{binding}' of {size} bytes exceeds the device limit of {limit}"
+            ),
+            Self::TooManyViews { count, limit } => {
+                write!(f, "{count} views exceed the device limit of {limit}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+/// Error indicating invalid placement of the indirect arguments in a buffer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IndirectError {
+    /// The offset is not aligned to `INDIRECT_ARGS_ALIGNMENT`.
+    MisalignedOffset { offset: u64 },
+    /// The stride is smaller than the arguments, or not aligned to `INDIRECT_ARGS_ALIGNMENT`.
+    InvalidStride { stride: u64, size: u64 },
+    /// The arguments reach past the end of the buffer.
+    OutOfBounds { end: u64, buffer_size: u64 },
+}
+
+impl fmt::Display for IndirectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::MisalignedOffset { offset } => write!(
+                f,
+                "offset {offset} is not aligned to {INDIRECT_ARGS_ALIGNMENT} bytes"
+            ),
+            Self::InvalidStride { stride, size } => write!(
+                f,
+                "stride {stride} doesn't fit the arguments of {size} bytes with the alignment of {INDIRECT_ARGS_ALIGNMENT}"
+            ),
+            Self::OutOfBounds { end, buffer_size } => write!(
+                f,
+                "arguments end at {end}, past the buffer size {buffer_size}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IndirectError {}
+
 /// GPU memory usage statistics.
 #[derive(Clone, Copy, Debug, Default)]
 pub struct MemoryStats {
@@ -218,6 +447,37 @@ pub struct MemoryStats {
     /// Current memory usage across all device-local heaps (bytes).
     /// Zero if the backend doesn't support memory budget queries.
     pub usage: u64,
+    /// Pools of shader data descriptors.
+    /// Empty if the backend binds the data without pools.
+    pub descriptor_pools: DescriptorPoolStats,
+}
+
+/// Descriptor pool statistics, across all the command encoders of a context.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DescriptorPoolStats {
+    /// Number of live pools.
+    pub pool_count: u32,
+    /// Highest number of live pools.
+    pub peak_pool_count: u32,
+    /// Number of sets that fit into the live pools.
+    pub set_capacity: u64,
+    /// Highest number of sets that fit into the live pools.
+    pub peak_set_capacity: u64,
+    /// Number of sets allocated since the pools were last reset.
+    pub allocated_sets: u64,
+    /// Highest number of allocated sets.
+    pub peak_allocated_sets: u64,
+}
+
+/// Statistics of the sampler deduplication, see `Context::sampler_cache_stats`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SamplerCacheStats {
+    /// Number of samplers returned from the cache.
+    pub hits: u64,
+    /// Number of samplers created.
+    pub misses: u64,
+    /// Number of distinct live samplers in the cache.
+    pub cached_count: u32,
 }
 
 /// Cooperative matrix support information.
@@ -243,6 +503,11 @@ impl CooperativeMatrix {
 pub struct Capabilities {
     /// Support binding arrays of handles.
     pub binding_array: bool,
+    /// Maximum number of textures in the binding arrays of a pipeline,
+    /// zero without `binding_array`.
+    pub max_binding_array_size: u32,
+    /// Maximum size in bytes of a plain data binding, see `ShaderBinding::Plain`.
+    pub max_plain_data_size: u32,
     /// Which shader stages support ray queries.
     pub ray_query: ShaderVisibility,
     /// Bit mask of supported MSAA sample counts.
@@ -253,6 +518,40 @@ pub struct Capabilities {
     pub shader_float16: bool,
     /// Cooperative matrix support.
     pub cooperative_matrix: CooperativeMatrix,
+    /// Support for top-level acceleration structures with motion instances.
+    /// Without it, the motion functions return `ResourceError::MotionNotSupported`.
+    /// Ray queries have no time operand in SPIR-V, so their traversal happens at time 0.
+    pub acceleration_structure_motion: bool,
+    /// Support for command encoders that are recorded once and submitted multiple times,
+    /// see `CommandEncoder::start_reusable`.
+    pub reusable_command_encoders: bool,
+    /// Robust buffer access and null bindings are enabled, see `ContextDesc::robustness`.
+    pub robustness: bool,
+    /// Support for resolving multisampled depth targets with `DepthResolveMode::SampleZero`,
+    /// see `FinishOp::ResolveDepthTo`.
+    pub depth_resolve: bool,
+    /// Support for resolving multisampled depth targets with `DepthResolveMode::Min`
+    /// and `DepthResolveMode::Max`.
+    pub depth_resolve_min_max: bool,
+    /// Support for `ColorTargetState::logic_op`.
+    /// Without it, bitwise accumulation needs atomics on a storage texture.
+    pub logic_op: bool,
+    /// Size in bytes of the memory tiles of sparse textures, see `TextureUsage::SPARSE`.
+    /// Zero if sparse residency is not supported.
+    pub sparse_tile_size: u32,
+    /// Support for `BufferPiece::device_address`.
+    pub buffer_device_address: bool,
+    /// Maximum number of views of a multiview pass, see `RenderTargetSet::multiview`.
+    /// Zero if multiview is not supported.
+    pub max_view_count: u32,
+    /// Support for skipping draws based on a value in a buffer,
+    /// see `RenderCommandEncoder::begin_conditional`.
+    pub conditional_rendering: bool,
+    /// Alignment in bytes of the offsets of the dynamic buffers,
+    /// see `ShaderBinding::DynamicBuffer`. Zero if they are not supported.
+    pub dynamic_offset_alignment: u32,
+    /// Largest absolute value of `SamplerDesc::lod_bias`. Zero if the bias is not supported.
+    pub max_sampler_lod_bias: f32,
 }
 
 #[derive(Clone, Debug)]
@@ -277,6 +576,7 @@ pub struct DeviceReport {
 }
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DeviceInformation {
     /// If this is something like llvmpipe, not a real GPU
     pub is_software_emulated: bool,
@@ -376,6 +676,19 @@ impl BufferPiece {
         );
         unsafe { base.offset(self.offset as isize) }
     }
+
+    /// Return the GPU virtual address of the piece, which requires
+    /// `Capabilities::buffer_device_address`. It stays the same for the
+    /// lifetime of the buffer. Buffers imported from external memory have no address.
+    ///
+    /// Shaders can't dereference the addresses yet, since naga has no
+    /// physical pointers. They can be stored in shader data as `vec2<u32>`,
+    /// low bits first, for example to refer to the buffers of a scene.
+    pub fn device_address(&self) -> u64 {
+        let base = self.buffer.device_address();
+        assert_ne!(base, 0, "Buffer has no device address");
+        base + self.offset
+    }
 }
 
 impl Buffer {
@@ -385,6 +698,44 @@ impl Buffer {
             offset,
         }
     }
+
+    /// Write the data into a mapped buffer, at the offset in elements of `T`.
+    pub fn write_slice<T: bytemuck::Pod>(&self, offset_elems: usize, data: &[T]) {
+        let bytes = bytemuck::cast_slice::<T, u8>(data);
+        let offset = offset_elems * mem::size_of::<T>();
+        self.check_mapped_range(offset, bytes.len());
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), self.data().add(offset), bytes.len());
+        }
+    }
+
+    /// Read `count` elements from a mapped buffer, at the offset in elements of `T`.
+    ///
+    /// The GPU has to be done writing them, e.g. by waiting for the sync point.
+    pub fn read_slice<T: bytemuck::Pod>(&self, offset_elems: usize, count: usize) -> Vec<T> {
+        let mut result = vec![T::zeroed(); count];
+        let bytes = bytemuck::cast_slice_mut::<T, u8>(&mut result);
+        let offset = offset_elems * mem::size_of::<T>();
+        self.check_mapped_range(offset, bytes.len());
+        unsafe {
+            ptr::copy_nonoverlapping(self.data().add(offset), bytes.as_mut_ptr(), bytes.len());
+        }
+        result
+    }
+
+    fn check_mapped_range(&self, offset: usize, size: usize) {
+        assert!(
+            !self.data().is_null(),
+            "Buffer is not mapped, it needs `Memory::Upload` or `Memory::Shared`"
+        );
+        assert!(
+            offset + size <= self.size() as usize,
+            "Range {}..{} is out of the buffer size {}",
+            offset,
+            offset + size,
+            self.size()
+        );
+    }
 }
 
 pub type ResourceIndex = u32;
@@ -436,6 +787,24 @@ pub type TextureArray<const N: ResourceIndex> = ResourceArray<TextureView, N>;
 pub type AccelerationStructureArray<const N: ResourceIndex> =
     ResourceArray<AccelerationStructure, N>;
 
+/// A buffer or a texture binding that can be left empty.
+/// Shaders read zeros from an empty binding.
+///
+/// Without `Capabilities::robustness`, `None` is bound to `Context::dummy_black`
+/// or `Context::dummy_buffer`, so the shaders must not write to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Nullable<T>(pub Option<T>);
+impl<T> From<Option<T>> for Nullable<T> {
+    fn from(value: Option<T>) -> Self {
+        Self(value)
+    }
+}
+impl<T> From<T> for Nullable<T> {
+    fn from(value: T) -> Self {
+        Self(Some(value))
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct TexturePiece {
     pub texture: Texture,
@@ -444,6 +813,25 @@ pub struct TexturePiece {
     pub origin: [u32; 3],
 }
 
+/// Region of a texture subresource.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureRegion {
+    pub mip_level: u32,
+    pub array_layer: u32,
+    pub origin: [u32; 3],
+    pub size: Extent,
+}
+
+/// Change of the memory backing the tiles of a sparse texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Residency {
+    /// Allocate the memory of the tiles.
+    /// Their contents are undefined until written.
+    Bind,
+    /// Release the memory of the tiles, making their contents undefined.
+    Unbind,
+}
+
 impl From<Texture> for TexturePiece {
     fn from(texture: Texture) -> Self {
         Self {
@@ -455,6 +843,97 @@ impl From<Texture> for TexturePiece {
     }
 }
 
+impl TexturePiece {
+    /// Size of the mip level of this piece.
+    pub fn mip_size(&self) -> Extent {
+        self.texture.size().at_mip_level(self.mip_level)
+    }
+
+    /// Check that a region of the given extent, starting at this piece,
+    /// fits into the mip level and doesn't split the texel blocks.
+    pub fn validate(&self, extent: Extent) -> Result<(), RegionError> {
+        let mip_size = self.mip_size();
+        let ends = [
+            self.origin[0].checked_add(extent.width),
+            self.origin[1].checked_add(extent.height),
+            self.origin[2].checked_add(extent.depth),
+        ];
+        let limits = [mip_size.width, mip_size.height, mip_size.depth];
+        if ends
+            .into_iter()
+            .zip(limits)
+            .any(|(end, limit)| end.is_none_or(|end| end > limit))
+        {
+            return Err(RegionError::OutOfBounds {
+                origin: self.origin,
+                extent,
+                mip_size,
+            });
+        }
+
+        // Partial blocks are only allowed where the region touches the edge
+        let format = self.texture.format();
+        let block_size = format.block_info().dimensions;
+        let edge_extent = Extent {
+            width: if ends[0] == Some(limits[0]) {
+                extent.width.next_multiple_of(block_size.0 as u32)
+            } else {
+                extent.width
+            },
+            height: if ends[1] == Some(limits[1]) {
+                extent.height.next_multiple_of(block_size.1 as u32)
+            } else {
+                extent.height
+            },
+            depth: extent.depth,
+        };
+        let origin = Extent {
+            width: self.origin[0],
+            height: self.origin[1],
+            depth: self.origin[2],
+        };
+        if !origin.is_block_aligned(format) || !edge_extent.is_block_aligned(format) {
+            return Err(RegionError::PartialBlock {
+                origin: self.origin,
+                extent,
+                block_size,
+            });
+        }
+        Ok(())
+    }
+
+    /// Get a piece at an `origin` relative to this one,
+    /// checking that a region of `extent` fits there.
+    pub fn subregion(&self, origin: [u32; 3], extent: Extent) -> Result<Self, RegionError> {
+        let absolute = [0, 1, 2].map(|i| self.origin[i].checked_add(origin[i]));
+        let piece = Self {
+            origin: absolute.map(|coord| coord.unwrap_or(u32::MAX)),
+            ..*self
+        };
+        if absolute.contains(&None) {
+            return Err(RegionError::OutOfBounds {
+                origin: piece.origin,
+                extent,
+                mip_size: self.mip_size(),
+            });
+        }
+        piece.validate(extent).map(|()| piece)
+    }
+}
+
+/// Panic on an invalid copy region in debug builds,
+/// instead of leaving it to the driver.
+pub(crate) fn debug_check_region(piece: &TexturePiece, extent: Extent) {
+    if cfg!(debug_assertions)
+        && let Err(e) = piece.validate(extent)
+    {
+        panic!(
+            "Invalid region of texture {:?} at mip level {}: {e}",
+            piece.texture, piece.mip_level
+        );
+    }
+}
+
 #[non_exhaustive]
 #[derive(Clone, Copy, Debug, Hash, Eq, Ord, PartialEq, PartialOrd)]
 pub enum TextureFormat {
@@ -495,6 +974,14 @@ pub enum TextureFormat {
     Bc6hFloat,
     Bc7Unorm,
     Bc7UnormSrgb,
+    // ETC2 block compression
+    Etc2Rgb8Unorm,
+    Etc2Rgb8UnormSrgb,
+    Etc2Rgba8Unorm,
+    Etc2Rgba8UnormSrgb,
+    // ASTC block compression
+    Astc4x4Unorm,
+    Astc4x4UnormSrgb,
     // packed 32-bit
     Rgb10a2Unorm,
     Rg11b10Ufloat,
@@ -575,6 +1062,23 @@ impl Extent {
             depth: (self.depth >> level).max(1),
         }
     }
+    /// Component-wise minimum of two extents.
+    pub fn min(self, other: Self) -> Self {
+        Self {
+            width: self.width.min(other.width),
+            height: self.height.min(other.height),
+            depth: self.depth.min(other.depth),
+        }
+    }
+    /// Check if the extent covers whole texel blocks of a format.
+    ///
+    /// Copies of compressed formats need this, unless the region
+    /// ends at the edge of the mip level.
+    pub fn is_block_aligned(&self, format: TextureFormat) -> bool {
+        let (block_width, block_height) = format.block_info().dimensions;
+        self.width.is_multiple_of(block_width as u32)
+            && self.height.is_multiple_of(block_height as u32)
+    }
 }
 
 bitflags::bitflags! {
@@ -584,6 +1088,20 @@ bitflags::bitflags! {
         const TARGET = 1 << 1;
         const RESOURCE = 1 << 2;
         const STORAGE = 1 << 3;
+        /// Written by the CPU with `write_texture`, skipping the staging copy.
+        ///
+        /// Only reported by `supported_texture_usage` when the GPU shares memory
+        /// with the CPU. Such textures may use a less efficient tiling or skip
+        /// the compression, making them slower to sample or render into on some GPUs.
+        const HOST_WRITE = 1 << 4;
+        /// Memory is bound to the tiles on demand with `update_texture_residency`,
+        /// instead of backing the whole texture at creation.
+        ///
+        /// Only reported by `supported_texture_usage` for the formats with
+        /// sparse residency, see `Capabilities::sparse_tile_size`. Limited to
+        /// single-sampled 2D color textures. The mip levels smaller than a tile
+        /// form the mip tail, which is always resident.
+        const SPARSE = 1 << 5;
     }
 }
 
@@ -600,6 +1118,19 @@ pub struct TextureDesc<'a> {
     pub external: Option<ExternalMemorySource>,
 }
 
+impl TextureDesc<'_> {
+    /// Check the format and usage against the ones supported by the device.
+    pub(crate) fn check_usage(&self, supported: TextureUsage) -> Result<(), ResourceError> {
+        if supported.is_empty() {
+            Err(ResourceError::UnsupportedFormat(self.format))
+        } else if !supported.contains(self.usage) {
+            Err(ResourceError::UnsupportedUsage(self.usage - supported))
+        } else {
+            Ok(())
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct TextureSubresources {
     pub base_mip_level: u32,
@@ -675,13 +1206,24 @@ pub enum CompareFunction {
     Always,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 pub enum TextureColor {
     TransparentBlack,
     OpaqueBlack,
     White,
 }
 
+/// Explicit value to clear a color target to, see `InitOp::ClearColor`.
+///
+/// It has to match the kind of the texels, see `TextureFormat::is_integer`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClearColor {
+    /// Value of the float and normalized texels.
+    Float([f32; 4]),
+    /// Value of the unsigned integer texels, such as the IDs of the objects.
+    Uint([u32; 4]),
+}
+
 #[derive(Debug, Default)]
 pub struct SamplerDesc<'a> {
     pub name: &'a str,
@@ -689,16 +1231,60 @@ pub struct SamplerDesc<'a> {
     pub mag_filter: FilterMode,
     pub min_filter: FilterMode,
     pub mipmap_filter: FilterMode,
+    /// Lowest mip level that can be sampled.
     pub lod_min_clamp: f32,
+    /// Highest mip level that can be sampled, or `None` for no limit.
+    ///
+    /// Clamping it keeps the sampling off the mips that are not loaded yet.
     pub lod_max_clamp: Option<f32>,
+    /// Bias added to the level of detail computed by the sampling.
+    ///
+    /// It's clamped to `Capabilities::max_sampler_lod_bias`, which is zero on
+    /// the backends without the support.
+    pub lod_bias: f32,
     pub compare: Option<CompareFunction>,
     pub anisotropy_clamp: u32,
     pub border_color: Option<TextureColor>,
 }
 
+impl SamplerDesc<'_> {
+    /// Check the LOD range, returning the LOD bias clamped to the limit.
+    fn check_lod(&self, max_lod_bias: f32) -> f32 {
+        assert!(
+            self.lod_min_clamp >= 0.0,
+            "Sampler '{}' has a negative LOD min clamp {}",
+            self.name,
+            self.lod_min_clamp
+        );
+        if let Some(max) = self.lod_max_clamp {
+            assert!(
+                self.lod_min_clamp <= max,
+                "Sampler '{}' has the LOD min clamp {} above the max {}",
+                self.name,
+                self.lod_min_clamp,
+                max
+            );
+        }
+        if self.lod_bias.abs() > max_lod_bias {
+            log::warn!(
+                "Sampler '{}' LOD bias {} is clamped to the supported {}",
+                self.name,
+                self.lod_bias,
+                max_lod_bias
+            );
+            self.lod_bias.clamp(-max_lod_bias, max_lod_bias)
+        } else {
+            self.lod_bias
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum AccelerationStructureType {
     TopLevel,
+    /// Top level with motion instances.
+    /// Requires `Capabilities::acceleration_structure_motion`.
+    TopLevelMotion,
     BottomLevel,
 }
 
@@ -758,12 +1344,40 @@ impl Default for AccelerationStructureInstance {
     }
 }
 
+/// Instance of a motion top-level acceleration structure.
+/// The transform is interpolated between the start and the end
+/// of the time interval [0, 1].
+#[derive(Clone, Debug)]
+pub struct AccelerationStructureMotionInstance {
+    pub acceleration_structure_index: u32,
+    pub transform_start: Transform,
+    pub transform_end: Transform,
+    pub mask: u32,
+    pub custom_index: u32,
+}
+
+impl Default for AccelerationStructureMotionInstance {
+    fn default() -> Self {
+        Self {
+            acceleration_structure_index: 0,
+            transform_start: IDENTITY_TRANSFORM,
+            transform_end: IDENTITY_TRANSFORM,
+            mask: 0xFF,
+            custom_index: 0,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct AccelerationStructureSizes {
     /// Size of the permanent GPU data
     pub data: u64,
-    /// Size of the scratch space
+    /// Size of the scratch space for a full build
     pub scratch: u64,
+    /// Size of the scratch space for an in-place update
+    pub update_scratch: u64,
+    /// Required alignment of the scratch buffer offset
+    pub scratch_alignment: u64,
 }
 
 pub struct Shader {
@@ -788,18 +1402,51 @@ impl ShaderFunction<'_> {
             .position(|ep| ep.name == self.entry_point)
             .expect("Entry point not found in the shader")
     }
+
+    /// Number of the color outputs of a fragment shader.
+    fn color_output_count(&self) -> u32 {
+        let ep = &self.shader.module.entry_points[self.entry_point_index()];
+        let result = match ep.function.result {
+            Some(ref result) => result,
+            None => return 0,
+        };
+        // The second source of dual-source blending goes to the same target
+        let is_color = |binding: &Option<naga::Binding>| match *binding {
+            Some(naga::Binding::Location { blend_src, .. }) => blend_src != Some(1),
+            _ => false,
+        };
+        match self.shader.module.types[result.ty].inner {
+            naga::TypeInner::Struct { ref members, .. } => members
+                .iter()
+                .filter(|member| is_color(&member.binding))
+                .count() as u32,
+            _ => is_color(&result.binding) as u32,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ShaderBinding {
     Texture,
-    TextureArray { count: u32 },
+    TextureArray {
+        count: u32,
+    },
     Sampler,
     Buffer,
-    BufferArray { count: u32 },
+    /// Buffer with an offset that can change between the draws and dispatches
+    /// without binding the group again, see `PipelineEncoder::set_dynamic_offsets`.
+    /// The shaders access the size of their variable from the offset on.
+    DynamicBuffer,
+    BufferArray {
+        count: u32,
+    },
     AccelerationStructure,
-    AccelerationStructureArray { count: u32 },
-    Plain { size: u32 },
+    AccelerationStructureArray {
+        count: u32,
+    },
+    Plain {
+        size: u32,
+    },
 }
 
 pub trait ShaderBindable: Clone + Copy + derive::HasShaderBinding {
@@ -810,6 +1457,10 @@ pub trait ShaderBindable: Clone + Copy + derive::HasShaderBinding {
 struct ShaderDataInfo {
     visibility: ShaderVisibility,
     binding_access: Box<[StorageAccess]>,
+    /// Sizes of the dynamic buffers in the shaders, zero for the other bindings.
+    binding_sizes: Box<[u32]>,
+    /// Bitmask: bit N is set if binding N is a uniform buffer in the shaders.
+    uniform_mask: u64,
 }
 
 #[derive(Clone, Debug, Default, PartialEq)]
@@ -825,6 +1476,44 @@ impl ShaderDataLayout {
         ShaderDataInfo {
             visibility: ShaderVisibility::empty(),
             binding_access: vec![StorageAccess::empty(); self.bindings.len()].into_boxed_slice(),
+            binding_sizes: vec![0; self.bindings.len()].into_boxed_slice(),
+            uniform_mask: 0,
+        }
+    }
+
+    /// Check that the texture binding arrays of the pipeline layouts fit into the limit.
+    fn check_binding_array_size(layouts: &[&Self], limit: u32) -> Result<(), PipelineError> {
+        let count = layouts
+            .iter()
+            .flat_map(|layout| layout.bindings.iter())
+            .map(|&(_, binding)| match binding {
+                ShaderBinding::TextureArray { count } => count,
+                _ => 0,
+            })
+            .sum();
+        if count > limit {
+            Err(PipelineError::TooManyBindingArrayTextures { count, limit })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check that the plain data bindings of the pipeline layouts fit into the limit.
+    fn check_plain_data_size(layouts: &[&Self], limit: u32) -> Result<(), PipelineError> {
+        let oversized = layouts
+            .iter()
+            .flat_map(|layout| layout.bindings.iter())
+            .find_map(|&(name, binding)| match binding {
+                ShaderBinding::Plain { size } if size > limit => Some((name, size)),
+                _ => None,
+            });
+        match oversized {
+            Some((name, size)) => Err(PipelineError::PlainDataTooLarge {
+                binding: name.to_string(),
+                size,
+                limit,
+            }),
+            None => Ok(()),
         }
     }
 }
@@ -886,6 +1575,21 @@ pub struct CommandEncoderDesc<'a> {
     pub buffer_count: u32,
 }
 
+/// Recording state of a command encoder, tracking the reuse of its commands.
+#[allow(dead_code)] // Metal doesn't support reusable encoders
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum RecordingState {
+    /// Commands are recorded for a single submission.
+    #[default]
+    Transient,
+    /// Commands are recorded for multiple submissions.
+    Reusable,
+    /// Reusable commands are finished and can be submitted again.
+    Recorded,
+    /// Commands are discarded, the encoder needs to be started again.
+    Invalid,
+}
+
 pub struct ComputePipelineDesc<'a> {
     pub name: &'a str,
     pub data_layouts: &'a [&'a ShaderDataLayout],
@@ -937,6 +1641,9 @@ pub enum PrimitiveTopology {
 }
 
 /// Vertex winding order which classifies the "front" face of a triangle.
+///
+/// The winding is the one seen on the screen, with Y pointing up in NDC,
+/// on every backend.
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub enum FrontFace {
     /// Triangles with vertices in counter clockwise order are considered the front face.
@@ -1018,6 +1725,12 @@ impl StencilFaceState {
         depth_fail_op: StencilOperation::Keep,
         pass_op: StencilOperation::Keep,
     };
+
+    fn writes(&self) -> bool {
+        [self.fail_op, self.depth_fail_op, self.pass_op]
+            .iter()
+            .any(|&op| op != StencilOperation::Keep)
+    }
 }
 
 impl Default for StencilFaceState {
@@ -1066,6 +1779,22 @@ pub struct DepthStencilState {
     pub bias: DepthBiasState,
 }
 
+impl DepthStencilState {
+    /// Aspects of the depth-stencil target that can be written by the pipeline.
+    fn write_aspects(&self) -> TexelAspects {
+        let mut aspects = TexelAspects::empty();
+        if self.depth_write_enabled {
+            aspects |= TexelAspects::DEPTH;
+        }
+        if self.stencil.write_mask & 0xFF != 0
+            && (self.stencil.front.writes() || self.stencil.back.writes())
+        {
+            aspects |= TexelAspects::STENCIL;
+        }
+        aspects
+    }
+}
+
 /// Alpha blend factor.
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 pub enum BlendFactor {
@@ -1133,6 +1862,43 @@ pub enum BlendOperation {
     Max,
 }
 
+/// Bitwise operation between the fragment output (S) and the target value (D).
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum LogicOp {
+    /// 0
+    Clear,
+    /// S & D
+    And,
+    /// S & !D
+    AndReverse,
+    /// S
+    Copy,
+    /// !S & D
+    AndInverted,
+    /// D
+    NoOp,
+    /// S ^ D
+    Xor,
+    /// S | D
+    Or,
+    /// !(S | D)
+    Nor,
+    /// !(S ^ D)
+    Equivalent,
+    /// !D
+    Invert,
+    /// S | !D
+    OrReverse,
+    /// !S
+    CopyInverted,
+    /// !S | D
+    OrInverted,
+    /// !(S & D)
+    Nand,
+    /// !0
+    Set,
+}
+
 /// Describes a blend component of a [`BlendState`].
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct BlendComponent {
@@ -1257,6 +2023,10 @@ pub struct ColorTargetState {
     pub blend: Option<BlendState>,
     /// Mask which enables/disables writes to different color/alpha channel.
     pub write_mask: ColorWrites,
+    /// Bitwise operation with the target, for integer formats only.
+    /// It applies to all the integer targets of the pipeline,
+    /// so they have to use the same one. See `Capabilities::logic_op`.
+    pub logic_op: Option<LogicOp>,
 }
 
 impl From<TextureFormat> for ColorTargetState {
@@ -1265,6 +2035,7 @@ impl From<TextureFormat> for ColorTargetState {
             format,
             blend: None,
             write_mask: ColorWrites::ALL,
+            logic_op: None,
         }
     }
 }
@@ -1279,6 +2050,69 @@ pub struct RenderPipelineDesc<'a> {
     pub fragment: Option<ShaderFunction<'a>>,
     pub color_targets: &'a [ColorTargetState],
     pub multisample_state: MultisampleState,
+    /// Number of views rendered at once, which has to match
+    /// `RenderTargetSet::multiview` of the passes the pipeline is used in.
+    pub multiview: Option<NonZeroU32>,
+}
+
+impl RenderPipelineDesc<'_> {
+    /// Check that a pipeline without color targets doesn't write any colors.
+    /// Such a pipeline can skip the fragment shader, or use one for depth only.
+    fn check_color_targets(&self) -> Result<(), PipelineError> {
+        match self.fragment {
+            Some(ref fragment) if self.color_targets.is_empty() => {
+                match fragment.color_output_count() {
+                    0 => Ok(()),
+                    count => Err(PipelineError::ColorOutputsWithoutTargets {
+                        fragment: fragment.entry_point.to_string(),
+                        count,
+                    }),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the logic operations of the color targets,
+    /// returning the one used by the pipeline.
+    fn check_logic_op(&self, supported: bool) -> Result<Option<LogicOp>, PipelineError> {
+        let mut logic_op = None;
+        for (index, ct) in self.color_targets.iter().enumerate() {
+            let Some(op) = ct.logic_op else {
+                continue;
+            };
+            if !supported {
+                return Err(PipelineError::LogicOpNotSupported);
+            }
+            if !ct.format.is_integer() {
+                return Err(PipelineError::LogicOpOnNonIntegerTarget {
+                    index: index as u32,
+                    format: ct.format,
+                });
+            }
+            logic_op = Some(op);
+        }
+        if logic_op.is_some()
+            && self
+                .color_targets
+                .iter()
+                .any(|ct| ct.format.is_integer() && ct.logic_op != logic_op)
+        {
+            return Err(PipelineError::MismatchedLogicOps);
+        }
+        Ok(logic_op)
+    }
+
+    /// Check that the views of the pipeline fit into the limit.
+    fn check_multiview(&self, limit: u32) -> Result<(), PipelineError> {
+        match self.multiview {
+            Some(count) if count.get() > limit => Err(PipelineError::TooManyViews {
+                count: count.get(),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -1301,20 +2135,60 @@ impl Default for MultisampleState {
 #[derive(Clone, Copy, Debug)]
 pub enum InitOp {
     Load,
+    /// Clear to a named color, which fits any target.
+    /// White is the maximum value of the integer texels,
+    /// and it clears the depth to 1 and the stencil to all ones.
     Clear(TextureColor),
+    /// Clear a color target to an explicit value.
+    ClearColor(ClearColor),
+    /// Clear the depth and the stencil aspects of a depth-stencil target
+    /// to separate values. An aspect without a value is loaded instead.
+    /// Aspects missing from the target are ignored.
+    ClearDepthStencil {
+        depth: Option<f32>,
+        stencil: Option<u32>,
+    },
     DontCare,
 }
 
+/// Way of picking a single value out of the samples of a multisampled depth target.
+#[derive(Clone, Copy, Debug, Default, Hash, Eq, PartialEq)]
+pub enum DepthResolveMode {
+    /// Value of the first sample.
+    #[default]
+    SampleZero,
+    /// Minimum value of all samples.
+    Min,
+    /// Maximum value of all samples.
+    Max,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum FinishOp {
     Store,
     Discard,
     /// The texture specified here will be stored but it is undefined what
-    /// happens to the original render target
+    /// happens to the original render target.
+    /// Depth targets are resolved with `DepthResolveMode::SampleZero`.
     ResolveTo(TextureView),
+    /// Resolve a multisampled depth target into the texture specified here,
+    /// see `Capabilities::depth_resolve`.
+    /// The original render target is undefined afterwards.
+    ResolveDepthTo(TextureView, DepthResolveMode),
     Ignore,
 }
 
+impl FinishOp {
+    /// Texture to resolve into, and the resolve mode for the depth targets.
+    fn resolve(&self) -> Option<(TextureView, DepthResolveMode)> {
+        match *self {
+            Self::ResolveTo(view) => Some((view, DepthResolveMode::SampleZero)),
+            Self::ResolveDepthTo(view, mode) => Some((view, mode)),
+            Self::Store | Self::Discard | Self::Ignore => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct RenderTarget {
     pub view: TextureView,
@@ -1322,10 +2196,90 @@ pub struct RenderTarget {
     pub finish_op: FinishOp,
 }
 
+impl RenderTarget {
+    /// Value to clear a color target to, given the format of the target view.
+    ///
+    /// Panics if the clear value doesn't fit the format.
+    fn color_clear_value(&self, format: TextureFormat) -> Option<ClearColor> {
+        match self.init_op {
+            InitOp::Clear(color) => Some(if format.is_integer() {
+                ClearColor::Uint(color.uint_value())
+            } else {
+                ClearColor::Float(color.float_value())
+            }),
+            InitOp::ClearColor(color) if color.fits(format) => Some(color),
+            InitOp::ClearColor(_) | InitOp::ClearDepthStencil { .. } => panic!(
+                "Clear value {:?} doesn't fit the color target with {format:?}",
+                self.init_op
+            ),
+            InitOp::Load | InitOp::DontCare => None,
+        }
+    }
+
+    /// Values to clear the depth and the stencil aspects of a target to,
+    /// given the format of the target view.
+    /// An aspect without a value is not cleared.
+    ///
+    /// Panics if the clear value doesn't fit the format.
+    fn depth_stencil_clear_values(&self, format: TextureFormat) -> (Option<f32>, Option<u32>) {
+        match self.init_op {
+            InitOp::Clear(color) => (
+                Some(color.depth_clear_value()),
+                Some(color.stencil_clear_value()),
+            ),
+            InitOp::ClearDepthStencil { depth, stencil } => (depth, stencil),
+            InitOp::ClearColor(_) => panic!(
+                "Clear value {:?} doesn't fit the depth-stencil target with {format:?}",
+                self.init_op
+            ),
+            InitOp::Load | InitOp::DontCare => (None, None),
+        }
+    }
+}
+
+/// Targets of a render pass.
+///
+/// The colors can be empty for a depth-only pass, such as a shadow map
+/// or a depth prepass, as long as there is a depth target.
 #[derive(Debug)]
 pub struct RenderTargetSet<'a> {
     pub colors: &'a [RenderTarget],
     pub depth_stencil: Option<RenderTarget>,
+    /// Aspects of the depth-stencil target that are only read by the pass.
+    /// They have to be loaded, and the pipelines of the pass can't write them.
+    /// In exchange, the shaders can sample them at the same time,
+    /// using a view of the aspect, such as a `Depth32Float` view
+    /// of a `Depth32FloatStencil8Uint` texture.
+    pub depth_stencil_read_only: TexelAspects,
+    /// Number of views rendered at once, such as the eyes of a stereo view,
+    /// see `Capabilities::max_view_count`. Every draw is broadcast to all the views.
+    /// Each of them renders into its own layer of the targets, which have to be
+    /// `ViewDimension::D2Array` views, and is identified by `@builtin(view_index)`
+    /// in the shaders. Per-view data, like the cameras, can be indexed by it.
+    pub multiview: Option<NonZeroU32>,
+}
+
+impl RenderTargetSet<'_> {
+    /// Aspects of the depth-stencil target that are written by the pass,
+    /// given the aspects of the target view.
+    fn depth_stencil_writes(&self, aspects: TexelAspects) -> TexelAspects {
+        assert!(
+            aspects.contains(self.depth_stencil_read_only),
+            "Read-only aspects {:?} are not in the depth-stencil target with {:?}",
+            self.depth_stencil_read_only,
+            aspects,
+        );
+        if let Some(ref rt) = self.depth_stencil
+            && !self.depth_stencil_read_only.is_empty()
+        {
+            assert!(
+                matches!(rt.init_op, InitOp::Load),
+                "Read-only depth-stencil aspects have to be loaded, not {:?}",
+                rt.init_op
+            );
+        }
+        aspects - self.depth_stencil_read_only
+    }
 }
 
 /// Mechanism used to acquire frames and display them on screen.
@@ -1421,3 +2375,94 @@ pub struct Viewport {
 }
 
 pub type Timings = Vec<(String, std::time::Duration)>;
+
+/// Names of the timed passes, kept in a single string
+/// to avoid allocating for every pass.
+#[derive(Debug, Default)]
+pub(crate) struct PassNames {
+    data: String,
+    ends: Vec<usize>,
+    /// Recording index of the encoder, in which the passes are timed.
+    pub(crate) recording: u64,
+}
+
+impl PassNames {
+    pub(crate) fn push(&mut self, name: &str) {
+        self.data.push_str(name);
+        self.ends.push(self.data.len());
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.data.clear();
+        self.ends.clear();
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &str> {
+        let mut start = 0;
+        self.ends.iter().map(move |&end| {
+            let name = &self.data[start..end];
+            start = end;
+            name
+        })
+    }
+
+    pub(crate) fn retained_bytes(&self) -> usize {
+        self.data.capacity() + self.ends.capacity() * mem::size_of::<usize>()
+    }
+}
+
+/// Replace the contents of `timings`, reusing the allocated names.
+pub(crate) fn fill_timings<'a>(
+    timings: &mut Timings,
+    passes: impl Iterator<Item = (&'a str, std::time::Duration)>,
+) {
+    let mut count = 0;
+    for (name, duration) in passes {
+        match timings.get_mut(count) {
+            Some(&mut (ref mut old_name, ref mut old_duration)) => {
+                old_name.clear();
+                old_name.push_str(name);
+                *old_duration = duration;
+            }
+            None => timings.push((name.to_string(), duration)),
+        }
+        count += 1;
+    }
+    timings.truncate(count);
+}
+
+pub(crate) fn timings_retained_bytes(timings: &Timings) -> usize {
+    timings.capacity() * mem::size_of::<(String, std::time::Duration)>()
+        + timings
+            .iter()
+            .map(|timing| timing.0.capacity())
+            .sum::<usize>()
+}
+
+/// Memory kept by a command encoder for recording,
+/// which is reused across the `start` calls instead of being allocated again.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EncoderMemoryStats {
+    /// Bytes of the internal containers currently retained.
+    pub retained_bytes: usize,
+    /// Highest number of retained bytes since the encoder was created.
+    pub peak_retained_bytes: usize,
+}
+
+/// Counters of the shader data bindings recorded by a command encoder.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BindingStats {
+    /// Bindings that were passed on to the backend.
+    pub issued: u32,
+    /// Bindings that were skipped, since the same data was already bound
+    /// at the same group of the current pipeline.
+    pub skipped: u32,
+}
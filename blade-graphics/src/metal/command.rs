@@ -2,7 +2,7 @@ use objc2_foundation::{NSArray, NSRange, NSString};
 use objc2_metal::{
     self as metal, MTLAccelerationStructureCommandEncoder as _, MTLBlitCommandEncoder,
     MTLCommandBuffer as _, MTLCommandEncoder, MTLComputeCommandEncoder as _,
-    MTLCounterSampleBuffer, MTLRenderCommandEncoder,
+    MTLCounterSampleBuffer, MTLRenderCommandEncoder, MTLTexture as _,
 };
 use std::{marker::PhantomData, mem, ptr::NonNull, slice, time::Duration};
 
@@ -44,9 +44,52 @@ impl crate::ShaderBindable for super::TextureView {
         }
     }
 }
+impl crate::ShaderBindable for crate::Nullable<super::TextureView> {
+    fn bind_to(&self, ctx: &mut super::PipelineContext, index: u32) {
+        let view = match self.0 {
+            Some(view) => view,
+            None => ctx.dummies.expect("No dummy resources").black,
+        };
+        view.bind_to(ctx, index);
+    }
+}
 impl<'a, const N: crate::ResourceIndex> crate::ShaderBindable for &'a crate::TextureArray<N> {
-    fn bind_to(&self, _ctx: &mut super::PipelineContext, _index: u32) {
-        unimplemented!()
+    fn bind_to(&self, ctx: &mut super::PipelineContext, index: u32) {
+        assert!(self.data.len() <= N as usize);
+        let slot = ctx.targets[index as usize] as _;
+        let device = match (ctx.cs_encoder, ctx.vs_encoder.or(ctx.fs_encoder)) {
+            (Some(encoder), _) => encoder.device(),
+            (None, Some(encoder)) => encoder.device(),
+            (None, None) => return,
+        };
+        // Fill the whole array, like the other backends
+        let count = if self.data.is_empty() { 0 } else { N as usize };
+        let buffer = ctx.arguments.encode_textures(
+            &device,
+            self.data
+                .iter()
+                .map(|view| view.as_ref())
+                .cycle()
+                .take(count),
+            count,
+        );
+        let value = Some(buffer);
+        unsafe {
+            if let Some(encoder) = ctx.vs_encoder {
+                encoder.setVertexBuffer_offset_atIndex(value, 0, slot);
+            }
+            if let Some(encoder) = ctx.fs_encoder {
+                encoder.setFragmentBuffer_offset_atIndex(value, 0, slot);
+            }
+            if let Some(encoder) = ctx.cs_encoder {
+                encoder.setBuffer_offset_atIndex(value, 0, slot);
+            }
+        }
+        ctx.use_resources(
+            self.data.iter().map(|view| {
+                view.raw as *mut objc2::runtime::ProtocolObject<dyn metal::MTLResource>
+            }),
+        );
     }
 }
 impl crate::ShaderBindable for super::Sampler {
@@ -66,8 +109,41 @@ impl crate::ShaderBindable for super::Sampler {
         }
     }
 }
+fn reset_dynamic_buffers(
+    dynamic_buffers: &mut Vec<Vec<(u32, u64)>>,
+    group: u32,
+) -> &mut Vec<(u32, u64)> {
+    let group_index = group as usize;
+    if dynamic_buffers.len() <= group_index {
+        dynamic_buffers.resize_with(group_index + 1, Vec::new);
+    }
+    let list = &mut dynamic_buffers[group_index];
+    list.clear();
+    list
+}
+
+fn bound_dynamic_buffers<'a>(
+    dynamic_buffers: &'a [Vec<(u32, u64)>],
+    group: u32,
+    offsets: &[u32],
+) -> &'a [(u32, u64)] {
+    let list = dynamic_buffers
+        .get(group as usize)
+        .map_or(&[][..], Vec::as_slice);
+    assert_eq!(
+        offsets.len(),
+        list.len(),
+        "Group {group} has {} bound dynamic buffers",
+        list.len()
+    );
+    list
+}
+
 impl crate::ShaderBindable for crate::BufferPiece {
     fn bind_to(&self, ctx: &mut super::PipelineContext, index: u32) {
+        if ctx.dynamic_mask & (1 << index) != 0 {
+            ctx.dynamic_buffers.push((index, self.offset));
+        }
         let slot = ctx.targets[index as usize] as _;
         let value = Some(self.buffer.as_ref());
         unsafe {
@@ -83,6 +159,15 @@ impl crate::ShaderBindable for crate::BufferPiece {
         }
     }
 }
+impl crate::ShaderBindable for crate::Nullable<crate::BufferPiece> {
+    fn bind_to(&self, ctx: &mut super::PipelineContext, index: u32) {
+        let piece = match self.0 {
+            Some(piece) => piece,
+            None => ctx.dummies.expect("No dummy resources").buffer.into(),
+        };
+        piece.bind_to(ctx, index);
+    }
+}
 impl<'a, const N: crate::ResourceIndex> crate::ShaderBindable for &'a crate::BufferArray<N> {
     fn bind_to(&self, _ctx: &mut super::PipelineContext, _index: u32) {
         unimplemented!()
@@ -169,15 +254,111 @@ impl<'a, const N: crate::ResourceIndex> crate::ShaderBindable
     }
 }
 
+impl super::ArgumentBuffers {
+    /// Encode the textures into a buffer of the current pool.
+    fn encode_textures<'t>(
+        &mut self,
+        device: &objc2::runtime::ProtocolObject<dyn metal::MTLDevice>,
+        textures: impl Iterator<Item = &'t objc2::runtime::ProtocolObject<dyn metal::MTLTexture>>,
+        count: usize,
+    ) -> &objc2::runtime::ProtocolObject<dyn metal::MTLBuffer> {
+        use metal::{MTLArgumentEncoder as _, MTLBuffer as _, MTLDevice as _};
+
+        let encoder = self.texture_encoder.get_or_insert_with(|| unsafe {
+            let descriptor = metal::MTLArgumentDescriptor::new();
+            descriptor.setDataType(metal::MTLDataType::Texture);
+            descriptor.setIndex(0);
+            descriptor.setTextureType(metal::MTLTextureType::Type2D);
+            descriptor.setAccess(metal::MTLBindingAccess::ReadOnly);
+            device
+                .newArgumentEncoderWithArguments(&NSArray::from_retained_slice(&[descriptor]))
+                .unwrap()
+        });
+        let stride = encoder.encodedLength();
+        let size = (stride * count).max(stride);
+
+        let pool = &mut self.pools[0];
+        let index = pool.used_count;
+        pool.used_count += 1;
+        let reusable = pool
+            .buffers
+            .get(index)
+            .is_some_and(|buffer| buffer.length() >= size);
+        if !reusable {
+            let buffer = device
+                .newBufferWithLength_options(size, metal::MTLResourceOptions::StorageModeShared)
+                .unwrap();
+            if index < pool.buffers.len() {
+                pool.buffers[index] = buffer;
+            } else {
+                pool.buffers.push(buffer);
+            }
+        }
+        let buffer: &objc2::runtime::ProtocolObject<dyn metal::MTLBuffer> = &pool.buffers[index];
+
+        for (i, texture) in textures.enumerate() {
+            unsafe {
+                encoder.setArgumentBuffer_offset(Some(buffer), i * stride);
+                encoder.setTexture_atIndex(Some(texture), 0);
+            }
+        }
+        buffer
+    }
+}
+
+impl super::PipelineContext<'_> {
+    /// Make the resources referenced by argument buffers resident,
+    /// skipping the ones that already are in the current pass.
+    fn use_resources(
+        &mut self,
+        resources: impl Iterator<Item = *mut objc2::runtime::ProtocolObject<dyn metal::MTLResource>>,
+    ) {
+        let arguments = &mut *self.arguments;
+        arguments.pending.clear();
+        for raw in resources {
+            if arguments.resident.insert(raw) {
+                arguments.pending.push(NonNull::new(raw).unwrap());
+            }
+        }
+        if arguments.pending.is_empty() {
+            return;
+        }
+        let list = NonNull::new(arguments.pending.as_mut_ptr()).unwrap();
+        let count = arguments.pending.len();
+        unsafe {
+            if let Some(encoder) = self.cs_encoder {
+                encoder.useResources_count_usage(list, count, metal::MTLResourceUsage::Read);
+            }
+            // Resident resources are skipped in the later binds of the pass,
+            // so make them available to both stages.
+            if let Some(encoder) = self.vs_encoder.or(self.fs_encoder) {
+                encoder.useResources_count_usage_stages(
+                    list,
+                    count,
+                    metal::MTLResourceUsage::Read,
+                    metal::MTLRenderStages::Vertex | metal::MTLRenderStages::Fragment,
+                );
+            }
+        }
+    }
+}
+
 impl super::TimingData {
     fn add(&mut self, label: &str) -> usize {
         let counter_index = self.pass_names.len() * 2;
-        self.pass_names.push(label.to_string());
+        self.pass_names.push(label);
         counter_index
     }
 }
 
 impl super::CommandEncoder {
+    fn retained_bytes(&self) -> usize {
+        crate::timings_retained_bytes(&self.timings)
+            + self.timing_datas.as_ref().map_or(0, |tds| {
+                tds.iter().map(|td| td.pass_names.retained_bytes()).sum()
+            })
+    }
+
     fn begin_pass(&mut self, label: &str) {
         if self.enable_debug_groups {
             //HACK: close the previous group
@@ -195,7 +376,9 @@ impl super::CommandEncoder {
         if self.has_open_debug_group {
             self.raw.as_mut().unwrap().popDebugGroup();
         }
-        self.raw.take().unwrap()
+        self.raw
+            .take()
+            .expect("Command encoder is not started, or invalidated")
     }
 
     pub fn transfer(&mut self, label: &str) -> super::TransferCommandEncoder<'_> {
@@ -221,7 +404,8 @@ impl super::CommandEncoder {
         });
         super::TransferCommandEncoder {
             raw,
-            phantom: PhantomData,
+            cmd_buf: self.raw.as_ref().unwrap(),
+            blitter: &self.blitter,
         }
     }
 
@@ -278,10 +462,13 @@ impl super::CommandEncoder {
                 .computeCommandEncoderWithDescriptor(&descriptor)
                 .unwrap()
         });
+        self.arguments.resident.clear();
         super::ComputeCommandEncoder {
             raw,
             enable_debug_groups: self.enable_debug_groups,
-            phantom: PhantomData,
+            binding_stats: &mut self.binding_stats,
+            arguments: &mut self.arguments,
+            dummies: self.dummies,
         }
     }
 
@@ -290,6 +477,10 @@ impl super::CommandEncoder {
         label: &str,
         targets: crate::RenderTargetSet,
     ) -> super::RenderCommandEncoder<'_> {
+        assert!(
+            !targets.colors.is_empty() || targets.depth_stencil.is_some(),
+            "Render pass '{label}' has neither color nor depth targets"
+        );
         let raw = objc2::rc::autoreleasepool(|_| {
             let descriptor = unsafe { metal::MTLRenderPassDescriptor::new() };
 
@@ -300,9 +491,11 @@ impl super::CommandEncoder {
 
                 let load_action = match rt.init_op {
                     crate::InitOp::Load => metal::MTLLoadAction::Load,
-                    crate::InitOp::Clear(color) => {
-                        let clear_color = map_clear_color(color);
-                        at_descriptor.setClearColor(clear_color);
+                    crate::InitOp::Clear(_)
+                    | crate::InitOp::ClearColor(_)
+                    | crate::InitOp::ClearDepthStencil { .. } => {
+                        let color = rt.color_clear_value(rt.view.format).unwrap();
+                        at_descriptor.setClearColor(map_clear_color(color));
                         metal::MTLLoadAction::Clear
                     }
                     crate::InitOp::DontCare => metal::MTLLoadAction::DontCare,
@@ -314,7 +507,8 @@ impl super::CommandEncoder {
                         metal::MTLStoreAction::Store
                     }
                     crate::FinishOp::Discard => metal::MTLStoreAction::DontCare,
-                    crate::FinishOp::ResolveTo(ref view) => {
+                    crate::FinishOp::ResolveTo(ref view)
+                    | crate::FinishOp::ResolveDepthTo(ref view, _) => {
                         at_descriptor.setResolveTexture(Some(view.as_ref()));
                         metal::MTLStoreAction::MultisampleResolve
                     }
@@ -323,15 +517,23 @@ impl super::CommandEncoder {
             }
 
             if let Some(ref rt) = targets.depth_stencil {
+                // Read-only aspects are still stored, since their contents are preserved
+                let _ = targets.depth_stencil_writes(rt.view.aspects);
                 if rt.view.aspects.contains(crate::TexelAspects::DEPTH) {
                     let at_descriptor = descriptor.depthAttachment();
                     at_descriptor.setTexture(Some(rt.view.as_ref()));
                     let load_action = match rt.init_op {
                         crate::InitOp::Load => metal::MTLLoadAction::Load,
-                        crate::InitOp::Clear(color) => {
-                            let clear_depth = color.depth_clear_value();
-                            at_descriptor.setClearDepth(clear_depth as f64);
-                            metal::MTLLoadAction::Clear
+                        crate::InitOp::Clear(_)
+                        | crate::InitOp::ClearColor(_)
+                        | crate::InitOp::ClearDepthStencil { .. } => {
+                            match rt.depth_stencil_clear_values(rt.view.format) {
+                                (Some(clear_depth), _) => {
+                                    at_descriptor.setClearDepth(clear_depth as f64);
+                                    metal::MTLLoadAction::Clear
+                                }
+                                (None, _) => metal::MTLLoadAction::Load,
+                            }
                         }
                         crate::InitOp::DontCare => metal::MTLLoadAction::DontCare,
                     };
@@ -340,7 +542,22 @@ impl super::CommandEncoder {
                             metal::MTLStoreAction::Store
                         }
                         crate::FinishOp::Discard => metal::MTLStoreAction::DontCare,
-                        crate::FinishOp::ResolveTo(_) => panic!("Can't resolve depth texture"),
+                        crate::FinishOp::ResolveTo(_) | crate::FinishOp::ResolveDepthTo(..) => {
+                            let (view, mode) = rt.finish_op.resolve().unwrap();
+                            at_descriptor.setResolveTexture(Some(view.as_ref()));
+                            at_descriptor.setDepthResolveFilter(match mode {
+                                crate::DepthResolveMode::SampleZero => {
+                                    metal::MTLMultisampleDepthResolveFilter::Sample0
+                                }
+                                crate::DepthResolveMode::Min => {
+                                    metal::MTLMultisampleDepthResolveFilter::Min
+                                }
+                                crate::DepthResolveMode::Max => {
+                                    metal::MTLMultisampleDepthResolveFilter::Max
+                                }
+                            });
+                            metal::MTLStoreAction::MultisampleResolve
+                        }
                     };
                     at_descriptor.setLoadAction(load_action);
                     at_descriptor.setStoreAction(store_action);
@@ -352,10 +569,16 @@ impl super::CommandEncoder {
 
                     let load_action = match rt.init_op {
                         crate::InitOp::Load => metal::MTLLoadAction::Load,
-                        crate::InitOp::Clear(color) => {
-                            let clear_stencil = color.stencil_clear_value();
-                            at_descriptor.setClearStencil(clear_stencil);
-                            metal::MTLLoadAction::Clear
+                        crate::InitOp::Clear(_)
+                        | crate::InitOp::ClearColor(_)
+                        | crate::InitOp::ClearDepthStencil { .. } => {
+                            match rt.depth_stencil_clear_values(rt.view.format) {
+                                (_, Some(clear_stencil)) => {
+                                    at_descriptor.setClearStencil(clear_stencil);
+                                    metal::MTLLoadAction::Clear
+                                }
+                                (_, None) => metal::MTLLoadAction::Load,
+                            }
                         }
                         crate::InitOp::DontCare => metal::MTLLoadAction::DontCare,
                     };
@@ -364,7 +587,14 @@ impl super::CommandEncoder {
                             metal::MTLStoreAction::Store
                         }
                         crate::FinishOp::Discard => metal::MTLStoreAction::DontCare,
-                        crate::FinishOp::ResolveTo(_) => panic!("Can't resolve stencil texture"),
+                        crate::FinishOp::ResolveTo(ref view)
+                        | crate::FinishOp::ResolveDepthTo(ref view, _) => {
+                            at_descriptor.setResolveTexture(Some(view.as_ref()));
+                            at_descriptor.setStencilResolveFilter(
+                                metal::MTLMultisampleStencilResolveFilter::Sample0,
+                            );
+                            metal::MTLStoreAction::MultisampleResolve
+                        }
                     };
 
                     at_descriptor.setLoadAction(load_action);
@@ -385,17 +615,43 @@ impl super::CommandEncoder {
                 }
             }
 
-            self.raw
+            if let Some(count) = targets.multiview {
+                descriptor.setRenderTargetArrayLength(count.get() as usize);
+            }
+
+            let encoder = self
+                .raw
                 .as_mut()
                 .unwrap()
                 .renderCommandEncoderWithDescriptor(&descriptor)
-                .unwrap()
+                .unwrap();
+            if let Some(count) = targets.multiview {
+                // Every view renders into the layer of the same index
+                let mut mappings = [metal::MTLVertexAmplificationViewMapping {
+                    viewportArrayIndexOffset: 0,
+                    renderTargetArrayIndexOffset: 0,
+                }; super::MAX_VIEWS];
+                for (index, mapping) in mappings.iter_mut().enumerate() {
+                    mapping.renderTargetArrayIndexOffset = index as u32;
+                }
+                unsafe {
+                    encoder.setVertexAmplificationCount_viewMappings(
+                        count.get() as usize,
+                        mappings.as_ptr(),
+                    )
+                };
+            }
+            encoder
         });
 
+        self.arguments.resident.clear();
         super::RenderCommandEncoder {
             raw,
             enable_debug_groups: self.enable_debug_groups,
-            phantom: PhantomData,
+            binding_stats: &mut self.binding_stats,
+            arguments: &mut self.arguments,
+            dummies: self.dummies,
+            depth_stencil_read_only: targets.depth_stencil_read_only,
         }
     }
 }
@@ -406,11 +662,18 @@ impl crate::traits::CommandEncoder for super::CommandEncoder {
     type Frame = super::Frame;
 
     fn start(&mut self) {
+        self.peak_retained_bytes = self.peak_retained_bytes.max(self.retained_bytes());
+        self.binding_stats = Default::default();
+        self.arguments.pools.rotate_left(1);
+        self.arguments.pools[0].used_count = 0;
+        self.recording_index += 1;
         if let Some(ref mut td_array) = self.timing_datas {
-            self.timings.clear();
             td_array.rotate_left(1);
             let td = td_array.first_mut().unwrap();
-            if !td.pass_names.is_empty() {
+            if td.pass_names.is_empty() {
+                self.timings.clear();
+                self.timings_recording_index = None;
+            } else {
                 let ns_data = unsafe {
                     td.sample_buffer
                         .resolveCounterRange(NSRange::new(0, td.pass_names.len() * 2))
@@ -422,11 +685,14 @@ impl crate::traits::CommandEncoder for super::CommandEncoder {
                         ns_data.len() / mem::size_of::<u64>(),
                     )
                 };
-                for (name, chunk) in td.pass_names.drain(..).zip(counters.chunks(2)) {
-                    let duration = Duration::from_nanos(chunk[1] - chunk[0]);
-                    self.timings.push((name, duration));
-                }
+                let durations = counters
+                    .chunks(2)
+                    .map(|chunk| Duration::from_nanos(chunk[1] - chunk[0]));
+                crate::fill_timings(&mut self.timings, td.pass_names.iter().zip(durations));
+                self.timings_recording_index = Some(td.pass_names.recording);
+                td.pass_names.clear();
             }
+            td.pass_names.recording = self.recording_index;
         }
 
         let queue = self.queue.lock().unwrap();
@@ -439,17 +705,51 @@ impl crate::traits::CommandEncoder for super::CommandEncoder {
             cmd_buf
         }));
         self.has_open_debug_group = false;
+        self.present = None;
+    }
+
+    fn start_reusable(&mut self) {
+        panic!(
+            "Reusable command encoders are not supported, see `Capabilities::reusable_command_encoders`"
+        );
+    }
+
+    fn invalidate(&mut self) {
+        self.raw = None;
+        self.has_open_debug_group = false;
+        self.present = None;
     }
 
     fn init_texture(&mut self, _texture: super::Texture) {}
 
     fn present(&mut self, frame: super::Frame) {
-        self.raw.as_mut().unwrap().presentDrawable(&frame.drawable);
+        assert!(self.raw.is_some(), "Command encoder is not started");
+        self.present = Some(frame.drawable);
     }
 
     fn timings(&self) -> &crate::Timings {
         &self.timings
     }
+
+    fn recording_index(&self) -> u64 {
+        self.recording_index
+    }
+
+    fn timings_recording_index(&self) -> Option<u64> {
+        self.timings_recording_index
+    }
+
+    fn binding_stats(&self) -> crate::BindingStats {
+        self.binding_stats
+    }
+
+    fn memory_stats(&self) -> crate::EncoderMemoryStats {
+        let retained_bytes = self.retained_bytes();
+        crate::EncoderMemoryStats {
+            retained_bytes,
+            peak_retained_bytes: self.peak_retained_bytes.max(retained_bytes),
+        }
+    }
 }
 
 #[hidden_trait::expose]
@@ -489,6 +789,16 @@ impl crate::traits::TransferEncoder for super::TransferCommandEncoder<'_> {
         dst: crate::TexturePiece,
         size: crate::Extent,
     ) {
+        crate::debug_check_region(&src, size);
+        crate::debug_check_region(&dst, size);
+        let (src_format, dst_format) = (
+            src.texture.as_ref().pixelFormat(),
+            dst.texture.as_ref().pixelFormat(),
+        );
+        assert_eq!(
+            src_format, dst_format,
+            "Texels can only be copied between the same formats, use `blit` to convert"
+        );
         unsafe {
             self.raw.copyFromTexture_sourceSlice_sourceLevel_sourceOrigin_sourceSize_toTexture_destinationSlice_destinationLevel_destinationOrigin(
                 src.texture.as_ref(),
@@ -504,6 +814,95 @@ impl crate::traits::TransferEncoder for super::TransferCommandEncoder<'_> {
         };
     }
 
+    fn blit(
+        &mut self,
+        src: crate::TexturePiece,
+        src_size: crate::Extent,
+        dst: crate::TexturePiece,
+        dst_size: crate::Extent,
+        filter: crate::FilterMode,
+    ) {
+        crate::debug_check_region(&src, src_size);
+        crate::debug_check_region(&dst, dst_size);
+        let src_texture = src.texture.as_ref();
+        let dst_texture = dst.texture.as_ref();
+        let (src_format, dst_format) = (src_texture.pixelFormat(), dst_texture.pixelFormat());
+        let depth_stencil_formats = [
+            metal::MTLPixelFormat::Depth32Float,
+            metal::MTLPixelFormat::Depth32Float_Stencil8,
+            metal::MTLPixelFormat::Stencil8,
+        ];
+        assert!(
+            !depth_stencil_formats.contains(&src_format)
+                && !depth_stencil_formats.contains(&dst_format),
+            "Only color textures can be blitted, not {:?} to {:?}",
+            src_format,
+            dst_format
+        );
+        assert!(
+            src_size.depth == 1 && dst_size.depth == 1,
+            "Only 2D regions can be blitted"
+        );
+        if src_format == dst_format && src_size == dst_size {
+            self.copy_texture_to_texture(src, dst, src_size);
+            return;
+        }
+
+        // Scaling and conversion need a render pass, which interrupts the blit encoder
+        self.raw.endEncoding();
+        let pipeline = self.blitter.lock().unwrap().pipeline(dst_format, filter);
+        let src_view = unsafe {
+            src_texture
+                .newTextureViewWithPixelFormat_textureType_levels_slices(
+                    src_format,
+                    metal::MTLTextureType::Type2D,
+                    NSRange::new(src.mip_level as usize, 1),
+                    NSRange::new(src.array_layer as usize, 1),
+                )
+                .unwrap()
+        };
+        let src_width = (src_texture.width() >> src.mip_level).max(1) as f32;
+        let src_height = (src_texture.height() >> src.mip_level).max(1) as f32;
+        let rect = [
+            src.origin[0] as f32 / src_width,
+            src.origin[1] as f32 / src_height,
+            (src.origin[0] + src_size.width) as f32 / src_width,
+            (src.origin[1] + src_size.height) as f32 / src_height,
+        ];
+        objc2::rc::autoreleasepool(|_| unsafe {
+            let descriptor = metal::MTLRenderPassDescriptor::new();
+            let at_descriptor = descriptor.colorAttachments().objectAtIndexedSubscript(0);
+            at_descriptor.setTexture(Some(dst_texture));
+            at_descriptor.setLevel(dst.mip_level as usize);
+            at_descriptor.setSlice(dst.array_layer as usize);
+            at_descriptor.setLoadAction(metal::MTLLoadAction::Load);
+            at_descriptor.setStoreAction(metal::MTLStoreAction::Store);
+
+            let encoder = self
+                .cmd_buf
+                .renderCommandEncoderWithDescriptor(&descriptor)
+                .unwrap();
+            encoder.setRenderPipelineState(&pipeline);
+            encoder.setViewport(metal::MTLViewport {
+                originX: dst.origin[0] as _,
+                originY: dst.origin[1] as _,
+                width: dst_size.width as _,
+                height: dst_size.height as _,
+                znear: 0.0,
+                zfar: 1.0,
+            });
+            encoder.setVertexBytes_length_atIndex(
+                NonNull::new_unchecked(rect.as_ptr() as *mut _),
+                mem::size_of_val(&rect),
+                0,
+            );
+            encoder.setFragmentTexture_atIndex(Some(&src_view), 0);
+            encoder.drawPrimitives_vertexStart_vertexCount(metal::MTLPrimitiveType::Triangle, 0, 3);
+            encoder.endEncoding();
+        });
+        self.raw = self.cmd_buf.blitCommandEncoder().unwrap();
+    }
+
     fn copy_buffer_to_texture(
         &mut self,
         src: crate::BufferPiece,
@@ -511,6 +910,7 @@ impl crate::traits::TransferEncoder for super::TransferCommandEncoder<'_> {
         dst: crate::TexturePiece,
         size: crate::Extent,
     ) {
+        crate::debug_check_region(&dst, size);
         unsafe {
             self.raw.copyFromBuffer_sourceOffset_sourceBytesPerRow_sourceBytesPerImage_sourceSize_toTexture_destinationSlice_destinationLevel_destinationOrigin_options(
                 src.buffer.as_ref(),
@@ -534,6 +934,7 @@ impl crate::traits::TransferEncoder for super::TransferCommandEncoder<'_> {
         bytes_per_row: u32,
         size: crate::Extent,
     ) {
+        crate::debug_check_region(&src, size);
         unsafe {
             self.raw.copyFromTexture_sourceSlice_sourceLevel_sourceOrigin_sourceSize_toBuffer_destinationOffset_destinationBytesPerRow_destinationBytesPerImage_options(
                 src.texture.as_ref(),
@@ -634,6 +1035,18 @@ impl crate::traits::AccelerationStructureEncoder
     }
 }
 
+impl super::AccelerationStructureCommandEncoder<'_> {
+    pub fn build_top_level_motion(
+        &mut self,
+        _acceleration_structure: super::AccelerationStructure,
+        _instance_count: u32,
+        _instance_data: crate::BufferPiece,
+        _scratch_data: crate::BufferPiece,
+    ) -> Result<(), crate::ResourceError> {
+        Err(crate::ResourceError::MotionNotSupported)
+    }
+}
+
 impl Drop for super::AccelerationStructureCommandEncoder<'_> {
     fn drop(&mut self) {
         self.raw.endEncoding();
@@ -684,6 +1097,10 @@ impl super::ComputeCommandEncoder<'_> {
             wg_size: pipeline.wg_size,
             group_mappings: &pipeline.layout.group_mappings,
             enable_debug_groups: self.enable_debug_groups,
+            binding_stats: self.binding_stats,
+            arguments: self.arguments,
+            dynamic_buffers: Vec::new(),
+            dummies: self.dummies,
         }
     }
 }
@@ -739,6 +1156,13 @@ impl super::RenderCommandEncoder<'_> {
         &'p mut self,
         pipeline: &'p super::RenderPipeline,
     ) -> super::RenderPipelineContext<'p> {
+        debug_assert!(
+            !pipeline
+                .depth_stencil_writes
+                .intersects(self.depth_stencil_read_only),
+            "Pipeline writes {:?} of the depth-stencil target, which is read-only in the pass",
+            pipeline.depth_stencil_writes & self.depth_stencil_read_only,
+        );
         if self.enable_debug_groups {
             self.raw.pushDebugGroup(&NSString::from_str(&pipeline.name));
         }
@@ -778,8 +1202,20 @@ impl super::RenderCommandEncoder<'_> {
             primitive_type: pipeline.primitive_type,
             group_mappings: &pipeline.layout.group_mappings,
             enable_debug_groups: self.enable_debug_groups,
+            binding_stats: self.binding_stats,
+            arguments: self.arguments,
+            dynamic_buffers: Vec::new(),
+            dummies: self.dummies,
         }
     }
+
+    pub fn begin_conditional(&mut self, _predicate: crate::BufferPiece) {
+        unimplemented!("Conditional rendering")
+    }
+
+    pub fn end_conditional(&mut self) {
+        unimplemented!("Conditional rendering")
+    }
 }
 
 impl Drop for super::RenderCommandEncoder<'_> {
@@ -792,6 +1228,8 @@ impl Drop for super::RenderCommandEncoder<'_> {
 impl crate::traits::PipelineEncoder for super::ComputePipelineContext<'_> {
     fn bind<D: crate::ShaderData>(&mut self, group: u32, data: &D) {
         let info = &self.group_mappings[group as usize];
+        self.binding_stats.issued += 1;
+        let dynamic_buffers = reset_dynamic_buffers(&mut self.dynamic_buffers, group);
 
         data.fill(super::PipelineContext {
             cs_encoder: if info.visibility.contains(crate::ShaderVisibility::COMPUTE) {
@@ -802,7 +1240,24 @@ impl crate::traits::PipelineEncoder for super::ComputePipelineContext<'_> {
             vs_encoder: None,
             fs_encoder: None,
             targets: &info.targets,
+            arguments: self.arguments,
+            dynamic_mask: info.dynamic_mask,
+            dynamic_buffers,
+            dummies: self.dummies,
         });
+        dynamic_buffers.sort_by_key(|&(index, _)| index);
+    }
+
+    fn set_dynamic_offsets(&mut self, group: u32, offsets: &[u32]) {
+        let info = &self.group_mappings[group as usize];
+        let dynamic_buffers = bound_dynamic_buffers(&self.dynamic_buffers, group, offsets);
+        for (&(index, base), &offset) in dynamic_buffers.iter().zip(offsets) {
+            let slot = info.targets[index as usize] as usize;
+            unsafe {
+                self.encoder
+                    .setBufferOffset_atIndex((base + offset as u64) as usize, slot);
+            }
+        }
     }
 }
 
@@ -821,6 +1276,7 @@ impl crate::traits::ComputePipelineEncoder for super::ComputePipelineContext<'_>
     }
 
     fn dispatch_indirect(&mut self, indirect_buf: crate::BufferPiece) {
+        crate::indirect::debug_check_indirect::<crate::DispatchIndirectArgs>(&indirect_buf);
         unsafe {
             self.encoder
                 .dispatchThreadgroupsWithIndirectBuffer_indirectBufferOffset_threadsPerThreadgroup(
@@ -844,6 +1300,8 @@ impl Drop for super::ComputePipelineContext<'_> {
 impl crate::traits::PipelineEncoder for super::RenderPipelineContext<'_> {
     fn bind<D: crate::ShaderData>(&mut self, group: u32, data: &D) {
         let info = &self.group_mappings[group as usize];
+        self.binding_stats.issued += 1;
+        let dynamic_buffers = reset_dynamic_buffers(&mut self.dynamic_buffers, group);
 
         data.fill(super::PipelineContext {
             cs_encoder: None,
@@ -858,7 +1316,29 @@ impl crate::traits::PipelineEncoder for super::RenderPipelineContext<'_> {
                 None
             },
             targets: &info.targets,
+            arguments: self.arguments,
+            dynamic_mask: info.dynamic_mask,
+            dynamic_buffers,
+            dummies: self.dummies,
         });
+        dynamic_buffers.sort_by_key(|&(index, _)| index);
+    }
+
+    fn set_dynamic_offsets(&mut self, group: u32, offsets: &[u32]) {
+        let info = &self.group_mappings[group as usize];
+        let dynamic_buffers = bound_dynamic_buffers(&self.dynamic_buffers, group, offsets);
+        for (&(index, base), &offset) in dynamic_buffers.iter().zip(offsets) {
+            let slot = info.targets[index as usize] as usize;
+            let offset = (base + offset as u64) as usize;
+            unsafe {
+                if info.visibility.contains(crate::ShaderVisibility::VERTEX) {
+                    self.encoder.setVertexBufferOffset_atIndex(offset, slot);
+                }
+                if info.visibility.contains(crate::ShaderVisibility::FRAGMENT) {
+                    self.encoder.setFragmentBufferOffset_atIndex(offset, slot);
+                }
+            }
+        }
     }
 }
 
@@ -970,6 +1450,7 @@ impl crate::traits::RenderPipelineEncoder for super::RenderPipelineContext<'_> {
     }
 
     fn draw_indirect(&mut self, indirect_buf: crate::BufferPiece) {
+        crate::indirect::debug_check_indirect::<crate::DrawIndirectArgs>(&indirect_buf);
         unsafe {
             self.encoder
                 .drawPrimitives_indirectBuffer_indirectBufferOffset(
@@ -986,6 +1467,7 @@ impl crate::traits::RenderPipelineEncoder for super::RenderPipelineContext<'_> {
         index_type: crate::IndexType,
         indirect_buf: crate::BufferPiece,
     ) {
+        crate::indirect::debug_check_indirect::<crate::DrawIndexedIndirectArgs>(&indirect_buf);
         let raw_index_type = super::map_index_type(index_type);
         unsafe {
             self.encoder.drawIndexedPrimitives_indexType_indexBuffer_indexBufferOffset_indirectBuffer_indirectBufferOffset(
@@ -1008,7 +1490,7 @@ impl Drop for super::RenderPipelineContext<'_> {
     }
 }
 
-fn map_origin(origin: &[u32; 3]) -> metal::MTLOrigin {
+pub(super) fn map_origin(origin: &[u32; 3]) -> metal::MTLOrigin {
     metal::MTLOrigin {
         x: origin[0] as usize,
         y: origin[1] as usize,
@@ -1016,7 +1498,7 @@ fn map_origin(origin: &[u32; 3]) -> metal::MTLOrigin {
     }
 }
 
-fn map_extent(extent: &crate::Extent) -> metal::MTLSize {
+pub(super) fn map_extent(extent: &crate::Extent) -> metal::MTLSize {
     metal::MTLSize {
         width: extent.width as usize,
         height: extent.height as usize,
@@ -1024,25 +1506,17 @@ fn map_extent(extent: &crate::Extent) -> metal::MTLSize {
     }
 }
 
-fn map_clear_color(color: crate::TextureColor) -> metal::MTLClearColor {
-    match color {
-        crate::TextureColor::TransparentBlack => metal::MTLClearColor {
-            red: 0.0,
-            green: 0.0,
-            blue: 0.0,
-            alpha: 0.0,
-        },
-        crate::TextureColor::OpaqueBlack => metal::MTLClearColor {
-            red: 0.0,
-            green: 0.0,
-            blue: 0.0,
-            alpha: 1.0,
-        },
-        crate::TextureColor::White => metal::MTLClearColor {
-            red: 1.0,
-            green: 1.0,
-            blue: 1.0,
-            alpha: 1.0,
-        },
+fn map_clear_color(color: crate::ClearColor) -> metal::MTLClearColor {
+    // Integer targets are cleared to the values converted from doubles,
+    // which represent all the 32-bit integers exactly.
+    let [red, green, blue, alpha] = match color {
+        crate::ClearColor::Float(value) => value.map(|v| v as f64),
+        crate::ClearColor::Uint(value) => value.map(|v| v as f64),
+    };
+    metal::MTLClearColor {
+        red,
+        green,
+        blue,
+        alpha,
     }
 }
@@ -1,6 +1,7 @@
 use objc2::{rc::Retained, runtime::ProtocolObject};
 use objc2_metal::{self as metal, MTLDevice};
 use std::{
+    collections::{HashMap, HashSet},
     marker::PhantomData,
     ptr,
     sync::{Arc, Mutex},
@@ -13,6 +14,14 @@ mod resource;
 mod surface;
 
 const MAX_TIMESTAMPS: usize = crate::limits::PASS_COUNT * 2;
+/// Textures per argument buffer on tier 2 devices.
+const MAX_ARGUMENT_BUFFER_TEXTURES: u32 = 500_000;
+/// Plain data is passed with `setBytes`, which is limited to 4 KiB.
+const MAX_PLAIN_DATA_SIZE: u32 = 4096;
+/// Largest vertex amplification count to look for.
+const MAX_VIEWS: usize = 8;
+/// Memory shared by the resident tiles of all the sparse textures.
+const SPARSE_HEAP_SIZE: usize = 256 << 20;
 
 pub struct Surface {
     view: Option<objc2::rc::Retained<objc2::runtime::NSObject>>,
@@ -24,6 +33,7 @@ pub struct Surface {
 pub struct Frame {
     drawable: Retained<ProtocolObject<dyn metal::MTLDrawable>>,
     texture: Retained<ProtocolObject<dyn metal::MTLTexture>>,
+    format: crate::TextureFormat,
 }
 
 unsafe impl Send for Frame {}
@@ -32,6 +42,7 @@ impl Frame {
     pub fn texture(&self) -> Texture {
         Texture {
             raw: Retained::as_ptr(&self.texture) as *mut _,
+            format: self.format,
         }
     }
 
@@ -39,6 +50,7 @@ impl Frame {
         TextureView {
             raw: Retained::as_ptr(&self.texture) as *mut _,
             aspects: crate::TexelAspects::COLOR,
+            format: self.format,
         }
     }
 }
@@ -48,6 +60,8 @@ struct PrivateInfo {
     language_version: metal::MTLLanguageVersion,
     enable_debug_groups: bool,
     enable_dispatch_type: bool,
+    max_binding_array_size: u32,
+    max_view_count: u32,
 }
 
 pub struct Context {
@@ -55,8 +69,15 @@ pub struct Context {
     queue: Arc<Mutex<Retained<ProtocolObject<dyn metal::MTLCommandQueue>>>>,
     capture: Option<Retained<metal::MTLCaptureManager>>,
     timestamp_counter_set: Option<Retained<ProtocolObject<dyn metal::MTLCounterSet>>>,
+    blitter: Arc<Mutex<Blitter>>,
     info: PrivateInfo,
     device_information: crate::DeviceInformation,
+    /// Created on the first sparse texture.
+    sparse_heap: Mutex<Option<Retained<ProtocolObject<dyn metal::MTLHeap>>>>,
+    pub(crate) deferred_destructions: crate::deferred::DeferredDestructions,
+    pub(crate) dummy_resources: crate::dummy::DummyResources,
+    pub(crate) reporter: crate::report::Reporter,
+    pub(crate) sampler_cache: crate::cache::SamplerCache,
 }
 
 // needed for `capture` and `timestamp_counter_set`
@@ -93,11 +114,17 @@ impl Buffer {
         use metal::MTLResource as _;
         self.as_ref().allocatedSize() as u64
     }
+
+    pub fn device_address(&self) -> u64 {
+        use metal::MTLBuffer as _;
+        self.as_ref().gpuAddress()
+    }
 }
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq)]
 pub struct Texture {
     raw: *mut ProtocolObject<dyn metal::MTLTexture>,
+    format: crate::TextureFormat,
 }
 
 unsafe impl Send for Texture {}
@@ -107,6 +134,7 @@ impl Default for Texture {
     fn default() -> Self {
         Self {
             raw: ptr::null_mut(),
+            format: crate::TextureFormat::Rgba8Unorm,
         }
     }
 }
@@ -115,12 +143,28 @@ impl Texture {
     fn as_ref(&self) -> &ProtocolObject<dyn metal::MTLTexture> {
         unsafe { &*self.raw }
     }
+
+    /// Size of the base mip level.
+    pub fn size(&self) -> crate::Extent {
+        use metal::MTLTexture as _;
+        let texture = self.as_ref();
+        crate::Extent {
+            width: texture.width() as u32,
+            height: texture.height() as u32,
+            depth: texture.depth() as u32,
+        }
+    }
+
+    pub fn format(&self) -> crate::TextureFormat {
+        self.format
+    }
 }
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq)]
 pub struct TextureView {
     raw: *mut ProtocolObject<dyn metal::MTLTexture>,
     aspects: crate::TexelAspects,
+    format: crate::TextureFormat,
 }
 
 unsafe impl Send for TextureView {}
@@ -131,6 +175,7 @@ impl Default for TextureView {
         Self {
             raw: ptr::null_mut(),
             aspects: crate::TexelAspects::COLOR,
+            format: crate::TextureFormat::Rgba8Unorm,
         }
     }
 }
@@ -146,14 +191,23 @@ impl TextureView {
         raw: &Retained<ProtocolObject<dyn metal::MTLTexture>>,
         aspects: crate::TexelAspects,
     ) -> Self {
+        use metal::MTLTexture as _;
+        let pixel_format = raw.pixelFormat();
+        // Formats unknown to Blade are treated as the float color ones
+        let format = crate::TextureFormat::ALL
+            .iter()
+            .copied()
+            .find(|&format| map_texture_format(format) == pixel_format)
+            .unwrap_or(crate::TextureFormat::Rgba8Unorm);
         Self {
             raw: Retained::into_raw(raw.clone()),
             aspects,
+            format,
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Sampler {
     raw: *mut ProtocolObject<dyn metal::MTLSamplerState>,
 }
@@ -210,26 +264,73 @@ unsafe impl Send for SyncPoint {}
 unsafe impl Sync for SyncPoint {}
 
 struct TimingData {
-    pass_names: Vec<String>,
+    pass_names: crate::PassNames,
     sample_buffer: Retained<ProtocolObject<dyn metal::MTLCounterSampleBuffer>>,
 }
 
+/// Render pipelines for the blits that scale or convert, created on demand.
+struct Blitter {
+    device: Retained<ProtocolObject<dyn metal::MTLDevice>>,
+    library: Option<Retained<ProtocolObject<dyn metal::MTLLibrary>>>,
+    pipelines: HashMap<
+        (metal::MTLPixelFormat, crate::FilterMode),
+        Retained<ProtocolObject<dyn metal::MTLRenderPipelineState>>,
+    >,
+}
+
 type RawCommandBuffer = Retained<ProtocolObject<dyn metal::MTLCommandBuffer>>;
+
+/// Buffers with the encoded binding arrays of one submission.
+#[derive(Default)]
+struct ArgumentPool {
+    buffers: Vec<Retained<ProtocolObject<dyn metal::MTLBuffer>>>,
+    used_count: usize,
+}
+
+/// Argument buffers of the binding arrays, and the residency of their resources.
+struct ArgumentBuffers {
+    /// Encoder of an argument holding a single texture, created on first use.
+    texture_encoder: Option<Retained<ProtocolObject<dyn metal::MTLArgumentEncoder>>>,
+    /// Pools of the submissions in flight, with the current one first.
+    /// Command buffers don't retain the resources, so the buffers
+    /// are kept alive until the pool comes around again.
+    pools: Box<[ArgumentPool]>,
+    /// Resources that are made resident in the current pass.
+    resident: HashSet<*mut ProtocolObject<dyn metal::MTLResource>>,
+    /// Scratch list of the resources to make resident.
+    pending: Vec<ptr::NonNull<ProtocolObject<dyn metal::MTLResource>>>,
+}
+
 pub struct CommandEncoder {
     raw: Option<RawCommandBuffer>,
     name: String,
     queue: Arc<Mutex<Retained<ProtocolObject<dyn metal::MTLCommandQueue>>>>,
+    blitter: Arc<Mutex<Blitter>>,
     enable_debug_groups: bool,
     enable_dispatch_type: bool,
     has_open_debug_group: bool,
     timing_datas: Option<Box<[TimingData]>>,
     timings: crate::Timings,
+    timings_recording_index: Option<u64>,
+    recording_index: u64,
+    binding_stats: crate::BindingStats,
+    arguments: ArgumentBuffers,
+    /// Resources bound for `Nullable` bindings of `None`.
+    dummies: Option<crate::dummy::DummySet>,
+    peak_retained_bytes: usize,
+    /// Drawable to present after the submission.
+    present: Option<Retained<ProtocolObject<dyn metal::MTLDrawable>>>,
 }
 
+// Safe because the command buffer is only accessed by the owner of the encoder
+unsafe impl Send for CommandEncoder {}
+
 #[derive(Debug)]
 struct ShaderDataMapping {
     visibility: crate::ShaderVisibility,
     targets: Box<[u32]>,
+    /// Bitmask: bit N is set if binding N is a dynamic buffer.
+    dynamic_mask: u64,
 }
 
 #[derive(Debug)]
@@ -260,6 +361,11 @@ impl crate::traits::ComputePipelineBase for ComputePipeline {
             self.wg_size.depth as u32,
         ]
     }
+    fn get_max_group_count(&self) -> [u32; 3] {
+        // Metal doesn't report the limit, this is the conservative one
+        // guaranteed by the other APIs.
+        [65535; 3]
+    }
 }
 
 pub struct RenderPipeline {
@@ -279,6 +385,7 @@ pub struct RenderPipeline {
         Retained<ProtocolObject<dyn metal::MTLDepthStencilState>>,
         super::DepthBiasState,
     )>,
+    depth_stencil_writes: crate::TexelAspects,
 }
 
 unsafe impl Send for RenderPipeline {}
@@ -286,7 +393,8 @@ unsafe impl Sync for RenderPipeline {}
 
 pub struct TransferCommandEncoder<'a> {
     raw: Retained<ProtocolObject<dyn metal::MTLBlitCommandEncoder>>,
-    phantom: PhantomData<&'a CommandEncoder>,
+    cmd_buf: &'a ProtocolObject<dyn metal::MTLCommandBuffer>,
+    blitter: &'a Mutex<Blitter>,
 }
 
 pub struct AccelerationStructureCommandEncoder<'a> {
@@ -297,21 +405,29 @@ pub struct AccelerationStructureCommandEncoder<'a> {
 pub struct ComputeCommandEncoder<'a> {
     raw: Retained<ProtocolObject<dyn metal::MTLComputeCommandEncoder>>,
     enable_debug_groups: bool,
-    phantom: PhantomData<&'a CommandEncoder>,
+    binding_stats: &'a mut crate::BindingStats,
+    arguments: &'a mut ArgumentBuffers,
+    dummies: Option<crate::dummy::DummySet>,
 }
 
 pub struct RenderCommandEncoder<'a> {
     raw: Retained<ProtocolObject<dyn metal::MTLRenderCommandEncoder>>,
     enable_debug_groups: bool,
-    phantom: PhantomData<&'a CommandEncoder>,
+    binding_stats: &'a mut crate::BindingStats,
+    arguments: &'a mut ArgumentBuffers,
+    dummies: Option<crate::dummy::DummySet>,
+    depth_stencil_read_only: crate::TexelAspects,
 }
 
 pub struct PipelineContext<'a> {
-    //raw: metal::ArgumentEncoderRef,
     cs_encoder: Option<&'a ProtocolObject<dyn metal::MTLComputeCommandEncoder>>,
     vs_encoder: Option<&'a ProtocolObject<dyn metal::MTLRenderCommandEncoder>>,
     fs_encoder: Option<&'a ProtocolObject<dyn metal::MTLRenderCommandEncoder>>,
     targets: &'a [u32],
+    arguments: &'a mut ArgumentBuffers,
+    dynamic_mask: u64,
+    dynamic_buffers: &'a mut Vec<(u32, u64)>,
+    dummies: Option<crate::dummy::DummySet>,
 }
 
 pub struct ComputePipelineContext<'a> {
@@ -319,6 +435,11 @@ pub struct ComputePipelineContext<'a> {
     wg_size: metal::MTLSize,
     group_mappings: &'a [ShaderDataMapping],
     enable_debug_groups: bool,
+    binding_stats: &'a mut crate::BindingStats,
+    arguments: &'a mut ArgumentBuffers,
+    /// Binding indices and base offsets of the dynamic buffers, per group.
+    dynamic_buffers: Vec<Vec<(u32, u64)>>,
+    dummies: Option<crate::dummy::DummySet>,
 }
 
 pub struct RenderPipelineContext<'a> {
@@ -326,6 +447,34 @@ pub struct RenderPipelineContext<'a> {
     primitive_type: metal::MTLPrimitiveType,
     group_mappings: &'a [ShaderDataMapping],
     enable_debug_groups: bool,
+    binding_stats: &'a mut crate::BindingStats,
+    arguments: &'a mut ArgumentBuffers,
+    /// Binding indices and base offsets of the dynamic buffers, per group.
+    dynamic_buffers: Vec<Vec<(u32, u64)>>,
+    dummies: Option<crate::dummy::DummySet>,
+}
+
+/// Number of textures that binding arrays can hold.
+///
+/// Binding arrays are translated into arrays of argument buffers,
+/// which tier 1 devices can't index into.
+fn max_binding_array_size(device: &ProtocolObject<dyn metal::MTLDevice>) -> u32 {
+    if device.argumentBuffersSupport() == metal::MTLArgumentBuffersTier::Tier2 {
+        MAX_ARGUMENT_BUFFER_TEXTURES
+    } else {
+        0
+    }
+}
+
+/// Number of views that multiview passes can render.
+///
+/// Multiview is implemented with vertex amplification,
+/// routing every amplified vertex to its own layer of the targets.
+fn max_view_count(device: &ProtocolObject<dyn metal::MTLDevice>) -> u32 {
+    (2..=MAX_VIEWS)
+        .rev()
+        .find(|&count| device.supportsVertexAmplificationCount(count))
+        .unwrap_or(0) as u32
 }
 
 fn map_texture_format(format: crate::TextureFormat) -> metal::MTLPixelFormat {
@@ -366,6 +515,12 @@ fn map_texture_format(format: crate::TextureFormat) -> metal::MTLPixelFormat {
         Tf::Bc6hFloat => Mpf::BC6H_RGBFloat,
         Tf::Bc7Unorm => Mpf::BC7_RGBAUnorm,
         Tf::Bc7UnormSrgb => Mpf::BC7_RGBAUnorm_sRGB,
+        Tf::Etc2Rgb8Unorm => Mpf::ETC2_RGB8,
+        Tf::Etc2Rgb8UnormSrgb => Mpf::ETC2_RGB8_sRGB,
+        Tf::Etc2Rgba8Unorm => Mpf::EAC_RGBA8,
+        Tf::Etc2Rgba8UnormSrgb => Mpf::EAC_RGBA8_sRGB,
+        Tf::Astc4x4Unorm => Mpf::ASTC_4x4_LDR,
+        Tf::Astc4x4UnormSrgb => Mpf::ASTC_4x4_sRGB,
         Tf::Rgb10a2Unorm => Mpf::RGB10A2Unorm,
         Tf::Rg11b10Ufloat => Mpf::RG11B10Float,
         Tf::Rgb9e5Ufloat => Mpf::RGB9E5Float,
@@ -510,18 +665,48 @@ impl Context {
             }
         }
 
+        let blitter = Blitter {
+            device: device.clone(),
+            library: None,
+            pipelines: HashMap::new(),
+        };
+        //TODO: determine based on OS version
+        let language_version = metal::MTLLanguageVersion::Version2_4;
+        let reporter = crate::report::Reporter::new(crate::InitReport {
+            backend: "Metal".to_string(),
+            api_version: format!(
+                "MSL {}.{}",
+                language_version.0 >> 16,
+                language_version.0 & 0xFFFF
+            ),
+            device: device_information.clone(),
+            extensions: Vec::new(),
+            features: Vec::new(),
+            memory_heaps: vec![crate::MemoryHeapReport {
+                size: device.recommendedMaxWorkingSetSize(),
+                device_local: true,
+            }],
+            surface: None,
+        });
         Ok(Context {
             device: Mutex::new(device),
             queue: Arc::new(Mutex::new(queue)),
             capture,
             timestamp_counter_set,
+            blitter: Arc::new(Mutex::new(blitter)),
             info: PrivateInfo {
-                //TODO: determine based on OS version
-                language_version: metal::MTLLanguageVersion::Version2_4,
+                language_version,
                 enable_debug_groups: desc.capture,
                 enable_dispatch_type: true,
+                max_binding_array_size: max_binding_array_size(&device),
+                max_view_count: max_view_count(&device),
             },
             device_information,
+            sparse_heap: Mutex::new(None),
+            deferred_destructions: Default::default(),
+            dummy_resources: Default::default(),
+            reporter,
+            sampler_cache: Default::default(),
         })
     }
 
@@ -535,7 +720,9 @@ impl Context {
     ) -> crate::Capabilities {
         use metal::MTLDevice as _;
         crate::Capabilities {
-            binding_array: false,
+            binding_array: max_binding_array_size(device) != 0,
+            max_binding_array_size: max_binding_array_size(device),
+            max_plain_data_size: MAX_PLAIN_DATA_SIZE,
             ray_query: if device.supportsFamily(metal::MTLGPUFamily::Apple6) {
                 crate::ShaderVisibility::all()
             } else if device.supportsFamily(metal::MTLGPUFamily::Mac2)
@@ -563,6 +750,30 @@ impl Context {
             } else {
                 crate::CooperativeMatrix::default()
             },
+            acceleration_structure_motion: false,
+            // Command buffers can only be committed once
+            reusable_command_encoders: false,
+            robustness: false,
+            depth_resolve: device.supportsFamily(metal::MTLGPUFamily::Apple3)
+                || device.supportsFamily(metal::MTLGPUFamily::Mac2),
+            depth_resolve_min_max: device.supportsFamily(metal::MTLGPUFamily::Apple3)
+                || device.supportsFamily(metal::MTLGPUFamily::Mac2),
+            // Metal has no logic operations
+            logic_op: false,
+            sparse_tile_size: if device.supportsFamily(metal::MTLGPUFamily::Apple6) {
+                device.sparseTileSizeInBytes() as u32
+            } else {
+                0
+            },
+            // `gpuAddress` is available since Metal 3
+            buffer_device_address: device.supportsFamily(metal::MTLGPUFamily::Metal3),
+            max_view_count: max_view_count(device),
+            // Metal only predicates the draws through indirect arguments
+            conditional_rendering: false,
+            // Constant buffers have the strictest offset alignment on macOS
+            dynamic_offset_alignment: 256,
+            // The bias can only be applied in the shaders
+            max_sampler_lod_bias: 0.0,
         }
     }
 
@@ -570,6 +781,62 @@ impl Context {
         &self.device_information
     }
 
+    /// Return the usages supported by the textures of the given format,
+    /// which are empty if the format isn't supported at all.
+    pub fn supported_texture_usage(&self, format: crate::TextureFormat) -> crate::TextureUsage {
+        use crate::TextureFormat as Tf;
+        use metal::MTLDevice as _;
+        let device = self.device.lock().unwrap();
+        let is_supported = match format {
+            Tf::Bc1Unorm
+            | Tf::Bc1UnormSrgb
+            | Tf::Bc2Unorm
+            | Tf::Bc2UnormSrgb
+            | Tf::Bc3Unorm
+            | Tf::Bc3UnormSrgb
+            | Tf::Bc4Unorm
+            | Tf::Bc4Snorm
+            | Tf::Bc5Unorm
+            | Tf::Bc5Snorm
+            | Tf::Bc6hUfloat
+            | Tf::Bc6hFloat
+            | Tf::Bc7Unorm
+            | Tf::Bc7UnormSrgb => device.supportsBCTextureCompression(),
+            Tf::Etc2Rgb8Unorm
+            | Tf::Etc2Rgb8UnormSrgb
+            | Tf::Etc2Rgba8Unorm
+            | Tf::Etc2Rgba8UnormSrgb
+            | Tf::Astc4x4Unorm
+            | Tf::Astc4x4UnormSrgb => device.supportsFamily(metal::MTLGPUFamily::Apple2),
+            _ => true,
+        };
+        if !is_supported {
+            return crate::TextureUsage::empty();
+        }
+        let mut usage = if format.block_info().dimensions != (1, 1) {
+            crate::TextureUsage::COPY | crate::TextureUsage::RESOURCE
+        } else if format.aspects().contains(crate::TexelAspects::COLOR) {
+            crate::TextureUsage::all()
+                - crate::TextureUsage::HOST_WRITE
+                - crate::TextureUsage::SPARSE
+        } else {
+            crate::TextureUsage::all()
+                - crate::TextureUsage::STORAGE
+                - crate::TextureUsage::HOST_WRITE
+                - crate::TextureUsage::SPARSE
+        };
+        // Depth and stencil textures can't be shared with the CPU.
+        if device.hasUnifiedMemory() && format.aspects().contains(crate::TexelAspects::COLOR) {
+            usage |= crate::TextureUsage::HOST_WRITE;
+        }
+        if device.supportsFamily(metal::MTLGPUFamily::Apple6)
+            && format.aspects() == crate::TexelAspects::COLOR
+        {
+            usage |= crate::TextureUsage::SPARSE;
+        }
+        usage
+    }
+
     pub fn enumerate() -> Result<Vec<crate::DeviceReport>, crate::NotSupportedError> {
         Ok(Self::inspect_devices(None))
     }
@@ -617,7 +884,120 @@ impl Context {
         crate::MemoryStats {
             budget: device.recommendedMaxWorkingSetSize(),
             usage: device.currentAllocatedSize() as u64,
+            descriptor_pools: crate::DescriptorPoolStats::default(),
+        }
+    }
+
+    /// Release the memory that is no longer needed.
+    /// Shader data is bound directly to the encoders, so there are no pools to trim.
+    pub fn trim(&self) {}
+
+    /// Return the size of the tiles of sparse textures in the given format,
+    /// or `None` if it doesn't support `TextureUsage::SPARSE`.
+    pub fn sparse_tile_extent(&self, format: crate::TextureFormat) -> Option<crate::Extent> {
+        if !self
+            .supported_texture_usage(format)
+            .contains(crate::TextureUsage::SPARSE)
+        {
+            return None;
+        }
+        let size = unsafe {
+            self.device
+                .lock()
+                .unwrap()
+                .sparseTileSizeWithTextureType_pixelFormat_sampleCount(
+                    metal::MTLTextureType::Type2D,
+                    map_texture_format(format),
+                    1,
+                )
+        };
+        Some(crate::Extent {
+            width: size.width as u32,
+            height: size.height as u32,
+            depth: size.depth as u32,
+        })
+    }
+
+    /// Map or unmap the tiles of a sparse texture, see `TextureUsage::SPARSE`.
+    ///
+    /// The regions have to be aligned to `sparse_tile_extent`, or reach
+    /// the edges of their mip level. The tiles of all the sparse textures
+    /// share a heap of 256 MiB, and mapping them past it fails silently.
+    ///
+    /// The update is encoded into a command buffer of its own,
+    /// so it's ordered with the submitted work.
+    pub fn update_texture_residency(
+        &self,
+        texture: Texture,
+        regions: &[crate::TextureRegion],
+        residency: crate::Residency,
+    ) -> Result<(), crate::ResourceError> {
+        use metal::{
+            MTLCommandBuffer as _, MTLCommandEncoder as _, MTLCommandQueue as _,
+            MTLResourceStateCommandEncoder as _,
+        };
+
+        let tile = self
+            .sparse_tile_extent(texture.format)
+            .expect("Sparse residency is not supported for the format");
+        let mode = match residency {
+            crate::Residency::Bind => metal::MTLSparseTextureMappingMode::Map,
+            crate::Residency::Unbind => metal::MTLSparseTextureMappingMode::Unmap,
+        };
+        let queue = self.queue.lock().unwrap();
+        objc2::rc::autoreleasepool(|_| {
+            let cmd_buf = queue.commandBuffer().unwrap();
+            let encoder = cmd_buf.resourceStateCommandEncoder().unwrap();
+            for region in regions {
+                // The regions are in tiles
+                let tile_region = metal::MTLRegion {
+                    origin: metal::MTLOrigin {
+                        x: (region.origin[0] / tile.width) as usize,
+                        y: (region.origin[1] / tile.height) as usize,
+                        z: (region.origin[2] / tile.depth) as usize,
+                    },
+                    size: metal::MTLSize {
+                        width: region.size.width.div_ceil(tile.width) as usize,
+                        height: region.size.height.div_ceil(tile.height) as usize,
+                        depth: region.size.depth.div_ceil(tile.depth) as usize,
+                    },
+                };
+                unsafe {
+                    encoder.updateTextureMapping_mode_region_mipLevel_slice(
+                        texture.as_ref(),
+                        mode,
+                        tile_region,
+                        region.mip_level as usize,
+                        region.array_layer as usize,
+                    )
+                };
+            }
+            encoder.endEncoding();
+            cmd_buf.commit();
+        });
+        Ok(())
+    }
+
+    /// Return the heap backing the tiles of the sparse textures.
+    fn sparse_heap(
+        &self,
+    ) -> Result<Retained<ProtocolObject<dyn metal::MTLHeap>>, crate::ResourceError> {
+        let mut sparse_heap = self.sparse_heap.lock().unwrap();
+        if let Some(ref heap) = *sparse_heap {
+            return Ok(heap.clone());
         }
+        let descriptor = metal::MTLHeapDescriptor::new();
+        descriptor.setType(metal::MTLHeapType::Sparse);
+        descriptor.setStorageMode(metal::MTLStorageMode::Private);
+        descriptor.setSize(SPARSE_HEAP_SIZE);
+        let heap = self
+            .device
+            .lock()
+            .unwrap()
+            .newHeapWithDescriptor(&descriptor)
+            .ok_or(crate::ResourceError::OutOfDeviceMemory)?;
+        *sparse_heap = Some(heap.clone());
+        Ok(heap)
     }
 }
 
@@ -650,7 +1030,7 @@ impl crate::traits::CommandDevice for Context {
                 };
                 array.push(TimingData {
                     sample_buffer,
-                    pass_names: Vec::new(),
+                    pass_names: Default::default(),
                 });
             }
             Some(array.into_boxed_slice())
@@ -662,11 +1042,26 @@ impl crate::traits::CommandDevice for Context {
             raw: None,
             name: desc.name.to_string(),
             queue: Arc::clone(&self.queue),
+            blitter: Arc::clone(&self.blitter),
             enable_debug_groups: self.info.enable_debug_groups,
             enable_dispatch_type: self.info.enable_dispatch_type,
             has_open_debug_group: false,
             timing_datas,
             timings: Default::default(),
+            timings_recording_index: None,
+            recording_index: 0,
+            binding_stats: Default::default(),
+            dummies: self.dummy_resources.for_encoder(self),
+            arguments: ArgumentBuffers {
+                texture_encoder: None,
+                pools: (0..desc.buffer_count.max(1))
+                    .map(|_| ArgumentPool::default())
+                    .collect(),
+                resident: HashSet::default(),
+                pending: Vec::new(),
+            },
+            peak_retained_bytes: 0,
+            present: None,
         }
     }
 
@@ -674,11 +1069,43 @@ impl crate::traits::CommandDevice for Context {
 
     fn submit(&self, encoder: &mut CommandEncoder) -> SyncPoint {
         use metal::MTLCommandBuffer as _;
+        self.deferred_destructions.drain(self);
         let cmd_buf = encoder.finish();
+        if let Some(drawable) = encoder.present.take() {
+            cmd_buf.presentDrawable(&drawable);
+        }
         cmd_buf.commit();
         SyncPoint { cmd_buf }
     }
 
+    fn submit_batch(&self, encoders: &mut [&mut CommandEncoder]) -> SyncPoint {
+        use metal::MTLCommandBuffer as _;
+        assert!(!encoders.is_empty(), "Nothing to submit");
+        assert!(
+            encoders.iter().filter(|e| e.present.is_some()).count() <= 1,
+            "Only one encoder in a batch can present"
+        );
+        self.deferred_destructions.drain(self);
+        let cmd_bufs = encoders
+            .iter_mut()
+            .map(|encoder| encoder.finish())
+            .collect::<Vec<_>>();
+        // Present after the last command buffer, so that the whole batch
+        // is done by then, like on the other backends.
+        if let Some(drawable) = encoders.iter_mut().find_map(|e| e.present.take()) {
+            cmd_bufs.last().unwrap().presentDrawable(&drawable);
+        }
+        // Command buffers of a queue are executed in the order of their commits,
+        // and Metal tracks the hazards between them.
+        let _guard = self.queue.lock().unwrap();
+        for cmd_buf in cmd_bufs.iter() {
+            cmd_buf.commit();
+        }
+        SyncPoint {
+            cmd_buf: cmd_bufs.into_iter().last().unwrap(),
+        }
+    }
+
     fn wait_for(&self, sp: &SyncPoint, timeout_ms: u32) -> Result<bool, crate::DeviceError> {
         use metal::MTLCommandBuffer as _;
 
@@ -689,7 +1116,10 @@ impl crate::traits::CommandDevice for Context {
         let start = time::Instant::now();
         loop {
             match sp.cmd_buf.status() {
-                metal::MTLCommandBufferStatus::Completed => return Ok(true),
+                metal::MTLCommandBufferStatus::Completed => {
+                    self.deferred_destructions.drain(self);
+                    return Ok(true);
+                }
                 metal::MTLCommandBufferStatus::Error => return Err(crate::DeviceError::DeviceLost),
                 _ => {}
             }
@@ -710,6 +1140,8 @@ impl Drop for Context {
             }
             capture_manager.stopCapture();
         }
+        self.dummy_resources.destroy(self);
+        self.deferred_destructions.destroy_all(self);
     }
 }
 
@@ -141,7 +141,8 @@ fn make_pipeline_layout(
     let mut num_buffers = reserved_vertex_buffers;
     for layout in bind_group_layouts.iter() {
         let mut targets = Vec::with_capacity(layout.bindings.len());
-        for &(_, ref binding) in layout.bindings.iter() {
+        let mut dynamic_mask = 0u64;
+        for (binding_index, &(_, ref binding)) in layout.bindings.iter().enumerate() {
             targets.push(match *binding {
                 crate::ShaderBinding::Texture => {
                     num_textures += 1;
@@ -156,8 +157,18 @@ fn make_pipeline_layout(
                     num_buffers += 1;
                     num_buffers - 1
                 }
-                crate::ShaderBinding::TextureArray { .. }
-                | crate::ShaderBinding::BufferArray { .. }
+                crate::ShaderBinding::DynamicBuffer => {
+                    dynamic_mask |= 1 << binding_index;
+                    unsized_buffer_count += 1;
+                    num_buffers += 1;
+                    num_buffers - 1
+                }
+                // Encoded into an argument buffer
+                crate::ShaderBinding::TextureArray { .. } => {
+                    num_buffers += 1;
+                    num_buffers - 1
+                }
+                crate::ShaderBinding::BufferArray { .. }
                 | crate::ShaderBinding::AccelerationStructureArray { .. } => unimplemented!(),
                 crate::ShaderBinding::AccelerationStructure => {
                     num_buffers += 1;
@@ -173,6 +184,7 @@ fn make_pipeline_layout(
         group_mappings.push(super::ShaderDataMapping {
             visibility: crate::ShaderVisibility::empty(),
             targets: targets.into_boxed_slice(),
+            dynamic_mask,
         });
     }
 
@@ -190,6 +202,79 @@ fn make_pipeline_layout(
     }
 }
 
+const BLIT_SOURCE: &str = r#"
+#include <metal_stdlib>
+using namespace metal;
+
+struct BlitVertex {
+    float4 position [[position]];
+    float2 tex_coords;
+};
+
+// A triangle covering the viewport, mapped to the source rectangle
+vertex BlitVertex blit_vs(uint vertex_id [[vertex_id]], constant float4 &rect [[buffer(0)]]) {
+    float2 t = float2((vertex_id << 1) & 2, vertex_id & 2);
+    BlitVertex out;
+    out.position = float4(t.x * 2.0 - 1.0, 1.0 - t.y * 2.0, 0.0, 1.0);
+    out.tex_coords = rect.xy + t * (rect.zw - rect.xy);
+    return out;
+}
+
+fragment float4 blit_fs_nearest(BlitVertex in [[stage_in]], texture2d<float> source [[texture(0)]]) {
+    constexpr sampler s(filter::nearest, address::clamp_to_edge);
+    return source.sample(s, in.tex_coords);
+}
+
+fragment float4 blit_fs_linear(BlitVertex in [[stage_in]], texture2d<float> source [[texture(0)]]) {
+    constexpr sampler s(filter::linear, address::clamp_to_edge);
+    return source.sample(s, in.tex_coords);
+}
+"#;
+
+impl super::Blitter {
+    pub(super) fn pipeline(
+        &mut self,
+        format: metal::MTLPixelFormat,
+        filter: crate::FilterMode,
+    ) -> Retained<ProtocolObject<dyn metal::MTLRenderPipelineState>> {
+        if let Some(pipeline) = self.pipelines.get(&(format, filter)) {
+            return pipeline.clone();
+        }
+        let device = &self.device;
+        let library = self.library.get_or_insert_with(|| {
+            device
+                .newLibraryWithSource_options_error(&NSString::from_str(BLIT_SOURCE), None)
+                .unwrap_or_else(|err| {
+                    panic!("MSL compilation error:\n{}", err.localizedDescription());
+                })
+        });
+        let fs_name = match filter {
+            crate::FilterMode::Nearest => "blit_fs_nearest",
+            crate::FilterMode::Linear => "blit_fs_linear",
+        };
+
+        let pipeline = objc2::rc::autoreleasepool(|_| {
+            let descriptor = metal::MTLRenderPipelineDescriptor::new();
+            let vs = library
+                .newFunctionWithName(&NSString::from_str("blit_vs"))
+                .unwrap();
+            let fs = library
+                .newFunctionWithName(&NSString::from_str(fs_name))
+                .unwrap();
+            descriptor.setVertexFunction(Some(&vs));
+            descriptor.setFragmentFunction(Some(&fs));
+            let at_descriptor =
+                unsafe { descriptor.colorAttachments().objectAtIndexedSubscript(0) };
+            at_descriptor.setPixelFormat(format);
+            device
+                .newRenderPipelineStateWithDescriptor_error(&descriptor)
+                .unwrap()
+        });
+        self.pipelines.insert((format, filter), pipeline.clone());
+        pipeline
+    }
+}
+
 impl super::Context {
     fn load_shader(
         &self,
@@ -263,13 +348,14 @@ impl super::Context {
                         ..Default::default()
                     },
                     crate::ShaderBinding::Buffer
+                    | crate::ShaderBinding::DynamicBuffer
                     | crate::ShaderBinding::Plain { .. }
-                    | crate::ShaderBinding::AccelerationStructure => msl::BindTarget {
+                    | crate::ShaderBinding::AccelerationStructure
+                    | crate::ShaderBinding::TextureArray { .. } => msl::BindTarget {
                         buffer: Some(slot as _),
                         ..Default::default()
                     },
-                    crate::ShaderBinding::TextureArray { .. }
-                    | crate::ShaderBinding::BufferArray { .. }
+                    crate::ShaderBinding::BufferArray { .. }
                     | crate::ShaderBinding::AccelerationStructureArray { .. } => todo!(),
                 };
                 naga_resources.resources.insert(res_binding, bind_target);
@@ -350,10 +436,27 @@ impl crate::traits::ShaderDevice for super::Context {
     type RenderPipeline = super::RenderPipeline;
 
     fn create_compute_pipeline(&self, desc: crate::ComputePipelineDesc) -> super::ComputePipeline {
+        let name = desc.name;
+        self.try_create_compute_pipeline(desc)
+            .unwrap_or_else(|e| panic!("Unable to create compute pipeline '{name}': {e}"))
+    }
+
+    fn try_create_compute_pipeline(
+        &self,
+        desc: crate::ComputePipelineDesc,
+    ) -> Result<super::ComputePipeline, crate::PipelineError> {
         use metal::MTLDevice as _;
+        crate::ShaderDataLayout::check_binding_array_size(
+            desc.data_layouts,
+            self.info.max_binding_array_size,
+        )?;
+        crate::ShaderDataLayout::check_plain_data_size(
+            desc.data_layouts,
+            super::MAX_PLAIN_DATA_SIZE,
+        )?;
         let mut layout = make_pipeline_layout(desc.data_layouts, 0);
 
-        objc2::rc::autoreleasepool(|_| {
+        Ok(objc2::rc::autoreleasepool(|_| {
             let cs = self.load_shader(
                 desc.compute,
                 desc.data_layouts,
@@ -379,7 +482,7 @@ impl crate::traits::ShaderDevice for super::Context {
                 wg_size: cs.wg_size,
                 wg_memory_sizes: cs.wg_memory_sizes.into_boxed_slice(),
             }
-        })
+        }))
     }
 
     fn get_compute_pipeline_statistics(
@@ -423,6 +526,26 @@ impl crate::traits::ShaderDevice for super::Context {
     }
 
     fn create_render_pipeline(&self, desc: crate::RenderPipelineDesc) -> super::RenderPipeline {
+        let name = desc.name;
+        self.try_create_render_pipeline(desc)
+            .unwrap_or_else(|e| panic!("Unable to create render pipeline '{name}': {e}"))
+    }
+
+    fn try_create_render_pipeline(
+        &self,
+        desc: crate::RenderPipelineDesc,
+    ) -> Result<super::RenderPipeline, crate::PipelineError> {
+        crate::ShaderDataLayout::check_binding_array_size(
+            desc.data_layouts,
+            self.info.max_binding_array_size,
+        )?;
+        crate::ShaderDataLayout::check_plain_data_size(
+            desc.data_layouts,
+            super::MAX_PLAIN_DATA_SIZE,
+        )?;
+        desc.check_color_targets()?;
+        desc.check_logic_op(false)?;
+        desc.check_multiview(self.info.max_view_count)?;
         let mut layout = make_pipeline_layout(desc.data_layouts, desc.vertex_fetches.len() as u32);
 
         let triangle_fill_mode = match desc.primitive.wireframe {
@@ -453,7 +576,7 @@ impl crate::traits::ShaderDevice for super::Context {
             ),
         };
 
-        objc2::rc::autoreleasepool(|_| {
+        Ok(objc2::rc::autoreleasepool(|_| {
             let descriptor = metal::MTLRenderPipelineDescriptor::new();
 
             let vs = self.load_shader(
@@ -468,6 +591,9 @@ impl crate::traits::ShaderDevice for super::Context {
             );
             descriptor.setVertexFunction(Some(&vs.function));
             descriptor.setRasterSampleCount(desc.multisample_state.sample_count as _);
+            if let Some(count) = desc.multiview {
+                unsafe { descriptor.setMaxVertexAmplificationCount(count.get() as usize) };
+            }
             descriptor.setAlphaToCoverageEnabled(desc.multisample_state.alpha_to_coverage);
 
             // Fragment shader
@@ -599,8 +725,12 @@ impl crate::traits::ShaderDevice for super::Context {
                     metal::MTLDepthClipMode::Clip
                 },
                 depth_stencil,
+                depth_stencil_writes: desc
+                    .depth_stencil
+                    .as_ref()
+                    .map_or(crate::TexelAspects::empty(), |ds| ds.write_aspects()),
             }
-        })
+        }))
     }
 
     fn destroy_render_pipeline(&self, _pipeline: &mut super::RenderPipeline) {
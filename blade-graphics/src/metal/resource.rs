@@ -1,4 +1,4 @@
-use metal::{MTLDevice as _, MTLResource as _};
+use metal::{MTLDevice as _, MTLHeap as _, MTLResource as _};
 use objc2::rc::Retained;
 use objc2_foundation::{NSRange, NSString};
 use objc2_metal::{self as metal, MTLTexture};
@@ -98,6 +98,8 @@ impl super::Context {
         crate::AccelerationStructureSizes {
             data: accel_sizes.accelerationStructureSize as u64,
             scratch: accel_sizes.buildScratchBufferSize as u64,
+            update_scratch: accel_sizes.refitScratchBufferSize as u64,
+            scratch_alignment: crate::limits::ACCELERATION_STRUCTURE_SCRATCH_ALIGNMENT,
         }
     }
 
@@ -117,9 +119,18 @@ impl super::Context {
         crate::AccelerationStructureSizes {
             data: accel_sizes.accelerationStructureSize as u64,
             scratch: accel_sizes.buildScratchBufferSize as u64,
+            update_scratch: accel_sizes.refitScratchBufferSize as u64,
+            scratch_alignment: crate::limits::ACCELERATION_STRUCTURE_SCRATCH_ALIGNMENT,
         }
     }
 
+    pub fn get_top_level_motion_acceleration_structure_sizes(
+        &self,
+        _instance_count: u32,
+    ) -> Result<crate::AccelerationStructureSizes, crate::ResourceError> {
+        Err(crate::ResourceError::MotionNotSupported)
+    }
+
     pub fn create_acceleration_structure_instance_buffer(
         &self,
         instances: &[crate::AccelerationStructureInstance],
@@ -167,6 +178,14 @@ impl super::Context {
             raw: Retained::into_raw(object),
         }
     }
+
+    pub fn create_acceleration_structure_motion_instance_buffer(
+        &self,
+        _instances: &[crate::AccelerationStructureMotionInstance],
+        _bottom_level: &[super::AccelerationStructure],
+    ) -> Result<super::Buffer, crate::ResourceError> {
+        Err(crate::ResourceError::MotionNotSupported)
+    }
 }
 
 #[hidden_trait::expose]
@@ -178,6 +197,15 @@ impl crate::traits::ResourceDevice for super::Context {
     type AccelerationStructure = super::AccelerationStructure;
 
     fn create_buffer(&self, desc: crate::BufferDesc) -> super::Buffer {
+        let name = desc.name;
+        self.try_create_buffer(desc)
+            .unwrap_or_else(|e| panic!("Unable to create buffer '{name}': {e}"))
+    }
+
+    fn try_create_buffer(
+        &self,
+        desc: crate::BufferDesc,
+    ) -> Result<super::Buffer, crate::ResourceError> {
         let options = match desc.memory {
             crate::Memory::Device => metal::MTLResourceOptions::StorageModePrivate,
             crate::Memory::Shared => metal::MTLResourceOptions::StorageModeShared,
@@ -188,18 +216,21 @@ impl crate::traits::ResourceDevice for super::Context {
             crate::Memory::External(_) => unimplemented!(),
         };
         let object = objc2::rc::autoreleasepool(|_| {
-            self.device
-                .lock()
-                .unwrap()
+            let device = self.device.lock().unwrap();
+            let limit = device.maxBufferLength() as u64;
+            if desc.size > limit {
+                return Err(crate::ResourceError::TooLarge { limit });
+            }
+            device
                 .newBufferWithLength_options(desc.size as usize, options)
-                .unwrap()
-        });
+                .ok_or(crate::ResourceError::OutOfDeviceMemory)
+        })?;
         if !desc.name.is_empty() {
             object.setLabel(Some(&NSString::from_str(desc.name)));
         }
-        super::Buffer {
+        Ok(super::Buffer {
             raw: Retained::into_raw(object),
-        }
+        })
     }
 
     fn sync_buffer(&self, _buffer: super::Buffer) {}
@@ -209,6 +240,36 @@ impl crate::traits::ResourceDevice for super::Context {
     }
 
     fn create_texture(&self, desc: crate::TextureDesc) -> super::Texture {
+        let name = desc.name;
+        self.try_create_texture(desc)
+            .unwrap_or_else(|e| panic!("Unable to create texture '{name}': {e}"))
+    }
+
+    fn try_create_texture(
+        &self,
+        desc: crate::TextureDesc,
+    ) -> Result<super::Texture, crate::ResourceError> {
+        desc.check_usage(self.supported_texture_usage(desc.format))?;
+        // Limits of the Apple7 and Mac2 GPU families
+        let (max_size, max_layers) = match desc.dimension {
+            crate::TextureDimension::D3 => (2048, 1),
+            crate::TextureDimension::D1 | crate::TextureDimension::D2 => (16384, 2048),
+        };
+        let limit = if desc.size.width > max_size
+            || desc.size.height > max_size
+            || desc.size.depth > max_size
+        {
+            Some(max_size)
+        } else if desc.array_layer_count > max_layers {
+            Some(max_layers)
+        } else {
+            None
+        };
+        if let Some(limit) = limit {
+            return Err(crate::ResourceError::TooLarge {
+                limit: limit as u64,
+            });
+        }
         let mtl_format = super::map_texture_format(desc.format);
 
         let mtl_type = match desc.dimension {
@@ -237,6 +298,16 @@ impl crate::traits::ResourceDevice for super::Context {
             crate::TextureDimension::D3 => metal::MTLTextureType::Type3D,
         };
         let mtl_usage = map_texture_usage(desc.usage);
+        let sparse_heap = if desc.usage.contains(crate::TextureUsage::SPARSE) {
+            if desc.dimension != crate::TextureDimension::D2 || desc.sample_count > 1 {
+                return Err(crate::ResourceError::UnsupportedUsage(
+                    crate::TextureUsage::SPARSE,
+                ));
+            }
+            Some(self.sparse_heap()?)
+        } else {
+            None
+        };
 
         let object = objc2::rc::autoreleasepool(|_| unsafe {
             let descriptor = metal::MTLTextureDescriptor::new();
@@ -249,33 +320,88 @@ impl crate::traits::ResourceDevice for super::Context {
             descriptor.setPixelFormat(mtl_format);
             descriptor.setSampleCount(desc.sample_count as _);
             descriptor.setUsage(mtl_usage);
-            descriptor.setStorageMode(metal::MTLStorageMode::Private);
+            // Shared textures live in the memory visible to the CPU,
+            // which is only efficient with unified memory.
+            descriptor.setStorageMode(if desc.usage.contains(crate::TextureUsage::HOST_WRITE) {
+                metal::MTLStorageMode::Shared
+            } else {
+                metal::MTLStorageMode::Private
+            });
 
-            self.device
-                .lock()
-                .unwrap()
-                .newTextureWithDescriptor(&descriptor)
-                .unwrap()
-        });
+            match sparse_heap {
+                Some(ref heap) => heap.newTextureWithDescriptor(&descriptor),
+                None => self
+                    .device
+                    .lock()
+                    .unwrap()
+                    .newTextureWithDescriptor(&descriptor),
+            }
+            .ok_or(crate::ResourceError::OutOfDeviceMemory)
+        })?;
         if !desc.name.is_empty() {
             object.setLabel(Some(&NSString::from_str(desc.name)));
         }
-        super::Texture {
+        Ok(super::Texture {
             raw: Retained::into_raw(object),
-        }
+            format: desc.format,
+        })
     }
 
     fn destroy_texture(&self, texture: super::Texture) {
         let _ = unsafe { Retained::from_raw(texture.raw) };
     }
 
+    fn write_texture(
+        &self,
+        dst: crate::TexturePiece,
+        data: &[u8],
+        bytes_per_row: u32,
+        size: crate::Extent,
+    ) {
+        crate::debug_check_region(&dst, size);
+        let block_info = dst.texture.format.block_info();
+        let bytes_per_image =
+            bytes_per_row as usize * size.height.div_ceil(block_info.dimensions.1 as u32) as usize;
+        assert!(
+            data.len() >= bytes_per_image * size.depth as usize,
+            "Not enough data for {size}"
+        );
+        let region = metal::MTLRegion {
+            origin: super::command::map_origin(&dst.origin),
+            size: super::command::map_extent(&size),
+        };
+        unsafe {
+            dst.texture
+                .as_ref()
+                .replaceRegion_mipmapLevel_slice_withBytes_bytesPerRow_bytesPerImage(
+                    region,
+                    dst.mip_level as usize,
+                    dst.array_layer as usize,
+                    ptr::NonNull::new_unchecked(data.as_ptr() as *mut _),
+                    bytes_per_row as usize,
+                    bytes_per_image,
+                )
+        };
+    }
+
     fn create_texture_view(
         &self,
         texture: super::Texture,
         desc: crate::TextureViewDesc,
     ) -> super::TextureView {
+        let mtl_format = match (texture.format, desc.format) {
+            // Single-aspect views of a combined depth-stencil texture
+            (
+                crate::TextureFormat::Depth32FloatStencil8Uint,
+                crate::TextureFormat::Stencil8Uint,
+            ) => metal::MTLPixelFormat::X32_Stencil8,
+            (
+                crate::TextureFormat::Depth32FloatStencil8Uint,
+                crate::TextureFormat::Depth32Float,
+            ) => metal::MTLPixelFormat::Depth32Float_Stencil8,
+            (_, format) => super::map_texture_format(format),
+        };
         let texture = texture.as_ref();
-        let mtl_format = super::map_texture_format(desc.format);
         let mtl_type = map_view_dimension(desc.dimension, texture.sampleCount());
         let mip_level_count = match desc.subresources.mip_level_count {
             Some(count) => count.get() as usize,
@@ -308,6 +434,7 @@ impl crate::traits::ResourceDevice for super::Context {
         super::TextureView {
             raw: Retained::into_raw(object),
             aspects: desc.format.aspects(),
+            format: desc.format,
         }
     }
 
@@ -316,57 +443,62 @@ impl crate::traits::ResourceDevice for super::Context {
     }
 
     fn create_sampler(&self, desc: crate::SamplerDesc) -> super::Sampler {
-        let object = objc2::rc::autoreleasepool(|_| {
-            let descriptor = metal::MTLSamplerDescriptor::new();
-
-            descriptor.setMinFilter(map_filter_mode(desc.min_filter));
-            descriptor.setMagFilter(map_filter_mode(desc.mag_filter));
-            descriptor.setMipFilter(match desc.mipmap_filter {
-                crate::FilterMode::Nearest => metal::MTLSamplerMipFilter::Nearest,
-                crate::FilterMode::Linear => metal::MTLSamplerMipFilter::Linear,
-            });
+        let _ = desc.check_lod(0.0);
+        self.sampler_cache.get_or_create(&desc, || {
+            let object = objc2::rc::autoreleasepool(|_| {
+                let descriptor = metal::MTLSamplerDescriptor::new();
+
+                descriptor.setMinFilter(map_filter_mode(desc.min_filter));
+                descriptor.setMagFilter(map_filter_mode(desc.mag_filter));
+                descriptor.setMipFilter(match desc.mipmap_filter {
+                    crate::FilterMode::Nearest => metal::MTLSamplerMipFilter::Nearest,
+                    crate::FilterMode::Linear => metal::MTLSamplerMipFilter::Linear,
+                });
+
+                descriptor.setSAddressMode(map_address_mode(desc.address_modes[0]));
+                descriptor.setTAddressMode(map_address_mode(desc.address_modes[1]));
+                descriptor.setRAddressMode(map_address_mode(desc.address_modes[2]));
+
+                if desc.anisotropy_clamp > 1 {
+                    descriptor.setMaxAnisotropy(desc.anisotropy_clamp as usize);
+                }
 
-            descriptor.setSAddressMode(map_address_mode(desc.address_modes[0]));
-            descriptor.setTAddressMode(map_address_mode(desc.address_modes[1]));
-            descriptor.setRAddressMode(map_address_mode(desc.address_modes[2]));
+                descriptor.setLodMinClamp(desc.lod_min_clamp);
+                if let Some(lod) = desc.lod_max_clamp {
+                    descriptor.setLodMaxClamp(lod);
+                }
 
-            if desc.anisotropy_clamp > 1 {
-                descriptor.setMaxAnisotropy(desc.anisotropy_clamp as usize);
-            }
+                // optimization
+                descriptor.setLodAverage(true);
 
-            descriptor.setLodMinClamp(desc.lod_min_clamp);
-            if let Some(lod) = desc.lod_max_clamp {
-                descriptor.setLodMaxClamp(lod);
-            }
-
-            // optimization
-            descriptor.setLodAverage(true);
+                if let Some(fun) = desc.compare {
+                    descriptor.setCompareFunction(super::map_compare_function(fun));
+                }
 
-            if let Some(fun) = desc.compare {
-                descriptor.setCompareFunction(super::map_compare_function(fun));
-            }
+                if let Some(border_color) = desc.border_color {
+                    descriptor.setBorderColor(map_border_color(border_color));
+                }
 
-            if let Some(border_color) = desc.border_color {
-                descriptor.setBorderColor(map_border_color(border_color));
-            }
+                if !desc.name.is_empty() {
+                    descriptor.setLabel(Some(&NSString::from_str(desc.name)));
+                }
+                self.device
+                    .lock()
+                    .unwrap()
+                    .newSamplerStateWithDescriptor(&descriptor)
+                    .unwrap()
+            });
 
-            if !desc.name.is_empty() {
-                descriptor.setLabel(Some(&NSString::from_str(desc.name)));
+            super::Sampler {
+                raw: Retained::into_raw(object),
             }
-            self.device
-                .lock()
-                .unwrap()
-                .newSamplerStateWithDescriptor(&descriptor)
-                .unwrap()
-        });
-
-        super::Sampler {
-            raw: Retained::into_raw(object),
-        }
+        })
     }
 
     fn destroy_sampler(&self, sampler: super::Sampler) {
-        let _ = unsafe { Retained::from_raw(sampler.raw) };
+        if self.sampler_cache.release(sampler) {
+            let _ = unsafe { Retained::from_raw(sampler.raw) };
+        }
     }
 
     fn create_acceleration_structure(
@@ -26,7 +26,11 @@ impl super::Surface {
             let texture = drawable.texture();
             (Retained::cast_unchecked(drawable), texture)
         });
-        super::Frame { drawable, texture }
+        super::Frame {
+            drawable,
+            texture,
+            format: self.info.format,
+        }
     }
 }
 
@@ -143,5 +147,12 @@ impl super::Context {
             });
             surface.render_layer.setDisplaySyncEnabled(vsync);
         }
+        self.reporter.set_surface(crate::SurfaceReport {
+            width: config.size.width,
+            height: config.size.height,
+            format: format!("{:?}", surface.info.format),
+            alpha: format!("{:?}", surface.info.alpha),
+            present_mode: if vsync { "display sync" } else { "immediate" }.to_string(),
+        });
     }
 }
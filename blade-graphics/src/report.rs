@@ -0,0 +1,155 @@
+use std::{fmt, sync::Mutex};
+
+/// Memory heap available to the context.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryHeapReport {
+    /// Size in bytes.
+    pub size: u64,
+    /// If the heap is local to the device, as opposed to the host memory.
+    pub device_local: bool,
+}
+
+/// Details of the last configured surface.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SurfaceReport {
+    pub width: u32,
+    pub height: u32,
+    /// Format of the frames, see `SurfaceInfo::format`.
+    pub format: String,
+    /// Alpha mode of the frames, see `SurfaceInfo::alpha`.
+    pub alpha: String,
+    /// Presentation mode chosen by the backend for the `DisplaySync`.
+    pub present_mode: String,
+}
+
+/// Details of the context initialization, see `Context::report`.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InitReport {
+    /// Name of the graphics API.
+    pub backend: String,
+    /// Version of the graphics API in use.
+    pub api_version: String,
+    pub device: crate::DeviceInformation,
+    /// Enabled extensions of the graphics API, including the instance ones.
+    pub extensions: Vec<String>,
+    /// Optional features of `Capabilities` that are supported.
+    pub features: Vec<String>,
+    pub memory_heaps: Vec<MemoryHeapReport>,
+    /// Surface details, once a surface is configured.
+    pub surface: Option<SurfaceReport>,
+}
+
+impl fmt::Display for InitReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Backend: {} {}", self.backend, self.api_version)?;
+        writeln!(f, "Device: {}", self.device.device_name)?;
+        writeln!(
+            f,
+            "Driver: {} ({})",
+            self.device.driver_name, self.device.driver_info
+        )?;
+        if self.device.is_software_emulated {
+            writeln!(f, "\tsoftware emulated")?;
+        }
+        writeln!(f, "Features:")?;
+        for feature in self.features.iter() {
+            writeln!(f, "\t{feature}")?;
+        }
+        writeln!(f, "Extensions:")?;
+        for extension in self.extensions.iter() {
+            writeln!(f, "\t{extension}")?;
+        }
+        writeln!(f, "Memory heaps:")?;
+        for heap in self.memory_heaps.iter() {
+            let location = if heap.device_local { "device" } else { "host" };
+            writeln!(f, "\t{} MB on {location}", heap.size >> 20)?;
+        }
+        match self.surface {
+            Some(ref surface) => write!(
+                f,
+                "Surface: {}x{} {} with {} alpha, presenting with {}",
+                surface.width, surface.height, surface.format, surface.alpha, surface.present_mode
+            ),
+            None => write!(f, "Surface: not configured"),
+        }
+    }
+}
+
+/// Names of the optional features that are supported.
+fn feature_names(caps: &crate::Capabilities) -> Vec<String> {
+    let flags = [
+        ("binding_array", caps.binding_array),
+        ("ray_query", !caps.ray_query.is_empty()),
+        ("dual_source_blending", caps.dual_source_blending),
+        ("shader_float16", caps.shader_float16),
+        ("cooperative_matrix", caps.cooperative_matrix.is_supported()),
+        (
+            "acceleration_structure_motion",
+            caps.acceleration_structure_motion,
+        ),
+        ("reusable_command_encoders", caps.reusable_command_encoders),
+        ("robustness", caps.robustness),
+        ("depth_resolve", caps.depth_resolve),
+        ("depth_resolve_min_max", caps.depth_resolve_min_max),
+        ("logic_op", caps.logic_op),
+        ("sparse_residency", caps.sparse_tile_size != 0),
+        ("buffer_device_address", caps.buffer_device_address),
+        ("multiview", caps.max_view_count != 0),
+        ("conditional_rendering", caps.conditional_rendering),
+        ("dynamic_offsets", caps.dynamic_offset_alignment != 0),
+    ];
+    flags
+        .iter()
+        .filter(|&&(_, supported)| supported)
+        .map(|&(name, _)| name.to_string())
+        .collect()
+}
+
+/// Initialization report of a context, with the surface details filled in later.
+pub(crate) struct Reporter {
+    init: InitReport,
+    surface: Mutex<Option<SurfaceReport>>,
+}
+
+impl Reporter {
+    pub(crate) fn new(init: InitReport) -> Self {
+        log::info!(
+            "Initialized {} {} on {}",
+            init.backend,
+            init.api_version,
+            init.device.device_name
+        );
+        log::debug!("Extensions: {:?}", init.extensions);
+        Self {
+            init,
+            surface: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn set_surface(&self, report: SurfaceReport) {
+        log::info!(
+            "Configured surface {}x{} {} with {}",
+            report.width,
+            report.height,
+            report.format,
+            report.present_mode
+        );
+        *self.surface.lock().unwrap() = Some(report);
+    }
+}
+
+impl crate::Context {
+    /// Details of the initialization, for logging and crash reports.
+    ///
+    /// Surface details are included once a surface is configured.
+    pub fn report(&self) -> InitReport {
+        InitReport {
+            features: feature_names(&self.capabilities()),
+            surface: self.reporter.surface.lock().unwrap().clone(),
+            ..self.reporter.init.clone()
+        }
+    }
+}
@@ -47,6 +47,10 @@ impl super::Context {
             naga::valid::Capabilities::COOPERATIVE_MATRIX,
             device_caps.cooperative_matrix.is_supported(),
         );
+        caps.set(
+            naga::valid::Capabilities::MULTIVIEW,
+            device_caps.max_view_count > 0,
+        );
         // Subgroup operations (subgroupAdd, subgroupBroadcast, etc.) are
         // broadly supported on modern GPUs via VK_EXT_subgroup_size_control.
         // Enable unconditionally so naga validates subgroup ops and emits
@@ -218,6 +222,13 @@ impl super::Shader {
                             };
                             (proto, var_access)
                         }
+                        _ if proto_binding == crate::ShaderBinding::DynamicBuffer => {
+                            info.binding_sizes[binding_index] = layouter[var.ty].size;
+                            if var.space == naga::AddressSpace::Uniform {
+                                info.uniform_mask |= 1 << binding_index;
+                            }
+                            (crate::ShaderBinding::DynamicBuffer, var_access)
+                        }
                         _ => {
                             let type_layout = &layouter[var.ty];
                             let proto = if var_access.is_empty()
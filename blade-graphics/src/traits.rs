@@ -1,5 +1,10 @@
 use std::{fmt::Debug, hash::Hash};
 
+/// Creation and destruction of the resources.
+///
+/// The methods can be called from any thread, concurrently with
+/// the command recording and submission on the other threads.
+/// A resource can't be destroyed while it's still in use.
 pub trait ResourceDevice {
     type Buffer: Send + Sync + Clone + Copy + Debug + Hash + PartialEq;
     type Texture: Send + Sync + Clone + Copy + Debug + Hash + PartialEq;
@@ -7,11 +12,37 @@ pub trait ResourceDevice {
     type Sampler: Send + Sync + Clone + Copy + Debug + Hash + PartialEq;
     type AccelerationStructure: Send + Sync + Clone + Copy + Debug + Hash + PartialEq;
 
+    /// Create a buffer, panicking on failure.
     fn create_buffer(&self, desc: super::BufferDesc) -> Self::Buffer;
+    /// Create a buffer, returning an error if the device can't fit it.
+    fn try_create_buffer(
+        &self,
+        desc: super::BufferDesc,
+    ) -> Result<Self::Buffer, super::ResourceError>;
     fn sync_buffer(&self, buffer: Self::Buffer);
     fn destroy_buffer(&self, buffer: Self::Buffer);
+    /// Create a texture, panicking on failure.
     fn create_texture(&self, desc: super::TextureDesc) -> Self::Texture;
+    /// Create a texture, returning an error if the device doesn't support
+    /// its format and usage, or can't fit it.
+    fn try_create_texture(
+        &self,
+        desc: super::TextureDesc,
+    ) -> Result<Self::Texture, super::ResourceError>;
     fn destroy_texture(&self, texture: Self::Texture);
+    /// Write data into a texture created with `TextureUsage::HOST_WRITE` on the CPU.
+    ///
+    /// The data is laid out the same way as for `copy_buffer_to_texture`.
+    /// The texture must not be used by the GPU at the same time.
+    /// It's initialized at creation, so it doesn't need `init_texture`,
+    /// which would discard the written contents.
+    fn write_texture(
+        &self,
+        dst: super::TexturePiece,
+        data: &[u8],
+        bytes_per_row: u32,
+        size: super::Extent,
+    );
     fn create_texture_view(
         &self,
         texture: Self::Texture,
@@ -29,15 +60,31 @@ pub trait ResourceDevice {
 
 pub trait ComputePipelineBase {
     fn get_workgroup_size(&self) -> [u32; 3];
+    /// Max number of workgroups in each dimension of a dispatch.
+    fn get_max_group_count(&self) -> [u32; 3];
 }
 
 pub trait ShaderDevice {
     type ComputePipeline: Send + Sync + ComputePipelineBase;
     type RenderPipeline: Send + Sync;
 
+    /// Create a compute pipeline, panicking on failure.
     fn create_compute_pipeline(&self, desc: super::ComputePipelineDesc) -> Self::ComputePipeline;
+    /// Create a compute pipeline, returning an error if its bindings
+    /// exceed the limits of the device.
+    fn try_create_compute_pipeline(
+        &self,
+        desc: super::ComputePipelineDesc,
+    ) -> Result<Self::ComputePipeline, super::PipelineError>;
     fn destroy_compute_pipeline(&self, pipeline: &mut Self::ComputePipeline);
+    /// Create a render pipeline, panicking on failure.
     fn create_render_pipeline(&self, desc: super::RenderPipelineDesc) -> Self::RenderPipeline;
+    /// Create a render pipeline, returning an error if its bindings
+    /// exceed the limits of the device.
+    fn try_create_render_pipeline(
+        &self,
+        desc: super::RenderPipelineDesc,
+    ) -> Result<Self::RenderPipeline, super::PipelineError>;
     fn destroy_render_pipeline(&self, pipeline: &mut Self::RenderPipeline);
     fn get_compute_pipeline_statistics(
         &self,
@@ -56,6 +103,19 @@ pub trait CommandDevice {
     fn create_command_encoder(&self, desc: super::CommandEncoderDesc) -> Self::CommandEncoder;
     fn destroy_command_encoder(&self, encoder: &mut Self::CommandEncoder);
     fn submit(&self, encoder: &mut Self::CommandEncoder) -> Self::SyncPoint;
+    /// Submit several encoders at once, executing them in the given order.
+    ///
+    /// The encoders can be recorded on different threads beforehand.
+    /// Their passes are synchronized with each other the same way as
+    /// the passes within a single encoder. All of them must be started,
+    /// but an encoder without any passes is fine and changes nothing.
+    ///
+    /// Only one of them can present, and the frame is presented after
+    /// the whole batch. Encoders before the presenting one don't wait
+    /// for the frame to be acquired.
+    ///
+    /// The returned sync point is reached when all of the encoders are done.
+    fn submit_batch(&self, encoders: &mut [&mut Self::CommandEncoder]) -> Self::SyncPoint;
     fn wait_for(&self, sp: &Self::SyncPoint, timeout_ms: u32) -> Result<bool, super::DeviceError>;
 }
 
@@ -63,9 +123,39 @@ pub trait CommandEncoder {
     type Texture: Send + Sync + Clone + Copy + Debug;
     type Frame: Send + Sync + Debug;
     fn start(&mut self);
+    /// Start recording commands that can be submitted multiple times,
+    /// until the encoder is started again or invalidated.
+    /// Requires `Capabilities::reusable_command_encoders`.
+    ///
+    /// The commands are submitted exactly as recorded, so it's up to the user
+    /// to guarantee that between the submissions:
+    ///   - all the used resources stay alive, and their contents can change
+    ///     but not their handles. Shader data is bound at the recording time.
+    ///   - the resources are in the same state when the commands start,
+    ///     e.g. a render target with `InitOp::Load` has the expected contents.
+    ///   - the previous submission is finished, unless the encoder only
+    ///     reads the resources that the other submissions don't write.
+    ///
+    /// Reusable encoders can't initialize textures or present frames,
+    /// and their timings are not updated after the first submission.
+    /// This is checked in debug builds.
+    fn start_reusable(&mut self);
+    /// Discard the recorded commands, requiring the encoder
+    /// to be started again before the next submission.
+    fn invalidate(&mut self);
     fn init_texture(&mut self, texture: Self::Texture);
     fn present(&mut self, frame: Self::Frame);
     fn timings(&self) -> &super::Timings;
+    /// Index of the current recording, incremented by every start, starting with 1.
+    fn recording_index(&self) -> u64;
+    /// Recording index, in which `timings` were measured. They are resolved
+    /// a few recordings later, depending on the backend and the buffer count.
+    /// `None` if there are no timings.
+    fn timings_recording_index(&self) -> Option<u64>;
+    /// Binding counters since the last start of the encoder.
+    fn binding_stats(&self) -> super::BindingStats;
+    /// Memory retained by the encoder for recording.
+    fn memory_stats(&self) -> super::EncoderMemoryStats;
 }
 
 pub trait TransferEncoder {
@@ -74,12 +164,28 @@ pub trait TransferEncoder {
 
     fn fill_buffer(&mut self, dst: Self::BufferPiece, size: u64, value: u8);
     fn copy_buffer_to_buffer(&mut self, src: Self::BufferPiece, dst: Self::BufferPiece, size: u64);
+    /// Copy the texels as is, which requires the formats to have the same texel block size.
+    /// On Metal, the formats have to be the same.
     fn copy_texture_to_texture(
         &mut self,
         src: Self::TexturePiece,
         dst: Self::TexturePiece,
         size: super::Extent,
     );
+    /// Copy a 2D region of a texture into a region of another one,
+    /// scaling it with the filter and converting between the color formats.
+    /// Depth and stencil textures can't be blitted.
+    ///
+    /// On Metal, the source texture needs `TextureUsage::RESOURCE` and
+    /// the destination needs `TextureUsage::TARGET`.
+    fn blit(
+        &mut self,
+        src: Self::TexturePiece,
+        src_size: super::Extent,
+        dst: Self::TexturePiece,
+        dst_size: super::Extent,
+        filter: super::FilterMode,
+    );
 
     fn copy_buffer_to_texture(
         &mut self,
@@ -103,6 +209,10 @@ pub trait AccelerationStructureEncoder {
     type AccelerationStructureMesh: Send + Sync + Clone + Debug;
     type BufferPiece: Send + Sync + Clone + Copy + Debug;
 
+    /// Build a bottom-level structure from the meshes.
+    ///
+    /// The scratch offset has to be aligned to `AccelerationStructureSizes::scratch_alignment`.
+    /// Consecutive builds within one pass are synchronized, so they can share scratch memory.
     fn build_bottom_level(
         &mut self,
         acceleration_structure: Self::AccelerationStructure,
@@ -110,6 +220,9 @@ pub trait AccelerationStructureEncoder {
         scratch_data: Self::BufferPiece,
     );
 
+    /// Build a top-level structure from the instance data.
+    ///
+    /// Same scratch requirements as `build_bottom_level`.
     fn build_top_level(
         &mut self,
         acceleration_structure: Self::AccelerationStructure,
@@ -127,7 +240,20 @@ pub trait RenderEncoder {
 }
 
 pub trait PipelineEncoder {
+    /// Bind shader data at a given group of the current pipeline.
+    ///
+    /// Binding the same data again at the same group may be skipped
+    /// by the backend, since the previous binding is still in place.
+    /// Plain data is compared by value, so changed uniforms are always uploaded.
     fn bind<D: super::ShaderData>(&mut self, group: u32, data: &D);
+
+    /// Set the offsets of the dynamic buffers of a bound group,
+    /// see `ShaderBinding::DynamicBuffer`.
+    ///
+    /// The offsets are added to the buffer pieces given to `bind`, one per
+    /// dynamic buffer in the order of the bindings. They have to be multiples of
+    /// `Capabilities::dynamic_offset_alignment`. Binding the group resets them to zero.
+    fn set_dynamic_offsets(&mut self, group: u32, offsets: &[u32]);
 }
 
 pub trait ComputePipelineEncoder: PipelineEncoder {
@@ -43,6 +43,53 @@ pub fn emit_annotated_error<E: Error>(ann_err: &naga::WithSpan<E>, filename: &st
 }
 
 impl super::TextureFormat {
+    /// All the formats, in the declaration order.
+    pub const ALL: &'static [Self] = &[
+        Self::R8Unorm,
+        Self::Rg8Unorm,
+        Self::Rg8Snorm,
+        Self::Rgba8Unorm,
+        Self::Rgba8UnormSrgb,
+        Self::Bgra8Unorm,
+        Self::Bgra8UnormSrgb,
+        Self::Rgba8Snorm,
+        Self::R16Float,
+        Self::Rg16Float,
+        Self::Rgba16Float,
+        Self::R32Float,
+        Self::Rg32Float,
+        Self::Rgba32Float,
+        Self::R32Uint,
+        Self::Rg32Uint,
+        Self::Rgba32Uint,
+        Self::Depth32Float,
+        Self::Depth32FloatStencil8Uint,
+        Self::Stencil8Uint,
+        Self::Bc1Unorm,
+        Self::Bc1UnormSrgb,
+        Self::Bc2Unorm,
+        Self::Bc2UnormSrgb,
+        Self::Bc3Unorm,
+        Self::Bc3UnormSrgb,
+        Self::Bc4Unorm,
+        Self::Bc4Snorm,
+        Self::Bc5Unorm,
+        Self::Bc5Snorm,
+        Self::Bc6hUfloat,
+        Self::Bc6hFloat,
+        Self::Bc7Unorm,
+        Self::Bc7UnormSrgb,
+        Self::Etc2Rgb8Unorm,
+        Self::Etc2Rgb8UnormSrgb,
+        Self::Etc2Rgba8Unorm,
+        Self::Etc2Rgba8UnormSrgb,
+        Self::Astc4x4Unorm,
+        Self::Astc4x4UnormSrgb,
+        Self::Rgb10a2Unorm,
+        Self::Rg11b10Ufloat,
+        Self::Rgb9e5Ufloat,
+    ];
+
     pub const fn block_info(&self) -> super::TexelBlockInfo {
         const fn uncompressed(size: u8) -> super::TexelBlockInfo {
             super::TexelBlockInfo {
@@ -91,12 +138,51 @@ impl super::TextureFormat {
             Self::Bc6hFloat => cx_bc(16),
             Self::Bc7Unorm => cx_bc(16),
             Self::Bc7UnormSrgb => cx_bc(16),
+            Self::Etc2Rgb8Unorm => cx_bc(8),
+            Self::Etc2Rgb8UnormSrgb => cx_bc(8),
+            Self::Etc2Rgba8Unorm => cx_bc(16),
+            Self::Etc2Rgba8UnormSrgb => cx_bc(16),
+            Self::Astc4x4Unorm => cx_bc(16),
+            Self::Astc4x4UnormSrgb => cx_bc(16),
             Self::Rgb10a2Unorm => uncompressed(4),
             Self::Rg11b10Ufloat => uncompressed(4),
             Self::Rgb9e5Ufloat => uncompressed(4),
         }
     }
 
+    /// Return true if the texels are encoded in sRGB,
+    /// which is converted to and from linear on access.
+    pub const fn is_srgb(&self) -> bool {
+        matches!(
+            *self,
+            Self::Rgba8UnormSrgb
+                | Self::Bgra8UnormSrgb
+                | Self::Bc1UnormSrgb
+                | Self::Bc2UnormSrgb
+                | Self::Bc3UnormSrgb
+                | Self::Bc7UnormSrgb
+                | Self::Etc2Rgb8UnormSrgb
+                | Self::Etc2Rgba8UnormSrgb
+                | Self::Astc4x4UnormSrgb
+        )
+    }
+
+    /// Return true if the color texels are unnormalized integers.
+    pub const fn is_integer(&self) -> bool {
+        matches!(*self, Self::R32Uint | Self::Rg32Uint | Self::Rgba32Uint)
+    }
+
+    /// Check if the texels can be copied between the formats without a conversion.
+    pub fn is_copy_compatible(&self, other: Self) -> bool {
+        if self.aspects() != super::TexelAspects::COLOR {
+            return *self == other;
+        }
+        let (info, other_info) = (self.block_info(), other.block_info());
+        other.aspects() == super::TexelAspects::COLOR
+            && info.dimensions == other_info.dimensions
+            && info.size == other_info.size
+    }
+
     pub fn aspects(&self) -> super::TexelAspects {
         match *self {
             Self::Depth32Float => super::TexelAspects::DEPTH,
@@ -112,7 +198,82 @@ impl super::TextureFormat {
     }
 }
 
+// The match is exhaustive, so adding a format fails to compile here
+// as a reminder to extend `ALL`, and the order check catches the gaps.
+const _: () = {
+    use super::TextureFormat as Tf;
+    let mut i = 0;
+    while i < Tf::ALL.len() {
+        assert!(
+            Tf::ALL[i] as usize == i,
+            "TextureFormat::ALL doesn't follow the declaration order"
+        );
+        i += 1;
+    }
+    match Tf::ALL[Tf::ALL.len() - 1] {
+        Tf::R8Unorm
+        | Tf::Rg8Unorm
+        | Tf::Rg8Snorm
+        | Tf::Rgba8Unorm
+        | Tf::Rgba8UnormSrgb
+        | Tf::Bgra8Unorm
+        | Tf::Bgra8UnormSrgb
+        | Tf::Rgba8Snorm
+        | Tf::R16Float
+        | Tf::Rg16Float
+        | Tf::Rgba16Float
+        | Tf::R32Float
+        | Tf::Rg32Float
+        | Tf::Rgba32Float
+        | Tf::R32Uint
+        | Tf::Rg32Uint
+        | Tf::Rgba32Uint
+        | Tf::Depth32Float
+        | Tf::Depth32FloatStencil8Uint
+        | Tf::Stencil8Uint
+        | Tf::Bc1Unorm
+        | Tf::Bc1UnormSrgb
+        | Tf::Bc2Unorm
+        | Tf::Bc2UnormSrgb
+        | Tf::Bc3Unorm
+        | Tf::Bc3UnormSrgb
+        | Tf::Bc4Unorm
+        | Tf::Bc4Snorm
+        | Tf::Bc5Unorm
+        | Tf::Bc5Snorm
+        | Tf::Bc6hUfloat
+        | Tf::Bc6hFloat
+        | Tf::Bc7Unorm
+        | Tf::Bc7UnormSrgb
+        | Tf::Etc2Rgb8Unorm
+        | Tf::Etc2Rgb8UnormSrgb
+        | Tf::Etc2Rgba8Unorm
+        | Tf::Etc2Rgba8UnormSrgb
+        | Tf::Astc4x4Unorm
+        | Tf::Astc4x4UnormSrgb
+        | Tf::Rgb10a2Unorm
+        | Tf::Rg11b10Ufloat
+        | Tf::Rgb9e5Ufloat => {}
+    }
+};
+
 impl super::TextureColor {
+    pub const fn float_value(&self) -> [f32; 4] {
+        match *self {
+            Self::TransparentBlack => [0.0; 4],
+            Self::OpaqueBlack => [0.0, 0.0, 0.0, 1.0],
+            Self::White => [1.0; 4],
+        }
+    }
+
+    pub const fn uint_value(&self) -> [u32; 4] {
+        match *self {
+            Self::TransparentBlack => [0; 4],
+            Self::OpaqueBlack => [0, 0, 0, !0],
+            Self::White => [!0; 4],
+        }
+    }
+
     pub const fn stencil_clear_value(&self) -> u32 {
         match *self {
             crate::TextureColor::TransparentBlack => 0,
@@ -130,6 +291,19 @@ impl super::TextureColor {
     }
 }
 
+impl super::ClearColor {
+    /// Check if the value can be written into the texels of a format.
+    pub fn fits(&self, format: super::TextureFormat) -> bool {
+        if format.aspects() != super::TexelAspects::COLOR {
+            return false;
+        }
+        match *self {
+            Self::Float(_) => !format.is_integer(),
+            Self::Uint(_) => format.is_integer(),
+        }
+    }
+}
+
 impl super::ComputePipeline {
     /// Return the dispatch group counts sufficient to cover the given extent.
     pub fn get_dispatch_for(&self, extent: super::Extent) -> [u32; 3] {
@@ -140,4 +314,51 @@ impl super::ComputePipeline {
             extent.depth.div_ceil(wg_size[2]),
         ]
     }
+
+    /// Return the dispatch group counts sufficient to cover `count` invocations
+    /// in a flat range, see `fold_group_count`.
+    ///
+    /// The shader needs to reconstruct the flat index from the group,
+    /// and skip the invocations past the end:
+    /// ```wgsl
+    /// @compute @workgroup_size(64)
+    /// fn main(
+    ///     @builtin(workgroup_id) group_id: vec3<u32>,
+    ///     @builtin(num_workgroups) group_count: vec3<u32>,
+    ///     @builtin(local_invocation_index) local_index: u32,
+    /// ) {
+    ///     let group = group_id.x + (group_id.y + group_id.z * group_count.y) * group_count.x;
+    ///     let index = group * 64u + local_index;
+    ///     if (index >= params.count) {
+    ///         return;
+    ///     }
+    ///     ...
+    /// }
+    /// ```
+    pub fn get_dispatch_for_linear(&self, count: u32) -> [u32; 3] {
+        let wg_size = self.get_workgroup_size();
+        let group_size = wg_size[0] * wg_size[1] * wg_size[2];
+        fold_group_count(count.div_ceil(group_size), self.get_max_group_count())
+    }
+}
+
+/// Spread a flat number of workgroups over the dimensions of a dispatch,
+/// so that none of them exceeds `max_group_count`.
+///
+/// Once the groups don't fit into X, it's filled up to the limit,
+/// and the rest is folded into Y, and then Z. The result may contain
+/// up to a row of extra groups.
+pub fn fold_group_count(group_count: u32, max_group_count: [u32; 3]) -> [u32; 3] {
+    if group_count <= max_group_count[0] {
+        return [group_count, 1, 1];
+    }
+    let x = max_group_count[0] as u64;
+    let rows = (group_count as u64).div_ceil(x);
+    let y = rows.min(max_group_count[1] as u64);
+    let z = rows.div_ceil(y);
+    assert!(
+        z <= max_group_count[2] as u64,
+        "{group_count} groups don't fit into the dispatch limits {max_group_count:?}"
+    );
+    [x as u32, y as u32, z as u32]
 }
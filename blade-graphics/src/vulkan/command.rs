@@ -1,6 +1,9 @@
 use ash::vk;
 use std::{ptr, str, time::Duration};
 
+/// Max number of color targets in a render pass, matching the common device limit.
+pub(super) const MAX_COLOR_TARGETS: usize = 8;
+
 impl super::CrashHandler {
     fn add_marker(&mut self, marker: &str) -> u32 {
         if self.next_offset < self.raw_string.len() {
@@ -91,6 +94,14 @@ impl<T: bytemuck::Pod> crate::ShaderBindable for T {
 }
 impl crate::ShaderBindable for super::TextureView {
     fn bind_to(&self, ctx: &mut super::PipelineContext, index: u32) {
+        if let Some((image, aspects)) = ctx.depth_stencil_writes {
+            debug_assert!(
+                image != self.image || !aspects.intersects(self.aspects),
+                "Texture view with {:?} is bound while being written as the depth-stencil target, \
+                 it needs to be in `RenderTargetSet::depth_stencil_read_only`",
+                self.aspects,
+            );
+        }
         ctx.write(
             index,
             vk::DescriptorImageInfo {
@@ -101,6 +112,23 @@ impl crate::ShaderBindable for super::TextureView {
         );
     }
 }
+impl crate::ShaderBindable for crate::Nullable<super::TextureView> {
+    fn bind_to(&self, ctx: &mut super::PipelineContext, index: u32) {
+        match (self.0, ctx.dummies) {
+            (Some(ref view), _) => view.bind_to(ctx, index),
+            (None, Some(dummies)) => dummies.black.bind_to(ctx, index),
+            // Null descriptors read as zeros
+            (None, None) => ctx.write(
+                index,
+                vk::DescriptorImageInfo {
+                    sampler: vk::Sampler::null(),
+                    image_view: vk::ImageView::null(),
+                    image_layout: vk::ImageLayout::GENERAL,
+                },
+            ),
+        }
+    }
+}
 impl<'a, const N: crate::ResourceIndex> crate::ShaderBindable for &'a crate::TextureArray<N> {
     fn bind_to(&self, ctx: &mut super::PipelineContext, index: u32) {
         assert!(self.data.len() <= N as usize);
@@ -142,6 +170,22 @@ impl crate::ShaderBindable for crate::BufferPiece {
         );
     }
 }
+impl crate::ShaderBindable for crate::Nullable<crate::BufferPiece> {
+    fn bind_to(&self, ctx: &mut super::PipelineContext, index: u32) {
+        match (self.0, ctx.dummies) {
+            (Some(ref piece), _) => piece.bind_to(ctx, index),
+            (None, Some(dummies)) => crate::BufferPiece::from(dummies.buffer).bind_to(ctx, index),
+            (None, None) => ctx.write(
+                index,
+                vk::DescriptorBufferInfo {
+                    buffer: vk::Buffer::null(),
+                    offset: 0,
+                    range: vk::WHOLE_SIZE,
+                },
+            ),
+        }
+    }
+}
 impl<'a, const N: crate::ResourceIndex> crate::ShaderBindable for &'a crate::BufferArray<N> {
     fn bind_to(&self, ctx: &mut super::PipelineContext, index: u32) {
         assert!(self.data.len() <= N as usize);
@@ -181,7 +225,7 @@ impl<'a, const N: crate::ResourceIndex> crate::ShaderBindable
 }
 
 impl crate::TexturePiece {
-    fn subresource_layers(&self) -> vk::ImageSubresourceLayers {
+    pub(super) fn subresource_layers(&self) -> vk::ImageSubresourceLayers {
         vk::ImageSubresourceLayers {
             aspect_mask: super::map_aspects(self.texture.format.aspects()),
             mip_level: self.mip_level,
@@ -191,7 +235,7 @@ impl crate::TexturePiece {
     }
 }
 
-fn map_origin(origin: &[u32; 3]) -> vk::Offset3D {
+pub(super) fn map_origin(origin: &[u32; 3]) -> vk::Offset3D {
     vk::Offset3D {
         x: origin[0] as i32,
         y: origin[1] as i32,
@@ -217,6 +261,23 @@ fn make_buffer_image_copy(
     }
 }
 
+/// Load op of the stencil aspect of a depth-stencil target,
+/// which can differ from the one of the depth aspect.
+fn map_stencil_load_op(rt: &crate::RenderTarget) -> vk::AttachmentLoadOp {
+    match rt.init_op {
+        crate::InitOp::Load => vk::AttachmentLoadOp::LOAD,
+        crate::InitOp::DontCare => vk::AttachmentLoadOp::DONT_CARE,
+        crate::InitOp::Clear(_)
+        | crate::InitOp::ClearColor(_)
+        | crate::InitOp::ClearDepthStencil { .. } => {
+            match rt.depth_stencil_clear_values(rt.view.format) {
+                (_, Some(_)) => vk::AttachmentLoadOp::CLEAR,
+                (_, None) => vk::AttachmentLoadOp::LOAD,
+            }
+        }
+    }
+}
+
 fn map_render_target(rt: &crate::RenderTarget) -> vk::RenderingAttachmentInfo<'static> {
     let mut vk_info = vk::RenderingAttachmentInfo::default()
         .image_view(rt.view.raw)
@@ -226,44 +287,58 @@ fn map_render_target(rt: &crate::RenderTarget) -> vk::RenderingAttachmentInfo<'s
         crate::InitOp::Load => vk_info = vk_info.load_op(vk::AttachmentLoadOp::LOAD),
         crate::InitOp::DontCare => vk_info = vk_info.load_op(vk::AttachmentLoadOp::DONT_CARE),
 
-        crate::InitOp::Clear(color) => {
-            let cv = if rt.view.aspects.contains(crate::TexelAspects::COLOR) {
-                vk::ClearValue {
+        crate::InitOp::Clear(_)
+        | crate::InitOp::ClearColor(_)
+        | crate::InitOp::ClearDepthStencil { .. } => {
+            if rt.view.aspects.contains(crate::TexelAspects::COLOR) {
+                let color = rt.color_clear_value(rt.view.format).unwrap();
+                let cv = vk::ClearValue {
                     color: match color {
-                        crate::TextureColor::TransparentBlack => {
-                            vk::ClearColorValue { float32: [0.0; 4] }
-                        }
-                        crate::TextureColor::OpaqueBlack => vk::ClearColorValue {
-                            float32: [0.0, 0.0, 0.0, 1.0],
-                        },
-                        crate::TextureColor::White => vk::ClearColorValue { float32: [1.0; 4] },
+                        crate::ClearColor::Float(value) => vk::ClearColorValue { float32: value },
+                        crate::ClearColor::Uint(value) => vk::ClearColorValue { uint32: value },
                     },
-                }
+                };
+                vk_info = vk_info.load_op(vk::AttachmentLoadOp::CLEAR).clear_value(cv);
             } else {
-                vk::ClearValue {
+                // The stencil aspect gets its own load op, see `map_stencil_load_op`
+                let (depth, stencil) = rt.depth_stencil_clear_values(rt.view.format);
+                let cv = vk::ClearValue {
                     depth_stencil: vk::ClearDepthStencilValue {
-                        depth: color.depth_clear_value(),
-                        stencil: color.stencil_clear_value(),
+                        depth: depth.unwrap_or_default(),
+                        stencil: stencil.unwrap_or_default(),
                     },
-                }
-            };
-
-            vk_info = vk_info.load_op(vk::AttachmentLoadOp::CLEAR).clear_value(cv);
+                };
+                vk_info = vk_info
+                    .load_op(match depth {
+                        Some(_) => vk::AttachmentLoadOp::CLEAR,
+                        None => vk::AttachmentLoadOp::LOAD,
+                    })
+                    .clear_value(cv);
+            }
         }
     }
 
-    if let crate::FinishOp::ResolveTo(resolve_view) = rt.finish_op {
+    if let Some((resolve_view, depth_mode)) = rt.finish_op.resolve() {
+        let resolve_mode = if rt.view.aspects.contains(crate::TexelAspects::COLOR) {
+            vk::ResolveModeFlags::AVERAGE
+        } else {
+            match depth_mode {
+                crate::DepthResolveMode::SampleZero => vk::ResolveModeFlags::SAMPLE_ZERO,
+                crate::DepthResolveMode::Min => vk::ResolveModeFlags::MIN,
+                crate::DepthResolveMode::Max => vk::ResolveModeFlags::MAX,
+            }
+        };
         vk_info = vk_info
             .resolve_image_view(resolve_view.raw)
             .resolve_image_layout(vk::ImageLayout::GENERAL)
-            .resolve_mode(vk::ResolveModeFlags::AVERAGE);
+            .resolve_mode(resolve_mode);
     }
 
     vk_info.store_op = match rt.finish_op {
         crate::FinishOp::Store => vk::AttachmentStoreOp::STORE,
         crate::FinishOp::Discard => vk::AttachmentStoreOp::DONT_CARE,
         crate::FinishOp::Ignore => vk::AttachmentStoreOp::DONT_CARE,
-        crate::FinishOp::ResolveTo(..) => {
+        crate::FinishOp::ResolveTo(..) | crate::FinishOp::ResolveDepthTo(..) => {
             /*
                 TODO: DONT_CARE is most optimal in many cases where the msaa texture itself is never read afterwards but only the resolved,
                       but how can the user specify this in blade?
@@ -322,11 +397,18 @@ impl super::CommandEncoder {
                     index,
                 );
             }
-            cmd_buf.timed_pass_names.push(label.to_string());
+            cmd_buf.timed_pass_names.push(label);
         }
     }
 
     fn begin_pass(&mut self, label: &str) {
+        debug_assert!(
+            matches!(
+                self.recording,
+                crate::RecordingState::Transient | crate::RecordingState::Reusable
+            ),
+            "Command encoder is not recording"
+        );
         self.barrier();
         self.add_marker(label);
         self.add_timestamp(label);
@@ -347,7 +429,21 @@ impl super::CommandEncoder {
         }
     }
 
+    pub(super) fn check_submittable(&self) {
+        assert_ne!(
+            self.recording,
+            crate::RecordingState::Invalid,
+            "Command encoder is invalidated, it needs to be started again"
+        );
+    }
+
     pub(super) fn finish(&mut self) -> vk::CommandBuffer {
+        match self.recording {
+            crate::RecordingState::Transient => {}
+            crate::RecordingState::Reusable => self.recording = crate::RecordingState::Recorded,
+            crate::RecordingState::Recorded => return self.buffers[0].raw,
+            crate::RecordingState::Invalid => unreachable!(),
+        }
         self.barrier();
         self.add_marker("finish");
         let cmd_buf = self.buffers.first_mut().unwrap();
@@ -404,6 +500,7 @@ impl super::CommandEncoder {
         super::AccelerationStructureCommandEncoder {
             raw: self.buffers[0].raw,
             device: &self.device,
+            has_builds: false,
         }
     }
 
@@ -412,7 +509,7 @@ impl super::CommandEncoder {
         super::ComputeCommandEncoder {
             cmd_buf: self.buffers.first_mut().unwrap(),
             device: &self.device,
-            update_data: &mut self.update_data,
+            binding: &mut self.binding,
         }
     }
 
@@ -424,25 +521,47 @@ impl super::CommandEncoder {
         self.begin_pass(label);
 
         let mut target_size = [0u16; 2];
-        let mut color_attachments = Vec::with_capacity(targets.colors.len());
-        let depth_stencil_attachment;
-        for rt in targets.colors {
+        assert!(
+            targets.colors.len() <= MAX_COLOR_TARGETS,
+            "Too many color targets: {}",
+            targets.colors.len()
+        );
+        assert!(
+            !targets.colors.is_empty() || targets.depth_stencil.is_some(),
+            "Render pass '{label}' has neither color nor depth targets"
+        );
+        let mut color_attachments = [vk::RenderingAttachmentInfo::default(); MAX_COLOR_TARGETS];
+        let mut depth_stencil_attachment = vk::RenderingAttachmentInfo::default();
+        let mut stencil_attachment = vk::RenderingAttachmentInfo::default();
+        for (attachment, rt) in color_attachments.iter_mut().zip(targets.colors) {
             target_size = rt.view.target_size;
-            color_attachments.push(map_render_target(rt));
+            *attachment = map_render_target(rt);
         }
 
         let mut rendering_info = vk::RenderingInfoKHR::default()
             .layer_count(1)
-            .color_attachments(&color_attachments);
+            .view_mask(super::map_view_mask(targets.multiview))
+            .color_attachments(&color_attachments[..targets.colors.len()]);
 
-        if let Some(rt) = targets.depth_stencil {
+        self.binding.depth_stencil_writes = None;
+        if let Some(ref rt) = targets.depth_stencil {
             target_size = rt.view.target_size;
-            depth_stencil_attachment = map_render_target(&rt);
+            let writes = targets.depth_stencil_writes(rt.view.aspects);
+            self.binding.depth_stencil_writes = Some((rt.view.image, writes));
+            depth_stencil_attachment = map_render_target(rt);
+            stencil_attachment = depth_stencil_attachment;
+            stencil_attachment.load_op = map_stencil_load_op(rt);
             if rt.view.aspects.contains(crate::TexelAspects::DEPTH) {
+                if !writes.contains(crate::TexelAspects::DEPTH) {
+                    depth_stencil_attachment.store_op = self.device.read_only_store_op;
+                }
                 rendering_info = rendering_info.depth_attachment(&depth_stencil_attachment);
             }
             if rt.view.aspects.contains(crate::TexelAspects::STENCIL) {
-                rendering_info = rendering_info.stencil_attachment(&depth_stencil_attachment);
+                if !writes.contains(crate::TexelAspects::STENCIL) {
+                    stencil_attachment.store_op = self.device.read_only_store_op;
+                }
+                rendering_info = rendering_info.stencil_attachment(&stencil_attachment);
             }
         }
 
@@ -471,60 +590,63 @@ impl super::CommandEncoder {
             self.device
                 .core
                 .cmd_set_scissor(cmd_buf.raw, 0, &[render_area]);
-            self.device
-                .dynamic_rendering
-                .cmd_begin_rendering(cmd_buf.raw, &rendering_info);
+            match self.device.dynamic_rendering {
+                Some(ref dynamic_rendering) => {
+                    dynamic_rendering.cmd_begin_rendering(cmd_buf.raw, &rendering_info)
+                }
+                None => self.device.begin_render_pass(
+                    cmd_buf,
+                    &targets,
+                    &color_attachments[..targets.colors.len()],
+                    targets
+                        .depth_stencil
+                        .as_ref()
+                        .map(|_| (&depth_stencil_attachment, &stencil_attachment)),
+                    render_area,
+                ),
+            }
         };
 
         super::RenderCommandEncoder {
             cmd_buf,
             device: &self.device,
-            update_data: &mut self.update_data,
+            binding: &mut self.binding,
+            depth_stencil_read_only: targets.depth_stencil_read_only,
+            conditional: false,
         }
     }
 
-    pub(super) fn check_gpu_crash<T>(&self, ret: Result<T, vk::Result>) -> Option<T> {
-        match ret {
-            Ok(value) => Some(value),
-            Err(vk::Result::ERROR_DEVICE_LOST) => match self.crash_handler {
-                Some(ref ch) => {
-                    let last_id = unsafe { *(ch.marker_buf.data() as *mut u32) };
-                    if last_id != 0 {
-                        let (history, last_marker) = ch.extract(last_id);
-                        log::error!("Last GPU executed marker is '{last_marker}'");
-                        log::info!("Marker history: {}", history);
-                    }
-                    panic!("GPU has crashed in {}", ch.name);
-                }
-                None => {
-                    panic!("GPU has crashed, and no debug information is available.");
-                }
-            },
-            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
-                log::warn!("GPU frame is out of date");
-                None
-            }
-            Err(other) => panic!("GPU error {}", other),
-        }
+    fn retained_bytes(&self) -> usize {
+        self.binding.retained_bytes()
+            + self.temp_label.capacity()
+            + crate::timings_retained_bytes(&self.timings)
+            + self
+                .buffers
+                .iter()
+                .map(|cmd_buf| cmd_buf.timed_pass_names.retained_bytes())
+                .sum::<usize>()
     }
-}
 
-#[hidden_trait::expose]
-impl crate::traits::CommandEncoder for super::CommandEncoder {
-    type Texture = super::Texture;
-    type Frame = super::Frame;
-
-    fn start(&mut self) {
+    fn begin_recording(&mut self, recording: crate::RecordingState) {
+        self.peak_retained_bytes = self.peak_retained_bytes.max(self.retained_bytes());
+        self.recording = recording;
+        self.binding.stats = Default::default();
         self.buffers.rotate_left(1);
+        self.recording_index += 1;
         let cmd_buf = self.buffers.first_mut().unwrap();
         self.device
             .reset_descriptor_pool(&mut cmd_buf.descriptor_pool);
+        self.device.destroy_framebuffers(cmd_buf);
         if let Some(ref mut scratch) = cmd_buf.scratch {
             scratch.offset = 0;
         }
 
         let vk_info = vk::CommandBufferBeginInfo {
-            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            flags: match recording {
+                // The previous submission may still be in flight
+                crate::RecordingState::Reusable => vk::CommandBufferUsageFlags::SIMULTANEOUS_USE,
+                _ => vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            },
             ..Default::default()
         };
         unsafe {
@@ -535,8 +657,10 @@ impl crate::traits::CommandEncoder for super::CommandEncoder {
         }
 
         if let Some(ref timing) = self.device.timing {
-            self.timings.clear();
-            if !cmd_buf.timed_pass_names.is_empty() {
+            if cmd_buf.timed_pass_names.is_empty() {
+                self.timings.clear();
+                self.timings_recording_index = None;
+            } else {
                 let mut timestamps = [0u64; super::QUERY_POOL_SIZE];
                 unsafe {
                     self.device
@@ -549,17 +673,18 @@ impl crate::traits::CommandEncoder for super::CommandEncoder {
                         )
                         .unwrap();
                 }
-                let mut prev = timestamps[0];
-                for (name, &ts) in cmd_buf
-                    .timed_pass_names
-                    .drain(..)
-                    .zip(timestamps[1..].iter())
-                {
-                    let diff = (ts - prev) as f32 * timing.period;
-                    prev = ts;
-                    self.timings.push((name, Duration::from_nanos(diff as _)));
-                }
+                let durations = timestamps.windows(2).map(|pair| {
+                    let diff = (pair[1] - pair[0]) as f32 * timing.period;
+                    Duration::from_nanos(diff as _)
+                });
+                crate::fill_timings(
+                    &mut self.timings,
+                    cmd_buf.timed_pass_names.iter().zip(durations),
+                );
+                self.timings_recording_index = Some(cmd_buf.timed_pass_names.recording);
+                cmd_buf.timed_pass_names.clear();
             }
+            cmd_buf.timed_pass_names.recording = self.recording_index;
             unsafe {
                 self.device.core.cmd_reset_query_pool(
                     cmd_buf.raw,
@@ -571,7 +696,55 @@ impl crate::traits::CommandEncoder for super::CommandEncoder {
         }
     }
 
+    pub(super) fn check_gpu_crash<T>(&self, ret: Result<T, vk::Result>) -> Option<T> {
+        match ret {
+            Ok(value) => Some(value),
+            Err(vk::Result::ERROR_DEVICE_LOST) => match self.crash_handler {
+                Some(ref ch) => {
+                    let last_id = unsafe { *(ch.marker_buf.data() as *mut u32) };
+                    if last_id != 0 {
+                        let (history, last_marker) = ch.extract(last_id);
+                        log::error!("Last GPU executed marker is '{last_marker}'");
+                        log::info!("Marker history: {}", history);
+                    }
+                    panic!("GPU has crashed in {}", ch.name);
+                }
+                None => {
+                    panic!("GPU has crashed, and no debug information is available.");
+                }
+            },
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                log::warn!("GPU frame is out of date");
+                None
+            }
+            Err(other) => panic!("GPU error {}", other),
+        }
+    }
+}
+
+#[hidden_trait::expose]
+impl crate::traits::CommandEncoder for super::CommandEncoder {
+    type Texture = super::Texture;
+    type Frame = super::Frame;
+
+    fn start(&mut self) {
+        self.begin_recording(crate::RecordingState::Transient);
+    }
+
+    fn start_reusable(&mut self) {
+        self.begin_recording(crate::RecordingState::Reusable);
+    }
+
+    fn invalidate(&mut self) {
+        self.recording = crate::RecordingState::Invalid;
+    }
+
     fn init_texture(&mut self, texture: super::Texture) {
+        debug_assert_ne!(
+            self.recording,
+            crate::RecordingState::Reusable,
+            "Textures can't be initialized by a reusable encoder"
+        );
         let barrier = vk::ImageMemoryBarrier {
             old_layout: vk::ImageLayout::UNDEFINED,
             new_layout: vk::ImageLayout::GENERAL,
@@ -599,6 +772,11 @@ impl crate::traits::CommandEncoder for super::CommandEncoder {
     }
 
     fn present(&mut self, frame: super::Frame) {
+        debug_assert_ne!(
+            self.recording,
+            crate::RecordingState::Reusable,
+            "Frames can't be presented by a reusable encoder"
+        );
         let image_index = match frame.image_index {
             Some(index) => index,
             None => {
@@ -654,6 +832,26 @@ impl crate::traits::CommandEncoder for super::CommandEncoder {
     fn timings(&self) -> &crate::Timings {
         &self.timings
     }
+
+    fn recording_index(&self) -> u64 {
+        self.recording_index
+    }
+
+    fn timings_recording_index(&self) -> Option<u64> {
+        self.timings_recording_index
+    }
+
+    fn binding_stats(&self) -> crate::BindingStats {
+        self.binding.stats
+    }
+
+    fn memory_stats(&self) -> crate::EncoderMemoryStats {
+        let retained_bytes = self.retained_bytes();
+        crate::EncoderMemoryStats {
+            retained_bytes,
+            peak_retained_bytes: self.peak_retained_bytes.max(retained_bytes),
+        }
+    }
 }
 
 #[hidden_trait::expose]
@@ -694,6 +892,14 @@ impl crate::traits::TransferEncoder for super::TransferCommandEncoder<'_> {
         dst: crate::TexturePiece,
         size: crate::Extent,
     ) {
+        crate::debug_check_region(&src, size);
+        crate::debug_check_region(&dst, size);
+        assert!(
+            src.texture.format.is_copy_compatible(dst.texture.format),
+            "Texels can't be copied from {:?} to {:?}",
+            src.texture.format,
+            dst.texture.format
+        );
         let copy = vk::ImageCopy {
             src_subresource: src.subresource_layers(),
             src_offset: map_origin(&src.origin),
@@ -713,6 +919,55 @@ impl crate::traits::TransferEncoder for super::TransferCommandEncoder<'_> {
         };
     }
 
+    fn blit(
+        &mut self,
+        src: crate::TexturePiece,
+        src_size: crate::Extent,
+        dst: crate::TexturePiece,
+        dst_size: crate::Extent,
+        filter: crate::FilterMode,
+    ) {
+        crate::debug_check_region(&src, src_size);
+        crate::debug_check_region(&dst, dst_size);
+        assert!(
+            src.texture.format.aspects() == crate::TexelAspects::COLOR
+                && dst.texture.format.aspects() == crate::TexelAspects::COLOR,
+            "Only color textures can be blitted, not {:?} to {:?}",
+            src.texture.format,
+            dst.texture.format
+        );
+        assert!(
+            src_size.depth == 1 && dst_size.depth == 1,
+            "Only 2D regions can be blitted"
+        );
+        let corners = |piece: &crate::TexturePiece, size: &crate::Extent| {
+            let start = map_origin(&piece.origin);
+            let end = vk::Offset3D {
+                x: start.x + size.width as i32,
+                y: start.y + size.height as i32,
+                z: start.z + 1,
+            };
+            [start, end]
+        };
+        let blit = vk::ImageBlit {
+            src_subresource: src.subresource_layers(),
+            src_offsets: corners(&src, &src_size),
+            dst_subresource: dst.subresource_layers(),
+            dst_offsets: corners(&dst, &dst_size),
+        };
+        unsafe {
+            self.device.core.cmd_blit_image(
+                self.raw,
+                src.texture.raw,
+                vk::ImageLayout::GENERAL,
+                dst.texture.raw,
+                vk::ImageLayout::GENERAL,
+                &[blit],
+                super::resource::map_filter_mode(filter),
+            )
+        };
+    }
+
     fn copy_buffer_to_texture(
         &mut self,
         src: crate::BufferPiece,
@@ -720,6 +975,7 @@ impl crate::traits::TransferEncoder for super::TransferCommandEncoder<'_> {
         dst: crate::TexturePiece,
         size: crate::Extent,
     ) {
+        crate::debug_check_region(&dst, size);
         let copy = make_buffer_image_copy(&src, bytes_per_row, &dst, &size);
         unsafe {
             self.device.core.cmd_copy_buffer_to_image(
@@ -739,6 +995,7 @@ impl crate::traits::TransferEncoder for super::TransferCommandEncoder<'_> {
         bytes_per_row: u32,
         size: crate::Extent,
     ) {
+        crate::debug_check_region(&src, size);
         let copy = make_buffer_image_copy(&dst, bytes_per_row, &src, &size);
         unsafe {
             self.device.core.cmd_copy_image_to_buffer(
@@ -777,10 +1034,11 @@ impl crate::traits::AccelerationStructureEncoder
         blas_input.build_info.dst_acceleration_structure = acceleration_structure.raw;
         let scratch_address = self.device.get_device_address(&scratch_data);
         assert_eq!(
-            scratch_address & rt.scratch_buffer_alignment,
+            scratch_address & (rt.scratch_buffer_alignment - 1),
             0,
             "BLAS scratch address {scratch_address} is not aligned"
         );
+        self.scratch_barrier();
         blas_input.build_info.scratch_data = vk::DeviceOrHostAddressKHR {
             device_address: scratch_address,
         };
@@ -801,6 +1059,25 @@ impl crate::traits::AccelerationStructureEncoder
         instance_count: u32,
         instance_data: crate::BufferPiece,
         scratch_data: crate::BufferPiece,
+    ) {
+        self.build_top_level_impl(
+            acceleration_structure,
+            instance_count,
+            instance_data,
+            scratch_data,
+            vk::BuildAccelerationStructureFlagsKHR::empty(),
+        );
+    }
+}
+
+impl super::AccelerationStructureCommandEncoder<'_> {
+    fn build_top_level_impl(
+        &mut self,
+        acceleration_structure: super::AccelerationStructure,
+        instance_count: u32,
+        instance_data: crate::BufferPiece,
+        scratch_data: crate::BufferPiece,
+        flags: vk::BuildAccelerationStructureFlagsKHR,
     ) {
         let build_range_info = vk::AccelerationStructureBuildRangeInfoKHR {
             primitive_count: instance_count,
@@ -821,18 +1098,26 @@ impl crate::traits::AccelerationStructureEncoder
             ..Default::default()
         };
         let geometries = [geometry];
+        let rt = self.device.ray_tracing.as_ref().unwrap();
+        let scratch_address = self.device.get_device_address(&scratch_data);
+        assert_eq!(
+            scratch_address & (rt.scratch_buffer_alignment - 1),
+            0,
+            "TLAS scratch address {scratch_address} is not aligned"
+        );
         let build_info = vk::AccelerationStructureBuildGeometryInfoKHR {
             ty: vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+            flags,
             mode: vk::BuildAccelerationStructureModeKHR::BUILD,
             scratch_data: vk::DeviceOrHostAddressKHR {
-                device_address: self.device.get_device_address(&scratch_data),
+                device_address: scratch_address,
             },
             dst_acceleration_structure: acceleration_structure.raw,
             ..Default::default()
         }
         .geometries(&geometries);
 
-        let rt = self.device.ray_tracing.as_ref().unwrap();
+        self.scratch_barrier();
         unsafe {
             rt.acceleration_structure.cmd_build_acceleration_structures(
                 self.raw,
@@ -841,6 +1126,56 @@ impl crate::traits::AccelerationStructureEncoder
             );
         }
     }
+
+    /// Build a top-level structure out of motion instances.
+    ///
+    /// The structure has to be created with `AccelerationStructureType::TopLevelMotion`,
+    /// and the instance data produced by `create_acceleration_structure_motion_instance_buffer`.
+    pub fn build_top_level_motion(
+        &mut self,
+        acceleration_structure: super::AccelerationStructure,
+        instance_count: u32,
+        instance_data: crate::BufferPiece,
+        scratch_data: crate::BufferPiece,
+    ) -> Result<(), crate::ResourceError> {
+        if !self.device.supports_motion() {
+            return Err(crate::ResourceError::MotionNotSupported);
+        }
+        self.build_top_level_impl(
+            acceleration_structure,
+            instance_count,
+            instance_data,
+            scratch_data,
+            vk::BuildAccelerationStructureFlagsKHR::MOTION_NV,
+        );
+        Ok(())
+    }
+
+    /// Make sure the previous build in this pass is done with its scratch
+    /// memory before the next one starts, since they may share a buffer.
+    fn scratch_barrier(&mut self) {
+        if !self.has_builds {
+            self.has_builds = true;
+            return;
+        }
+        let barrier = vk::MemoryBarrier {
+            src_access_mask: vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR,
+            dst_access_mask: vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR
+                | vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR,
+            ..Default::default()
+        };
+        unsafe {
+            self.device.core.cmd_pipeline_barrier(
+                self.raw,
+                vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
+                vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
+                vk::DependencyFlags::empty(),
+                &[barrier],
+                &[],
+                &[],
+            );
+        }
+    }
 }
 
 impl Drop for super::AccelerationStructureCommandEncoder<'_> {
@@ -885,12 +1220,13 @@ impl<'a> super::ComputeCommandEncoder<'a> {
                 .core
                 .cmd_bind_pipeline(self.cmd_buf.raw, bind_point, pipeline.raw)
         };
+        self.binding.bound_groups = 0;
         super::PipelineEncoder {
             cmd_buf: self.cmd_buf,
             layout: &pipeline.layout,
             bind_point,
             device: self.device,
-            update_data: self.update_data,
+            binding: self.binding,
         }
     }
 }
@@ -906,28 +1242,82 @@ impl<'a> super::RenderCommandEncoder<'a> {
         &'b mut self,
         pipeline: &'p super::RenderPipeline,
     ) -> super::PipelineEncoder<'b, 'p> {
+        debug_assert!(
+            !pipeline
+                .depth_stencil_writes
+                .intersects(self.depth_stencil_read_only),
+            "Pipeline writes {:?} of the depth-stencil target, which is read-only in the pass",
+            pipeline.depth_stencil_writes & self.depth_stencil_read_only,
+        );
         let bind_point = vk::PipelineBindPoint::GRAPHICS;
         unsafe {
             self.device
                 .core
                 .cmd_bind_pipeline(self.cmd_buf.raw, bind_point, pipeline.raw)
         };
+        self.binding.bound_groups = 0;
         super::PipelineEncoder {
             cmd_buf: self.cmd_buf,
             layout: &pipeline.layout,
             bind_point,
             device: self.device,
-            update_data: self.update_data,
+            binding: self.binding,
         }
     }
+
+    /// Start skipping the following draws of the pass if the predicate is zero.
+    ///
+    /// The predicate is a `u32` at the piece, which has to be 4-byte aligned.
+    /// Any non-zero value executes the draws. It's read on the GPU, so it can be
+    /// written by an earlier pass, such as the results of occlusion culling.
+    /// Requires `Capabilities::conditional_rendering`, and can't be nested.
+    pub fn begin_conditional(&mut self, predicate: crate::BufferPiece) {
+        let conditional_rendering = self
+            .device
+            .conditional_rendering
+            .as_ref()
+            .expect("Conditional rendering is not supported");
+        assert!(
+            !self.conditional,
+            "Conditional rendering can't be nested, end the previous one first"
+        );
+        assert_eq!(predicate.offset & 3, 0, "Predicate offset is not aligned");
+        let info = vk::ConditionalRenderingBeginInfoEXT {
+            buffer: predicate.buffer.raw,
+            offset: predicate.offset,
+            ..Default::default()
+        };
+        unsafe {
+            (conditional_rendering
+                .fp()
+                .cmd_begin_conditional_rendering_ext)(self.cmd_buf.raw, &info)
+        };
+        self.conditional = true;
+    }
+
+    /// Stop skipping the draws, see `begin_conditional`.
+    pub fn end_conditional(&mut self) {
+        assert!(self.conditional, "Conditional rendering is not started");
+        let conditional_rendering = self.device.conditional_rendering.as_ref().unwrap();
+        unsafe { (conditional_rendering.fp().cmd_end_conditional_rendering_ext)(self.cmd_buf.raw) };
+        self.conditional = false;
+    }
 }
 
 impl Drop for super::RenderCommandEncoder<'_> {
     fn drop(&mut self) {
+        if self.conditional {
+            log::error!("Conditional rendering is not ended in the pass");
+            self.end_conditional();
+        }
+        self.binding.depth_stencil_writes = None;
         unsafe {
-            self.device
-                .dynamic_rendering
-                .cmd_end_rendering(self.cmd_buf.raw)
+            match self.device.dynamic_rendering {
+                Some(ref dynamic_rendering) => {
+                    dynamic_rendering.cmd_end_rendering(self.cmd_buf.raw)
+                }
+                None => self.device.core.cmd_end_render_pass(self.cmd_buf.raw),
+            }
         };
         end_pass(self.device, self.cmd_buf.raw);
     }
@@ -996,16 +1386,54 @@ impl crate::traits::RenderEncoder for super::RenderCommandEncoder<'_> {
 impl crate::traits::PipelineEncoder for super::PipelineEncoder<'_, '_> {
     fn bind<D: crate::ShaderData>(&mut self, group: u32, data: &D) {
         let dsl = &self.layout.descriptor_set_layouts[group as usize];
+        let binding = &mut *self.binding;
+        binding.update_data.clear();
         if !dsl.is_empty() {
-            self.update_data.clear();
-            self.update_data.resize(dsl.template_size as usize, 0);
+            binding.update_data.resize(dsl.template_size as usize, 0);
             data.fill(super::PipelineContext {
-                update_data: self.update_data.as_mut_slice(),
+                update_data: binding.update_data.as_mut_slice(),
                 template_offsets: &dsl.template_offsets,
                 scratch: self.cmd_buf.scratch.as_mut(),
                 inline_uniform_mask: dsl.inline_uniform_mask,
+                depth_stencil_writes: binding.depth_stencil_writes,
+                dummies: binding.dummies,
             });
         }
+        // Dynamic offsets can't be applied to the whole buffer ranges
+        for &(offset, size) in dsl.dynamic_ranges.iter() {
+            unsafe {
+                let ptr = binding.update_data.as_mut_ptr().add(offset as usize)
+                    as *mut vk::DescriptorBufferInfo;
+                let mut buffer_info = ptr::read_unaligned(ptr);
+                if buffer_info.buffer != vk::Buffer::null() {
+                    buffer_info.range = size as vk::DeviceSize;
+                    ptr::write_unaligned(ptr, buffer_info);
+                }
+            }
+        }
+
+        // Uniforms in the scratch buffer get a new offset on every binding,
+        // so only the bindings of identical resources and inline data are skipped.
+        // Groups with dynamic buffers are always bound, resetting the offsets.
+        let group_index = group as usize;
+        let group_bit = 1u64 << group;
+        if binding.bound_data.len() <= group_index {
+            binding.bound_data.resize_with(group_index + 1, Vec::new);
+            binding
+                .bound_sets
+                .resize(group_index + 1, vk::DescriptorSet::null());
+        }
+        let bound = &mut binding.bound_data[group_index];
+        if binding.bound_groups & group_bit != 0
+            && dsl.dynamic_ranges.is_empty()
+            && *bound == binding.update_data
+        {
+            binding.stats.skipped += 1;
+            return;
+        }
+        binding.stats.issued += 1;
+        binding.bound_groups |= group_bit;
+        bound.clone_from(&binding.update_data);
 
         let vk_set = self
             .device
@@ -1015,7 +1443,7 @@ impl crate::traits::PipelineEncoder for super::PipelineEncoder<'_, '_> {
                 self.device.core.update_descriptor_set_with_template(
                     vk_set,
                     dsl.update_template,
-                    self.update_data.as_ptr() as *const _,
+                    binding.update_data.as_ptr() as *const _,
                 );
             }
             self.device.core.cmd_bind_descriptor_sets(
@@ -1024,7 +1452,33 @@ impl crate::traits::PipelineEncoder for super::PipelineEncoder<'_, '_> {
                 self.layout.raw,
                 group,
                 &[vk_set],
-                &[],
+                &[0; super::MAX_DYNAMIC_BUFFERS][..dsl.dynamic_ranges.len()],
+            );
+        }
+        binding.bound_sets[group_index] = vk_set;
+    }
+
+    fn set_dynamic_offsets(&mut self, group: u32, offsets: &[u32]) {
+        let dsl = &self.layout.descriptor_set_layouts[group as usize];
+        assert_eq!(
+            offsets.len(),
+            dsl.dynamic_ranges.len(),
+            "Group {group} has {} dynamic buffers",
+            dsl.dynamic_ranges.len()
+        );
+        assert_ne!(
+            self.binding.bound_groups & (1 << group),
+            0,
+            "Group {group} is not bound"
+        );
+        unsafe {
+            self.device.core.cmd_bind_descriptor_sets(
+                self.cmd_buf.raw,
+                self.bind_point,
+                self.layout.raw,
+                group,
+                &[self.binding.bound_sets[group as usize]],
+                offsets,
             );
         }
     }
@@ -1042,6 +1496,7 @@ impl crate::traits::ComputePipelineEncoder for super::PipelineEncoder<'_, '_> {
         };
     }
     fn dispatch_indirect(&mut self, indirect_buf: crate::BufferPiece) {
+        crate::indirect::debug_check_indirect::<crate::DispatchIndirectArgs>(&indirect_buf);
         unsafe {
             self.device.core.cmd_dispatch_indirect(
                 self.cmd_buf.raw,
@@ -1145,6 +1600,7 @@ impl crate::traits::RenderPipelineEncoder for super::PipelineEncoder<'_, '_> {
     }
 
     fn draw_indirect(&mut self, indirect_buf: crate::BufferPiece) {
+        crate::indirect::debug_check_indirect::<crate::DrawIndirectArgs>(&indirect_buf);
         unsafe {
             self.device.core.cmd_draw_indirect(
                 self.cmd_buf.raw,
@@ -1162,6 +1618,7 @@ impl crate::traits::RenderPipelineEncoder for super::PipelineEncoder<'_, '_> {
         index_type: crate::IndexType,
         indirect_buf: crate::BufferPiece,
     ) {
+        crate::indirect::debug_check_indirect::<crate::DrawIndexedIndirectArgs>(&indirect_buf);
         let raw_index_type = super::map_index_type(index_type);
         unsafe {
             self.device.core.cmd_bind_index_buffer(
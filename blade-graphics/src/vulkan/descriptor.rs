@@ -1,8 +1,13 @@
 use ash::vk;
+use std::{
+    mem,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+};
 
 //TODO: replace by an abstraction in `gpu-descriptor`
 // https://github.com/zakarumych/gpu-descriptor/issues/42
-const COUNT_BASE: u32 = 16;
+pub(super) const COUNT_BASE: u32 = 16;
+const GROWTH_FACTOR: u32 = 16;
 /// Budget for inline uniform block bytes per descriptor set.
 /// The hardware max (e.g. 4 MiB on RADV) is far larger than actual
 /// usage (typically 32–256 bytes of push constants per set).
@@ -10,14 +15,65 @@ const COUNT_BASE: u32 = 16;
 /// request more memory than the device has (e.g. 4096 sets × 4 MiB = 16 GiB).
 const IUB_BYTES_PER_SET: u32 = 4096;
 
+/// Counters of the descriptor pools, shared by all the command encoders.
+#[derive(Debug, Default)]
+pub(super) struct DescriptorCounters {
+    pools: AtomicU32,
+    peak_pools: AtomicU32,
+    capacity: AtomicU64,
+    peak_capacity: AtomicU64,
+    allocated_sets: AtomicU64,
+    peak_allocated_sets: AtomicU64,
+    /// Bumped by `Context::trim`, so that the pools shrink on their next reset.
+    trim_epoch: AtomicU32,
+}
+
+impl DescriptorCounters {
+    fn add_pool(&self, max_sets: u32) {
+        let pools = self.pools.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak_pools.fetch_max(pools, Ordering::Relaxed);
+        let capacity =
+            self.capacity.fetch_add(max_sets as u64, Ordering::Relaxed) + max_sets as u64;
+        self.peak_capacity.fetch_max(capacity, Ordering::Relaxed);
+    }
+
+    fn remove_pool(&self, max_sets: u32) {
+        self.pools.fetch_sub(1, Ordering::Relaxed);
+        self.capacity.fetch_sub(max_sets as u64, Ordering::Relaxed);
+    }
+
+    pub(super) fn trim(&self) {
+        self.trim_epoch.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn stats(&self) -> crate::DescriptorPoolStats {
+        crate::DescriptorPoolStats {
+            pool_count: self.pools.load(Ordering::Relaxed),
+            peak_pool_count: self.peak_pools.load(Ordering::Relaxed),
+            set_capacity: self.capacity.load(Ordering::Relaxed),
+            peak_set_capacity: self.peak_capacity.load(Ordering::Relaxed),
+            allocated_sets: self.allocated_sets.load(Ordering::Relaxed),
+            peak_allocated_sets: self.peak_allocated_sets.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SubPool {
+    raw: vk::DescriptorPool,
+    max_sets: u32,
+}
+
 #[derive(Debug)]
 pub struct DescriptorPool {
-    sub_pools: Vec<vk::DescriptorPool>,
+    sub_pools: Vec<SubPool>,
     growth_iter: usize,
+    allocated_sets: u32,
+    trim_epoch: u32,
 }
 
 impl super::Device {
-    fn create_descriptor_sub_pool(&self, max_sets: u32) -> vk::DescriptorPool {
+    fn create_descriptor_sub_pool(&self, max_sets: u32) -> SubPool {
         log::info!("Creating a descriptor pool for at most {} sets", max_sets);
         let mut descriptor_sizes = vec![
             vk::DescriptorPoolSize {
@@ -49,6 +105,14 @@ impl super::Device {
             ty: vk::DescriptorType::UNIFORM_BUFFER,
             descriptor_count: max_sets,
         });
+        descriptor_sizes.push(vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+            descriptor_count: max_sets,
+        });
+        descriptor_sizes.push(vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+            descriptor_count: max_sets,
+        });
         if self.ray_tracing.is_some() {
             descriptor_sizes.push(vk::DescriptorPoolSize {
                 ty: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
@@ -69,24 +133,41 @@ impl super::Device {
             descriptor_pool_info = descriptor_pool_info.push_next(&mut inline_uniform_block_info);
         }
 
-        unsafe {
+        let raw = unsafe {
             self.core
                 .create_descriptor_pool(&descriptor_pool_info, None)
                 .unwrap()
-        }
+        };
+        self.descriptor_counters.add_pool(max_sets);
+        SubPool { raw, max_sets }
+    }
+
+    fn destroy_descriptor_sub_pool(&self, sub_pool: SubPool) {
+        unsafe { self.core.destroy_descriptor_pool(sub_pool.raw, None) };
+        self.descriptor_counters.remove_pool(sub_pool.max_sets);
+    }
+
+    fn release_descriptor_sets(&self, pool: &mut DescriptorPool) {
+        self.descriptor_counters
+            .allocated_sets
+            .fetch_sub(pool.allocated_sets as u64, Ordering::Relaxed);
+        pool.allocated_sets = 0;
     }
 
     pub(super) fn create_descriptor_pool(&self) -> DescriptorPool {
-        let vk_pool = self.create_descriptor_sub_pool(COUNT_BASE);
+        let sub_pool = self.create_descriptor_sub_pool(self.descriptor_pool_size);
         DescriptorPool {
-            sub_pools: vec![vk_pool],
+            sub_pools: vec![sub_pool],
             growth_iter: 0,
+            allocated_sets: 0,
+            trim_epoch: self.descriptor_counters.trim_epoch.load(Ordering::Relaxed),
         }
     }
 
     pub(super) fn destroy_descriptor_pool(&self, pool: &mut DescriptorPool) {
+        self.release_descriptor_sets(pool);
         for sub_pool in pool.sub_pools.drain(..) {
-            unsafe { self.core.destroy_descriptor_pool(sub_pool, None) };
+            self.destroy_descriptor_sub_pool(sub_pool);
         }
     }
 
@@ -99,33 +180,72 @@ impl super::Device {
 
         loop {
             let descriptor_set_info = vk::DescriptorSetAllocateInfo::default()
-                .descriptor_pool(pool.sub_pools[0])
+                .descriptor_pool(pool.sub_pools[0].raw)
                 .set_layouts(&descriptor_set_layouts);
-            match unsafe { self.core.allocate_descriptor_sets(&descriptor_set_info) } {
-                Ok(vk_sets) => return vk_sets[0],
-                Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY)
-                | Err(vk::Result::ERROR_FRAGMENTED_POOL) => {}
-                Err(other) => panic!("Unexpected descriptor allocation error: {:?}", other),
+            // Calling the raw function, since the `ash` wrapper allocates a vector
+            let mut vk_set = vk::DescriptorSet::null();
+            let result = unsafe {
+                (self.core.fp_v1_0().allocate_descriptor_sets)(
+                    self.core.handle(),
+                    &descriptor_set_info,
+                    &mut vk_set,
+                )
+            };
+            match result {
+                vk::Result::SUCCESS => {
+                    pool.allocated_sets += 1;
+                    let counters = &self.descriptor_counters;
+                    let total = counters.allocated_sets.fetch_add(1, Ordering::Relaxed) + 1;
+                    counters
+                        .peak_allocated_sets
+                        .fetch_max(total, Ordering::Relaxed);
+                    return vk_set;
+                }
+                vk::Result::ERROR_OUT_OF_POOL_MEMORY | vk::Result::ERROR_FRAGMENTED_POOL => {}
+                other => panic!("Unexpected descriptor allocation error: {:?}", other),
             };
 
-            let next_max_sets = COUNT_BASE.pow(pool.growth_iter as u32 + 1);
+            let next_max_sets = self
+                .descriptor_pool_size
+                .saturating_mul(GROWTH_FACTOR.saturating_pow(pool.growth_iter as u32));
             pool.growth_iter += 1;
-            let vk_pool = self.create_descriptor_sub_pool(next_max_sets);
-            pool.sub_pools.insert(0, vk_pool);
+            let sub_pool = self.create_descriptor_sub_pool(next_max_sets);
+            pool.sub_pools.insert(0, sub_pool);
         }
     }
 
+    /// Reset the pool, expecting none of its sets to be in use by the GPU.
+    ///
+    /// Only the largest sub-pool is kept, unless the context was trimmed
+    /// since the last reset, in which case the pool goes back to its initial size.
     pub(super) fn reset_descriptor_pool(&self, pool: &mut DescriptorPool) {
-        for vk_pool in pool.sub_pools.drain(1..) {
-            unsafe {
-                self.core.destroy_descriptor_pool(vk_pool, None);
-            }
+        self.release_descriptor_sets(pool);
+        for sub_pool in pool.sub_pools.drain(1..) {
+            self.destroy_descriptor_sub_pool(sub_pool);
         }
 
-        unsafe {
-            self.core
-                .reset_descriptor_pool(pool.sub_pools[0], vk::DescriptorPoolResetFlags::empty())
-                .unwrap();
+        let trim_epoch = self.descriptor_counters.trim_epoch.load(Ordering::Relaxed);
+        if pool.trim_epoch != trim_epoch && pool.growth_iter != 0 {
+            log::info!(
+                "Trimming a descriptor pool of {} sets",
+                pool.sub_pools[0].max_sets
+            );
+            let old = mem::replace(
+                &mut pool.sub_pools[0],
+                self.create_descriptor_sub_pool(self.descriptor_pool_size),
+            );
+            self.destroy_descriptor_sub_pool(old);
+            pool.growth_iter = 0;
+        } else {
+            unsafe {
+                self.core
+                    .reset_descriptor_pool(
+                        pool.sub_pools[0].raw,
+                        vk::DescriptorPoolResetFlags::empty(),
+                    )
+                    .unwrap();
+            }
         }
+        pool.trim_epoch = trim_epoch;
     }
 }
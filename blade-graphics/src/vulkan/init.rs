@@ -64,7 +64,6 @@ mod layer {
 const REQUIRED_DEVICE_EXTENSIONS: &[&ffi::CStr] = &[
     vk::KHR_TIMELINE_SEMAPHORE_NAME,
     vk::KHR_DESCRIPTOR_UPDATE_TEMPLATE_NAME,
-    vk::KHR_DYNAMIC_RENDERING_NAME,
 ];
 
 fn is_promoted_instance_extension(name: &ffi::CStr, api_version: u32) -> bool {
@@ -79,6 +78,7 @@ fn is_promoted_instance_extension(name: &ffi::CStr, api_version: u32) -> bool {
 #[derive(Debug)]
 struct RayTracingCapabilities {
     min_scratch_buffer_alignment: u64,
+    motion_blur: bool,
 }
 
 #[derive(Debug)]
@@ -94,6 +94,8 @@ struct AdapterCapabilities {
     device_information: crate::DeviceInformation,
     queue_family_index: u32,
     layered: bool,
+    /// Without it, rendering goes through the render pass objects.
+    dynamic_rendering: bool,
     binding_array: bool,
     ray_tracing: Option<RayTracingCapabilities>,
     buffer_device_address: bool,
@@ -109,8 +111,25 @@ struct AdapterCapabilities {
     /// `Memory::External(HostAllocation(..))` allocation must be a
     /// multiple of this value (0 when the extension is unsupported).
     min_imported_host_pointer_alignment: u64,
+    /// `VK_EXT_host_image_copy`, only enabled on integrated GPUs,
+    /// where writing textures from the CPU is cheaper than staging.
+    host_image_copy: bool,
+    /// `VK_EXT_robustness2` with null descriptors, only enabled on request.
+    robustness: bool,
+    /// Resolve modes of the depth targets, only with dynamic rendering.
+    depth_resolve_modes: vk::ResolveModeFlags,
     timing: bool,
     dual_source_blending: bool,
+    logic_op: bool,
+    /// Sparse residency of 2D images with the standard 64 KiB tiles,
+    /// bound on the main queue.
+    sparse_residency: bool,
+    /// Maximum number of views of the multiview passes, zero without multiview.
+    max_view_count: u32,
+    /// `VK_EXT_conditional_rendering` for the draws of the render passes.
+    conditional_rendering: bool,
+    /// Supported core features of the block-compressed textures.
+    texture_compression: vk::PhysicalDeviceFeatures,
     shader_float16: bool,
     cooperative_matrix: crate::CooperativeMatrix,
     unified_image_layouts: bool,
@@ -119,9 +138,35 @@ struct AdapterCapabilities {
 }
 
 impl AdapterCapabilities {
+    fn max_binding_array_size(&self) -> u32 {
+        if self.binding_array {
+            self.properties
+                .limits
+                .max_per_stage_descriptor_sampled_images
+        } else {
+            0
+        }
+    }
+
+    fn max_plain_data_size(&self) -> u32 {
+        // Bindings that don't fit into inline uniform blocks are copied into the scratch buffer
+        self.max_inline_uniform_block_size
+            .max(self.properties.limits.max_uniform_buffer_range)
+            .min(super::SCRATCH_SIZE as u32)
+    }
+
+    fn dynamic_offset_alignment(&self) -> u32 {
+        let limits = &self.properties.limits;
+        limits
+            .min_uniform_buffer_offset_alignment
+            .max(limits.min_storage_buffer_offset_alignment) as u32
+    }
+
     fn to_capabilities(&self) -> crate::Capabilities {
         crate::Capabilities {
             binding_array: self.binding_array,
+            max_binding_array_size: self.max_binding_array_size(),
+            max_plain_data_size: self.max_plain_data_size(),
             ray_query: match self.ray_tracing {
                 Some(_) => crate::ShaderVisibility::all(),
                 None => crate::ShaderVisibility::empty(),
@@ -132,6 +177,29 @@ impl AdapterCapabilities {
             dual_source_blending: self.dual_source_blending,
             shader_float16: self.shader_float16,
             cooperative_matrix: self.cooperative_matrix,
+            acceleration_structure_motion: self
+                .ray_tracing
+                .as_ref()
+                .is_some_and(|rt| rt.motion_blur),
+            reusable_command_encoders: true,
+            robustness: self.robustness,
+            depth_resolve: self
+                .depth_resolve_modes
+                .contains(vk::ResolveModeFlags::SAMPLE_ZERO),
+            depth_resolve_min_max: self
+                .depth_resolve_modes
+                .contains(vk::ResolveModeFlags::MIN | vk::ResolveModeFlags::MAX),
+            logic_op: self.logic_op,
+            sparse_tile_size: if self.sparse_residency {
+                super::SPARSE_TILE_SIZE
+            } else {
+                0
+            },
+            buffer_device_address: self.buffer_device_address,
+            max_view_count: self.max_view_count,
+            conditional_rendering: self.conditional_rendering,
+            dynamic_offset_alignment: self.dynamic_offset_alignment(),
+            max_sampler_lod_bias: self.properties.limits.max_sampler_lod_bias,
         }
     }
 }
@@ -240,13 +308,18 @@ fn inspect_adapter(
         vk::PhysicalDevicePortabilitySubsetPropertiesKHR::default();
 
     let mut driver_properties = vk::PhysicalDeviceDriverPropertiesKHR::default();
+    let mut depth_stencil_resolve_properties =
+        vk::PhysicalDeviceDepthStencilResolveProperties::default();
+    let mut multiview_properties = vk::PhysicalDeviceMultiviewProperties::default();
     let mut properties2_khr = vk::PhysicalDeviceProperties2KHR::default()
         .push_next(&mut inline_uniform_block_properties)
         .push_next(&mut timeline_semaphore_properties)
         .push_next(&mut descriptor_indexing_properties)
         .push_next(&mut acceleration_structure_properties)
         .push_next(&mut portability_subset_properties)
-        .push_next(&mut driver_properties);
+        .push_next(&mut driver_properties)
+        .push_next(&mut depth_stencil_resolve_properties)
+        .push_next(&mut multiview_properties);
     unsafe {
         instance
             .get_physical_device_properties2
@@ -295,6 +368,12 @@ fn inspect_adapter(
     };
 
     let queue_family_index = 0; //TODO
+    if desc.presentation && !supported_extensions.contains(&vk::KHR_SWAPCHAIN_NAME) {
+        return Err(format!(
+            "no presentation support: {:?} is not supported",
+            vk::KHR_SWAPCHAIN_NAME
+        ));
+    }
     if desc.presentation
         && is_presentation_broken(properties.vendor_id, gpu_vendors, display_server)
     {
@@ -315,12 +394,18 @@ fn inspect_adapter(
     let mut acceleration_structure_features =
         vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default();
     let mut ray_query_features = vk::PhysicalDeviceRayQueryFeaturesKHR::default();
+    let mut motion_blur_features = vk::PhysicalDeviceRayTracingMotionBlurFeaturesNV::default();
     let mut cooperative_matrix_features = vk::PhysicalDeviceCooperativeMatrixFeaturesKHR::default();
     let mut vulkan_memory_model_features = vk::PhysicalDeviceVulkanMemoryModelFeatures::default();
     let mut float16_int8_features = vk::PhysicalDeviceShaderFloat16Int8Features::default();
     let mut storage_16bit_features = vk::PhysicalDevice16BitStorageFeatures::default();
     let mut unified_image_layouts_features =
         unified_image_layouts::PhysicalDeviceFeatures::default();
+    let mut host_image_copy_features = vk::PhysicalDeviceHostImageCopyFeaturesEXT::default();
+    let mut robustness2_features = vk::PhysicalDeviceRobustness2FeaturesEXT::default();
+    let mut multiview_features = vk::PhysicalDeviceMultiviewFeatures::default();
+    let mut conditional_rendering_features =
+        vk::PhysicalDeviceConditionalRenderingFeaturesEXT::default();
     let mut features2_khr = vk::PhysicalDeviceFeatures2::default()
         .push_next(&mut inline_uniform_block_features)
         .push_next(&mut timeline_semaphore_features)
@@ -329,11 +414,16 @@ fn inspect_adapter(
         .push_next(&mut buffer_device_address_features)
         .push_next(&mut acceleration_structure_features)
         .push_next(&mut ray_query_features)
+        .push_next(&mut motion_blur_features)
         .push_next(&mut cooperative_matrix_features)
         .push_next(&mut vulkan_memory_model_features)
         .push_next(&mut float16_int8_features)
         .push_next(&mut storage_16bit_features)
-        .push_next(&mut unified_image_layouts_features);
+        .push_next(&mut unified_image_layouts_features)
+        .push_next(&mut host_image_copy_features)
+        .push_next(&mut robustness2_features)
+        .push_next(&mut multiview_features)
+        .push_next(&mut conditional_rendering_features);
     unsafe {
         instance
             .get_physical_device_properties2
@@ -341,7 +431,35 @@ fn inspect_adapter(
     };
 
     let dual_source_blending = features2_khr.features.dual_src_blend != 0;
+    let logic_op = features2_khr.features.logic_op != 0;
+    let queue_families = unsafe {
+        instance
+            .core
+            .get_physical_device_queue_family_properties(phd)
+    };
+    let sparse_residency = features2_khr.features.sparse_binding != 0
+        && features2_khr.features.sparse_residency_image2_d != 0
+        && properties
+            .sparse_properties
+            .residency_standard2_d_block_shape
+            != 0
+        && queue_families[queue_family_index as usize]
+            .queue_flags
+            .contains(vk::QueueFlags::SPARSE_BINDING);
+    let robust_buffer_access = features2_khr.features.robust_buffer_access != 0;
+    let texture_compression = vk::PhysicalDeviceFeatures {
+        texture_compression_bc: features2_khr.features.texture_compression_bc,
+        texture_compression_etc2: features2_khr.features.texture_compression_etc2,
+        texture_compression_astc_ldr: features2_khr.features.texture_compression_astc_ldr,
+        ..Default::default()
+    };
     let shader_float16 = float16_int8_features.shader_float16 != 0;
+    // Multiview is core in Vulkan 1.1, and the views are selected by a 32-bit mask
+    let max_view_count = if multiview_features.multiview == vk::TRUE {
+        multiview_properties.max_multiview_view_count.min(32)
+    } else {
+        0
+    };
 
     let has_inline_ub = supported_extensions.contains(&vk::EXT_INLINE_UNIFORM_BLOCK_NAME)
         && inline_uniform_block_properties.max_descriptor_set_inline_uniform_blocks > 0
@@ -368,9 +486,26 @@ fn inspect_adapter(
         return Err("timeline semaphore feature is not supported".to_string());
     }
 
-    if dynamic_rendering_features.dynamic_rendering == 0 {
-        return Err("dynamic rendering feature is not supported".to_string());
-    }
+    let dynamic_rendering = if desc.legacy_render_passes {
+        log::info!("Dynamic rendering disabled by configuration");
+        false
+    } else if (api_version >= vk::API_VERSION_1_3
+        || supported_extensions.contains(&vk::KHR_DYNAMIC_RENDERING_NAME))
+        && dynamic_rendering_features.dynamic_rendering == vk::TRUE
+    {
+        true
+    } else {
+        log::info!("Dynamic rendering is not supported, falling back to render passes");
+        false
+    };
+
+    // Depth resolve is core in Vulkan 1.2, and the render pass objects
+    // would need to be created with `VK_KHR_create_renderpass2` to support it.
+    let depth_resolve_modes = if dynamic_rendering && api_version >= vk::API_VERSION_1_2 {
+        depth_stencil_resolve_properties.supported_depth_resolve_modes
+    } else {
+        vk::ResolveModeFlags::empty()
+    };
 
     let external_memory = supported_extensions.contains(&vk::KHR_EXTERNAL_MEMORY_NAME);
     let external_memory = external_memory
@@ -464,10 +599,16 @@ fn inspect_adapter(
     } else {
         log::info!("Ray tracing is supported");
         log::debug!("Ray tracing properties: {acceleration_structure_properties:#?}");
+        let motion_blur = supported_extensions.contains(&vk::NV_RAY_TRACING_MOTION_BLUR_NAME)
+            && motion_blur_features.ray_tracing_motion_blur == vk::TRUE;
+        if motion_blur {
+            log::info!("Ray tracing motion blur is supported");
+        }
         Some(RayTracingCapabilities {
             min_scratch_buffer_alignment: acceleration_structure_properties
                 .min_acceleration_structure_scratch_offset_alignment
                 as u64,
+            motion_blur,
         })
     };
 
@@ -543,6 +684,26 @@ fn inspect_adapter(
         supported_extensions.contains(&vk::KHR_PIPELINE_EXECUTABLE_PROPERTIES_NAME);
     let full_screen_exclusive = supported_extensions.contains(&vk::EXT_FULL_SCREEN_EXCLUSIVE_NAME);
     let memory_budget = supported_extensions.contains(&vk::EXT_MEMORY_BUDGET_NAME);
+    let conditional_rendering = supported_extensions.contains(&vk::EXT_CONDITIONAL_RENDERING_NAME)
+        && conditional_rendering_features.conditional_rendering == vk::TRUE;
+    // Discrete GPUs expose host image copies too, but they go over the bus
+    // and end up slower than the staging copies on the GPU.
+    let host_image_copy = supported_extensions.contains(&vk::EXT_HOST_IMAGE_COPY_NAME)
+        && host_image_copy_features.host_image_copy == vk::TRUE
+        && api_version >= vk::API_VERSION_1_3
+        && properties.device_type == vk::PhysicalDeviceType::INTEGRATED_GPU;
+    let robustness = if !desc.robustness {
+        false
+    } else if supported_extensions.contains(&vk::EXT_ROBUSTNESS2_NAME)
+        && robust_buffer_access
+        && robustness2_features.robust_buffer_access2 == vk::TRUE
+        && robustness2_features.null_descriptor == vk::TRUE
+    {
+        true
+    } else {
+        log::info!("Robustness is not supported");
+        false
+    };
 
     let device_information = unsafe {
         crate::DeviceInformation {
@@ -565,6 +726,7 @@ fn inspect_adapter(
         device_information,
         queue_family_index,
         layered: portability_subset_properties.min_vertex_input_binding_stride_alignment != 0,
+        dynamic_rendering,
         binding_array,
         ray_tracing,
         buffer_device_address,
@@ -576,8 +738,16 @@ fn inspect_adapter(
         external_memory,
         external_memory_host,
         min_imported_host_pointer_alignment,
+        host_image_copy,
+        robustness,
+        depth_resolve_modes,
         timing,
         dual_source_blending,
+        logic_op,
+        sparse_residency,
+        max_view_count,
+        conditional_rendering,
+        texture_compression,
         shader_float16,
         cooperative_matrix,
         unified_image_layouts: supported_extensions.contains(&unified_image_layouts::NAME)
@@ -664,6 +834,7 @@ impl super::VulkanInstance {
             .map(|ext_prop| unsafe { ffi::CStr::from_ptr(ext_prop.extension_name.as_ptr()) })
             .collect::<Vec<_>>();
 
+        let mut extensions = Vec::new();
         let core_instance = {
             let mut create_flags = vk::InstanceCreateFlags::empty();
 
@@ -671,7 +842,16 @@ impl super::VulkanInstance {
                 vk::EXT_DEBUG_UTILS_NAME,
                 vk::KHR_GET_PHYSICAL_DEVICE_PROPERTIES2_NAME,
             ];
+            // Surface extensions are only requested for presentation,
+            // so that headless systems without them can still compute.
             if desc.presentation {
+                if !supported_instance_extensions.contains(&vk::KHR_SURFACE_NAME) {
+                    log::error!(
+                        "Presentation is requested, but {:?} is not supported",
+                        vk::KHR_SURFACE_NAME
+                    );
+                    return Err(NotSupportedError::PresentationNotSupported);
+                }
                 instance_extensions.push(vk::KHR_SURFACE_NAME);
                 instance_extensions.push(vk::KHR_GET_SURFACE_CAPABILITIES2_NAME);
                 let candidates = [
@@ -710,6 +890,11 @@ impl super::VulkanInstance {
                 enabled_instance_extensions.push(vk::KHR_PORTABILITY_ENUMERATION_NAME);
                 create_flags |= vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR;
             }
+            extensions.extend(
+                enabled_instance_extensions
+                    .iter()
+                    .map(|name| name.to_string_lossy().into_owned()),
+            );
 
             let app_info = vk::ApplicationInfo::default()
                 .engine_name(c"blade")
@@ -747,8 +932,14 @@ impl super::VulkanInstance {
                     )
                 }
             } else {
-                unsafe { entry.create_instance(&create_info, None) }
-                    .map_err(crate::PlatformError::init)?
+                match unsafe { entry.create_instance(&create_info, None) } {
+                    Ok(instance) => instance,
+                    Err(vk::Result::ERROR_INCOMPATIBLE_DRIVER) => {
+                        log::error!("No Vulkan driver (ICD) is installed");
+                        return Err(NotSupportedError::NoDriverFound);
+                    }
+                    Err(err) => return Err(crate::PlatformError::init(err).into()),
+                }
             }
         };
 
@@ -778,6 +969,7 @@ impl super::VulkanInstance {
             entry,
             instance,
             driver_api_version,
+            extensions,
         })
     }
 }
@@ -796,6 +988,7 @@ fn inspect_devices(
     //    (it would reject devices based on the display server, not device features)
     let inspect_desc = crate::ContextDesc {
         ray_tracing: true,
+        robustness: true,
         ..Default::default()
     };
 
@@ -900,8 +1093,13 @@ impl super::Context {
             .map_err(|_| NotSupportedError::NoSupportedDeviceFound)?;
             (physical_device, capabilities)
         } else {
-            unsafe { inner.instance.core.enumerate_physical_devices() }
-                .map_err(crate::PlatformError::init)?
+            let physical_devices = unsafe { inner.instance.core.enumerate_physical_devices() }
+                .map_err(crate::PlatformError::init)?;
+            if physical_devices.is_empty() {
+                log::error!("No Vulkan devices found, the driver (ICD) is likely missing");
+                return Err(NotSupportedError::NoDriverFound);
+            }
+            physical_devices
                 .into_iter()
                 .find_map(|phd| {
                     inspect_adapter(
@@ -912,6 +1110,7 @@ impl super::Context {
                         &gpu_vendors,
                         display_server,
                     )
+                    .inspect_err(|reason| log::warn!("Adapter is rejected: {reason}"))
                     .ok()
                     .map(|caps| (phd, caps))
                 })
@@ -924,6 +1123,7 @@ impl super::Context {
             min_buffer_alignment = min_buffer_alignment.max(rt.min_scratch_buffer_alignment);
         }
 
+        let mut extensions = inner.extensions.clone();
         let device_core = {
             let family_info = vk::DeviceQueueCreateInfo::default()
                 .queue_family_index(capabilities.queue_family_index)
@@ -937,6 +1137,9 @@ impl super::Context {
             if desc.presentation {
                 device_extensions.push(vk::KHR_SWAPCHAIN_NAME);
             }
+            if capabilities.dynamic_rendering && capabilities.api_version < vk::API_VERSION_1_3 {
+                device_extensions.push(vk::KHR_DYNAMIC_RENDERING_NAME);
+            }
             if capabilities.layered {
                 log::info!("Enabling Vulkan Portability");
                 device_extensions.push(vk::KHR_PORTABILITY_SUBSET_NAME);
@@ -955,6 +1158,13 @@ impl super::Context {
                 device_extensions.push(vk::KHR_DEFERRED_HOST_OPERATIONS_NAME);
                 device_extensions.push(vk::KHR_ACCELERATION_STRUCTURE_NAME);
                 device_extensions.push(vk::KHR_RAY_QUERY_NAME);
+                if capabilities
+                    .ray_tracing
+                    .as_ref()
+                    .is_some_and(|rt| rt.motion_blur)
+                {
+                    device_extensions.push(vk::NV_RAY_TRACING_MOTION_BLUR_NAME);
+                }
             } else if capabilities.buffer_device_address
                 && capabilities.api_version < vk::API_VERSION_1_2
             {
@@ -992,11 +1202,25 @@ impl super::Context {
             if capabilities.memory_budget {
                 device_extensions.push(vk::EXT_MEMORY_BUDGET_NAME);
             }
+            if capabilities.host_image_copy {
+                device_extensions.push(vk::EXT_HOST_IMAGE_COPY_NAME);
+            }
+            if capabilities.robustness {
+                device_extensions.push(vk::EXT_ROBUSTNESS2_NAME);
+            }
+            if capabilities.conditional_rendering {
+                device_extensions.push(vk::EXT_CONDITIONAL_RENDERING_NAME);
+            }
             if capabilities.unified_image_layouts {
                 // TODO: Replace with ash constant once available.
                 device_extensions.push(unified_image_layouts::NAME);
             }
 
+            extensions.extend(
+                device_extensions
+                    .iter()
+                    .map(|name| name.to_string_lossy().into_owned()),
+            );
             let str_pointers = device_extensions
                 .iter()
                 .map(|&s| s.as_ptr())
@@ -1017,8 +1241,10 @@ impl super::Context {
             let mut device_create_info = vk::DeviceCreateInfo::default()
                 .queue_create_infos(&family_infos)
                 .enabled_extension_names(&str_pointers)
-                .push_next(&mut khr_timeline_semaphore)
-                .push_next(&mut khr_dynamic_rendering);
+                .push_next(&mut khr_timeline_semaphore);
+            if capabilities.dynamic_rendering {
+                device_create_info = device_create_info.push_next(&mut khr_dynamic_rendering);
+            }
             if capabilities.max_inline_uniform_block_size > 0 {
                 device_create_info = device_create_info.push_next(&mut ext_inline_uniform_block);
             }
@@ -1045,7 +1271,8 @@ impl super::Context {
 
             let mut khr_acceleration_structure;
             let mut khr_ray_query;
-            if capabilities.ray_tracing.is_some() {
+            let mut nv_motion_blur;
+            if let Some(ref rt) = capabilities.ray_tracing {
                 khr_acceleration_structure = vk::PhysicalDeviceAccelerationStructureFeaturesKHR {
                     acceleration_structure: vk::TRUE,
                     ..Default::default()
@@ -1057,6 +1284,13 @@ impl super::Context {
                 device_create_info = device_create_info
                     .push_next(&mut khr_acceleration_structure)
                     .push_next(&mut khr_ray_query);
+                if rt.motion_blur {
+                    nv_motion_blur = vk::PhysicalDeviceRayTracingMotionBlurFeaturesNV {
+                        ray_tracing_motion_blur: vk::TRUE,
+                        ..Default::default()
+                    };
+                    device_create_info = device_create_info.push_next(&mut nv_motion_blur);
+                }
             }
 
             let mut khr_float16_int8;
@@ -1104,6 +1338,25 @@ impl super::Context {
                     device_create_info.push_next(&mut khr_pipeline_executable_properties);
             }
 
+            let mut ext_host_image_copy;
+            if capabilities.host_image_copy {
+                ext_host_image_copy = vk::PhysicalDeviceHostImageCopyFeaturesEXT {
+                    host_image_copy: vk::TRUE,
+                    ..Default::default()
+                };
+                device_create_info = device_create_info.push_next(&mut ext_host_image_copy);
+            }
+
+            let mut ext_robustness2;
+            if capabilities.robustness {
+                ext_robustness2 = vk::PhysicalDeviceRobustness2FeaturesEXT {
+                    robust_buffer_access2: vk::TRUE,
+                    null_descriptor: vk::TRUE,
+                    ..Default::default()
+                };
+                device_create_info = device_create_info.push_next(&mut ext_robustness2);
+            }
+
             // TODO: Replace with ash typed struct once available.
             let mut khr_unified_image_layouts;
             if capabilities.unified_image_layouts {
@@ -1114,10 +1367,39 @@ impl super::Context {
                 device_create_info = device_create_info.push_next(&mut khr_unified_image_layouts);
             }
 
-            let mut core_features = vk::PhysicalDeviceFeatures::default();
+            let mut multiview;
+            if capabilities.max_view_count > 0 {
+                multiview = vk::PhysicalDeviceMultiviewFeatures {
+                    multiview: vk::TRUE,
+                    ..Default::default()
+                };
+                device_create_info = device_create_info.push_next(&mut multiview);
+            }
+
+            let mut ext_conditional_rendering;
+            if capabilities.conditional_rendering {
+                ext_conditional_rendering = vk::PhysicalDeviceConditionalRenderingFeaturesEXT {
+                    conditional_rendering: vk::TRUE,
+                    ..Default::default()
+                };
+                device_create_info = device_create_info.push_next(&mut ext_conditional_rendering);
+            }
+
+            // Compressed formats are only usable with their features enabled
+            let mut core_features = capabilities.texture_compression;
             if capabilities.dual_source_blending {
                 core_features.dual_src_blend = vk::TRUE;
             }
+            if capabilities.logic_op {
+                core_features.logic_op = vk::TRUE;
+            }
+            if capabilities.sparse_residency {
+                core_features.sparse_binding = vk::TRUE;
+                core_features.sparse_residency_image2_d = vk::TRUE;
+            }
+            if capabilities.robustness {
+                core_features.robust_buffer_access = vk::TRUE;
+            }
 
             let mut device_features2 =
                 vk::PhysicalDeviceFeatures2::default().features(core_features);
@@ -1155,6 +1437,9 @@ impl super::Context {
         };
 
         let instance = &inner.instance;
+        let max_binding_array_size = capabilities.max_binding_array_size();
+        let max_plain_data_size = capabilities.max_plain_data_size();
+        let dynamic_offset_alignment = capabilities.dynamic_offset_alignment();
         let device = super::Device {
             swapchain: if desc.presentation {
                 Some(khr::swapchain::Device::new(&instance.core, &device_core))
@@ -1163,7 +1448,15 @@ impl super::Context {
             },
             debug_utils: ext::debug_utils::Device::new(&instance.core, &device_core),
             timeline_semaphore: khr::timeline_semaphore::Device::new(&instance.core, &device_core),
-            dynamic_rendering: khr::dynamic_rendering::Device::new(&instance.core, &device_core),
+            dynamic_rendering: if capabilities.dynamic_rendering {
+                Some(khr::dynamic_rendering::Device::new(
+                    &instance.core,
+                    &device_core,
+                ))
+            } else {
+                None
+            },
+            render_passes: Default::default(),
             ray_tracing: if let Some(ref caps) = capabilities.ray_tracing {
                 Some(super::RayTracingDevice {
                     acceleration_structure: khr::acceleration_structure::Device::new(
@@ -1171,6 +1464,7 @@ impl super::Context {
                         &device_core,
                     ),
                     scratch_buffer_alignment: caps.min_scratch_buffer_alignment,
+                    motion_blur: caps.motion_blur,
                 })
             } else {
                 None
@@ -1225,6 +1519,22 @@ impl super::Context {
                 None
             },
             min_imported_host_pointer_alignment: capabilities.min_imported_host_pointer_alignment,
+            host_image_copy: if capabilities.host_image_copy {
+                Some(ext::host_image_copy::Device::new(
+                    &instance.core,
+                    &device_core,
+                ))
+            } else {
+                None
+            },
+            conditional_rendering: if capabilities.conditional_rendering {
+                Some(ext::conditional_rendering::Device::new(
+                    &instance.core,
+                    &device_core,
+                ))
+            } else {
+                None
+            },
             core: device_core,
             device_information: capabilities.device_information,
             command_scope: if desc.capture {
@@ -1258,8 +1568,21 @@ impl super::Context {
                     vk::DescriptorPoolCreateFlags::empty()
                 },
             },
+            read_only_store_op: if capabilities.dynamic_rendering
+                && capabilities.api_version >= vk::API_VERSION_1_3
+            {
+                vk::AttachmentStoreOp::NONE
+            } else {
+                vk::AttachmentStoreOp::STORE
+            },
+            descriptor_pool_size: match desc.descriptor_pool_size {
+                0 => super::descriptor::COUNT_BASE,
+                size => size,
+            },
+            descriptor_counters: Default::default(),
         };
 
+        let mut memory_heaps = Vec::new();
         let memory_manager = {
             let mem_properties = unsafe {
                 inner
@@ -1295,6 +1618,17 @@ impl super::Context {
                 buffer_device_address: capabilities.buffer_device_address,
             };
 
+            memory_heaps.extend(
+                mem_properties.memory_heaps[..mem_properties.memory_heap_count as usize]
+                    .iter()
+                    .map(|memory_heap| crate::MemoryHeapReport {
+                        size: memory_heap.size,
+                        device_local: memory_heap
+                            .flags
+                            .contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+                    }),
+            );
+
             let known_memory_flags = vk::MemoryPropertyFlags::DEVICE_LOCAL
                 | vk::MemoryPropertyFlags::HOST_VISIBLE
                 | vk::MemoryPropertyFlags::HOST_COHERENT
@@ -1412,6 +1746,21 @@ impl super::Context {
             None
         };
 
+        let reporter = crate::report::Reporter::new(crate::InitReport {
+            backend: "Vulkan".to_string(),
+            api_version: format!(
+                "{}.{}.{}",
+                vk::api_version_major(capabilities.api_version),
+                vk::api_version_minor(capabilities.api_version),
+                vk::api_version_patch(capabilities.api_version)
+            ),
+            device: device.device_information.clone(),
+            extensions,
+            features: Vec::new(),
+            memory_heaps,
+            surface: None,
+        });
+
         Ok(super::Context {
             memory: Mutex::new(memory_manager),
             device,
@@ -1420,6 +1769,8 @@ impl super::Context {
                 raw: queue,
                 timeline_semaphore,
                 last_progress,
+                command_buffers: Vec::new(),
+                sparse_bind_pending: false,
             }),
             physical_device,
             naga_flags,
@@ -1429,6 +1780,10 @@ impl super::Context {
                 .properties
                 .limits
                 .min_uniform_buffer_offset_alignment,
+            max_compute_work_group_count: capabilities
+                .properties
+                .limits
+                .max_compute_work_group_count,
             sample_count_flags: capabilities
                 .properties
                 .limits
@@ -1438,12 +1793,26 @@ impl super::Context {
                     .limits
                     .framebuffer_depth_sample_counts,
             dual_source_blending: capabilities.dual_source_blending,
+            logic_op: capabilities.logic_op,
+            max_view_count: capabilities.max_view_count,
+            sparse_residency: capabilities.sparse_residency,
+            sparse_tiles: Default::default(),
             shader_float16: capabilities.shader_float16,
             cooperative_matrix: capabilities.cooperative_matrix,
             binding_array: capabilities.binding_array,
+            max_binding_array_size,
+            max_plain_data_size,
+            dynamic_offset_alignment,
+            max_sampler_lod_bias: capabilities.properties.limits.max_sampler_lod_bias,
+            robustness: capabilities.robustness,
+            depth_resolve_modes: capabilities.depth_resolve_modes,
             memory_budget: capabilities.memory_budget,
             inner,
             xr,
+            deferred_destructions: Default::default(),
+            dummy_resources: Default::default(),
+            reporter,
+            sampler_cache: Default::default(),
         })
     }
 
@@ -1462,6 +1831,8 @@ impl super::Context {
     pub fn capabilities(&self) -> crate::Capabilities {
         crate::Capabilities {
             binding_array: self.binding_array,
+            max_binding_array_size: self.max_binding_array_size,
+            max_plain_data_size: self.max_plain_data_size,
             ray_query: match self.device.ray_tracing {
                 Some(_) => crate::ShaderVisibility::all(),
                 None => crate::ShaderVisibility::empty(),
@@ -1470,6 +1841,30 @@ impl super::Context {
             dual_source_blending: self.dual_source_blending,
             shader_float16: self.shader_float16,
             cooperative_matrix: self.cooperative_matrix,
+            acceleration_structure_motion: self
+                .device
+                .ray_tracing
+                .as_ref()
+                .is_some_and(|rt| rt.motion_blur),
+            reusable_command_encoders: true,
+            robustness: self.robustness,
+            depth_resolve: self
+                .depth_resolve_modes
+                .contains(vk::ResolveModeFlags::SAMPLE_ZERO),
+            depth_resolve_min_max: self
+                .depth_resolve_modes
+                .contains(vk::ResolveModeFlags::MIN | vk::ResolveModeFlags::MAX),
+            logic_op: self.logic_op,
+            sparse_tile_size: if self.sparse_residency {
+                super::SPARSE_TILE_SIZE
+            } else {
+                0
+            },
+            buffer_device_address: self.device.buffer_device_address,
+            max_view_count: self.max_view_count,
+            conditional_rendering: self.device.conditional_rendering.is_some(),
+            dynamic_offset_alignment: self.dynamic_offset_alignment,
+            max_sampler_lod_bias: self.max_sampler_lod_bias,
         }
     }
 
@@ -1477,6 +1872,58 @@ impl super::Context {
         &self.device.device_information
     }
 
+    /// Return the usages supported by the textures of the given format,
+    /// which are empty if the format isn't supported at all.
+    pub fn supported_texture_usage(&self, format: crate::TextureFormat) -> crate::TextureUsage {
+        let properties = unsafe {
+            self.inner
+                .instance
+                .core
+                .get_physical_device_format_properties(
+                    self.physical_device,
+                    super::map_texture_format(format),
+                )
+        };
+        let features = properties.optimal_tiling_features;
+        let mut usage = crate::TextureUsage::empty();
+        if features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE) {
+            usage |= crate::TextureUsage::RESOURCE | crate::TextureUsage::COPY;
+        }
+        if features.intersects(
+            vk::FormatFeatureFlags::COLOR_ATTACHMENT
+                | vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        ) {
+            usage |= crate::TextureUsage::TARGET | crate::TextureUsage::COPY;
+        }
+        if features.contains(vk::FormatFeatureFlags::STORAGE_IMAGE) {
+            usage |= crate::TextureUsage::STORAGE | crate::TextureUsage::COPY;
+        }
+        if self.device.host_image_copy.is_some() {
+            let mut properties3 = vk::FormatProperties3::default();
+            let mut properties2 = vk::FormatProperties2::default().push_next(&mut properties3);
+            unsafe {
+                self.inner
+                    .instance
+                    .core
+                    .get_physical_device_format_properties2(
+                        self.physical_device,
+                        super::map_texture_format(format),
+                        &mut properties2,
+                    )
+            };
+            if properties3
+                .optimal_tiling_features
+                .contains(vk::FormatFeatureFlags2::HOST_IMAGE_TRANSFER_EXT)
+            {
+                usage |= crate::TextureUsage::HOST_WRITE;
+            }
+        }
+        if self.sparse_tile_extent(format).is_some() {
+            usage |= crate::TextureUsage::SPARSE;
+        }
+        usage
+    }
+
     pub fn enumerate() -> Result<Vec<crate::DeviceReport>, NotSupportedError> {
         let desc = crate::ContextDesc::default();
         let inner = unsafe { super::VulkanInstance::create(&desc)? };
@@ -1495,9 +1942,21 @@ impl super::Context {
         )
     }
 
+    /// Release the descriptor pool memory that is no longer needed.
+    ///
+    /// The pools of every command buffer shrink back to their initial size
+    /// the next time it's started, when the GPU is done with its descriptors.
+    pub fn trim(&self) {
+        self.device.descriptor_counters.trim();
+    }
+
     pub fn memory_stats(&self) -> crate::MemoryStats {
+        let descriptor_pools = self.device.descriptor_counters.stats();
         if !self.memory_budget {
-            return crate::MemoryStats::default();
+            return crate::MemoryStats {
+                descriptor_pools,
+                ..Default::default()
+            };
         }
 
         let mut budget_properties = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
@@ -1532,6 +1991,7 @@ impl super::Context {
         crate::MemoryStats {
             budget: total_budget,
             usage: total_usage,
+            descriptor_pools,
         }
     }
 }
@@ -1546,6 +2006,9 @@ impl Drop for super::Context {
                     .core
                     .destroy_semaphore(queue.timeline_semaphore, None);
             }
+            self.dummy_resources.destroy(self);
+            self.deferred_destructions.destroy_all(self);
+            self.device.render_passes.destroy(&self.device.core);
             if let Ok(mut manager) = self.memory.lock() {
                 let leaked: Vec<_> = manager.slab.drain().collect();
                 for (block, name) in leaked {
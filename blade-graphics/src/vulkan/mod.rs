@@ -3,12 +3,20 @@ use ash::{
     vk::{self},
 };
 use openxr as xr;
-use std::{mem, num::NonZeroU32, path::PathBuf, ptr, sync::Mutex};
+use std::{
+    collections::HashMap,
+    mem,
+    num::NonZeroU32,
+    path::PathBuf,
+    ptr,
+    sync::{Arc, Mutex},
+};
 
 mod command;
 mod descriptor;
 mod init;
 mod pipeline;
+mod render_pass;
 mod resource;
 mod surface;
 
@@ -17,10 +25,20 @@ struct VulkanInstance {
     pub entry: ash::Entry,
     pub instance: Instance,
     pub driver_api_version: u32,
+    /// Names of the enabled instance extensions.
+    pub extensions: Vec<String>,
 }
 
 const QUERY_POOL_SIZE: usize = crate::limits::PASS_COUNT + 1;
+/// Size of the standard sparse image blocks.
+const SPARSE_TILE_SIZE: u32 = 0x10000;
+/// Size of the per-command-buffer memory for the uniform buffer bindings.
+const SCRATCH_SIZE: u64 = 1 << 20;
 const MAX_XR_EYES: usize = 2;
+/// Maximum number of dynamic buffers in a group, matching the minimum device limit.
+const MAX_DYNAMIC_BUFFERS: usize = 8;
+/// Motion instances are required to be laid out with this stride.
+const MOTION_INSTANCE_STRIDE: usize = 160;
 
 struct Instance {
     core: ash::Instance,
@@ -35,6 +53,7 @@ struct Instance {
 struct RayTracingDevice {
     acceleration_structure: khr::acceleration_structure::Device,
     scratch_buffer_alignment: u64,
+    motion_blur: bool,
 }
 
 #[derive(Clone, Default)]
@@ -58,7 +77,9 @@ struct Device {
     swapchain: Option<khr::swapchain::Device>,
     debug_utils: ash::ext::debug_utils::Device,
     timeline_semaphore: khr::timeline_semaphore::Device,
-    dynamic_rendering: khr::dynamic_rendering::Device,
+    /// Falls back to `render_passes` if dynamic rendering is not available.
+    dynamic_rendering: Option<khr::dynamic_rendering::Device>,
+    render_passes: Arc<render_pass::RenderPassCache>,
     ray_tracing: Option<RayTracingDevice>,
     buffer_device_address: bool,
     max_inline_uniform_block_size: u32,
@@ -78,9 +99,19 @@ struct Device {
     /// Driver-reported alignment for imported host pointers and
     /// allocation sizes (0 when `external_memory_host` is `None`).
     min_imported_host_pointer_alignment: u64,
+    /// `VK_EXT_host_image_copy` device wrapper, backing `write_texture`.
+    host_image_copy: Option<ash::ext::host_image_copy::Device>,
+    /// `VK_EXT_conditional_rendering` device wrapper, backing `begin_conditional`.
+    conditional_rendering: Option<ash::ext::conditional_rendering::Device>,
     command_scope: Option<CommandScopeDevice>,
     timing: Option<TimingDevice>,
     workarounds: Workarounds,
+    /// Store operation of the read-only depth-stencil aspects.
+    /// It doesn't access them where supported, so they can be sampled in the pass.
+    read_only_store_op: vk::AttachmentStoreOp,
+    /// Number of sets in the first descriptor pool of a command buffer.
+    descriptor_pool_size: u32,
+    descriptor_counters: Arc<descriptor::DescriptorCounters>,
 }
 
 struct MemoryManager {
@@ -89,10 +120,24 @@ struct MemoryManager {
     valid_ash_memory_types: u32,
 }
 
+/// Tile of a sparse texture, with the coordinates in tiles.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+struct SparseTile {
+    image: vk::Image,
+    mip_level: u32,
+    array_layer: u32,
+    coordinates: [u32; 3],
+}
+
 struct Queue {
     raw: vk::Queue,
     timeline_semaphore: vk::Semaphore,
     last_progress: u64,
+    /// Reused for collecting the command buffers of a submission.
+    command_buffers: Vec<vk::CommandBuffer>,
+    /// The last timeline value is signaled by a sparse binding,
+    /// which the next submission has to wait for.
+    sparse_bind_pending: bool,
 }
 
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
@@ -191,8 +236,13 @@ impl Frame {
         Texture {
             raw: self.internal.image,
             memory_handle: !0,
-            target_size: self.swapchain.target_size,
+            size: crate::Extent {
+                width: self.swapchain.target_size[0] as u32,
+                height: self.swapchain.target_size[1] as u32,
+                depth: 1,
+            },
             format: self.swapchain.format,
+            sample_count: 1,
             external: None,
         }
     }
@@ -200,8 +250,11 @@ impl Frame {
     pub fn texture_view(&self) -> TextureView {
         TextureView {
             raw: self.internal.view,
+            image: self.internal.image,
             target_size: self.swapchain.target_size,
             aspects: crate::TexelAspects::COLOR,
+            format: self.swapchain.format,
+            samples: vk::SampleCountFlags::TYPE_1,
         }
     }
 
@@ -217,8 +270,11 @@ impl Frame {
         );
         TextureView {
             raw,
+            image: self.internal.image,
             target_size: self.swapchain.target_size,
             aspects: crate::TexelAspects::COLOR,
+            format: self.swapchain.format,
+            samples: vk::SampleCountFlags::TYPE_1,
         }
     }
 
@@ -280,14 +336,30 @@ pub struct Context {
     shader_debug_path: Option<PathBuf>,
     min_buffer_alignment: u64,
     min_uniform_buffer_offset_alignment: u64,
+    max_compute_work_group_count: [u32; 3],
     sample_count_flags: vk::SampleCountFlags,
     dual_source_blending: bool,
+    logic_op: bool,
+    max_view_count: u32,
+    sparse_residency: bool,
+    /// Memory bound to the tiles of the sparse textures.
+    sparse_tiles: Mutex<HashMap<SparseTile, usize>>,
     shader_float16: bool,
     cooperative_matrix: crate::CooperativeMatrix,
     binding_array: bool,
+    max_binding_array_size: u32,
+    max_plain_data_size: u32,
+    dynamic_offset_alignment: u32,
+    max_sampler_lod_bias: f32,
+    robustness: bool,
+    depth_resolve_modes: vk::ResolveModeFlags,
     memory_budget: bool,
     inner: VulkanInstance,
     xr: Option<Mutex<XrSessionState>>,
+    pub(crate) deferred_destructions: crate::deferred::DeferredDestructions,
+    pub(crate) dummy_resources: crate::dummy::DummyResources,
+    pub(crate) reporter: crate::report::Reporter,
+    pub(crate) sampler_cache: crate::cache::SamplerCache,
 }
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq)]
@@ -296,6 +368,7 @@ pub struct Buffer {
     memory_handle: usize,
     mapped_data: *mut u8,
     size: u64,
+    device_address: u64,
     external: Option<crate::ExternalMemorySource>,
 }
 
@@ -306,6 +379,7 @@ impl Default for Buffer {
             memory_handle: !0,
             mapped_data: ptr::null_mut(),
             size: 0,
+            device_address: 0,
             external: None,
         }
     }
@@ -319,6 +393,10 @@ impl Buffer {
     pub fn size(&self) -> u64 {
         self.size
     }
+
+    pub fn device_address(&self) -> u64 {
+        self.device_address
+    }
 }
 
 unsafe impl Send for Buffer {}
@@ -328,8 +406,9 @@ unsafe impl Sync for Buffer {}
 pub struct Texture {
     raw: vk::Image,
     memory_handle: usize,
-    target_size: [u16; 2],
+    size: crate::Extent,
     format: crate::TextureFormat,
+    sample_count: u32,
     external: Option<crate::ExternalMemorySource>,
 }
 
@@ -338,21 +417,49 @@ impl Default for Texture {
         Self {
             raw: vk::Image::default(),
             memory_handle: !0,
-            target_size: [0; 2],
+            size: crate::Extent::default(),
             format: crate::TextureFormat::Rgba8Unorm,
+            sample_count: 1,
             external: None,
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, Default, Hash, PartialEq)]
+impl Texture {
+    /// Size of the base mip level.
+    pub fn size(&self) -> crate::Extent {
+        self.size
+    }
+
+    pub fn format(&self) -> crate::TextureFormat {
+        self.format
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq)]
 pub struct TextureView {
     raw: vk::ImageView,
+    image: vk::Image,
     target_size: [u16; 2],
     aspects: crate::TexelAspects,
+    format: crate::TextureFormat,
+    samples: vk::SampleCountFlags,
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq)]
+impl Default for TextureView {
+    fn default() -> Self {
+        Self {
+            raw: vk::ImageView::default(),
+            image: vk::Image::default(),
+            target_size: [0; 2],
+            aspects: crate::TexelAspects::empty(),
+            format: crate::TextureFormat::Rgba8Unorm,
+            samples: vk::SampleCountFlags::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Sampler {
     raw: vk::Sampler,
 }
@@ -373,6 +480,8 @@ struct DescriptorSetLayout {
     /// Bitmask: bit N is set if binding N uses inline uniform blocks.
     /// Clear bits use uniform buffer objects via the scratch buffer.
     inline_uniform_mask: u64,
+    /// Template offsets and shader sizes of the dynamic buffers, in binding order.
+    dynamic_ranges: Box<[(u32, u32)]>,
 }
 
 impl DescriptorSetLayout {
@@ -397,12 +506,17 @@ struct ScratchBuffer {
     alignment: u64,
 }
 
+// The mapping is only written through the command encoder owning it
+unsafe impl Send for ScratchBuffer {}
+
 pub struct PipelineContext<'a> {
     update_data: &'a mut [u8],
     template_offsets: &'a [u32],
     scratch: Option<&'a mut ScratchBuffer>,
     /// Bitmask: bit N is set if binding N uses inline uniform blocks.
     inline_uniform_mask: u64,
+    depth_stencil_writes: Option<(vk::Image, crate::TexelAspects)>,
+    dummies: Option<crate::dummy::DummySet>,
 }
 
 #[derive(Debug)]
@@ -410,6 +524,7 @@ pub struct ComputePipeline {
     raw: vk::Pipeline,
     layout: PipelineLayout,
     wg_size: [u32; 3],
+    max_group_count: [u32; 3],
 }
 
 #[hidden_trait::expose]
@@ -417,12 +532,16 @@ impl crate::traits::ComputePipelineBase for ComputePipeline {
     fn get_workgroup_size(&self) -> [u32; 3] {
         self.wg_size
     }
+    fn get_max_group_count(&self) -> [u32; 3] {
+        self.max_group_count
+    }
 }
 
 #[derive(Debug)]
 pub struct RenderPipeline {
     raw: vk::Pipeline,
     layout: PipelineLayout,
+    depth_stencil_writes: crate::TexelAspects,
 }
 
 #[derive(Debug)]
@@ -430,8 +549,10 @@ struct CommandBuffer {
     raw: vk::CommandBuffer,
     descriptor_pool: descriptor::DescriptorPool,
     query_pool: vk::QueryPool,
-    timed_pass_names: Vec<String>,
+    timed_pass_names: crate::PassNames,
     scratch: Option<ScratchBuffer>,
+    /// Framebuffers of the render passes, without dynamic rendering.
+    framebuffers: Vec<vk::Framebuffer>,
 }
 
 struct CrashHandler {
@@ -441,15 +562,47 @@ struct CrashHandler {
     next_offset: usize,
 }
 
+/// Descriptor data of the bindings, used to skip the redundant ones.
+#[derive(Default)]
+struct BindingCache {
+    update_data: Vec<u8>,
+    /// Data of the descriptor sets bound for the current pipeline, per group.
+    /// Only valid for the groups in `bound_groups`.
+    bound_data: Vec<Vec<u8>>,
+    /// Descriptor sets bound for the current pipeline, per group,
+    /// which are bound again with the new dynamic offsets.
+    bound_sets: Vec<vk::DescriptorSet>,
+    bound_groups: u64,
+    stats: crate::BindingStats,
+    /// Depth-stencil target of the current render pass, with the written aspects,
+    /// which can't be sampled at the same time.
+    depth_stencil_writes: Option<(vk::Image, crate::TexelAspects)>,
+    /// Resources bound for `Nullable` bindings of `None`, without robustness.
+    dummies: Option<crate::dummy::DummySet>,
+}
+
+impl BindingCache {
+    fn retained_bytes(&self) -> usize {
+        self.update_data.capacity()
+            + self.bound_data.capacity() * mem::size_of::<Vec<u8>>()
+            + self.bound_data.iter().map(Vec::capacity).sum::<usize>()
+            + self.bound_sets.capacity() * mem::size_of::<vk::DescriptorSet>()
+    }
+}
+
 pub struct CommandEncoder {
     pool: vk::CommandPool,
     buffers: Box<[CommandBuffer]>,
     device: Device,
-    update_data: Vec<u8>,
+    binding: BindingCache,
     present: Option<Presentation>,
     crash_handler: Option<CrashHandler>,
     temp_label: Vec<u8>,
     timings: crate::Timings,
+    timings_recording_index: Option<u64>,
+    recording_index: u64,
+    recording: crate::RecordingState,
+    peak_retained_bytes: usize,
 }
 pub struct TransferCommandEncoder<'a> {
     raw: vk::CommandBuffer,
@@ -458,11 +611,12 @@ pub struct TransferCommandEncoder<'a> {
 pub struct AccelerationStructureCommandEncoder<'a> {
     raw: vk::CommandBuffer,
     device: &'a Device,
+    has_builds: bool,
 }
 pub struct ComputeCommandEncoder<'a> {
     cmd_buf: &'a mut CommandBuffer,
     device: &'a Device,
-    update_data: &'a mut Vec<u8>,
+    binding: &'a mut BindingCache,
 }
 //Note: we aren't merging this with `ComputeCommandEncoder`
 // because the destructors are different, and they can't be specialized
@@ -470,7 +624,10 @@ pub struct ComputeCommandEncoder<'a> {
 pub struct RenderCommandEncoder<'a> {
     cmd_buf: &'a mut CommandBuffer,
     device: &'a Device,
-    update_data: &'a mut Vec<u8>,
+    binding: &'a mut BindingCache,
+    depth_stencil_read_only: crate::TexelAspects,
+    /// Draws are predicated by `begin_conditional`.
+    conditional: bool,
 }
 
 pub struct PipelineEncoder<'a, 'p> {
@@ -478,7 +635,7 @@ pub struct PipelineEncoder<'a, 'p> {
     layout: &'p PipelineLayout,
     bind_point: vk::PipelineBindPoint,
     device: &'a Device,
-    update_data: &'a mut Vec<u8>,
+    binding: &'a mut BindingCache,
 }
 
 #[derive(Clone, Debug)]
@@ -537,7 +694,6 @@ impl crate::traits::CommandDevice for Context {
                 // Always create a scratch buffer for UBO bindings.
                 // Even when inline uniform blocks are supported, individual
                 // bindings that exceed the device limit fall back to UBOs.
-                const SCRATCH_SIZE: u64 = 1 << 20; // 1 MiB
                 let scratch_buf = self.create_buffer(crate::BufferDesc {
                     name: "_scratch",
                     size: SCRATCH_SIZE,
@@ -555,8 +711,9 @@ impl crate::traits::CommandDevice for Context {
                     raw,
                     descriptor_pool,
                     query_pool,
-                    timed_pass_names: Vec::new(),
+                    timed_pass_names: Default::default(),
                     scratch,
+                    framebuffers: Vec::new(),
                 }
             })
             .collect();
@@ -580,11 +737,23 @@ impl crate::traits::CommandDevice for Context {
             pool,
             buffers,
             device: self.device.clone(),
-            update_data: Vec::new(),
+            binding: BindingCache {
+                // Null descriptors are only valid with robustness
+                dummies: if self.robustness {
+                    None
+                } else {
+                    self.dummy_resources.for_encoder(self)
+                },
+                ..Default::default()
+            },
             present: None,
             crash_handler,
             temp_label: Vec::new(),
             timings: Default::default(),
+            timings_recording_index: None,
+            recording_index: 0,
+            recording: Default::default(),
+            peak_retained_bytes: 0,
         }
     }
 
@@ -605,12 +774,14 @@ impl crate::traits::CommandDevice for Context {
                         .destroy_query_pool(cmd_buf.query_pool, None);
                 }
             }
+            self.device.destroy_framebuffers(cmd_buf);
             if let Some(ref scratch) = cmd_buf.scratch {
                 self.destroy_buffer(super::Buffer {
                     raw: scratch.raw,
                     memory_handle: scratch.memory_handle,
                     mapped_data: scratch.mapped,
                     size: 0,
+                    device_address: 0,
                     external: None,
                 });
             }
@@ -626,42 +797,99 @@ impl crate::traits::CommandDevice for Context {
     }
 
     fn submit(&self, encoder: &mut CommandEncoder) -> SyncPoint {
-        let raw_cmd_buf = encoder.finish();
+        self.submit_batch(&mut [encoder])
+    }
+
+    fn submit_batch(&self, encoders: &mut [&mut CommandEncoder]) -> SyncPoint {
+        assert!(!encoders.is_empty(), "Nothing to submit");
+        self.deferred_destructions.drain(self);
+        for encoder in encoders.iter() {
+            encoder.check_submittable();
+        }
+        assert!(
+            encoders.iter().filter(|e| e.present.is_some()).count() <= 1,
+            "Only one encoder in a batch can present"
+        );
+        let present_index = encoders.iter().position(|e| e.present.is_some());
+
         let mut queue = self.queue.lock().unwrap();
+        // Every encoder ends with a full barrier, so the passes of the next one
+        // see the results, the same way as within a single encoder.
+        let mut command_buffers = mem::take(&mut queue.command_buffers);
+        command_buffers.clear();
+        command_buffers.extend(encoders.iter_mut().map(|encoder| encoder.finish()));
+        let encoder = &mut *encoders[present_index.unwrap_or(encoders.len() - 1)];
+        // Encoders before the presenting one don't need to wait for the frame,
+        // so they go into a separate batch of the same submission.
+        let (early_command_buffers, command_buffers_slice) =
+            command_buffers.split_at(present_index.unwrap_or(0));
+        let mut early_wait_value = 0;
+        let mut early_wait_semaphore = vk::Semaphore::null();
+        let mut wait_values_all = [0; 2];
+        let mut wait_semaphores_all = [vk::Semaphore::null(); 2];
+        let wait_stages = [vk::PipelineStageFlags::ALL_COMMANDS; 2];
+        let mut num_wait_semaphores = 0;
+        // Sparse bindings are not ordered with the submissions otherwise
+        if mem::take(&mut queue.sparse_bind_pending) {
+            if early_command_buffers.is_empty() {
+                wait_semaphores_all[0] = queue.timeline_semaphore;
+                wait_values_all[0] = queue.last_progress;
+                num_wait_semaphores += 1;
+            } else {
+                early_wait_semaphore = queue.timeline_semaphore;
+                early_wait_value = queue.last_progress;
+            }
+        }
         queue.last_progress += 1;
         let progress = queue.last_progress;
-        let command_buffers = [raw_cmd_buf];
-        let wait_values_all = [0];
-        let mut wait_semaphores_all = [vk::Semaphore::null()];
-        let wait_stages = [vk::PipelineStageFlags::ALL_COMMANDS];
         let mut signal_semaphores_all = [queue.timeline_semaphore, vk::Semaphore::null()];
         let signal_values_all = [progress, 0];
-        let (num_wait_semaphores, num_signal_sepahores) = match encoder.present {
+        let num_signal_sepahores = match encoder.present {
             Some(Presentation::Window {
                 acquire_semaphore,
                 present_semaphore,
                 ..
             }) => {
-                wait_semaphores_all[0] = acquire_semaphore;
+                wait_semaphores_all[num_wait_semaphores] = acquire_semaphore;
+                num_wait_semaphores += 1;
                 signal_semaphores_all[1] = present_semaphore;
-                (1, 2)
+                2
             }
-            Some(Presentation::Xr { .. }) | None => (0, 1),
+            Some(Presentation::Xr { .. }) | None => 1,
         };
         let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::default()
             .wait_semaphore_values(&wait_values_all[..num_wait_semaphores])
             .signal_semaphore_values(&signal_values_all[..num_signal_sepahores]);
         let vk_info = vk::SubmitInfo::default()
-            .command_buffers(&command_buffers)
+            .command_buffers(command_buffers_slice)
             .wait_semaphores(&wait_semaphores_all[..num_wait_semaphores])
             .wait_dst_stage_mask(&wait_stages[..num_wait_semaphores])
             .signal_semaphores(&signal_semaphores_all[..num_signal_sepahores])
             .push_next(&mut timeline_info);
+        let num_early_waits = (early_wait_semaphore != vk::Semaphore::null()) as usize;
+        let early_wait_values = [early_wait_value];
+        let early_wait_semaphores = [early_wait_semaphore];
+        let mut early_timeline_info = vk::TimelineSemaphoreSubmitInfo::default()
+            .wait_semaphore_values(&early_wait_values[..num_early_waits]);
+        let early_vk_info = vk::SubmitInfo::default()
+            .command_buffers(early_command_buffers)
+            .wait_semaphores(&early_wait_semaphores[..num_early_waits])
+            .wait_dst_stage_mask(&wait_stages[..num_early_waits])
+            .push_next(&mut early_timeline_info);
+        // Batches of a submission start in order, and every encoder ends with a full barrier,
+        // so the presenting encoder still sees the results of the early ones.
+        let vk_infos = [early_vk_info, vk_info];
+        let vk_infos = if early_command_buffers.is_empty() {
+            &vk_infos[1..]
+        } else {
+            &vk_infos[..]
+        };
         let ret = unsafe {
             self.device
                 .core
-                .queue_submit(queue.raw, &[vk_info], vk::Fence::null())
+                .queue_submit(queue.raw, vk_infos, vk::Fence::null())
         };
+        queue.command_buffers = command_buffers;
         encoder.check_gpu_crash(ret);
 
         if let Some(presentation) = encoder.present.take() {
@@ -787,7 +1015,10 @@ impl crate::traits::CommandDevice for Context {
                 .timeline_semaphore
                 .wait_semaphores(&wait_info, timeout_ns)
         } {
-            Ok(()) => Ok(true),
+            Ok(()) => {
+                self.deferred_destructions.drain(self);
+                Ok(true)
+            }
             Err(vk::Result::TIMEOUT) => Ok(false),
             Err(vk::Result::ERROR_DEVICE_LOST) => Err(crate::DeviceError::DeviceLost),
             Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY)
@@ -823,8 +1054,8 @@ fn map_texture_format(format: crate::TextureFormat) -> vk::Format {
         Tf::Depth32Float => vk::Format::D32_SFLOAT,
         Tf::Depth32FloatStencil8Uint => vk::Format::D32_SFLOAT_S8_UINT,
         Tf::Stencil8Uint => vk::Format::S8_UINT,
-        Tf::Bc1Unorm => vk::Format::BC1_RGBA_SRGB_BLOCK,
-        Tf::Bc1UnormSrgb => vk::Format::BC1_RGBA_UNORM_BLOCK,
+        Tf::Bc1Unorm => vk::Format::BC1_RGBA_UNORM_BLOCK,
+        Tf::Bc1UnormSrgb => vk::Format::BC1_RGBA_SRGB_BLOCK,
         Tf::Bc2Unorm => vk::Format::BC2_UNORM_BLOCK,
         Tf::Bc2UnormSrgb => vk::Format::BC2_SRGB_BLOCK,
         Tf::Bc3Unorm => vk::Format::BC3_UNORM_BLOCK,
@@ -837,6 +1068,12 @@ fn map_texture_format(format: crate::TextureFormat) -> vk::Format {
         Tf::Bc6hFloat => vk::Format::BC6H_SFLOAT_BLOCK,
         Tf::Bc7Unorm => vk::Format::BC7_UNORM_BLOCK,
         Tf::Bc7UnormSrgb => vk::Format::BC7_SRGB_BLOCK,
+        Tf::Etc2Rgb8Unorm => vk::Format::ETC2_R8G8B8_UNORM_BLOCK,
+        Tf::Etc2Rgb8UnormSrgb => vk::Format::ETC2_R8G8B8_SRGB_BLOCK,
+        Tf::Etc2Rgba8Unorm => vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+        Tf::Etc2Rgba8UnormSrgb => vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+        Tf::Astc4x4Unorm => vk::Format::ASTC_4X4_UNORM_BLOCK,
+        Tf::Astc4x4UnormSrgb => vk::Format::ASTC_4X4_SRGB_BLOCK,
         Tf::Rgb10a2Unorm => vk::Format::A2B10G10R10_UNORM_PACK32,
         Tf::Rg11b10Ufloat => vk::Format::B10G11R11_UFLOAT_PACK32,
         Tf::Rgb9e5Ufloat => vk::Format::E5B9G9R9_UFLOAT_PACK32,
@@ -882,6 +1119,11 @@ fn map_subresource_range(
     }
 }
 
+/// Mask of the views of a multiview pass, zero for a regular pass.
+fn map_view_mask(multiview: Option<NonZeroU32>) -> u32 {
+    multiview.map_or(0, |count| !0 >> (32 - count.get()))
+}
+
 fn map_comparison(fun: crate::CompareFunction) -> vk::CompareOp {
     use crate::CompareFunction as Cf;
     match fun {
@@ -929,6 +1171,10 @@ struct BottomLevelAccelerationStructureInput<'a> {
 }
 
 impl Device {
+    fn supports_motion(&self) -> bool {
+        self.ray_tracing.as_ref().is_some_and(|rt| rt.motion_blur)
+    }
+
     fn get_device_address(&self, piece: &crate::BufferPiece) -> u64 {
         let vk_info = vk::BufferDeviceAddressInfo {
             buffer: piece.buffer.raw,
@@ -187,6 +187,7 @@ impl super::Context {
         let mut template_offsets = Vec::with_capacity(layout.bindings.len());
         let mut binding_flags = Vec::with_capacity(layout.bindings.len());
         let mut inline_uniform_mask = 0u64;
+        let mut dynamic_ranges = Vec::new();
         let mut update_offset = 0;
         for (binding_index, (&(_, binding), &access)) in layout
             .bindings
@@ -227,6 +228,19 @@ impl super::Context {
                     1u32,
                     vk::DescriptorBindingFlags::empty(),
                 ),
+                crate::ShaderBinding::DynamicBuffer => {
+                    dynamic_ranges.push((update_offset as u32, info.binding_sizes[binding_index]));
+                    (
+                        if info.uniform_mask & (1 << binding_index) != 0 {
+                            vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+                        } else {
+                            vk::DescriptorType::STORAGE_BUFFER_DYNAMIC
+                        },
+                        mem::size_of::<vk::DescriptorBufferInfo>(),
+                        1u32,
+                        vk::DescriptorBindingFlags::empty(),
+                    )
+                }
                 crate::ShaderBinding::BufferArray { count } => (
                     vk::DescriptorType::STORAGE_BUFFER,
                     mem::size_of::<vk::DescriptorBufferInfo>(),
@@ -293,6 +307,11 @@ impl super::Context {
             template_offsets.push(update_offset as u32);
             update_offset += descriptor_size * descriptor_count as usize;
         }
+        assert!(
+            dynamic_ranges.len() <= super::MAX_DYNAMIC_BUFFERS,
+            "Too many dynamic buffers in a group: {}",
+            dynamic_ranges.len()
+        );
 
         let mut binding_flags_info =
             vk::DescriptorSetLayoutBindingFlagsCreateInfo::default().binding_flags(&binding_flags);
@@ -323,6 +342,7 @@ impl super::Context {
             template_size: update_offset as u32,
             template_offsets: template_offsets.into_boxed_slice(),
             inline_uniform_mask,
+            dynamic_ranges: dynamic_ranges.into_boxed_slice(),
         }
     }
 
@@ -449,6 +469,23 @@ impl crate::traits::ShaderDevice for super::Context {
     type RenderPipeline = super::RenderPipeline;
 
     fn create_compute_pipeline(&self, desc: crate::ComputePipelineDesc) -> super::ComputePipeline {
+        let name = desc.name;
+        self.try_create_compute_pipeline(desc)
+            .unwrap_or_else(|e| panic!("Unable to create compute pipeline '{name}': {e}"))
+    }
+
+    fn try_create_compute_pipeline(
+        &self,
+        desc: crate::ComputePipelineDesc,
+    ) -> Result<super::ComputePipeline, crate::PipelineError> {
+        crate::ShaderDataLayout::check_binding_array_size(
+            desc.data_layouts,
+            self.max_binding_array_size,
+        )?;
+        crate::ShaderDataLayout::check_plain_data_size(
+            desc.data_layouts,
+            self.max_plain_data_size,
+        )?;
         let mut group_infos = desc
             .data_layouts
             .iter()
@@ -502,11 +539,12 @@ impl crate::traits::ShaderDevice for super::Context {
         if !desc.name.is_empty() {
             self.set_object_name(raw, desc.name);
         }
-        super::ComputePipeline {
+        Ok(super::ComputePipeline {
             raw,
             layout,
             wg_size: cs.wg_size,
-        }
+            max_group_count: self.max_compute_work_group_count,
+        })
     }
 
     fn get_compute_pipeline_statistics(
@@ -530,6 +568,26 @@ impl crate::traits::ShaderDevice for super::Context {
     }
 
     fn create_render_pipeline(&self, desc: crate::RenderPipelineDesc) -> super::RenderPipeline {
+        let name = desc.name;
+        self.try_create_render_pipeline(desc)
+            .unwrap_or_else(|e| panic!("Unable to create render pipeline '{name}': {e}"))
+    }
+
+    fn try_create_render_pipeline(
+        &self,
+        desc: crate::RenderPipelineDesc,
+    ) -> Result<super::RenderPipeline, crate::PipelineError> {
+        crate::ShaderDataLayout::check_binding_array_size(
+            desc.data_layouts,
+            self.max_binding_array_size,
+        )?;
+        crate::ShaderDataLayout::check_plain_data_size(
+            desc.data_layouts,
+            self.max_plain_data_size,
+        )?;
+        desc.check_color_targets()?;
+        let logic_op = desc.check_logic_op(self.logic_op)?;
+        desc.check_multiview(self.max_view_count)?;
         let mut group_infos = desc
             .data_layouts
             .iter()
@@ -710,15 +768,21 @@ impl crate::traits::ShaderDevice for super::Context {
             color_formats.push(super::map_texture_format(ct.format));
             vk_attachments.push(vk_attachment);
         }
-        let vk_color_blend =
+        let mut vk_color_blend =
             vk::PipelineColorBlendStateCreateInfo::default().attachments(&vk_attachments);
+        if let Some(op) = logic_op {
+            vk_color_blend = vk_color_blend
+                .logic_op_enable(true)
+                .logic_op(map_logic_op(op));
+        }
 
         let mut rendering_info = vk::PipelineRenderingCreateInfo::default()
             .color_attachment_formats(&color_formats)
             .depth_attachment_format(d_format)
-            .stencil_attachment_format(s_format);
+            .stencil_attachment_format(s_format)
+            .view_mask(super::map_view_mask(desc.multiview));
 
-        let create_info = vk::GraphicsPipelineCreateInfo::default()
+        let mut create_info = vk::GraphicsPipelineCreateInfo::default()
             .layout(layout.raw)
             .stages(stages)
             .vertex_input_state(&vk_vertex_input)
@@ -728,8 +792,24 @@ impl crate::traits::ShaderDevice for super::Context {
             .multisample_state(&vk_multisample)
             .depth_stencil_state(&vk_depth_stencil)
             .color_blend_state(&vk_color_blend)
-            .dynamic_state(&vk_dynamic_state)
-            .push_next(&mut rendering_info);
+            .dynamic_state(&vk_dynamic_state);
+        if self.device.dynamic_rendering.is_some() {
+            create_info = create_info.push_next(&mut rendering_info);
+        } else {
+            let key = super::render_pass::RenderPassKey::for_pipeline(
+                &color_formats,
+                if d_format != vk::Format::UNDEFINED {
+                    d_format
+                } else {
+                    s_format
+                },
+                desc.multisample_state.sample_count,
+                super::map_view_mask(desc.multiview),
+            );
+            create_info = create_info
+                .render_pass(self.device.get_render_pass(&key))
+                .subpass(0);
+        }
 
         let mut raw_vec = unsafe {
             self.device
@@ -749,7 +829,14 @@ impl crate::traits::ShaderDevice for super::Context {
         if !desc.name.is_empty() {
             self.set_object_name(raw, desc.name);
         }
-        super::RenderPipeline { raw, layout }
+        Ok(super::RenderPipeline {
+            raw,
+            layout,
+            depth_stencil_writes: desc
+                .depth_stencil
+                .as_ref()
+                .map_or(crate::TexelAspects::empty(), |ds| ds.write_aspects()),
+        })
     }
 
     fn destroy_render_pipeline(&self, pipeline: &mut super::RenderPipeline) {
@@ -881,6 +968,28 @@ fn map_blend_op(operation: crate::BlendOperation) -> vk::BlendOp {
     }
 }
 
+fn map_logic_op(op: crate::LogicOp) -> vk::LogicOp {
+    use crate::LogicOp as Lo;
+    match op {
+        Lo::Clear => vk::LogicOp::CLEAR,
+        Lo::And => vk::LogicOp::AND,
+        Lo::AndReverse => vk::LogicOp::AND_REVERSE,
+        Lo::Copy => vk::LogicOp::COPY,
+        Lo::AndInverted => vk::LogicOp::AND_INVERTED,
+        Lo::NoOp => vk::LogicOp::NO_OP,
+        Lo::Xor => vk::LogicOp::XOR,
+        Lo::Or => vk::LogicOp::OR,
+        Lo::Nor => vk::LogicOp::NOR,
+        Lo::Equivalent => vk::LogicOp::EQUIVALENT,
+        Lo::Invert => vk::LogicOp::INVERT,
+        Lo::OrReverse => vk::LogicOp::OR_REVERSE,
+        Lo::CopyInverted => vk::LogicOp::COPY_INVERTED,
+        Lo::OrInverted => vk::LogicOp::OR_INVERTED,
+        Lo::Nand => vk::LogicOp::NAND,
+        Lo::Set => vk::LogicOp::SET,
+    }
+}
+
 fn map_blend_component(
     component: &crate::BlendComponent,
 ) -> (vk::BlendOp, vk::BlendFactor, vk::BlendFactor) {
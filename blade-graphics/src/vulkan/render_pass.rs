@@ -0,0 +1,275 @@
+//! Render passes and framebuffers, used in place of dynamic rendering
+//! on the drivers that don't support `VK_KHR_dynamic_rendering`.
+
+use ash::vk;
+use std::{collections::HashMap, sync::Mutex};
+
+use super::command::MAX_COLOR_TARGETS;
+
+/// Attachment description, with everything a render pass depends on.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+struct AttachmentKey {
+    format: vk::Format,
+    samples: vk::SampleCountFlags,
+    load_op: vk::AttachmentLoadOp,
+    store_op: vk::AttachmentStoreOp,
+    stencil_load_op: vk::AttachmentLoadOp,
+    stencil_store_op: vk::AttachmentStoreOp,
+    resolve: bool,
+}
+
+impl AttachmentKey {
+    /// Attachment of a pipeline, which only needs to be compatible
+    /// with the ones it's used in, so the ops don't matter.
+    fn compatible(format: vk::Format, samples: vk::SampleCountFlags) -> Self {
+        Self {
+            format,
+            samples,
+            load_op: vk::AttachmentLoadOp::LOAD,
+            store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::LOAD,
+            stencil_store_op: vk::AttachmentStoreOp::STORE,
+            resolve: false,
+        }
+    }
+
+    fn from_target(view: &super::TextureView, info: &vk::RenderingAttachmentInfo) -> Self {
+        Self::from_depth_stencil_target(view, info, info)
+    }
+
+    /// Attachment of a depth-stencil target, with separate ops for the stencil aspect.
+    fn from_depth_stencil_target(
+        view: &super::TextureView,
+        info: &vk::RenderingAttachmentInfo,
+        stencil_info: &vk::RenderingAttachmentInfo,
+    ) -> Self {
+        Self {
+            format: super::map_texture_format(view.format),
+            samples: view.samples,
+            load_op: info.load_op,
+            store_op: info.store_op,
+            stencil_load_op: stencil_info.load_op,
+            stencil_store_op: stencil_info.store_op,
+            resolve: info.resolve_image_view != vk::ImageView::null(),
+        }
+    }
+
+    fn to_description(self) -> vk::AttachmentDescription {
+        vk::AttachmentDescription {
+            format: self.format,
+            samples: self.samples,
+            load_op: self.load_op,
+            store_op: self.store_op,
+            // Only relevant to the formats with stencil
+            stencil_load_op: self.stencil_load_op,
+            stencil_store_op: self.stencil_store_op,
+            initial_layout: vk::ImageLayout::GENERAL,
+            final_layout: vk::ImageLayout::GENERAL,
+            ..Default::default()
+        }
+    }
+}
+
+/// Key of the render pass cache.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub(super) struct RenderPassKey {
+    colors: [AttachmentKey; MAX_COLOR_TARGETS],
+    color_count: usize,
+    depth_stencil: Option<AttachmentKey>,
+    view_mask: u32,
+}
+
+impl RenderPassKey {
+    /// Key of a render pass compatible with the pipeline targets.
+    pub(super) fn for_pipeline(
+        color_formats: &[vk::Format],
+        depth_stencil_format: vk::Format,
+        sample_count: u32,
+        view_mask: u32,
+    ) -> Self {
+        let samples = vk::SampleCountFlags::from_raw(sample_count);
+        let mut key = Self {
+            color_count: color_formats.len(),
+            view_mask,
+            ..Default::default()
+        };
+        for (attachment, &format) in key.colors.iter_mut().zip(color_formats) {
+            *attachment = AttachmentKey::compatible(format, samples);
+        }
+        if depth_stencil_format != vk::Format::UNDEFINED {
+            key.depth_stencil = Some(AttachmentKey::compatible(depth_stencil_format, samples));
+        }
+        key
+    }
+}
+
+/// Render passes created so far, shared by the whole device.
+///
+/// Their number is bounded by the combinations of formats and ops
+/// the application renders with, so they are only destroyed with the device.
+#[derive(Debug, Default)]
+pub(super) struct RenderPassCache {
+    passes: Mutex<HashMap<RenderPassKey, vk::RenderPass>>,
+}
+
+impl RenderPassCache {
+    pub(super) fn destroy(&self, device: &ash::Device) {
+        let mut passes = self.passes.lock().unwrap();
+        for (_, raw) in passes.drain() {
+            unsafe { device.destroy_render_pass(raw, None) };
+        }
+    }
+}
+
+impl super::Device {
+    /// Get the render pass matching the key, creating it on first use.
+    pub(super) fn get_render_pass(&self, key: &RenderPassKey) -> vk::RenderPass {
+        let mut passes = self.render_passes.passes.lock().unwrap();
+        *passes
+            .entry(*key)
+            .or_insert_with(|| create_render_pass(&self.core, key))
+    }
+
+    /// Begin a render pass with a new framebuffer for the targets.
+    ///
+    /// The framebuffer is owned by the command buffer, and is destroyed
+    /// once the command buffer is recorded again.
+    pub(super) fn begin_render_pass(
+        &self,
+        cmd_buf: &mut super::CommandBuffer,
+        targets: &crate::RenderTargetSet,
+        attachments: &[vk::RenderingAttachmentInfo],
+        depth_stencil_attachments: Option<(
+            &vk::RenderingAttachmentInfo,
+            &vk::RenderingAttachmentInfo,
+        )>,
+        render_area: vk::Rect2D,
+    ) {
+        let mut key = RenderPassKey {
+            color_count: attachments.len(),
+            view_mask: super::map_view_mask(targets.multiview),
+            ..Default::default()
+        };
+        let mut views = [vk::ImageView::null(); 2 * MAX_COLOR_TARGETS + 1];
+        let mut clear_values = [vk::ClearValue::default(); 2 * MAX_COLOR_TARGETS + 1];
+        let mut count = 0;
+        for ((attachment, rt), info) in key.colors.iter_mut().zip(targets.colors).zip(attachments) {
+            *attachment = AttachmentKey::from_target(&rt.view, info);
+            views[count] = info.image_view;
+            clear_values[count] = info.clear_value;
+            count += 1;
+        }
+        for info in attachments {
+            if info.resolve_image_view != vk::ImageView::null() {
+                views[count] = info.resolve_image_view;
+                count += 1;
+            }
+        }
+        if let (Some(rt), Some((info, stencil_info))) =
+            (targets.depth_stencil.as_ref(), depth_stencil_attachments)
+        {
+            assert!(
+                info.resolve_image_view == vk::ImageView::null(),
+                "Depth resolve is not supported without dynamic rendering"
+            );
+            key.depth_stencil = Some(AttachmentKey::from_depth_stencil_target(
+                &rt.view,
+                info,
+                stencil_info,
+            ));
+            views[count] = info.image_view;
+            clear_values[count] = info.clear_value;
+            count += 1;
+        }
+
+        let render_pass = self.get_render_pass(&key);
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass)
+            .attachments(&views[..count])
+            .width(render_area.extent.width)
+            .height(render_area.extent.height)
+            .layers(1);
+        let framebuffer = unsafe {
+            self.core
+                .create_framebuffer(&framebuffer_info, None)
+                .unwrap()
+        };
+        cmd_buf.framebuffers.push(framebuffer);
+
+        let begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(render_pass)
+            .framebuffer(framebuffer)
+            .render_area(render_area)
+            .clear_values(&clear_values[..count]);
+        unsafe {
+            self.core
+                .cmd_begin_render_pass(cmd_buf.raw, &begin_info, vk::SubpassContents::INLINE)
+        };
+    }
+
+    pub(super) fn destroy_framebuffers(&self, cmd_buf: &mut super::CommandBuffer) {
+        for framebuffer in cmd_buf.framebuffers.drain(..) {
+            unsafe { self.core.destroy_framebuffer(framebuffer, None) };
+        }
+    }
+}
+
+fn create_render_pass(device: &ash::Device, key: &RenderPassKey) -> vk::RenderPass {
+    let colors = &key.colors[..key.color_count];
+    let mut attachments = Vec::with_capacity(2 * colors.len() + 1);
+    let mut color_refs = Vec::with_capacity(colors.len());
+    let mut resolve_refs = Vec::with_capacity(colors.len());
+    for color in colors {
+        color_refs.push(vk::AttachmentReference {
+            attachment: attachments.len() as u32,
+            layout: vk::ImageLayout::GENERAL,
+        });
+        attachments.push(color.to_description());
+    }
+    for color in colors {
+        resolve_refs.push(vk::AttachmentReference {
+            attachment: if color.resolve {
+                attachments.push(vk::AttachmentDescription {
+                    format: color.format,
+                    samples: vk::SampleCountFlags::TYPE_1,
+                    load_op: vk::AttachmentLoadOp::DONT_CARE,
+                    store_op: vk::AttachmentStoreOp::STORE,
+                    initial_layout: vk::ImageLayout::GENERAL,
+                    final_layout: vk::ImageLayout::GENERAL,
+                    ..Default::default()
+                });
+                attachments.len() as u32 - 1
+            } else {
+                vk::ATTACHMENT_UNUSED
+            },
+            layout: vk::ImageLayout::GENERAL,
+        });
+    }
+    let depth_stencil_ref = key.depth_stencil.map(|ds| {
+        attachments.push(ds.to_description());
+        vk::AttachmentReference {
+            attachment: attachments.len() as u32 - 1,
+            layout: vk::ImageLayout::GENERAL,
+        }
+    });
+
+    let mut subpass = vk::SubpassDescription::default()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(&color_refs);
+    if colors.iter().any(|color| color.resolve) {
+        subpass = subpass.resolve_attachments(&resolve_refs);
+    }
+    if let Some(ref ds_ref) = depth_stencil_ref {
+        subpass = subpass.depth_stencil_attachment(ds_ref);
+    }
+    let subpasses = [subpass];
+    let mut create_info = vk::RenderPassCreateInfo::default()
+        .attachments(&attachments)
+        .subpasses(&subpasses);
+    let view_masks = [key.view_mask];
+    let mut multiview_info = vk::RenderPassMultiviewCreateInfo::default().view_masks(&view_masks);
+    if key.view_mask != 0 {
+        create_info = create_info.push_next(&mut multiview_info);
+    }
+    unsafe { device.create_render_pass(&create_info, None).unwrap() }
+}
@@ -16,7 +16,7 @@ impl super::Context {
         requirements: vk::MemoryRequirements,
         memory: crate::Memory,
         name: &str,
-    ) -> Allocation {
+    ) -> Result<Allocation, crate::ResourceError> {
         let mut manager = self.memory.lock().unwrap();
         let device_address_usage = if self.device.buffer_device_address {
             gpu_alloc::UsageFlags::DEVICE_ADDRESS
@@ -35,7 +35,9 @@ impl super::Context {
                     | device_address_usage
             }
             crate::Memory::Upload => {
-                gpu_alloc::UsageFlags::HOST_ACCESS | gpu_alloc::UsageFlags::UPLOAD
+                gpu_alloc::UsageFlags::HOST_ACCESS
+                    | gpu_alloc::UsageFlags::UPLOAD
+                    | device_address_usage
             }
         };
         let memory_types = requirements.memory_type_bits & manager.valid_ash_memory_types;
@@ -196,7 +198,18 @@ impl super::Context {
                             memory_types,
                         },
                     )
-                    .unwrap()
+                    .map_err(|e| match e {
+                        gpu_alloc::AllocationError::OutOfDeviceMemory
+                        | gpu_alloc::AllocationError::TooManyObjects => {
+                            crate::ResourceError::OutOfDeviceMemory
+                        }
+                        gpu_alloc::AllocationError::OutOfHostMemory => {
+                            crate::ResourceError::OutOfHostMemory
+                        }
+                        gpu_alloc::AllocationError::NoCompatibleMemoryTypes => {
+                            panic!("No compatible memory types for {memory:?}")
+                        }
+                    })?
             },
         };
 
@@ -216,13 +229,13 @@ impl super::Context {
                     .as_ptr()
             },
         };
-        Allocation {
+        Ok(Allocation {
             memory: *block.memory(),
             offset: block.offset(),
             data,
             handle: manager.slab.insert((block, name.to_string())),
             memory_type: memory,
-        }
+        })
     }
 
     fn free_memory(&self, handle: usize) {
@@ -255,12 +268,38 @@ impl super::Context {
         crate::AccelerationStructureSizes {
             data: sizes_raw.acceleration_structure_size,
             scratch: sizes_raw.build_scratch_size,
+            update_scratch: sizes_raw.update_scratch_size,
+            scratch_alignment: rt.scratch_buffer_alignment,
         }
     }
 
     pub fn get_top_level_acceleration_structure_sizes(
         &self,
         instance_count: u32,
+    ) -> crate::AccelerationStructureSizes {
+        self.get_top_level_sizes(
+            instance_count,
+            vk::BuildAccelerationStructureFlagsKHR::empty(),
+        )
+    }
+
+    pub fn get_top_level_motion_acceleration_structure_sizes(
+        &self,
+        instance_count: u32,
+    ) -> Result<crate::AccelerationStructureSizes, crate::ResourceError> {
+        if !self.device.supports_motion() {
+            return Err(crate::ResourceError::MotionNotSupported);
+        }
+        Ok(self.get_top_level_sizes(
+            instance_count,
+            vk::BuildAccelerationStructureFlagsKHR::MOTION_NV,
+        ))
+    }
+
+    fn get_top_level_sizes(
+        &self,
+        instance_count: u32,
+        flags: vk::BuildAccelerationStructureFlagsKHR,
     ) -> crate::AccelerationStructureSizes {
         let geometry = vk::AccelerationStructureGeometryKHR::default()
             .geometry_type(vk::GeometryTypeKHR::INSTANCES)
@@ -270,6 +309,7 @@ impl super::Context {
         let geometries = [geometry];
         let build_info = vk::AccelerationStructureBuildGeometryInfoKHR::default()
             .ty(vk::AccelerationStructureTypeKHR::TOP_LEVEL)
+            .flags(flags)
             .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
             .geometries(&geometries);
 
@@ -287,6 +327,8 @@ impl super::Context {
         crate::AccelerationStructureSizes {
             data: sizes_raw.acceleration_structure_size,
             scratch: sizes_raw.build_scratch_size,
+            update_scratch: sizes_raw.update_scratch_size,
+            scratch_alignment: rt.scratch_buffer_alignment,
         }
     }
 
@@ -337,6 +379,70 @@ impl super::Context {
         buffer
     }
 
+    pub fn create_acceleration_structure_motion_instance_buffer(
+        &self,
+        instances: &[crate::AccelerationStructureMotionInstance],
+        bottom_level: &[super::AccelerationStructure],
+    ) -> Result<super::Buffer, crate::ResourceError> {
+        if !self.device.supports_motion() {
+            return Err(crate::ResourceError::MotionNotSupported);
+        }
+        let rt = self.device.ray_tracing.as_ref().unwrap();
+        let buffer = self.create_buffer(crate::BufferDesc {
+            name: "motion instance buffer",
+            size: (instances.len().max(1) * super::MOTION_INSTANCE_STRIDE) as u64,
+            memory: crate::Memory::Shared,
+        });
+        for (i, instance) in instances.iter().enumerate() {
+            let device_address_info = vk::AccelerationStructureDeviceAddressInfoKHR {
+                acceleration_structure: bottom_level
+                    [instance.acceleration_structure_index as usize]
+                    .raw,
+                ..Default::default()
+            };
+            let matrix_instance = vk::AccelerationStructureMatrixMotionInstanceNV {
+                transform_t0: unsafe {
+                    mem::transmute::<mint::RowMatrix3x4<f32>, vk::TransformMatrixKHR>(
+                        instance.transform_start,
+                    )
+                },
+                transform_t1: unsafe {
+                    mem::transmute::<mint::RowMatrix3x4<f32>, vk::TransformMatrixKHR>(
+                        instance.transform_end,
+                    )
+                },
+                instance_custom_index_and_mask: vk::Packed24_8::new(
+                    instance.custom_index,
+                    instance.mask as u8,
+                ),
+                instance_shader_binding_table_record_offset_and_flags: vk::Packed24_8::new(0, 0),
+                acceleration_structure_reference: vk::AccelerationStructureReferenceKHR {
+                    device_handle: unsafe {
+                        rt.acceleration_structure
+                            .get_acceleration_structure_device_address(&device_address_info)
+                    },
+                },
+            };
+            let vk_instance = vk::AccelerationStructureMotionInstanceNV {
+                ty: vk::AccelerationStructureMotionInstanceTypeNV::MATRIX_MOTION,
+                flags: vk::AccelerationStructureMotionInstanceFlagsNV::empty(),
+                data: vk::AccelerationStructureMotionInstanceDataNV {
+                    matrix_motion_instance: matrix_instance,
+                },
+            };
+            unsafe {
+                ptr::write(
+                    buffer
+                        .data()
+                        .add(i * super::MOTION_INSTANCE_STRIDE)
+                        .cast::<vk::AccelerationStructureMotionInstanceNV>(),
+                    vk_instance,
+                );
+            }
+        }
+        Ok(buffer)
+    }
+
     pub fn get_external_texture_source(
         &self,
         texture: super::Texture,
@@ -352,6 +458,264 @@ impl super::Context {
     }
 }
 
+impl super::Context {
+    /// Submit sparse bindings, ordered after the submitted work,
+    /// returning the timeline value that they signal.
+    fn bind_sparse(
+        &self,
+        opaque_binds: &[vk::SparseImageOpaqueMemoryBindInfo],
+        image_binds: &[vk::SparseImageMemoryBindInfo],
+    ) -> u64 {
+        let mut queue = self.queue.lock().unwrap();
+        let semaphores = [queue.timeline_semaphore];
+        let wait_values = [queue.last_progress];
+        queue.last_progress += 1;
+        let signal_values = [queue.last_progress];
+        let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::default()
+            .wait_semaphore_values(&wait_values)
+            .signal_semaphore_values(&signal_values);
+        let vk_info = vk::BindSparseInfo::default()
+            .wait_semaphores(&semaphores)
+            .image_opaque_binds(opaque_binds)
+            .image_binds(image_binds)
+            .signal_semaphores(&semaphores)
+            .push_next(&mut timeline_info);
+        unsafe {
+            self.device
+                .core
+                .queue_bind_sparse(queue.raw, &[vk_info], vk::Fence::null())
+                .unwrap()
+        };
+        queue.sparse_bind_pending = true;
+        queue.last_progress
+    }
+
+    /// Bind the memory of the mip tail of a sparse image,
+    /// returning the allocation handle, or `!0` if there is no tail.
+    fn bind_sparse_mip_tail(
+        &self,
+        image: vk::Image,
+        requirements: vk::MemoryRequirements,
+        desc: &crate::TextureDesc,
+    ) -> Result<usize, crate::ResourceError> {
+        // Color images have a single aspect
+        let sparse_requirements =
+            unsafe { self.device.core.get_image_sparse_memory_requirements(image) }[0];
+        if sparse_requirements.image_mip_tail_first_lod >= desc.mip_level_count {
+            return Ok(!0);
+        }
+        let tail_count = if sparse_requirements
+            .format_properties
+            .flags
+            .contains(vk::SparseImageFormatFlags::SINGLE_MIPTAIL)
+        {
+            1
+        } else {
+            desc.array_layer_count
+        };
+        let tail_size = sparse_requirements.image_mip_tail_size;
+        let allocation = self.allocate_memory(
+            vk::MemoryRequirements {
+                size: tail_size * tail_count as u64,
+                ..requirements
+            },
+            crate::Memory::Device,
+            desc.name,
+        )?;
+        let binds = (0..tail_count)
+            .map(|i| vk::SparseMemoryBind {
+                resource_offset: sparse_requirements.image_mip_tail_offset
+                    + i as u64 * sparse_requirements.image_mip_tail_stride,
+                size: tail_size,
+                memory: allocation.memory,
+                memory_offset: allocation.offset + i as u64 * tail_size,
+                flags: vk::SparseMemoryBindFlags::empty(),
+            })
+            .collect::<Vec<_>>();
+        let opaque_bind = vk::SparseImageOpaqueMemoryBindInfo::default()
+            .image(image)
+            .binds(&binds);
+        self.bind_sparse(&[opaque_bind], &[]);
+        Ok(allocation.handle)
+    }
+
+    /// Return the size of the tiles of sparse textures in the given format,
+    /// or `None` if it doesn't support `TextureUsage::SPARSE`.
+    pub fn sparse_tile_extent(&self, format: crate::TextureFormat) -> Option<crate::Extent> {
+        if !self.sparse_residency || format.aspects() != crate::TexelAspects::COLOR {
+            return None;
+        }
+        let properties = unsafe {
+            self.inner
+                .instance
+                .core
+                .get_physical_device_sparse_image_format_properties(
+                    self.physical_device,
+                    super::map_texture_format(format),
+                    vk::ImageType::TYPE_2D,
+                    vk::SampleCountFlags::TYPE_1,
+                    vk::ImageUsageFlags::SAMPLED,
+                    vk::ImageTiling::OPTIMAL,
+                )
+        };
+        properties
+            .first()
+            .filter(|p| {
+                !p.flags
+                    .contains(vk::SparseImageFormatFlags::NONSTANDARD_BLOCK_SIZE)
+            })
+            .map(|p| crate::Extent {
+                width: p.image_granularity.width,
+                height: p.image_granularity.height,
+                depth: p.image_granularity.depth,
+            })
+    }
+
+    /// Bind or unbind the memory of the tiles of a sparse texture,
+    /// see `TextureUsage::SPARSE`.
+    ///
+    /// The regions have to be aligned to `sparse_tile_extent`, or reach
+    /// the edges of their mip level, which can't be in the mip tail.
+    /// Binding the tiles that are already resident is skipped, same as
+    /// unbinding the non-resident ones.
+    ///
+    /// The update happens after the work submitted before, and before
+    /// the work submitted after. Unbinding waits for the GPU to finish it,
+    /// in order to release the memory.
+    pub fn update_texture_residency(
+        &self,
+        texture: super::Texture,
+        regions: &[crate::TextureRegion],
+        residency: crate::Residency,
+    ) -> Result<(), crate::ResourceError> {
+        let tile = self
+            .sparse_tile_extent(texture.format)
+            .expect("Sparse residency is not supported for the format");
+        let requirements = unsafe { self.device.core.get_image_memory_requirements(texture.raw) };
+        // The standard sparse blocks have the size of the alignment
+        let tile_requirements = vk::MemoryRequirements {
+            size: requirements.alignment,
+            ..requirements
+        };
+        let mip_tail_first_lod = unsafe {
+            self.device
+                .core
+                .get_image_sparse_memory_requirements(texture.raw)
+        }[0]
+        .image_mip_tail_first_lod;
+
+        let mut sparse_tiles = self.sparse_tiles.lock().unwrap();
+        let mut binds = Vec::new();
+        let mut allocated = Vec::new();
+        let mut released = Vec::new();
+        for region in regions {
+            assert!(
+                region.mip_level < mip_tail_first_lod,
+                "Mip level {} is in the mip tail, which is always resident",
+                region.mip_level
+            );
+            let mip_size = texture.size.at_mip_level(region.mip_level);
+            let tile_size = [tile.width, tile.height, tile.depth];
+            let mip_end = [mip_size.width, mip_size.height, mip_size.depth];
+            let region_end = [
+                region.origin[0] + region.size.width,
+                region.origin[1] + region.size.height,
+                region.origin[2] + region.size.depth,
+            ];
+            for i in 0..3 {
+                assert!(
+                    region.origin[i] % tile_size[i] == 0
+                        && (region_end[i] % tile_size[i] == 0 || region_end[i] == mip_end[i])
+                        && region_end[i] <= mip_end[i],
+                    "Region {region:?} is not aligned to the tiles of {tile}"
+                );
+            }
+            for z in region.origin[2] / tile.depth..region_end[2].div_ceil(tile.depth) {
+                for y in region.origin[1] / tile.height..region_end[1].div_ceil(tile.height) {
+                    for x in region.origin[0] / tile.width..region_end[0].div_ceil(tile.width) {
+                        let key = super::SparseTile {
+                            image: texture.raw,
+                            mip_level: region.mip_level,
+                            array_layer: region.array_layer,
+                            coordinates: [x, y, z],
+                        };
+                        let (memory, memory_offset) = match residency {
+                            crate::Residency::Bind => {
+                                if sparse_tiles.contains_key(&key) {
+                                    continue;
+                                }
+                                let allocation = match self.allocate_memory(
+                                    tile_requirements,
+                                    crate::Memory::Device,
+                                    "sparse tile",
+                                ) {
+                                    Ok(allocation) => allocation,
+                                    Err(e) => {
+                                        // Roll back the tiles that are not bound yet
+                                        for key in allocated {
+                                            let handle = sparse_tiles.remove(&key).unwrap();
+                                            self.free_memory(handle);
+                                        }
+                                        return Err(e);
+                                    }
+                                };
+                                sparse_tiles.insert(key, allocation.handle);
+                                allocated.push(key);
+                                (allocation.memory, allocation.offset)
+                            }
+                            crate::Residency::Unbind => {
+                                let Some(handle) = sparse_tiles.remove(&key) else {
+                                    continue;
+                                };
+                                released.push(handle);
+                                (vk::DeviceMemory::null(), 0)
+                            }
+                        };
+                        let offset = [x * tile.width, y * tile.height, z * tile.depth];
+                        binds.push(vk::SparseImageMemoryBind {
+                            subresource: vk::ImageSubresource {
+                                aspect_mask: vk::ImageAspectFlags::COLOR,
+                                mip_level: region.mip_level,
+                                array_layer: region.array_layer,
+                            },
+                            offset: vk::Offset3D {
+                                x: offset[0] as i32,
+                                y: offset[1] as i32,
+                                z: offset[2] as i32,
+                            },
+                            extent: vk::Extent3D {
+                                width: tile.width.min(mip_end[0] - offset[0]),
+                                height: tile.height.min(mip_end[1] - offset[1]),
+                                depth: tile.depth.min(mip_end[2] - offset[2]),
+                            },
+                            memory,
+                            memory_offset,
+                            flags: vk::SparseMemoryBindFlags::empty(),
+                        });
+                    }
+                }
+            }
+        }
+        drop(sparse_tiles);
+        if binds.is_empty() {
+            return Ok(());
+        }
+
+        let image_bind = vk::SparseImageMemoryBindInfo::default()
+            .image(texture.raw)
+            .binds(&binds);
+        let progress = self.bind_sparse(&[], &[image_bind]);
+        if !released.is_empty() {
+            // The memory can't be reused until the tiles are unbound
+            let _ = self.wait_for(&super::SyncPoint { progress }, !0);
+            for handle in released {
+                self.free_memory(handle);
+            }
+        }
+        Ok(())
+    }
+}
+
 #[hidden_trait::expose]
 impl crate::traits::ResourceDevice for super::Context {
     type Buffer = super::Buffer;
@@ -361,6 +725,15 @@ impl crate::traits::ResourceDevice for super::Context {
     type AccelerationStructure = super::AccelerationStructure;
 
     fn create_buffer(&self, desc: crate::BufferDesc) -> super::Buffer {
+        let name = desc.name;
+        self.try_create_buffer(desc)
+            .unwrap_or_else(|e| panic!("Unable to create buffer '{name}': {e}"))
+    }
+
+    fn try_create_buffer(
+        &self,
+        desc: crate::BufferDesc,
+    ) -> Result<super::Buffer, crate::ResourceError> {
         use vk::BufferUsageFlags as Buf;
         let external_source = match desc.memory {
             crate::Memory::External(e) => Some(e),
@@ -393,11 +766,17 @@ impl crate::traits::ResourceDevice for super::Context {
         if self.device.ray_tracing.is_some() {
             vk_info.usage |= Buf::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR;
         }
+        if self.device.conditional_rendering.is_some() {
+            vk_info.usage |= Buf::CONDITIONAL_RENDERING_EXT;
+        }
 
-        let raw = unsafe { self.device.core.create_buffer(&vk_info, None).unwrap() };
+        let raw = unsafe { self.device.core.create_buffer(&vk_info, None) }
+            .map_err(map_resource_error)?;
         let mut requirements = unsafe { self.device.core.get_buffer_memory_requirements(raw) };
         requirements.alignment = requirements.alignment.max(self.min_buffer_alignment);
-        let allocation = self.allocate_memory(requirements, desc.memory, desc.name);
+        let allocation = self
+            .allocate_memory(requirements, desc.memory, desc.name)
+            .inspect_err(|_| unsafe { self.device.core.destroy_buffer(raw, None) })?;
 
         log::info!(
             "Creating buffer {:?} of size {}, name '{}', handle {:?}",
@@ -415,14 +794,25 @@ impl crate::traits::ResourceDevice for super::Context {
         if !desc.name.is_empty() {
             self.set_object_name(raw, desc.name);
         }
+        // Imported memory is allocated without the device address flag
+        let device_address = if self.device.buffer_device_address && external_source.is_none() {
+            let vk_info = vk::BufferDeviceAddressInfo {
+                buffer: raw,
+                ..Default::default()
+            };
+            unsafe { self.device.core.get_buffer_device_address(&vk_info) }
+        } else {
+            0
+        };
 
-        super::Buffer {
+        Ok(super::Buffer {
             raw,
             memory_handle: allocation.handle,
             mapped_data: allocation.data,
             size: desc.size,
+            device_address,
             external: fetch_external_source(&self.device, allocation),
-        }
+        })
     }
 
     fn sync_buffer(&self, _buffer: super::Buffer) {}
@@ -438,6 +828,16 @@ impl crate::traits::ResourceDevice for super::Context {
     }
 
     fn create_texture(&self, desc: crate::TextureDesc) -> super::Texture {
+        let name = desc.name;
+        self.try_create_texture(desc)
+            .unwrap_or_else(|e| panic!("Unable to create texture '{name}': {e}"))
+    }
+
+    fn try_create_texture(
+        &self,
+        desc: crate::TextureDesc,
+    ) -> Result<super::Texture, crate::ResourceError> {
+        desc.check_usage(self.supported_texture_usage(desc.format))?;
         let mut create_flags = vk::ImageCreateFlags::empty();
         if desc.dimension == crate::TextureDimension::D2
             && desc.size.depth.is_multiple_of(6)
@@ -453,6 +853,19 @@ impl crate::traits::ResourceDevice for super::Context {
         {
             create_flags |= vk::ImageCreateFlags::MUTABLE_FORMAT;
         }
+        let sparse = desc.usage.contains(crate::TextureUsage::SPARSE);
+        if sparse {
+            if desc.dimension != crate::TextureDimension::D2
+                || desc.sample_count > 1
+                || desc.external.is_some()
+            {
+                return Err(crate::ResourceError::UnsupportedUsage(
+                    crate::TextureUsage::SPARSE,
+                ));
+            }
+            create_flags |=
+                vk::ImageCreateFlags::SPARSE_BINDING | vk::ImageCreateFlags::SPARSE_RESIDENCY;
+        }
 
         let mut external_next = desc.external.map(|e| vk::ExternalMemoryImageCreateInfo {
             handle_types: external_source_handle_type(e),
@@ -473,6 +886,49 @@ impl crate::traits::ResourceDevice for super::Context {
             ..Default::default()
         };
 
+        // External memory has its own limits, which are queried separately
+        let max_resource_size = if desc.external.is_none() {
+            let properties = unsafe {
+                self.inner
+                    .instance
+                    .core
+                    .get_physical_device_image_format_properties(
+                        self.physical_device,
+                        vk_info.format,
+                        vk_info.image_type,
+                        vk_info.tiling,
+                        vk_info.usage,
+                        vk_info.flags,
+                    )
+            }
+            .map_err(|e| match e {
+                vk::Result::ERROR_FORMAT_NOT_SUPPORTED => {
+                    crate::ResourceError::UnsupportedUsage(desc.usage)
+                }
+                other => map_resource_error(other),
+            })?;
+            let max = properties.max_extent;
+            let limit = if desc.size.width > max.width {
+                Some(max.width)
+            } else if desc.size.height > max.height {
+                Some(max.height)
+            } else if desc.size.depth > max.depth {
+                Some(max.depth)
+            } else if desc.array_layer_count > properties.max_array_layers {
+                Some(properties.max_array_layers)
+            } else {
+                None
+            };
+            if let Some(limit) = limit {
+                return Err(crate::ResourceError::TooLarge {
+                    limit: limit as u64,
+                });
+            }
+            properties.max_resource_size
+        } else {
+            !0
+        };
+
         if let Some(external_next) = external_next.as_mut() {
             vk_info = vk_info.push_next(external_next);
         }
@@ -481,14 +937,40 @@ impl crate::traits::ResourceDevice for super::Context {
             TODO(ErikWDev): Support lazily allocated texture with transient allocation for efficient msaa?
                             Measure bandwidth usage!
         */
-        let raw = unsafe { self.device.core.create_image(&vk_info, None).unwrap() };
+        let raw =
+            unsafe { self.device.core.create_image(&vk_info, None) }.map_err(map_resource_error)?;
         let requirements = unsafe { self.device.core.get_image_memory_requirements(raw) };
-        let allocation = self.allocate_memory(
-            requirements,
-            desc.external
-                .map_or(crate::Memory::Device, crate::Memory::External),
-            desc.name,
-        );
+        if requirements.size > max_resource_size {
+            unsafe { self.device.core.destroy_image(raw, None) };
+            return Err(crate::ResourceError::TooLarge {
+                limit: max_resource_size,
+            });
+        }
+        let (memory_handle, external) = if sparse {
+            let handle = self
+                .bind_sparse_mip_tail(raw, requirements, &desc)
+                .inspect_err(|_| unsafe { self.device.core.destroy_image(raw, None) })?;
+            (handle, None)
+        } else {
+            let allocation = self
+                .allocate_memory(
+                    requirements,
+                    desc.external
+                        .map_or(crate::Memory::Device, crate::Memory::External),
+                    desc.name,
+                )
+                .inspect_err(|_| unsafe { self.device.core.destroy_image(raw, None) })?;
+            unsafe {
+                self.device
+                    .core
+                    .bind_image_memory(raw, allocation.memory, allocation.offset)
+                    .unwrap()
+            };
+            (
+                allocation.handle,
+                fetch_external_source(&self.device, allocation),
+            )
+        };
 
         log::info!(
             "Creating texture {:?} of size {} and format {:?}, name '{}', handle {:?}",
@@ -496,25 +978,44 @@ impl crate::traits::ResourceDevice for super::Context {
             desc.size,
             desc.format,
             desc.name,
-            allocation.handle
+            memory_handle
         );
-        unsafe {
-            self.device
-                .core
-                .bind_image_memory(raw, allocation.memory, allocation.offset)
-                .unwrap()
-        };
         if !desc.name.is_empty() {
             self.set_object_name(raw, desc.name);
         }
 
-        super::Texture {
+        if desc.usage.contains(crate::TextureUsage::HOST_WRITE) {
+            // Host writes happen outside of command buffers,
+            // so the layout has to be initialized right away.
+            let hic = self
+                .device
+                .host_image_copy
+                .as_ref()
+                .expect("Host writes are not supported");
+            let transition = vk::HostImageLayoutTransitionInfoEXT {
+                image: raw,
+                old_layout: vk::ImageLayout::UNDEFINED,
+                new_layout: vk::ImageLayout::GENERAL,
+                subresource_range: vk::ImageSubresourceRange {
+                    aspect_mask: super::map_aspects(desc.format.aspects()),
+                    base_mip_level: 0,
+                    level_count: vk::REMAINING_MIP_LEVELS,
+                    base_array_layer: 0,
+                    layer_count: vk::REMAINING_ARRAY_LAYERS,
+                },
+                ..Default::default()
+            };
+            unsafe { hic.transition_image_layout(&[transition]).unwrap() };
+        }
+
+        Ok(super::Texture {
             raw,
-            memory_handle: allocation.handle,
-            target_size: [desc.size.width as u16, desc.size.height as u16],
+            memory_handle,
+            size: desc.size,
             format: desc.format,
-            external: fetch_external_source(&self.device, allocation),
-        }
+            sample_count: desc.sample_count,
+            external,
+        })
     }
 
     fn destroy_texture(&self, texture: super::Texture) {
@@ -524,7 +1025,58 @@ impl crate::traits::ResourceDevice for super::Context {
             texture.memory_handle
         );
         unsafe { self.device.core.destroy_image(texture.raw, None) };
-        self.free_memory(texture.memory_handle);
+        // Sparse textures only own the memory of their mip tail
+        if texture.memory_handle != !0 {
+            self.free_memory(texture.memory_handle);
+        }
+        if self.sparse_residency {
+            let mut sparse_tiles = self.sparse_tiles.lock().unwrap();
+            sparse_tiles.retain(|tile, &mut handle| {
+                if tile.image == texture.raw {
+                    self.free_memory(handle);
+                }
+                tile.image != texture.raw
+            });
+        }
+    }
+
+    fn write_texture(
+        &self,
+        dst: crate::TexturePiece,
+        data: &[u8],
+        bytes_per_row: u32,
+        size: crate::Extent,
+    ) {
+        crate::debug_check_region(&dst, size);
+        let hic = self
+            .device
+            .host_image_copy
+            .as_ref()
+            .expect("Host writes are not supported");
+        let block_info = dst.texture.format.block_info();
+        let row_count = size.height.div_ceil(block_info.dimensions.1 as u32);
+        assert!(
+            data.len() as u64 >= bytes_per_row as u64 * (row_count * size.depth) as u64,
+            "Not enough data for {size}"
+        );
+        let region = vk::MemoryToImageCopyEXT {
+            p_host_pointer: data.as_ptr() as *const _,
+            memory_row_length: block_info.dimensions.0 as u32
+                * (bytes_per_row / block_info.size as u32),
+            memory_image_height: 0,
+            image_subresource: dst.subresource_layers(),
+            image_offset: super::command::map_origin(&dst.origin),
+            image_extent: super::map_extent_3d(&size),
+            ..Default::default()
+        };
+        let info = vk::CopyMemoryToImageInfoEXT {
+            dst_image: dst.texture.raw,
+            dst_image_layout: vk::ImageLayout::GENERAL,
+            region_count: 1,
+            p_regions: &region,
+            ..Default::default()
+        };
+        unsafe { hic.copy_memory_to_image(&info).unwrap() };
     }
 
     fn create_texture_view(
@@ -533,11 +1085,19 @@ impl crate::traits::ResourceDevice for super::Context {
         desc: crate::TextureViewDesc,
     ) -> super::TextureView {
         let aspects = desc.format.aspects();
+        // Views of a single aspect of a depth-stencil texture keep its format
+        let format = if aspects != texture.format.aspects()
+            && !aspects.contains(crate::TexelAspects::COLOR)
+        {
+            texture.format
+        } else {
+            desc.format
+        };
         let subresource_range = super::map_subresource_range(desc.subresources, aspects);
         let vk_info = vk::ImageViewCreateInfo {
             image: texture.raw,
             view_type: map_view_dimension(desc.dimension),
-            format: super::map_texture_format(desc.format),
+            format: super::map_texture_format(format),
             subresource_range,
             ..Default::default()
         };
@@ -547,13 +1107,14 @@ impl crate::traits::ResourceDevice for super::Context {
             self.set_object_name(raw, desc.name);
         }
 
+        let mip_size = texture.size.at_mip_level(desc.subresources.base_mip_level);
         super::TextureView {
             raw,
-            target_size: [
-                (texture.target_size[0] >> desc.subresources.base_mip_level).max(1),
-                (texture.target_size[1] >> desc.subresources.base_mip_level).max(1),
-            ],
+            image: texture.raw,
+            target_size: [mip_size.width as u16, mip_size.height as u16],
             aspects,
+            format,
+            samples: vk::SampleCountFlags::from_raw(texture.sample_count),
         }
     }
 
@@ -562,6 +1123,7 @@ impl crate::traits::ResourceDevice for super::Context {
     }
 
     fn create_sampler(&self, desc: crate::SamplerDesc) -> super::Sampler {
+        let lod_bias = desc.check_lod(self.max_sampler_lod_bias);
         let mut vk_info = vk::SamplerCreateInfo {
             mag_filter: map_filter_mode(desc.mag_filter),
             min_filter: map_filter_mode(desc.min_filter),
@@ -571,6 +1133,7 @@ impl crate::traits::ResourceDevice for super::Context {
             address_mode_w: map_address_mode(desc.address_modes[2]),
             min_lod: desc.lod_min_clamp,
             max_lod: desc.lod_max_clamp.unwrap_or(vk::LOD_CLAMP_NONE),
+            mip_lod_bias: lod_bias,
             ..Default::default()
         };
 
@@ -586,22 +1149,30 @@ impl crate::traits::ResourceDevice for super::Context {
             vk_info.border_color = map_border_color(color);
         }
 
-        let raw = unsafe { self.device.core.create_sampler(&vk_info, None).unwrap() };
-        if !desc.name.is_empty() {
-            self.set_object_name(raw, desc.name);
-        }
-
-        super::Sampler { raw }
+        self.sampler_cache.get_or_create(&desc, || {
+            let raw = unsafe { self.device.core.create_sampler(&vk_info, None).unwrap() };
+            if !desc.name.is_empty() {
+                self.set_object_name(raw, desc.name);
+            }
+            super::Sampler { raw }
+        })
     }
 
     fn destroy_sampler(&self, sampler: super::Sampler) {
-        unsafe { self.device.core.destroy_sampler(sampler.raw, None) };
+        if self.sampler_cache.release(sampler) {
+            unsafe { self.device.core.destroy_sampler(sampler.raw, None) };
+        }
     }
 
     fn create_acceleration_structure(
         &self,
         desc: crate::AccelerationStructureDesc,
     ) -> super::AccelerationStructure {
+        assert!(
+            self.device.supports_motion()
+                || !matches!(desc.ty, crate::AccelerationStructureType::TopLevelMotion),
+            "Motion acceleration structures are not supported"
+        );
         let buffer_info = vk::BufferCreateInfo {
             size: desc.size,
             usage: vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR
@@ -612,7 +1183,9 @@ impl crate::traits::ResourceDevice for super::Context {
 
         let buffer = unsafe { self.device.core.create_buffer(&buffer_info, None).unwrap() };
         let requirements = unsafe { self.device.core.get_buffer_memory_requirements(buffer) };
-        let allocation = self.allocate_memory(requirements, crate::Memory::Device, desc.name);
+        let allocation = self
+            .allocate_memory(requirements, crate::Memory::Device, desc.name)
+            .unwrap();
 
         unsafe {
             self.device
@@ -621,15 +1194,22 @@ impl crate::traits::ResourceDevice for super::Context {
                 .unwrap()
         };
 
-        let raw_ty = match desc.ty {
-            crate::AccelerationStructureType::TopLevel => {
-                vk::AccelerationStructureTypeKHR::TOP_LEVEL
-            }
-            crate::AccelerationStructureType::BottomLevel => {
-                vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL
-            }
+        let (raw_ty, create_flags) = match desc.ty {
+            crate::AccelerationStructureType::TopLevel => (
+                vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+                vk::AccelerationStructureCreateFlagsKHR::empty(),
+            ),
+            crate::AccelerationStructureType::TopLevelMotion => (
+                vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+                vk::AccelerationStructureCreateFlagsKHR::MOTION_NV,
+            ),
+            crate::AccelerationStructureType::BottomLevel => (
+                vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+                vk::AccelerationStructureCreateFlagsKHR::empty(),
+            ),
         };
         let vk_info = vk::AccelerationStructureCreateInfoKHR {
+            create_flags,
             ty: raw_ty,
             buffer,
             size: desc.size,
@@ -667,6 +1247,14 @@ impl crate::traits::ResourceDevice for super::Context {
     }
 }
 
+fn map_resource_error(result: vk::Result) -> crate::ResourceError {
+    match result {
+        vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => crate::ResourceError::OutOfDeviceMemory,
+        vk::Result::ERROR_OUT_OF_HOST_MEMORY => crate::ResourceError::OutOfHostMemory,
+        other => panic!("Unexpected resource creation error: {other:?}"),
+    }
+}
+
 fn map_texture_dimension(dimension: crate::TextureDimension) -> vk::ImageType {
     match dimension {
         crate::TextureDimension::D1 => vk::ImageType::TYPE_1D,
@@ -711,10 +1299,13 @@ pub(super) fn map_texture_usage(
     if usage.intersects(crate::TextureUsage::STORAGE) {
         flags |= vk::ImageUsageFlags::STORAGE;
     }
+    if usage.contains(crate::TextureUsage::HOST_WRITE) {
+        flags |= vk::ImageUsageFlags::HOST_TRANSFER_EXT;
+    }
     flags
 }
 
-fn map_filter_mode(mode: crate::FilterMode) -> vk::Filter {
+pub(super) fn map_filter_mode(mode: crate::FilterMode) -> vk::Filter {
     match mode {
         crate::FilterMode::Nearest => vk::Filter::NEAREST,
         crate::FilterMode::Linear => vk::Filter::LINEAR,
@@ -526,6 +526,13 @@ impl super::Context {
             alpha,
             target_size,
         };
+        self.reporter.set_surface(crate::SurfaceReport {
+            width: config.size.width,
+            height: config.size.height,
+            format: format!("{format:?}"),
+            alpha: format!("{alpha:?}"),
+            present_mode: format!("{present_mode:?}"),
+        });
     }
 
     fn xr_recommended_surface_config(
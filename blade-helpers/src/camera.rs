@@ -1,6 +1,8 @@
 use super::ExposeHud;
 
 const MAX_FLY_SPEED: f32 = 1000000.0;
+// Distance at which the orthographic view starts matching the perspective one.
+const ORTHOGRAPHIC_DISTANCE: f32 = 10.0;
 
 pub struct ControlledCamera {
     pub inner: blade_render::Camera,
@@ -27,6 +29,8 @@ impl Default for ControlledCamera {
                 fov_y: 0.0,
                 depth: 0.0,
                 fov: None,
+                lens: blade_render::Lens::default(),
+                projection: blade_render::Projection::default(),
             },
             fly_speed: 0.0,
         }
@@ -116,7 +120,56 @@ impl ExposeHud for ControlledCamera {
             ui.add(egui::DragValue::new(&mut self.inner.rot.v.z));
             ui.add(egui::DragValue::new(&mut self.inner.rot.s));
         });
-        ui.add(egui::Slider::new(&mut self.inner.fov_y, 0.5f32..=2.0f32).text("FOV"));
+        let mut is_orthographic = matches!(
+            self.inner.projection,
+            blade_render::Projection::Orthographic { .. }
+        );
+        if ui.checkbox(&mut is_orthographic, "Orthographic").changed() {
+            self.inner.projection = if is_orthographic {
+                // Match the perspective view at some distance, keeping the aspect ratio
+                let viewport = ui.ctx().viewport_rect();
+                let height = 2.0 * ORTHOGRAPHIC_DISTANCE * (0.5 * self.inner.fov_y).tan();
+                blade_render::Projection::Orthographic {
+                    width: height * viewport.aspect_ratio(),
+                    height,
+                }
+            } else {
+                blade_render::Projection::Perspective
+            };
+        }
+        match self.inner.projection {
+            blade_render::Projection::Orthographic {
+                ref mut width,
+                ref mut height,
+            } => {
+                ui.horizontal(|ui| {
+                    ui.label("Extents:");
+                    ui.add(egui::DragValue::new(width).speed(0.1).range(0.01..=1000.0));
+                    ui.add(egui::DragValue::new(height).speed(0.1).range(0.01..=1000.0));
+                });
+            }
+            _ => {
+                ui.add(egui::Slider::new(&mut self.inner.fov_y, 0.5f32..=2.0f32).text("FOV"));
+            }
+        }
+        let lens = &mut self.inner.lens;
+        ui.add(
+            egui::Slider::new(&mut lens.f_stop, 0.0f32..=22.0f32)
+                .text("F-stop (0 = pinhole)")
+                .logarithmic(true),
+        );
+        if lens.f_stop > 0.0 {
+            ui.add(
+                egui::Slider::new(&mut lens.focus_distance, 0.1f32..=1000.0f32)
+                    .text("Focus distance")
+                    .logarithmic(true),
+            );
+            ui.add(egui::Slider::new(&mut lens.blade_count, 0..=12).text("Aperture blades"));
+            ui.add(
+                egui::Slider::new(&mut lens.blade_rotation, 0.0f32..=std::f32::consts::PI)
+                    .text("Blade rotation"),
+            );
+        }
         ui.add(
             egui::Slider::new(&mut self.fly_speed, 1f32..=MAX_FLY_SPEED)
                 .text("Fly speed")
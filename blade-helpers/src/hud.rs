@@ -1,3 +1,5 @@
+use std::f32::consts::PI;
+
 pub trait ExposeHud {
     fn populate_hud(&mut self, ui: &mut egui::Ui);
 }
@@ -13,6 +15,11 @@ impl ExposeHud for blade_render::RayConfig {
             &mut self.environment_importance_sampling,
             "Env importance sampling",
         );
+        ui.add(egui::Slider::new(&mut self.num_light_samples, 0..=16u32).text("Num light samples"));
+        ui.add(
+            egui::Slider::new(&mut self.num_emissive_samples, 0..=16u32)
+                .text("Num emissive samples"),
+        );
         ui.add(egui::widgets::Slider::new(&mut self.tap_count, 0..=10).text("Tap count"));
         ui.add(egui::widgets::Slider::new(&mut self.tap_radius, 1..=50).text("Tap radius (px)"));
         ui.add(
@@ -31,29 +38,166 @@ impl ExposeHud for blade_render::RayConfig {
         ui.add(
             egui::widgets::Slider::new(&mut self.defensive_mis, 0.0..=1.0).text("Defensive MIS"),
         );
+        ui.add(
+            egui::widgets::Slider::new(&mut self.max_bounces, 0..=blade_render::MAX_BOUNCES)
+                .text("Max bounces"),
+        );
+        ui.add(
+            egui::widgets::Slider::new(&mut self.russian_roulette_start, 0..=self.max_bounces)
+                .text("Russian roulette start"),
+        );
+        ui.add(
+            egui::widgets::Slider::new(&mut self.firefly_clamp, 0.0..=100.0)
+                .text("Firefly clamp")
+                .logarithmic(true),
+        );
+        ui.add(
+            egui::widgets::Slider::new(
+                &mut self.max_samples,
+                1..=blade_render::MAX_ADAPTIVE_SAMPLES,
+            )
+            .text("Max adaptive samples"),
+        );
+        ui.add(
+            egui::widgets::Slider::new(&mut self.variance_threshold, 0.01..=10.0)
+                .text("Variance threshold")
+                .logarithmic(true),
+        );
+        ui.collapsing("Medium", |ui| {
+            let medium = &mut self.medium;
+            ui.add(
+                egui::widgets::Slider::new(
+                    &mut medium.step_count,
+                    0..=blade_render::MAX_MEDIUM_STEPS,
+                )
+                .text("Steps"),
+            );
+            ui.add(
+                egui::widgets::Slider::new(&mut medium.absorption, 0.0..=1.0)
+                    .text("Absorption")
+                    .logarithmic(true),
+            );
+            ui.add(
+                egui::widgets::Slider::new(&mut medium.scattering, 0.0..=1.0)
+                    .text("Scattering")
+                    .logarithmic(true),
+            );
+            ui.horizontal(|ui| {
+                ui.color_edit_button_rgb(&mut medium.color);
+                ui.label("Color");
+            });
+            ui.add(
+                egui::widgets::Slider::new(&mut medium.anisotropy, -0.9..=0.9).text("Anisotropy"),
+            );
+            ui.add(egui::DragValue::new(&mut medium.base_height).prefix("Base height: "));
+            ui.add(
+                egui::widgets::Slider::new(&mut medium.height_falloff, 0.0..=2.0)
+                    .text("Height falloff"),
+            );
+        });
+        ui.collapsing("Environment", |ui| self.environment.populate_hud(ui));
+    }
+}
+
+impl ExposeHud for blade_render::EnvironmentConfig {
+    fn populate_hud(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.yaw, -PI..=PI).text("Yaw"));
+        ui.add(
+            egui::Slider::new(&mut self.intensity, 0.01..=100.0)
+                .text("Intensity")
+                .logarithmic(true),
+        );
+    }
+}
+
+impl ExposeHud for blade_render::SkyModel {
+    fn populate_hud(&mut self, ui: &mut egui::Ui) {
+        let dir = glam::Vec3::from(self.sun_direction).normalize_or(glam::Vec3::Y);
+        let mut elevation = dir.y.asin();
+        let mut azimuth = dir.x.atan2(dir.z);
+        let elevation_changed = ui
+            .add(egui::Slider::new(&mut elevation, -0.2..=0.5 * PI).text("Sun elevation"))
+            .changed();
+        let azimuth_changed = ui
+            .add(egui::Slider::new(&mut azimuth, -PI..=PI).text("Sun azimuth"))
+            .changed();
+        if elevation_changed || azimuth_changed {
+            self.sun_direction = [
+                elevation.cos() * azimuth.sin(),
+                elevation.sin(),
+                elevation.cos() * azimuth.cos(),
+            ]
+            .into();
+        }
+        ui.add(egui::Slider::new(&mut self.turbidity, 2.0..=10.0).text("Turbidity"));
+        ui.horizontal(|ui| {
+            ui.color_edit_button_rgb(&mut self.ground_albedo);
+            ui.label("Ground albedo");
+        });
+        ui.add(
+            egui::Slider::new(&mut self.intensity, 0.001..=10.0)
+                .text("Intensity")
+                .logarithmic(true),
+        );
     }
 }
 
 impl ExposeHud for blade_render::DenoiserConfig {
     fn populate_hud(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Enable");
         ui.add(egui::Slider::new(&mut self.temporal_weight, 0.0..=1.0f32).text("Temporal weight"));
-        ui.add(egui::Slider::new(&mut self.num_passes, 0..=5u32).text("A-trous passes"));
+        ui.add(egui::Slider::new(&mut self.spatial_passes, 0..=5u32).text("A-trous passes"));
+        ui.add(
+            egui::Slider::new(&mut self.history_clamp_sigma, 0.0..=8.0f32)
+                .text("History clamp (sigma)"),
+        );
     }
 }
 
 impl ExposeHud for blade_render::PostProcConfig {
     fn populate_hud(&mut self, ui: &mut egui::Ui) {
+        use strum::IntoEnumIterator as _;
+
+        egui::ComboBox::from_label("Tone map")
+            .selected_text(format!("{:?}", self.tone_map))
+            .show_ui(ui, |ui| {
+                for value in blade_render::ToneMap::iter() {
+                    ui.selectable_value(&mut self.tone_map, value, format!("{value:?}"));
+                }
+            });
+        ui.checkbox(&mut self.auto_exposure, "Auto exposure");
+        let ev_label = if self.auto_exposure {
+            "Compensation EV"
+        } else {
+            "Exposure EV"
+        };
+        ui.add(egui::Slider::new(&mut self.exposure_ev, -10f32..=10f32).text(ev_label));
         ui.add(
-            egui::Slider::new(&mut self.average_luminocity, 0.1f32..=1_000f32)
-                .text("Average luminocity")
+            egui::Slider::new(&mut self.white_point, 0.1f32..=16f32)
+                .text("White point")
                 .logarithmic(true),
         );
+        ui.checkbox(&mut self.encode_srgb, "Encode sRGB");
         ui.add(
-            egui::Slider::new(&mut self.exposure_key_value, 0.01f32..=10f32)
-                .text("Key value")
-                .logarithmic(true),
+            egui::Slider::new(&mut self.upscale_sharpness, 0.0..=1.0f32).text("Upscale sharpness"),
         );
-        ui.add(egui::Slider::new(&mut self.white_level, 0.1f32..=2f32).text("White level"));
+        ui.checkbox(&mut self.bloom.enabled, "Bloom");
+        if self.bloom.enabled {
+            ui.add(
+                egui::Slider::new(&mut self.bloom.intensity, 0.0..=0.5f32).text("Bloom intensity"),
+            );
+            ui.add(egui::Slider::new(&mut self.bloom.radius, 0.0..=1.0f32).text("Bloom radius"));
+        }
+    }
+}
+
+impl ExposeHud for blade_render::AutoExposureConfig {
+    fn populate_hud(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.low_percentile, 0.0..=1.0f32).text("Low percentile"));
+        ui.add(egui::Slider::new(&mut self.high_percentile, 0.0..=1.0f32).text("High percentile"));
+        ui.add(egui::Slider::new(&mut self.speed, 0.1..=20.0f32).text("Speed EV/s"));
+        ui.add(egui::Slider::new(&mut self.min_ev, -20.0..=20.0f32).text("Min EV"));
+        ui.add(egui::Slider::new(&mut self.max_ev, -20.0..=20.0f32).text("Max EV"));
     }
 }
 
@@ -115,6 +259,28 @@ impl ExposeHud for blade_render::RasterConfig {
             };
         }
         ui.label("Ambient color");
+
+        ui.add(
+            egui::Slider::new(
+                &mut self.shadows.cascade_count,
+                0..=blade_render::raster::MAX_SHADOW_CASCADES,
+            )
+            .text("Shadow cascades"),
+        );
+        if self.shadows.cascade_count != 0 {
+            ui.add(
+                egui::Slider::new(&mut self.shadows.resolution, 256..=4096)
+                    .logarithmic(true)
+                    .text("Shadow resolution"),
+            );
+            ui.add(
+                egui::Slider::new(&mut self.shadows.max_distance, 1.0..=1000.0)
+                    .logarithmic(true)
+                    .text("Shadow distance"),
+            );
+            ui.checkbox(&mut self.shadows.show_cascades, "Show cascades");
+        }
+        ui.collapsing("Environment", |ui| self.environment.populate_hud(ui));
     }
 }
 
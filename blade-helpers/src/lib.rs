@@ -11,6 +11,8 @@ pub fn default_ray_config() -> blade_render::RayConfig {
     blade_render::RayConfig {
         num_environment_samples: 1,
         environment_importance_sampling: true,
+        num_light_samples: 1,
+        num_emissive_samples: 1,
         tap_count: 2,
         tap_radius: 20,
         tap_confidence_near: 15,
@@ -18,5 +20,12 @@ pub fn default_ray_config() -> blade_render::RayConfig {
         t_start: 0.01,
         pairwise_mis: true,
         defensive_mis: 0.1,
+        max_bounces: 0,
+        firefly_clamp: 10.0,
+        russian_roulette_start: 2,
+        max_samples: 1,
+        variance_threshold: 0.5,
+        medium: blade_render::Medium::default(),
+        environment: blade_render::EnvironmentConfig::default(),
     }
 }
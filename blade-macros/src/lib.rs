@@ -15,7 +15,18 @@ use proc_macro::TokenStream;
 ///   sm: blade_graphics::Sampler,
 /// }
 /// ```
-#[proc_macro_derive(ShaderData)]
+///
+/// Buffer fields marked with `#[shader_data(dynamic)]` are bound as
+/// `ShaderBinding::DynamicBuffer`, with the offsets set per draw:
+///
+/// ```rust
+/// #[derive(blade_macros::ShaderData)]
+/// struct Test {
+///   #[shader_data(dynamic)]
+///   instance: blade_graphics::BufferPiece,
+/// }
+/// ```
+#[proc_macro_derive(ShaderData, attributes(shader_data))]
 pub fn shader_data_derive(input: TokenStream) -> TokenStream {
     let stream = match shader_data::generate(input) {
         Ok(tokens) => tokens,
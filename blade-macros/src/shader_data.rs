@@ -19,8 +19,34 @@ pub fn generate(input_stream: TokenStream) -> syn::Result<proc_macro2::TokenStre
         let index = index_usize as u32;
         let name = field.ident.as_ref().unwrap();
         let ty = &field.ty;
-        bindings.push(quote! {
-            (stringify!(#name), <#ty as blade_graphics::derive::HasShaderBinding>::TYPE)
+        let mut is_dynamic = false;
+        for attr in field.attrs.iter() {
+            if attr.path().is_ident("shader_data") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("dynamic") {
+                        is_dynamic = true;
+                        Ok(())
+                    } else {
+                        Err(meta.error("Unsupported shader data attribute"))
+                    }
+                })?;
+            }
+        }
+        bindings.push(if is_dynamic {
+            quote! {
+                (stringify!(#name), {
+                    assert_eq!(
+                        <#ty as blade_graphics::derive::HasShaderBinding>::TYPE,
+                        blade_graphics::ShaderBinding::Buffer,
+                        "Only buffers can be dynamic",
+                    );
+                    blade_graphics::ShaderBinding::DynamicBuffer
+                })
+            }
+        } else {
+            quote! {
+                (stringify!(#name), <#ty as blade_graphics::derive::HasShaderBinding>::TYPE)
+            }
         });
         assignments.push(quote! {
             self.#name.bind_to(&mut ctx, #index);
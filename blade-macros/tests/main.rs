@@ -12,6 +12,28 @@ struct ShaderParams {
     sprite_sampler: blade_graphics::Sampler,
 }
 
+#[derive(blade_macros::ShaderData)]
+#[allow(dead_code)]
+struct DynamicParams {
+    #[shader_data(dynamic)]
+    instance: blade_graphics::BufferPiece,
+    output: blade_graphics::BufferPiece,
+}
+
+#[test]
+fn test_dynamic_buffer_layout() {
+    use blade_graphics::{ShaderBinding, ShaderData as _};
+
+    let layout = DynamicParams::layout();
+    assert_eq!(
+        layout.bindings,
+        [
+            ("instance", ShaderBinding::DynamicBuffer),
+            ("output", ShaderBinding::Buffer),
+        ]
+    );
+}
+
 #[derive(blade_macros::Flat, PartialEq, Debug)]
 struct FlatData<'a> {
     array: [u32; 2],
@@ -109,6 +109,7 @@ impl ParticlePipeline {
                 format: desc.draw_format,
                 blend: Some(gpu::BlendState::ALPHA_BLENDING),
                 write_mask: gpu::ColorWrites::default(),
+                logic_op: None,
             }],
             depth_stencil: desc.depth_format.map(|format| gpu::DepthStencilState {
                 format,
@@ -121,6 +122,7 @@ impl ParticlePipeline {
                 sample_count: desc.sample_count,
                 ..Default::default()
             },
+            multiview: None,
         });
 
         Self {
@@ -13,6 +13,8 @@ struct Params {
     temporal_weight: f32,
     iteration: u32,
     use_motion_vectors: u32,
+    // standard deviations of the history around the current frame, 0 to disable
+    history_clamp_sigma: f32,
 }
 
 var<uniform> camera: CameraParams;
@@ -21,13 +23,28 @@ var<uniform> params: Params;
 var t_depth: texture_2d<f32>;
 var t_prev_depth: texture_2d<f32>;
 var t_flat_normal: texture_2d<f32>;
+var t_basis: texture_2d<f32>;
 var t_prev_flat_normal: texture_2d<f32>;
 var t_motion: texture_2d<f32>;
 var input: texture_2d<f32>;
 var output: texture_storage_2d<rgba16float, read_write>;
+var t_prev_history: texture_2d<f32>;
+var out_history: texture_storage_2d<r32float, write>;
+
+struct TilePriorityParams {
+    // relative variance of a tile per extra sample
+    variance_threshold: f32,
+    max_samples: u32,
+    pad: vec2<u32>,
+}
+var<uniform> tile_params: TilePriorityParams;
+var out_tile_samples: texture_storage_2d<r32uint, write>;
 
 const LUMA: vec3<f32> = vec3<f32>(0.2126, 0.7152, 0.0722);
 const MIN_WEIGHT: f32 = 0.01;
+const MAX_HISTORY: f32 = 256.0;
+// Relative difference of the depths, above which the history is rejected.
+const REPROJECTION_DEPTH_TOLERANCE: f32 = 0.1;
 
 fn read_surface(pixel: vec2<i32>) -> Surface {
     var surface = Surface();
@@ -42,15 +59,41 @@ fn read_prev_surface(pixel: vec2<i32>) -> Surface {
     return surface;
 }
 
+fn read_shading_normal(pixel: vec2<i32>) -> vec3<f32> {
+    let basis = normalize(textureLoad(t_basis, pixel, 0));
+    return qrot(basis, vec3<f32>(0.0, 0.0, 1.0));
+}
+
 fn get_prev_pixel(pixel: vec2<i32>, pos_world: vec3<f32>) -> vec2<f32> {
     if (USE_MOTION_VECTORS && params.use_motion_vectors != 0u) {
-        let motion = textureLoad(t_motion, pixel, 0).xy / MOTION_SCALE;
+        let motion = textureLoad(t_motion, pixel, 0).xy;
         return vec2<f32>(pixel) + 0.5 + motion;
     } else {
         return get_projected_pixel_float(prev_camera, pos_world);
     }
 }
 
+// Depth of the surface point, as seen by the previous camera.
+fn get_prev_depth(pixel: vec2<i32>, pos_world: vec3<f32>) -> f32 {
+    if (USE_MOTION_VECTORS && params.use_motion_vectors != 0u) {
+        return textureLoad(t_motion, pixel, 0).z;
+    } else {
+        return get_camera_depth(prev_camera, pos_world);
+    }
+}
+
+// Rescale the history, so that its luminance is within the configured number
+// of the standard deviations, estimated from the history moments, around the current frame.
+// Discards the stale history, which is the source of ghosting.
+fn clamp_history(prev_ilm: vec4<f32>, cur_luminocity: f32) -> vec4<f32> {
+    let prev_luminocity = dot(prev_ilm.xyz, LUMA);
+    let std_deviation = sqrt(max(0.0, prev_ilm.w - prev_luminocity * prev_luminocity));
+    let margin = params.history_clamp_sigma * std_deviation + EPSILON;
+    let clamped = clamp(prev_luminocity, cur_luminocity - margin, cur_luminocity + margin);
+    let scale = select(1.0, clamped / prev_luminocity, prev_luminocity > 0.0);
+    return prev_ilm * vec4<f32>(vec3<f32>(scale), scale * scale);
+}
+
 @compute @workgroup_size(8, 8)
 fn temporal_accum(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let pixel = vec2<i32>(global_id.xy);
@@ -59,9 +102,11 @@ fn temporal_accum(@builtin(global_invocation_id) global_id: vec3<u32>) {
     }
 
     let surface = read_surface(pixel);
-    let pos_world = camera.position + surface.depth * get_ray_direction(camera, pixel);
+    let ray = get_camera_ray(camera, pixel);
+    let pos_world = ray.origin + surface.depth * ray.dir;
     // considering all samples in 2x2 quad, to help with edges
     var center_pixel = get_prev_pixel(pixel, pos_world);
+    let prev_depth = get_prev_depth(pixel, pos_world);
     var prev_pixels = array<vec2<i32>, 4>(
         vec2<i32>(vec2<f32>(center_pixel.x - 0.5, center_pixel.y - 0.5)),
         vec2<i32>(vec2<f32>(center_pixel.x + 0.5, center_pixel.y - 0.5)),
@@ -79,6 +124,7 @@ fn temporal_accum(@builtin(global_invocation_id) global_id: vec3<u32>) {
 
     var sum_weight = 0.0;
     var sum_ilm = vec4<f32>(0.0);
+    var sum_history = 0.0;
     if (params.temporal_weight != 1.0) {
         //TODO: optimize depth load with a gather operation
         for (var i = 0; i < 4; i += 1) {
@@ -88,8 +134,8 @@ fn temporal_accum(@builtin(global_invocation_id) global_id: vec3<u32>) {
                 if (compare_flat_normals(surface.flat_normal, prev_surface.flat_normal) < 0.5) {
                     continue;
                 }
-                let projected_distance = length(pos_world - prev_camera.position);
-                if (compare_depths(prev_surface.depth, projected_distance) < 0.5) {
+                // a different surface was visible there, such as before a moving object
+                if (abs(prev_surface.depth - prev_depth) > REPROJECTION_DEPTH_TOLERANCE * prev_depth) {
                     continue;
                 }
                 let w = prev_weights[i];
@@ -97,6 +143,7 @@ fn temporal_accum(@builtin(global_invocation_id) global_id: vec3<u32>) {
                 let illumination = w * textureLoad(input, prev_pixel, 0).xyz;
                 let luminocity = dot(illumination, LUMA);
                 sum_ilm += vec4<f32>(illumination, luminocity * luminocity);
+                sum_history += w * textureLoad(t_prev_history, prev_pixel, 0).x;
             }
         }
     }
@@ -104,12 +151,18 @@ fn temporal_accum(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let cur_illumination = textureLoad(output, pixel).xyz;
     let cur_luminocity = dot(cur_illumination, LUMA);
     var mixed_ilm = vec4<f32>(cur_illumination, cur_luminocity * cur_luminocity);
+    var history = 1.0;
     if (sum_weight > MIN_WEIGHT) {
-        let prev_ilm = sum_ilm / vec4(vec3<f32>(sum_weight), max(0.001, sum_weight*sum_weight));
+        var prev_ilm = sum_ilm / vec4(vec3<f32>(sum_weight), max(0.001, sum_weight*sum_weight));
+        if (params.history_clamp_sigma > 0.0) {
+            prev_ilm = clamp_history(prev_ilm, cur_luminocity);
+        }
         mixed_ilm = mix(mixed_ilm, prev_ilm, sum_weight * (1.0 - params.temporal_weight));
+        history = min(sum_history / sum_weight + 1.0, MAX_HISTORY);
     }
     //Note: could also use HW blending for this
     textureStore(output, pixel, mixed_ilm);
+    textureStore(out_history, pixel, vec4<f32>(history));
 }
 
 const GAUSSIAN_WEIGHTS = vec2<f32>(0.44198, 0.27901);
@@ -135,6 +188,7 @@ fn atrous3x3(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let center_luma = dot(center_ilm.xyz, LUMA);
     let variance = sqrt(center_ilm.w);
     let center_suf = read_surface(center);
+    let center_normal = read_shading_normal(center);
     var sum_weight = GAUSSIAN_WEIGHTS[0] * GAUSSIAN_WEIGHTS[0];
     var sum_ilm = w4(sum_weight) * center_ilm;
 
@@ -150,6 +204,8 @@ fn atrous3x3(@builtin(global_invocation_id) global_id: vec3<u32>) {
             var weight = GAUSSIAN_WEIGHTS[abs(xx)] * GAUSSIAN_WEIGHTS[abs(yy)];
             //TODO: make it stricter on higher iterations
             weight *= compare_flat_normals(surface.flat_normal, center_suf.flat_normal);
+            // respect the details brought by the normal maps
+            weight *= compare_flat_normals(read_shading_normal(p), center_normal);
             //Note: should we use a projected depth instead of the surface one?
             weight *= compare_depths(surface.depth, center_suf.depth);
             let other_ilm = textureLoad(input, p, 0);
@@ -162,3 +218,41 @@ fn atrous3x3(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let filtered_ilm = select(center_ilm, sum_ilm / w4(sum_weight), sum_weight > MIN_WEIGHT);
     textureStore(output, global_id.xy, filtered_ilm);
 }
+
+// Has to match the workgroup size
+const TILE_PIXELS: u32 = 64u;
+var<workgroup> tile_variance: array<f32, TILE_PIXELS>;
+
+// Estimate the number of samples per pixel for each tile, used by the next frame.
+// Every workgroup covers a tile, averaging the relative variance of its pixels.
+@compute @workgroup_size(8, 8)
+fn tile_priority(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(workgroup_id) group_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let pixel = vec2<i32>(global_id.xy);
+    var relative_variance = 0.0;
+    if (all(pixel < params.extent)) {
+        let ilm = textureLoad(input, pixel, 0);
+        let luma = dot(ilm.xyz, LUMA);
+        let variance = max(0.0, ilm.w - luma * luma);
+        relative_variance = variance / max(luma * luma, EPSILON);
+    }
+    tile_variance[local_index] = relative_variance;
+    workgroupBarrier();
+
+    for (var stride = TILE_PIXELS / 2u; stride > 0u; stride >>= 1u) {
+        if (local_index < stride) {
+            tile_variance[local_index] += tile_variance[local_index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if (local_index == 0u) {
+        let mean_variance = tile_variance[0] / f32(TILE_PIXELS);
+        let extra = min(mean_variance / tile_params.variance_threshold, f32(tile_params.max_samples));
+        let samples = clamp(u32(extra) + 1u, 1u, tile_params.max_samples);
+        textureStore(out_tile_samples, group_id.xy, vec4<u32>(samples, 0u, 0u, 0u));
+    }
+}
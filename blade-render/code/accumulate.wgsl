@@ -0,0 +1,35 @@
+// Progressive accumulation of the final radiance, for the offline rendering.
+
+struct AccumulateParams {
+    // number of frames accumulated so far
+    frame_count: u32,
+}
+
+var<uniform> params: AccumulateParams;
+var t_albedo: texture_2d<f32>;
+var light_diffuse: texture_2d<f32>;
+var t_emission: texture_2d<f32>;
+// In-scattered radiance of the medium, and the transmittance to the surface
+var t_medium: texture_2d<f32>;
+var output: texture_storage_2d<rgba32float, read_write>;
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (any(global_id.xy >= textureDimensions(output))) {
+        return;
+    }
+
+    let pixel = vec2<i32>(global_id.xy);
+    let albedo = textureLoad(t_albedo, pixel, 0).xyz;
+    let illumination = textureLoad(light_diffuse, pixel, 0).xyz;
+    let emission = textureLoad(t_emission, pixel, 0).xyz;
+    let medium = textureLoad(t_medium, pixel, 0);
+    let radiance = medium.w * (albedo * illumination + emission) + medium.xyz;
+
+    var accumulated = radiance;
+    if (params.frame_count != 0u) {
+        let count = f32(params.frame_count);
+        accumulated = (textureLoad(output, pixel).xyz * count + radiance) / (count + 1.0);
+    }
+    textureStore(output, pixel, vec4<f32>(accumulated, 1.0));
+}
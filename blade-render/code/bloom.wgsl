@@ -0,0 +1,144 @@
+// Threshold-free bloom, following "Next Generation Post Processing in Call of Duty: Advanced Warfare"
+// http://www.iryoku.com/next-generation-post-processing-in-call-of-duty-advanced-warfare
+
+const LUMA: vec3<f32> = vec3<f32>(0.2126, 0.7152, 0.0722);
+// Largest value representable by the half-float levels.
+const MAX_RADIANCE: f32 = 65000.0;
+
+struct BloomParams {
+    // fraction of the wider levels blended into each finer one
+    scatter: f32,
+    use_accumulation: u32,
+    pad: vec2<u32>,
+}
+
+var<uniform> params: BloomParams;
+var t_albedo: texture_2d<f32>;
+var light_diffuse: texture_2d<f32>;
+var t_emission: texture_2d<f32>;
+// In-scattered radiance of the medium, and the transmittance to the surface
+var t_medium: texture_2d<f32>;
+var t_accumulation: texture_2d<f32>;
+var input: texture_2d<f32>;
+var samp: sampler;
+var output: texture_storage_2d<rgba16float, write>;
+var blended: texture_storage_2d<rgba16float, read_write>;
+
+fn load_radiance(pixel: vec2<i32>) -> vec3<f32> {
+    let tc = clamp(pixel, vec2<i32>(0), vec2<i32>(textureDimensions(light_diffuse)) - 1);
+    if (params.use_accumulation != 0u) {
+        return textureLoad(t_accumulation, tc, 0).xyz;
+    }
+    let albedo = textureLoad(t_albedo, tc, 0).xyz;
+    let illumination = textureLoad(light_diffuse, tc, 0).xyz;
+    let emission = textureLoad(t_emission, tc, 0).xyz;
+    let medium = textureLoad(t_medium, tc, 0);
+    let radiance = medium.w * (albedo * illumination + emission) + medium.xyz;
+    // Keep NaNs and infinities away from the chain, which would spread them over the frame
+    return select(vec3<f32>(0.0), clamp(radiance, vec3<f32>(0.0), vec3<f32>(MAX_RADIANCE)), radiance == radiance);
+}
+
+fn box_average(texels: ptr<function, array<vec3<f32>, 36>>, x: i32, y: i32) -> vec3<f32> {
+    let i = y * 6 + x;
+    return 0.25 * ((*texels)[i] + (*texels)[i + 1] + (*texels)[i + 6] + (*texels)[i + 7]);
+}
+
+fn karis_weight(color: vec3<f32>) -> f32 {
+    return 1.0 / (1.0 + dot(color, LUMA));
+}
+
+// The first downsample, from the radiance at the render resolution.
+// Each of the 5 overlapping boxes is weighted by its inverse luminance,
+// so that a single bright pixel doesn't flicker through the chain.
+@compute @workgroup_size(8, 8)
+fn downsample_first(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if (any(global_id.xy >= textureDimensions(output))) {
+        return;
+    }
+    // The 6x6 source texels covered by the filter, centered at this pixel
+    let base = 2 * vec2<i32>(global_id.xy) - 2;
+    var texels: array<vec3<f32>, 36>;
+    for (var y = 0; y < 6; y += 1) {
+        for (var x = 0; x < 6; x += 1) {
+            texels[y * 6 + x] = load_radiance(base + vec2<i32>(x, y));
+        }
+    }
+    // Boxes of 2x2 texels, taking the place of the bilinear taps
+    let a = box_average(&texels, 0, 0);
+    let b = box_average(&texels, 2, 0);
+    let c = box_average(&texels, 4, 0);
+    let d = box_average(&texels, 1, 1);
+    let e = box_average(&texels, 3, 1);
+    let f = box_average(&texels, 0, 2);
+    let g = box_average(&texels, 2, 2);
+    let h = box_average(&texels, 4, 2);
+    let i = box_average(&texels, 1, 3);
+    let j = box_average(&texels, 3, 3);
+    let k = box_average(&texels, 0, 4);
+    let l = box_average(&texels, 2, 4);
+    let m = box_average(&texels, 4, 4);
+
+    let groups = array<vec3<f32>, 5>(
+        0.25 * (d + e + i + j),
+        0.25 * (a + b + f + g),
+        0.25 * (b + c + g + h),
+        0.25 * (f + g + k + l),
+        0.25 * (g + h + l + m),
+    );
+    let group_weights = array<f32, 5>(0.5, 0.125, 0.125, 0.125, 0.125);
+    var sum = vec3<f32>(0.0);
+    var weight_sum = 0.0;
+    for (var n = 0; n < 5; n += 1) {
+        let weight = group_weights[n] * karis_weight(groups[n]);
+        sum += weight * groups[n];
+        weight_sum += weight;
+    }
+    textureStore(output, global_id.xy, vec4<f32>(sum / weight_sum, 1.0));
+}
+
+// 13-tap downsample of the previous level, relying on the bilinear filtering.
+@compute @workgroup_size(8, 8)
+fn downsample(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let size = textureDimensions(output);
+    if (any(global_id.xy >= size)) {
+        return;
+    }
+    let uv = (vec2<f32>(global_id.xy) + 0.5) / vec2<f32>(size);
+    let texel = 1.0 / vec2<f32>(textureDimensions(input, 0));
+    let a = textureSampleLevel(input, samp, uv + texel * vec2<f32>(-2.0, -2.0), 0.0).xyz;
+    let b = textureSampleLevel(input, samp, uv + texel * vec2<f32>(0.0, -2.0), 0.0).xyz;
+    let c = textureSampleLevel(input, samp, uv + texel * vec2<f32>(2.0, -2.0), 0.0).xyz;
+    let d = textureSampleLevel(input, samp, uv + texel * vec2<f32>(-1.0, -1.0), 0.0).xyz;
+    let e = textureSampleLevel(input, samp, uv + texel * vec2<f32>(1.0, -1.0), 0.0).xyz;
+    let f = textureSampleLevel(input, samp, uv + texel * vec2<f32>(-2.0, 0.0), 0.0).xyz;
+    let g = textureSampleLevel(input, samp, uv, 0.0).xyz;
+    let h = textureSampleLevel(input, samp, uv + texel * vec2<f32>(2.0, 0.0), 0.0).xyz;
+    let i = textureSampleLevel(input, samp, uv + texel * vec2<f32>(-1.0, 1.0), 0.0).xyz;
+    let j = textureSampleLevel(input, samp, uv + texel * vec2<f32>(1.0, 1.0), 0.0).xyz;
+    let k = textureSampleLevel(input, samp, uv + texel * vec2<f32>(-2.0, 2.0), 0.0).xyz;
+    let l = textureSampleLevel(input, samp, uv + texel * vec2<f32>(0.0, 2.0), 0.0).xyz;
+    let m = textureSampleLevel(input, samp, uv + texel * vec2<f32>(2.0, 2.0), 0.0).xyz;
+
+    let color = 0.125 * (d + e + i + j) + 0.0625 * (b + f + h + l) + 0.03125 * (a + c + k + m) + 0.125 * g;
+    textureStore(output, global_id.xy, vec4<f32>(color, 1.0));
+}
+
+// Blend the 3x3 tent-filtered coarser level into this one.
+@compute @workgroup_size(8, 8)
+fn upsample(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let size = textureDimensions(blended);
+    if (any(global_id.xy >= size)) {
+        return;
+    }
+    let uv = (vec2<f32>(global_id.xy) + 0.5) / vec2<f32>(size);
+    let texel = 1.0 / vec2<f32>(textureDimensions(input, 0));
+    var wide = vec3<f32>(0.0);
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let weight = f32((2 - abs(x)) * (2 - abs(y))) / 16.0;
+            wide += weight * textureSampleLevel(input, samp, uv + texel * vec2<f32>(f32(x), f32(y)), 0.0).xyz;
+        }
+    }
+    let narrow = textureLoad(blended, global_id.xy).xyz;
+    textureStore(blended, global_id.xy, vec4<f32>(mix(narrow, wide, params.scatter), 1.0));
+}
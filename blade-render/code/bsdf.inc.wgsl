@@ -0,0 +1,108 @@
+// Layered BSDF of the glTF materials: a diffuse base with thin-walled
+// transmission, the sheen on top of it, and the clear coat on top of all.
+// The BSDF is demodulated, i.e. divided by the base color, which is
+// applied at the post-processing. The lobes that aren't tinted by
+// the base color are divided by it as well.
+
+const MIN_DEMODULATION: f32 = 0.01;
+const MIN_ROUGHNESS: f32 = 0.05;
+// Clear coat has a fixed IOR of 1.5
+const CLEARCOAT_F0: f32 = 0.04;
+
+// Reflectance at the normal incidence.
+fn ior_to_reflectance(ior: f32) -> f32 {
+    let r = (ior - 1.0) / (ior + 1.0);
+    return r * r;
+}
+
+fn fresnel_schlick(f0: f32, cos_theta: f32) -> f32 {
+    return f0 + (1.0 - f0) * pow(1.0 - saturate(cos_theta), 5.0);
+}
+
+fn ggx_distribution(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// Height-correlated Smith visibility, including the denominator of the microfacet BRDF.
+fn ggx_visibility(n_dot_l: f32, n_dot_v: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let gv = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - a2) + a2);
+    let gl = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - a2) + a2);
+    return 0.5 / max(gv + gl, 1e-6);
+}
+
+// See "Production Friendly Microfacet Sheen BRDF" by Estevez and Kulla
+fn charlie_distribution(n_dot_h: f32, roughness: f32) -> f32 {
+    let r = max(roughness, MIN_ROUGHNESS);
+    let inv_alpha = 1.0 / (r * r);
+    let sin2 = 1.0 - n_dot_h * n_dot_h;
+    return (2.0 + inv_alpha) * pow(sin2, 0.5 * inv_alpha) / (2.0 * PI);
+}
+
+// See "Crafting a Next-Gen Material Pipeline for The Order: 1886" by Neubelt and Pettineo
+fn sheen_visibility(n_dot_l: f32, n_dot_v: f32) -> f32 {
+    return 1.0 / (4.0 * (n_dot_l + n_dot_v - n_dot_l * n_dot_v));
+}
+
+fn clearcoat_alpha(material: Material) -> f32 {
+    let r = max(material.clearcoat_roughness, MIN_ROUGHNESS);
+    return r * r;
+}
+
+// Evaluate the demodulated BSDF, multiplied by the cosine term,
+// for the light coming from the given direction.
+fn evaluate_bsdf(surface: Surface, dir: vec3<f32>) -> vec3<f32> {
+    let m = surface.material;
+    let inv_basis = qinv(surface.basis);
+    let l = qrot(inv_basis, dir);
+    let v = qrot(inv_basis, surface.view);
+    if (l.z <= 0.0) {
+        // light from behind passes through, scattered by the thin wall
+        let transmitted = m.transmission * (1.0 - ior_to_reflectance(m.ior));
+        return vec3<f32>(transmitted * -l.z / PI);
+    }
+
+    let n_dot_l = l.z;
+    let n_dot_v = max(v.z, 1e-4);
+    let h = normalize(l + v);
+    let n_dot_h = max(h.z, 0.0);
+    let inv_base_color = 1.0 / max(m.base_color, vec3<f32>(MIN_DEMODULATION));
+    var color = vec3<f32>((1.0 - m.transmission) / PI);
+    if (any(m.sheen_color > vec3<f32>(0.0))) {
+        let sheen = charlie_distribution(n_dot_h, m.sheen_roughness) * sheen_visibility(n_dot_l, n_dot_v);
+        color += sheen * m.sheen_color * inv_base_color;
+    }
+    color *= n_dot_l;
+    if (m.clearcoat > 0.0) {
+        let alpha = clearcoat_alpha(m);
+        let fresnel = m.clearcoat * fresnel_schlick(CLEARCOAT_F0, dot(v, h));
+        let specular = ggx_distribution(n_dot_h, alpha) * ggx_visibility(n_dot_l, n_dot_v, alpha);
+        color = (1.0 - fresnel) * color + fresnel * specular * n_dot_l * inv_base_color;
+    }
+    return color;
+}
+
+// Sample a reflected direction from the distribution of the clear coat normals.
+fn sample_clearcoat(surface: Surface, random: vec2<f32>) -> vec3<f32> {
+    let alpha = clearcoat_alpha(surface.material);
+    let a2 = alpha * alpha;
+    let cos2 = (1.0 - random.x) / (1.0 + (a2 - 1.0) * random.x);
+    let sin_theta = sqrt(max(0.0, 1.0 - cos2));
+    let phi = 2.0 * PI * random.y;
+    let h_local = vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), sqrt(cos2));
+    let h = qrot(surface.basis, h_local);
+    return reflect(-surface.view, h);
+}
+
+// Probability density of `sample_clearcoat` returning the direction.
+fn compute_clearcoat_pdf(surface: Surface, dir: vec3<f32>) -> f32 {
+    let h = normalize(dir + surface.view);
+    let n_dot_h = qrot(qinv(surface.basis), h).z;
+    let v_dot_h = dot(surface.view, h);
+    if (n_dot_h <= 0.0 || v_dot_h <= 0.0) {
+        return 0.0;
+    }
+    return ggx_distribution(n_dot_h, clearcoat_alpha(surface.material)) * n_dot_h / (4.0 * v_dot_h);
+}
@@ -2,18 +2,97 @@ struct CameraParams {
     position: vec3<f32>,
     depth: f32,
     orientation: vec4<f32>,
-    fov: vec2<f32>,
     target_size: vec2<u32>,
+    // Rays for the view space pixel coordinates from -1 to 1,
+    // starting at Z = 0 and having the directions scaled to Z = -1.
+    ray_origin_scale: vec2<f32>,
+    ray_origin_offset: vec2<f32>,
+    ray_dir_scale: vec2<f32>,
+    ray_dir_offset: vec2<f32>,
+    // zero radius makes a pinhole
+    lens_radius: f32,
+    focus_distance: f32,
+    // less than 3 blades make a round aperture
+    aperture_blades: u32,
+    aperture_rotation: f32,
+    lens_seed: u32,
+}
+
+struct CameraRay {
+    origin: vec3<f32>,
+    dir: vec3<f32>,
 }
 
 const VFLIP: vec2<f32> = vec2<f32>(1.0, -1.0);
 
-fn get_ray_direction(cp: CameraParams, pixel: vec2<i32>) -> vec3<f32> {
+// Ray through the pixel in the view space, with the direction scaled to Z = -1.
+fn get_local_ray(cp: CameraParams, pixel: vec2<i32>) -> CameraRay {
     let half_size = 0.5 * vec2<f32>(cp.target_size);
-    let ndc = (vec2<f32>(pixel) + vec2<f32>(0.5) - half_size) / half_size;
     // Right-handed coordinate system with X=right, Y=up, and Z=towards the camera
-    let local_dir = vec3<f32>(VFLIP * ndc * tan(0.5 * cp.fov), -1.0);
-    return normalize(qrot(cp.orientation, local_dir));
+    let ndc = VFLIP * (vec2<f32>(pixel) + vec2<f32>(0.5) - half_size) / half_size;
+    let origin = vec3<f32>(ndc * cp.ray_origin_scale + cp.ray_origin_offset, 0.0);
+    let dir = vec3<f32>(ndc * cp.ray_dir_scale + cp.ray_dir_offset, -1.0);
+    return CameraRay(origin, dir);
+}
+
+// Ray through the pixel center, ignoring the lens.
+fn get_projection_ray(cp: CameraParams, pixel: vec2<i32>) -> CameraRay {
+    let local = get_local_ray(cp, pixel);
+    let origin = cp.position + qrot(cp.orientation, local.origin);
+    return CameraRay(origin, normalize(qrot(cp.orientation, local.dir)));
+}
+
+fn hash_lens(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Uniformly sample the aperture of unit radius.
+fn sample_aperture(cp: CameraParams, random: vec2<f32>) -> vec2<f32> {
+    let full_turn = 6.2831853;
+    if (cp.aperture_blades < 3u) {
+        let angle = full_turn * random.y;
+        return sqrt(random.x) * vec2<f32>(cos(angle), sin(angle));
+    }
+    // The polygon is a fan of triangles, one per blade.
+    let blades = f32(cp.aperture_blades);
+    let scaled = random.x * blades;
+    let angle = cp.aperture_rotation + floor(scaled) * full_turn / blades;
+    let a = vec2<f32>(cos(angle), sin(angle));
+    let b = vec2<f32>(cos(angle + full_turn / blades), sin(angle + full_turn / blades));
+    let t = fract(scaled);
+    return sqrt(random.y) * mix(a, b, t);
+}
+
+// Primary ray through the pixel, starting at a point on the lens
+// that is chosen per pixel and per frame.
+fn get_camera_ray(cp: CameraParams, pixel: vec2<i32>) -> CameraRay {
+    if (cp.lens_radius <= 0.0) {
+        return get_projection_ray(cp, pixel);
+    }
+    let local = get_local_ray(cp, pixel);
+    let seed = hash_lens(hash_lens(u32(pixel.x) + hash_lens(u32(pixel.y))) + hash_lens(cp.lens_seed));
+    let random = vec2<f32>(f32(seed & 0xFFFFu), f32(seed >> 16u)) / 65536.0;
+    let lens_origin = local.origin + vec3<f32>(cp.lens_radius * sample_aperture(cp, random), 0.0);
+    // All the rays through the pixel converge on the plane in focus.
+    let focus_point = local.origin + cp.focus_distance * local.dir;
+    let origin = cp.position + qrot(cp.orientation, lens_origin);
+    return CameraRay(origin, normalize(qrot(cp.orientation, focus_point - lens_origin)));
+}
+
+// Coordinates of a view space point from -1 to 1, inverting `get_local_ray`.
+fn get_local_ndc(cp: CameraParams, local: vec3<f32>) -> vec2<f32> {
+    let scale = cp.ray_origin_scale - local.z * cp.ray_dir_scale;
+    return (local.xy - cp.ray_origin_offset + local.z * cp.ray_dir_offset) / scale;
+}
+
+// Distance from the ray origin to the point, matching the depth of the G-buffer.
+fn get_camera_depth(cp: CameraParams, point: vec3<f32>) -> f32 {
+    let local = qrot(qinv(cp.orientation), point - cp.position);
+    let ndc = get_local_ndc(cp, local);
+    let origin = vec3<f32>(ndc * cp.ray_origin_scale + cp.ray_origin_offset, 0.0);
+    return length(local - origin);
 }
 
 fn get_projected_pixel_float(cp: CameraParams, point: vec3<f32>) -> vec2<f32> {
@@ -21,7 +100,7 @@ fn get_projected_pixel_float(cp: CameraParams, point: vec3<f32>) -> vec2<f32> {
     if local_dir.z >= 0.0 {
         return vec2<f32>(-1.0);
     }
-    let ndc = local_dir.xy / (-local_dir.z * tan(0.5 * cp.fov));
+    let ndc = get_local_ndc(cp, local_dir);
     let half_size = 0.5 * vec2<f32>(cp.target_size);
     return (VFLIP * ndc + vec2<f32>(1.0)) * half_size;
 }
@@ -9,6 +9,8 @@ struct DebugVarying {
     @builtin(position) pos: vec4<f32>,
     @location(0) color: vec4<f32>,
     @location(1) dir: vec3<f32>,
+    // the target may be upscaled from the depth
+    @location(2) @interpolate(linear) ndc: vec2<f32>,
 }
 
 @vertex
@@ -21,12 +23,13 @@ fn debug_vs(@builtin(vertex_index) vertex_id: u32, @builtin(instance_index) inst
 
     let world_dir = point.pos - camera.position;
     let local_dir = qrot(qinv(camera.orientation), world_dir);
-    let ndc = local_dir.xy / tan(0.5 * camera.fov);
+    let ndc = get_local_ndc(camera, local_dir);
 
     var out: DebugVarying;
-    out.pos = vec4<f32>(ndc, 0.0, -local_dir.z);
+    out.pos = vec4<f32>(-local_dir.z * ndc, 0.0, -local_dir.z);
     out.color = unpack4x8unorm(point.color);
     out.dir = world_dir;
+    out.ndc = ndc;
     return out;
 }
 
@@ -35,7 +38,7 @@ var depth: texture_2d<f32>;
 @fragment
 fn debug_fs(in: DebugVarying) -> @location(0) vec4<f32> {
     let geo_dim = textureDimensions(depth);
-    let depth_itc = vec2<i32>(i32(in.pos.x), i32(geo_dim.y) - i32(in.pos.y));
+    let depth_itc = vec2<i32>((0.5 * in.ndc + 0.5) * vec2<f32>(geo_dim));
     let depth = textureLoad(depth, depth_itc, 0).x;
     let alpha = select(0.8, 0.2, depth != 0.0 && dot(in.dir, in.dir) > depth*depth);
     return vec4<f32>(in.color.xyz, alpha);
@@ -35,6 +35,12 @@ struct DebugBuffer {
 
 var<storage, read_write> debug_buf: DebugBuffer;
 
+// Maps [0, 1] to a blue-green-red gradient.
+fn debug_heatmap(value: f32) -> vec3<f32> {
+    let t = 4.0 * clamp(value, 0.0, 1.0);
+    return clamp(vec3<f32>(t - 2.0, 2.0 - abs(t - 2.0), 2.0 - t), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
 fn debug_line(a: vec3<f32>, b: vec3<f32>, color: u32) {
     if (debug_buf.open != 0u) {
         let index = atomicAdd(&debug_buf.instance_count, 1u);
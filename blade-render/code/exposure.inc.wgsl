@@ -0,0 +1,11 @@
+// Has to match the host!
+struct ExposureState {
+    // metered exposure in stops, applied on top of the manual one
+    ev: f32,
+    // exposure that the metering adapts to
+    target_ev: f32,
+    // set after the first metering, which jumps straight to the target
+    is_valid: u32,
+    // set while the exposure is moving towards the target
+    is_adapting: u32,
+}
@@ -0,0 +1,141 @@
+#include "exposure.inc.wgsl"
+
+// Luminance histogram of the HDR image, and the auto-exposure adapting to it.
+
+// Has to match the host!
+const HISTOGRAM_BINS: u32 = 128u;
+const LUMA: vec3<f32> = vec3<f32>(0.2126, 0.7152, 0.0722);
+// Luminance that the average is mapped to, the middle gray.
+const KEY_VALUE: f32 = 0.18;
+
+struct ExposureParams {
+    // log2 of the luminance of the first non-black bin
+    min_log_luminance: f32,
+    // log2 range of the luminance covered by the non-black bins
+    log_luminance_range: f32,
+    // fractions of the pixels ignored at the dark and the bright ends
+    low_percentile: f32,
+    high_percentile: f32,
+    // largest change of the exposure for this frame, in stops
+    max_step: f32,
+    // difference with the target that starts the adaptation, in stops
+    tolerance: f32,
+    min_ev: f32,
+    max_ev: f32,
+    use_accumulation: u32,
+    pad0: u32,
+    pad1: u32,
+    pad2: u32,
+}
+
+var<uniform> params: ExposureParams;
+var t_albedo: texture_2d<f32>;
+var light_diffuse: texture_2d<f32>;
+var t_emission: texture_2d<f32>;
+// In-scattered radiance of the medium, and the transmittance to the surface
+var t_medium: texture_2d<f32>;
+var t_accumulation: texture_2d<f32>;
+// Bin 0 counts the black pixels, the others are spread over the log luminance.
+var<storage, read_write> histogram: array<atomic<u32>, HISTOGRAM_BINS>;
+var<storage, read_write> state: ExposureState;
+
+var<workgroup> local_bins: array<atomic<u32>, HISTOGRAM_BINS>;
+
+fn load_radiance(pixel: vec2<i32>) -> vec3<f32> {
+    if (params.use_accumulation != 0u) {
+        return textureLoad(t_accumulation, pixel, 0).xyz;
+    }
+    let albedo = textureLoad(t_albedo, pixel, 0).xyz;
+    let illumination = textureLoad(light_diffuse, pixel, 0).xyz;
+    let emission = textureLoad(t_emission, pixel, 0).xyz;
+    let medium = textureLoad(t_medium, pixel, 0);
+    return medium.w * (albedo * illumination + emission) + medium.xyz;
+}
+
+fn luminance_bin(luminance: f32) -> u32 {
+    if (!(luminance > 0.0)) {
+        return 0u;
+    }
+    let t = (log2(luminance) - params.min_log_luminance) / params.log_luminance_range;
+    return 1u + u32(clamp(t, 0.0, 1.0) * f32(HISTOGRAM_BINS - 2u));
+}
+
+@compute @workgroup_size(16, 16)
+fn histogram_main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    if (local_index < HISTOGRAM_BINS) {
+        atomicStore(&local_bins[local_index], 0u);
+    }
+    workgroupBarrier();
+
+    if (all(global_id.xy < textureDimensions(light_diffuse))) {
+        let luminance = dot(load_radiance(vec2<i32>(global_id.xy)), LUMA);
+        atomicAdd(&local_bins[luminance_bin(luminance)], 1u);
+    }
+    workgroupBarrier();
+
+    if (local_index < HISTOGRAM_BINS) {
+        let count = atomicLoad(&local_bins[local_index]);
+        if (count != 0u) {
+            atomicAdd(&histogram[local_index], count);
+        }
+    }
+}
+
+var<workgroup> bin_counts: array<u32, HISTOGRAM_BINS>;
+
+// Single workgroup, one thread per bin. Clears the histogram for the next frame.
+@compute @workgroup_size(128)
+fn adapt(@builtin(local_invocation_index) local_index: u32) {
+    bin_counts[local_index] = atomicExchange(&histogram[local_index], 0u);
+    workgroupBarrier();
+    if (local_index != 0u) {
+        return;
+    }
+
+    // Black pixels carry no information about the exposure
+    var total = 0u;
+    for (var i = 1u; i < HISTOGRAM_BINS; i += 1u) {
+        total += bin_counts[i];
+    }
+    if (total == 0u) {
+        return;
+    }
+
+    // Average the log luminance between the percentiles
+    let low = f32(total) * params.low_percentile;
+    let high = f32(total) * params.high_percentile;
+    var cumulative = 0.0;
+    var weight_sum = 0.0;
+    var log_sum = 0.0;
+    for (var i = 1u; i < HISTOGRAM_BINS; i += 1u) {
+        let count = f32(bin_counts[i]);
+        let weight = max(min(cumulative + count, high) - max(cumulative, low), 0.0);
+        let t = (f32(i - 1u) + 0.5) / f32(HISTOGRAM_BINS - 2u);
+        log_sum += weight * (params.min_log_luminance + t * params.log_luminance_range);
+        weight_sum += weight;
+        cumulative += count;
+    }
+    if (weight_sum <= 0.0) {
+        return;
+    }
+
+    let target_ev = clamp(log2(KEY_VALUE) - log_sum / weight_sum, params.min_ev, params.max_ev);
+    state.target_ev = target_ev;
+    if (state.is_valid == 0u) {
+        state.ev = target_ev;
+        state.is_valid = 1u;
+        state.is_adapting = 0u;
+        return;
+    }
+    // Small fluctuations of the noisy input don't start the adaptation,
+    // but once started, it goes all the way to the target.
+    let delta = target_ev - state.ev;
+    if (state.is_adapting != 0u || abs(delta) > params.tolerance) {
+        let step = clamp(delta, -params.max_step, params.max_step);
+        state.ev += step;
+        state.is_adapting = u32(step != delta);
+    }
+}
@@ -4,59 +4,33 @@ enable wgpu_ray_query;
 #include "debug.inc.wgsl"
 #include "debug-param.inc.wgsl"
 #include "gbuf.inc.wgsl"
+#include "geometry.inc.wgsl"
 
-// Has to match the host!
-struct Vertex {
-    pos: vec3<f32>,
-    bitangent_sign: f32,
-    tex_coords: vec2<f32>,
-    normal: u32,
-    tangent: u32,
+struct FillParams {
+    // the objects have moved since the previous frame
+    use_motion_vectors: u32,
 }
-struct VertexBuffer {
-    data: array<Vertex>,
-}
-struct IndexBuffer {
-    data: array<u32>,
-}
-var<storage, read> vertex_buffers: binding_array<VertexBuffer>;
-var<storage, read> index_buffers: binding_array<IndexBuffer>;
-var textures: binding_array<texture_2d<f32>>;
+
 var sampler_linear: sampler;
 var sampler_nearest: sampler;
 
-struct HitEntry {
-    index_buf: u32,
-    vertex_buf: u32,
-    winding: f32,
-    // packed quaternion
-    geometry_to_world_rotation: u32,
-    geometry_to_object: mat4x3<f32>,
-    prev_object_to_world: mat4x3<f32>,
-    base_color_texture: u32,
-    // packed color factor
-    base_color_factor: u32,
-    normal_texture: u32,
-    normal_scale: f32,
-}
-var<storage, read> hit_entries: array<HitEntry>;
-
 var<uniform> camera: CameraParams;
 var<uniform> prev_camera: CameraParams;
 var<uniform> debug: DebugParams;
+var<uniform> parameters: FillParams;
 var acc_struct: acceleration_structure;
 
 var out_depth: texture_storage_2d<r32float, write>;
 var out_flat_normal: texture_storage_2d<rgba8snorm, write>;
 var out_basis: texture_storage_2d<rgba8snorm, write>;
 var out_albedo: texture_storage_2d<rgba8unorm, write>;
-var out_motion: texture_storage_2d<rg8snorm, write>;
+var out_hit_entry: texture_storage_2d<r32uint, write>;
+// Offset to the pixel of the same surface point in the previous frame,
+// followed by the distance from it to the previous camera.
+var out_motion: texture_storage_2d<rgba16float, write>;
+var out_emission: texture_storage_2d<rgba16float, write>;
 var out_debug: texture_storage_2d<rgba8unorm, write>;
 
-fn decode_normal(raw: u32) -> vec3<f32> {
-    return unpack4x8snorm(raw).xyz;
-}
-
 fn debug_raw_normal(pos: vec3<f32>, normal_raw: u32, rotation: vec4<f32>, debug_len: f32, color: u32) {
     let nw = normalize(qrot(rotation, decode_normal(normal_raw)));
     debug_line(pos, pos + debug_len * nw, color);
@@ -72,9 +46,15 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     }
 
     var rq: ray_query;
-    let ray_dir = get_ray_direction(camera, vec2<i32>(global_id.xy));
-    rayQueryInitialize(&rq, acc_struct, RayDesc(RAY_FLAG_CULL_NO_OPAQUE, 0xFFu, 0.0, camera.depth, camera.position, ray_dir));
-    rayQueryProceed(&rq);
+    let ray = get_camera_ray(camera, vec2<i32>(global_id.xy));
+    // skip the instances culled by the frustum, see `INSTANCE_MASK_PRIMARY`
+    rayQueryInitialize(&rq, acc_struct, RayDesc(RAY_FLAG_NONE, 0x1u, 0.0, camera.depth, ray.origin, ray.dir));
+    while (rayQueryProceed(&rq)) {
+        let candidate = rayQueryGetCandidateIntersection(&rq);
+        if (is_candidate_visible(candidate, sampler_linear)) {
+            rayQueryConfirmIntersection(&rq);
+        }
+    }
     let intersection = rayQueryGetCommittedIntersection(&rq);
 
     var depth = 0.0;
@@ -82,24 +62,17 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     var flat_normal = vec3<f32>(0.0);
     var albedo = vec3<f32>(1.0);
     var motion = vec2<f32>(0.0);
+    var prev_depth = 0.0;
+    var emission = vec3<f32>(0.0);
+    var hit_entry = ~0u;
     let enable_debug = all(global_id.xy == debug.mouse_pos);
 
     if (intersection.kind != RAY_QUERY_INTERSECTION_NONE) {
-        let entry = hit_entries[intersection.instance_custom_data + intersection.geometry_index];
+        hit_entry = intersection.instance_custom_data + intersection.geometry_index;
+        let entry = hit_entries[hit_entry];
         depth = intersection.t;
 
-        var indices = intersection.primitive_index * 3u + vec3<u32>(0u, 1u, 2u);
-        if (entry.index_buf != ~0u) {
-            let iptr = &index_buffers[entry.index_buf].data;
-            indices = vec3<u32>((*iptr)[indices.x], (*iptr)[indices.y], (*iptr)[indices.z]);
-        }
-
-        let vptr = &vertex_buffers[entry.vertex_buf].data;
-        let vertices = array<Vertex, 3>(
-            (*vptr)[indices.x],
-            (*vptr)[indices.y],
-            (*vptr)[indices.z],
-        );
+        let vertices = fetch_triangle(entry, intersection.primitive_index);
 
         let positions_object = entry.geometry_to_object * mat3x4(
             vec4<f32>(vertices[0].pos, 1.0), vec4<f32>(vertices[1].pos, 1.0), vec4<f32>(vertices[2].pos, 1.0)
@@ -111,7 +84,10 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 
         let barycentrics = vec3<f32>(1.0 - intersection.barycentrics.x - intersection.barycentrics.y, intersection.barycentrics);
         let position_object = vec4<f32>(positions_object * barycentrics, 1.0);
-        let tex_coords = mat3x2(vertices[0].tex_coords, vertices[1].tex_coords, vertices[2].tex_coords) * barycentrics;
+        let tex_coords0 = mat3x2(vertices[0].tex_coords, vertices[1].tex_coords, vertices[2].tex_coords) * barycentrics;
+        let tex_coords1 = fetch_tex_coords1(entry, intersection.primitive_index, barycentrics);
+        let base_color_tex_coords = map_tex_coords(entry.base_color_tex_coord, entry.base_color_transform, tex_coords0, tex_coords1);
+        let normal_tex_coords = map_tex_coords(entry.normal_tex_coord, entry.normal_transform, tex_coords0, tex_coords1);
         let normal_geo = normalize(mat3x3(decode_normal(vertices[0].normal), decode_normal(vertices[1].normal), decode_normal(vertices[2].normal)) * barycentrics);
         let tangent_geo = normalize(mat3x3(decode_normal(vertices[0].tangent), decode_normal(vertices[1].tangent), decode_normal(vertices[2].tangent)) * barycentrics);
         let bitangent_geo = normalize(cross(normal_geo, tangent_geo)) * vertices[0].bitangent_sign;
@@ -124,18 +100,18 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         if ((debug.texture_flags & DebugTextureFlags_NORMAL) != 0u) {
             normal_local = vec3<f32>(0.0, 0.0, 1.0); // ignore normal map
         } else {
-            let raw_unorm = textureSampleLevel(textures[entry.normal_texture], sampler_linear, tex_coords, lod).xy;
+            let raw_unorm = textureSampleLevel(textures[entry.normal_texture], sampler_linear, normal_tex_coords, lod).xy;
             let n_xy = entry.normal_scale * (2.0 * raw_unorm - 1.0);
             normal_local = vec3<f32>(n_xy, sqrt(max(0.0, 1.0 - dot(n_xy, n_xy))));
         }
         var normal = qrot(geo_to_world_rot, tangent_space_geo * normal_local);
         basis = shortest_arc_quat(vec3<f32>(0.0, 0.0, 1.0), normalize(normal));
 
-        let hit_position = camera.position + intersection.t * ray_dir;
+        let hit_position = ray.origin + intersection.t * ray.dir;
         if (enable_debug) {
             debug_buf.entry.custom_index = intersection.instance_custom_data;
             debug_buf.entry.depth = intersection.t;
-            debug_buf.entry.tex_coords = tex_coords;
+            debug_buf.entry.tex_coords = tex_coords0;
             debug_buf.entry.base_color_texture = entry.base_color_texture;
             debug_buf.entry.normal_texture = entry.normal_texture;
             debug_buf.entry.position = hit_position;
@@ -170,7 +146,7 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         if ((debug.texture_flags & DebugTextureFlags_ALBEDO) != 0u) {
             albedo = base_color_factor.xyz;
         } else {
-            let base_color_sample = textureSampleLevel(textures[entry.base_color_texture], sampler_linear, tex_coords, lod);
+            let base_color_sample = textureSampleLevel(textures[entry.base_color_texture], sampler_linear, base_color_tex_coords, lod);
             albedo = (base_color_factor * base_color_sample).xyz;
         }
 
@@ -193,6 +169,10 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
             if (debug.view_mode == DebugMode_ShadingNormal) {
                 textureStore(out_debug, global_id.xy, vec4<f32>(normal, 0.0));
             }
+            if (debug.view_mode == DebugMode_Lod) {
+                // the levels past the fourth one saturate
+                textureStore(out_debug, global_id.xy, vec4<f32>(debug_heatmap(f32(entry.lod) / 3.0), 0.0));
+            }
             if (debug.view_mode == DebugMode_HitConsistency) {
                 let reprojected = get_projected_pixel(camera, hit_position);
                 let barycentrics_pos_diff = (intersection.object_to_world * position_object).xyz - hit_position;
@@ -202,14 +182,20 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
             }
         }
 
-        let prev_position = (entry.prev_object_to_world * position_object).xyz;
+        emission = entry.emissive_factor;
+
+        // Reconstruct the hit position in the previous frame,
+        // following both the object transform and the skinning.
+        var prev_position = hit_position;
+        if (parameters.use_motion_vectors != 0u) {
+            let prev_positions_object = entry.geometry_to_object * fetch_prev_positions(entry, intersection.primitive_index);
+            let prev_position_object = vec4<f32>(prev_positions_object * barycentrics, 1.0);
+            prev_position = (entry.prev_object_to_world * prev_position_object).xyz;
+        }
         let prev_screen = get_projected_pixel_float(prev_camera, prev_position);
-        //TODO: consider just storing integers here?
         //TODO: technically this "0.5" is just a waste compute on both packing and unpacking
         motion = prev_screen - vec2<f32>(global_id.xy) - 0.5;
-        if (WRITE_DEBUG_IMAGE && debug.view_mode == DebugMode_Motion) {
-            textureStore(out_debug, global_id.xy, vec4<f32>(motion * MOTION_SCALE + vec2<f32>(0.5), 0.0, 1.0));
-        }
+        prev_depth = get_camera_depth(prev_camera, prev_position);
     } else {
         if (enable_debug) {
             debug_buf.entry = DebugEntry();
@@ -221,5 +207,7 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     textureStore(out_basis, global_id.xy, basis);
     textureStore(out_flat_normal, global_id.xy, vec4<f32>(flat_normal, 0.0));
     textureStore(out_albedo, global_id.xy, vec4<f32>(albedo, 0.0));
-    textureStore(out_motion, global_id.xy, vec4<f32>(motion * MOTION_SCALE, 0.0, 0.0));
+    textureStore(out_hit_entry, global_id.xy, vec4<u32>(hit_entry, 0u, 0u, 0u));
+    textureStore(out_motion, global_id.xy, vec4<f32>(motion, prev_depth, 0.0));
+    textureStore(out_emission, global_id.xy, vec4<f32>(emission, 0.0));
 }
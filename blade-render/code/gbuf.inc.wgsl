@@ -1,5 +1,4 @@
 #use DEBUG_MODE
 
-const MOTION_SCALE: f32 = 0.02;
 const USE_MOTION_VECTORS: bool = true;
 const WRITE_DEBUG_IMAGE: bool = DEBUG_MODE;
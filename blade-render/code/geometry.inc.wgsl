@@ -0,0 +1,131 @@
+// Has to match the host!
+struct Vertex {
+    pos: vec3<f32>,
+    bitangent_sign: f32,
+    tex_coords: vec2<f32>,
+    normal: u32,
+    tangent: u32,
+}
+struct VertexBuffer {
+    data: array<Vertex>,
+}
+struct IndexBuffer {
+    data: array<u32>,
+}
+struct TexCoordBuffer {
+    data: array<vec2<f32>>,
+}
+var<storage, read> vertex_buffers: binding_array<VertexBuffer>;
+var<storage, read> index_buffers: binding_array<IndexBuffer>;
+var<storage, read> tex_coord_buffers: binding_array<TexCoordBuffer>;
+
+struct HitEntry {
+    index_buf: u32,
+    vertex_buf: u32,
+    winding: f32,
+    // packed quaternion
+    geometry_to_world_rotation: u32,
+    geometry_to_object: mat4x3<f32>,
+    prev_object_to_world: mat4x3<f32>,
+    base_color_texture: u32,
+    // packed color factor
+    base_color_factor: u32,
+    normal_texture: u32,
+    normal_scale: f32,
+    emissive_factor: vec3<f32>,
+    alpha_mode: u32,
+    alpha_cutoff: f32,
+    transmission: f32,
+    ior: f32,
+    clearcoat: f32,
+    sheen_color: vec3<f32>,
+    sheen_roughness: f32,
+    clearcoat_roughness: f32,
+    // vertices of the previous frame, only different for the skinned geometry
+    prev_vertex_buf: u32,
+    // set of the texture coordinates for each texture
+    base_color_tex_coord: u32,
+    normal_tex_coord: u32,
+    base_color_transform: mat3x2<f32>,
+    normal_transform: mat3x2<f32>,
+    // second set of the texture coordinates, if any texture uses it
+    tex_coords1_buf: u32,
+    // level of detail of the model, 0 being the finest
+    lod: u32,
+}
+var<storage, read> hit_entries: array<HitEntry>;
+var textures: binding_array<texture_2d<f32>>;
+
+const ALPHA_MODE_OPAQUE: u32 = 0u;
+const ALPHA_MODE_MASK: u32 = 1u;
+const ALPHA_MODE_BLEND: u32 = 2u;
+
+fn decode_normal(raw: u32) -> vec3<f32> {
+    return unpack4x8snorm(raw).xyz;
+}
+
+fn fetch_indices(entry: HitEntry, primitive_index: u32) -> vec3<u32> {
+    let indices = primitive_index * 3u + vec3<u32>(0u, 1u, 2u);
+    if (entry.index_buf == ~0u) {
+        return indices;
+    }
+    let iptr = &index_buffers[entry.index_buf].data;
+    return vec3<u32>((*iptr)[indices.x], (*iptr)[indices.y], (*iptr)[indices.z]);
+}
+
+fn fetch_triangle(entry: HitEntry, primitive_index: u32) -> array<Vertex, 3> {
+    let indices = fetch_indices(entry, primitive_index);
+    let vptr = &vertex_buffers[entry.vertex_buf].data;
+    return array<Vertex, 3>(
+        (*vptr)[indices.x],
+        (*vptr)[indices.y],
+        (*vptr)[indices.z],
+    );
+}
+
+// Vertex positions of the triangle in the previous frame, in the geometry space.
+fn fetch_prev_positions(entry: HitEntry, primitive_index: u32) -> mat3x4<f32> {
+    let indices = fetch_indices(entry, primitive_index);
+    let vptr = &vertex_buffers[entry.prev_vertex_buf].data;
+    return mat3x4<f32>(
+        vec4<f32>((*vptr)[indices.x].pos, 1.0),
+        vec4<f32>((*vptr)[indices.y].pos, 1.0),
+        vec4<f32>((*vptr)[indices.z].pos, 1.0),
+    );
+}
+
+// Interpolated second set of the texture coordinates of the triangle,
+// or zero if none of the textures use it.
+fn fetch_tex_coords1(entry: HitEntry, primitive_index: u32, weights: vec3<f32>) -> vec2<f32> {
+    if (entry.base_color_tex_coord != 1u && entry.normal_tex_coord != 1u) {
+        return vec2<f32>(0.0);
+    }
+    let indices = fetch_indices(entry, primitive_index);
+    let tptr = &tex_coord_buffers[entry.tex_coords1_buf].data;
+    return mat3x2((*tptr)[indices.x], (*tptr)[indices.y], (*tptr)[indices.z]) * weights;
+}
+
+// Texture coordinates of a material texture, given both of the sets.
+fn map_tex_coords(tex_coord: u32, transform: mat3x2<f32>, tex_coords0: vec2<f32>, tex_coords1: vec2<f32>) -> vec2<f32> {
+    let tc = select(tex_coords0, tex_coords1, tex_coord == 1u);
+    return transform * vec3<f32>(tc, 1.0);
+}
+
+// Sample the base color alpha at the candidate intersection.
+fn sample_alpha(entry: HitEntry, primitive_index: u32, barycentrics: vec2<f32>, sampler_linear: sampler) -> f32 {
+    let vertices = fetch_triangle(entry, primitive_index);
+    let weights = vec3<f32>(1.0 - barycentrics.x - barycentrics.y, barycentrics);
+    let tex_coords0 = mat3x2(vertices[0].tex_coords, vertices[1].tex_coords, vertices[2].tex_coords) * weights;
+    let tex_coords1 = fetch_tex_coords1(entry, primitive_index, weights);
+    let tex_coords = map_tex_coords(entry.base_color_tex_coord, entry.base_color_transform, tex_coords0, tex_coords1);
+    let base_color_sample = textureSampleLevel(textures[entry.base_color_texture], sampler_linear, tex_coords, 0.0);
+    return unpack4x8unorm(entry.base_color_factor).w * base_color_sample.w;
+}
+
+// Primary visibility of a non-opaque candidate.
+// Blended surfaces are treated as masked with the default cutoff.
+fn is_candidate_visible(intersection: RayIntersection, sampler_linear: sampler) -> bool {
+    let entry = hit_entries[intersection.instance_custom_data + intersection.geometry_index];
+    let alpha = sample_alpha(entry, intersection.primitive_index, intersection.barycentrics, sampler_linear);
+    return entry.alpha_mode == ALPHA_MODE_OPAQUE || alpha >= entry.alpha_cutoff;
+}
@@ -0,0 +1,44 @@
+enable wgpu_ray_query;
+#include "quaternion.inc.wgsl"
+#include "camera.inc.wgsl"
+
+struct PickParams {
+    pixel: vec2<i32>,
+    pad: vec2<u32>,
+}
+
+// Has to match the host!
+struct PickResult {
+    hit: u32,
+    instance_index: u32,
+    geometry_index: u32,
+    primitive_index: u32,
+    distance: f32,
+    pad: u32,
+    barycentrics: vec2<f32>,
+}
+
+var<uniform> camera: CameraParams;
+var<uniform> params: PickParams;
+var acc_struct: acceleration_structure;
+var<storage, read_write> result: PickResult;
+
+@compute @workgroup_size(1)
+fn main() {
+    var rq: ray_query;
+    let ray = get_projection_ray(camera, params.pixel);
+    rayQueryInitialize(&rq, acc_struct, RayDesc(RAY_FLAG_CULL_NO_OPAQUE, 0xFFu, 0.0, camera.depth, ray.origin, ray.dir));
+    rayQueryProceed(&rq);
+    let intersection = rayQueryGetCommittedIntersection(&rq);
+
+    var pr = PickResult();
+    if (intersection.kind != RAY_QUERY_INTERSECTION_NONE) {
+        pr.hit = 1u;
+        pr.instance_index = intersection.instance_index;
+        pr.geometry_index = intersection.geometry_index;
+        pr.primitive_index = intersection.primitive_index;
+        pr.distance = intersection.t;
+        pr.barycentrics = intersection.barycentrics;
+    }
+    result = pr;
+}
@@ -1,19 +1,59 @@
+#use ToneMap
 #include "debug.inc.wgsl"
 #include "debug-param.inc.wgsl"
+#include "exposure.inc.wgsl"
 
 struct ToneMapParams {
-    enabled: u32,
-    average_lum: f32,
-    key_value: f32,
+    mode: u32,
+    // linear scale applied to the radiance, 2^EV
+    exposure: f32,
     // minimum value of the pixels mapped to white brightness
-    white_level: f32,
+    white_point: f32,
+    encode_srgb: u32,
+    // multiply the exposure by the metered one
+    auto_exposure: u32,
+    pad0: u32,
+    pad1: u32,
+    pad2: u32,
+}
+struct PostProcParams {
+    is_view_available: u32,
+    use_accumulation: u32,
+    // maximum number of samples per pixel of the adaptive sampling
+    max_samples: u32,
+    // fraction of the radiance replaced by the bloom, 0 if it's not available
+    bloom_intensity: f32,
+    // size of the input relative to the output
+    input_scale: vec2<f32>,
+    // strength of the sharpening of the upscaled image
+    sharpness: f32,
+    is_upscaled: u32,
 }
 
 var t_albedo: texture_2d<f32>;
 var light_diffuse: texture_2d<f32>;
+var t_emission: texture_2d<f32>;
+// In-scattered radiance of the medium, and the transmittance to the surface
+var t_medium: texture_2d<f32>;
 var t_debug: texture_2d<f32>;
+var t_history: texture_2d<f32>;
+var t_accumulation: texture_2d<f32>;
+var t_tile_samples: texture_2d<u32>;
+var t_motion: texture_2d<f32>;
 var<uniform> tone_map_params: ToneMapParams;
 var<uniform> debug_params: DebugParams;
+var<uniform> post_proc_params: PostProcParams;
+var<storage, read> exposure_state: ExposureState;
+// Finest level of the bloom chain, at half of the resolution
+var t_bloom: texture_2d<f32>;
+var bloom_sampler: sampler;
+
+// Number of accumulated frames shown as the hottest color of the age view.
+const ACCUMULATION_AGE_HEATMAP_MAX: f32 = 64.0;
+// Has to match the host!
+const ADAPTIVE_TILE_SIZE: i32 = 8;
+// Motion in pixels shown as the full intensity of the motion view.
+const MOTION_VIEW_MAX: f32 = 25.0;
 
 struct VertexOutput {
     @builtin(position) clip_pos: vec4<f32>,
@@ -28,24 +68,153 @@ fn postfx_vs(@builtin(vertex_index) vi: u32) -> VertexOutput {
     return vo;
 }
 
+// Following https://blog.en.uwa4d.com/2022/07/19/physically-based-renderingg-hdr-tone-mapping/
+fn tone_map_reinhard(color: vec3<f32>, l_white: f32) -> vec3<f32> {
+    return color * (1.0 + color / (l_white * l_white)) / (1.0 + color);
+}
+
+// Narkowicz 2015, "ACES Filmic Tone Mapping Curve"
+fn tone_map_aces(color: vec3<f32>) -> vec3<f32> {
+    return (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14);
+}
+
+// https://github.com/KhronosGroup/ToneMapping/tree/main/PBR_Neutral
+fn tone_map_pbr_neutral(color_in: vec3<f32>) -> vec3<f32> {
+    let start_compression = 0.8 - 0.04;
+    let desaturation = 0.15;
+
+    let x = min(color_in.x, min(color_in.y, color_in.z));
+    let offset = select(0.04, x - 6.25 * x * x, x < 0.08);
+    var color = color_in - offset;
+    let peak = max(color.x, max(color.y, color.z));
+    if (peak < start_compression) {
+        return color;
+    }
+
+    let d = 1.0 - start_compression;
+    let new_peak = 1.0 - d * d / (peak + d - start_compression);
+    color *= new_peak / peak;
+    let g = 1.0 - 1.0 / (desaturation * (peak - new_peak) + 1.0);
+    return mix(color, vec3<f32>(new_peak), g);
+}
+
+fn tone_map(color: vec3<f32>) -> vec3<f32> {
+    let l_white = tone_map_params.white_point;
+    switch (tone_map_params.mode) {
+        case ToneMap_Reinhard: {
+            return tone_map_reinhard(color, l_white);
+        }
+        case ToneMap_Aces: {
+            return tone_map_aces(color) / tone_map_aces(vec3<f32>(l_white));
+        }
+        case ToneMap_KhronosPbrNeutral: {
+            return tone_map_pbr_neutral(color);
+        }
+        default: {
+            return color / l_white;
+        }
+    }
+}
+
+fn encode_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+// Tone-mapped color of an input pixel, before the sRGB encoding.
+fn load_tone_mapped(pixel: vec2<i32>, input_size: vec2<u32>) -> vec3<f32> {
+    let tc = clamp(pixel, vec2<i32>(0), vec2<i32>(input_size) - 1);
+    var color: vec3<f32>;
+    if (post_proc_params.use_accumulation != 0u) {
+        color = textureLoad(t_accumulation, tc, 0).xyz;
+    } else {
+        let albedo = textureLoad(t_albedo, tc, 0).xyz;
+        let illumination = textureLoad(light_diffuse, tc, 0).xyz;
+        let emission = textureLoad(t_emission, tc, 0).xyz;
+        let medium = textureLoad(t_medium, tc, 0);
+        color = medium.w * (albedo * illumination + emission) + medium.xyz;
+    }
+    if (post_proc_params.bloom_intensity > 0.0) {
+        let uv = (vec2<f32>(tc) + 0.5) / vec2<f32>(input_size);
+        let bloom = textureSampleLevel(t_bloom, bloom_sampler, uv, 0.0).xyz;
+        color = mix(color, bloom, post_proc_params.bloom_intensity);
+    }
+    var exposure = tone_map_params.exposure;
+    if (tone_map_params.auto_exposure != 0u) {
+        exposure *= exp2(exposure_state.ev);
+    }
+    let exposed = max(exposure * color, vec3<f32>(0.0));
+    return clamp(tone_map(exposed), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// Bilinear upscaling, sharpened by the contrast-adaptive filter, after
+// "FidelityFX Contrast Adaptive Sharpening" by AMD.
+// The bilinear samples at the pixel and its 4 neighbors, one input pixel apart,
+// are taken from the 4x4 input pixels around the position.
+fn upscale(frag_pos: vec2<f32>, input_size: vec2<u32>) -> vec3<f32> {
+    let pos = frag_pos * post_proc_params.input_scale - 0.5;
+    let base = vec2<i32>(floor(pos));
+    let f = pos - floor(pos);
+    var texels: array<vec3<f32>, 16>;
+    for (var y = 0; y < 4; y += 1) {
+        for (var x = 0; x < 4; x += 1) {
+            texels[y * 4 + x] = load_tone_mapped(base + vec2<i32>(x - 1, y - 1), input_size);
+        }
+    }
+    var samples: array<vec3<f32>, 5>;
+    let offsets = array<vec2<i32>, 5>(vec2<i32>(0, 0), vec2<i32>(0, -1), vec2<i32>(-1, 0), vec2<i32>(1, 0), vec2<i32>(0, 1));
+    for (var i = 0; i < 5; i += 1) {
+        let k = (offsets[i].y + 1) * 4 + offsets[i].x + 1;
+        let top = mix(texels[k], texels[k + 1], f.x);
+        let bottom = mix(texels[k + 4], texels[k + 5], f.x);
+        samples[i] = mix(top, bottom, f.y);
+    }
+    let center = samples[0];
+    let min_color = min(center, min(min(samples[1], samples[2]), min(samples[3], samples[4])));
+    let max_color = max(center, max(max(samples[1], samples[2]), max(samples[3], samples[4])));
+    // Sharpen less where the contrast is high already
+    let amp = sqrt(clamp(min(min_color, 1.0 - max_color) / max(max_color, vec3<f32>(1e-5)), vec3<f32>(0.0), vec3<f32>(1.0)));
+    let weight = -0.2 * post_proc_params.sharpness * amp;
+    let neighbors = samples[1] + samples[2] + samples[3] + samples[4];
+    return clamp((center + weight * neighbors) / (1.0 + 4.0 * weight), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
 @fragment
 fn postfx_fs(vo: VertexOutput) -> @location(0) vec4<f32> {
-    let tc = vec2<i32>(i32(vo.clip_pos.x), i32(vo.clip_pos.y));
+    // the nearest input pixel, unless the final image is upscaled
+    let tc = vec2<i32>(vo.clip_pos.xy * post_proc_params.input_scale);
     let illumunation = textureLoad(light_diffuse, tc, 0);
+    if (post_proc_params.is_view_available == 0u) {
+        // the selected buffer is not produced, avoid showing stale data
+        let checker = ((tc.x >> 3u) ^ (tc.y >> 3u)) & 1;
+        return vec4<f32>(vec3<f32>(select(0.2, 0.4, checker != 0)), 1.0);
+    }
     if (debug_params.view_mode == DebugMode_Final) {
-        let albedo = textureLoad(t_albedo, tc, 0).xyz;
-        let color = albedo.xyz * illumunation.xyz;
-        if (tone_map_params.enabled != 0u) {
-            // Following https://blog.en.uwa4d.com/2022/07/19/physically-based-renderingg-hdr-tone-mapping/
-            let l_adjusted = tone_map_params.key_value / tone_map_params.average_lum * color;
-            let l_white = tone_map_params.white_level;
-            let l_ldr = l_adjusted * (1.0 + l_adjusted / (l_white*l_white)) / (1.0 + l_adjusted);
-            return vec4<f32>(l_ldr, 1.0);
+        var ldr: vec3<f32>;
+        if (post_proc_params.is_upscaled != 0u) {
+            ldr = upscale(vo.clip_pos.xy, vo.input_size);
         } else {
-            return vec4<f32>(color, 1.0);
+            ldr = load_tone_mapped(tc, vo.input_size);
+        }
+        if (tone_map_params.encode_srgb != 0u) {
+            ldr = encode_srgb(ldr);
         }
+        return vec4<f32>(ldr, 1.0);
     } else if (debug_params.view_mode == DebugMode_Variance) {
         return vec4<f32>(illumunation.w);
+    } else if (debug_params.view_mode == DebugMode_Albedo) {
+        return vec4<f32>(textureLoad(t_albedo, tc, 0).xyz, 1.0);
+    } else if (debug_params.view_mode == DebugMode_AccumulationAge) {
+        let age = textureLoad(t_history, tc, 0).x;
+        return vec4<f32>(debug_heatmap(age / ACCUMULATION_AGE_HEATMAP_MAX), 1.0);
+    } else if (debug_params.view_mode == DebugMode_Motion) {
+        let motion = textureLoad(t_motion, tc, 0).xy / MOTION_VIEW_MAX;
+        return vec4<f32>(0.5 * motion + vec2<f32>(0.5), 0.0, 1.0);
+    } else if (debug_params.view_mode == DebugMode_AdaptiveSamples) {
+        let samples = textureLoad(t_tile_samples, tc / ADAPTIVE_TILE_SIZE, 0).x;
+        let extra = f32(max(1u, samples) - 1u) / f32(max(1u, post_proc_params.max_samples - 1u));
+        return vec4<f32>(debug_heatmap(extra), 1.0);
     } else {
         return textureLoad(t_debug, tc, 0);
     }
@@ -6,17 +6,37 @@ struct RasterFrameParams {
     light_color: vec4<f32>,
     ambient_color: vec4<f32>,
     material: vec4<f32>,
+    // yaw and intensity of the environment map
+    environment: vec4<f32>,
 }
 
 const PI: f32 = 3.1415926;
+// Grid of the environment map directions integrated for every irradiance texel.
+const IRRADIANCE_SAMPLES: vec2<u32> = vec2<u32>(64u, 32u);
 
 struct RasterDrawParams {
     model: mat4x4<f32>,
     normal_quat: vec4<f32>,
     base_color_factor: vec4<f32>,
     material: vec4<f32>,
+    // linear part of the transform, then the offset and the texture coordinate set
+    base_color_mapping: array<vec4<f32>, 2>,
+    normal_mapping: array<vec4<f32>, 2>,
 }
 
+struct RasterShadowParams {
+    cascade_view_proj: array<mat4x4<f32>, 4>,
+    // Size of a shadow texel in world units, per cascade
+    cascade_texel_sizes: vec4<f32>,
+    cascade_count: u32,
+    show_cascades: u32,
+}
+
+// Offset of the shadow lookups along the normal, in shadow texels.
+const SHADOW_NORMAL_OFFSET: f32 = 1.5;
+// Fraction of the cascade edges that is left for the filtering kernel.
+const SHADOW_CASCADE_MARGIN: f32 = 0.02;
+
 struct Vertex {
     position: vec3<f32>,
     bitangent_sign: f32,
@@ -29,6 +49,10 @@ struct VertexBuffer {
     data: array<Vertex>,
 }
 
+struct TexCoordBuffer {
+    data: array<vec2<f32>>,
+}
+
 struct VertexOutput {
     @builtin(position) clip_pos: vec4<f32>,
     @location(0) world_pos: vec3<f32>,
@@ -36,14 +60,20 @@ struct VertexOutput {
     @location(2) tangent: vec3<f32>,
     @location(3) bitangent: vec3<f32>,
     @location(4) uv: vec2<f32>,
+    @location(5) normal_uv: vec2<f32>,
 }
 
 var<uniform> frame_params: RasterFrameParams;
 var<uniform> draw_params: RasterDrawParams;
 var<storage, read> vertices: VertexBuffer;
+var<storage, read> tex_coords1: TexCoordBuffer;
 var samp: sampler;
 var base_color_tex: texture_2d<f32>;
 var normal_tex: texture_2d<f32>;
+var irradiance_map: texture_2d<f32>;
+var<uniform> shadow_params: RasterShadowParams;
+var shadow_map: texture_depth_2d_array;
+var shadow_sampler: sampler_comparison;
 
 fn decode_normal(raw: u32) -> vec3<f32> {
     return unpack4x8snorm(raw).xyz;
@@ -53,6 +83,14 @@ fn quat_rotate(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
     return v + 2.0 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
 }
 
+fn map_tex_coords(mapping: array<vec4<f32>, 2>, tex_coords0: vec2<f32>, vertex_index: u32) -> vec2<f32> {
+    var tc = tex_coords0;
+    if (mapping[1].z == 1.0) {
+        tc = tex_coords1.data[vertex_index];
+    }
+    return mat3x2<f32>(mapping[0].xy, mapping[0].zw, mapping[1].xy) * vec3<f32>(tc, 1.0);
+}
+
 @vertex
 fn raster_vs(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
     let input = vertices.data[vertex_index];
@@ -66,7 +104,8 @@ fn raster_vs(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
     out.normal = n;
     out.tangent = t;
     out.bitangent = b;
-    out.uv = input.tex_coords;
+    out.uv = map_tex_coords(draw_params.base_color_mapping, input.tex_coords, vertex_index);
+    out.normal_uv = map_tex_coords(draw_params.normal_mapping, input.tex_coords, vertex_index);
     return out;
 }
 
@@ -80,6 +119,26 @@ fn map_equirect_dir_to_uv(dir: vec3<f32>) -> vec2<f32> {
     return vec2<f32>((yaw / PI + 1.0) * 0.5, pitch / PI + 0.5);
 }
 
+fn map_equirect_uv_to_dir(uv: vec2<f32>) -> vec3<f32> {
+    let yaw = (2.0 * uv.x - 1.0) * PI;
+    let pitch = (uv.y - 0.5) * PI;
+    return vec3<f32>(cos(pitch) * sin(yaw), sin(pitch), cos(pitch) * cos(yaw));
+}
+
+// Rotate a world direction into the space of the environment map,
+// which is turned around the vertical axis by the yaw.
+fn rotate_to_environment(dir: vec3<f32>, yaw: f32) -> vec3<f32> {
+    let c = cos(yaw);
+    let s = sin(yaw);
+    return vec3<f32>(c * dir.x - s * dir.z, dir.y, c * dir.z + s * dir.x);
+}
+
+fn sample_irradiance(dir: vec3<f32>) -> vec3<f32> {
+    let env_dir = rotate_to_environment(dir, frame_params.environment.x);
+    let irradiance = textureSampleLevel(irradiance_map, samp, map_equirect_dir_to_uv(env_dir), 0.0).xyz;
+    return frame_params.environment.y * irradiance;
+}
+
 fn distribution_ggx(n: vec3<f32>, h: vec3<f32>, roughness: f32) -> f32 {
     let a = roughness * roughness;
     let a2 = a * a;
@@ -102,6 +161,44 @@ fn geometry_smith(n: vec3<f32>, v: vec3<f32>, l: vec3<f32>, roughness: f32) -> f
     return ggx1 * ggx2;
 }
 
+// Find the most detailed cascade containing the point,
+// or return the cascade count if there is none.
+fn select_cascade(world_pos: vec3<f32>) -> u32 {
+    for (var i = 0u; i < shadow_params.cascade_count; i += 1u) {
+        let clip = shadow_params.cascade_view_proj[i] * vec4<f32>(world_pos, 1.0);
+        if (all(abs(clip.xy) < vec2<f32>(1.0 - SHADOW_CASCADE_MARGIN)) && clip.z < 1.0) {
+            return i;
+        }
+    }
+    return shadow_params.cascade_count;
+}
+
+// Percentage-closer filtering of the cascade, returning the visibility of the light.
+fn sample_shadow(world_pos: vec3<f32>, normal: vec3<f32>, cascade: u32) -> f32 {
+    let offset = normal * (SHADOW_NORMAL_OFFSET * shadow_params.cascade_texel_sizes[cascade]);
+    let clip = shadow_params.cascade_view_proj[cascade] * vec4<f32>(world_pos + offset, 1.0);
+    let uv = vec2<f32>(0.5 + 0.5 * clip.x, 0.5 - 0.5 * clip.y);
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_map));
+    var sum = 0.0;
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let tc = uv + vec2<f32>(f32(x), f32(y)) * texel;
+            sum += textureSampleCompareLevel(shadow_map, shadow_sampler, tc, cascade, clip.z);
+        }
+    }
+    return sum / 9.0;
+}
+
+fn cascade_color(cascade: u32) -> vec3<f32> {
+    var colors = array<vec3<f32>, 4>(
+        vec3<f32>(1.0, 0.4, 0.4),
+        vec3<f32>(0.4, 1.0, 0.4),
+        vec3<f32>(0.4, 0.4, 1.0),
+        vec3<f32>(1.0, 1.0, 0.4),
+    );
+    return colors[cascade];
+}
+
 @fragment
 fn raster_fs(input: VertexOutput) -> @location(0) vec4<f32> {
     let albedo = textureSample(base_color_tex, samp, input.uv).rgb * draw_params.base_color_factor.rgb;
@@ -109,7 +206,7 @@ fn raster_fs(input: VertexOutput) -> @location(0) vec4<f32> {
     var n = normalize(input.normal);
     let normal_scale = draw_params.material.x;
     if (normal_scale > 0.0) {
-        let raw_unorm = textureSample(normal_tex, samp, input.uv).xy;
+        let raw_unorm = textureSample(normal_tex, samp, input.normal_uv).xy;
         let n_xy = normal_scale * (2.0 * raw_unorm - 1.0);
         let n_z = sqrt(max(0.0, 1.0 - dot(n_xy, n_xy)));
         let n_tangent = normalize(vec3<f32>(n_xy, n_z));
@@ -121,8 +218,9 @@ fn raster_fs(input: VertexOutput) -> @location(0) vec4<f32> {
     let l = normalize(frame_params.light_dir.xyz);
     let h = normalize(v + l);
 
-    let roughness = clamp(frame_params.material.x, 0.04, 1.0);
-    let metallic = clamp(frame_params.material.y, 0.0, 1.0);
+    // the per-object multipliers are in the draw parameters
+    let roughness = clamp(frame_params.material.x * draw_params.material.y, 0.04, 1.0);
+    let metallic = clamp(frame_params.material.y * draw_params.material.z, 0.0, 1.0);
     let f0 = mix(vec3<f32>(0.04), albedo, metallic);
 
     let n_dot_l = max(dot(n, l), 0.0);
@@ -139,9 +237,27 @@ fn raster_fs(input: VertexOutput) -> @location(0) vec4<f32> {
     let k_d = (vec3<f32>(1.0) - k_s) * (1.0 - metallic);
     let diffuse = k_d * albedo / PI;
 
-    let light = (diffuse + specular) * frame_params.light_color.xyz * n_dot_l;
-    let ambient = albedo * frame_params.ambient_color.xyz;
-    let color = ambient + light;
+    var shadow = 1.0;
+    var tint = vec3<f32>(1.0);
+    let cascade = select_cascade(input.world_pos);
+    if (cascade < shadow_params.cascade_count) {
+        shadow = sample_shadow(input.world_pos, normalize(input.normal), cascade);
+        if (shadow_params.show_cascades != 0u) {
+            tint = cascade_color(cascade);
+        }
+    }
+
+    let light = (diffuse + specular) * frame_params.light_color.xyz * n_dot_l * shadow;
+    var ambient = albedo * frame_params.ambient_color.xyz;
+    let irradiance_enabled = frame_params.material.w > 0.5;
+    if (irradiance_enabled) {
+        let k_s_ambient = fresnel_schlick(n_dot_v, f0);
+        let k_d_ambient = (vec3<f32>(1.0) - k_s_ambient) * (1.0 - metallic);
+        // The irradiance is too blurry for sharp reflections, but it keeps the energy
+        let reflected = sample_irradiance(reflect(-v, n));
+        ambient = k_d_ambient * albedo * sample_irradiance(n) + k_s_ambient * reflected;
+    }
+    let color = (ambient + light) * tint;
 
     let mapped = color / (color + vec3<f32>(1.0));
     let gamma = pow(mapped, vec3<f32>(1.0 / 2.2));
@@ -182,8 +298,8 @@ fn raster_sky_fs(input: SkyOutput) -> @location(0) vec4<f32> {
     let env_enabled = sky_params.material.z > 0.5;
     var color = vec3<f32>(0.0);
     if (env_enabled) {
-        let uv = map_equirect_dir_to_uv(dir);
-        color = textureSampleLevel(env_map, samp, uv, 0.0).xyz;
+        let uv = map_equirect_dir_to_uv(rotate_to_environment(dir, sky_params.environment.x));
+        color = sky_params.environment.y * textureSampleLevel(env_map, samp, uv, 0.0).xyz;
     } else {
         // Use ambient_color.w as a flag: values > 0.5 mean "space mode" (black sky)
         let space_mode = sky_params.ambient_color.w > 0.5;
@@ -245,3 +361,33 @@ fn raster_sky_fs(input: SkyOutput) -> @location(0) vec4<f32> {
     let gamma = pow(mapped, vec3<f32>(1.0 / 2.2));
     return vec4<f32>(gamma, 1.0);
 }
+
+var out_irradiance: texture_storage_2d<rgba16float, write>;
+
+// Convolve the environment map with the cosine lobe, producing
+// the diffuse irradiance divided by PI for every normal direction.
+@compute @workgroup_size(8, 8)
+fn raster_irradiance(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let size = textureDimensions(out_irradiance);
+    if (any(global_id.xy >= size)) {
+        return;
+    }
+    let normal = map_equirect_uv_to_dir((vec2<f32>(global_id.xy) + 0.5) / vec2<f32>(size));
+    var sum = vec3<f32>(0.0);
+    for (var y = 0u; y < IRRADIANCE_SAMPLES.y; y += 1u) {
+        for (var x = 0u; x < IRRADIANCE_SAMPLES.x; x += 1u) {
+            let uv = (vec2<f32>(f32(x), f32(y)) + 0.5) / vec2<f32>(IRRADIANCE_SAMPLES);
+            let dir = map_equirect_uv_to_dir(uv);
+            let cos_theta = dot(normal, dir);
+            if (cos_theta > 0.0) {
+                // The solid angle of the samples shrinks towards the poles
+                let cos_pitch = sqrt(max(0.0, 1.0 - dir.y * dir.y));
+                let radiance = textureSampleLevel(env_map, samp, uv, 0.0).xyz;
+                sum += radiance * cos_theta * cos_pitch;
+            }
+        }
+    }
+    // Each sample covers (2PI / width) * (PI / height) * cos_pitch of the sphere
+    let scale = 2.0 * PI / f32(IRRADIANCE_SAMPLES.x * IRRADIANCE_SAMPLES.y);
+    textureStore(out_irradiance, global_id.xy, vec4<f32>(sum * scale, 1.0));
+}
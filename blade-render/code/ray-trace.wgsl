@@ -7,6 +7,8 @@ enable wgpu_ray_query;
 #include "camera.inc.wgsl"
 #include "surface.inc.wgsl"
 #include "gbuf.inc.wgsl"
+#include "geometry.inc.wgsl"
+#include "bsdf.inc.wgsl"
 
 const PI: f32 = 3.1415926;
 const MAX_RESERVOIRS: u32 = 4u;
@@ -16,6 +18,25 @@ const DECOUPLED_SHADING: bool = false;
 
 // How many more candidates to consder than the taps we need
 const FACTOR_CANDIDATES: u32 = 3u;
+// Environment candidates taken from the clear coat lobe, if there is one
+const CLEARCOAT_ENVIRONMENT_SAMPLES: u32 = 1u;
+// Number of rays per pixel shown as the hottest color of the ray count view.
+const RAY_COUNT_HEATMAP_MAX: f32 = 32.0;
+// Has to match the host!
+const ADAPTIVE_TILE_SIZE: u32 = 8u;
+// Optical depth, after which the medium in front of the sky is considered opaque.
+const MEDIUM_MAX_OPTICAL_DEPTH: f32 = 7.0;
+// Weight of the current frame in the temporal accumulation of the medium.
+const MEDIUM_TEMPORAL_WEIGHT: f32 = 0.1;
+// Relative difference of the depths, above which the medium history is rejected.
+const MEDIUM_DEPTH_TOLERANCE: f32 = 0.1;
+// Spherical harmonics coefficients per probe of the irradiance volume. Has to match the host!
+const PROBE_COEFFICIENTS: u32 = 4u;
+// Constant factors of the real spherical harmonics basis
+const SH_L0: f32 = 0.282095;
+const SH_L1: f32 = 0.488603;
+// Minimum weight of a probe behind the surface, to avoid gaps in the interpolation.
+const PROBE_MIN_FACING_WEIGHT: f32 = 0.05;
 
 struct MainParams {
     frame_index: u32,
@@ -29,11 +50,45 @@ struct MainParams {
     use_pairwise_mis: u32,
     defensive_mis: f32,
     use_motion_vectors: u32,
+    num_light_samples: u32,
+    light_count: u32,
+    num_emissive_samples: u32,
+    emissive_count: u32,
+    max_bounces: u32,
+    firefly_clamp: f32,
+    russian_roulette_start: u32,
+    use_adaptive_sampling: u32,
+    environment_yaw: f32,
+    environment_intensity: f32,
 };
 
+struct MediumParams {
+    color: vec3<f32>,
+    anisotropy: f32,
+    absorption: f32,
+    scattering: f32,
+    base_height: f32,
+    height_falloff: f32,
+    // zero if the medium is disabled
+    step_count: u32,
+    use_history: u32,
+}
+
+struct ProbeParams {
+    origin: vec3<f32>,
+    // zero if there is no irradiance volume
+    is_enabled: u32,
+    spacing: vec3<f32>,
+    rays_per_probe: u32,
+    counts: vec3<u32>,
+    pad: u32,
+}
+
 var<uniform> camera: CameraParams;
 var<uniform> prev_camera: CameraParams;
 var<uniform> parameters: MainParams;
+var<uniform> medium: MediumParams;
+var<uniform> probes: ProbeParams;
 var<uniform> debug: DebugParams;
 var acc_struct: acceleration_structure;
 var prev_acc_struct: acceleration_structure;
@@ -41,6 +96,37 @@ var env_map: texture_2d<f32>;
 var sampler_linear: sampler;
 var sampler_nearest: sampler;
 
+const LIGHT_KIND_POINT: u32 = 0u;
+const LIGHT_KIND_SPOT: u32 = 1u;
+const LIGHT_KIND_DIRECTIONAL: u32 = 2u;
+
+struct AnalyticLight {
+    position: vec3<f32>,
+    kind: u32,
+    direction: vec3<f32>,
+    cos_inner: f32,
+    radiance: vec3<f32>,
+    cos_outer: f32,
+    pdf: f32,
+    cdf: f32,
+    pad: vec2<u32>,
+}
+// Light index 0 is reserved for the environment,
+// analytic lights are stored at `light_index - 1`,
+// followed by the emissive triangles.
+var<storage, read> lights: array<AnalyticLight>;
+
+struct EmissiveTriangle {
+    hit_entry: u32,
+    primitive_index: u32,
+    area: f32,
+    pdf: f32,
+    radiance: vec3<f32>,
+    cdf: f32,
+    object_to_world: mat4x3<f32>,
+}
+var<storage, read> emissive_triangles: array<EmissiveTriangle>;
+
 struct StoredReservoir {
     light_uv: vec2<f32>,
     light_index: u32,
@@ -149,9 +235,21 @@ var t_basis: texture_2d<f32>;
 var t_prev_basis: texture_2d<f32>;
 var t_flat_normal: texture_2d<f32>;
 var t_prev_flat_normal: texture_2d<f32>;
+var t_albedo: texture_2d<f32>;
+var t_hit_entry: texture_2d<u32>;
+var t_prev_hit_entry: texture_2d<u32>;
 var t_motion: texture_2d<f32>;
+// Number of samples per pixel in each tile, estimated from the variance
+var t_tile_samples: texture_2d<u32>;
+var t_prev_medium: texture_2d<f32>;
 var out_diffuse: texture_storage_2d<rgba16float, write>;
+// In-scattered radiance of the medium, and the transmittance to the surface
+var out_medium: texture_storage_2d<rgba16float, write>;
 var out_debug: texture_storage_2d<rgba8unorm, write>;
+// Radiance of the probes projected onto the spherical harmonics,
+// with each coefficient in a separate slab of the grid along Z.
+var t_probes: texture_3d<f32>;
+var<storage, read_write> out_probes: array<vec4<f32>>;
 
 fn sample_circle(random: f32) -> vec2<f32> {
     let angle = 2.0 * PI * random;
@@ -162,21 +260,23 @@ fn square(v: f32) -> f32 {
     return v * v;
 }
 
+// The equirectangular mapping of the environment map,
+// which is rotated around the vertical axis by `environment_yaw`.
 fn map_equirect_dir_to_uv(dir: vec3<f32>) -> vec2<f32> {
     //Note: Y axis is up
     let yaw = asin(dir.y);
-    let pitch = atan2(dir.x, dir.z);
-    return vec2<f32>(pitch + PI, -2.0 * yaw + PI) / (2.0 * PI);
+    let pitch = atan2(dir.x, dir.z) - parameters.environment_yaw;
+    return vec2<f32>(fract((pitch + PI) / (2.0 * PI)), (-2.0 * yaw + PI) / (2.0 * PI));
 }
 fn map_equirect_uv_to_dir(uv: vec2<f32>) -> vec3<f32> {
     let yaw = PI * (0.5 - uv.y);
-    let pitch = 2.0 * PI * (uv.x - 0.5);
+    let pitch = 2.0 * PI * (uv.x - 0.5) + parameters.environment_yaw;
     return vec3<f32>(cos(yaw) * sin(pitch), sin(yaw), cos(yaw) * cos(pitch));
 }
 
 fn evaluate_environment(dir: vec3<f32>) -> vec3<f32> {
     let uv = map_equirect_dir_to_uv(dir);
-    return textureSampleLevel(env_map, sampler_linear, uv, 0.0).xyz;
+    return parameters.environment_intensity * textureSampleLevel(env_map, sampler_linear, uv, 0.0).xyz;
 }
 
 fn sample_light_from_sphere(rng: ptr<function, RandomState>) -> LightSample {
@@ -187,7 +287,7 @@ fn sample_light_from_sphere(rng: ptr<function, RandomState>) -> LightSample {
     var ls = LightSample();
     ls.uv = map_equirect_dir_to_uv(dir);
     ls.pdf = 1.0 / (4.0 * PI);
-    ls.radiance = textureSampleLevel(env_map, sampler_linear, ls.uv, 0.0).xyz;
+    ls.radiance = parameters.environment_intensity * textureSampleLevel(env_map, sampler_linear, ls.uv, 0.0).xyz;
     return ls;
 }
 
@@ -197,7 +297,7 @@ fn sample_light_from_environment(rng: ptr<function, RandomState>) -> LightSample
     var ls = LightSample();
     ls.pdf = es.pdf;
     // sample the incoming radiance
-    ls.radiance = textureLoad(env_map, es.pixel, 0).xyz;
+    ls.radiance = parameters.environment_intensity * textureLoad(env_map, es.pixel, 0).xyz;
     // for determining direction - offset randomly within the texel
     // this offset has to be uniformly distributed across the surface of the texel
     let u = (f32(es.pixel.x) + random_gen(rng)) / f32(dim.x);
@@ -207,11 +307,167 @@ fn sample_light_from_environment(rng: ptr<function, RandomState>) -> LightSample
     return ls;
 }
 
+fn make_orthonormal_basis(n: vec3<f32>) -> mat3x3<f32> {
+    // "Building an Orthonormal Basis, Revisited" by Duff et al.
+    let s = select(-1.0, 1.0, n.z >= 0.0);
+    let a = -1.0 / (s + n.z);
+    let b = n.x * n.y * a;
+    let t = vec3<f32>(1.0 + s * n.x * n.x * a, s * b, -s * n.x);
+    let bt = vec3<f32>(b, s + n.y * n.y * a, -n.y);
+    return mat3x3<f32>(t, bt, n);
+}
+
+struct LightRay {
+    direction: vec3<f32>,
+    distance: f32,
+    radiance: vec3<f32>,
+}
+
+fn evaluate_analytic_light(light: AnalyticLight, position: vec3<f32>, uv: vec2<f32>) -> LightRay {
+    var ray = LightRay();
+    if (light.kind == LIGHT_KIND_DIRECTIONAL) {
+        // pick a direction within the cone covered by the light disk
+        let cos_theta = mix(1.0, light.cos_outer, uv.x);
+        let sin_theta = sqrt(max(0.0, 1.0 - square(cos_theta)));
+        let local = vec3<f32>(sin_theta * sample_circle(uv.y), cos_theta);
+        ray.direction = make_orthonormal_basis(-light.direction) * local;
+        ray.distance = camera.depth;
+        ray.radiance = light.radiance;
+    } else {
+        let offset = light.position - position;
+        ray.distance = length(offset);
+        ray.direction = offset / max(ray.distance, 0.0001);
+        var falloff = 1.0;
+        if (light.kind == LIGHT_KIND_SPOT) {
+            let cos_angle = dot(-ray.direction, light.direction);
+            falloff = select(
+                step(light.cos_outer, cos_angle),
+                smoothstep(light.cos_outer, light.cos_inner, cos_angle),
+                light.cos_inner > light.cos_outer);
+        }
+        ray.radiance = light.radiance * falloff / max(square(ray.distance), 0.0001);
+    }
+    return ray;
+}
+
+fn evaluate_emissive_triangle(tri: EmissiveTriangle, position: vec3<f32>, uv: vec2<f32>) -> LightRay {
+    let entry = hit_entries[tri.hit_entry];
+    let vertices = fetch_triangle(entry, tri.primitive_index);
+    let positions_object = entry.geometry_to_object * mat3x4(
+        vec4<f32>(vertices[0].pos, 1.0), vec4<f32>(vertices[1].pos, 1.0), vec4<f32>(vertices[2].pos, 1.0)
+    );
+    let positions = tri.object_to_world * mat3x4(
+        vec4<f32>(positions_object[0], 1.0), vec4<f32>(positions_object[1], 1.0), vec4<f32>(positions_object[2], 1.0)
+    );
+    // uniformly distributed point on the triangle
+    let su = sqrt(uv.x);
+    let barycentrics = vec3<f32>(1.0 - su, su * (1.0 - uv.y), su * uv.y);
+    let offset = positions * barycentrics - position;
+    let normal = normalize(cross(positions[1] - positions[0], positions[2] - positions[0]));
+
+    var ray = LightRay();
+    ray.distance = length(offset);
+    ray.direction = offset / max(ray.distance, 0.0001);
+    // Convert from the area measure, accounting for the sampling density of 1/area.
+    // Emitters are double-sided.
+    let cos_light = abs(dot(normal, ray.direction));
+    ray.radiance = tri.radiance * cos_light * tri.area / max(square(ray.distance), 0.0001);
+    // stop the visibility ray before it hits the emitter itself
+    ray.distance *= 0.999;
+    return ray;
+}
+
+fn evaluate_light_ray(position: vec3<f32>, light_index: u32, light_uv: vec2<f32>) -> LightRay {
+    if (light_index == 0u) {
+        let direction = map_equirect_uv_to_dir(light_uv);
+        let radiance = parameters.environment_intensity * textureSampleLevel(env_map, sampler_nearest, light_uv, 0.0).xyz;
+        return LightRay(direction, camera.depth, radiance);
+    }
+    if (light_index <= parameters.light_count) {
+        return evaluate_analytic_light(lights[light_index - 1u], position, light_uv);
+    }
+    let triangle_index = light_index - 1u - parameters.light_count;
+    if (triangle_index < parameters.emissive_count) {
+        return evaluate_emissive_triangle(emissive_triangles[triangle_index], position, light_uv);
+    }
+    return LightRay();
+}
+
+struct AnalyticLightSample {
+    ls: LightSample,
+    light_index: u32,
+    ray: LightRay,
+}
+
+fn sample_analytic_light(rng: ptr<function, RandomState>, position: vec3<f32>) -> AnalyticLightSample {
+    // binary search for the light in the CDF
+    let r = random_gen(rng);
+    var lo = 0u;
+    var hi = parameters.light_count - 1u;
+    while (lo < hi) {
+        let mid = (lo + hi) / 2u;
+        if (r < lights[mid].cdf) {
+            hi = mid;
+        } else {
+            lo = mid + 1u;
+        }
+    }
+    let light = lights[lo];
+    var als = AnalyticLightSample();
+    als.ls.uv = vec2<f32>(random_gen(rng), random_gen(rng));
+    als.ls.pdf = light.pdf;
+    als.light_index = lo + 1u;
+    als.ray = evaluate_analytic_light(light, position, als.ls.uv);
+    als.ls.radiance = als.ray.radiance;
+    return als;
+}
+
+fn sample_emissive_triangle(rng: ptr<function, RandomState>, position: vec3<f32>) -> AnalyticLightSample {
+    // binary search for the triangle in the CDF
+    let r = random_gen(rng);
+    var lo = 0u;
+    var hi = parameters.emissive_count - 1u;
+    while (lo < hi) {
+        let mid = (lo + hi) / 2u;
+        if (r < emissive_triangles[mid].cdf) {
+            hi = mid;
+        } else {
+            lo = mid + 1u;
+        }
+    }
+    let tri = emissive_triangles[lo];
+    var als = AnalyticLightSample();
+    als.ls.uv = vec2<f32>(random_gen(rng), random_gen(rng));
+    als.ls.pdf = tri.pdf;
+    als.light_index = 1u + parameters.light_count + lo;
+    als.ray = evaluate_emissive_triangle(tri, position, als.ls.uv);
+    als.ls.radiance = als.ray.radiance;
+    return als;
+}
+
+fn read_material(pixel: vec2<i32>, hit_entry: u32) -> Material {
+    var material = Material();
+    material.base_color = textureLoad(t_albedo, pixel, 0).xyz;
+    material.ior = 1.5;
+    if (hit_entry != ~0u) {
+        let entry = hit_entries[hit_entry];
+        material.transmission = entry.transmission;
+        material.ior = entry.ior;
+        material.clearcoat = entry.clearcoat;
+        material.clearcoat_roughness = entry.clearcoat_roughness;
+        material.sheen_color = entry.sheen_color;
+        material.sheen_roughness = entry.sheen_roughness;
+    }
+    return material;
+}
+
 fn read_surface(pixel: vec2<i32>) -> Surface {
     var surface: Surface;
     surface.basis = normalize(textureLoad(t_basis, pixel, 0));
     surface.flat_normal = normalize(textureLoad(t_flat_normal, pixel, 0).xyz);
     surface.depth = textureLoad(t_depth, pixel, 0).x;
+    surface.view = -get_camera_ray(camera, pixel).dir;
+    surface.material = read_material(pixel, textureLoad(t_hit_entry, pixel, 0).x);
     return surface;
 }
 
@@ -220,52 +476,84 @@ fn read_prev_surface(pixel: vec2<i32>) -> Surface {
     surface.basis = normalize(textureLoad(t_prev_basis, pixel, 0));
     surface.flat_normal = normalize(textureLoad(t_prev_flat_normal, pixel, 0).xyz);
     surface.depth = textureLoad(t_prev_depth, pixel, 0).x;
+    surface.view = -get_camera_ray(prev_camera, pixel).dir;
+    //Note: the albedo of the previous frame isn't kept, so the current one is used
+    surface.material = read_material(pixel, textureLoad(t_prev_hit_entry, pixel, 0).x);
     return surface;
 }
 
-fn evaluate_brdf(surface: Surface, dir: vec3<f32>) -> f32 {
-    let lambert_brdf = 1.0 / PI;
-    let lambert_term = qrot(qinv(surface.basis), dir).z;
-    //Note: albedo not modulated
-    return lambert_brdf * max(0.0, lambert_term);
+// Light can only reach the back side through the transmission.
+fn is_lit_from(surface: Surface, dir: vec3<f32>) -> bool {
+    return dot(dir, surface.flat_normal) > 0.0 || surface.material.transmission > 0.0;
+}
+
+fn compute_environment_pdf(dir: vec3<f32>) -> f32 {
+    if (parameters.environment_importance_sampling != 0u) {
+        let dim = textureDimensions(env_map, 0);
+        let uv = map_equirect_dir_to_uv(dir);
+        let pixel = min(vec2<i32>(uv * vec2<f32>(dim)), vec2<i32>(dim) - 1);
+        return compute_environment_sample_pdf(pixel, dim);
+    } else {
+        return 1.0 / (4.0 * PI);
+    }
 }
 
 var<private> debug_len: f32;
+var<private> ray_count: u32;
 
-fn check_ray_occluded(acs: acceleration_structure, position: vec3<f32>, direction: vec3<f32>, debug_len: f32, debug_color: u32) -> bool {
+// Returns the fraction of light passing through, where 0 means fully occluded.
+fn evaluate_transmittance(acs: acceleration_structure, position: vec3<f32>, direction: vec3<f32>, t_max: f32, debug_len: f32, debug_color: u32) -> f32 {
+    ray_count += 1u;
     var rq: ray_query;
-    let flags = RAY_FLAG_TERMINATE_ON_FIRST_HIT | RAY_FLAG_CULL_NO_OPAQUE;
+    let flags = RAY_FLAG_TERMINATE_ON_FIRST_HIT;
     rayQueryInitialize(&rq, acs,
-        RayDesc(flags, 0xFFu, parameters.t_start, camera.depth, position, direction)
+        RayDesc(flags, 0xFFu, parameters.t_start, t_max, position, direction)
     );
-    rayQueryProceed(&rq);
+    var transmittance = 1.0;
+    while (rayQueryProceed(&rq)) {
+        let candidate = rayQueryGetCandidateIntersection(&rq);
+        let entry = hit_entries[candidate.instance_custom_data + candidate.geometry_index];
+        var opacity = 1.0;
+        if (entry.alpha_mode != ALPHA_MODE_OPAQUE) {
+            let alpha = sample_alpha(entry, candidate.primitive_index, candidate.barycentrics, sampler_linear);
+            if (entry.alpha_mode == ALPHA_MODE_BLEND) {
+                opacity = alpha;
+            } else {
+                opacity = select(0.0, 1.0, alpha >= entry.alpha_cutoff);
+            }
+        }
+        // thin-walled transmission lets the refracted part of the light through
+        opacity *= 1.0 - entry.transmission * (1.0 - ior_to_reflectance(entry.ior));
+        transmittance *= 1.0 - opacity;
+        if (transmittance <= 0.0) {
+            rayQueryConfirmIntersection(&rq);
+        }
+    }
     let intersection = rayQueryGetCommittedIntersection(&rq);
+    if (intersection.kind != RAY_QUERY_INTERSECTION_NONE) {
+        transmittance = 0.0;
+    }
 
-    let occluded = intersection.kind != RAY_QUERY_INTERSECTION_NONE;
     if (DEBUG_MODE && debug_len > 0.0) {
-        let color = select(0xFFFFFFu, 0x808080u, occluded) & debug_color;
+        let color = select(0x808080u, 0xFFFFFFu, transmittance > 0.0) & debug_color;
         debug_line(position, position + debug_len * direction, color);
     }
-    return occluded;
+    return transmittance;
 }
 
-fn evaluate_reflected_light(surface: Surface, light_index: u32, light_uv: vec2<f32>) -> vec3<f32> {
-    if (light_index != 0u) {
-        return vec3<f32>(0.0);
-    }
-    let direction = map_equirect_uv_to_dir(light_uv);
-    let brdf = evaluate_brdf(surface, direction);
-    if (brdf <= 0.0) {
+fn evaluate_reflected_light(surface: Surface, position: vec3<f32>, light_index: u32, light_uv: vec2<f32>) -> vec3<f32> {
+    let ray = evaluate_light_ray(position, light_index, light_uv);
+    let bsdf = evaluate_bsdf(surface, ray.direction);
+    if (all(bsdf <= vec3<f32>(0.0))) {
         return vec3<f32>(0.0);
     }
     // Note: returns radiance not modulated by albedo
-    let radiance = textureSampleLevel(env_map, sampler_nearest, light_uv, 0.0).xyz;
-    return radiance * brdf;
+    return ray.radiance * bsdf;
 }
 
 fn get_prev_pixel(pixel: vec2<i32>, pos_world: vec3<f32>) -> vec2<f32> {
     if (USE_MOTION_VECTORS && parameters.use_motion_vectors != 0u) {
-        let motion = textureLoad(t_motion, pixel, 0).xy / MOTION_SCALE;
+        let motion = textureLoad(t_motion, pixel, 0).xy;
         return vec2<f32>(pixel) + 0.5 + motion;
     } else {
         return get_projected_pixel_float(prev_camera, pos_world);
@@ -285,48 +573,41 @@ fn estimate_target_score_with_occlusion(
     surface: Surface, position: vec3<f32>, light_index: u32, light_uv: vec2<f32>, acs: acceleration_structure,
     debug_len: f32, debug_color: u32,
 ) -> TargetScore {
-    if (light_index != 0u) {
+    let ray = evaluate_light_ray(position, light_index, light_uv);
+    if (!is_lit_from(surface, ray.direction)) {
         return TargetScore();
     }
-    let direction = map_equirect_uv_to_dir(light_uv);
-    if (dot(direction, surface.flat_normal) <= 0.0) {
-        return TargetScore();
-    }
-    let brdf = evaluate_brdf(surface, direction);
-    if (brdf <= 0.0) {
+    let bsdf = evaluate_bsdf(surface, ray.direction);
+    if (all(bsdf <= vec3<f32>(0.0))) {
         return TargetScore();
     }
 
-    if (check_ray_occluded(acs, position, direction, debug_len, debug_color)) {
+    let transmittance = evaluate_transmittance(acs, position, ray.direction, ray.distance, debug_len, debug_color);
+    if (transmittance <= 0.0) {
         return TargetScore();
     } else {
-        //Note: same as `evaluate_reflected_light`
-        let radiance = textureSampleLevel(env_map, sampler_nearest, light_uv, 0.0).xyz;
-        return make_target_score(brdf * radiance);
+        //Note: same as `evaluate_reflected_light`, but with the transmittance
+        return make_target_score(bsdf * transmittance * ray.radiance);
     }
 }
 
-fn evaluate_sample(ls: LightSample, surface: Surface, start_pos: vec3<f32>, debug_len: f32, debug_color: u32) -> f32 {
-    let dir = map_equirect_uv_to_dir(ls.uv);
-    if (dot(dir, surface.flat_normal) <= 0.0) {
-        return 0.0;
+fn evaluate_sample(ls: LightSample, dir: vec3<f32>, t_max: f32, surface: Surface, start_pos: vec3<f32>, debug_len: f32, debug_color: u32) -> vec3<f32> {
+    if (!is_lit_from(surface, dir)) {
+        return vec3<f32>(0.0);
     }
 
-    let brdf = evaluate_brdf(surface, dir);
-    if (brdf <= 0.0) {
-        return 0.0;
+    let bsdf = evaluate_bsdf(surface, dir);
+    if (all(bsdf <= vec3<f32>(0.0))) {
+        return vec3<f32>(0.0);
     }
 
     let target_score = compute_target_score(ls.radiance);
     if (target_score < 0.01 * ls.pdf) {
-        return 0.0;
-    }
-
-    if (check_ray_occluded(acc_struct, start_pos, dir, debug_len, debug_color)) {
-        return 0.0;
+        return vec3<f32>(0.0);
     }
 
-    return brdf;
+    let transmittance = evaluate_transmittance(acc_struct, start_pos, dir, t_max, debug_len, debug_color);
+    return bsdf * transmittance;
 }
 
 fn ratio(a: f32, b: f32) -> f32 {
@@ -337,34 +618,90 @@ struct RestirOutput {
     radiance: vec3<f32>,
 }
 
-fn compute_restir(surface: Surface, pixel: vec2<i32>, rng: ptr<function, RandomState>, enable_debug: bool) -> RestirOutput {
-    let ray_dir = get_ray_direction(camera, pixel);
+fn compute_restir(surface: Surface, pixel: vec2<i32>, sample_count: u32, rng: ptr<function, RandomState>, enable_debug: bool) -> RestirOutput {
+    let ray = get_camera_ray(camera, pixel);
     let pixel_index = get_reservoir_index(pixel, camera);
     if (surface.depth == 0.0) {
         reservoirs[pixel_index] = StoredReservoir();
-        let env = evaluate_environment(ray_dir);
+        let env = evaluate_environment(ray.dir);
         return RestirOutput(env);
     }
 
     if (WRITE_DEBUG_IMAGE && debug.view_mode == DebugMode_Depth) {
         textureStore(out_debug, pixel, vec4<f32>(1.0 / surface.depth));
     }
-    let position = camera.position + surface.depth * ray_dir;
+    if (WRITE_DEBUG_IMAGE && debug.view_mode == DebugMode_LinearDepth) {
+        textureStore(out_debug, pixel, vec4<f32>(surface.depth / camera.depth));
+    }
+    let position = ray.origin + surface.depth * ray.dir;
     let normal = qrot(surface.basis, vec3<f32>(0.0, 0.0, 1.0));
     let debug_len = select(0.0, surface.depth * 0.2, enable_debug);
 
+    // Environment, analytic lights, and emissive triangles are disjoint domains, so each
+    // candidate is weighted by the fraction of the candidates taken from its domain.
+    // Extra samples of the adaptive sampling are spent on more candidates.
+    let num_env_samples = sample_count * parameters.num_environment_samples;
+    let num_clearcoat_samples = select(0u, CLEARCOAT_ENVIRONMENT_SAMPLES, num_env_samples != 0u && surface.material.clearcoat > 0.0);
+    let num_light_samples = select(0u, sample_count * parameters.num_light_samples, parameters.light_count != 0u);
+    let num_emissive_samples = select(0u, sample_count * parameters.num_emissive_samples, parameters.emissive_count != 0u);
+    let total_samples = f32(max(1u, num_env_samples + num_clearcoat_samples + num_light_samples + num_emissive_samples));
+
+    // The environment is sampled by two strategies, combined with the balance heuristic.
     var canonical = LiveReservoir();
-    for (var i = 0u; i < parameters.num_environment_samples; i += 1u) {
+    for (var i = 0u; i < num_env_samples + num_clearcoat_samples; i += 1u) {
         var ls: LightSample;
-        if (parameters.environment_importance_sampling != 0u) {
-            ls = sample_light_from_environment(rng);
+        var dir: vec3<f32>;
+        var env_pdf: f32;
+        if (i >= num_env_samples) {
+            dir = sample_clearcoat(surface, vec2<f32>(random_gen(rng), random_gen(rng)));
+            ls.uv = map_equirect_dir_to_uv(dir);
+            ls.radiance = parameters.environment_intensity * textureSampleLevel(env_map, sampler_linear, ls.uv, 0.0).xyz;
+            env_pdf = compute_environment_pdf(dir);
+            ls.pdf = env_pdf;
         } else {
-            ls = sample_light_from_sphere(rng);
+            if (parameters.environment_importance_sampling != 0u) {
+                ls = sample_light_from_environment(rng);
+            } else {
+                ls = sample_light_from_sphere(rng);
+            }
+            dir = map_equirect_uv_to_dir(ls.uv);
+            env_pdf = ls.pdf;
         }
 
-        let brdf = evaluate_sample(ls, surface, position, debug_len, 0x00FF00u);
-        if (brdf > 0.0) {
-            let other = make_reservoir(ls, 0u, vec3<f32>(brdf));
+        let bsdf = evaluate_sample(ls, dir, camera.depth, surface, position, debug_len, 0x00FF00u);
+        if (any(bsdf > vec3<f32>(0.0))) {
+            var clearcoat_pdf = 0.0;
+            if (num_clearcoat_samples != 0u) {
+                clearcoat_pdf = compute_clearcoat_pdf(surface, dir);
+            }
+            ls.pdf = (f32(num_env_samples) * env_pdf + f32(num_clearcoat_samples) * clearcoat_pdf) / total_samples;
+            let other = make_reservoir(ls, 0u, bsdf);
+            merge_reservoir(&canonical, other, random_gen(rng));
+        } else {
+            bump_reservoir(&canonical, 1.0);
+        }
+    }
+
+    let light_fraction = f32(num_light_samples) / total_samples;
+    for (var i = 0u; i < num_light_samples; i += 1u) {
+        var als = sample_analytic_light(rng, position);
+        let bsdf = evaluate_sample(als.ls, als.ray.direction, als.ray.distance, surface, position, debug_len, 0xFFFF00u);
+        if (any(bsdf > vec3<f32>(0.0))) {
+            als.ls.pdf *= light_fraction;
+            let other = make_reservoir(als.ls, als.light_index, bsdf);
+            merge_reservoir(&canonical, other, random_gen(rng));
+        } else {
+            bump_reservoir(&canonical, 1.0);
+        }
+    }
+
+    let emissive_fraction = f32(num_emissive_samples) / total_samples;
+    for (var i = 0u; i < num_emissive_samples; i += 1u) {
+        var als = sample_emissive_triangle(rng, position);
+        let bsdf = evaluate_sample(als.ls, als.ray.direction, als.ray.distance, surface, position, debug_len, 0xFF00FFu);
+        if (any(bsdf > vec3<f32>(0.0))) {
+            als.ls.pdf *= emissive_fraction;
+            let other = make_reservoir(als.ls, als.light_index, bsdf);
             merge_reservoir(&canonical, other, random_gen(rng));
         } else {
             bump_reservoir(&canonical, 1.0);
@@ -430,8 +767,8 @@ fn compute_restir(surface: Surface, pixel: vec2<i32>, rng: ptr<function, RandomS
             let neighbor_history = min(neighbor.confidence, max_confidence);
             {   // scoping this to hint the register allocation
                 let neighbor_surface = read_prev_surface(neighbor_pixel);
-                let neighbor_dir = get_ray_direction(prev_camera, neighbor_pixel);
-                let neighbor_position = prev_camera.position + neighbor_surface.depth * neighbor_dir;
+                let neighbor_ray = get_camera_ray(prev_camera, neighbor_pixel);
+                let neighbor_position = neighbor_ray.origin + neighbor_surface.depth * neighbor_ray.dir;
 
                 let t_canonical_at_neighbor = estimate_target_score_with_occlusion(
                     neighbor_surface, neighbor_position, canonical.selected_light_index, canonical.selected_uv, prev_acc_struct, debug_len, 0xFF0000u);
@@ -451,7 +788,7 @@ fn compute_restir(surface: Surface, pixel: vec2<i32>, rng: ptr<function, RandomS
             other.selected_radiance = t_neighbor_at_canonical.color;
             other.weight_sum = t_neighbor_at_canonical.score * neighbor.contribution_weight * mis_neighbor;
         } else {
-            let radiance = evaluate_reflected_light(surface, neighbor.light_index, neighbor.light_uv);
+            let radiance = evaluate_reflected_light(surface, position, neighbor.light_index, neighbor.light_uv);
             other = unpack_reservoir(neighbor, max_confidence, radiance);
         }
 
@@ -488,6 +825,256 @@ fn compute_restir(surface: Surface, pixel: vec2<i32>, rng: ptr<function, RandomS
     return ro;
 }
 
+fn sample_cosine_hemisphere(normal: vec3<f32>, random: vec2<f32>) -> vec3<f32> {
+    let local = vec3<f32>(sqrt(random.x) * sample_circle(random.y), sqrt(1.0 - random.x));
+    return make_orthonormal_basis(normal) * local;
+}
+
+struct BounceHit {
+    position: vec3<f32>,
+    // faces the incoming ray
+    normal: vec3<f32>,
+    albedo: vec3<f32>,
+}
+
+// Find the closest visible surface along the ray. Returns false on a miss.
+fn trace_bounce(origin: vec3<f32>, dir: vec3<f32>, hit: ptr<function, BounceHit>) -> bool {
+    ray_count += 1u;
+    var rq: ray_query;
+    rayQueryInitialize(&rq, acc_struct, RayDesc(RAY_FLAG_NONE, 0xFFu, parameters.t_start, camera.depth, origin, dir));
+    while (rayQueryProceed(&rq)) {
+        let candidate = rayQueryGetCandidateIntersection(&rq);
+        if (is_candidate_visible(candidate, sampler_linear)) {
+            rayQueryConfirmIntersection(&rq);
+        }
+    }
+    let intersection = rayQueryGetCommittedIntersection(&rq);
+    if (intersection.kind == RAY_QUERY_INTERSECTION_NONE) {
+        return false;
+    }
+
+    let entry = hit_entries[intersection.instance_custom_data + intersection.geometry_index];
+    let vertices = fetch_triangle(entry, intersection.primitive_index);
+    let barycentrics = vec3<f32>(1.0 - intersection.barycentrics.x - intersection.barycentrics.y, intersection.barycentrics);
+    let tex_coords0 = mat3x2(vertices[0].tex_coords, vertices[1].tex_coords, vertices[2].tex_coords) * barycentrics;
+    let tex_coords1 = fetch_tex_coords1(entry, intersection.primitive_index, barycentrics);
+    let tex_coords = map_tex_coords(entry.base_color_tex_coord, entry.base_color_transform, tex_coords0, tex_coords1);
+    let normal_geo = normalize(mat3x3(decode_normal(vertices[0].normal), decode_normal(vertices[1].normal), decode_normal(vertices[2].normal)) * barycentrics);
+    let geo_to_world_rot = normalize(unpack4x8snorm(entry.geometry_to_world_rotation));
+    let normal = qrot(geo_to_world_rot, normal_geo);
+    let base_color_factor = unpack4x8unorm(entry.base_color_factor);
+    let base_color_sample = textureSampleLevel(textures[entry.base_color_texture], sampler_linear, tex_coords, 0.0);
+
+    (*hit).position = origin + intersection.t * dir;
+    (*hit).normal = select(normal, -normal, dot(normal, dir) > 0.0);
+    (*hit).albedo = (base_color_factor * base_color_sample).xyz;
+    return true;
+}
+
+struct AnyLightSample {
+    ray: LightRay,
+    // includes the probability of choosing the domain
+    pdf: f32,
+}
+
+// Sample a single light from a randomly chosen domain.
+fn sample_any_light(position: vec3<f32>, rng: ptr<function, RandomState>) -> AnyLightSample {
+    let env_end = select(0u, 1u, parameters.num_environment_samples != 0u);
+    let lights_end = env_end + select(0u, 1u, parameters.light_count != 0u);
+    let num_domains = lights_end + select(0u, 1u, parameters.emissive_count != 0u);
+    var als = AnyLightSample();
+    if (num_domains == 0u) {
+        return als;
+    }
+
+    let choice = min(u32(random_gen(rng) * f32(num_domains)), num_domains - 1u);
+    if (choice < env_end) {
+        var ls: LightSample;
+        if (parameters.environment_importance_sampling != 0u) {
+            ls = sample_light_from_environment(rng);
+        } else {
+            ls = sample_light_from_sphere(rng);
+        }
+        als.ray = LightRay(map_equirect_uv_to_dir(ls.uv), camera.depth, ls.radiance);
+        als.pdf = ls.pdf;
+    } else if (choice < lights_end) {
+        let sample = sample_analytic_light(rng, position);
+        als.ray = sample.ray;
+        als.pdf = sample.ls.pdf;
+    } else {
+        let sample = sample_emissive_triangle(rng, position);
+        als.ray = sample.ray;
+        als.pdf = sample.ls.pdf;
+    }
+    als.pdf /= f32(num_domains);
+    return als;
+}
+
+// Next event estimation at a diffuse bounce, using a single light sample
+// from a randomly chosen domain.
+fn sample_bounce_lighting(hit: BounceHit, rng: ptr<function, RandomState>) -> vec3<f32> {
+    let als = sample_any_light(hit.position, rng);
+    let cos_theta = dot(hit.normal, als.ray.direction);
+    if (cos_theta <= 0.0 || als.pdf <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    let transmittance = evaluate_transmittance(acc_struct, hit.position, als.ray.direction, als.ray.distance, 0.0, 0u);
+    return hit.albedo * (cos_theta / PI) * transmittance * als.ray.radiance / als.pdf;
+}
+
+// Irradiance of a single probe, arriving at a surface with the given normal.
+fn evaluate_probe_irradiance(cell: vec3<i32>, normal: vec3<f32>) -> vec3<f32> {
+    let slab = vec3<i32>(0, 0, i32(probes.counts.z));
+    let l0 = textureLoad(t_probes, cell, 0).xyz;
+    let l1y = textureLoad(t_probes, cell + slab, 0).xyz;
+    let l1z = textureLoad(t_probes, cell + 2 * slab, 0).xyz;
+    let l1x = textureLoad(t_probes, cell + 3 * slab, 0).xyz;
+    // convolution with the clamped cosine lobe
+    let irradiance = PI * SH_L0 * l0 + (2.0 * PI / 3.0) * SH_L1 * (l1y * normal.y + l1z * normal.z + l1x * normal.x);
+    return max(irradiance, vec3<f32>(0.0));
+}
+
+// Irradiance from the volume, interpolated between the 8 surrounding probes.
+// The probes behind the surface have less weight, reducing the light leaks through the walls.
+fn sample_irradiance_volume(position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let max_cell = vec3<i32>(probes.counts) - 1;
+    let grid_pos = clamp((position - probes.origin) / probes.spacing, vec3<f32>(0.0), vec3<f32>(max_cell));
+    let base = vec3<i32>(floor(grid_pos));
+    let fraction = grid_pos - vec3<f32>(base);
+    var sum = vec4<f32>(0.0);
+    for (var i = 0u; i < 8u; i += 1u) {
+        let offset = vec3<i32>(vec3<u32>(i, i >> 1u, i >> 2u) & vec3<u32>(1u));
+        let cell = min(base + offset, max_cell);
+        let trilinear = select(1.0 - fraction, fraction, offset != vec3<i32>(0));
+        let to_probe = probes.origin + vec3<f32>(cell) * probes.spacing - position;
+        let distance = length(to_probe);
+        let facing = select(1.0, dot(to_probe, normal) / distance, distance > 0.0001);
+        let weight = trilinear.x * trilinear.y * trilinear.z * max(PROBE_MIN_FACING_WEIGHT, square(0.5 * (facing + 1.0)));
+        sum += weight * vec4<f32>(evaluate_probe_irradiance(cell, normal), 1.0);
+    }
+    return sum.xyz / max(sum.w, 0.0001);
+}
+
+// Path traced indirect lighting, demodulated by the albedo of the primary surface.
+// Bounces are treated as diffuse, and the lights are only reached by the
+// next event estimation, so nothing is counted twice with the direct lighting.
+// With an irradiance volume, only the first bounce is traced, and the rest
+// of the path is taken from the volume.
+fn compute_indirect(surface: Surface, position: vec3<f32>, rng: ptr<function, RandomState>) -> vec3<f32> {
+    let normal = qrot(surface.basis, vec3<f32>(0.0, 0.0, 1.0));
+    let dir = sample_cosine_hemisphere(normal, vec2<f32>(random_gen(rng), random_gen(rng)));
+    let pdf = dot(normal, dir) / PI;
+    if (pdf <= 0.0 || !is_lit_from(surface, dir)) {
+        return vec3<f32>(0.0);
+    }
+
+    var throughput = evaluate_bsdf(surface, dir) / pdf;
+    var origin = position;
+    var ray_dir = dir;
+    var radiance = vec3<f32>(0.0);
+    for (var bounce = 1u; bounce <= parameters.max_bounces; bounce += 1u) {
+        var hit: BounceHit;
+        if (!trace_bounce(origin, ray_dir, &hit)) {
+            break;
+        }
+        radiance += throughput * sample_bounce_lighting(hit, rng);
+        if (probes.is_enabled != 0u) {
+            radiance += throughput * hit.albedo * sample_irradiance_volume(hit.position, hit.normal) / PI;
+            break;
+        }
+        if (bounce >= parameters.russian_roulette_start) {
+            let survival = clamp(max(throughput.x, max(throughput.y, throughput.z)), 0.05, 1.0);
+            if (random_gen(rng) >= survival) {
+                break;
+            }
+            throughput /= survival;
+        }
+        // the cosine term cancels out with the sampling density
+        throughput *= hit.albedo;
+        origin = hit.position;
+        ray_dir = sample_cosine_hemisphere(hit.normal, vec2<f32>(random_gen(rng), random_gen(rng)));
+    }
+
+    let score = compute_target_score(radiance);
+    if (parameters.firefly_clamp > 0.0 && score > parameters.firefly_clamp) {
+        radiance *= parameters.firefly_clamp / score;
+    }
+    return radiance;
+}
+
+// Henyey-Greenstein phase function, with the angle between
+// the directions of the light propagation before and after the scattering.
+fn evaluate_phase(cos_theta: f32) -> f32 {
+    let g = medium.anisotropy;
+    let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+    return (1.0 - g * g) / (4.0 * PI * denom * sqrt(denom));
+}
+
+fn get_medium_density(position: vec3<f32>) -> f32 {
+    return exp(-medium.height_falloff * max(position.y - medium.base_height, 0.0));
+}
+
+// Single scattering along the primary ray, up to the given distance.
+// Returns the radiance scattered towards the camera, and the transmittance.
+// The attenuation of the light on its way to the scattering points is ignored.
+fn integrate_medium(ray: CameraRay, distance: f32, rng: ptr<function, RandomState>) -> vec4<f32> {
+    let extinction = medium.absorption + medium.scattering;
+    let step_size = distance / f32(medium.step_count);
+    // every frame covers the steps at a different offset
+    let jitter = random_gen(rng);
+    var transmittance = 1.0;
+    var radiance = vec3<f32>(0.0);
+    for (var i = 0u; i < medium.step_count; i += 1u) {
+        let position = ray.origin + (f32(i) + jitter) * step_size * ray.dir;
+        let step_transmittance = exp(-extinction * get_medium_density(position) * step_size);
+        let als = sample_any_light(position, rng);
+        if (als.pdf > 0.0) {
+            let visibility = evaluate_transmittance(acc_struct, position, als.ray.direction, als.ray.distance, 0.0, 0u);
+            // the scattering integrated over the step, relative to the incoming light
+            let scattered = transmittance * (1.0 - step_transmittance) * medium.scattering / extinction;
+            let phase = evaluate_phase(dot(als.ray.direction, ray.dir));
+            radiance += scattered * phase * visibility * als.ray.radiance / als.pdf;
+        }
+        transmittance *= step_transmittance;
+    }
+    return vec4<f32>(radiance * medium.color, transmittance);
+}
+
+fn compute_medium(surface: Surface, pixel: vec2<i32>, rng: ptr<function, RandomState>) -> vec4<f32> {
+    let ray = get_camera_ray(camera, pixel);
+    var distance = surface.depth;
+    if (surface.depth == 0.0) {
+        let extinction = medium.absorption + medium.scattering;
+        distance = min(camera.depth, MEDIUM_MAX_OPTICAL_DEPTH / extinction);
+    }
+    var result = integrate_medium(ray, distance, rng);
+
+    if (medium.use_history != 0u) {
+        let position = ray.origin + distance * ray.dir;
+        var prev_pixel: vec2<i32>;
+        var prev_depth = 0.0;
+        if (surface.depth == 0.0) {
+            prev_pixel = vec2<i32>(get_projected_pixel_float(prev_camera, position));
+        } else {
+            prev_pixel = vec2<i32>(get_prev_pixel(pixel, position));
+            if (USE_MOTION_VECTORS && parameters.use_motion_vectors != 0u) {
+                prev_depth = textureLoad(t_motion, pixel, 0).z;
+            } else {
+                prev_depth = get_camera_depth(prev_camera, position);
+            }
+        }
+        if (all(prev_pixel >= vec2<i32>(0)) && all(vec2<u32>(prev_pixel) < prev_camera.target_size)) {
+            // a different surface was visible there, such as before a moving object
+            let stored_depth = textureLoad(t_prev_depth, prev_pixel, 0).x;
+            if (abs(stored_depth - prev_depth) <= MEDIUM_DEPTH_TOLERANCE * prev_depth) {
+                let history = textureLoad(t_prev_medium, prev_pixel, 0);
+                result = mix(history, result, MEDIUM_TEMPORAL_WEIGHT);
+            }
+        }
+    }
+    return result;
+}
+
 @compute @workgroup_size(8, 4)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     if (any(global_id.xy >= camera.target_size)) {
@@ -500,9 +1087,41 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let surface = read_surface(vec2<i32>(global_id.xy));
     let enable_debug = DEBUG_MODE && all(global_id.xy == debug.mouse_pos);
     let enable_restir_debug = (debug.draw_flags & DebugDrawFlags_RESTIR) != 0u && enable_debug;
-    let ro = compute_restir(surface, vec2<i32>(global_id.xy), &rng, enable_restir_debug);
+    var sample_count = 1u;
+    if (parameters.use_adaptive_sampling != 0u) {
+        let tile = vec2<i32>(global_id.xy / ADAPTIVE_TILE_SIZE);
+        sample_count = max(1u, textureLoad(t_tile_samples, tile, 0).x);
+    }
+    let ro = compute_restir(surface, vec2<i32>(global_id.xy), sample_count, &rng, enable_restir_debug);
+    var color = ro.radiance;
+    if (parameters.max_bounces != 0u && surface.depth != 0.0) {
+        let ray = get_camera_ray(camera, vec2<i32>(global_id.xy));
+        let position = ray.origin + surface.depth * ray.dir;
+        var indirect = vec3<f32>(0.0);
+        for (var i = 0u; i < sample_count; i += 1u) {
+            indirect += compute_indirect(surface, position, &rng);
+        }
+        color += indirect / f32(sample_count);
+    } else if (probes.is_enabled != 0u && surface.depth != 0.0) {
+        // no bounces are traced, so all of the indirect diffuse comes from the volume
+        let ray = get_camera_ray(camera, vec2<i32>(global_id.xy));
+        let position = ray.origin + surface.depth * ray.dir;
+        let normal = qrot(surface.basis, vec3<f32>(0.0, 0.0, 1.0));
+        let diffuse = (1.0 - surface.material.transmission) / PI;
+        color += diffuse * sample_irradiance_volume(position, normal);
+    }
+
+    var medium_result = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    if (medium.step_count != 0u) {
+        medium_result = compute_medium(surface, vec2<i32>(global_id.xy), &rng);
+    }
+    textureStore(out_medium, global_id.xy, medium_result);
+
+    if (WRITE_DEBUG_IMAGE && debug.view_mode == DebugMode_RayCount) {
+        let heat = debug_heatmap(f32(ray_count) / RAY_COUNT_HEATMAP_MAX);
+        textureStore(out_debug, global_id.xy, vec4<f32>(heat, 1.0));
+    }
 
-    let color = ro.radiance;
     if (enable_debug) {
         debug_buf.variance.color_sum += color;
         debug_buf.variance.color2_sum += color * color;
@@ -510,3 +1129,44 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     }
     textureStore(out_diffuse, global_id.xy, vec4<f32>(color, 1.0));
 }
+
+// Bake the irradiance volume. Every probe gathers the radiance
+// of the environment and of the directly lit surfaces around it,
+// and projects it onto the spherical harmonics.
+@compute @workgroup_size(64)
+fn bake_probes(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let counts = probes.counts;
+    let probe_count = counts.x * counts.y * counts.z;
+    let index = global_id.x;
+    if (index >= probe_count) {
+        return;
+    }
+
+    let cell = vec3<u32>(index % counts.x, (index / counts.x) % counts.y, index / (counts.x * counts.y));
+    let position = probes.origin + vec3<f32>(cell) * probes.spacing;
+    var rng = random_init(index, parameters.frame_index);
+    var coefficients = array<vec3<f32>, PROBE_COEFFICIENTS>();
+    for (var i = 0u; i < probes.rays_per_probe; i += 1u) {
+        // uniformly distributed over the sphere
+        let h = 1.0 - 2.0 * random_gen(&rng);
+        let tangential = sqrt(1.0 - square(h)) * sample_circle(random_gen(&rng));
+        let dir = vec3<f32>(tangential.x, h, tangential.y);
+        var radiance: vec3<f32>;
+        var hit: BounceHit;
+        if (trace_bounce(position, dir, &hit)) {
+            radiance = sample_bounce_lighting(hit, &rng);
+        } else {
+            radiance = evaluate_environment(dir);
+        }
+        coefficients[0] += SH_L0 * radiance;
+        coefficients[1] += SH_L1 * dir.y * radiance;
+        coefficients[2] += SH_L1 * dir.z * radiance;
+        coefficients[3] += SH_L1 * dir.x * radiance;
+    }
+
+    // Monte Carlo estimate with the density of 1 / (4 pi)
+    let scale = 4.0 * PI / f32(max(probes.rays_per_probe, 1u));
+    for (var k = 0u; k < PROBE_COEFFICIENTS; k += 1u) {
+        out_probes[k * probe_count + index] = vec4<f32>(scale * coefficients[k], 1.0);
+    }
+}
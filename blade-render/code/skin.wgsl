@@ -0,0 +1,52 @@
+// Has to match the host!
+struct Vertex {
+    pos: vec3<f32>,
+    bitangent_sign: f32,
+    tex_coords: vec2<f32>,
+    normal: u32,
+    tangent: u32,
+}
+struct SkinVertex {
+    joints: vec4<u32>,
+    weights: vec4<f32>,
+}
+struct SkinParams {
+    vertex_count: u32,
+}
+
+var<uniform> params: SkinParams;
+// Joint transforms, premultiplied with the inverse bind matrices
+var<storage, read> joint_matrices: array<mat4x4<f32>>;
+var<storage, read> rest_vertices: array<Vertex>;
+var<storage, read> skin_vertices: array<SkinVertex>;
+var<storage, read_write> out_vertices: array<Vertex>;
+
+fn decode_normal(raw: u32) -> vec3<f32> {
+    return unpack4x8snorm(raw).xyz;
+}
+fn encode_normal(n: vec3<f32>) -> u32 {
+    return pack4x8snorm(vec4<f32>(n, 0.0));
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.vertex_count) {
+        return;
+    }
+    var vertex = rest_vertices[index];
+    let skin = skin_vertices[index];
+    let total_weight = dot(skin.weights, vec4<f32>(1.0));
+    // Vertices without weights don't belong to skinned geometry
+    if (total_weight > 0.0) {
+        let m = (skin.weights.x * joint_matrices[skin.joints.x] +
+            skin.weights.y * joint_matrices[skin.joints.y] +
+            skin.weights.z * joint_matrices[skin.joints.z] +
+            skin.weights.w * joint_matrices[skin.joints.w]) * (1.0 / total_weight);
+        let m3 = mat3x3<f32>(m[0].xyz, m[1].xyz, m[2].xyz);
+        vertex.pos = (m * vec4<f32>(vertex.pos, 1.0)).xyz;
+        vertex.normal = encode_normal(normalize(m3 * decode_normal(vertex.normal)));
+        vertex.tangent = encode_normal(normalize(m3 * decode_normal(vertex.tangent)));
+    }
+    out_vertices[index] = vertex;
+}
@@ -1,7 +1,21 @@
+// Shading parameters, only read by the ray tracing pass.
+struct Material {
+    base_color: vec3<f32>,
+    transmission: f32,
+    sheen_color: vec3<f32>,
+    sheen_roughness: f32,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    ior: f32,
+}
+
 struct Surface {
     basis: vec4<f32>,
     flat_normal: vec3<f32>,
     depth: f32,
+    // Direction towards the viewer
+    view: vec3<f32>,
+    material: Material,
 }
 
 const SIGMA_N: f32 = 4.0;
@@ -1,11 +1,29 @@
-use blade_asset::AssetManager;
-use std::{path::Path, sync::Arc};
+use blade_asset::{AssetManager, AssetSource, CookLimit, FileSource};
+use std::{path::Path, sync::Arc, time::Duration};
+
+/// Size of the environment map baked from a procedural sky.
+const SKY_SIZE: (u32, u32) = (256, 128);
 
 /// A single hub to manage all assets.
 pub struct AssetHub {
     pub textures: Arc<AssetManager<crate::texture::Baker>>,
     pub models: AssetManager<crate::model::Baker>,
     pub shaders: AssetManager<crate::shader::Baker>,
+    pub irradiance: AssetManager<crate::irradiance::Baker>,
+    cook_limit: Arc<CookLimit>,
+}
+
+/// Assets that got reloaded from their changed sources.
+#[derive(Default)]
+pub struct ReloadedAssets {
+    pub textures: Vec<blade_asset::Handle<crate::Texture>>,
+    pub models: Vec<blade_asset::Handle<crate::Model>>,
+}
+
+impl ReloadedAssets {
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty() && self.models.is_empty()
+    }
 }
 
 pub struct LoadContext<'a> {
@@ -15,35 +33,65 @@ pub struct LoadContext<'a> {
 }
 
 impl AssetHub {
-    /// Create a new hub.
+    /// Create a new hub, loading the assets from the file system.
     pub fn new(
         target: &Path,
         choir: &Arc<choir::Choir>,
         gpu_context: &Arc<blade_graphics::Context>,
+    ) -> Self {
+        Self::with_source(target, Arc::new(FileSource), choir, gpu_context)
+    }
+
+    /// Create a new hub, loading the assets from the given source,
+    /// such as a pack or the memory.
+    ///
+    /// The cooked assets are stored in `target`, which should be writable.
+    pub fn with_source(
+        target: &Path,
+        source: Arc<dyn AssetSource>,
+        choir: &Arc<choir::Choir>,
+        gpu_context: &Arc<blade_graphics::Context>,
     ) -> Self {
         let _ = std::fs::create_dir_all(target);
-        let textures = Arc::new(AssetManager::new(
+        let textures = Arc::new(AssetManager::with_source(
             target,
             choir,
             crate::texture::Baker::new(gpu_context),
+            Arc::clone(&source),
         ));
-        let models = AssetManager::new(
+        let models = AssetManager::with_source(
             target,
             choir,
             crate::model::Baker::new(gpu_context, &textures),
+            Arc::clone(&source),
         );
 
         let mut sh_baker = crate::shader::Baker::new(gpu_context);
         sh_baker.register_bool("DEBUG_MODE", cfg!(debug_assertions));
         sh_baker.register_enum::<crate::render::DebugMode>();
+        sh_baker.register_enum::<crate::render::ToneMap>();
         sh_baker.register_bitflags::<crate::render::DebugDrawFlags>();
         sh_baker.register_bitflags::<crate::render::DebugTextureFlags>();
-        let shaders = AssetManager::new(target, choir, sh_baker);
+        let shaders = AssetManager::with_source(target, choir, sh_baker, Arc::clone(&source));
+        let irradiance = AssetManager::with_source(
+            target,
+            choir,
+            crate::irradiance::Baker::new(&textures.baker),
+            source,
+        );
+
+        let cook_limit = Arc::new(CookLimit::new(None));
+        textures.set_cook_limit(&cook_limit);
+        models.set_cook_limit(&cook_limit);
+        shaders.set_cook_limit(&cook_limit);
+        irradiance.set_cook_limit(&cook_limit);
 
         Self {
             textures,
             models,
             shaders,
+            irradiance,
+            cook_limit,
         }
     }
 
@@ -61,8 +109,235 @@ impl AssetHub {
         self.models.baker.flush(command_encoder, temp_buffers);
     }
 
+    /// Set the compression of the cooked assets written from now on.
+    pub fn set_compression(&self, compression: blade_asset::Compression) {
+        self.textures.set_compression(compression);
+        self.models.set_compression(compression);
+        self.shaders.set_compression(compression);
+        self.irradiance.set_compression(compression);
+    }
+
+    /// Limit the number of assets cooking at the same time, across all the kinds.
+    ///
+    /// The cooks over the limit wait in a queue without occupying the workers,
+    /// so that a large batch of loads doesn't starve the other tasks of the `Choir`.
+    /// The cooked files are the same regardless of the limit.
+    pub fn set_concurrency(&self, max_cooks: Option<usize>) {
+        self.cook_limit.set_max(max_cooks);
+    }
+
+    /// Return the task pool running the loading and cooking,
+    /// which the application can share for its own tasks.
+    pub fn choir(&self) -> &Arc<choir::Choir> {
+        &self.shaders.choir
+    }
+
+    /// Start or stop watching the sources of the loaded textures and models.
+    ///
+    /// The changed assets are cooked again in the background,
+    /// after their sources stay unchanged for the `debounce` duration.
+    pub fn set_watching(&self, debounce: Option<Duration>) {
+        self.textures.set_watching(debounce);
+        self.models.set_watching(debounce);
+    }
+
+    /// Swap in the textures and models that finished reloading.
+    ///
+    /// The handles stay valid, so the renderers pick up the new data
+    /// on the next scene update. The GPU uploads are recorded by the next `flush`,
+    /// and the old resources are put into `temp`.
+    #[profiling::function]
+    pub fn take_reloaded(&self, temp: &mut crate::FrameResources) -> ReloadedAssets {
+        let mut reloaded = ReloadedAssets::default();
+        for (handle, old) in self.textures.take_reloaded() {
+            self.retire_texture(handle, old, temp);
+            reloaded.textures.push(handle);
+        }
+        for (handle, old) in self.models.take_reloaded() {
+            // The new model holds its own references to the textures
+            self.retire_model(old, temp);
+            reloaded.models.push(handle);
+        }
+        reloaded
+    }
+
+    /// Unload the assets that are no longer referenced.
+    ///
+    /// Textures, models, and irradiance volumes are referenced by `load` calls,
+    /// and released with `release` on their managers. Evicted models release
+    /// their textures in turn. The GPU resources are put into `temp`, and
+    /// loading an evicted asset again goes through the cooked cache.
+    #[profiling::function]
+    pub fn evict(&self, temp: &mut crate::FrameResources) {
+        for (_, model) in self.models.evict() {
+            self.retire_model(model, temp);
+        }
+        for (_, volume) in self.irradiance.evict() {
+            self.retire_texture_object(volume.texture, temp);
+        }
+        for (handle, texture) in self.textures.evict() {
+            self.retire_texture(handle, texture, temp);
+        }
+        for (_, shader) in self.shaders.evict() {
+            blade_asset::Baker::delete(&*self.shaders.baker, shader);
+        }
+    }
+
+    fn retire_texture(
+        &self,
+        handle: blade_asset::Handle<crate::Texture>,
+        texture: crate::Texture,
+        temp: &mut crate::FrameResources,
+    ) {
+        self.textures.baker.forget_streamed(handle, temp);
+        self.retire_texture_object(texture, temp);
+    }
+
+    fn retire_texture_object(&self, texture: crate::Texture, temp: &mut crate::FrameResources) {
+        temp.textures.push(texture.object);
+        temp.texture_views.push(texture.view);
+    }
+
+    fn retire_model(&self, model: crate::Model, temp: &mut crate::FrameResources) {
+        for texture in model.textures() {
+            self.textures.release(texture);
+        }
+        if model.acceleration_structure != blade_graphics::AccelerationStructure::default() {
+            temp.acceleration_structures
+                .push(model.acceleration_structure);
+        }
+        temp.buffers.push(model.vertex_buffer);
+        if model.skin_buffer != blade_graphics::Buffer::default() {
+            temp.buffers.push(model.skin_buffer);
+        }
+        if model.tex_coords1_buffer != blade_graphics::Buffer::default() {
+            temp.buffers.push(model.tex_coords1_buffer);
+        }
+        temp.buffers.push(model.index_buffer);
+        temp.buffers.push(model.transform_buffer);
+    }
+
+    /// Enable streaming of the texture mips within the given budget, or disable it with `None`.
+    ///
+    /// Only affects the textures loaded afterwards. These start with only the mip tail
+    /// resident, and the higher mips are streamed in by `stream_textures`.
+    pub fn set_texture_streaming(&self, config: Option<crate::texture::StreamingConfig>) {
+        self.textures.baker.set_streaming(config);
+    }
+
+    /// Return the view of a texture with all of its currently resident mips.
+    ///
+    /// The view of a streamed texture changes as the mips are streamed in and out,
+    /// so it shouldn't be retained across the frames.
+    pub fn texture_view(
+        &self,
+        handle: blade_asset::Handle<crate::Texture>,
+    ) -> blade_graphics::TextureView {
+        self.textures
+            .baker
+            .resident_view(handle, &self.textures[handle])
+    }
+
+    /// Request the mips of the object textures, based on their size on the screen.
+    ///
+    /// Each texture is assumed to be stretched over the bounding sphere of the model once.
+    #[profiling::function]
+    pub fn request_texture_mips(
+        &self,
+        camera: &crate::Camera,
+        objects: &[crate::Object],
+        screen_height: u32,
+    ) {
+        let mip_bias = match self.textures.baker.streaming_config() {
+            Some(config) => config.mip_bias,
+            None => return,
+        };
+        let projection = glam::Mat4::from(camera.projection_matrix(1.0, 1.0));
+        let is_perspective = projection.z_axis.w != 0.0;
+        let camera_pos = glam::Vec3::from(camera.pos);
+        for object in objects {
+            let model = &self.models[object.model];
+            let t = &object.transform;
+            let origin = glam::Vec3::new(t.x.w, t.y.w, t.z.w);
+            let scale = glam::Vec3::new(t.x.x, t.y.x, t.z.x)
+                .length()
+                .max(glam::Vec3::new(t.x.y, t.y.y, t.z.y).length())
+                .max(glam::Vec3::new(t.x.z, t.y.z, t.z.z).length());
+            let radius = model.radius * scale;
+            // Number of pixels covered by the diameter of the bounding sphere
+            let pixels = radius * projection.y_axis.y * screen_height as f32;
+            let pixels = if is_perspective {
+                let distance = (origin - camera_pos).length() - radius;
+                if distance > 0.0 {
+                    pixels / distance
+                } else {
+                    f32::INFINITY
+                }
+            } else {
+                pixels
+            };
+
+            for material in model.materials.iter() {
+                for handle in [material.base_color_texture, material.normal_texture]
+                    .into_iter()
+                    .flatten()
+                {
+                    let texture = &self.textures[handle];
+                    let texels = texture.extent.width.max(texture.extent.height) as f32;
+                    let mip_level = ((texels / pixels).log2() + mip_bias).max(0.0);
+                    self.textures
+                        .baker
+                        .request_mip(handle, texture, mip_level as u32);
+                }
+            }
+        }
+    }
+
+    /// Stream the texture mips in and out according to the requests of this frame.
+    ///
+    /// Has to be called once per frame, after the requests.
+    #[profiling::function]
+    pub fn stream_textures(
+        &self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        temp: &mut crate::FrameResources,
+    ) {
+        self.textures.baker.stream(command_encoder, temp);
+    }
+
+    /// Create an environment map from a procedural sky.
+    pub fn create_sky(&self, sky: &crate::SkyModel) -> blade_asset::Handle<crate::Texture> {
+        self.textures.insert(self.bake_sky(sky))
+    }
+
+    /// Regenerate the environment map of a procedural sky with new parameters.
+    ///
+    /// The handle stays valid, but the view changes, so the renderers pick up
+    /// the new sky, together with its importance sampling, on the next scene update.
+    /// The upload is recorded by the next `flush`, and the old texture is put into `temp`.
+    pub fn update_sky(
+        &self,
+        handle: blade_asset::Handle<crate::Texture>,
+        sky: &crate::SkyModel,
+        temp: &mut crate::FrameResources,
+    ) {
+        let old = self.textures.replace(handle, self.bake_sky(sky));
+        temp.textures.push(old.object);
+        temp.texture_views.push(old.view);
+    }
+
+    fn bake_sky(&self, sky: &crate::SkyModel) -> crate::Texture {
+        let data = sky.bake(SKY_SIZE.0, SKY_SIZE.1);
+        self.textures
+            .baker
+            .create_hdr_texture("sky", SKY_SIZE.0, SKY_SIZE.1, &data)
+    }
+
     /// Destroy the hub contents.
     pub fn destroy(&mut self) {
+        self.set_watching(None);
+        self.textures.baker.clear_streaming();
+        self.irradiance.clear();
         self.textures.clear();
         self.models.clear();
         self.shaders.clear();
@@ -80,12 +355,27 @@ impl AssetHub {
         }
     }
 
+    /// Report the progress of loading all the assets, without blocking.
+    ///
+    /// Can be called every frame to show a progress bar,
+    /// as well as the errors of the assets that failed to cook.
+    #[profiling::function]
+    pub fn progress(&self) -> blade_asset::Progress {
+        let mut progress = blade_asset::Progress::default();
+        self.textures.collect_progress(&mut progress);
+        self.models.collect_progress(&mut progress);
+        self.shaders.collect_progress(&mut progress);
+        self.irradiance.collect_progress(&mut progress);
+        progress
+    }
+
     #[profiling::function]
     pub fn list_running_tasks(&self) -> Vec<choir::RunningTask> {
         let mut list = Vec::new();
         self.textures.list_running_tasks(&mut list);
         self.models.list_running_tasks(&mut list);
         self.shaders.list_running_tasks(&mut list);
+        self.irradiance.list_running_tasks(&mut list);
         list
     }
 }
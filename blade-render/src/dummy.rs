@@ -8,6 +8,8 @@ pub struct DummyResources {
     pub black_view: blade_graphics::TextureView,
     pub red_texture: blade_graphics::Texture,
     pub red_view: blade_graphics::TextureView,
+    pub black_volume_texture: blade_graphics::Texture,
+    pub black_volume_view: blade_graphics::TextureView,
     staging_buf: blade_graphics::Buffer,
 }
 
@@ -81,10 +83,31 @@ impl DummyResources {
                 subresources: &blade_graphics::TextureSubresources::default(),
             },
         );
+        let black_volume_texture = gpu.create_texture(blade_graphics::TextureDesc {
+            name: "dummy/black-volume",
+            format: blade_graphics::TextureFormat::Rgba8Unorm,
+            size,
+            array_layer_count: 1,
+            mip_level_count: 1,
+            dimension: blade_graphics::TextureDimension::D3,
+            usage: blade_graphics::TextureUsage::COPY | blade_graphics::TextureUsage::RESOURCE,
+            sample_count: 1,
+            external: None,
+        });
+        let black_volume_view = gpu.create_texture_view(
+            black_volume_texture,
+            blade_graphics::TextureViewDesc {
+                name: "dummy/black-volume",
+                format: blade_graphics::TextureFormat::Rgba8Unorm,
+                dimension: blade_graphics::ViewDimension::D3,
+                subresources: &blade_graphics::TextureSubresources::default(),
+            },
+        );
 
         command_encoder.init_texture(white_texture);
         command_encoder.init_texture(black_texture);
         command_encoder.init_texture(red_texture);
+        command_encoder.init_texture(black_volume_texture);
         let mut transfers = command_encoder.transfer("init dummy");
         let staging_buf = gpu.create_buffer(blade_graphics::BufferDesc {
             name: "dummy/staging",
@@ -100,6 +123,7 @@ impl DummyResources {
         transfers.copy_buffer_to_texture(staging_buf.at(0), 4, white_texture.into(), size);
         transfers.copy_buffer_to_texture(staging_buf.at(4), 4, black_texture.into(), size);
         transfers.copy_buffer_to_texture(staging_buf.at(8), 4, red_texture.into(), size);
+        transfers.copy_buffer_to_texture(staging_buf.at(4), 4, black_volume_texture.into(), size);
 
         Self {
             size,
@@ -109,6 +133,8 @@ impl DummyResources {
             black_view,
             red_texture,
             red_view,
+            black_volume_texture,
+            black_volume_view,
             staging_buf,
         }
     }
@@ -120,6 +146,8 @@ impl DummyResources {
         gpu.destroy_texture(self.black_texture);
         gpu.destroy_texture_view(self.red_view);
         gpu.destroy_texture(self.red_texture);
+        gpu.destroy_texture_view(self.black_volume_view);
+        gpu.destroy_texture(self.black_volume_texture);
         gpu.destroy_buffer(self.staging_buf);
     }
 }
@@ -82,19 +82,28 @@ impl EnvironmentMap {
         gpu.destroy_compute_pipeline(&mut self.prepare_pipeline);
     }
 
+    /// Assign a new environment map, rebuilding the importance sampling weights on GPU.
+    ///
+    /// The previous weights are put into `temp`, to be destroyed once the frame is retired.
+    /// Returns true if the environment map has changed.
     pub fn assign(
         &mut self,
         view: blade_graphics::TextureView,
         extent: blade_graphics::Extent,
         encoder: &mut blade_graphics::CommandEncoder,
         gpu: &blade_graphics::Context,
-    ) {
+        temp: &mut crate::FrameResources,
+    ) -> bool {
         if self.main_view == view {
-            return;
+            return false;
         }
         self.main_view = view;
         self.size = extent;
-        self.destroy(gpu);
+        if let Some(weight_texture) = self.weight_texture.take() {
+            temp.textures.push(weight_texture);
+            temp.texture_views.push(self.weight_view);
+        }
+        temp.texture_views.append(&mut self.weight_mips);
 
         let mip_level_count = extent
             .width
@@ -162,5 +171,6 @@ impl EnvironmentMap {
             );
             pass.dispatch(groups);
         }
+        true
     }
 }
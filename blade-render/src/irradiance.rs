@@ -0,0 +1,243 @@
+use std::{f32::consts::PI, fmt, fs, io, path::Path, sync::Arc};
+
+/// Number of the spherical harmonics coefficients per probe, covering the L0 and L1 bands.
+/// Has to match the shaders!
+pub const PROBE_COEFFICIENTS: usize = 4;
+/// Extension of the files produced by `BakedIrradiance::save`.
+pub const FILE_EXTENSION: &str = "irradiance";
+
+const MAGIC: [u8; 4] = *b"BLIV";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 4 * (2 + 3 + 3 + 3);
+// Constant factors of the real spherical harmonics basis
+const SH_L0: f32 = 0.282095;
+const SH_L1: f32 = 0.488603;
+
+/// Regular grid of the light probes, aligned to the world axes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProbeGrid {
+    /// Position of the first probe, at the minimum corner of the grid.
+    pub origin: mint::Vector3<f32>,
+    /// Distance between the neighboring probes along each axis.
+    pub spacing: mint::Vector3<f32>,
+    /// Number of the probes along each axis.
+    pub counts: [u32; 3],
+}
+
+impl ProbeGrid {
+    pub fn probe_count(&self) -> u32 {
+        self.counts.iter().product()
+    }
+
+    /// Return the world position of the probe in the given cell.
+    pub fn probe_position(&self, cell: [u32; 3]) -> mint::Vector3<f32> {
+        mint::Vector3 {
+            x: self.origin.x + cell[0] as f32 * self.spacing.x,
+            y: self.origin.y + cell[1] as f32 * self.spacing.y,
+            z: self.origin.z + cell[2] as f32 * self.spacing.z,
+        }
+    }
+
+    /// Size of the 3D texture holding the grid,
+    /// with the coefficients stacked along the Z axis.
+    pub(crate) fn texture_extent(&self) -> blade_graphics::Extent {
+        blade_graphics::Extent {
+            width: self.counts[0],
+            height: self.counts[1],
+            depth: self.counts[2] * PROBE_COEFFICIENTS as u32,
+        }
+    }
+}
+
+/// Irradiance volume in the system memory, as produced by the baking.
+///
+/// Every probe stores the incoming radiance projected onto the spherical
+/// harmonics up to the L1 band. The coefficients are laid out the same way as
+/// in the 3D texture: each one is a separate slab of the grid, stacked along Z.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BakedIrradiance {
+    pub grid: ProbeGrid,
+    /// RGB values of the coefficients, with the alpha unused.
+    pub coefficients: Vec<[f32; 4]>,
+}
+
+impl BakedIrradiance {
+    /// Return the index of a coefficient of the probe in the given cell.
+    pub fn coefficient_index(&self, cell: [u32; 3], coefficient: usize) -> usize {
+        let [cx, cy, cz] = self.grid.counts;
+        let z = coefficient as u32 * cz + cell[2];
+        ((z * cy + cell[1]) * cx + cell[0]) as usize
+    }
+
+    /// Evaluate the irradiance of the probe in the given cell,
+    /// arriving at a surface with the given normal.
+    pub fn probe_irradiance(&self, cell: [u32; 3], normal: mint::Vector3<f32>) -> [f32; 3] {
+        let n = glam::Vec3::from(normal).normalize_or_zero();
+        let c = |k: usize| glam::Vec4::from(self.coefficients[self.coefficient_index(cell, k)]);
+        // Convolution with the clamped cosine lobe
+        let value =
+            PI * SH_L0 * c(0) + (2.0 * PI / 3.0) * SH_L1 * (c(1) * n.y + c(2) * n.z + c(3) * n.x);
+        [value.x.max(0.0), value.y.max(0.0), value.z.max(0.0)]
+    }
+
+    /// Serialize into the format of the `.irradiance` files.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.coefficients.len() * 16);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        let g = &self.grid;
+        for value in [g.origin.x, g.origin.y, g.origin.z] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [g.spacing.x, g.spacing.y, g.spacing.z] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for count in g.counts {
+            bytes.extend_from_slice(&count.to_le_bytes());
+        }
+        for value in self.coefficients.iter().flatten() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Deserialize from the format of the `.irradiance` files.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        if bytes.len() < HEADER_SIZE {
+            return Err("Truncated header");
+        }
+        if bytes[..4] != MAGIC {
+            return Err("Not an irradiance volume");
+        }
+        let words = bytes[4..]
+            .chunks_exact(4)
+            .map(|chunk| [chunk[0], chunk[1], chunk[2], chunk[3]])
+            .collect::<Vec<_>>();
+        if u32::from_le_bytes(words[0]) != VERSION {
+            return Err("Unsupported version");
+        }
+        let float = |i: usize| f32::from_le_bytes(words[i]);
+        let grid = ProbeGrid {
+            origin: [float(1), float(2), float(3)].into(),
+            spacing: [float(4), float(5), float(6)].into(),
+            counts: [7, 8, 9].map(|i| u32::from_le_bytes(words[i])),
+        };
+        let data = &words[10..];
+        let count = grid.probe_count() as usize * PROBE_COEFFICIENTS;
+        if data.len() != count * 4 {
+            return Err("Mismatched size of the coefficients");
+        }
+        let coefficients = data
+            .chunks_exact(4)
+            .map(|texel| [0, 1, 2, 3].map(|i| f32::from_le_bytes(texel[i])))
+            .collect();
+        Ok(Self { grid, coefficients })
+    }
+
+    /// Save into a file, which can be loaded back by `AssetHub::irradiance`.
+    ///
+    /// The file should have the `.irradiance` extension.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+}
+
+#[derive(blade_macros::Flat)]
+pub struct CookedVolume<'a> {
+    origin: [f32; 3],
+    spacing: [f32; 3],
+    counts: [u32; 3],
+    coefficients: &'a [[f32; 4]],
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Meta;
+impl fmt::Display for Meta {
+    fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// Irradiance volume on the GPU, sampled by the ray tracer.
+pub struct IrradianceVolume {
+    pub grid: ProbeGrid,
+    pub texture: crate::Texture,
+}
+
+pub struct Baker {
+    textures: Arc<crate::texture::Baker>,
+}
+
+impl Baker {
+    pub fn new(textures: &Arc<crate::texture::Baker>) -> Self {
+        Self {
+            textures: Arc::clone(textures),
+        }
+    }
+
+    /// Create a volume directly from the baked data.
+    ///
+    /// The upload is recorded by the next `AssetHub::flush`.
+    pub fn create_volume(&self, baked: &BakedIrradiance) -> IrradianceVolume {
+        self.create_volume_impl(baked.grid, &baked.coefficients)
+    }
+
+    fn create_volume_impl(&self, grid: ProbeGrid, coefficients: &[[f32; 4]]) -> IrradianceVolume {
+        let texture =
+            self.textures
+                .create_volume_texture("irradiance", grid.texture_extent(), coefficients);
+        IrradianceVolume { grid, texture }
+    }
+}
+
+impl blade_asset::Baker for Baker {
+    type Meta = Meta;
+    type Data<'a> = CookedVolume<'a>;
+    type Output = IrradianceVolume;
+
+    fn cook(
+        &self,
+        source: &[u8],
+        extension: &str,
+        _meta: Meta,
+        cooker: Arc<blade_asset::Cooker<Self>>,
+        _exe_context: &choir::ExecutionContext,
+    ) {
+        assert_eq!(
+            extension, FILE_EXTENSION,
+            "Unknown irradiance volume extension"
+        );
+        let baked = match BakedIrradiance::from_bytes(source) {
+            Ok(baked) => baked,
+            Err(e) => panic!("Unable to parse the irradiance volume: {e}"),
+        };
+        let g = baked.grid;
+        cooker.finish(CookedVolume {
+            origin: g.origin.into(),
+            spacing: g.spacing.into(),
+            counts: g.counts,
+            coefficients: &baked.coefficients,
+        });
+    }
+
+    fn serve(
+        &self,
+        cooked: CookedVolume<'_>,
+        _exe_context: &choir::ExecutionContext,
+    ) -> Self::Output {
+        let grid = ProbeGrid {
+            origin: cooked.origin.into(),
+            spacing: cooked.spacing.into(),
+            counts: cooked.counts,
+        };
+        self.create_volume_impl(grid, cooked.coefficients)
+    }
+
+    fn footprint(&self, volume: &Self::Output) -> blade_asset::Footprint {
+        blade_asset::Baker::footprint(&*self.textures, &volume.texture)
+    }
+
+    fn delete(&self, volume: Self::Output) {
+        blade_asset::Baker::delete(&*self.textures, volume.texture);
+    }
+}
@@ -15,18 +15,24 @@
 
 mod dummy;
 mod env_map;
+mod sky;
 pub use dummy::DummyResources;
 pub use env_map::EnvironmentMap;
+pub use sky::SkyModel;
 
 #[cfg(not(any(gles, target_arch = "wasm32")))]
 mod asset_hub;
 #[cfg(not(any(gles, target_arch = "wasm32")))]
+pub mod irradiance;
+#[cfg(not(any(gles, target_arch = "wasm32")))]
 pub mod model;
 #[cfg(not(any(gles, target_arch = "wasm32")))]
 pub mod raster;
 #[cfg(not(any(gles, target_arch = "wasm32")))]
 mod render;
 #[cfg(not(any(gles, target_arch = "wasm32")))]
+mod scene;
+#[cfg(not(any(gles, target_arch = "wasm32")))]
 pub mod shader;
 #[cfg(not(any(gles, target_arch = "wasm32")))]
 pub mod texture;
@@ -36,16 +42,29 @@ pub mod util;
 #[cfg(not(any(gles, target_arch = "wasm32")))]
 pub use asset_hub::*;
 #[cfg(not(any(gles, target_arch = "wasm32")))]
+pub use irradiance::{BakedIrradiance, IrradianceVolume, ProbeGrid};
+#[cfg(not(any(gles, target_arch = "wasm32")))]
 pub use model::{Model, ProceduralGeometry};
 #[cfg(not(any(gles, target_arch = "wasm32")))]
-pub use raster::{RasterConfig, Rasterizer};
+pub use raster::{RasterConfig, Rasterizer, ShadowConfig};
 #[cfg(not(any(gles, target_arch = "wasm32")))]
 pub use render::*;
 #[cfg(not(any(gles, target_arch = "wasm32")))]
+pub use scene::{LightHandle, ObjectHandle, Scene};
+#[cfg(not(any(gles, target_arch = "wasm32")))]
 pub use shader::Shader;
 #[cfg(not(any(gles, target_arch = "wasm32")))]
 pub use texture::Texture;
 
+/// Temporary resources associated with a GPU frame.
+#[derive(Default)]
+pub struct FrameResources {
+    pub buffers: Vec<blade_graphics::Buffer>,
+    pub acceleration_structures: Vec<blade_graphics::AccelerationStructure>,
+    pub textures: Vec<blade_graphics::Texture>,
+    pub texture_views: Vec<blade_graphics::TextureView>,
+}
+
 // Has to match the `Vertex` in shaders
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, bytemuck::Zeroable, bytemuck::Pod)]
@@ -57,6 +76,15 @@ pub struct Vertex {
     pub tangent: u32,
 }
 
+// Has to match the `SkinVertex` in shaders
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Zeroable, bytemuck::Pod)]
+pub struct SkinVertex {
+    /// Indices into the joints of the model.
+    pub joints: [u32; 4],
+    pub weights: [f32; 4],
+}
+
 /// Asymmetric field-of-view angles (in radians).
 /// All angles are positive: left/down are measured from center toward left/down.
 #[derive(Clone, Copy, Debug)]
@@ -67,6 +95,67 @@ pub struct Fov {
     pub down: f32,
 }
 
+/// Thin lens of a physically based camera, producing the depth of field.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Lens {
+    /// Ratio of the focal length to the aperture diameter.
+    /// Zero makes an ideal pinhole camera, with everything in focus.
+    pub f_stop: f32,
+    /// Distance to the plane in focus, along the view direction.
+    pub focus_distance: f32,
+    /// Number of the aperture blades, shaping the bokeh into a polygon.
+    /// Less than 3 makes a round aperture.
+    pub blade_count: u32,
+    /// Rotation of the aperture blades, in radians.
+    pub blade_rotation: f32,
+}
+
+impl Lens {
+    /// Height of the sensor, in meters, matching the full frame 35mm film.
+    pub const SENSOR_HEIGHT: f32 = 0.024;
+
+    /// Radius of the aperture for the given vertical field of view.
+    pub fn aperture_radius(&self, fov_y: f32) -> f32 {
+        if self.f_stop <= 0.0 {
+            return 0.0;
+        }
+        let focal_length = 0.5 * Self::SENSOR_HEIGHT / (0.5 * fov_y).tan();
+        0.5 * focal_length / self.f_stop
+    }
+}
+
+/// Placement of the environment map around the scene.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct EnvironmentConfig {
+    /// Rotation around the vertical axis, in radians.
+    pub yaw: f32,
+    /// Multiplier of the environment radiance.
+    pub intensity: f32,
+}
+
+impl Default for EnvironmentConfig {
+    fn default() -> Self {
+        Self {
+            yaw: 0.0,
+            intensity: 1.0,
+        }
+    }
+}
+
+/// Projection of the view space onto the screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Projection {
+    /// Perspective projection, with the field of view given by
+    /// `Camera::fov_y`, or `Camera::fov` when set.
+    #[default]
+    Perspective,
+    /// Orthographic projection, covering the given extents in world units.
+    Orthographic { width: f32, height: f32 },
+    /// Custom matrix from the view space, looking along -Z, into the clip space
+    /// with the depth range from 0 to 1. Has to be either perspective or affine.
+    Matrix(mint::ColumnMatrix4<f32>),
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Camera {
     pub pos: mint::Vector3<f32>,
@@ -75,6 +164,53 @@ pub struct Camera {
     pub depth: f32,
     /// Per-eye asymmetric FOV. When set, overrides `fov_y` for projection.
     pub fov: Option<Fov>,
+    /// Lens for the depth of field. Pinhole by default.
+    pub lens: Lens,
+    /// Projection onto the screen. Perspective by default.
+    pub projection: Projection,
+}
+
+impl Camera {
+    /// Projection matrix for the rasterization, from the view space
+    /// into the clip space with the depth range from 0 to 1.
+    pub fn projection_matrix(&self, aspect: f32, near: f32) -> mint::ColumnMatrix4<f32> {
+        let far = self.depth;
+        let matrix = match self.projection {
+            Projection::Perspective => match self.fov {
+                // Asymmetric off-center projection for XR
+                Some(fov) => {
+                    let left = -fov.left.tan() * near;
+                    let right = fov.right.tan() * near;
+                    let bottom = -fov.down.tan() * near;
+                    let top = fov.up.tan() * near;
+                    let w = right - left;
+                    let h = top - bottom;
+                    glam::Mat4::from_cols(
+                        glam::Vec4::new(2.0 * near / w, 0.0, 0.0, 0.0),
+                        glam::Vec4::new(0.0, 2.0 * near / h, 0.0, 0.0),
+                        glam::Vec4::new(
+                            (right + left) / w,
+                            (top + bottom) / h,
+                            far / (near - far),
+                            -1.0,
+                        ),
+                        glam::Vec4::new(0.0, 0.0, far * near / (near - far), 0.0),
+                    )
+                }
+                None => glam::Mat4::perspective_rh(self.fov_y, aspect, near, far),
+            },
+            Projection::Orthographic { width, height } => glam::Mat4::orthographic_rh(
+                -0.5 * width,
+                0.5 * width,
+                -0.5 * height,
+                0.5 * height,
+                near,
+                far,
+            ),
+            Projection::Matrix(matrix) => return matrix,
+        };
+        matrix.into()
+    }
 }
 
 #[cfg(not(any(gles, target_arch = "wasm32")))]
@@ -85,8 +221,51 @@ pub struct Object {
     /// Per-object color tint multiplied with the material's base_color_factor.
     /// Default: [1.0, 1.0, 1.0, 1.0] (no tint).
     pub color_tint: [f32; 4],
+    /// Model-space transforms of the joints of a skinned model,
+    /// in the order of `Model::joints`.
+    /// Empty means the rest pose.
+    pub joints: Vec<mint::ColumnMatrix4<f32>>,
+    /// Bit mask of the visibility layers the object belongs to.
+    /// Default: `DEFAULT_LAYERS`.
+    pub layers: u32,
+    /// Adjustments of the materials of this object only.
+    pub material_overrides: MaterialOverrides,
+}
+
+/// Per-object adjustments applied on top of the model materials,
+/// without duplicating the material assets.
+///
+/// All the fields are multipliers, so the default is a no-op.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaterialOverrides {
+    /// Multiplied with the base color factor of the materials.
+    pub base_color_factor: [f32; 4],
+    /// Multiplied with the emissive factor of the materials.
+    /// Only the emissive materials take part in the light sampling.
+    pub emissive_factor: [f32; 3],
+    /// Multiplied with the roughness of the materials.
+    pub roughness_factor: f32,
+    /// Multiplied with the metalness of the materials.
+    /// Only the rasterizer has metals.
+    pub metallic_factor: f32,
+}
+
+impl Default for MaterialOverrides {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0; 4],
+            emissive_factor: [1.0; 3],
+            roughness_factor: 1.0,
+            metallic_factor: 1.0,
+        }
+    }
 }
 
+/// Layers of the objects, unless specified otherwise.
+pub const DEFAULT_LAYERS: u32 = 1;
+/// Mask of all the visibility layers.
+pub const ALL_LAYERS: u32 = !0;
+
 #[cfg(not(any(gles, target_arch = "wasm32")))]
 impl From<blade_asset::Handle<Model>> for Object {
     fn from(model: blade_asset::Handle<Model>) -> Self {
@@ -95,10 +274,41 @@ impl From<blade_asset::Handle<Model>> for Object {
             transform: blade_graphics::IDENTITY_TRANSFORM,
             prev_transform: blade_graphics::IDENTITY_TRANSFORM,
             color_tint: [1.0; 4],
+            joints: Vec::new(),
+            layers: DEFAULT_LAYERS,
+            material_overrides: MaterialOverrides::default(),
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    /// Shines equally in all directions from the position.
+    Point,
+    /// Shines in a cone around the direction.
+    /// Angles are in radians, measured from the direction,
+    /// with a smooth falloff between the inner and the outer one.
+    Spot { inner_angle: f32, outer_angle: f32 },
+    /// Infinitely far light shining along the direction, like the sun.
+    /// Non-zero angular radius (in radians) gives soft shadows.
+    Directional { angular_radius: f32 },
+}
+
+/// Analytic light source.
+#[derive(Clone, Copy, Debug)]
+pub struct Light {
+    pub kind: LightKind,
+    /// Position in world space. Ignored for directional lights.
+    pub position: mint::Vector3<f32>,
+    /// Direction the light is shining at. Ignored for point lights.
+    pub direction: mint::Vector3<f32>,
+    /// Linear color of the light.
+    pub color: [f32; 3],
+    /// Luminous intensity in candela for point and spot lights,
+    /// illuminance in lux for directional lights.
+    pub intensity: f32,
+}
+
 #[cfg(not(any(gles, target_arch = "wasm32")))]
 #[repr(C)]
 #[derive(Clone, Copy, Default, bytemuck::Zeroable, bytemuck::Pod)]
@@ -106,6 +316,81 @@ struct CameraParams {
     position: [f32; 3],
     depth: f32,
     orientation: [f32; 4],
-    fov: [f32; 2],
     target_size: [u32; 2],
+    // Rays for the view space pixel coordinates from -1 to 1,
+    // starting at Z = 0 and having the directions scaled to Z = -1.
+    ray_origin_scale: [f32; 2],
+    ray_origin_offset: [f32; 2],
+    ray_dir_scale: [f32; 2],
+    ray_dir_offset: [f32; 2],
+    lens_radius: f32,
+    focus_distance: f32,
+    aperture_blades: u32,
+    aperture_rotation: f32,
+    lens_seed: u32,
+    pad: u32,
+}
+
+#[cfg(not(any(gles, target_arch = "wasm32")))]
+impl CameraParams {
+    fn new(camera: &Camera, target_size: blade_graphics::Extent) -> Self {
+        let aspect = target_size.width as f32 / target_size.height.max(1) as f32;
+        let (origin_scale, origin_offset, dir_scale, dir_offset) = match camera.projection {
+            Projection::Perspective => {
+                let (scale, offset) = match camera.fov {
+                    Some(fov) => {
+                        let (left, right) = (fov.left.tan(), fov.right.tan());
+                        let (down, up) = (fov.down.tan(), fov.up.tan());
+                        (
+                            [0.5 * (right + left), 0.5 * (up + down)],
+                            [0.5 * (right - left), 0.5 * (up - down)],
+                        )
+                    }
+                    None => {
+                        let half_height = (0.5 * camera.fov_y).tan();
+                        ([half_height * aspect, half_height], [0.0; 2])
+                    }
+                };
+                ([0.0; 2], [0.0; 2], scale, offset)
+            }
+            Projection::Orthographic { width, height } => {
+                ([0.5 * width, 0.5 * height], [0.0; 2], [0.0; 2], [0.0; 2])
+            }
+            Projection::Matrix(matrix) => {
+                let inverse = glam::Mat4::from(matrix).inverse();
+                // Intersect the ray through two unprojected points with Z = 0
+                let ray_at = |x: f32, y: f32| {
+                    let a = inverse.project_point3(glam::Vec3::new(x, y, 0.25));
+                    let b = inverse.project_point3(glam::Vec3::new(x, y, 0.75));
+                    let dir = (b - a) / (a.z - b.z);
+                    (a + a.z * dir, dir)
+                };
+                let (origin, dir) = ray_at(0.0, 0.0);
+                let (origin_x, dir_x) = ray_at(1.0, 0.0);
+                let (origin_y, dir_y) = ray_at(0.0, 1.0);
+                (
+                    [origin_x.x - origin.x, origin_y.y - origin.y],
+                    [origin.x, origin.y],
+                    [dir_x.x - dir.x, dir_y.y - dir.y],
+                    [dir.x, dir.y],
+                )
+            }
+        };
+        Self {
+            position: camera.pos.into(),
+            depth: camera.depth,
+            orientation: camera.rot.into(),
+            target_size: [target_size.width, target_size.height],
+            ray_origin_scale: origin_scale,
+            ray_origin_offset: origin_offset,
+            ray_dir_scale: dir_scale,
+            ray_dir_offset: dir_offset,
+            lens_radius: camera.lens.aperture_radius(camera.fov_y),
+            focus_distance: camera.lens.focus_distance,
+            aperture_blades: camera.lens.blade_count,
+            aperture_rotation: camera.lens.blade_rotation,
+            lens_seed: 0,
+            pad: 0,
+        }
+    }
 }
@@ -0,0 +1,838 @@
+use gltf::json;
+
+pub const EXTENSION: &str = "KHR_draco_mesh_compression";
+
+const METADATA_FLAG: u16 = 0x8000;
+const ENCODER_TRIANGULAR_MESH: u8 = 1;
+const METHOD_SEQUENTIAL: u8 = 0;
+const METHOD_EDGEBREAKER: u8 = 1;
+const SEQUENTIAL_COMPRESSED_INDICES: u8 = 0;
+const SYMBOL_CODING_TAGGED: u8 = 0;
+const SYMBOL_CODING_RAW: u8 = 1;
+const PREDICTION_NONE: i8 = -2;
+const PREDICTION_DIFFERENCE: i8 = 0;
+const TRANSFORM_WRAP: i8 = 1;
+const TRANSFORM_NORMAL_OCTAHEDRON_CANONICALIZED: i8 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DataType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    I64,
+    U64,
+    F32,
+    F64,
+    Bool,
+}
+
+impl DataType {
+    fn from_draco(value: u8) -> Self {
+        match value {
+            1 => Self::I8,
+            2 => Self::U8,
+            3 => Self::I16,
+            4 => Self::U16,
+            5 => Self::I32,
+            6 => Self::U32,
+            7 => Self::I64,
+            8 => Self::U64,
+            9 => Self::F32,
+            10 => Self::F64,
+            11 => Self::Bool,
+            other => panic!("Unknown Draco data type {other}"),
+        }
+    }
+
+    fn read(self, reader: &mut Reader) -> f64 {
+        match self {
+            Self::I8 => reader.u8() as i8 as f64,
+            Self::U8 | Self::Bool => reader.u8() as f64,
+            Self::I16 => i16::from_le_bytes(reader.array()) as f64,
+            Self::U16 => u16::from_le_bytes(reader.array()) as f64,
+            Self::I32 => reader.i32() as f64,
+            Self::U32 => reader.u32() as f64,
+            Self::I64 => i64::from_le_bytes(reader.array()) as f64,
+            Self::U64 => u64::from_le_bytes(reader.array()) as f64,
+            Self::F32 => reader.f32() as f64,
+            Self::F64 => f64::from_le_bytes(reader.array()),
+        }
+    }
+
+    /// Convert a decoded integer into the value of this type.
+    fn cast_integer(self, value: i32) -> f64 {
+        match self {
+            Self::U32 | Self::U64 => value as u32 as f64,
+            _ => value as f64,
+        }
+    }
+}
+
+/// The way attribute values are encoded by the sequential attribute decoders.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ValueCoding {
+    Generic,
+    Integer,
+    Quantization,
+    Normals,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> &'a [u8] {
+        let bytes = self
+            .data
+            .get(self.offset..)
+            .and_then(|rest| rest.get(..count))
+            .expect("Draco stream ends unexpectedly");
+        self.offset += count;
+        bytes
+    }
+
+    fn array<const N: usize>(&mut self) -> [u8; N] {
+        self.bytes(N).try_into().unwrap()
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.bytes(1)[0]
+    }
+
+    fn i8(&mut self) -> i8 {
+        self.u8() as i8
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.array())
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.array())
+    }
+
+    fn i32(&mut self) -> i32 {
+        i32::from_le_bytes(self.array())
+    }
+
+    fn f32(&mut self) -> f32 {
+        f32::from_le_bytes(self.array())
+    }
+
+    fn varint(&mut self) -> u64 {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8();
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+        }
+        panic!("Draco varint is too long")
+    }
+
+    fn skip_metadata(&mut self) {
+        let num_entries = self.varint();
+        for _ in 0..num_entries {
+            let name_length = self.u8() as usize;
+            self.bytes(name_length);
+            let value_length = self.varint() as usize;
+            self.bytes(value_length);
+        }
+        let num_children = self.varint();
+        for _ in 0..num_children {
+            let name_length = self.u8() as usize;
+            self.bytes(name_length);
+            self.skip_metadata();
+        }
+    }
+}
+
+/// Reader of the bits, starting from the least significant bit of every byte.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, count: u32) -> u32 {
+        let mut value = 0;
+        for bit in 0..count {
+            // Reading past the end produces zeroes
+            let byte = self.data.get(self.position / 8).map_or(0, |&b| b as u32);
+            value |= ((byte >> (self.position % 8)) & 1) << bit;
+            self.position += 1;
+        }
+        value
+    }
+}
+
+struct RansDecoder<'a> {
+    data: &'a [u8],
+    offset: usize,
+    state: u32,
+    precision_bits: u32,
+    /// Probability and the cumulative probability of every symbol.
+    probabilities: Vec<(u32, u32)>,
+    /// Symbol for every slot of the precision range.
+    lookup: Vec<u32>,
+}
+
+impl<'a> RansDecoder<'a> {
+    fn new(reader: &mut Reader<'a>, unique_symbols_bit_length: u32) -> Self {
+        let precision_bits = (3 * unique_symbols_bit_length / 2).clamp(12, 20);
+        let precision = 1usize << precision_bits;
+
+        let num_symbols = reader.varint() as usize;
+        assert_ne!(num_symbols, 0, "Draco symbol table is empty");
+        let mut probabilities = Vec::new();
+        let mut lookup = Vec::with_capacity(precision);
+        while probabilities.len() < num_symbols {
+            let data = reader.u8();
+            let token = data & 3;
+            if token == 3 {
+                // Run of the symbols that never appear
+                let count = (data >> 2) as usize + 1;
+                assert!(
+                    probabilities.len() + count <= num_symbols,
+                    "Draco symbol table overflows"
+                );
+                let cumulative = lookup.len() as u32;
+                probabilities.extend((0..count).map(|_| (0, cumulative)));
+            } else {
+                let mut probability = (data >> 2) as u32;
+                for i in 0..token as u32 {
+                    probability |= (reader.u8() as u32) << (8 * (i + 1) - 2);
+                }
+                assert!(
+                    lookup.len() + probability as usize <= precision,
+                    "Draco symbol probabilities exceed the precision"
+                );
+                let symbol = probabilities.len() as u32;
+                probabilities.push((probability, lookup.len() as u32));
+                lookup.resize(lookup.len() + probability as usize, symbol);
+            }
+        }
+        assert_eq!(
+            lookup.len(),
+            precision,
+            "Draco symbol probabilities don't add up"
+        );
+
+        let size = reader.varint() as usize;
+        let data = reader.bytes(size);
+        assert_ne!(size, 0, "Draco symbol data is empty");
+        // The final state is stored at the end, prefixed by its size
+        let prefix_size = (data[size - 1] >> 6) as usize + 1;
+        assert!(prefix_size <= size, "Draco symbol state is truncated");
+        let offset = size - prefix_size;
+        let mut state_bytes = [0; 4];
+        state_bytes[..prefix_size].copy_from_slice(&data[offset..]);
+        let mask = (1u32 << (8 * prefix_size - 2)) - 1;
+        let lower_bound = (precision as u32) * 4;
+        let state = (u32::from_le_bytes(state_bytes) & mask) + lower_bound;
+        assert!(
+            state < lower_bound * 256,
+            "Draco symbol state is out of range"
+        );
+
+        Self {
+            data,
+            offset,
+            state,
+            precision_bits,
+            probabilities,
+            lookup,
+        }
+    }
+
+    fn read(&mut self) -> u32 {
+        let lower_bound = 4 << self.precision_bits;
+        while self.state < lower_bound && self.offset > 0 {
+            self.offset -= 1;
+            self.state = self.state * 256 + self.data[self.offset] as u32;
+        }
+        let quotient = self.state >> self.precision_bits;
+        let remainder = self.state & ((1 << self.precision_bits) - 1);
+        let symbol = self.lookup[remainder as usize];
+        let (probability, cumulative) = self.probabilities[symbol as usize];
+        self.state = quotient * probability + remainder - cumulative;
+        symbol
+    }
+}
+
+fn decode_symbols(reader: &mut Reader, count: usize, num_components: usize) -> Vec<u32> {
+    if count == 0 {
+        return Vec::new();
+    }
+    match reader.u8() {
+        SYMBOL_CODING_TAGGED => {
+            // Bit lengths are entropy coded, followed by the raw bits of the values
+            let mut tags = RansDecoder::new(reader, 5);
+            let mut bits = BitReader {
+                data: &reader.data[reader.offset..],
+                position: 0,
+            };
+            let mut values = Vec::with_capacity(count);
+            while values.len() < count {
+                let bit_length = tags.read();
+                assert!(bit_length <= 32, "Draco symbol is too long");
+                for _ in 0..num_components {
+                    values.push(bits.read(bit_length));
+                }
+            }
+            reader.offset += bits.position.div_ceil(8);
+            values.truncate(count);
+            values
+        }
+        SYMBOL_CODING_RAW => {
+            let bit_length = reader.u8() as u32;
+            assert!(
+                (1..=18).contains(&bit_length),
+                "Invalid Draco symbol bit length {bit_length}"
+            );
+            let mut decoder = RansDecoder::new(reader, bit_length);
+            (0..count).map(|_| decoder.read()).collect()
+        }
+        other => panic!("Unknown Draco symbol coding {other}"),
+    }
+}
+
+fn symbol_to_signed(symbol: u32) -> i32 {
+    let magnitude = (symbol >> 1) as i32;
+    if symbol & 1 == 0 {
+        magnitude
+    } else {
+        -magnitude - 1
+    }
+}
+
+/// Quantized octahedral coordinates of the unit vectors.
+struct Octahedron {
+    max_quantized_value: i32,
+    max_value: i32,
+    center_value: i32,
+}
+
+impl Octahedron {
+    fn new(quantization_bits: u32) -> Self {
+        assert!(
+            (2..=30).contains(&quantization_bits),
+            "Invalid Draco normal quantization bits {quantization_bits}"
+        );
+        let max_quantized_value = (1 << quantization_bits) - 1;
+        let max_value = max_quantized_value - 1;
+        Self {
+            max_quantized_value,
+            max_value,
+            center_value: max_value / 2,
+        }
+    }
+
+    fn is_in_diamond(&self, [s, t]: [i32; 2]) -> bool {
+        s.abs() + t.abs() <= self.center_value
+    }
+
+    /// Reflect the point around the diamond edge, the center being at the origin.
+    fn invert_diamond(&self, [s, t]: [i32; 2]) -> [i32; 2] {
+        let (sign_s, sign_t) = if s >= 0 && t >= 0 {
+            (1, 1)
+        } else if s <= 0 && t <= 0 {
+            (-1, -1)
+        } else {
+            (s.signum(), t.signum())
+        };
+        let corner_s = sign_s * self.center_value;
+        let corner_t = sign_t * self.center_value;
+        let s = s.wrapping_mul(2).wrapping_sub(corner_s);
+        let t = t.wrapping_mul(2).wrapping_sub(corner_t);
+        let (s, t) = if sign_s * sign_t >= 0 {
+            (t.wrapping_neg(), s.wrapping_neg())
+        } else {
+            (t, s)
+        };
+        [s.wrapping_add(corner_s) / 2, t.wrapping_add(corner_t) / 2]
+    }
+
+    fn mod_max(&self, x: i32) -> i32 {
+        if x > self.center_value {
+            x - self.max_quantized_value
+        } else if x < -self.center_value {
+            x + self.max_quantized_value
+        } else {
+            x
+        }
+    }
+
+    fn rotation_count([s, t]: [i32; 2]) -> u32 {
+        match (s.signum(), t.signum()) {
+            (0, 0) => 0,
+            (0, 1) => 3,
+            (0, _) => 1,
+            (1, 0 | 1) => 2,
+            (1, _) => 1,
+            (_, -1 | 0) => 0,
+            (_, _) => 3,
+        }
+    }
+
+    fn rotate([s, t]: [i32; 2], count: u32) -> [i32; 2] {
+        match count {
+            1 => [t, -s],
+            2 => [-s, -t],
+            3 => [-t, s],
+            _ => [s, t],
+        }
+    }
+
+    /// Revert the canonicalized octahedron transform of the prediction correction.
+    fn correct(&self, predicted: [i32; 2], correction: [i32; 2]) -> [i32; 2] {
+        let center = self.center_value;
+        let mut pred = [predicted[0] - center, predicted[1] - center];
+        let in_diamond = self.is_in_diamond(pred);
+        if !in_diamond {
+            pred = self.invert_diamond(pred);
+        }
+        let in_bottom_left = pred == [0, 0] || (pred[0] < 0 && pred[1] <= 0);
+        let rotation_count = Self::rotation_count(pred);
+        if !in_bottom_left {
+            pred = Self::rotate(pred, rotation_count);
+        }
+        let mut orig = [
+            self.mod_max(pred[0].wrapping_add(correction[0])),
+            self.mod_max(pred[1].wrapping_add(correction[1])),
+        ];
+        if !in_bottom_left {
+            orig = Self::rotate(orig, (4 - rotation_count) % 4);
+        }
+        if !in_diamond {
+            orig = self.invert_diamond(orig);
+        }
+        [orig[0] + center, orig[1] + center]
+    }
+
+    fn unit_vector(&self, s: i32, t: i32) -> [f32; 3] {
+        let scale = 2.0 / self.max_value as f32;
+        let mut y = s as f32 * scale - 1.0;
+        let mut z = t as f32 * scale - 1.0;
+        let x = 1.0 - y.abs() - z.abs();
+        // The lower half of the octahedron is folded over the diamond edges
+        let offset = (-x).max(0.0);
+        y += if y < 0.0 { offset } else { -offset };
+        z += if z < 0.0 { offset } else { -offset };
+        let norm_squared = x * x + y * y + z * z;
+        if norm_squared < 1e-6 {
+            [0.0; 3]
+        } else {
+            let d = 1.0 / norm_squared.sqrt();
+            [x * d, y * d, z * d]
+        }
+    }
+}
+
+/// Decode the integer values of an attribute, reverting the prediction.
+fn decode_integers(
+    reader: &mut Reader,
+    num_points: usize,
+    num_components: usize,
+    is_normal: bool,
+) -> Vec<i32> {
+    let method = reader.i8();
+    let transform = if method == PREDICTION_NONE {
+        None
+    } else {
+        assert_eq!(
+            method, PREDICTION_DIFFERENCE,
+            "Draco prediction scheme {method} is not supported"
+        );
+        Some(reader.i8())
+    };
+    let count = num_points * num_components;
+    let symbols = if reader.u8() != 0 {
+        decode_symbols(reader, count, num_components)
+    } else {
+        let num_bytes = reader.u8() as usize;
+        assert!(
+            (1..=4).contains(&num_bytes),
+            "Invalid Draco integer size {num_bytes}"
+        );
+        (0..count)
+            .map(|_| {
+                let mut bytes = [0; 4];
+                bytes[..num_bytes].copy_from_slice(reader.bytes(num_bytes));
+                u32::from_le_bytes(bytes)
+            })
+            .collect()
+    };
+    // Octahedron corrections are always positive
+    let mut values = if transform.is_some() && is_normal {
+        symbols.into_iter().map(|symbol| symbol as i32).collect()
+    } else {
+        symbols
+            .into_iter()
+            .map(symbol_to_signed)
+            .collect::<Vec<_>>()
+    };
+
+    match transform {
+        None => {}
+        Some(TRANSFORM_WRAP) if !is_normal => {
+            let min = reader.i32();
+            let max = reader.i32();
+            assert!(min <= max, "Invalid Draco wrap range");
+            let max_dif = 1i32.wrapping_add(max.wrapping_sub(min));
+            for i in 0..values.len() {
+                let predicted = match i.checked_sub(num_components) {
+                    Some(previous) => values[previous].clamp(min, max),
+                    None => 0i32.clamp(min, max),
+                };
+                let mut value = predicted.wrapping_add(values[i]);
+                if value > max {
+                    value = value.wrapping_sub(max_dif);
+                } else if value < min {
+                    value = value.wrapping_add(max_dif);
+                }
+                values[i] = value;
+            }
+        }
+        Some(TRANSFORM_NORMAL_OCTAHEDRON_CANONICALIZED) if is_normal => {
+            let max_quantized_value = reader.i32();
+            let _center_value = reader.i32();
+            assert!(
+                max_quantized_value > 0 && max_quantized_value % 2 == 1,
+                "Invalid Draco octahedron range {max_quantized_value}"
+            );
+            let octahedron = Octahedron::new(32 - max_quantized_value.leading_zeros());
+            let mut predicted = [0; 2];
+            for pair in values.chunks_exact_mut(2) {
+                let value = octahedron.correct(predicted, [pair[0], pair[1]]);
+                pair.copy_from_slice(&value);
+                predicted = value;
+            }
+        }
+        Some(other) => panic!("Draco prediction transform {other} is not supported"),
+    }
+    values
+}
+
+/// Attribute of the decoded Draco mesh.
+pub struct Attribute {
+    pub unique_id: u32,
+    /// Values of all the points, one after another.
+    pub values: Vec<f64>,
+}
+
+/// Triangle mesh decoded from a Draco stream.
+pub struct Mesh {
+    pub indices: Vec<u32>,
+    pub attributes: Vec<Attribute>,
+}
+
+fn decode_sequential_connectivity(reader: &mut Reader) -> (Vec<u32>, usize) {
+    let num_faces = reader.varint() as usize;
+    let num_points = reader.varint() as usize;
+    let index_count = num_faces * 3;
+    let indices = if reader.u8() == SEQUENTIAL_COMPRESSED_INDICES {
+        // Every index is stored as the signed difference from the previous one
+        let mut last = 0i32;
+        decode_symbols(reader, index_count, 1)
+            .into_iter()
+            .map(|symbol| {
+                let diff = (symbol >> 1) as i32;
+                last = last.wrapping_add(if symbol & 1 != 0 { -diff } else { diff });
+                last as u32
+            })
+            .collect::<Vec<_>>()
+    } else {
+        (0..index_count)
+            .map(|_| match num_points {
+                0..0x100 => reader.u8() as u32,
+                0x100..0x10000 => reader.u16() as u32,
+                0x10000..0x200000 => reader.varint() as u32,
+                _ => reader.u32(),
+            })
+            .collect()
+    };
+    if let Some(&index) = indices.iter().find(|&&index| index as usize >= num_points) {
+        panic!("Draco index {index} is out of {num_points} points");
+    }
+    (indices, num_points)
+}
+
+struct AttributeInfo {
+    data_type: DataType,
+    num_components: usize,
+    unique_id: u32,
+    coding: ValueCoding,
+}
+
+fn decode_attributes(reader: &mut Reader, num_points: usize) -> Vec<Attribute> {
+    let num_decoders = reader.u8();
+    let decoders = (0..num_decoders)
+        .map(|_| {
+            let num_attributes = reader.varint() as usize;
+            let mut infos = (0..num_attributes)
+                .map(|_| {
+                    let _attribute_type = reader.u8();
+                    let data_type = DataType::from_draco(reader.u8());
+                    let num_components = reader.u8() as usize;
+                    let _normalized = reader.u8();
+                    AttributeInfo {
+                        data_type,
+                        num_components,
+                        unique_id: reader.varint() as u32,
+                        coding: ValueCoding::Generic,
+                    }
+                })
+                .collect::<Vec<_>>();
+            for info in infos.iter_mut() {
+                info.coding = match reader.u8() {
+                    0 => ValueCoding::Generic,
+                    1 => ValueCoding::Integer,
+                    2 => ValueCoding::Quantization,
+                    3 => ValueCoding::Normals,
+                    other => panic!("Unknown Draco attribute decoder {other}"),
+                };
+                match info.coding {
+                    ValueCoding::Generic | ValueCoding::Integer => {}
+                    ValueCoding::Quantization => assert_eq!(
+                        info.data_type,
+                        DataType::F32,
+                        "Draco quantization needs float attributes"
+                    ),
+                    ValueCoding::Normals => assert!(
+                        info.data_type == DataType::F32 && info.num_components == 3,
+                        "Draco normals need 3 float components"
+                    ),
+                }
+            }
+            infos
+        })
+        .collect::<Vec<_>>();
+
+    let mut attributes = Vec::new();
+    for infos in decoders {
+        // All the values of the decoder go first, then the data of their transforms
+        let portable = infos
+            .iter()
+            .map(|info| match info.coding {
+                ValueCoding::Generic => Err((0..num_points * info.num_components)
+                    .map(|_| info.data_type.read(reader))
+                    .collect::<Vec<_>>()),
+                ValueCoding::Integer | ValueCoding::Quantization => Ok(decode_integers(
+                    reader,
+                    num_points,
+                    info.num_components,
+                    false,
+                )),
+                ValueCoding::Normals => Ok(decode_integers(reader, num_points, 2, true)),
+            })
+            .collect::<Vec<_>>();
+        for (info, portable) in infos.iter().zip(portable) {
+            let values = match (info.coding, portable) {
+                (_, Err(values)) => values,
+                (ValueCoding::Quantization, Ok(integers)) => {
+                    let min_values = (0..info.num_components)
+                        .map(|_| reader.f32())
+                        .collect::<Vec<_>>();
+                    let range = reader.f32();
+                    let bits = reader.u8() as u32;
+                    assert!(
+                        (1..=30).contains(&bits),
+                        "Invalid Draco quantization bits {bits}"
+                    );
+                    let delta = range / ((1u32 << bits) - 1) as f32;
+                    integers
+                        .iter()
+                        .zip(min_values.iter().cycle())
+                        .map(|(&value, &min)| (value as f32 * delta + min) as f64)
+                        .collect()
+                }
+                (ValueCoding::Normals, Ok(integers)) => {
+                    let octahedron = Octahedron::new(reader.u8() as u32);
+                    integers
+                        .chunks_exact(2)
+                        .flat_map(|st| octahedron.unit_vector(st[0], st[1]))
+                        .map(|value| value as f64)
+                        .collect()
+                }
+                (_, Ok(integers)) => integers
+                    .into_iter()
+                    .map(|value| info.data_type.cast_integer(value))
+                    .collect(),
+            };
+            attributes.push(Attribute {
+                unique_id: info.unique_id,
+                values,
+            });
+        }
+    }
+    attributes
+}
+
+/// Decode a Draco compressed triangle mesh.
+///
+/// Only the sequential connectivity is supported, which is produced
+/// by the encoders at the lowest compression level.
+pub fn decode(data: &[u8]) -> Mesh {
+    let mut reader = Reader { data, offset: 0 };
+    assert_eq!(reader.bytes(5), b"DRACO", "Not a Draco stream");
+    let version = (reader.u8(), reader.u8());
+    assert_eq!(
+        version,
+        (2, 2),
+        "Draco version {}.{} is not supported",
+        version.0,
+        version.1
+    );
+    let encoder_type = reader.u8();
+    assert_eq!(
+        encoder_type, ENCODER_TRIANGULAR_MESH,
+        "Draco stream is not a triangle mesh"
+    );
+    let method = reader.u8();
+    let flags = reader.u16();
+    if flags & METADATA_FLAG != 0 {
+        let num_attribute_metadata = reader.varint();
+        for _ in 0..num_attribute_metadata {
+            let _unique_id = reader.varint();
+            reader.skip_metadata();
+        }
+        reader.skip_metadata();
+    }
+
+    let (indices, num_points) = match method {
+        METHOD_SEQUENTIAL => decode_sequential_connectivity(&mut reader),
+        METHOD_EDGEBREAKER => {
+            panic!("Draco edgebreaker connectivity is not supported, use the sequential encoding")
+        }
+        other => panic!("Unknown Draco encoding method {other}"),
+    };
+    let attributes = decode_attributes(&mut reader, num_points);
+    Mesh {
+        indices,
+        attributes,
+    }
+}
+
+/// Append the values in the format of an accessor, pointing it to them.
+fn write_accessor(
+    root: &mut json::Root,
+    accessor_index: json::Index<json::Accessor>,
+    values: impl ExactSizeIterator<Item = f64>,
+    buffer: json::Index<json::Buffer>,
+    data: &mut Vec<u8>,
+) {
+    use json::{accessor::ComponentType as Ct, validation::Checked};
+
+    let accessor = &root.accessors[accessor_index.value()];
+    let (&Checked::Valid(component_type), &Checked::Valid(ty)) =
+        (&accessor.component_type, &accessor.type_)
+    else {
+        panic!("Invalid accessor {}", accessor_index.value());
+    };
+    assert_eq!(
+        values.len(),
+        accessor.count.0 as usize * ty.multiplicity(),
+        "Draco data doesn't match the accessor {}",
+        accessor_index.value()
+    );
+    while !data.len().is_multiple_of(4) {
+        data.push(0);
+    }
+    let offset = data.len();
+    let component_type = component_type.0;
+    for value in values {
+        match component_type {
+            Ct::I8 => data.push(value as i8 as u8),
+            Ct::U8 => data.push(value as u8),
+            Ct::I16 => data.extend((value as i16).to_le_bytes()),
+            Ct::U16 => data.extend((value as u16).to_le_bytes()),
+            Ct::U32 => data.extend((value as u32).to_le_bytes()),
+            Ct::F32 => data.extend((value as f32).to_le_bytes()),
+        }
+    }
+    let view = root.push(json::buffer::View {
+        buffer,
+        byte_length: (data.len() - offset).into(),
+        byte_offset: Some(offset.into()),
+        byte_stride: None,
+        name: None,
+        target: None,
+        extensions: None,
+        extras: Default::default(),
+    });
+    let accessor = &mut root.accessors[accessor_index.value()];
+    accessor.buffer_view = Some(view);
+    accessor.byte_offset = None;
+}
+
+/// Decode the primitives compressed with `KHR_draco_mesh_compression` into
+/// a new buffer, pointing their accessors to the decoded data.
+pub fn decompress(root: &mut json::Root, buffers: &mut Vec<Vec<u8>>) {
+    let buffer = json::Index::new(buffers.len() as u32);
+    let mut data = Vec::new();
+    for mesh_index in 0..root.meshes.len() {
+        for prim_index in 0..root.meshes[mesh_index].primitives.len() {
+            let primitive = &mut root.meshes[mesh_index].primitives[prim_index];
+            let Some(extension) = primitive
+                .extensions
+                .as_mut()
+                .and_then(|ext| ext.others.remove(EXTENSION))
+            else {
+                continue;
+            };
+            let indices = primitive.indices;
+            let semantics = primitive
+                .attributes
+                .iter()
+                .map(|(semantic, &accessor)| (semantic.to_string(), accessor))
+                .collect::<Vec<_>>();
+
+            let view_index = extension["bufferView"]
+                .as_u64()
+                .expect("Draco extension has no buffer view");
+            let view = &root.buffer_views[view_index as usize];
+            let offset = view.byte_offset.map_or(0, |offset| offset.0 as usize);
+            let end = offset + view.byte_length.0 as usize;
+            let mesh = decode(&buffers[view.buffer.value()][offset..end]);
+
+            if let Some(accessor) = indices {
+                let values = mesh.indices.iter().map(|&index| index as f64);
+                write_accessor(root, accessor, values, buffer, &mut data);
+            }
+            let attributes = extension["attributes"]
+                .as_object()
+                .expect("Draco extension has no attributes");
+            for (semantic, unique_id) in attributes {
+                let Some(&(_, accessor)) = semantics.iter().find(|entry| entry.0 == *semantic)
+                else {
+                    log::warn!("Draco attribute {semantic} is not used by the primitive");
+                    continue;
+                };
+                let attribute = mesh
+                    .attributes
+                    .iter()
+                    .find(|attribute| Some(attribute.unique_id as u64) == unique_id.as_u64())
+                    .unwrap_or_else(|| panic!("Draco attribute {semantic} is not found"));
+                let values = attribute.values.iter().cloned();
+                write_accessor(root, accessor, values, buffer, &mut data);
+            }
+        }
+    }
+
+    root.push(json::Buffer {
+        byte_length: data.len().into(),
+        name: None,
+        uri: None,
+        extensions: None,
+        extras: Default::default(),
+    });
+    buffers.push(data);
+    root.extensions_required.retain(|ext| ext != EXTENSION);
+}
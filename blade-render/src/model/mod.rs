@@ -3,22 +3,34 @@ use std::{
     collections::hash_map::{Entry, HashMap},
     fmt, hash, mem,
     ops::Range,
+    path::PathBuf,
     ptr, str,
     sync::{Arc, Mutex},
 };
 
+#[cfg(feature = "draco")]
+mod draco;
+#[cfg(feature = "asset")]
+mod obj;
+#[cfg(feature = "asset")]
+mod optimize;
+#[cfg(feature = "asset")]
+mod ply;
+
 const PRELOAD_TEXTURES: bool = false;
 
 const META_BASE_COLOR: crate::texture::Meta = crate::texture::Meta {
     format: blade_graphics::TextureFormat::Bc1UnormSrgb,
     generate_mips: true,
     y_flip: false,
+    basis: None,
 };
 const META_NORMAL: crate::texture::Meta = crate::texture::Meta {
     //Note: "texpresso" doesn't know how to produce signed normalized
     format: blade_graphics::TextureFormat::Bc5Unorm,
     generate_mips: false,
     y_flip: false,
+    basis: None,
 };
 
 fn pack4x8snorm(v: [f32; 4]) -> u32 {
@@ -31,6 +43,39 @@ fn encode_normal(v: [f32; 3]) -> u32 {
     pack4x8snorm([v[0], v[1], v[2], 0.0])
 }
 
+/// Radius of the bounding sphere around the model origin,
+/// given the vertices in the geometry space and the geometry transform (row-major 3x4).
+fn bounding_radius(vertices: &[crate::Vertex], transform: &[f32; 12]) -> f32 {
+    vertices
+        .iter()
+        .map(|vertex| {
+            let p = vertex.position;
+            let row = |r: usize| {
+                transform[r * 4] * p[0]
+                    + transform[r * 4 + 1] * p[1]
+                    + transform[r * 4 + 2] * p[2]
+                    + transform[r * 4 + 3]
+            };
+            glam::Vec3::new(row(0), row(1), row(2)).length()
+        })
+        .fold(0.0, f32::max)
+}
+
+fn collect_triangles(vertices: &[crate::Vertex], indices: &[u32]) -> Vec<[[f32; 3]; 3]> {
+    let position = |i: u32| vertices[i as usize].position;
+    if indices.is_empty() {
+        vertices
+            .chunks_exact(3)
+            .map(|tri| [tri[0].position, tri[1].position, tri[2].position])
+            .collect()
+    } else {
+        indices
+            .chunks_exact(3)
+            .map(|tri| [position(tri[0]), position(tri[1]), position(tri[2])])
+            .collect()
+    }
+}
+
 pub struct Geometry {
     pub name: String,
     pub vertex_range: Range<u32>,
@@ -39,15 +84,116 @@ pub struct Geometry {
     pub triangle_count: u32,
     pub transform: blade_graphics::Transform,
     pub material_index: usize,
+    /// Triangle positions in geometry space, if the material is emissive.
+    pub emissive_triangles: Vec<[[f32; 3]; 3]>,
+    /// Vertices are deformed by the joints of the model.
+    pub skinned: bool,
+    /// The second set of texture coordinates is in `Model::tex_coords1_buffer`,
+    /// at the same range as the vertices.
+    pub has_tex_coords1: bool,
+}
+
+pub struct Joint {
+    pub name: String,
+    /// Transforms from the model space into the local space of the joint.
+    pub inverse_bind_matrix: mint::ColumnMatrix4<f32>,
+    /// Model-space transform of the joint in the rest pose.
+    pub rest_transform: mint::ColumnMatrix4<f32>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AlphaMode {
+    #[default]
+    Opaque,
+    /// Surface is visible where the base color alpha is at least the cutoff.
+    Mask { cutoff: f32 },
+    /// Surface is see-through, attenuating the shadow rays by the alpha.
+    Blend,
+}
+
+impl AlphaMode {
+    pub(crate) fn to_raw(self) -> (u32, f32) {
+        match self {
+            Self::Opaque => (0, 0.0),
+            Self::Mask { cutoff } => (1, cutoff),
+            Self::Blend => (2, 0.5),
+        }
+    }
+    fn from_raw(mode: u32, cutoff: f32) -> Self {
+        match mode {
+            1 => Self::Mask { cutoff },
+            2 => Self::Blend,
+            _ => Self::Opaque,
+        }
+    }
+}
+
+/// Mapping of the vertex texture coordinates into a texture of the material.
+#[derive(blade_macros::Flat, Clone, Copy, Debug, PartialEq)]
+pub struct TextureMapping {
+    /// Set of the texture coordinates, either 0 or 1.
+    pub tex_coord: u32,
+    /// Affine transform of the texture coordinates, as the columns of a 3x2 matrix.
+    pub transform: [[f32; 2]; 3],
+}
+
+impl Default for TextureMapping {
+    fn default() -> Self {
+        Self {
+            tex_coord: 0,
+            transform: [[1.0, 0.0], [0.0, 1.0], [0.0, 0.0]],
+        }
+    }
+}
+
+impl TextureMapping {
+    /// Compose the transform out of the offset, the rotation (in radians),
+    /// and the scale, in the order of `KHR_texture_transform`.
+    pub fn new(tex_coord: u32, offset: [f32; 2], rotation: f32, scale: [f32; 2]) -> Self {
+        let (sin, cos) = rotation.sin_cos();
+        Self {
+            tex_coord,
+            transform: [
+                [cos * scale[0], -sin * scale[0]],
+                [sin * scale[1], cos * scale[1]],
+                offset,
+            ],
+        }
+    }
 }
 
 //TODO: move out into a separate asset type
 pub struct Material {
     pub base_color_texture: Option<blade_asset::Handle<crate::Texture>>,
     pub base_color_factor: [f32; 4],
+    pub base_color_mapping: TextureMapping,
     pub normal_texture: Option<blade_asset::Handle<crate::Texture>>,
     pub normal_scale: f32,
-    pub transparent: bool,
+    pub normal_mapping: TextureMapping,
+    pub alpha_mode: AlphaMode,
+    /// Linear emitted radiance, with the emissive strength applied.
+    pub emissive_factor: [f32; 3],
+    /// Fraction of the light passing through a thin-walled surface.
+    pub transmission: f32,
+    /// Index of refraction, affecting the reflected part of the transmitted light.
+    pub ior: f32,
+    /// Strength of the clear coat layer on top of the surface.
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    /// Linear color of the sheen, black if there is none.
+    pub sheen_color: [f32; 3],
+    pub sheen_roughness: f32,
+}
+
+impl Material {
+    pub fn is_emissive(&self) -> bool {
+        self.emissive_factor.iter().any(|&c| c > 0.0)
+    }
+
+    /// Light can't pass through the surface.
+    pub fn is_opaque(&self) -> bool {
+        self.alpha_mode == AlphaMode::Opaque && self.transmission <= 0.0
+    }
 }
 
 pub struct Model {
@@ -55,10 +201,57 @@ pub struct Model {
     pub winding: f32,
     pub geometries: Vec<Geometry>,
     pub materials: Vec<Material>,
+    /// Radius of the bounding sphere around the model origin, in the rest pose.
+    pub radius: f32,
+    /// Joints of all the skins, referenced by `SkinVertex::joints`.
+    pub joints: Vec<Joint>,
     pub vertex_buffer: blade_graphics::Buffer,
+    /// Skinning data for every vertex, if there are any joints.
+    pub skin_buffer: blade_graphics::Buffer,
+    /// Second set of texture coordinates for every vertex,
+    /// if any of the geometries has it.
+    pub tex_coords1_buffer: blade_graphics::Buffer,
     pub index_buffer: blade_graphics::Buffer,
     pub transform_buffer: blade_graphics::Buffer,
     pub acceleration_structure: blade_graphics::AccelerationStructure,
+    /// Total size of the buffers and the acceleration structure.
+    pub gpu_size: u64,
+}
+
+impl Model {
+    /// Iterate over the textures referenced by the materials.
+    pub fn textures(&self) -> impl Iterator<Item = blade_asset::Handle<crate::Texture>> + '_ {
+        self.materials
+            .iter()
+            .flat_map(|material| [material.base_color_texture, material.normal_texture])
+            .flatten()
+    }
+
+    /// Describe the geometries for building the bottom-level acceleration structure.
+    fn acceleration_structure_meshes(&self) -> Vec<blade_graphics::AccelerationStructureMesh> {
+        let vertex_stride = mem::size_of::<crate::Vertex>() as u32;
+        self.geometries
+            .iter()
+            .enumerate()
+            .map(|(index, geometry)| {
+                let transform_offset =
+                    index as u64 * mem::size_of::<blade_graphics::Transform>() as u64;
+                blade_graphics::AccelerationStructureMesh {
+                    vertex_data: self
+                        .vertex_buffer
+                        .at(geometry.vertex_range.start as u64 * vertex_stride as u64),
+                    vertex_format: blade_graphics::VertexFormat::F32Vec3,
+                    vertex_stride,
+                    vertex_count: geometry.vertex_range.end - geometry.vertex_range.start,
+                    index_data: self.index_buffer.at(geometry.index_offset),
+                    index_type: geometry.index_type,
+                    triangle_count: geometry.triangle_count,
+                    transform_data: self.transform_buffer.at(transform_offset),
+                    is_opaque: self.materials[geometry.material_index].is_opaque(),
+                }
+            })
+            .collect()
+    }
 }
 
 #[derive(blade_macros::Flat, Default)]
@@ -73,18 +266,143 @@ struct TextureReference<'a> {
 struct CookedMaterial<'a> {
     base_color: TextureReference<'a>,
     base_color_factor: [f32; 4],
+    base_color_mapping: TextureMapping,
     normal: TextureReference<'a>,
     normal_scale: f32,
-    transparent: bool,
+    normal_mapping: TextureMapping,
+    alpha_mode: u32,
+    alpha_cutoff: f32,
+    emissive_factor: [f32; 3],
+    transmission: f32,
+    ior: f32,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    sheen_color: [f32; 3],
+    sheen_roughness: f32,
+}
+
+#[derive(blade_macros::Flat)]
+struct CookedJoint<'a> {
+    name: Cow<'a, [u8]>,
+    inverse_bind_matrix: [f32; 16],
+    rest_transform: [f32; 16],
 }
 
 #[derive(blade_macros::Flat)]
 struct CookedGeometry<'a> {
     name: Cow<'a, [u8]>,
     vertices: Cow<'a, [crate::Vertex]>,
+    /// Empty if the geometry isn't skinned.
+    skin: Cow<'a, [crate::SkinVertex]>,
     indices: Cow<'a, [u32]>,
     transform: [f32; 12],
     material_index: u32,
+    /// Empty if the material doesn't use the second set of texture coordinates.
+    tex_coords1: Cow<'a, [[f32; 2]]>,
+    /// Vertices in the quantized form, replacing `vertices` if not empty.
+    quantized_vertices: Cow<'a, [QuantizedVertex]>,
+    quantization: Quantization,
+}
+
+/// Vertex with the position and the texture coordinates
+/// quantized into 16-bit integers.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Zeroable, bytemuck::Pod)]
+struct QuantizedVertex {
+    /// Position, followed by the sign of the bitangent.
+    position: [u16; 4],
+    tex_coords: [u16; 2],
+    normal: u32,
+    tangent: u32,
+}
+
+/// Dequantization factors of the vertices.
+#[derive(blade_macros::Flat, Clone, Copy, Debug, Default, PartialEq)]
+struct Quantization {
+    position_offset: [f32; 3],
+    position_scale: [f32; 3],
+    tex_coord_offset: [f32; 2],
+    tex_coord_scale: [f32; 2],
+}
+
+impl Quantization {
+    /// Factors mapping the range of values into the 16-bit integers.
+    #[cfg(feature = "asset")]
+    fn range<const N: usize>(values: impl Iterator<Item = [f32; N]>) -> ([f32; N], [f32; N]) {
+        let mut min = [f32::MAX; N];
+        let mut max = [f32::MIN; N];
+        for value in values {
+            for i in 0..N {
+                min[i] = min[i].min(value[i]);
+                max[i] = max[i].max(value[i]);
+            }
+        }
+        if min[0] > max[0] {
+            return ([0.0; N], [0.0; N]);
+        }
+        let mut scale = [0.0; N];
+        for i in 0..N {
+            scale[i] = (max[i] - min[i]) / u16::MAX as f32;
+        }
+        (min, scale)
+    }
+
+    #[cfg(feature = "asset")]
+    fn quantize<const N: usize>(value: [f32; N], offset: [f32; N], scale: [f32; N]) -> [u16; N] {
+        let mut result = [0; N];
+        for i in 0..N {
+            if scale[i] > 0.0 {
+                result[i] = ((value[i] - offset[i]) / scale[i]).round() as u16;
+            }
+        }
+        result
+    }
+
+    fn dequantize<const N: usize>(value: [u16; N], offset: [f32; N], scale: [f32; N]) -> [f32; N] {
+        let mut result = offset;
+        for i in 0..N {
+            result[i] += value[i] as f32 * scale[i];
+        }
+        result
+    }
+
+    #[cfg(feature = "asset")]
+    fn quantize_vertex(&self, v: &crate::Vertex) -> QuantizedVertex {
+        let [x, y, z] = Self::quantize(v.position, self.position_offset, self.position_scale);
+        QuantizedVertex {
+            position: [x, y, z, (v.bitangent_sign >= 0.0) as u16],
+            tex_coords: Self::quantize(v.tex_coords, self.tex_coord_offset, self.tex_coord_scale),
+            normal: v.normal,
+            tangent: v.tangent,
+        }
+    }
+
+    fn dequantize_vertex(&self, v: &QuantizedVertex) -> crate::Vertex {
+        let [x, y, z, sign] = v.position;
+        crate::Vertex {
+            position: Self::dequantize([x, y, z], self.position_offset, self.position_scale),
+            bitangent_sign: if sign != 0 { 1.0 } else { -1.0 },
+            tex_coords: Self::dequantize(v.tex_coords, self.tex_coord_offset, self.tex_coord_scale),
+            normal: v.normal,
+            tangent: v.tangent,
+        }
+    }
+}
+
+impl CookedGeometry<'_> {
+    /// Vertices in the full precision.
+    fn full_vertices(&self) -> Cow<'_, [crate::Vertex]> {
+        if self.quantized_vertices.is_empty() {
+            Cow::Borrowed(&self.vertices)
+        } else {
+            Cow::Owned(
+                self.quantized_vertices
+                    .iter()
+                    .map(|v| self.quantization.dequantize_vertex(v))
+                    .collect(),
+            )
+        }
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -93,6 +411,9 @@ struct GltfVertex {
     normal: [f32; 3],
     tangent: [f32; 4],
     tex_coords: [f32; 2],
+    tex_coords1: [f32; 2],
+    joints: [u32; 4],
+    weights: [f32; 4],
 }
 impl Default for GltfVertex {
     fn default() -> Self {
@@ -101,6 +422,9 @@ impl Default for GltfVertex {
             normal: [0.0, 1.0, 0.0],
             tangent: [1.0, 0.0, 0.0, 0.0],
             tex_coords: [0.0; 2],
+            tex_coords1: [0.0; 2],
+            joints: [0; 4],
+            weights: [0.0; 4],
         }
     }
 }
@@ -116,43 +440,70 @@ impl hash::Hash for GltfVertex {
         for f in self.tangent.iter() {
             f.to_bits().hash(state);
         }
-        for f in self.tex_coords.iter() {
+        for f in self.tex_coords.iter().chain(self.tex_coords1.iter()) {
+            f.to_bits().hash(state);
+        }
+        self.joints.hash(state);
+        for f in self.weights.iter() {
             f.to_bits().hash(state);
         }
     }
 }
 
 #[cfg(feature = "asset")]
-struct FlattenedGeometry(Box<[GltfVertex]>);
+struct FlattenedGeometry {
+    vertices: Box<[GltfVertex]>,
+    /// The source provided the tangents.
+    has_tangents: bool,
+    skinned: bool,
+    /// The second set of texture coordinates is used by the material.
+    has_tex_coords1: bool,
+    /// Set of the texture coordinates to generate the tangents from.
+    tangent_tex_coord: u32,
+}
+#[cfg(feature = "asset")]
+struct ReconstructedGeometry {
+    indices: Vec<u32>,
+    vertices: Vec<crate::Vertex>,
+    skin: Vec<crate::SkinVertex>,
+    tex_coords1: Vec<[f32; 2]>,
+}
 #[cfg(feature = "asset")]
 impl mikktspace::Geometry for FlattenedGeometry {
     fn num_faces(&self) -> usize {
-        self.0.len() / 3
+        self.vertices.len() / 3
     }
     fn num_vertices_of_face(&self, _face: usize) -> usize {
         3
     }
     fn position(&self, face: usize, vert: usize) -> [f32; 3] {
-        self.0[face * 3 + vert].position
+        self.vertices[face * 3 + vert].position
     }
     fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
-        self.0[face * 3 + vert].normal
+        self.vertices[face * 3 + vert].normal
     }
     fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
-        self.0[face * 3 + vert].tex_coords
+        let v = &self.vertices[face * 3 + vert];
+        if self.tangent_tex_coord == 1 {
+            v.tex_coords1
+        } else {
+            v.tex_coords
+        }
     }
     fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
-        self.0[face * 3 + vert].tangent = tangent;
+        self.vertices[face * 3 + vert].tangent = tangent;
     }
 }
 #[cfg(feature = "asset")]
 impl FlattenedGeometry {
     #[profiling::function]
-    fn reconstruct_indices(self) -> (Vec<u32>, Vec<crate::Vertex>) {
-        let mut indices = Vec::with_capacity(self.0.len());
+    fn reconstruct_indices(self) -> ReconstructedGeometry {
+        let mut indices = Vec::with_capacity(self.vertices.len());
         let mut vertices = Vec::new();
+        let mut skin = Vec::new();
+        let mut tex_coords1 = Vec::new();
         let mut cache = HashMap::new();
-        for v in self.0.iter() {
+        for v in self.vertices.iter() {
             let i = match cache.entry(v.clone()) {
                 Entry::Occupied(e) => *e.get(),
                 Entry::Vacant(e) => {
@@ -165,13 +516,47 @@ impl FlattenedGeometry {
                         normal: encode_normal(v.normal),
                         tangent: encode_normal([t[0], t[1], t[2]]),
                     });
+                    if self.skinned {
+                        skin.push(crate::SkinVertex {
+                            joints: v.joints,
+                            weights: v.weights,
+                        });
+                    }
+                    if self.has_tex_coords1 {
+                        tex_coords1.push(v.tex_coords1);
+                    }
                     *e.insert(i)
                 }
             };
             indices.push(i);
         }
-        log::debug!("Compacted {}->{}", self.0.len(), vertices.len());
-        (indices, vertices)
+        log::debug!("Compacted {}->{}", self.vertices.len(), vertices.len());
+        ReconstructedGeometry {
+            indices,
+            vertices,
+            skin,
+            tex_coords1,
+        }
+    }
+
+    /// Generate smooth normals for the vertices selected by `mask`,
+    /// averaging the area-weighted normals of the triangles around each position.
+    fn generate_normals(&mut self, mask: &[bool], winding: f32) {
+        let mut accumulated = HashMap::<[u32; 3], glam::Vec3>::new();
+        for triangle in self.vertices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| glam::Vec3::from(triangle[i].position));
+            let normal = winding * (b - a).cross(c - a);
+            for v in triangle {
+                *accumulated.entry(v.position.map(f32::to_bits)).or_default() += normal;
+            }
+        }
+        for (v, _) in self.vertices.iter_mut().zip(mask).filter(|&(_, &m)| m) {
+            let normal = accumulated[&v.position.map(f32::to_bits)];
+            v.normal = match normal.try_normalize() {
+                Some(n) => n.into(),
+                None => GltfVertex::default().normal,
+            };
+        }
     }
 }
 
@@ -180,9 +565,93 @@ pub struct CookedModel<'a> {
     name: &'a [u8],
     winding: f32,
     materials: Vec<CookedMaterial<'a>>,
+    joints: Vec<CookedJoint<'a>>,
     geometries: Vec<CookedGeometry<'a>>,
 }
 
+#[cfg(feature = "asset")]
+impl CookedModel<'_> {
+    fn new(front_face: FrontFace) -> Self {
+        Self {
+            name: &[],
+            winding: match front_face {
+                FrontFace::Clockwise => -1.0,
+                FrontFace::CounterClockwise => 1.0,
+            },
+            materials: Vec::new(),
+            joints: Vec::new(),
+            geometries: Vec::new(),
+        }
+    }
+
+    /// Add a geometry, to be filled from the flattened vertices.
+    fn add_flattened(
+        &mut self,
+        name: &str,
+        transform: [f32; 12],
+        material_index: u32,
+        flattened: FlattenedGeometry,
+        flattened_geos: &mut Vec<FlattenedGeometry>,
+    ) {
+        flattened_geos.push(flattened);
+        self.geometries.push(CookedGeometry {
+            name: Cow::Owned(name.as_bytes().to_owned()),
+            vertices: Cow::Borrowed(&[]),
+            skin: Cow::Borrowed(&[]),
+            indices: Cow::Borrowed(&[]),
+            transform,
+            material_index,
+            tex_coords1: Cow::Borrowed(&[]),
+            quantized_vertices: Cow::Borrowed(&[]),
+            quantization: Quantization::default(),
+        });
+    }
+}
+
+#[cfg(feature = "asset")]
+impl CookedMaterial<'_> {
+    /// Opaque material of a single color, used by the formats without materials.
+    fn plain(base_color_factor: [f32; 4]) -> Self {
+        Self {
+            base_color: TextureReference {
+                source_index: !0,
+                ..Default::default()
+            },
+            base_color_factor,
+            base_color_mapping: TextureMapping::default(),
+            normal: TextureReference {
+                source_index: !0,
+                ..Default::default()
+            },
+            normal_scale: 0.0,
+            normal_mapping: TextureMapping::default(),
+            alpha_mode: 0,
+            alpha_cutoff: 0.5,
+            emissive_factor: [0.0; 3],
+            transmission: 0.0,
+            ior: 1.5,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.0,
+            sheen_color: [0.0; 3],
+            sheen_roughness: 0.0,
+        }
+    }
+}
+
+#[cfg(feature = "asset")]
+fn compute_global_transforms(
+    g_node: gltf::Node,
+    parent_transform: glam::Mat4,
+    global_transforms: &mut [glam::Mat4],
+) {
+    let local_transform = glam::Mat4::from_cols_array_2d(&g_node.transform().matrix());
+    let global_transform = parent_transform * local_transform;
+    global_transforms[g_node.index()] = global_transform;
+    for child in g_node.children() {
+        compute_global_transforms(child, global_transform, global_transforms);
+    }
+}
+
 #[cfg(feature = "asset")]
 impl CookedModel<'_> {
     fn populate_gltf(
@@ -190,6 +659,7 @@ impl CookedModel<'_> {
         g_node: gltf::Node,
         parent_transform: glam::Mat4,
         data_buffers: &[Vec<u8>],
+        skin_joint_offsets: &[u32],
         flattened_geos: &mut Vec<FlattenedGeometry>,
     ) {
         let local_transform = glam::Mat4::from_cols_array_2d(&g_node.transform().matrix());
@@ -197,11 +667,19 @@ impl CookedModel<'_> {
 
         if let Some(g_mesh) = g_node.mesh() {
             let name = g_node.name().unwrap_or("");
+            // Skinned meshes are positioned by the joints, ignoring the node transform
+            let joint_offset = g_node
+                .skin()
+                .map(|g_skin| skin_joint_offsets[g_skin.index()]);
+            let mesh_transform = match joint_offset {
+                Some(_) => glam::Mat4::IDENTITY,
+                None => global_transform,
+            };
             let col_matrix = mint::ColumnMatrix3x4 {
-                x: global_transform.x_axis.truncate().into(),
-                y: global_transform.y_axis.truncate().into(),
-                z: global_transform.z_axis.truncate().into(),
-                w: global_transform.w_axis.truncate().into(),
+                x: mesh_transform.x_axis.truncate().into(),
+                y: mesh_transform.y_axis.truncate().into(),
+                z: mesh_transform.z_axis.truncate().into(),
+                w: mesh_transform.w_axis.truncate().into(),
             };
             let transform = mint::RowMatrix3x4::from(col_matrix).into();
 
@@ -231,7 +709,7 @@ impl CookedModel<'_> {
                 let vertex_count = g_primitive.get(&gltf::Semantic::Positions).unwrap().count();
 
                 // Read the vertices into memory
-                flattened_geos.push({
+                let flattened = {
                     profiling::scope!("Read data");
                     let mut pre_vertices = vec![GltfVertex::default(); vertex_count];
 
@@ -251,6 +729,30 @@ impl CookedModel<'_> {
                     } else {
                         log::warn!("No tex coords in {name}");
                     }
+                    let material = &self.materials[material_index as usize];
+                    let has_tex_coords1 = if material.base_color_mapping.tex_coord == 1
+                        || material.normal_mapping.tex_coord == 1
+                    {
+                        match reader.read_tex_coords(1) {
+                            Some(iter) => {
+                                for (v, tc) in pre_vertices.iter_mut().zip(iter.into_f32()) {
+                                    v.tex_coords1 = tc;
+                                }
+                                true
+                            }
+                            None => {
+                                log::warn!("No second set of tex coords in {name}");
+                                false
+                            }
+                        }
+                    } else {
+                        false
+                    };
+                    let tangent_tex_coord = if has_tex_coords1 {
+                        material.normal_mapping.tex_coord
+                    } else {
+                        0
+                    };
                     if let Some(iter) = reader.read_normals() {
                         assert_eq!(
                             pre_vertices.len(),
@@ -264,30 +766,197 @@ impl CookedModel<'_> {
                     } else {
                         log::warn!("No normals in {name}");
                     }
+                    let has_tangents = match reader.read_tangents() {
+                        Some(iter) => {
+                            for (v, tangent) in pre_vertices.iter_mut().zip(iter) {
+                                v.tangent = tangent;
+                            }
+                            true
+                        }
+                        None => false,
+                    };
+                    let skinned =
+                        match (joint_offset, reader.read_joints(0), reader.read_weights(0)) {
+                            (Some(offset), Some(joints), Some(weights)) => {
+                                for (v, (j, w)) in pre_vertices
+                                    .iter_mut()
+                                    .zip(joints.into_u16().zip(weights.into_f32()))
+                                {
+                                    v.joints = j.map(|index| offset + index as u32);
+                                    v.weights = w;
+                                }
+                                true
+                            }
+                            (Some(_), _, _) => {
+                                log::warn!("No joints or weights in skinned {name}");
+                                false
+                            }
+                            (None, _, _) => false,
+                        };
 
                     // Untangle from the index buffer
-                    match reader.read_indices() {
-                        Some(read) => FlattenedGeometry(
-                            read.into_u32()
-                                .map(|i| pre_vertices[i as usize].clone())
-                                .collect(),
-                        ),
-                        None => FlattenedGeometry(pre_vertices.into_boxed_slice()),
+                    let vertices = match reader.read_indices() {
+                        Some(read) => read
+                            .into_u32()
+                            .map(|i| pre_vertices[i as usize].clone())
+                            .collect(),
+                        None => pre_vertices.into_boxed_slice(),
+                    };
+                    FlattenedGeometry {
+                        vertices,
+                        has_tangents,
+                        skinned,
+                        has_tex_coords1,
+                        tangent_tex_coord,
                     }
-                });
-
-                self.geometries.push(CookedGeometry {
-                    name: Cow::Owned(name.as_bytes().to_owned()),
-                    vertices: Cow::Borrowed(&[]),
-                    indices: Cow::Borrowed(&[]),
-                    transform,
-                    material_index,
-                });
+                };
+                self.add_flattened(name, transform, material_index, flattened, flattened_geos);
             }
         }
 
         for child in g_node.children() {
-            self.populate_gltf(child, global_transform, data_buffers, flattened_geos);
+            self.populate_gltf(
+                child,
+                global_transform,
+                data_buffers,
+                skin_joint_offsets,
+                flattened_geos,
+            );
+        }
+    }
+}
+
+#[cfg(feature = "asset")]
+impl TextureMapping {
+    fn from_gltf(tex_coord: u32, transform: Option<&gltf::json::Value>) -> Self {
+        let get_f32s = |ext: &gltf::json::Value, key: &str, default: [f32; 2]| {
+            let mut values = default;
+            if let Some(array) = ext.get(key).and_then(|value| value.as_array()) {
+                for (v, value) in values.iter_mut().zip(array) {
+                    *v = value.as_f64().unwrap_or(0.0) as f32;
+                }
+            }
+            values
+        };
+        let mapping = match transform {
+            Some(ext) => Self::new(
+                // The extension can override the set of the texture info
+                ext.get("texCoord")
+                    .and_then(|value| value.as_u64())
+                    .map_or(tex_coord, |value| value as u32),
+                get_f32s(ext, "offset", [0.0; 2]),
+                ext.get("rotation")
+                    .and_then(|value| value.as_f64())
+                    .map_or(0.0, |value| value as f32),
+                get_f32s(ext, "scale", [1.0; 2]),
+            ),
+            None => Self {
+                tex_coord,
+                ..Default::default()
+            },
+        };
+        if mapping.tex_coord > 1 {
+            log::warn!(
+                "Texture coordinate set {} is not supported, using the first one",
+                mapping.tex_coord
+            );
+            return Self {
+                tex_coord: 0,
+                ..mapping
+            };
+        }
+        mapping
+    }
+}
+
+/// Factors of the layered material extensions.
+#[cfg(feature = "asset")]
+struct MaterialLayers {
+    transmission: f32,
+    ior: f32,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    sheen_color: [f32; 3],
+    sheen_roughness: f32,
+}
+
+#[cfg(feature = "asset")]
+impl MaterialLayers {
+    fn from_gltf(g_material: &gltf::Material) -> Self {
+        let mut unsupported = Vec::new();
+        let transmission = match g_material.transmission() {
+            Some(g_transmission) => {
+                if g_transmission.transmission_texture().is_some() {
+                    unsupported.push("transmission texture");
+                }
+                g_transmission.transmission_factor()
+            }
+            None => 0.0,
+        };
+        if g_material.extension_value("KHR_materials_volume").is_some() {
+            unsupported.push("volume, treated as thin-walled");
+        }
+
+        let get_f32 = |ext: &gltf::json::Value, key: &str, default: f32| {
+            ext.get(key)
+                .and_then(|value| value.as_f64())
+                .map_or(default, |value| value as f32)
+        };
+        let mut check_textures = |ext: &gltf::json::Value, keys: &[&'static str]| {
+            for &key in keys {
+                if ext.get(key).is_some() {
+                    unsupported.push(key);
+                }
+            }
+        };
+
+        let (clearcoat, clearcoat_roughness) =
+            match g_material.extension_value("KHR_materials_clearcoat") {
+                Some(ext) => {
+                    check_textures(
+                        ext,
+                        &[
+                            "clearcoatTexture",
+                            "clearcoatRoughnessTexture",
+                            "clearcoatNormalTexture",
+                        ],
+                    );
+                    (
+                        get_f32(ext, "clearcoatFactor", 0.0),
+                        get_f32(ext, "clearcoatRoughnessFactor", 0.0),
+                    )
+                }
+                None => (0.0, 0.0),
+            };
+        let (sheen_color, sheen_roughness) = match g_material.extension_value("KHR_materials_sheen")
+        {
+            Some(ext) => {
+                check_textures(ext, &["sheenColorTexture", "sheenRoughnessTexture"]);
+                let mut color = [0.0; 3];
+                if let Some(values) = ext.get("sheenColorFactor").and_then(|v| v.as_array()) {
+                    for (c, value) in color.iter_mut().zip(values) {
+                        *c = value.as_f64().unwrap_or(0.0) as f32;
+                    }
+                }
+                (color, get_f32(ext, "sheenRoughnessFactor", 0.0))
+            }
+            None => ([0.0; 3], 0.0),
+        };
+
+        if !unsupported.is_empty() {
+            log::warn!(
+                "Material '{}' has unsupported features: {}",
+                g_material.name().unwrap_or(""),
+                unsupported.join(", ")
+            );
+        }
+        Self {
+            transmission,
+            ior: g_material.ior().unwrap_or(1.5),
+            clearcoat,
+            clearcoat_roughness,
+            sheen_color,
+            sheen_roughness,
         }
     }
 }
@@ -299,10 +968,32 @@ pub enum FrontFace {
     CounterClockwise,
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Meta {
+    /// Generate MikkTSpace tangents for the geometries
+    /// that don't have them in the source.
     pub generate_tangents: bool,
     pub front_face: FrontFace,
+    /// Reorder the triangles and the vertices for the GPU caches.
+    pub optimize: bool,
+    /// Store the positions and the texture coordinates as 16-bit integers,
+    /// shrinking the cooked data at the cost of precision.
+    ///
+    /// The positions share the quantization grid across the model,
+    /// so the vertices that match in the source keep matching.
+    /// The vertices are dequantized at upload.
+    pub quantize: bool,
+}
+
+impl Default for Meta {
+    fn default() -> Self {
+        Self {
+            generate_tangents: false,
+            front_face: FrontFace::default(),
+            optimize: true,
+            quantize: false,
+        }
+    }
 }
 
 impl fmt::Display for Meta {
@@ -441,13 +1132,124 @@ impl Baker {
                 } else {
                     uri
                 };
-                let full = parent_cooker.base_path().join(relative);
-                if PRELOAD_TEXTURES {
-                    self.asset_textures.load(&full, meta);
+                self.cook_texture_path(parent_cooker.base_path().join(relative), meta)
+            }
+        }
+    }
+
+    #[cfg(feature = "asset")]
+    fn cook_texture_path(&self, full: PathBuf, meta: super::texture::Meta) -> TextureSource {
+        if PRELOAD_TEXTURES {
+            // The reference is taken by the model when it's served
+            let (handle, _) = self.asset_textures.load(&full, meta);
+            self.asset_textures.release(handle);
+        }
+        TextureSource::Path(full.to_str().unwrap().to_string())
+    }
+
+    /// Generate the tangents and the indices of the flattened geometries,
+    /// then finish the model once the embedded textures are cooked.
+    #[cfg(feature = "asset")]
+    fn finish_cooking(
+        model: CookedModel<'static>,
+        flattened_geos: Vec<FlattenedGeometry>,
+        mut sources: slab::Slab<TextureSource>,
+        meta: Meta,
+        cooker: Arc<blade_asset::Cooker<Self>>,
+        exe_context: &choir::ExecutionContext,
+    ) {
+        assert!(
+            !model.geometries.is_empty(),
+            "Empty models are not supported yet"
+        );
+        // Positions share the grid, keeping the seams between the geometries closed
+        let position_range = meta.quantize.then(|| {
+            Quantization::range(
+                flattened_geos
+                    .iter()
+                    .flat_map(|fg| fg.vertices.iter().map(|v| v.position)),
+            )
+        });
+        let model_shared = Arc::new(Mutex::new(model));
+        let model_clone = Arc::clone(&model_shared);
+        let gen_tangents = exe_context.choir().spawn("generate tangents").init_iter(
+            flattened_geos.into_iter().enumerate(),
+            move |_, (index, mut fg)| {
+                if meta.generate_tangents
+                    && !fg.has_tangents
+                    && !mikktspace::generate_tangents(&mut fg)
+                {
+                    log::warn!("MikkTSpace failed for geometry [{index}]");
+                }
+                let exported_size = fg.vertices.len() * mem::size_of::<crate::Vertex>();
+                let mut rg = fg.reconstruct_indices();
+                let cache_miss_ratios = meta.optimize.then(|| rg.optimize());
+                let quantized = position_range.map(|(position_offset, position_scale)| {
+                    let (tex_coord_offset, tex_coord_scale) =
+                        Quantization::range(rg.vertices.iter().map(|v| v.tex_coords));
+                    let quantization = Quantization {
+                        position_offset,
+                        position_scale,
+                        tex_coord_offset,
+                        tex_coord_scale,
+                    };
+                    let vertices = rg
+                        .vertices
+                        .iter()
+                        .map(|v| quantization.quantize_vertex(v))
+                        .collect::<Vec<_>>();
+                    (vertices, quantization)
+                });
+                let vertex_size = match quantized {
+                    Some((ref vertices, _)) => vertices.len() * mem::size_of::<QuantizedVertex>(),
+                    None => rg.vertices.len() * mem::size_of::<crate::Vertex>(),
+                };
+                let cooked_size = vertex_size + rg.indices.len() * mem::size_of::<u32>();
+
+                let mut model = model_clone.lock().unwrap();
+                let geo = &mut model.geometries[index];
+                let name = String::from_utf8_lossy(&geo.name);
+                log::info!(
+                    "Geometry '{name}'[{index}]: {} vertices, {} triangles, {exported_size} -> {cooked_size} bytes",
+                    rg.vertices.len(),
+                    rg.indices.len() / 3,
+                );
+                if let Some((before, after)) = cache_miss_ratios {
+                    log::info!("Geometry '{name}'[{index}]: ACMR {before:.3} -> {after:.3}");
+                }
+                match quantized {
+                    Some((vertices, quantization)) => {
+                        geo.quantized_vertices = Cow::Owned(vertices);
+                        geo.quantization = quantization;
+                    }
+                    None => {
+                        geo.vertices = Cow::Owned(rg.vertices);
+                    }
                 }
-                TextureSource::Path(full.to_str().unwrap().to_string())
+                geo.skin = Cow::Owned(rg.skin);
+                geo.indices = Cow::Owned(rg.indices);
+                geo.tex_coords1 = Cow::Owned(rg.tex_coords1);
+            },
+        );
+
+        let mut dependencies = vec![gen_tangents];
+        for (_, source) in sources.iter_mut() {
+            if let TextureSource::Embedded(ref mut task, _) = *source {
+                dependencies.push(task.take().unwrap())
             }
         }
+
+        let mut finish = exe_context.fork("finish").init(move |_| {
+            let mut model = Arc::into_inner(model_shared).unwrap().into_inner().unwrap();
+            for material in model.materials.iter_mut() {
+                material.base_color.complete(&sources);
+                material.normal.complete(&sources);
+            }
+            cooker.finish(model);
+        });
+        for dependency in dependencies {
+            finish.depend_on(&dependency);
+        }
     }
 
     fn serve_texture(
@@ -485,8 +1287,32 @@ pub struct ProceduralGeometry {
     pub base_color_factor: [f32; 4],
 }
 
+impl crate::Vertex {
+    /// Create a vertex with the normal and the tangent packed.
+    ///
+    /// The `w` component of the tangent is the sign of the bitangent.
+    pub fn new(
+        position: [f32; 3],
+        tex_coords: [f32; 2],
+        normal: [f32; 3],
+        tangent: [f32; 4],
+    ) -> Self {
+        Self {
+            position,
+            bitangent_sign: tangent[3],
+            tex_coords,
+            normal: encode_normal(normal),
+            tangent: encode_normal([tangent[0], tangent[1], tangent[2]]),
+        }
+    }
+}
+
 impl Baker {
     /// Create a model from procedural geometry data, bypassing the asset cooking pipeline.
+    ///
+    /// The buffers use shared memory, so no GPU transfer is needed,
+    /// and the vertices can be changed later with `update_model_vertices`.
+    /// The acceleration structure is built by the next `flush`.
     pub fn create_model(&self, name: &str, geometries: Vec<ProceduralGeometry>) -> Model {
         assert!(!geometries.is_empty(), "Need at least one geometry");
 
@@ -563,9 +1389,18 @@ impl Baker {
             materials.push(Material {
                 base_color_texture: None,
                 base_color_factor: geo.base_color_factor,
+                base_color_mapping: TextureMapping::default(),
                 normal_texture: None,
                 normal_scale: 0.0,
-                transparent: false,
+                normal_mapping: TextureMapping::default(),
+                alpha_mode: AlphaMode::Opaque,
+                emissive_factor: [0.0; 3],
+                transmission: 0.0,
+                ior: 1.5,
+                clearcoat: 0.0,
+                clearcoat_roughness: 0.0,
+                sheen_color: [0.0; 3],
+                sheen_roughness: 0.0,
             });
 
             model_geometries.push(Geometry {
@@ -576,6 +1411,9 @@ impl Baker {
                 triangle_count,
                 transform,
                 material_index,
+                emissive_triangles: Vec::new(),
+                skinned: false,
+                has_tex_coords1: false,
             });
 
             start_vertex += geo.vertices.len() as u32;
@@ -583,16 +1421,97 @@ impl Baker {
             transform_offset += mem::size_of::<blade_graphics::Transform>() as u64;
         }
 
-        Model {
+        let mut model = Model {
             name: name.to_string(),
             winding: 1.0,
             geometries: model_geometries,
             materials,
+            radius: geometries
+                .iter()
+                .flat_map(|geo| geo.vertices.iter())
+                .map(|vertex| glam::Vec3::from(vertex.position).length())
+                .fold(0.0, f32::max),
+            joints: Vec::new(),
             vertex_buffer,
+            skin_buffer: blade_graphics::Buffer::default(),
+            tex_coords1_buffer: blade_graphics::Buffer::default(),
             index_buffer,
             transform_buffer,
             acceleration_structure: blade_graphics::AccelerationStructure::default(),
+            gpu_size: total_vertex_size + total_index_size + total_transform_size,
+        };
+        if !self.gpu_context.capabilities().ray_query.is_empty() {
+            let meshes = model.acceleration_structure_meshes();
+            let sizes = self
+                .gpu_context
+                .get_bottom_level_acceleration_structure_sizes(&meshes);
+            model.acceleration_structure = self.gpu_context.create_acceleration_structure(
+                blade_graphics::AccelerationStructureDesc {
+                    name,
+                    ty: blade_graphics::AccelerationStructureType::BottomLevel,
+                    size: sizes.data,
+                },
+            );
+            model.gpu_size += sizes.data;
+            self.build_acceleration_structure(&model, meshes, sizes.scratch);
         }
+        model
+    }
+
+    /// Overwrite the vertices of a model created by `create_model`,
+    /// keeping the indices, and rebuild its acceleration structure.
+    ///
+    /// The vertices of all the geometries are expected in their original order.
+    /// The shared memory is written in place, so the frames still in flight
+    /// may observe the new data. The bounding radius isn't updated.
+    pub fn update_model_vertices(&self, model: &Model, vertices: &[crate::Vertex]) {
+        let vertex_count = model
+            .geometries
+            .last()
+            .map_or(0, |geometry| geometry.vertex_range.end);
+        assert_eq!(
+            vertices.len(),
+            vertex_count as usize,
+            "Vertex count of '{}' can't change",
+            model.name
+        );
+        let data = model.vertex_buffer.data() as *mut crate::Vertex;
+        assert!(
+            !data.is_null(),
+            "Model '{}' is not procedural, its vertices are not shared",
+            model.name
+        );
+        unsafe {
+            ptr::copy_nonoverlapping(vertices.as_ptr(), data, vertices.len());
+        }
+        // There is no refitting, the BLAS is rebuilt in place with the same sizes
+        if model.acceleration_structure != blade_graphics::AccelerationStructure::default() {
+            let meshes = model.acceleration_structure_meshes();
+            let sizes = self
+                .gpu_context
+                .get_bottom_level_acceleration_structure_sizes(&meshes);
+            self.build_acceleration_structure(model, meshes, sizes.scratch);
+        }
+    }
+
+    /// Schedule the build of the model acceleration structure for the next `flush`.
+    fn build_acceleration_structure(
+        &self,
+        model: &Model,
+        meshes: Vec<blade_graphics::AccelerationStructureMesh>,
+        scratch_size: u64,
+    ) {
+        let scratch = self.gpu_context.create_buffer(blade_graphics::BufferDesc {
+            name: "BLAS scratch",
+            size: scratch_size,
+            memory: blade_graphics::Memory::Device,
+        });
+        let mut pending_ops = self.pending_operations.lock().unwrap();
+        pending_ops.blas_constructs.push(BlasConstruct {
+            meshes,
+            scratch,
+            dst: model.acceleration_structure,
+        });
     }
 }
 
@@ -614,7 +1533,14 @@ impl blade_asset::Baker for Baker {
             "gltf" | "glb" => {
                 use base64::engine::{Engine as _, general_purpose::URL_SAFE as ENCODING_ENGINE};
 
-                let gltf::Gltf { document, mut blob } = gltf::Gltf::from_slice(source).unwrap();
+                let gltf::Gltf { document, mut blob } =
+                    match gltf::Gltf::from_slice_without_validation(source) {
+                        Ok(gltf) => gltf,
+                        Err(e) => {
+                            cooker.fail(format!("Invalid glTF: {e}"));
+                            return;
+                        }
+                    };
                 // extract buffers
                 let mut buffers = Vec::new();
                 for buffer in document.buffers() {
@@ -639,19 +1565,35 @@ impl blade_asset::Baker for Baker {
                     }
                     buffers.push(data);
                 }
+                // Compressed accessors have no buffer views until decoded
+                #[cfg_attr(not(feature = "draco"), allow(unused_mut))]
+                let mut root = document.into_json();
+                if root
+                    .extensions_used
+                    .iter()
+                    .any(|ext| ext == "KHR_draco_mesh_compression")
+                {
+                    #[cfg(feature = "draco")]
+                    draco::decompress(&mut root, &mut buffers);
+                    #[cfg(not(feature = "draco"))]
+                    {
+                        cooker.fail("Draco compressed meshes require the `draco` feature");
+                        return;
+                    }
+                }
+                let document = match gltf::Document::from_json(root) {
+                    Ok(document) => document,
+                    Err(e) => {
+                        cooker.fail(format!("Invalid glTF: {e}"));
+                        return;
+                    }
+                };
 
                 let mut sources = slab::Slab::new();
-                let mut model = CookedModel {
-                    name: &[],
-                    winding: match meta.front_face {
-                        FrontFace::Clockwise => -1.0,
-                        FrontFace::CounterClockwise => 1.0,
-                    },
-                    materials: Vec::new(),
-                    geometries: Vec::new(),
-                };
+                let mut model = CookedModel::new(meta.front_face);
                 for g_material in document.materials() {
                     let pbr = g_material.pbr_metallic_roughness();
+                    let layers = MaterialLayers::from_gltf(&g_material);
                     model.materials.push(CookedMaterial {
                         base_color: TextureReference {
                             source_index: match pbr.base_color_texture() {
@@ -666,6 +1608,15 @@ impl blade_asset::Baker for Baker {
                             ..Default::default()
                         },
                         base_color_factor: pbr.base_color_factor(),
+                        base_color_mapping: pbr.base_color_texture().map_or(
+                            TextureMapping::default(),
+                            |info| {
+                                TextureMapping::from_gltf(
+                                    info.tex_coord(),
+                                    info.extension_value("KHR_texture_transform"),
+                                )
+                            },
+                        ),
                         normal: TextureReference {
                             source_index: match g_material.normal_texture() {
                                 Some(info) => sources.insert(self.cook_texture(
@@ -679,10 +1630,66 @@ impl blade_asset::Baker for Baker {
                             ..Default::default()
                         },
                         normal_scale: g_material.normal_texture().map_or(0.0, |info| info.scale()),
-                        transparent: g_material.alpha_mode() != gltf::material::AlphaMode::Opaque,
+                        normal_mapping: g_material.normal_texture().map_or(
+                            TextureMapping::default(),
+                            |info| {
+                                TextureMapping::from_gltf(
+                                    info.tex_coord(),
+                                    info.extension_value("KHR_texture_transform"),
+                                )
+                            },
+                        ),
+                        alpha_mode: match g_material.alpha_mode() {
+                            gltf::material::AlphaMode::Opaque => 0,
+                            gltf::material::AlphaMode::Mask => 1,
+                            gltf::material::AlphaMode::Blend => 2,
+                        },
+                        alpha_cutoff: g_material.alpha_cutoff().unwrap_or(0.5),
+                        emissive_factor: {
+                            let strength = g_material.emissive_strength().unwrap_or(1.0);
+                            g_material.emissive_factor().map(|c| c * strength)
+                        },
+                        transmission: layers.transmission,
+                        ior: layers.ior,
+                        clearcoat: layers.clearcoat,
+                        clearcoat_roughness: layers.clearcoat_roughness,
+                        sheen_color: layers.sheen_color,
+                        sheen_roughness: layers.sheen_roughness,
                     });
                 }
 
+                let mut global_transforms = vec![glam::Mat4::IDENTITY; document.nodes().len()];
+                for g_scene in document.scenes() {
+                    for g_node in g_scene.nodes() {
+                        compute_global_transforms(
+                            g_node,
+                            glam::Mat4::IDENTITY,
+                            &mut global_transforms,
+                        );
+                    }
+                }
+                // Joints of all the skins are concatenated
+                let mut skin_joint_offsets = Vec::new();
+                for g_skin in document.skins() {
+                    skin_joint_offsets.push(model.joints.len() as u32);
+                    let reader = g_skin.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let mut inverse_bind_matrices = reader.read_inverse_bind_matrices();
+                    for g_joint in g_skin.joints() {
+                        let inverse_bind_matrix = match inverse_bind_matrices {
+                            Some(ref mut iter) => iter.next().unwrap(),
+                            None => glam::Mat4::IDENTITY.to_cols_array_2d(),
+                        };
+                        model.joints.push(CookedJoint {
+                            name: Cow::Owned(g_joint.name().unwrap_or("").as_bytes().to_owned()),
+                            inverse_bind_matrix: glam::Mat4::from_cols_array_2d(
+                                &inverse_bind_matrix,
+                            )
+                            .to_cols_array(),
+                            rest_transform: global_transforms[g_joint.index()].to_cols_array(),
+                        });
+                    }
+                }
+
                 let mut flattened_geos = Vec::new();
                 for g_scene in document.scenes() {
                     for g_node in g_scene.nodes() {
@@ -690,50 +1697,34 @@ impl blade_asset::Baker for Baker {
                             g_node,
                             glam::Mat4::IDENTITY,
                             &buffers,
+                            &skin_joint_offsets,
                             &mut flattened_geos,
                         );
                     }
                 }
 
-                assert!(
-                    !model.geometries.is_empty(),
-                    "Empty models are not supported yet"
-                );
-                let model_shared = Arc::new(Mutex::new(model));
-                let model_clone = Arc::clone(&model_shared);
-                let gen_tangents = exe_context.choir().spawn("generate tangents").init_iter(
-                    flattened_geos.into_iter().enumerate(),
-                    move |_, (index, mut fg)| {
-                        if meta.generate_tangents {
-                            let ok = mikktspace::generate_tangents(&mut fg);
-                            assert!(ok, "MikkTSpace failed");
-                        }
-                        let (indices, vertices) = fg.reconstruct_indices();
-                        let mut model = model_clone.lock().unwrap();
-                        let geo = &mut model.geometries[index];
-                        geo.vertices = Cow::Owned(vertices);
-                        geo.indices = Cow::Owned(indices);
-                    },
+                Self::finish_cooking(model, flattened_geos, sources, meta, cooker, exe_context);
+            }
+            #[cfg(feature = "asset")]
+            "obj" => {
+                let mut sources = slab::Slab::new();
+                let mut model = CookedModel::new(meta.front_face);
+                let mut flattened_geos = Vec::new();
+                model.populate_obj(
+                    source,
+                    &cooker,
+                    |full| sources.insert(self.cook_texture_path(full, META_BASE_COLOR)),
+                    &mut flattened_geos,
                 );
-
-                let mut dependencies = vec![gen_tangents];
-                for (_, source) in sources.iter_mut() {
-                    if let TextureSource::Embedded(ref mut task, _) = *source {
-                        dependencies.push(task.take().unwrap())
-                    }
-                }
-
-                let mut finish = exe_context.fork("finish").init(move |_| {
-                    let mut model = Arc::into_inner(model_shared).unwrap().into_inner().unwrap();
-                    for material in model.materials.iter_mut() {
-                        material.base_color.complete(&sources);
-                        material.normal.complete(&sources);
-                    }
-                    cooker.finish(model);
-                });
-                for dependency in dependencies {
-                    finish.depend_on(&dependency);
-                }
+                Self::finish_cooking(model, flattened_geos, sources, meta, cooker, exe_context);
+            }
+            #[cfg(feature = "asset")]
+            "ply" => {
+                let mut model = CookedModel::new(meta.front_face);
+                let mut flattened_geos = Vec::new();
+                model.populate_ply(source, &mut flattened_geos);
+                let sources = slab::Slab::new();
+                Self::finish_cooking(model, flattened_geos, sources, meta, cooker, exe_context);
             }
             other => panic!("Unknown model extension: {}", other),
         }
@@ -749,16 +1740,29 @@ impl blade_asset::Baker for Baker {
                     exe_context,
                 ),
                 base_color_factor: material.base_color_factor,
+                base_color_mapping: material.base_color_mapping,
                 normal_texture: self.serve_texture(&material.normal, META_NORMAL, exe_context),
                 normal_scale: material.normal_scale,
-                transparent: material.transparent,
+                normal_mapping: material.normal_mapping,
+                alpha_mode: AlphaMode::from_raw(material.alpha_mode, material.alpha_cutoff),
+                emissive_factor: material.emissive_factor,
+                transmission: material.transmission,
+                ior: material.ior,
+                clearcoat: material.clearcoat,
+                clearcoat_roughness: material.clearcoat_roughness,
+                sheen_color: material.sheen_color,
+                sheen_roughness: material.sheen_roughness,
             });
         }
 
-        let total_vertices = model
+        let geometry_vertices = model
             .geometries
             .iter()
-            .map(|geo| geo.vertices.len())
+            .map(|geo| geo.full_vertices())
+            .collect::<Vec<_>>();
+        let total_vertices = geometry_vertices
+            .iter()
+            .map(|vertices| vertices.len())
             .sum::<usize>();
         let total_vertex_size = (total_vertices * mem::size_of::<crate::Vertex>()) as u64;
         let vertex_buffer = self.gpu_context.create_buffer(blade_graphics::BufferDesc {
@@ -772,6 +1776,48 @@ impl blade_asset::Baker for Baker {
             memory: blade_graphics::Memory::Upload,
         });
 
+        let skin_buffer_and_stage = if model.joints.is_empty() {
+            None
+        } else {
+            let total_skin_size = (total_vertices * mem::size_of::<crate::SkinVertex>()) as u64;
+            let skin_buffer = self.gpu_context.create_buffer(blade_graphics::BufferDesc {
+                name: "skin",
+                size: total_skin_size,
+                memory: blade_graphics::Memory::Device,
+            });
+            let skin_stage = self.gpu_context.create_buffer(blade_graphics::BufferDesc {
+                name: "skin stage",
+                size: total_skin_size,
+                memory: blade_graphics::Memory::Upload,
+            });
+            Some((skin_buffer, skin_stage, total_skin_size))
+        };
+
+        let tex_coords1_buffer_and_stage = if model
+            .geometries
+            .iter()
+            .all(|geo| geo.tex_coords1.is_empty())
+        {
+            None
+        } else {
+            let total_tex_coords1_size = (total_vertices * mem::size_of::<[f32; 2]>()) as u64;
+            let tex_coords1_buffer = self.gpu_context.create_buffer(blade_graphics::BufferDesc {
+                name: "tex coords 1",
+                size: total_tex_coords1_size,
+                memory: blade_graphics::Memory::Device,
+            });
+            let tex_coords1_stage = self.gpu_context.create_buffer(blade_graphics::BufferDesc {
+                name: "tex coords 1 stage",
+                size: total_tex_coords1_size,
+                memory: blade_graphics::Memory::Upload,
+            });
+            Some((
+                tex_coords1_buffer,
+                tex_coords1_stage,
+                total_tex_coords1_size,
+            ))
+        };
+
         let total_indices = model
             .geometries
             .iter()
@@ -809,7 +1855,7 @@ impl blade_asset::Baker for Baker {
         let mut index_offset = 0;
         let mut transform_offset = 0;
         let mut geometries = Vec::with_capacity(model.geometries.len());
-        for geometry in model.geometries.iter() {
+        for (geometry, vertices) in model.geometries.iter().zip(geometry_vertices.iter()) {
             index_offset = crate::util::align_to(
                 index_offset,
                 blade_graphics::limits::STORAGE_BUFFER_ALIGNMENT,
@@ -817,15 +1863,42 @@ impl blade_asset::Baker for Baker {
             let material = &model.materials[geometry.material_index as usize];
             unsafe {
                 ptr::copy_nonoverlapping(
-                    geometry.vertices.as_ptr(),
+                    vertices.as_ptr(),
                     (vertex_stage.data() as *mut crate::Vertex).add(start_vertex as usize),
-                    geometry.vertices.len(),
+                    vertices.len(),
                 );
                 ptr::copy_nonoverlapping(
                     geometry.indices.as_ptr(),
                     index_stage.data().add(index_offset as usize) as *mut u32,
                     geometry.indices.len(),
                 );
+                if let Some((_, skin_stage, _)) = skin_buffer_and_stage {
+                    // Vertices without weights are left intact by skinning
+                    let skin_ptr =
+                        (skin_stage.data() as *mut crate::SkinVertex).add(start_vertex as usize);
+                    if geometry.skin.is_empty() {
+                        ptr::write_bytes(skin_ptr, 0, vertices.len());
+                    } else {
+                        ptr::copy_nonoverlapping(
+                            geometry.skin.as_ptr(),
+                            skin_ptr,
+                            geometry.skin.len(),
+                        );
+                    }
+                }
+                if let Some((_, tex_coords1_stage, _)) = tex_coords1_buffer_and_stage {
+                    let tex_coords1_ptr =
+                        (tex_coords1_stage.data() as *mut [f32; 2]).add(start_vertex as usize);
+                    if geometry.tex_coords1.is_empty() {
+                        ptr::write_bytes(tex_coords1_ptr, 0, vertices.len());
+                    } else {
+                        ptr::copy_nonoverlapping(
+                            geometry.tex_coords1.as_ptr(),
+                            tex_coords1_ptr,
+                            geometry.tex_coords1.len(),
+                        );
+                    }
+                }
                 ptr::copy_nonoverlapping(
                     geometry.transform.as_ptr() as *const u8,
                     transform_stage.data().add(transform_offset as usize),
@@ -838,7 +1911,7 @@ impl blade_asset::Baker for Baker {
                 Some(blade_graphics::IndexType::U32)
             };
             let triangle_count = if geometry.indices.is_empty() {
-                geometry.vertices.len() as u32 / 3
+                vertices.len() as u32 / 3
             } else {
                 geometry.indices.len() as u32 / 3
             };
@@ -846,23 +1919,30 @@ impl blade_asset::Baker for Baker {
                 vertex_data: vertex_buffer.at(start_vertex as u64 * vertex_stride as u64),
                 vertex_format: blade_graphics::VertexFormat::F32Vec3,
                 vertex_stride,
-                vertex_count: geometry.vertices.len() as u32,
+                vertex_count: vertices.len() as u32,
                 index_data: index_buffer.at(index_offset),
                 index_type,
                 triangle_count,
                 transform_data: transform_buffer.at(transform_offset), //TODO
-                is_opaque: !material.transparent,
+                is_opaque: materials[geometry.material_index as usize].is_opaque(),
             });
             geometries.push(Geometry {
                 name: String::from_utf8_lossy(geometry.name.as_ref()).into_owned(),
-                vertex_range: start_vertex..start_vertex + geometry.vertices.len() as u32,
+                vertex_range: start_vertex..start_vertex + vertices.len() as u32,
                 index_offset,
                 index_type,
                 triangle_count,
                 transform: geometry.transform.into(),
                 material_index: geometry.material_index as usize,
+                emissive_triangles: if material.emissive_factor.iter().any(|&c| c > 0.0) {
+                    collect_triangles(vertices, &geometry.indices)
+                } else {
+                    Vec::new()
+                },
+                skinned: !geometry.skin.is_empty(),
+                has_tex_coords1: !geometry.tex_coords1.is_empty(),
             });
-            start_vertex += geometry.vertices.len() as u32;
+            start_vertex += vertices.len() as u32;
             index_offset += geometry.indices.len() as u64 * 4;
             transform_offset += mem::size_of::<blade_graphics::Transform>() as u64;
         }
@@ -871,6 +1951,13 @@ impl blade_asset::Baker for Baker {
         assert_eq!(transform_offset, total_transform_size);
 
         let ray_tracing_enabled = !self.gpu_context.capabilities().ray_query.is_empty();
+        let mut gpu_size = total_vertex_size + total_index_size + total_transform_size;
+        if let Some((_, _, total_skin_size)) = skin_buffer_and_stage {
+            gpu_size += total_skin_size;
+        }
+        if let Some((_, _, total_tex_coords1_size)) = tex_coords1_buffer_and_stage {
+            gpu_size += total_tex_coords1_size;
+        }
         let (acceleration_structure, scratch) = if ray_tracing_enabled {
             let sizes = self
                 .gpu_context
@@ -882,6 +1969,7 @@ impl blade_asset::Baker for Baker {
                     size: sizes.data,
                 },
             );
+            gpu_size += sizes.data;
             let scratch = self.gpu_context.create_buffer(blade_graphics::BufferDesc {
                 name: "BLAS scratch",
                 size: sizes.scratch,
@@ -908,6 +1996,22 @@ impl blade_asset::Baker for Baker {
             dst: transform_buffer,
             size: total_transform_size,
         });
+        if let Some((skin_buffer, skin_stage, total_skin_size)) = skin_buffer_and_stage {
+            pending_ops.transfers.push(Transfer {
+                stage: skin_stage,
+                dst: skin_buffer,
+                size: total_skin_size,
+            });
+        }
+        if let Some((tex_coords1_buffer, tex_coords1_stage, total_tex_coords1_size)) =
+            tex_coords1_buffer_and_stage
+        {
+            pending_ops.transfers.push(Transfer {
+                stage: tex_coords1_stage,
+                dst: tex_coords1_buffer,
+                size: total_tex_coords1_size,
+            });
+        }
         if let Some(scratch) = scratch {
             pending_ops.blas_constructs.push(BlasConstruct {
                 meshes,
@@ -919,12 +2023,49 @@ impl blade_asset::Baker for Baker {
         Model {
             name: String::from_utf8_lossy(model.name).into_owned(),
             winding: model.winding,
+            radius: model
+                .geometries
+                .iter()
+                .zip(geometry_vertices.iter())
+                .map(|(geometry, vertices)| bounding_radius(vertices, &geometry.transform))
+                .fold(0.0, f32::max),
             geometries,
             materials,
+            joints: model
+                .joints
+                .iter()
+                .map(|joint| Joint {
+                    name: String::from_utf8_lossy(joint.name.as_ref()).into_owned(),
+                    inverse_bind_matrix: mint::ColumnMatrix4::from(joint.inverse_bind_matrix),
+                    rest_transform: mint::ColumnMatrix4::from(joint.rest_transform),
+                })
+                .collect(),
             vertex_buffer,
+            skin_buffer: skin_buffer_and_stage
+                .map_or(blade_graphics::Buffer::default(), |(buffer, _, _)| buffer),
+            tex_coords1_buffer: tex_coords1_buffer_and_stage
+                .map_or(blade_graphics::Buffer::default(), |(buffer, _, _)| buffer),
             index_buffer,
             transform_buffer,
             acceleration_structure,
+            gpu_size,
+        }
+    }
+
+    fn footprint(&self, model: &Self::Output) -> blade_asset::Footprint {
+        let emissive_triangles = model
+            .geometries
+            .iter()
+            .map(|geometry| geometry.emissive_triangles.len() * mem::size_of::<[[f32; 3]; 3]>())
+            .sum::<usize>();
+        let cpu = mem::size_of::<Model>()
+            + model.geometries.len() * mem::size_of::<Geometry>()
+            + model.materials.len() * mem::size_of::<Material>()
+            + model.joints.len() * mem::size_of::<Joint>()
+            + emissive_triangles;
+        blade_asset::Footprint {
+            cpu: cpu as u64,
+            gpu: model.gpu_size,
         }
     }
 
@@ -934,6 +2075,12 @@ impl blade_asset::Baker for Baker {
                 .destroy_acceleration_structure(model.acceleration_structure);
         }
         self.gpu_context.destroy_buffer(model.vertex_buffer);
+        if model.skin_buffer != blade_graphics::Buffer::default() {
+            self.gpu_context.destroy_buffer(model.skin_buffer);
+        }
+        if model.tex_coords1_buffer != blade_graphics::Buffer::default() {
+            self.gpu_context.destroy_buffer(model.tex_coords1_buffer);
+        }
         self.gpu_context.destroy_buffer(model.index_buffer);
         self.gpu_context.destroy_buffer(model.transform_buffer);
     }
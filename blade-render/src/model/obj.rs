@@ -0,0 +1,284 @@
+use super::{CookedMaterial, CookedModel, FlattenedGeometry, GltfVertex};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::SplitWhitespace,
+};
+
+/// Parse the next `N` numbers of an OBJ or MTL statement,
+/// using `defaults` for the missing trailing ones.
+fn parse_floats<const N: usize>(
+    tokens: &mut SplitWhitespace,
+    defaults: [f32; N],
+    line_number: usize,
+) -> [f32; N] {
+    let mut values = defaults;
+    for value in values.iter_mut() {
+        if let Some(token) = tokens.next() {
+            *value = token
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid number '{token}' at line {line_number}"));
+        }
+    }
+    values
+}
+
+/// Convert an OBJ index into a 0-based one.
+fn resolve_index(token: &str, count: usize, kind: &str, line_number: usize) -> usize {
+    let index: isize = token
+        .parse()
+        .unwrap_or_else(|_| panic!("Invalid OBJ {kind} index '{token}' at line {line_number}"));
+    let resolved = match index {
+        // Positive indices start from 1
+        1.. => index - 1,
+        // Negative indices are relative to the end of the list so far
+        ..=-1 => count as isize + index,
+        0 => panic!("OBJ {kind} index 0 at line {line_number}, indices start from 1"),
+    };
+    if resolved < 0 || resolved >= count as isize {
+        panic!("OBJ {kind} index {index} is out of range at line {line_number}");
+    }
+    resolved as usize
+}
+
+/// Faces of an OBJ group, sharing the material.
+#[derive(Default)]
+struct Group {
+    name: String,
+    material_index: Option<u32>,
+    vertices: Vec<GltfVertex>,
+    /// The vertex doesn't have a normal in the source.
+    missing_normals: Vec<bool>,
+}
+
+impl CookedModel<'_> {
+    /// Read the materials of an MTL library, with the texture paths
+    /// relative to the `directory` of the library.
+    fn populate_mtl(
+        &mut self,
+        text: &str,
+        directory: &Path,
+        cooker: &blade_asset::Cooker<super::Baker>,
+        cook_texture: &mut impl FnMut(PathBuf) -> usize,
+        material_indices: &mut HashMap<String, u32>,
+    ) {
+        let first = self.materials.len();
+        let mut names = Vec::new();
+        let mut unsupported = Vec::new();
+        for (line_index, line) in text.lines().enumerate() {
+            let line_number = line_index + 1;
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+            if keyword == "newmtl" {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                material_indices.insert(name.clone(), self.materials.len() as u32);
+                names.push(name);
+                self.materials.push(CookedMaterial::plain([1.0; 4]));
+                continue;
+            }
+            let Some(material) = self.materials[first..].last_mut() else {
+                continue;
+            };
+            match keyword {
+                "Kd" => {
+                    let [r, g, b] = parse_floats(&mut tokens, [1.0; 3], line_number);
+                    material.base_color_factor[..3].copy_from_slice(&[r, g, b]);
+                }
+                "d" => {
+                    let [alpha] = parse_floats(&mut tokens, [1.0], line_number);
+                    material.base_color_factor[3] = alpha;
+                }
+                "Tr" => {
+                    let [transparency] = parse_floats(&mut tokens, [0.0], line_number);
+                    material.base_color_factor[3] = 1.0 - transparency;
+                }
+                "Ke" => {
+                    material.emissive_factor = parse_floats(&mut tokens, [0.0; 3], line_number);
+                }
+                "Ni" => {
+                    let [ior] = parse_floats(&mut tokens, [1.5], line_number);
+                    material.ior = ior;
+                }
+                "map_Kd" => {
+                    // The options come first, so the file name is the last argument
+                    let Some(file) = tokens.last() else {
+                        panic!("Missing texture file at line {line_number}");
+                    };
+                    let relative = directory.join(file.replace('\\', "/"));
+                    material.base_color.source_index =
+                        cook_texture(cooker.base_path().join(relative));
+                }
+                other if other.starts_with("map_") || matches!(other, "bump" | "norm" | "disp") => {
+                    unsupported.push((names.len() - 1, other.to_string()));
+                }
+                _ => {}
+            }
+        }
+
+        for material in self.materials[first..].iter_mut() {
+            if material.base_color_factor[3] < 1.0 {
+                material.alpha_mode = 2;
+            }
+        }
+        for (index, keyword) in unsupported {
+            log::warn!(
+                "Material '{}' has unsupported texture: {}",
+                names[index],
+                keyword
+            );
+        }
+    }
+
+    /// Read an OBJ file, splitting the faces into geometries
+    /// at every object, group, and material change.
+    pub(super) fn populate_obj(
+        &mut self,
+        source: &[u8],
+        cooker: &blade_asset::Cooker<super::Baker>,
+        mut cook_texture: impl FnMut(PathBuf) -> usize,
+        flattened_geos: &mut Vec<FlattenedGeometry>,
+    ) {
+        let text = String::from_utf8_lossy(source);
+        let mut positions = Vec::new();
+        let mut tex_coords = Vec::new();
+        let mut normals = Vec::new();
+        let mut material_indices = HashMap::new();
+        let mut default_material_index = None;
+        let mut skipped_elements = 0;
+        let mut group = Group::default();
+
+        let mut finish_group = |model: &mut Self, group: &mut Group| {
+            if group.vertices.is_empty() {
+                return;
+            }
+            let mut flattened = FlattenedGeometry {
+                vertices: group.vertices.drain(..).collect(),
+                has_tangents: false,
+                skinned: false,
+                has_tex_coords1: false,
+                tangent_tex_coord: 0,
+            };
+            if group.missing_normals.iter().any(|&missing| missing) {
+                log::info!("Generating normals for '{}'", group.name);
+                flattened.generate_normals(&group.missing_normals, model.winding);
+            }
+            group.missing_normals.clear();
+            let material_index = match group.material_index {
+                Some(index) => index,
+                None => *default_material_index.get_or_insert_with(|| {
+                    model.materials.push(CookedMaterial::plain([1.0; 4]));
+                    model.materials.len() as u32 - 1
+                }),
+            };
+            model.add_flattened(
+                &group.name,
+                blade_graphics::IDENTITY_TRANSFORM.into(),
+                material_index,
+                flattened,
+                flattened_geos,
+            );
+        };
+
+        for (line_index, line) in text.lines().enumerate() {
+            let line_number = line_index + 1;
+            let line = line.split('#').next().unwrap();
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+            match keyword {
+                "v" => positions.push(parse_floats(&mut tokens, [0.0; 3], line_number)),
+                "vt" => {
+                    let [u, v] = parse_floats(&mut tokens, [0.0; 2], line_number);
+                    // OBJ puts the origin at the bottom
+                    tex_coords.push([u, 1.0 - v]);
+                }
+                "vn" => normals.push(parse_floats(&mut tokens, [0.0; 3], line_number)),
+                "f" => {
+                    let corners = tokens
+                        .map(|token| {
+                            let mut parts = token.split('/');
+                            let mut vertex = GltfVertex::default();
+                            let position = parts.next().unwrap();
+                            vertex.position = positions
+                                [resolve_index(position, positions.len(), "position", line_number)];
+                            match parts.next() {
+                                Some("") | None => {}
+                                Some(tc) => {
+                                    vertex.tex_coords = tex_coords[resolve_index(
+                                        tc,
+                                        tex_coords.len(),
+                                        "texture coordinate",
+                                        line_number,
+                                    )];
+                                }
+                            }
+                            let has_normal = match parts.next() {
+                                Some("") | None => false,
+                                Some(normal) => {
+                                    vertex.normal = normals[resolve_index(
+                                        normal,
+                                        normals.len(),
+                                        "normal",
+                                        line_number,
+                                    )];
+                                    true
+                                }
+                            };
+                            (vertex, has_normal)
+                        })
+                        .collect::<Vec<_>>();
+                    if corners.len() < 3 {
+                        log::warn!(
+                            "Skipping a face with {} vertices at line {line_number}",
+                            corners.len()
+                        );
+                        continue;
+                    }
+                    // Triangulate the polygon as a fan around the first vertex
+                    for i in 1..corners.len() - 1 {
+                        for &(ref vertex, has_normal) in [&corners[0], &corners[i], &corners[i + 1]]
+                        {
+                            group.vertices.push(vertex.clone());
+                            group.missing_normals.push(!has_normal);
+                        }
+                    }
+                }
+                "o" | "g" => {
+                    finish_group(self, &mut group);
+                    group.name = tokens.collect::<Vec<_>>().join(" ");
+                }
+                "usemtl" => {
+                    finish_group(self, &mut group);
+                    let name = tokens.collect::<Vec<_>>().join(" ");
+                    group.material_index = material_indices.get(&name).cloned();
+                    if group.material_index.is_none() {
+                        log::warn!("Unknown material '{name}' at line {line_number}");
+                    }
+                }
+                "mtllib" => {
+                    for file in tokens {
+                        let relative = Path::new(file);
+                        let data = cooker.add_dependency(relative);
+                        self.populate_mtl(
+                            &String::from_utf8_lossy(&data),
+                            relative.parent().unwrap_or(Path::new("")),
+                            cooker,
+                            &mut cook_texture,
+                            &mut material_indices,
+                        );
+                    }
+                }
+                "l" | "p" => skipped_elements += 1,
+                _ => {}
+            }
+        }
+        finish_group(self, &mut group);
+
+        if skipped_elements != 0 {
+            log::warn!("Skipped {skipped_elements} line and point elements");
+        }
+    }
+}
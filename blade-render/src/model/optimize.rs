@@ -0,0 +1,281 @@
+/// Size of the LRU cache modelled by the vertex cache optimization.
+const CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+/// Size of the FIFO cache used for the statistics and the overdraw clusters.
+const FIFO_CACHE_SIZE: u32 = 16;
+
+fn vertex_score(cache_position: Option<usize>, remaining_triangles: usize) -> f32 {
+    if remaining_triangles == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        // The vertices of the last triangle are scored the same,
+        // so that the triangles don't follow a strip order
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scaler = 1.0 / (CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scaler).powf(CACHE_DECAY_POWER)
+        }
+    };
+    // Vertices with few triangles left are prioritized to avoid leaving them alone
+    let valence_boost =
+        VALENCE_BOOST_SCALE * (remaining_triangles as f32).powf(-VALENCE_BOOST_POWER);
+    cache_score + valence_boost
+}
+
+/// Reorder the triangles for the post-transform vertex cache,
+/// using the algorithm of Tom Forsyth.
+pub(super) fn optimize_vertex_cache(indices: &mut [u32], vertex_count: usize) {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return;
+    }
+
+    // Triangles of every vertex, with the live ones at the start
+    let mut offsets = vec![0usize; vertex_count + 1];
+    for &index in indices.iter() {
+        offsets[index as usize + 1] += 1;
+    }
+    for i in 0..vertex_count {
+        offsets[i + 1] += offsets[i];
+    }
+    let mut remaining = (0..vertex_count)
+        .map(|v| offsets[v + 1] - offsets[v])
+        .collect::<Vec<_>>();
+    let mut adjacency = vec![0u32; indices.len()];
+    let mut fill = offsets[..vertex_count].to_vec();
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        for &index in corners {
+            adjacency[fill[index as usize]] = triangle as u32;
+            fill[index as usize] += 1;
+        }
+    }
+
+    let mut cache_positions = vec![None; vertex_count];
+    let mut vertex_scores = (0..vertex_count)
+        .map(|v| vertex_score(None, remaining[v]))
+        .collect::<Vec<_>>();
+    let mut triangle_scores = indices
+        .chunks_exact(3)
+        .map(|corners| corners.iter().map(|&i| vertex_scores[i as usize]).sum())
+        .collect::<Vec<f32>>();
+    let mut emitted = vec![false; triangle_count];
+    let mut output = Vec::with_capacity(indices.len());
+    let mut cache = Vec::<u32>::with_capacity(CACHE_SIZE + 3);
+    let mut new_cache = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut scan_cursor = 0;
+
+    let mut best = triangle_scores
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(triangle, _)| triangle);
+    while let Some(triangle) = best {
+        emitted[triangle] = true;
+        let corners = [
+            indices[triangle * 3],
+            indices[triangle * 3 + 1],
+            indices[triangle * 3 + 2],
+        ];
+        output.extend_from_slice(&corners);
+
+        // Retire the triangle from the adjacency of its vertices
+        for &index in corners.iter() {
+            let v = index as usize;
+            let live = &mut adjacency[offsets[v]..offsets[v] + remaining[v]];
+            if let Some(position) = live.iter().position(|&t| t as usize == triangle) {
+                live.swap(position, remaining[v] - 1);
+                remaining[v] -= 1;
+            }
+        }
+
+        // Move the vertices of the triangle to the front of the cache
+        new_cache.clear();
+        new_cache.extend_from_slice(&corners);
+        new_cache.extend(cache.iter().filter(|index| !corners.contains(index)));
+        for &index in new_cache[CACHE_SIZE.min(new_cache.len())..].iter() {
+            cache_positions[index as usize] = None;
+        }
+        new_cache.truncate(CACHE_SIZE);
+        for (position, &index) in new_cache.iter().enumerate() {
+            cache_positions[index as usize] = Some(position);
+        }
+
+        // Re-score the affected vertices and their live triangles
+        let evicted = cache
+            .iter()
+            .filter(|index| cache_positions[**index as usize].is_none());
+        for &index in new_cache.iter().chain(evicted) {
+            let v = index as usize;
+            let score = vertex_score(cache_positions[v], remaining[v]);
+            let delta = score - vertex_scores[v];
+            vertex_scores[v] = score;
+            for &t in adjacency[offsets[v]..offsets[v] + remaining[v]].iter() {
+                triangle_scores[t as usize] += delta;
+            }
+        }
+        std::mem::swap(&mut cache, &mut new_cache);
+
+        // The next triangle is the best one touching the cache
+        best = None;
+        let mut best_score = -1.0;
+        for &index in cache.iter() {
+            let v = index as usize;
+            for &t in adjacency[offsets[v]..offsets[v] + remaining[v]].iter() {
+                if triangle_scores[t as usize] > best_score {
+                    best_score = triangle_scores[t as usize];
+                    best = Some(t as usize);
+                }
+            }
+        }
+        if best.is_none() {
+            while scan_cursor < triangle_count && emitted[scan_cursor] {
+                scan_cursor += 1;
+            }
+            if scan_cursor < triangle_count {
+                best = Some(scan_cursor);
+            }
+        }
+    }
+
+    indices.copy_from_slice(&output);
+}
+
+/// Simulate a FIFO cache of `FIFO_CACHE_SIZE` for a triangle,
+/// returning the number of misses.
+fn update_fifo_cache(corners: &[u32], timestamps: &mut [u32], timestamp: &mut u32) -> u32 {
+    let mut misses = 0;
+    for &index in corners {
+        let last = &mut timestamps[index as usize];
+        if *timestamp - *last > FIFO_CACHE_SIZE {
+            *last = *timestamp;
+            *timestamp += 1;
+            misses += 1;
+        }
+    }
+    misses
+}
+
+/// Average number of the vertex shader invocations per triangle,
+/// for a FIFO cache. It's 3 for the unindexed geometry, and 0.5 at best.
+pub(super) fn average_cache_miss_ratio(indices: &[u32], vertex_count: usize) -> f32 {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return 0.0;
+    }
+    let mut timestamps = vec![0; vertex_count];
+    let mut timestamp = FIFO_CACHE_SIZE + 1;
+    let misses = indices
+        .chunks_exact(3)
+        .map(|corners| update_fifo_cache(corners, &mut timestamps, &mut timestamp))
+        .sum::<u32>();
+    misses as f32 / triangle_count as f32
+}
+
+/// Reorder the clusters of triangles that are drawn together in the cache order,
+/// so that the ones facing outwards from the center come first and occlude the rest.
+///
+/// The triangles are expected to be already optimized for the vertex cache.
+pub(super) fn optimize_overdraw(indices: &mut [u32], positions: &[[f32; 3]]) {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return;
+    }
+
+    // A triangle missing all of its vertices starts a new patch of the surface
+    let mut timestamps = vec![0; positions.len()];
+    let mut timestamp = FIFO_CACHE_SIZE + 1;
+    let mut cluster_starts = Vec::new();
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        let misses = update_fifo_cache(corners, &mut timestamps, &mut timestamp);
+        if triangle == 0 || misses == 3 {
+            cluster_starts.push(triangle);
+        }
+    }
+    if cluster_starts.len() < 2 {
+        return;
+    }
+
+    let position = |index: u32| glam::Vec3::from(positions[index as usize]);
+    let mesh_center =
+        indices.iter().map(|&i| position(i)).sum::<glam::Vec3>() / indices.len() as f32;
+    let mut clusters = cluster_starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = cluster_starts.get(i + 1).cloned().unwrap_or(triangle_count);
+            let mut center = glam::Vec3::ZERO;
+            let mut normal = glam::Vec3::ZERO;
+            let mut area = 0.0;
+            for corners in indices[start * 3..end * 3].chunks_exact(3) {
+                let [a, b, c] = [corners[0], corners[1], corners[2]].map(position);
+                let cross = (b - a).cross(c - a);
+                let triangle_area = cross.length();
+                center += (a + b + c) * (triangle_area / 3.0);
+                normal += cross;
+                area += triangle_area;
+            }
+            if area > 0.0 {
+                center /= area;
+            }
+            let key = (center - mesh_center).dot(normal.normalize_or_zero());
+            (key, start..end)
+        })
+        .collect::<Vec<_>>();
+    // Stable, so that the clusters with the same key keep the cache order
+    clusters.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut output = Vec::with_capacity(indices.len());
+    for &(_, ref range) in clusters.iter() {
+        output.extend_from_slice(&indices[range.start * 3..range.end * 3]);
+    }
+    indices.copy_from_slice(&output);
+}
+
+/// Reorder the vertices by their first use in the triangles, dropping the unused ones.
+///
+/// Returns the old index of every new vertex.
+pub(super) fn optimize_vertex_fetch(indices: &mut [u32], vertex_count: usize) -> Vec<u32> {
+    let mut remap = vec![!0u32; vertex_count];
+    let mut order = Vec::with_capacity(vertex_count);
+    for index in indices.iter_mut() {
+        let new = &mut remap[*index as usize];
+        if *new == !0 {
+            *new = order.len() as u32;
+            order.push(*index);
+        }
+        *index = *new;
+    }
+    order
+}
+
+impl super::ReconstructedGeometry {
+    /// Reorder the triangles for the vertex cache and the overdraw,
+    /// and then the vertices for the fetch locality.
+    ///
+    /// Returns the average cache miss ratio before and after.
+    pub(super) fn optimize(&mut self) -> (f32, f32) {
+        let before = average_cache_miss_ratio(&self.indices, self.vertices.len());
+        optimize_vertex_cache(&mut self.indices, self.vertices.len());
+        let positions = self.vertices.iter().map(|v| v.position).collect::<Vec<_>>();
+        optimize_overdraw(&mut self.indices, &positions);
+
+        let order = optimize_vertex_fetch(&mut self.indices, self.vertices.len());
+        self.vertices = order.iter().map(|&i| self.vertices[i as usize]).collect();
+        if !self.skin.is_empty() {
+            self.skin = order.iter().map(|&i| self.skin[i as usize]).collect();
+        }
+        if !self.tex_coords1.is_empty() {
+            self.tex_coords1 = order
+                .iter()
+                .map(|&i| self.tex_coords1[i as usize])
+                .collect();
+        }
+        let after = average_cache_miss_ratio(&self.indices, self.vertices.len());
+        (before, after)
+    }
+}
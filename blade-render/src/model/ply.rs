@@ -0,0 +1,374 @@
+use super::{CookedMaterial, CookedModel, FlattenedGeometry, GltfVertex};
+use std::str;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Encoding {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Self {
+        match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            other => panic!("Unknown PLY property type '{other}'"),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    /// Scale that maps the values of a color channel into [0, 1].
+    fn color_scale(self) -> f64 {
+        match self {
+            Self::U8 => 1.0 / u8::MAX as f64,
+            Self::U16 => 1.0 / u16::MAX as f64,
+            _ => 1.0,
+        }
+    }
+}
+
+enum PropertyKind {
+    Scalar(ScalarType),
+    List { count: ScalarType, item: ScalarType },
+}
+
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn find(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|p| names.contains(&p.name.as_str()))
+    }
+}
+
+/// Reads the values of the element properties, in either of the encodings.
+struct Reader<'a> {
+    encoding: Encoding,
+    data: &'a [u8],
+    offset: usize,
+    tokens: str::SplitAsciiWhitespace<'a>,
+}
+
+impl Reader<'_> {
+    fn read(&mut self, ty: ScalarType) -> f64 {
+        if self.encoding == Encoding::Ascii {
+            let token = self.tokens.next().expect("Truncated PLY data");
+            return token
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid PLY value '{token}'"));
+        }
+        let size = ty.size();
+        let Some(bytes) = self.data.get(self.offset..self.offset + size) else {
+            panic!("Truncated PLY data");
+        };
+        self.offset += size;
+        let mut raw = [0u8; 8];
+        raw[..size].copy_from_slice(bytes);
+        if self.encoding == Encoding::BinaryBigEndian {
+            raw[..size].reverse();
+        }
+        let [b0, b1, b2, b3, ..] = raw;
+        match ty {
+            ScalarType::I8 => b0 as i8 as f64,
+            ScalarType::U8 => b0 as f64,
+            ScalarType::I16 => i16::from_le_bytes([b0, b1]) as f64,
+            ScalarType::U16 => u16::from_le_bytes([b0, b1]) as f64,
+            ScalarType::I32 => i32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            ScalarType::U32 => u32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            ScalarType::F32 => f32::from_le_bytes([b0, b1, b2, b3]) as f64,
+            ScalarType::F64 => f64::from_le_bytes(raw),
+        }
+    }
+
+    /// Read all the properties of an element, with the lists flattened.
+    fn read_element(&mut self, element: &Element, values: &mut Vec<f64>, list: &mut Vec<f64>) {
+        values.clear();
+        list.clear();
+        for property in element.properties.iter() {
+            match property.kind {
+                PropertyKind::Scalar(ty) => values.push(self.read(ty)),
+                PropertyKind::List { count, item } => {
+                    let count = self.read(count) as usize;
+                    values.push(count as f64);
+                    for _ in 0..count {
+                        list.push(self.read(item));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Split the header off the PLY file, parsing the encoding and the elements.
+fn parse_header(source: &[u8]) -> (Encoding, Vec<Element>, &[u8]) {
+    const END: &[u8] = b"end_header";
+    let Some(end) = source.windows(END.len()).position(|w| w == END) else {
+        panic!("Missing the PLY header end");
+    };
+    let mut body_start = end + END.len();
+    // The line ending is either "\n" or "\r\n"
+    if source.get(body_start) == Some(&b'\r') {
+        body_start += 1;
+    }
+    if source.get(body_start) == Some(&b'\n') {
+        body_start += 1;
+    }
+    let header = str::from_utf8(&source[..end]).expect("PLY header is not valid UTF-8");
+
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        panic!("Not a PLY file");
+    }
+    let mut encoding = None;
+    let mut elements = Vec::<Element>::new();
+    for line in lines {
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+        match *tokens.as_slice() {
+            ["format", name, _version] => {
+                encoding = Some(match name {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::BinaryLittleEndian,
+                    "binary_big_endian" => Encoding::BinaryBigEndian,
+                    other => panic!("Unknown PLY format '{other}'"),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid PLY element count '{count}'")),
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements
+                    .last_mut()
+                    .expect("PLY property outside of element");
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::List {
+                        count: ScalarType::parse(count),
+                        item: ScalarType::parse(item),
+                    },
+                });
+            }
+            ["property", ty, name] => {
+                let element = elements
+                    .last_mut()
+                    .expect("PLY property outside of element");
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::Scalar(ScalarType::parse(ty)),
+                });
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => panic!("Unexpected PLY header line '{line}'"),
+        }
+    }
+    let encoding = encoding.expect("Missing PLY format");
+    (encoding, elements, &source[body_start..])
+}
+
+impl CookedModel<'_> {
+    /// Read the vertices and faces of a PLY file into a single geometry.
+    /// Vertex colors are averaged into the base color factor of its material.
+    pub(super) fn populate_ply(
+        &mut self,
+        source: &[u8],
+        flattened_geos: &mut Vec<FlattenedGeometry>,
+    ) {
+        let (encoding, elements, body) = parse_header(source);
+        let mut reader = Reader {
+            encoding,
+            data: body,
+            offset: 0,
+            tokens: match encoding {
+                Encoding::Ascii => str::from_utf8(body)
+                    .expect("PLY data is not valid UTF-8")
+                    .split_ascii_whitespace(),
+                _ => "".split_ascii_whitespace(),
+            },
+        };
+
+        let mut vertices = Vec::<GltfVertex>::new();
+        let mut has_normals = false;
+        let mut color_sum = [0.0f64; 4];
+        let mut color_count = 0;
+        let mut flat_vertices = Vec::new();
+        let mut values = Vec::new();
+        let mut list = Vec::new();
+        for element in elements.iter() {
+            match element.name.as_str() {
+                "vertex" => {
+                    let find_all = |names: [&[&str]; 3]| {
+                        let found = names.map(|n| element.find(n));
+                        match found {
+                            [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+                            _ => None,
+                        }
+                    };
+                    let Some(position) = find_all([&["x"], &["y"], &["z"]]) else {
+                        panic!("PLY vertices don't have positions");
+                    };
+                    let normal = find_all([&["nx"], &["ny"], &["nz"]]);
+                    let tex_coord = match [
+                        element.find(&["u", "s", "texture_u", "texture_s"]),
+                        element.find(&["v", "t", "texture_v", "texture_t"]),
+                    ] {
+                        [Some(u), Some(v)] => Some([u, v]),
+                        _ => None,
+                    };
+                    let color = find_all([
+                        &["red", "diffuse_red", "r"],
+                        &["green", "diffuse_green", "g"],
+                        &["blue", "diffuse_blue", "b"],
+                    ]);
+                    let alpha = element.find(&["alpha", "diffuse_alpha", "a"]);
+                    let scale = |index: usize| match element.properties[index].kind {
+                        PropertyKind::Scalar(ty) => ty.color_scale(),
+                        PropertyKind::List { .. } => panic!("PLY color can't be a list"),
+                    };
+                    has_normals = normal.is_some();
+
+                    vertices.reserve(element.count);
+                    for _ in 0..element.count {
+                        reader.read_element(element, &mut values, &mut list);
+                        let mut vertex = GltfVertex {
+                            position: position.map(|i| values[i] as f32),
+                            ..Default::default()
+                        };
+                        assert!(
+                            vertex.position.iter().all(|c| c.is_finite()),
+                            "PLY vertex position is not finite"
+                        );
+                        if let Some(normal) = normal {
+                            vertex.normal = normal.map(|i| values[i] as f32);
+                        }
+                        if let Some([u, v]) = tex_coord {
+                            // PLY puts the origin at the bottom
+                            vertex.tex_coords = [values[u] as f32, 1.0 - values[v] as f32];
+                        }
+                        if let Some(color) = color {
+                            for (sum, i) in color_sum.iter_mut().zip(color) {
+                                *sum += values[i] * scale(i);
+                            }
+                            color_sum[3] += alpha.map_or(1.0, |i| values[i] * scale(i));
+                            color_count += 1;
+                        }
+                        vertices.push(vertex);
+                    }
+                }
+                "face" => {
+                    let Some(indices) = element.find(&["vertex_indices", "vertex_index"]) else {
+                        panic!("PLY faces don't have vertex indices");
+                    };
+                    // Lists only contribute their count to the values
+                    let list_start = |values: &[f64]| -> usize {
+                        element.properties[..indices]
+                            .iter()
+                            .zip(values)
+                            .filter(|&(p, _)| matches!(p.kind, PropertyKind::List { .. }))
+                            .map(|(_, &count)| count as usize)
+                            .sum()
+                    };
+                    for face_index in 0..element.count {
+                        reader.read_element(element, &mut values, &mut list);
+                        let start = list_start(&values);
+                        let corners = &list[start..start + values[indices] as usize];
+                        if corners.len() < 3 {
+                            log::warn!(
+                                "Skipping PLY face [{face_index}] with {} vertices",
+                                corners.len()
+                            );
+                            continue;
+                        }
+                        let vertex = |corner: f64| match vertices.get(corner as usize) {
+                            Some(v) => v.clone(),
+                            None => panic!("PLY vertex index {corner} is out of range"),
+                        };
+                        // Triangulate the polygon as a fan around the first vertex
+                        for i in 1..corners.len() - 1 {
+                            for &corner in [corners[0], corners[i], corners[i + 1]].iter() {
+                                flat_vertices.push(vertex(corner));
+                            }
+                        }
+                    }
+                }
+                other => {
+                    log::info!("Skipping PLY element '{other}'");
+                    for _ in 0..element.count {
+                        reader.read_element(element, &mut values, &mut list);
+                    }
+                }
+            }
+        }
+        if flat_vertices.is_empty() {
+            log::warn!("PLY doesn't have any faces");
+            return;
+        }
+
+        let mut flattened = FlattenedGeometry {
+            vertices: flat_vertices.into_boxed_slice(),
+            has_tangents: false,
+            skinned: false,
+            has_tex_coords1: false,
+            tangent_tex_coord: 0,
+        };
+        if !has_normals {
+            log::info!("Generating normals for PLY");
+            let mask = vec![true; flattened.vertices.len()];
+            flattened.generate_normals(&mask, self.winding);
+        }
+        let base_color_factor = if color_count != 0 {
+            log::warn!("PLY vertex colors are averaged into the base color factor");
+            color_sum.map(|sum| (sum / color_count as f64) as f32)
+        } else {
+            [1.0; 4]
+        };
+        self.materials
+            .push(CookedMaterial::plain(base_color_factor));
+        self.add_flattened(
+            "",
+            blade_graphics::IDENTITY_TRANSFORM.into(),
+            self.materials.len() as u32 - 1,
+            flattened,
+            flattened_geos,
+        );
+    }
+}
@@ -2,6 +2,44 @@ use crate::{AssetHub, CameraParams, DummyResources, Object, Shaders, Vertex};
 use blade_graphics as gpu;
 use std::mem;
 
+// Resolution of the diffuse irradiance map, convolved from the environment map.
+const IRRADIANCE_SIZE: gpu::Extent = gpu::Extent {
+    width: 32,
+    height: 16,
+    depth: 1,
+};
+/// Maximum number of the shadow cascades.
+pub const MAX_SHADOW_CASCADES: u32 = 4;
+// Blend factor between the uniform and the logarithmic cascade splits.
+const CASCADE_SPLIT_LAMBDA: f32 = 0.75;
+// Distance where the logarithmic cascade splits start.
+const CASCADE_NEAR: f32 = 0.1;
+
+/// Cascaded shadow maps of the directional light.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowConfig {
+    /// Number of cascades splitting the view distance, up to
+    /// `MAX_SHADOW_CASCADES`. Zero disables the shadows.
+    pub cascade_count: u32,
+    /// Width and height of each cascade, in texels.
+    pub resolution: u32,
+    /// Distance from the camera covered by the cascades.
+    pub max_distance: f32,
+    /// Tint the surfaces by the cascade they are shadowed from.
+    pub show_cascades: bool,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            cascade_count: 3,
+            resolution: 1024,
+            max_distance: 50.0,
+            show_cascades: false,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct RasterConfig {
     pub clear_color: gpu::TextureColor,
@@ -12,6 +50,11 @@ pub struct RasterConfig {
     pub metallic: f32,
     /// When true, the sky fallback renders pure black instead of a blue gradient.
     pub space_sky: bool,
+    pub shadows: ShadowConfig,
+    /// Rotation and intensity of the environment map.
+    pub environment: crate::EnvironmentConfig,
+    /// Mask of the object layers to draw, both into the view and the shadows.
+    pub visible_layers: u32,
 }
 
 impl Default for RasterConfig {
@@ -36,6 +79,9 @@ impl Default for RasterConfig {
             roughness: 0.4,
             metallic: 0.0,
             space_sky: false,
+            shadows: ShadowConfig::default(),
+            environment: crate::EnvironmentConfig::default(),
+            visible_layers: crate::ALL_LAYERS,
         }
     }
 }
@@ -50,6 +96,8 @@ struct RasterFrameParams {
     light_color: [f32; 4],
     ambient_color: [f32; 4],
     material: [f32; 4],
+    // yaw and intensity of the environment map
+    environment: [f32; 4],
 }
 
 #[repr(C)]
@@ -59,6 +107,20 @@ struct RasterDrawParams {
     normal_quat: [f32; 4],
     base_color_factor: [f32; 4],
     material: [f32; 4],
+    // texture coordinate mappings, see `pack_mapping`
+    base_color_mapping: [[f32; 4]; 2],
+    normal_mapping: [[f32; 4]; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct RasterShadowParams {
+    cascade_view_proj: [[f32; 16]; MAX_SHADOW_CASCADES as usize],
+    // Size of a shadow texel in world units, per cascade
+    cascade_texel_sizes: [f32; MAX_SHADOW_CASCADES as usize],
+    cascade_count: u32,
+    show_cascades: u32,
+    pad: [u32; 2],
 }
 
 #[derive(blade_macros::ShaderData)]
@@ -66,9 +128,29 @@ struct RasterMainData {
     frame_params: RasterFrameParams,
     draw_params: RasterDrawParams,
     vertices: gpu::BufferPiece,
+    tex_coords1: gpu::BufferPiece,
     samp: gpu::Sampler,
     base_color_tex: gpu::TextureView,
     normal_tex: gpu::TextureView,
+    irradiance_map: gpu::TextureView,
+    shadow_params: RasterShadowParams,
+    shadow_map: gpu::TextureView,
+    shadow_sampler: gpu::Sampler,
+}
+
+#[derive(blade_macros::ShaderData)]
+struct RasterDepthData {
+    frame_params: RasterFrameParams,
+    draw_params: RasterDrawParams,
+    vertices: gpu::BufferPiece,
+    tex_coords1: gpu::BufferPiece,
+}
+
+#[derive(blade_macros::ShaderData)]
+struct RasterIrradianceData {
+    samp: gpu::Sampler,
+    env_map: gpu::TextureView,
+    out_irradiance: gpu::TextureView,
 }
 
 #[derive(blade_macros::ShaderData)]
@@ -79,11 +161,80 @@ struct RasterSkyData {
 }
 
 struct RasterPipelines {
+    shadow: gpu::RenderPipeline,
+    depth: gpu::RenderPipeline,
     main: gpu::RenderPipeline,
     sky: gpu::RenderPipeline,
+    irradiance: gpu::ComputePipeline,
 }
 
 impl RasterPipelines {
+    fn create_shadow(shader: &gpu::Shader, gpu: &gpu::Context) -> gpu::RenderPipeline {
+        let depth_layout = <RasterDepthData as gpu::ShaderData>::layout();
+        gpu.create_render_pipeline(gpu::RenderPipelineDesc {
+            name: "raster-shadow",
+            data_layouts: &[&depth_layout],
+            vertex: shader.at("raster_vs"),
+            vertex_fetches: &[],
+            primitive: gpu::PrimitiveState {
+                topology: gpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: Some(gpu::DepthStencilState {
+                format: gpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: gpu::CompareFunction::Less,
+                stencil: gpu::StencilState::default(),
+                // Keeps the lit surfaces from shadowing themselves
+                bias: gpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            fragment: None,
+            color_targets: &[],
+            multisample_state: gpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    fn create_depth(
+        shader: &gpu::Shader,
+        info: gpu::SurfaceInfo,
+        gpu: &gpu::Context,
+    ) -> gpu::RenderPipeline {
+        shader.check_struct_size::<RasterFrameParams>();
+        shader.check_struct_size::<RasterDrawParams>();
+        let depth_layout = <RasterDepthData as gpu::ShaderData>::layout();
+        gpu.create_render_pipeline(gpu::RenderPipelineDesc {
+            name: "raster-depth",
+            data_layouts: &[&depth_layout],
+            vertex: shader.at("raster_vs"),
+            vertex_fetches: &[],
+            primitive: gpu::PrimitiveState {
+                topology: gpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: Some(gpu::DepthStencilState {
+                format: gpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: gpu::CompareFunction::Less,
+                stencil: gpu::StencilState::default(),
+                bias: gpu::DepthBiasState::default(),
+            }),
+            fragment: None,
+            color_targets: &[gpu::ColorTargetState {
+                format: info.format,
+                blend: None,
+                write_mask: gpu::ColorWrites::empty(),
+                logic_op: None,
+            }],
+            multisample_state: gpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
     fn create_main(
         shader: &gpu::Shader,
         info: gpu::SurfaceInfo,
@@ -91,6 +242,7 @@ impl RasterPipelines {
     ) -> gpu::RenderPipeline {
         shader.check_struct_size::<RasterFrameParams>();
         shader.check_struct_size::<RasterDrawParams>();
+        shader.check_struct_size::<RasterShadowParams>();
         let main_layout = <RasterMainData as gpu::ShaderData>::layout();
         gpu.create_render_pipeline(gpu::RenderPipelineDesc {
             name: "raster",
@@ -101,16 +253,18 @@ impl RasterPipelines {
                 topology: gpu::PrimitiveTopology::TriangleList,
                 ..Default::default()
             },
+            // Only the surfaces left visible by the depth pre-pass are shaded
             depth_stencil: Some(gpu::DepthStencilState {
                 format: gpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: gpu::CompareFunction::Less,
+                depth_write_enabled: false,
+                depth_compare: gpu::CompareFunction::LessEqual,
                 stencil: gpu::StencilState::default(),
                 bias: gpu::DepthBiasState::default(),
             }),
             fragment: Some(shader.at("raster_fs")),
             color_targets: &[info.format.into()],
             multisample_state: gpu::MultisampleState::default(),
+            multiview: None,
         })
     }
 
@@ -140,6 +294,16 @@ impl RasterPipelines {
             fragment: Some(shader.at("raster_sky_fs")),
             color_targets: &[info.format.into()],
             multisample_state: gpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    fn create_irradiance(shader: &gpu::Shader, gpu: &gpu::Context) -> gpu::ComputePipeline {
+        let irradiance_layout = <RasterIrradianceData as gpu::ShaderData>::layout();
+        gpu.create_compute_pipeline(gpu::ComputePipelineDesc {
+            name: "raster-irradiance",
+            data_layouts: &[&irradiance_layout],
+            compute: shader.at("raster_irradiance"),
         })
     }
 
@@ -151,20 +315,108 @@ impl RasterPipelines {
     ) -> Result<Self, &'static str> {
         let shader = shader_man[shaders.raster].raw.as_ref().unwrap();
         Ok(Self {
+            shadow: Self::create_shadow(shader, gpu),
+            depth: Self::create_depth(shader, config.surface_info, gpu),
             main: Self::create_main(shader, config.surface_info, gpu),
             sky: Self::create_sky(shader, config.surface_info, gpu),
+            irradiance: Self::create_irradiance(shader, gpu),
         })
     }
 }
 
+/// Depth array with a layer per shadow cascade.
+struct ShadowMap {
+    texture: gpu::Texture,
+    array_view: gpu::TextureView,
+    layer_views: Vec<gpu::TextureView>,
+    layer_count: u32,
+    resolution: u32,
+}
+
+impl ShadowMap {
+    fn new(
+        layer_count: u32,
+        resolution: u32,
+        encoder: &mut gpu::CommandEncoder,
+        gpu: &gpu::Context,
+    ) -> Self {
+        let texture = gpu.create_texture(gpu::TextureDesc {
+            name: "raster-shadow",
+            format: gpu::TextureFormat::Depth32Float,
+            size: gpu::Extent {
+                width: resolution,
+                height: resolution,
+                depth: 1,
+            },
+            dimension: gpu::TextureDimension::D2,
+            array_layer_count: layer_count,
+            mip_level_count: 1,
+            usage: gpu::TextureUsage::TARGET | gpu::TextureUsage::RESOURCE,
+            sample_count: 1,
+            external: None,
+        });
+        let array_view = gpu.create_texture_view(
+            texture,
+            gpu::TextureViewDesc {
+                name: "raster-shadow",
+                format: gpu::TextureFormat::Depth32Float,
+                dimension: gpu::ViewDimension::D2Array,
+                subresources: &gpu::TextureSubresources::default(),
+            },
+        );
+        let layer_views = (0..layer_count)
+            .map(|layer| {
+                gpu.create_texture_view(
+                    texture,
+                    gpu::TextureViewDesc {
+                        name: &format!("raster-shadow{layer}"),
+                        format: gpu::TextureFormat::Depth32Float,
+                        dimension: gpu::ViewDimension::D2,
+                        subresources: &gpu::TextureSubresources {
+                            base_array_layer: layer,
+                            array_layer_count: std::num::NonZeroU32::new(1),
+                            ..Default::default()
+                        },
+                    },
+                )
+            })
+            .collect();
+        encoder.init_texture(texture);
+        Self {
+            texture,
+            array_view,
+            layer_views,
+            layer_count,
+            resolution,
+        }
+    }
+
+    fn destroy(&mut self, gpu: &gpu::Context) {
+        for view in self.layer_views.drain(..) {
+            gpu.destroy_texture_view(view);
+        }
+        gpu.destroy_texture_view(self.array_view);
+        gpu.destroy_texture(self.texture);
+    }
+}
+
 pub struct Rasterizer {
     shaders: Shaders,
     pipelines: RasterPipelines,
     sampler_linear: gpu::Sampler,
+    sampler_shadow: gpu::Sampler,
     debug: Option<crate::render::DebugRender>,
+    debug_draw: crate::DebugDraw,
     dummy: DummyResources,
     depth_texture: gpu::Texture,
     depth_view: gpu::TextureView,
+    irradiance_texture: gpu::Texture,
+    irradiance_view: gpu::TextureView,
+    /// Environment map that the irradiance was convolved from.
+    irradiance_source: Option<gpu::TextureView>,
+    shadow_map: ShadowMap,
+    /// Cascades rendered by the last `prepare`.
+    shadow_params: RasterShadowParams,
     surface_size: gpu::Extent,
     surface_info: gpu::SurfaceInfo,
 }
@@ -203,16 +455,54 @@ impl Rasterizer {
             mipmap_filter: gpu::FilterMode::Linear,
             ..Default::default()
         });
+        let sampler_shadow = gpu.create_sampler(gpu::SamplerDesc {
+            name: "raster-shadow",
+            address_modes: [gpu::AddressMode::ClampToEdge; 3],
+            mag_filter: gpu::FilterMode::Linear,
+            min_filter: gpu::FilterMode::Linear,
+            compare: Some(gpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
         let (depth_texture, depth_view) = Self::create_depth_target(config.surface_size, gpu);
+        let irradiance_texture = gpu.create_texture(gpu::TextureDesc {
+            name: "raster-irradiance",
+            format: gpu::TextureFormat::Rgba16Float,
+            size: IRRADIANCE_SIZE,
+            dimension: gpu::TextureDimension::D2,
+            array_layer_count: 1,
+            mip_level_count: 1,
+            usage: gpu::TextureUsage::RESOURCE | gpu::TextureUsage::STORAGE,
+            sample_count: 1,
+            external: None,
+        });
+        let irradiance_view = gpu.create_texture_view(
+            irradiance_texture,
+            gpu::TextureViewDesc {
+                name: "raster-irradiance",
+                format: gpu::TextureFormat::Rgba16Float,
+                dimension: gpu::ViewDimension::D2,
+                subresources: &gpu::TextureSubresources::default(),
+            },
+        );
+        encoder.init_texture(irradiance_texture);
+        // Placeholder until the shadows are enabled
+        let shadow_map = ShadowMap::new(1, 1, encoder, gpu);
 
         Self {
             shaders,
             pipelines,
             sampler_linear,
+            sampler_shadow,
             debug,
+            debug_draw: crate::DebugDraw::default(),
             dummy,
             depth_texture,
             depth_view,
+            irradiance_texture,
+            irradiance_view,
+            irradiance_source: None,
+            shadow_map,
+            shadow_params: bytemuck::Zeroable::zeroed(),
             surface_size: config.surface_size,
             surface_info: config.surface_info,
         }
@@ -225,9 +515,16 @@ impl Rasterizer {
         self.dummy.destroy(gpu);
         gpu.destroy_texture_view(self.depth_view);
         gpu.destroy_texture(self.depth_texture);
+        gpu.destroy_texture_view(self.irradiance_view);
+        gpu.destroy_texture(self.irradiance_texture);
+        self.shadow_map.destroy(gpu);
         gpu.destroy_sampler(self.sampler_linear);
+        gpu.destroy_sampler(self.sampler_shadow);
+        gpu.destroy_render_pipeline(&mut self.pipelines.shadow);
+        gpu.destroy_render_pipeline(&mut self.pipelines.depth);
         gpu.destroy_render_pipeline(&mut self.pipelines.main);
         gpu.destroy_render_pipeline(&mut self.pipelines.sky);
+        gpu.destroy_compute_pipeline(&mut self.pipelines.irradiance);
     }
 
     #[profiling::function]
@@ -255,8 +552,13 @@ impl Rasterizer {
         if self.shaders.raster != old.raster
             && let Ok(ref shader) = asset_hub.shaders[self.shaders.raster].raw
         {
+            self.pipelines.shadow = RasterPipelines::create_shadow(shader, gpu);
+            self.pipelines.depth = RasterPipelines::create_depth(shader, self.surface_info, gpu);
             self.pipelines.main = RasterPipelines::create_main(shader, self.surface_info, gpu);
             self.pipelines.sky = RasterPipelines::create_sky(shader, self.surface_info, gpu);
+            self.pipelines.irradiance = RasterPipelines::create_irradiance(shader, gpu);
+            // The convolution may have changed
+            self.irradiance_source = None;
         }
 
         true
@@ -291,6 +593,180 @@ impl Rasterizer {
         self.surface_size = size;
     }
 
+    /// Prepare the lighting for `render`.
+    ///
+    /// Renders the shadow cascades for the camera, and convolves
+    /// the diffuse irradiance when the environment map changes.
+    #[profiling::function]
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare(
+        &mut self,
+        encoder: &mut gpu::CommandEncoder,
+        camera: &crate::Camera,
+        objects: &[Object],
+        asset_hub: &AssetHub,
+        environment_map: Option<blade_asset::Handle<crate::Texture>>,
+        config: RasterConfig,
+        gpu: &gpu::Context,
+    ) {
+        if let Some(ref mut debug) = self.debug {
+            debug.reset_shapes();
+        }
+        self.render_shadows(encoder, camera, objects, asset_hub, config, gpu);
+
+        let env_view = environment_map.map(|handle| asset_hub.textures[handle].view);
+        if env_view == self.irradiance_source {
+            return;
+        }
+        self.irradiance_source = env_view;
+        let Some(env_map) = env_view else {
+            return;
+        };
+        if let mut pass = encoder.compute("raster-irradiance") {
+            let groups = self.pipelines.irradiance.get_dispatch_for(IRRADIANCE_SIZE);
+            let mut pc = pass.with(&self.pipelines.irradiance);
+            pc.bind(
+                0,
+                &RasterIrradianceData {
+                    samp: self.sampler_linear,
+                    env_map,
+                    out_irradiance: self.irradiance_view,
+                },
+            );
+            pc.dispatch(groups);
+        }
+    }
+
+    fn render_shadows(
+        &mut self,
+        encoder: &mut gpu::CommandEncoder,
+        camera: &crate::Camera,
+        objects: &[Object],
+        asset_hub: &AssetHub,
+        config: RasterConfig,
+        gpu: &gpu::Context,
+    ) {
+        self.shadow_params = self.make_shadow_params(camera, config);
+        let cascade_count = self.shadow_params.cascade_count;
+        if cascade_count == 0 {
+            return;
+        }
+
+        let resolution = config.shadows.resolution.max(1);
+        if self.shadow_map.layer_count != cascade_count || self.shadow_map.resolution != resolution
+        {
+            self.shadow_map.destroy(gpu);
+            self.shadow_map = ShadowMap::new(cascade_count, resolution, encoder, gpu);
+        }
+
+        for (layer_view, view_proj) in self
+            .shadow_map
+            .layer_views
+            .iter()
+            .zip(self.shadow_params.cascade_view_proj.iter())
+        {
+            let frame_params = RasterFrameParams {
+                view_proj: *view_proj,
+                ..bytemuck::Zeroable::zeroed()
+            };
+            if let mut pass = encoder.render(
+                "raster-shadow",
+                gpu::RenderTargetSet {
+                    colors: &[],
+                    depth_stencil: Some(gpu::RenderTarget {
+                        view: *layer_view,
+                        init_op: gpu::InitOp::Clear(gpu::TextureColor::White),
+                        finish_op: gpu::FinishOp::Store,
+                    }),
+                    depth_stencil_read_only: gpu::TexelAspects::empty(),
+                    multiview: None,
+                },
+            ) && let mut pc = pass.with(&self.pipelines.shadow)
+            {
+                Self::for_each_draw(
+                    objects,
+                    asset_hub,
+                    config.visible_layers,
+                    |model, geometry, draw_params| {
+                        pc.bind(
+                            0,
+                            &RasterDepthData {
+                                frame_params,
+                                draw_params,
+                                vertices: model.vertex_buffer.at(0),
+                                tex_coords1: tex_coords1_piece(model),
+                            },
+                        );
+                        draw_geometry(&mut pc, model, geometry);
+                    },
+                );
+            }
+        }
+    }
+
+    /// Call `f` for every geometry of the objects, with its draw parameters.
+    fn for_each_draw(
+        objects: &[Object],
+        asset_hub: &AssetHub,
+        visible_layers: u32,
+        mut f: impl FnMut(&crate::Model, &crate::model::Geometry, RasterDrawParams),
+    ) {
+        for object in objects
+            .iter()
+            .filter(|object| object.layers & visible_layers != 0)
+        {
+            let model = &asset_hub.models[object.model];
+            let object_transform = mat4_transform(&object.transform);
+            let object_normal = object_transform.inverse().transpose();
+
+            for geometry in model.geometries.iter() {
+                let geometry_transform = mat4_transform(&geometry.transform);
+                let world_transform = object_transform * geometry_transform;
+                let normal_transform = object_normal * geometry_transform.inverse().transpose();
+                let normal_basis = glam::Mat3::from_cols(
+                    normal_transform.x_axis.truncate().normalize_or_zero(),
+                    normal_transform.y_axis.truncate().normalize_or_zero(),
+                    normal_transform.z_axis.truncate().normalize_or_zero(),
+                );
+                let normal_quat = glam::Quat::from_mat3(&normal_basis).normalize();
+                let material = &model.materials[geometry.material_index];
+                let normal_scale = match material.normal_texture {
+                    Some(_) => material.normal_scale,
+                    None => 0.0,
+                };
+                let overrides = &object.material_overrides;
+
+                f(
+                    model,
+                    geometry,
+                    RasterDrawParams {
+                        model: world_transform.to_cols_array(),
+                        normal_quat: normal_quat.to_array(),
+                        base_color_factor: [0, 1, 2, 3].map(|i| {
+                            material.base_color_factor[i]
+                                * object.color_tint[i]
+                                * overrides.base_color_factor[i]
+                        }),
+                        material: [
+                            normal_scale,
+                            overrides.roughness_factor,
+                            overrides.metallic_factor,
+                            0.0,
+                        ],
+                        base_color_mapping: pack_mapping(
+                            &material.base_color_mapping,
+                            geometry.has_tex_coords1,
+                        ),
+                        normal_mapping: pack_mapping(
+                            &material.normal_mapping,
+                            geometry.has_tex_coords1,
+                        ),
+                    },
+                );
+            }
+        }
+    }
+
     #[profiling::function]
     pub fn render(
         &mut self,
@@ -301,85 +777,93 @@ impl Rasterizer {
         environment_map: Option<blade_asset::Handle<crate::Texture>>,
         config: RasterConfig,
     ) {
-        let env_map_enabled = environment_map.is_some();
-        let frame_params = self.make_frame_params(camera, config, env_map_enabled);
-        if let mut pc = pass.with(&self.pipelines.main) {
-            for object in objects.iter() {
-                let model = &asset_hub.models[object.model];
-                let object_transform = mat4_transform(&object.transform);
-                let object_normal = object_transform.inverse().transpose();
-
-                for geometry in model.geometries.iter() {
-                    let geometry_transform = mat4_transform(&geometry.transform);
-                    let world_transform = object_transform * geometry_transform;
-                    let normal_transform = object_normal * geometry_transform.inverse().transpose();
-                    let normal_basis = glam::Mat3::from_cols(
-                        normal_transform.x_axis.truncate().normalize_or_zero(),
-                        normal_transform.y_axis.truncate().normalize_or_zero(),
-                        normal_transform.z_axis.truncate().normalize_or_zero(),
+        let env_view = environment_map.map(|handle| asset_hub.textures[handle].view);
+        // The irradiance is only valid if `prepare` has seen this environment map
+        let irradiance_map = match env_view {
+            Some(_) if env_view == self.irradiance_source => Some(self.irradiance_view),
+            _ => None,
+        };
+        let frame_params =
+            self.make_frame_params(camera, config, env_view.is_some(), irradiance_map.is_some());
+
+        // Depth pre-pass, so that the shading only runs once per pixel
+        if let mut pc = pass.with(&self.pipelines.depth) {
+            Self::for_each_draw(
+                objects,
+                asset_hub,
+                config.visible_layers,
+                |model, geometry, draw_params| {
+                    pc.bind(
+                        0,
+                        &RasterDepthData {
+                            frame_params,
+                            draw_params,
+                            vertices: model.vertex_buffer.at(0),
+                            tex_coords1: tex_coords1_piece(model),
+                        },
                     );
-                    let normal_quat = glam::Quat::from_mat3(&normal_basis).normalize();
-                    let material = &model.materials[geometry.material_index];
+                    draw_geometry(&mut pc, model, geometry);
+                },
+            );
+        }
 
-                    let (normal_tex, normal_scale) = match material.normal_texture {
-                        Some(handle) => {
-                            let texture = &asset_hub.textures[handle];
-                            (texture.view, material.normal_scale)
-                        }
-                        None => (self.dummy.white_view, 0.0),
+        if let mut pc = pass.with(&self.pipelines.main) {
+            Self::for_each_draw(
+                objects,
+                asset_hub,
+                config.visible_layers,
+                |model, geometry, draw_params| {
+                    let material = &model.materials[geometry.material_index];
+                    let normal_tex = match material.normal_texture {
+                        Some(handle) => asset_hub.texture_view(handle),
+                        None => self.dummy.white_view,
                     };
                     let base_color_tex = match material.base_color_texture {
-                        Some(handle) => asset_hub.textures[handle].view,
+                        Some(handle) => asset_hub.texture_view(handle),
                         None => self.dummy.white_view,
                     };
-
                     pc.bind(
                         0,
                         &RasterMainData {
                             frame_params,
-                            draw_params: RasterDrawParams {
-                                model: world_transform.to_cols_array(),
-                                normal_quat: normal_quat.to_array(),
-                                base_color_factor: [
-                                    material.base_color_factor[0] * object.color_tint[0],
-                                    material.base_color_factor[1] * object.color_tint[1],
-                                    material.base_color_factor[2] * object.color_tint[2],
-                                    material.base_color_factor[3] * object.color_tint[3],
-                                ],
-                                material: [normal_scale, 0.0, 0.0, 0.0],
-                            },
+                            draw_params,
                             vertices: model.vertex_buffer.at(0),
+                            tex_coords1: tex_coords1_piece(model),
                             samp: self.sampler_linear,
                             base_color_tex,
                             normal_tex,
+                            irradiance_map: irradiance_map.unwrap_or(self.dummy.black_view),
+                            shadow_params: self.shadow_params,
+                            shadow_map: self.shadow_map.array_view,
+                            shadow_sampler: self.sampler_shadow,
                         },
                     );
-
-                    let vertex_count = geometry.vertex_range.end - geometry.vertex_range.start;
-                    let index_count = geometry.triangle_count * 3;
-                    match geometry.index_type {
-                        Some(index_type) => {
-                            pc.draw_indexed(
-                                model.index_buffer.at(geometry.index_offset),
-                                index_type,
-                                index_count,
-                                geometry.vertex_range.start as i32,
-                                0,
-                                1,
-                            );
-                        }
-                        None => {
-                            pc.draw(geometry.vertex_range.start, vertex_count, 0, 1);
-                        }
-                    }
-                }
-            }
+                    draw_geometry(&mut pc, model, geometry);
+                },
+            );
         }
 
-        let env_map = environment_map
-            .map(|handle| asset_hub.textures[handle].view)
-            .unwrap_or(self.dummy.black_view);
-        self.render_sky(pass, frame_params, env_map);
+        self.render_sky(
+            pass,
+            frame_params,
+            env_view.unwrap_or(self.dummy.black_view),
+        );
+    }
+
+    /// Return the batch of debug shapes, to be drawn by `render_debug_lines` of this frame.
+    pub fn debug_draw(&mut self) -> &mut crate::DebugDraw {
+        &mut self.debug_draw
+    }
+
+    /// Upload the debug shapes collected for this frame, and start a new batch.
+    ///
+    /// Has to be called after `prepare`, and before `render_debug_lines`.
+    /// Does nothing if there are no shapes.
+    pub fn flush_debug_draw(&mut self, gpu: &gpu::Context, temp: &mut crate::FrameResources) {
+        match self.debug {
+            Some(ref mut debug) => debug.upload_shapes(&mut self.debug_draw, gpu, temp),
+            None => self.debug_draw.clear(),
+        }
     }
 
     pub fn render_debug_lines(
@@ -391,7 +875,7 @@ impl Rasterizer {
         let Some(debug) = self.debug.as_ref() else {
             return;
         };
-        if debug_lines.is_empty() {
+        if debug_lines.is_empty() && !debug.has_shapes() {
             return;
         }
         let camera_params = self.make_camera_params(camera);
@@ -408,7 +892,7 @@ impl Rasterizer {
         config: RasterConfig,
     ) {
         let env_map_enabled = environment_map.is_some();
-        let frame_params = self.make_frame_params(camera, config, env_map_enabled);
+        let frame_params = self.make_frame_params(camera, config, env_map_enabled, false);
         let env_map = environment_map
             .map(|handle| asset_hub.textures[handle].view)
             .unwrap_or(self.dummy.black_view);
@@ -461,17 +945,96 @@ impl Rasterizer {
     }
 
     fn make_camera_params(&self, camera: &crate::Camera) -> CameraParams {
-        let fov_x = 2.0
-            * ((camera.fov_y * 0.5).tan() * self.surface_size.width as f32
-                / self.surface_size.height as f32)
-                .atan();
-        CameraParams {
-            position: camera.pos.into(),
-            depth: camera.depth,
-            orientation: camera.rot.into(),
-            fov: [fov_x, camera.fov_y],
-            target_size: [self.surface_size.width, self.surface_size.height],
+        CameraParams::new(camera, self.surface_size)
+    }
+
+    /// Fit the shadow cascades to the slices of the camera frustum.
+    ///
+    /// The cascades are bounding spheres of the slices, which keeps their size
+    /// independent of the camera rotation, and they only move in whole texels.
+    /// Together this keeps the shadow edges from shimmering.
+    fn make_shadow_params(
+        &self,
+        camera: &crate::Camera,
+        config: RasterConfig,
+    ) -> RasterShadowParams {
+        let mut params = RasterShadowParams {
+            cascade_count: config.shadows.cascade_count.min(MAX_SHADOW_CASCADES),
+            show_cascades: config.shadows.show_cascades as u32,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        let light_dir = glam::Vec3::from(config.light_dir).normalize_or_zero();
+        if light_dir == glam::Vec3::ZERO {
+            params.cascade_count = 0;
         }
+        if params.cascade_count == 0 {
+            return params;
+        }
+
+        let camera_transform = glam::Mat4::from_rotation_translation(
+            glam::Quat::from(camera.rot),
+            glam::Vec3::from(camera.pos),
+        );
+        let aspect = self.surface_size.width as f32 / self.surface_size.height.max(1) as f32;
+        let inv_proj = glam::Mat4::from(camera.projection_matrix(aspect, 0.01)).inverse();
+        // View space rays through the corners of the frustum
+        let corner_rays = [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]].map(|[x, y]| {
+            let near = inv_proj.project_point3(glam::Vec3::new(x, y, 0.25));
+            let far = inv_proj.project_point3(glam::Vec3::new(x, y, 0.75));
+            (near, far - near)
+        });
+        // The light travels against its direction
+        let forward = -light_dir;
+        let up = if forward.y.abs() > 0.99 {
+            glam::Vec3::Z
+        } else {
+            glam::Vec3::Y
+        };
+        let light_view = glam::Mat4::look_to_rh(glam::Vec3::ZERO, forward, up);
+        let resolution = config.shadows.resolution.max(1) as f32;
+        let max_distance = config.shadows.max_distance.max(CASCADE_NEAR);
+
+        let mut slice_start = 0.0;
+        for cascade in 0..params.cascade_count as usize {
+            let fraction = (cascade + 1) as f32 / params.cascade_count as f32;
+            let uniform = CASCADE_NEAR + (max_distance - CASCADE_NEAR) * fraction;
+            let logarithmic = CASCADE_NEAR * (max_distance / CASCADE_NEAR).powf(fraction);
+            let slice_end =
+                CASCADE_SPLIT_LAMBDA * logarithmic + (1.0 - CASCADE_SPLIT_LAMBDA) * uniform;
+
+            let mut corners = [glam::Vec3::ZERO; 8];
+            for (i, &(origin, dir)) in corner_rays.iter().enumerate() {
+                for (j, distance) in [slice_start, slice_end].into_iter().enumerate() {
+                    let t = (-distance - origin.z) / dir.z;
+                    corners[j * 4 + i] = camera_transform.transform_point3(origin + t * dir);
+                }
+            }
+            let center = corners.iter().sum::<glam::Vec3>() / corners.len() as f32;
+            let radius = corners
+                .iter()
+                .map(|corner| corner.distance(center))
+                .fold(0.0, f32::max);
+            // Avoid the size flickering with the rounding errors
+            let radius = (radius * 16.0).ceil() / 16.0;
+            let texel_size = 2.0 * radius / resolution;
+
+            let light_center = light_view.transform_point3(center);
+            let x = (light_center.x / texel_size).floor() * texel_size;
+            let y = (light_center.y / texel_size).floor() * texel_size;
+            // Extend the depth range towards the light, to catch the casters outside of the view
+            let proj = glam::Mat4::orthographic_rh(
+                x - radius,
+                x + radius,
+                y - radius,
+                y + radius,
+                -light_center.z - radius - max_distance,
+                -light_center.z + radius,
+            );
+            params.cascade_view_proj[cascade] = (proj * light_view).to_cols_array();
+            params.cascade_texel_sizes[cascade] = texel_size;
+            slice_start = slice_end;
+        }
+        params
     }
 
     fn make_frame_params(
@@ -479,35 +1042,13 @@ impl Rasterizer {
         camera: &crate::Camera,
         config: RasterConfig,
         env_map_enabled: bool,
+        irradiance_enabled: bool,
     ) -> RasterFrameParams {
         let pos = glam::Vec3::from(camera.pos);
         let rot = glam::Quat::from(camera.rot);
         let view = glam::Mat4::from_rotation_translation(rot, pos).inverse();
-        let near = 0.01;
-        let far = camera.depth;
-        let proj = if let Some(fov) = camera.fov {
-            // Asymmetric off-center projection for XR
-            let left = -fov.left.tan() * near;
-            let right = fov.right.tan() * near;
-            let bottom = -fov.down.tan() * near;
-            let top = fov.up.tan() * near;
-            let w = right - left;
-            let h = top - bottom;
-            glam::Mat4::from_cols(
-                glam::Vec4::new(2.0 * near / w, 0.0, 0.0, 0.0),
-                glam::Vec4::new(0.0, 2.0 * near / h, 0.0, 0.0),
-                glam::Vec4::new(
-                    (right + left) / w,
-                    (top + bottom) / h,
-                    far / (near - far),
-                    -1.0,
-                ),
-                glam::Vec4::new(0.0, 0.0, far * near / (near - far), 0.0),
-            )
-        } else {
-            let aspect = self.surface_size.width as f32 / self.surface_size.height.max(1) as f32;
-            glam::Mat4::perspective_rh(camera.fov_y, aspect, near, far)
-        };
+        let aspect = self.surface_size.width as f32 / self.surface_size.height.max(1) as f32;
+        let proj = glam::Mat4::from(camera.projection_matrix(aspect, 0.01));
         let view_proj = proj * view;
         let inv_view_proj = view_proj.inverse();
         let light_dir = glam::Vec3::from(config.light_dir).normalize_or_zero();
@@ -528,6 +1069,12 @@ impl Rasterizer {
                 config.roughness,
                 config.metallic,
                 env_map_enabled as u32 as f32,
+                irradiance_enabled as u32 as f32,
+            ],
+            environment: [
+                config.environment.yaw,
+                config.environment.intensity,
+                0.0,
                 0.0,
             ],
         }
@@ -579,6 +1126,55 @@ impl gpu::Vertex for Vertex {
     }
 }
 
+fn draw_geometry(
+    pc: &mut gpu::PipelineEncoder,
+    model: &crate::Model,
+    geometry: &crate::model::Geometry,
+) {
+    match geometry.index_type {
+        Some(index_type) => {
+            pc.draw_indexed(
+                model.index_buffer.at(geometry.index_offset),
+                index_type,
+                geometry.triangle_count * 3,
+                geometry.vertex_range.start as i32,
+                0,
+                1,
+            );
+        }
+        None => {
+            let vertex_count = geometry.vertex_range.end - geometry.vertex_range.start;
+            pc.draw(geometry.vertex_range.start, vertex_count, 0, 1);
+        }
+    }
+}
+
+/// Pack the texture coordinate mapping into two vectors:
+/// the linear part, and the offset followed by the set.
+fn pack_mapping(mapping: &crate::model::TextureMapping, has_tex_coords1: bool) -> [[f32; 4]; 2] {
+    let [c0, c1, offset] = mapping.transform;
+    // without the second set, the first one is used instead
+    let tex_coord = if has_tex_coords1 {
+        mapping.tex_coord
+    } else {
+        0
+    };
+    [
+        [c0[0], c0[1], c1[0], c1[1]],
+        [offset[0], offset[1], tex_coord as f32, 0.0],
+    ]
+}
+
+/// Buffer of the second texture coordinate set, if the model has it.
+/// Otherwise, the vertex buffer is bound in its place, and never read.
+fn tex_coords1_piece(model: &crate::Model) -> gpu::BufferPiece {
+    if model.tex_coords1_buffer == gpu::Buffer::default() {
+        model.vertex_buffer.at(0)
+    } else {
+        model.tex_coords1_buffer.at(0)
+    }
+}
+
 fn mat4_transform(t: &gpu::Transform) -> glam::Mat4 {
     glam::Mat4 {
         x_axis: t.x.into(),
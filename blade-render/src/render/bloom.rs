@@ -0,0 +1,283 @@
+/// Largest number of levels in the chain, the first one being at half the resolution.
+const MAX_LEVELS: usize = 7;
+const LEVEL_FORMAT: blade_graphics::TextureFormat = blade_graphics::TextureFormat::Rgba16Float;
+
+/// Glow around the bright parts of the image, composited before the tone mapping.
+///
+/// There is no threshold: the whole image is blurred, and the blur
+/// replaces a fraction of it, so the energy is preserved.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BloomConfig {
+    pub enabled: bool,
+    /// Fraction of the radiance replaced by the blurred one.
+    pub intensity: f32,
+    /// Fraction of each coarser level blended into the finer one,
+    /// from 0 to 1. Higher values spread the glow wider.
+    pub radius: f32,
+}
+
+impl Default for BloomConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            intensity: 0.04,
+            radius: 0.7,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct BloomParams {
+    scatter: f32,
+    use_accumulation: u32,
+    pad: [u32; 2],
+}
+
+#[derive(blade_macros::ShaderData)]
+struct DownsampleFirstData {
+    params: BloomParams,
+    t_albedo: blade_graphics::TextureView,
+    light_diffuse: blade_graphics::TextureView,
+    t_emission: blade_graphics::TextureView,
+    t_medium: blade_graphics::TextureView,
+    t_accumulation: blade_graphics::TextureView,
+    output: blade_graphics::TextureView,
+}
+
+#[derive(blade_macros::ShaderData)]
+struct DownsampleData {
+    input: blade_graphics::TextureView,
+    samp: blade_graphics::Sampler,
+    output: blade_graphics::TextureView,
+}
+
+#[derive(blade_macros::ShaderData)]
+struct UpsampleData {
+    params: BloomParams,
+    input: blade_graphics::TextureView,
+    samp: blade_graphics::Sampler,
+    blended: blade_graphics::TextureView,
+}
+
+struct BloomPipelines {
+    downsample_first: blade_graphics::ComputePipeline,
+    downsample: blade_graphics::ComputePipeline,
+    upsample: blade_graphics::ComputePipeline,
+}
+
+impl BloomPipelines {
+    fn new(shader: &blade_graphics::Shader, gpu: &blade_graphics::Context) -> Self {
+        shader.check_struct_size::<BloomParams>();
+        let first_layout = <DownsampleFirstData as blade_graphics::ShaderData>::layout();
+        let down_layout = <DownsampleData as blade_graphics::ShaderData>::layout();
+        let up_layout = <UpsampleData as blade_graphics::ShaderData>::layout();
+        Self {
+            downsample_first: gpu.create_compute_pipeline(blade_graphics::ComputePipelineDesc {
+                name: "bloom-downsample-first",
+                data_layouts: &[&first_layout],
+                compute: shader.at("downsample_first"),
+            }),
+            downsample: gpu.create_compute_pipeline(blade_graphics::ComputePipelineDesc {
+                name: "bloom-downsample",
+                data_layouts: &[&down_layout],
+                compute: shader.at("downsample"),
+            }),
+            upsample: gpu.create_compute_pipeline(blade_graphics::ComputePipelineDesc {
+                name: "bloom-upsample",
+                data_layouts: &[&up_layout],
+                compute: shader.at("upsample"),
+            }),
+        }
+    }
+
+    fn destroy(&mut self, gpu: &blade_graphics::Context) {
+        gpu.destroy_compute_pipeline(&mut self.downsample_first);
+        gpu.destroy_compute_pipeline(&mut self.downsample);
+        gpu.destroy_compute_pipeline(&mut self.upsample);
+    }
+}
+
+/// Sizes of the chain levels for the render resolution.
+///
+/// Every level is half of the previous one, rounded up, and the chain
+/// stops before any side gets below 2 pixels.
+fn level_sizes(size: blade_graphics::Extent) -> Vec<blade_graphics::Extent> {
+    let mut sizes = Vec::new();
+    let mut level = size;
+    while sizes.len() < MAX_LEVELS && level.width.min(level.height) >= 4 {
+        level = blade_graphics::Extent {
+            width: level.width.div_ceil(2),
+            height: level.height.div_ceil(2),
+            depth: 1,
+        };
+        sizes.push(level);
+    }
+    sizes
+}
+
+pub(super) struct Bloom {
+    pipelines: BloomPipelines,
+    sampler: blade_graphics::Sampler,
+    levels: Vec<(super::RenderTarget<1>, blade_graphics::Extent)>,
+    /// Frame, for which the chain was produced.
+    frame_index: Option<usize>,
+}
+
+impl Bloom {
+    pub(super) fn init(
+        encoder: &mut blade_graphics::CommandEncoder,
+        gpu: &blade_graphics::Context,
+        shader: &blade_graphics::Shader,
+        size: blade_graphics::Extent,
+    ) -> Self {
+        let mut this = Self {
+            pipelines: BloomPipelines::new(shader, gpu),
+            sampler: gpu.create_sampler(blade_graphics::SamplerDesc {
+                name: "bloom",
+                address_modes: [blade_graphics::AddressMode::ClampToEdge; 3],
+                mag_filter: blade_graphics::FilterMode::Linear,
+                min_filter: blade_graphics::FilterMode::Linear,
+                mipmap_filter: blade_graphics::FilterMode::Nearest,
+                ..Default::default()
+            }),
+            levels: Vec::new(),
+            frame_index: None,
+        };
+        this.resize(size, encoder, gpu);
+        this
+    }
+
+    /// Recreate the chain for a new render resolution.
+    pub(super) fn resize(
+        &mut self,
+        size: blade_graphics::Extent,
+        encoder: &mut blade_graphics::CommandEncoder,
+        gpu: &blade_graphics::Context,
+    ) {
+        for (target, _) in self.levels.drain(..) {
+            target.destroy(gpu);
+        }
+        for (i, level_size) in level_sizes(size).into_iter().enumerate() {
+            let target = super::RenderTarget::new(
+                &format!("bloom{i}"),
+                LEVEL_FORMAT,
+                level_size,
+                encoder,
+                gpu,
+            );
+            self.levels.push((target, level_size));
+        }
+        self.frame_index = None;
+    }
+
+    pub(super) fn recreate_pipelines(
+        &mut self,
+        shader: &blade_graphics::Shader,
+        gpu: &blade_graphics::Context,
+    ) {
+        self.pipelines = BloomPipelines::new(shader, gpu);
+    }
+
+    /// The finest level with the bloom of the given frame, if it's produced.
+    pub(super) fn output(&self, frame_index: usize) -> Option<blade_graphics::TextureView> {
+        match self.levels.first() {
+            Some(&(ref target, _)) if self.frame_index == Some(frame_index) => {
+                Some(target.views[0])
+            }
+            _ => None,
+        }
+    }
+
+    pub(super) fn sampler(&self) -> blade_graphics::Sampler {
+        self.sampler
+    }
+
+    pub(super) fn destroy(&mut self, gpu: &blade_graphics::Context) {
+        for (target, _) in self.levels.drain(..) {
+            target.destroy(gpu);
+        }
+        gpu.destroy_sampler(self.sampler);
+        self.pipelines.destroy(gpu);
+    }
+}
+
+impl super::RayTracer {
+    /// Blur the image shown by `post_proc` into the bloom chain.
+    ///
+    /// Has to be called after `denoise`, and the result is only used
+    /// by `post_proc` of the same frame, with `PostProcConfig::bloom` enabled.
+    /// Does nothing if the bloom is disabled, or the resolution is too small.
+    #[profiling::function]
+    pub fn render_bloom(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        config: BloomConfig,
+    ) {
+        if !config.enabled || self.bloom.levels.is_empty() {
+            return;
+        }
+        let cur = self.frame_index % 2;
+        let params = BloomParams {
+            scatter: config.radius.clamp(0.0, 1.0),
+            use_accumulation: (self.accumulated_frames != 0) as u32,
+            pad: [0; 2],
+        };
+        let levels = &self.bloom.levels;
+        let pipelines = &self.bloom.pipelines;
+
+        {
+            let (ref target, size) = levels[0];
+            let mut pass = command_encoder.compute("bloom-downsample-first");
+            let mut pc = pass.with(&pipelines.downsample_first);
+            let groups = pipelines.downsample_first.get_dispatch_for(size);
+            pc.bind(
+                0,
+                &DownsampleFirstData {
+                    params,
+                    t_albedo: self.targets.albedo.views[0],
+                    light_diffuse: self.targets.light_diffuse.views[self.post_proc_input_index],
+                    t_emission: self.targets.emission.views[0],
+                    t_medium: self.targets.medium.views[cur],
+                    t_accumulation: self.targets.accumulation.views[0],
+                    output: target.views[0],
+                },
+            );
+            pc.dispatch(groups);
+        }
+        for pair in levels.windows(2) {
+            let (ref source, _) = pair[0];
+            let (ref target, size) = pair[1];
+            let mut pass = command_encoder.compute("bloom-downsample");
+            let mut pc = pass.with(&pipelines.downsample);
+            let groups = pipelines.downsample.get_dispatch_for(size);
+            pc.bind(
+                0,
+                &DownsampleData {
+                    input: source.views[0],
+                    samp: self.bloom.sampler,
+                    output: target.views[0],
+                },
+            );
+            pc.dispatch(groups);
+        }
+        for pair in levels.windows(2).rev() {
+            let (ref target, size) = pair[0];
+            let (ref source, _) = pair[1];
+            let mut pass = command_encoder.compute("bloom-upsample");
+            let mut pc = pass.with(&pipelines.upsample);
+            let groups = pipelines.upsample.get_dispatch_for(size);
+            pc.bind(
+                0,
+                &UpsampleData {
+                    params,
+                    input: source.views[0],
+                    samp: self.bloom.sampler,
+                    blended: target.views[0],
+                },
+            );
+            pc.dispatch(groups);
+        }
+        self.bloom.frame_index = Some(self.frame_index);
+    }
+}
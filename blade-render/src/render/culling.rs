@@ -0,0 +1,121 @@
+/// Instance mask bit of the rays from the camera.
+pub(super) const INSTANCE_MASK_PRIMARY: u32 = 0x1;
+
+/// Filtering of the TLAS instances against the camera,
+/// which is applied by the next `build_scene` or `update_scene`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CullingConfig {
+    /// Objects with the bounding spheres entirely farther than this
+    /// from the camera are left out of the TLAS. Infinity disables it.
+    pub max_distance: f32,
+    /// Hide the objects with the bounding spheres entirely outside of
+    /// the camera frustum from the primary rays. They are still seen
+    /// by the shadow and the indirect rays.
+    /// Has no effect with an open lens, which defocuses the rays.
+    pub frustum: bool,
+}
+
+impl Default for CullingConfig {
+    fn default() -> Self {
+        Self {
+            max_distance: f32::INFINITY,
+            frustum: false,
+        }
+    }
+}
+
+/// Instance counts of the last TLAS build.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CullingStats {
+    /// Instances of the scene.
+    pub instances: u32,
+    /// Instances in the TLAS, after the culling.
+    pub kept: u32,
+    /// Kept instances that are seen by the primary rays.
+    pub primary: u32,
+}
+
+/// Culling volume derived from the camera.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct Culling {
+    position: glam::Vec3,
+    max_distance: f32,
+    /// Side and far planes of the frustum, facing inwards.
+    planes: Option<[glam::Vec4; 5]>,
+}
+
+impl Default for Culling {
+    fn default() -> Self {
+        Self {
+            position: glam::Vec3::ZERO,
+            max_distance: f32::INFINITY,
+            planes: None,
+        }
+    }
+}
+
+impl Culling {
+    pub(super) fn new(
+        camera: &crate::Camera,
+        size: blade_graphics::Extent,
+        config: &CullingConfig,
+    ) -> Self {
+        let position = glam::Vec3::from(camera.pos);
+        let planes = if config.frustum && camera.lens.aperture_radius(camera.fov_y) == 0.0 {
+            let rot = glam::Quat::from(camera.rot);
+            let view = glam::Mat4::from_rotation_translation(rot, position).inverse();
+            let aspect = size.width as f32 / size.height.max(1) as f32;
+            let proj = glam::Mat4::from(camera.projection_matrix(aspect, 0.01));
+            let m = proj * view;
+            // The near plane is left out, since the rays start at the camera
+            let planes = [
+                m.row(3) + m.row(0),
+                m.row(3) - m.row(0),
+                m.row(3) + m.row(1),
+                m.row(3) - m.row(1),
+                m.row(3) - m.row(2),
+            ];
+            Some(planes.map(|p| p / p.truncate().length()))
+        } else {
+            None
+        };
+        if planes.is_none() && config.max_distance.is_infinite() {
+            // Keep it equal to the disabled culling of any other camera
+            return Self::default();
+        }
+        Self {
+            position,
+            max_distance: config.max_distance,
+            planes,
+        }
+    }
+
+    /// Mask of an instance with the given bounding sphere radius
+    /// around its origin, or 0 if it's culled entirely.
+    pub(super) fn instance_mask(
+        &self,
+        instance: &blade_graphics::AccelerationStructureInstance,
+        radius: f32,
+    ) -> u32 {
+        if instance.mask == 0 {
+            return 0;
+        }
+        let t = &instance.transform;
+        let center = glam::Vec3::new(t.x.w, t.y.w, t.z.w);
+        // The largest scale along the axes of the linear part
+        let scale = glam::Vec3::new(t.x.x, t.y.x, t.z.x)
+            .length()
+            .max(glam::Vec3::new(t.x.y, t.y.y, t.z.y).length())
+            .max(glam::Vec3::new(t.x.z, t.y.z, t.z.z).length());
+        let radius = radius * scale;
+        if center.distance(self.position) - radius > self.max_distance {
+            return 0;
+        }
+        match self.planes {
+            Some(planes) if planes.iter().any(|p| p.dot(center.extend(1.0)) < -radius) => {
+                instance.mask & !INSTANCE_MASK_PRIMARY
+            }
+            _ => instance.mask,
+        }
+    }
+}
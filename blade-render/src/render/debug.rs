@@ -22,6 +22,87 @@ pub struct DebugLine {
     pub b: DebugPoint,
 }
 
+/// Immediate-mode batch of debug shapes, drawn as lines on top of the frame.
+///
+/// The shapes are collected on CPU, uploaded once per frame,
+/// and cleared afterwards. There is no limit on the number of lines.
+#[derive(Default)]
+pub struct DebugDraw {
+    lines: Vec<DebugLine>,
+}
+
+impl DebugDraw {
+    /// Number of segments approximating a circle of a sphere.
+    const CIRCLE_SEGMENTS: usize = 32;
+
+    /// Add a line between two points.
+    /// The color is packed RGBA with the red in the lowest byte.
+    pub fn line(&mut self, a: mint::Vector3<f32>, b: mint::Vector3<f32>, color: u32) {
+        self.lines.push(DebugLine {
+            a: DebugPoint {
+                pos: a.into(),
+                color,
+            },
+            b: DebugPoint {
+                pos: b.into(),
+                color,
+            },
+        });
+    }
+
+    /// Add the edges of an axis-aligned box.
+    pub fn aabb(&mut self, min: mint::Vector3<f32>, max: mint::Vector3<f32>, color: u32) {
+        let corner = |i: usize| -> mint::Vector3<f32> {
+            [
+                if i & 1 != 0 { max.x } else { min.x },
+                if i & 2 != 0 { max.y } else { min.y },
+                if i & 4 != 0 { max.z } else { min.z },
+            ]
+            .into()
+        };
+        for i in 0..8 {
+            // connect each corner to the neighbors with a higher index
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    /// Add a sphere, outlined by the circles around each of the axes.
+    pub fn sphere(&mut self, center: mint::Vector3<f32>, radius: f32, color: u32) {
+        let center = glam::Vec3::from(center);
+        for axis in 0..3 {
+            let point = |k: usize| {
+                let angle = k as f32 * 2.0 * std::f32::consts::PI / Self::CIRCLE_SEGMENTS as f32;
+                let (sin, cos) = angle.sin_cos();
+                let mut offset = glam::Vec3::ZERO;
+                offset[(axis + 1) % 3] = radius * cos;
+                offset[(axis + 2) % 3] = radius * sin;
+                (center + offset).into()
+            };
+            for k in 0..Self::CIRCLE_SEGMENTS {
+                self.line(point(k), point(k + 1), color);
+            }
+        }
+    }
+
+    /// Return the lines collected so far.
+    pub fn lines(&self) -> &[DebugLine] {
+        &self.lines
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Remove all the shapes.
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
 #[derive(blade_macros::ShaderData)]
 struct DebugDrawData {
     camera: super::CameraParams,
@@ -94,8 +175,10 @@ fn create_draw_pipeline(
             format,
             blend: Some(blade_graphics::BlendState::ALPHA_BLENDING),
             write_mask: blade_graphics::ColorWrites::all(),
+            logic_op: None,
         }],
         multisample_state: blade_graphics::MultisampleState::default(),
+        multiview: None,
     })
 }
 
@@ -119,6 +202,7 @@ fn create_blit_pipeline(
         fragment: Some(shader.at("blit_fs")),
         color_targets: &[format.into()],
         multisample_state: blade_graphics::MultisampleState::default(),
+        multiview: None,
     })
 }
 
@@ -135,6 +219,8 @@ pub struct DebugRender {
     blit_pipeline: blade_graphics::RenderPipeline,
     line_size: u32,
     buffer_size: u32,
+    /// Lines of the `DebugDraw` shapes for the current frame.
+    shape_lines: Option<(blade_graphics::Buffer, u32)>,
 }
 
 impl DebugRender {
@@ -176,6 +262,7 @@ impl DebugRender {
             blit_pipeline: create_blit_pipeline(shader_blit, surface_info.format, gpu),
             line_size,
             buffer_size,
+            shape_lines: None,
         };
 
         let init_data = [2u32, 0, 0, 0, capacity];
@@ -279,6 +366,18 @@ impl DebugRender {
         );
         pc.draw_indirect(self.buffer.at(0));
 
+        if let Some((buffer, count)) = self.shape_lines {
+            pc.bind(
+                0,
+                &DebugDrawData {
+                    camera,
+                    debug_lines: buffer.into(),
+                    depth,
+                },
+            );
+            pc.draw(0, 2, 0, count);
+        }
+
         if !debug_lines.is_empty() {
             let (lines_buf, count) = self.add_lines(debug_lines);
             pc.bind(
@@ -293,6 +392,46 @@ impl DebugRender {
         }
     }
 
+    /// Forget the shapes of the previous frame.
+    pub(crate) fn reset_shapes(&mut self) {
+        self.shape_lines = None;
+    }
+
+    /// Upload the shapes into a buffer sized for this frame, and clear the batch.
+    ///
+    /// The buffer is freed once the frame is retired, so the shapes need
+    /// to be uploaded again for every frame they are drawn in.
+    pub(crate) fn upload_shapes(
+        &mut self,
+        draw: &mut DebugDraw,
+        gpu: &blade_graphics::Context,
+        temp: &mut super::FrameResources,
+    ) {
+        self.shape_lines = None;
+        if draw.is_empty() {
+            return;
+        }
+        let buffer = gpu.create_buffer(blade_graphics::BufferDesc {
+            name: "debug shapes",
+            size: (draw.lines.len() * mem::size_of::<DebugLine>()) as u64,
+            memory: blade_graphics::Memory::Shared,
+        });
+        unsafe {
+            ptr::copy_nonoverlapping(
+                draw.lines.as_ptr(),
+                buffer.data() as *mut DebugLine,
+                draw.lines.len(),
+            );
+        }
+        temp.buffers.push(buffer);
+        self.shape_lines = Some((buffer, draw.lines.len() as u32));
+        draw.clear();
+    }
+
+    pub(crate) fn has_shapes(&self) -> bool {
+        self.shape_lines.is_some()
+    }
+
     pub(crate) fn render_blits(
         &self,
         debug_blits: &[DebugBlit],
@@ -0,0 +1,448 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+    ops::Range,
+};
+
+/// Bindless indices of the index, vertex, previous vertex,
+/// and the second texture coordinate buffers of a geometry.
+pub(super) type BufferIndices = (u32, u32, u32, u32);
+
+/// Free ranges of a linear pool, reused in the first-fit order.
+#[derive(Default)]
+pub(super) struct FreeRanges {
+    /// Sorted and never adjacent to each other.
+    ranges: Vec<Range<u64>>,
+}
+
+impl FreeRanges {
+    pub(super) fn clear(&mut self) {
+        self.ranges.clear();
+    }
+
+    /// Take the start of a free range of the given size, if there is one.
+    pub(super) fn alloc(&mut self, size: u64) -> Option<u64> {
+        let index = self
+            .ranges
+            .iter()
+            .position(|range| range.end - range.start >= size)?;
+        let range = &mut self.ranges[index];
+        let start = range.start;
+        range.start += size;
+        if range.is_empty() {
+            self.ranges.remove(index);
+        }
+        Some(start)
+    }
+
+    /// Return a range into the pool, merging it with the adjacent ones.
+    pub(super) fn free(&mut self, range: Range<u64>) {
+        if range.is_empty() {
+            return;
+        }
+        let index = self.ranges.partition_point(|r| r.start < range.start);
+        let merges_next = self
+            .ranges
+            .get(index)
+            .is_some_and(|next| next.start == range.end);
+        let merges_prev = index > 0 && self.ranges[index - 1].end == range.start;
+        match (merges_prev, merges_next) {
+            (true, true) => {
+                let next = self.ranges.remove(index);
+                self.ranges[index - 1].end = next.end;
+            }
+            (true, false) => self.ranges[index - 1].end = range.end,
+            (false, true) => self.ranges[index].start = range.start,
+            (false, false) => self.ranges.insert(index, range),
+        }
+    }
+
+    /// Total size of the free ranges.
+    pub(super) fn size(&self) -> u64 {
+        self.ranges
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum()
+    }
+}
+
+/// Resources of an object in the built scene.
+pub(super) struct ObjectEntry {
+    /// Handle in the retained `Scene`, if the scene was built from one.
+    pub(super) handle: Option<crate::ObjectHandle>,
+    pub(super) model: blade_asset::Handle<crate::Model>,
+    /// First hit entry of the object geometries.
+    pub(super) geometry_offset: u32,
+    pub(super) geometry_count: u32,
+    pub(super) is_skinned: bool,
+}
+
+/// Geometry tables of a rigid model, shared by all its instances.
+pub(super) struct ModelEntry {
+    pub(super) blas_index: u32,
+    pub(super) buffer_indices: Vec<BufferIndices>,
+    /// Number of objects and levels of detail using the model.
+    pub(super) ref_count: usize,
+}
+
+/// Resource usage of the built scene.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SceneStats {
+    /// Objects of the scene.
+    pub objects: u32,
+    /// Hit entries used by the objects and their levels of detail.
+    pub hit_entries: u32,
+    /// Capacity of the hit entry buffer.
+    pub hit_entry_capacity: u32,
+    /// Bottom-level acceleration structures, shared by the instances of a model.
+    pub bottom_levels: u32,
+    /// Size of the skinned vertex pool, in bytes.
+    pub skinned_vertex_size: u64,
+    /// Number of times the scene was built from scratch.
+    pub full_builds: u32,
+}
+
+impl super::RayTracer {
+    /// Resource usage of the built scene.
+    pub fn scene_stats(&self) -> SceneStats {
+        let hit_entry_count = self.hit_entries.len() as u64 - self.free_hit_entries.size();
+        SceneStats {
+            objects: self.object_entries.len() as u32,
+            hit_entries: hit_entry_count as u32,
+            hit_entry_capacity: self.hit_capacity as u32,
+            bottom_levels: self.blases.len() as u32,
+            skinned_vertex_size: self.skinned_vertex_size,
+            full_builds: self.full_builds,
+        }
+    }
+
+    /// Get the shared geometry tables of a rigid model,
+    /// allocating them for the first user of the model.
+    pub(super) fn acquire_model_entry(
+        &mut self,
+        model_handle: blade_asset::Handle<crate::Model>,
+        model: &crate::Model,
+    ) -> (u32, Vec<BufferIndices>) {
+        if let Some(entry) = self.model_entries.get_mut(&model_handle) {
+            entry.ref_count += 1;
+            return (entry.blas_index, entry.buffer_indices.clone());
+        }
+        let blas_index = self.blases.len() as u32;
+        self.blases.push(model.acceleration_structure);
+        let buffer_indices = model
+            .geometries
+            .iter()
+            .map(|geometry| {
+                let vertex_offset =
+                    geometry.vertex_range.start as u64 * mem::size_of::<crate::Vertex>() as u64;
+                let index_buf = match geometry.index_type {
+                    Some(_) => self
+                        .index_buffers
+                        .alloc(model.index_buffer.at(geometry.index_offset)),
+                    None => !0,
+                };
+                let vertex_buf = self
+                    .vertex_buffers
+                    .alloc(model.vertex_buffer.at(vertex_offset));
+                let tex_coords1_buf = if geometry.has_tex_coords1 {
+                    self.tex_coord_buffers.alloc(
+                        model
+                            .tex_coords1_buffer
+                            .at(geometry.vertex_range.start as u64
+                                * mem::size_of::<[f32; 2]>() as u64),
+                    )
+                } else {
+                    !0
+                };
+                // rigid geometry doesn't move within the object
+                (index_buf, vertex_buf, vertex_buf, tex_coords1_buf)
+            })
+            .collect::<Vec<_>>();
+        self.model_entries.insert(
+            model_handle,
+            ModelEntry {
+                blas_index,
+                buffer_indices: buffer_indices.clone(),
+                ref_count: 1,
+            },
+        );
+        (blas_index, buffer_indices)
+    }
+
+    fn release_buffer_indices(&mut self, buffer_indices: &[BufferIndices], is_skinned: bool) {
+        for &(index_buf, vertex_buf, prev_vertex_buf, tex_coords1_buf) in buffer_indices {
+            if index_buf != !0 {
+                self.index_buffers.free(index_buf);
+            }
+            self.vertex_buffers.free(vertex_buf);
+            if is_skinned {
+                self.vertex_buffers.free(prev_vertex_buf);
+            }
+            if tex_coords1_buf != !0 {
+                self.tex_coord_buffers.free(tex_coords1_buf);
+            }
+        }
+    }
+
+    /// Get the resource index of a texture, allocating it for the first user.
+    pub(super) fn acquire_texture(
+        &mut self,
+        handle: blade_asset::Handle<crate::Texture>,
+        texture_indices: &mut HashMap<
+            blade_asset::Handle<crate::Texture>,
+            blade_graphics::ResourceIndex,
+        >,
+        asset_hub: &crate::AssetHub,
+    ) -> blade_graphics::ResourceIndex {
+        let res_id = *texture_indices
+            .entry(handle)
+            .or_insert_with(|| self.textures.alloc(asset_hub.texture_view(handle)));
+        self.texture_resource_lookup
+            .entry(res_id)
+            .or_insert((handle, 0))
+            .1 += 1;
+        res_id
+    }
+
+    fn release_texture(&mut self, res_id: blade_graphics::ResourceIndex) {
+        // the dummy textures aren't tracked
+        let Some(&mut (_, ref mut ref_count)) = self.texture_resource_lookup.get_mut(&res_id)
+        else {
+            return;
+        };
+        *ref_count -= 1;
+        if *ref_count == 0 {
+            self.texture_resource_lookup.remove(&res_id);
+            self.textures.free(res_id);
+        }
+    }
+
+    /// Release the resources of an object that is no longer in the scene.
+    fn release_object(
+        &mut self,
+        entry: ObjectEntry,
+        object_index: usize,
+        temp: &mut crate::FrameResources,
+    ) {
+        let geometries = entry.geometry_offset..entry.geometry_offset + entry.geometry_count;
+        for geometry_index in geometries.clone() {
+            let hit_entry = &self.hit_entries[geometry_index as usize];
+            let textures = [hit_entry.base_color_texture, hit_entry.normal_texture];
+            for res_id in textures {
+                self.release_texture(res_id);
+            }
+        }
+        self.free_hit_entries
+            .free(geometries.start as u64..geometries.end as u64);
+
+        if entry.is_skinned {
+            let position = self
+                .skinned_instances
+                .iter()
+                .position(|instance| instance.object_index == object_index)
+                .unwrap();
+            let instance = self.skinned_instances.swap_remove(position);
+            self.release_buffer_indices(&instance.buffer_indices, true);
+            temp.acceleration_structures.push(instance.blas);
+            self.free_skin_regions
+                .free(instance.vertex_offset..instance.vertex_offset + instance.region_size);
+        } else {
+            let model_entry = self.model_entries.get_mut(&entry.model).unwrap();
+            model_entry.ref_count -= 1;
+            if model_entry.ref_count == 0 {
+                let model_entry = self.model_entries.remove(&entry.model).unwrap();
+                self.release_buffer_indices(&model_entry.buffer_indices, false);
+            }
+        }
+    }
+
+    /// Apply the added and removed objects of a retained scene to the built one,
+    /// reusing the resources of the remaining objects and the ones freed by
+    /// the removed objects.
+    ///
+    /// Only updates the tables, the caller rebuilds the top-level acceleration structure
+    /// and the emissive triangles. Returns `false` if the scene has to be
+    /// built from scratch instead.
+    pub(super) fn update_objects(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        scene: &crate::Scene,
+        asset_hub: &crate::AssetHub,
+        gpu: &blade_graphics::Context,
+        temp: &mut crate::FrameResources,
+    ) -> bool {
+        let objects = scene.objects();
+        // The levels of detail are laid out after all the objects,
+        // so they are only placed by the full builds
+        if self.full_builds == 0
+            || self.are_lod_chains_changed
+            || !self.lod_entries.is_empty()
+            || objects.iter().any(|object| {
+                self.lod_chains.contains_key(&object.model)
+                    && asset_hub.models[object.model].joints.is_empty()
+            })
+        {
+            return false;
+        }
+        let mut old_indices = HashMap::with_capacity(self.object_entries.len());
+        for (index, entry) in self.object_entries.iter().enumerate() {
+            match entry.handle {
+                Some(handle) => old_indices.insert(handle, index),
+                None => return false,
+            };
+        }
+
+        // Release the removed objects first, so that the new ones reuse their resources
+        let mut old_entries = mem::take(&mut self.object_entries)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        let kept = scene
+            .handles()
+            .iter()
+            .filter_map(|handle| old_indices.get(handle).copied())
+            .collect::<HashSet<_>>();
+        for (index, slot) in old_entries.iter_mut().enumerate() {
+            if !kept.contains(&index) {
+                let entry = slot.take().unwrap();
+                self.release_object(entry, index, temp);
+            }
+        }
+
+        let mut old_skins = mem::take(&mut self.skinned_instances);
+
+        let mut texture_indices = self
+            .texture_resource_lookup
+            .iter()
+            .map(|(&res_id, &(handle, _))| (handle, res_id))
+            .collect::<HashMap<_, _>>();
+        let mut is_skin_added = false;
+        for (object_index, (object, handle)) in objects.iter().zip(scene.handles()).enumerate() {
+            if let Some(&old_index) = old_indices.get(handle) {
+                let entry = old_entries[old_index].take().unwrap();
+                if entry.is_skinned {
+                    let position = old_skins
+                        .iter()
+                        .position(|instance| instance.object_index == old_index)
+                        .unwrap();
+                    let mut instance = old_skins.swap_remove(position);
+                    instance.object_index = object_index;
+                    self.skinned_instances.push(instance);
+                }
+                self.object_entries.push(entry);
+                continue;
+            }
+
+            let model = &asset_hub.models[object.model];
+            let is_skinned = !model.joints.is_empty();
+            let buffer_indices = if is_skinned {
+                let vertex_count = skinned_vertex_count(model);
+                let region_size = skinned_region_size(vertex_count);
+                let Some(region_offset) = self.free_skin_regions.alloc(region_size) else {
+                    // the pool is full, the remaining skins are dropped by the full build
+                    self.skinned_instances.append(&mut old_skins);
+                    return false;
+                };
+                is_skin_added = true;
+                self.add_skinned_instance(object_index, model, region_offset, gpu)
+                    .1
+            } else {
+                self.acquire_model_entry(object.model, model).1
+            };
+            let geometry_count = model.geometries.len() as u32;
+            let geometry_offset = match self.free_hit_entries.alloc(geometry_count as u64) {
+                Some(offset) => offset as u32,
+                None => self.hit_entries.len() as u32,
+            };
+            let hit_entries = self.make_hit_entries(
+                object,
+                model,
+                &buffer_indices,
+                0,
+                &mut texture_indices,
+                asset_hub,
+            );
+            for (index, hit_entry) in hit_entries.into_iter().enumerate() {
+                match self.hit_entries.get_mut(geometry_offset as usize + index) {
+                    Some(slot) => *slot = hit_entry,
+                    None => self.hit_entries.push(hit_entry),
+                }
+            }
+            self.object_entries.push(ObjectEntry {
+                handle: Some(*handle),
+                model: object.model,
+                geometry_offset,
+                geometry_count,
+                is_skinned,
+            });
+        }
+
+        // Keep the bottom-level structures packed, since all of them
+        // are passed to the TLAS build
+        self.blases.clear();
+        for (&model_handle, model_entry) in self.model_entries.iter_mut() {
+            model_entry.blas_index = self.blases.len() as u32;
+            self.blases
+                .push(asset_hub.models[model_handle].acceleration_structure);
+        }
+        let mut skin_blas_indices = HashMap::new();
+        for instance in self.skinned_instances.iter() {
+            skin_blas_indices.insert(instance.object_index, self.blases.len() as u32);
+            self.blases.push(instance.blas);
+        }
+        self.instances.clear();
+        self.instance_radii.clear();
+        for (object_index, (object, entry)) in
+            objects.iter().zip(self.object_entries.iter()).enumerate()
+        {
+            let acceleration_structure_index = match skin_blas_indices.get(&object_index) {
+                Some(&blas_index) => blas_index,
+                None => self.model_entries[&entry.model].blas_index,
+            };
+            self.instances
+                .push(blade_graphics::AccelerationStructureInstance {
+                    acceleration_structure_index,
+                    transform: object.transform,
+                    mask: super::instance_mask(object.layers, self.visible_layers),
+                    custom_index: entry.geometry_offset,
+                });
+            self.instance_radii
+                .push(asset_hub.models[object.model].radius);
+        }
+
+        if self.hit_entries.len() > self.hit_capacity {
+            self.hit_capacity = self.hit_entries.len().max(self.hit_capacity * 2);
+            self.create_hit_buffer(gpu, temp);
+        }
+        self.upload_hit_entries(command_encoder, gpu, temp);
+        if is_skin_added {
+            self.skin_instances(command_encoder, objects, asset_hub, gpu, temp, false);
+        }
+        self.update_scene_radius();
+        log::info!(
+            "Updated the scene to {} instances, using {} of {} hit entries",
+            self.instances.len(),
+            self.hit_entries.len() as u64 - self.free_hit_entries.size(),
+            self.hit_capacity,
+        );
+        true
+    }
+}
+
+/// Number of vertices to skin for a model.
+pub(super) fn skinned_vertex_count(model: &crate::Model) -> u32 {
+    model
+        .geometries
+        .iter()
+        .map(|geometry| geometry.vertex_range.end)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Size of the vertex pool region of a skinned instance, in bytes.
+pub(super) fn skinned_region_size(vertex_count: u32) -> u64 {
+    crate::util::align_to(
+        vertex_count as u64 * mem::size_of::<crate::Vertex>() as u64,
+        blade_graphics::limits::STORAGE_BUFFER_ALIGNMENT,
+    )
+}
@@ -0,0 +1,237 @@
+use std::{mem, ptr};
+
+// Has to match the shader!
+const HISTOGRAM_BINS: u64 = 128;
+/// Luminance range covered by the histogram, in stops.
+const MIN_LOG_LUMINANCE: f32 = -12.0;
+const LOG_LUMINANCE_RANGE: f32 = 24.0;
+/// Difference with the target, in stops, that starts the adaptation.
+/// Keeps the noise of the denoised image from wobbling the exposure.
+const EXPOSURE_TOLERANCE: f32 = 0.1;
+
+/// Automatic exposure, metered from the luminance histogram of the frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoExposureConfig {
+    /// Fraction of the darkest pixels ignored by the metering.
+    pub low_percentile: f32,
+    /// Fraction of the pixels, starting from the darkest, above which
+    /// the brightest ones are ignored by the metering.
+    pub high_percentile: f32,
+    /// Adaptation speed, in stops per second.
+    pub speed: f32,
+    /// Range of the metered exposure, in stops.
+    pub min_ev: f32,
+    pub max_ev: f32,
+}
+
+impl Default for AutoExposureConfig {
+    fn default() -> Self {
+        Self {
+            low_percentile: 0.5,
+            high_percentile: 0.95,
+            speed: 3.0,
+            min_ev: -10.0,
+            max_ev: 10.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct ExposureParams {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    low_percentile: f32,
+    high_percentile: f32,
+    max_step: f32,
+    tolerance: f32,
+    min_ev: f32,
+    max_ev: f32,
+    use_accumulation: u32,
+    pad: [u32; 3],
+}
+
+// Has to match the shader!
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct ExposureState {
+    ev: f32,
+    target_ev: f32,
+    is_valid: u32,
+    is_adapting: u32,
+}
+
+#[derive(blade_macros::ShaderData)]
+struct HistogramData {
+    params: ExposureParams,
+    t_albedo: blade_graphics::TextureView,
+    light_diffuse: blade_graphics::TextureView,
+    t_emission: blade_graphics::TextureView,
+    t_medium: blade_graphics::TextureView,
+    t_accumulation: blade_graphics::TextureView,
+    histogram: blade_graphics::BufferPiece,
+}
+
+#[derive(blade_macros::ShaderData)]
+struct AdaptData {
+    params: ExposureParams,
+    histogram: blade_graphics::BufferPiece,
+    state: blade_graphics::BufferPiece,
+}
+
+fn create_histogram_pipeline(
+    shader: &blade_graphics::Shader,
+    gpu: &blade_graphics::Context,
+) -> blade_graphics::ComputePipeline {
+    shader.check_struct_size::<ExposureParams>();
+    shader.check_struct_size::<ExposureState>();
+    let layout = <HistogramData as blade_graphics::ShaderData>::layout();
+    gpu.create_compute_pipeline(blade_graphics::ComputePipelineDesc {
+        name: "exposure-histogram",
+        data_layouts: &[&layout],
+        compute: shader.at("histogram_main"),
+    })
+}
+
+fn create_adapt_pipeline(
+    shader: &blade_graphics::Shader,
+    gpu: &blade_graphics::Context,
+) -> blade_graphics::ComputePipeline {
+    let layout = <AdaptData as blade_graphics::ShaderData>::layout();
+    gpu.create_compute_pipeline(blade_graphics::ComputePipelineDesc {
+        name: "exposure-adapt",
+        data_layouts: &[&layout],
+        compute: shader.at("adapt"),
+    })
+}
+
+pub(super) struct Exposure {
+    histogram_pipeline: blade_graphics::ComputePipeline,
+    adapt_pipeline: blade_graphics::ComputePipeline,
+    histogram_buffer: blade_graphics::Buffer,
+    state_buffer: blade_graphics::Buffer,
+}
+
+impl Exposure {
+    pub(super) fn init(
+        encoder: &mut blade_graphics::CommandEncoder,
+        gpu: &blade_graphics::Context,
+        shader: &blade_graphics::Shader,
+    ) -> Self {
+        let histogram_size = HISTOGRAM_BINS * mem::size_of::<u32>() as u64;
+        let this = Self {
+            histogram_pipeline: create_histogram_pipeline(shader, gpu),
+            adapt_pipeline: create_adapt_pipeline(shader, gpu),
+            histogram_buffer: gpu.create_buffer(blade_graphics::BufferDesc {
+                name: "exposure histogram",
+                size: histogram_size,
+                memory: blade_graphics::Memory::Device,
+            }),
+            state_buffer: gpu.create_buffer(blade_graphics::BufferDesc {
+                name: "exposure state",
+                size: mem::size_of::<ExposureState>() as u64,
+                memory: blade_graphics::Memory::Shared,
+            }),
+        };
+        unsafe {
+            ptr::write_bytes(this.state_buffer.data(), 0, mem::size_of::<ExposureState>());
+        }
+        // the adaptation clears the histogram after that
+        let mut transfer = encoder.transfer("init exposure");
+        transfer.fill_buffer(this.histogram_buffer.into(), histogram_size, 0);
+        this
+    }
+
+    pub(super) fn recreate_pipelines(
+        &mut self,
+        shader: &blade_graphics::Shader,
+        gpu: &blade_graphics::Context,
+    ) {
+        self.histogram_pipeline = create_histogram_pipeline(shader, gpu);
+        self.adapt_pipeline = create_adapt_pipeline(shader, gpu);
+    }
+
+    pub(super) fn state_buffer(&self) -> blade_graphics::Buffer {
+        self.state_buffer
+    }
+
+    pub(super) fn destroy(&mut self, gpu: &blade_graphics::Context) {
+        gpu.destroy_buffer(self.histogram_buffer);
+        gpu.destroy_buffer(self.state_buffer);
+        gpu.destroy_compute_pipeline(&mut self.histogram_pipeline);
+        gpu.destroy_compute_pipeline(&mut self.adapt_pipeline);
+    }
+}
+
+impl super::RayTracer {
+    /// Meter the exposure of the image shown by `post_proc`,
+    /// and move the automatic exposure towards it.
+    ///
+    /// Has to be called after `denoise`, with the time since the last metering.
+    /// The first metering sets the exposure without the adaptation.
+    /// Only used by `post_proc` with `PostProcConfig::auto_exposure`.
+    #[profiling::function]
+    pub fn meter_exposure(
+        &self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        config: AutoExposureConfig,
+        delta_time: f32,
+    ) {
+        let cur = self.frame_index % 2;
+        let params = ExposureParams {
+            min_log_luminance: MIN_LOG_LUMINANCE,
+            log_luminance_range: LOG_LUMINANCE_RANGE,
+            low_percentile: config.low_percentile.clamp(0.0, 1.0),
+            high_percentile: config.high_percentile.clamp(config.low_percentile, 1.0),
+            max_step: config.speed.max(0.0) * delta_time.max(0.0),
+            tolerance: EXPOSURE_TOLERANCE,
+            min_ev: config.min_ev,
+            max_ev: config.max_ev.max(config.min_ev),
+            use_accumulation: (self.accumulated_frames != 0) as u32,
+            pad: [0; 3],
+        };
+
+        {
+            let mut pass = command_encoder.compute("exposure-histogram");
+            let mut pc = pass.with(&self.exposure.histogram_pipeline);
+            let groups = self
+                .exposure
+                .histogram_pipeline
+                .get_dispatch_for(self.render_size);
+            pc.bind(
+                0,
+                &HistogramData {
+                    params,
+                    t_albedo: self.targets.albedo.views[0],
+                    light_diffuse: self.targets.light_diffuse.views[self.post_proc_input_index],
+                    t_emission: self.targets.emission.views[0],
+                    t_medium: self.targets.medium.views[cur],
+                    t_accumulation: self.targets.accumulation.views[0],
+                    histogram: self.exposure.histogram_buffer.into(),
+                },
+            );
+            pc.dispatch(groups);
+        }
+        {
+            // the histogram has to be complete before the adaptation
+            let mut pass = command_encoder.compute("exposure-adapt");
+            let mut pc = pass.with(&self.exposure.adapt_pipeline);
+            pc.bind(
+                0,
+                &AdaptData {
+                    params,
+                    histogram: self.exposure.histogram_buffer.into(),
+                    state: self.exposure.state_buffer.into(),
+                },
+            );
+            pc.dispatch([1; 3]);
+        }
+    }
+
+    /// Exposure in stops produced by the last completed `meter_exposure`,
+    /// without the compensation of `PostProcConfig::exposure_ev`.
+    pub fn auto_exposure_ev(&self) -> f32 {
+        let state = unsafe { &*(self.exposure.state_buffer.data() as *const ExposureState) };
+        state.ev
+    }
+}
@@ -0,0 +1,109 @@
+/// Margin around the switch distances, relative to them, that the camera
+/// has to cross before the level changes, to avoid popping at the boundary.
+const LOD_HYSTERESIS: f32 = 0.1;
+
+/// Coarser version of a model, replacing the finer ones beyond a distance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LodLevel {
+    pub model: blade_asset::Handle<crate::Model>,
+    /// Distance from the camera to the object origin, starting from which
+    /// this level is used.
+    pub distance: f32,
+}
+
+/// Hit entries of a coarser level of an object.
+///
+/// They are stored after the hit entries of all the objects,
+/// in the order of the objects and their levels.
+pub(super) struct LodEntry {
+    pub(super) object_index: usize,
+    pub(super) model: blade_asset::Handle<crate::Model>,
+    pub(super) geometry_offset: u32,
+}
+
+/// Levels of detail of a TLAS instance.
+pub(super) struct InstanceLods {
+    pub(super) object_index: usize,
+    /// BLAS index and the first hit entry of every level,
+    /// starting with the object model itself.
+    pub(super) levels: Vec<(u32, u32)>,
+    /// Switch distances of the levels past the first one.
+    pub(super) distances: Vec<f32>,
+    pub(super) current: usize,
+}
+
+impl InstanceLods {
+    /// Select the level for the distance to the camera.
+    fn select(&self, distance: f32) -> usize {
+        let mut level = self.current;
+        while level < self.distances.len()
+            && distance > self.distances[level] * (1.0 + LOD_HYSTERESIS)
+        {
+            level += 1;
+        }
+        while level > 0 && distance < self.distances[level - 1] * (1.0 - LOD_HYSTERESIS) {
+            level -= 1;
+        }
+        level
+    }
+}
+
+/// Object and model behind every hit entry, in order.
+pub(super) fn hit_entry_models<'a>(
+    objects: &'a [crate::Object],
+    lod_entries: &'a [LodEntry],
+) -> impl Iterator<Item = (&'a crate::Object, blade_asset::Handle<crate::Model>)> {
+    let lods = lod_entries
+        .iter()
+        .map(|entry| (&objects[entry.object_index], entry.model));
+    objects
+        .iter()
+        .map(|object| (object, object.model))
+        .chain(lods)
+}
+
+/// First hit entry of every model in the `hit_entry_models` order.
+pub(super) fn hit_entry_offsets<'a>(
+    object_entries: &'a [super::ObjectEntry],
+    lod_entries: &'a [LodEntry],
+) -> impl Iterator<Item = u32> + 'a {
+    object_entries
+        .iter()
+        .map(|entry| entry.geometry_offset)
+        .chain(lod_entries.iter().map(|entry| entry.geometry_offset))
+}
+
+impl super::RayTracer {
+    /// Register the coarser levels of detail of a model, ordered by the distance.
+    /// An empty list removes them.
+    ///
+    /// Applied by the next `build_scene` or `update_scene`. Skinned models
+    /// always use the finest level.
+    pub fn set_lod_chain(&mut self, model: blade_asset::Handle<crate::Model>, levels: &[LodLevel]) {
+        assert!(
+            levels.is_sorted_by(|a, b| a.distance < b.distance),
+            "LOD distances have to be increasing"
+        );
+        if levels.is_empty() {
+            self.lod_chains.remove(&model);
+        } else {
+            self.lod_chains.insert(model, levels.to_vec());
+        }
+        self.are_lod_chains_changed = true;
+    }
+
+    /// Switch the instances to the levels matching the camera position.
+    pub(super) fn select_lods(&mut self) {
+        for lods in self.instance_lods.iter_mut() {
+            let instance = &mut self.instances[lods.object_index];
+            let t = &instance.transform;
+            let origin = glam::Vec3::new(t.x.w, t.y.w, t.z.w);
+            let level = lods.select(origin.distance(self.camera_position));
+            if level != lods.current {
+                lods.current = level;
+                (instance.acceleration_structure_index, instance.custom_index) = lods.levels[level];
+                self.is_lod_changed = true;
+            }
+        }
+    }
+}
@@ -1,15 +1,46 @@
+mod bloom;
+mod culling;
 mod debug;
-
-use crate::{CameraParams, DummyResources, EnvironmentMap};
+mod entries;
+mod exposure;
+mod lod;
+mod picker;
+mod probes;
+mod timing;
+
+use crate::{CameraParams, DummyResources, EnvironmentMap, FrameResources};
+use bloom::Bloom;
+use culling::Culling;
 use debug::{DebugEntry, DebugVariance};
+use entries::{FreeRanges, ModelEntry, ObjectEntry};
+use exposure::Exposure;
+use lod::{InstanceLods, LodEntry};
 
+pub use bloom::BloomConfig;
+pub use culling::{CullingConfig, CullingStats};
 pub(crate) use debug::DebugRender;
-pub use debug::{DebugBlit, DebugLine, DebugPoint};
+pub use debug::{DebugBlit, DebugDraw, DebugLine, DebugPoint};
+pub use entries::SceneStats;
+pub use exposure::AutoExposureConfig;
+pub use lod::LodLevel;
+pub use picker::{PickResult, PickToken, Picker};
+pub use probes::IrradianceBake;
+use probes::{ActiveVolume, ProbeParams};
+use timing::PassTimings;
 
-use std::{collections::HashMap, mem, num::NonZeroU32, path::Path, ptr};
+use std::{collections::HashMap, f32::consts, mem, num::NonZeroU32, path::Path, ptr, sync::Arc};
 
 const MAX_RESOURCES: u32 = 8192;
 const RADIANCE_FORMAT: blade_graphics::TextureFormat = blade_graphics::TextureFormat::Rgba16Float;
+/// Maximum number of the indirect bounces supported by the ray tracer.
+pub const MAX_BOUNCES: u32 = 8;
+/// Maximum number of samples per pixel taken by the adaptive sampling.
+pub const MAX_ADAPTIVE_SAMPLES: u32 = 16;
+/// Maximum number of the ray marching steps through the medium.
+pub const MAX_MEDIUM_STEPS: u32 = 64;
+/// Size of the square tiles sharing the adaptive sample count.
+/// Has to match the workgroup size of the tile priority shader.
+const ADAPTIVE_TILE_SIZE: u32 = 8;
 
 fn mat4_transform(t: &blade_graphics::Transform) -> glam::Mat4 {
     glam::Mat4 {
@@ -28,6 +59,17 @@ fn mat3_transform(t_orig: &blade_graphics::Transform) -> glam::Mat3 {
         z_axis: t.z.into(),
     }
 }
+fn make_normal_rotation(
+    object_transform: &blade_graphics::Transform,
+    geometry_transform: &blade_graphics::Transform,
+) -> [i8; 4] {
+    let m3_object = mat3_transform(object_transform);
+    let m3_geo = mat3_transform(geometry_transform);
+    let m3_normal = (m3_object * m3_geo).inverse().transpose();
+    let quat = glam::Quat::from_mat3(&m3_normal);
+    let qv = glam::Vec4::from(quat) * 127.0;
+    [qv.x as i8, qv.y as i8, qv.z as i8, qv.w as i8]
+}
 
 #[derive(Clone, Copy, Debug)]
 pub struct RenderConfig {
@@ -55,10 +97,23 @@ pub enum DebugMode {
     NormalScale = 5,
     GeometryNormal = 6,
     ShadingNormal = 7,
+    /// Screen-space motion of the surfaces since the previous frame.
     Motion = 8,
     HitConsistency = 9,
     SampleReuse = 10,
+    /// Final albedo stored in the G-buffer.
+    Albedo = 11,
+    /// Hit distance, normalized by the camera depth.
+    LinearDepth = 12,
+    /// Number of frames accumulated by the temporal filter.
+    AccumulationAge = 13,
+    /// Heatmap of the rays traced per pixel.
+    RayCount = 14,
     Variance = 15,
+    /// Heatmap of the samples per pixel chosen by the adaptive sampling.
+    AdaptiveSamples = 16,
+    /// Level of detail of the objects, from blue for the finest to red.
+    Lod = 17,
 }
 
 bitflags::bitflags! {
@@ -86,10 +141,61 @@ pub struct DebugConfig {
     pub mouse_pos: Option<[i32; 2]>,
 }
 
+/// Participating medium filling the scene, such as fog.
+///
+/// The light is scattered once along the primary rays, which are
+/// ray marched from the camera up to the visible surface.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Medium {
+    /// Fraction of the light absorbed per unit of distance.
+    pub absorption: f32,
+    /// Fraction of the light scattered per unit of distance.
+    pub scattering: f32,
+    /// Color of the scattered light.
+    pub color: [f32; 3],
+    /// Asymmetry `g` of the Henyey-Greenstein phase function,
+    /// from -1 for the backward to 1 for the forward scattering.
+    pub anisotropy: f32,
+    /// Height below which the medium has the full density.
+    pub base_height: f32,
+    /// Exponential falloff of the density above the base height.
+    /// Zero makes the medium homogeneous.
+    pub height_falloff: f32,
+    /// Number of the ray marching steps, with a light sample each.
+    /// Zero disables the medium. Can be up to `MAX_MEDIUM_STEPS`.
+    pub step_count: u32,
+}
+
+impl Default for Medium {
+    fn default() -> Self {
+        Self {
+            absorption: 0.0,
+            scattering: 0.0,
+            color: [1.0; 3],
+            anisotropy: 0.0,
+            base_height: 0.0,
+            height_falloff: 0.0,
+            step_count: 8,
+        }
+    }
+}
+
+impl Medium {
+    fn is_enabled(&self) -> bool {
+        self.step_count != 0 && self.absorption + self.scattering > 0.0
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
 pub struct RayConfig {
     pub num_environment_samples: u32,
     pub environment_importance_sampling: bool,
+    /// Number of candidates to draw from the analytic lights.
+    /// The lights are chosen proportionally to their estimated power.
+    pub num_light_samples: u32,
+    /// Number of candidates to draw from the emissive triangles.
+    /// The triangles are chosen proportionally to their power.
+    pub num_emissive_samples: u32,
     pub tap_count: u32,
     pub tap_radius: u32,
     pub tap_confidence_near: u32,
@@ -101,31 +207,210 @@ pub struct RayConfig {
     /// Defensive MIS factor for the canonical sample.
     /// Can be between 0 and 1.
     pub defensive_mis: f32,
+    /// Number of diffuse bounces traced for the indirect lighting.
+    /// Zero means direct lighting only. Can be up to `MAX_BOUNCES`.
+    pub max_bounces: u32,
+    /// Maximum luminance of the indirect lighting in a pixel.
+    /// Zero disables the clamping.
+    pub firefly_clamp: f32,
+    /// First bounce where paths are terminated by the russian roulette.
+    pub russian_roulette_start: u32,
+    /// Maximum number of samples per pixel taken in the noisy tiles.
+    /// One disables the adaptive sampling. Can be up to `MAX_ADAPTIVE_SAMPLES`.
+    /// Requires the temporal filter, which estimates the variance.
+    pub max_samples: u32,
+    /// Relative variance of the illumination in a tile, per extra sample.
+    /// Tiles below the threshold are sampled once.
+    pub variance_threshold: f32,
+    /// Participating medium along the primary rays.
+    pub medium: Medium,
+    /// Rotation and intensity of the environment map.
+    pub environment: crate::EnvironmentConfig,
+}
+
+impl RayConfig {
+    /// Return a copy with the values clamped into the supported ranges.
+    fn clamped(&self) -> Self {
+        let mut config = *self;
+        if config.max_bounces > MAX_BOUNCES {
+            log::debug!(
+                "Clamping max bounces {} to {}",
+                config.max_bounces,
+                MAX_BOUNCES
+            );
+            config.max_bounces = MAX_BOUNCES;
+        }
+        if config.firefly_clamp.is_nan() || config.firefly_clamp < 0.0 {
+            log::debug!("Disabling invalid firefly clamp {}", config.firefly_clamp);
+            config.firefly_clamp = 0.0;
+        }
+        if config.russian_roulette_start > config.max_bounces {
+            log::debug!(
+                "Clamping russian roulette start {} to {}",
+                config.russian_roulette_start,
+                config.max_bounces
+            );
+            config.russian_roulette_start = config.max_bounces;
+        }
+        if config.max_samples > MAX_ADAPTIVE_SAMPLES {
+            log::debug!(
+                "Clamping max samples {} to {}",
+                config.max_samples,
+                MAX_ADAPTIVE_SAMPLES
+            );
+            config.max_samples = MAX_ADAPTIVE_SAMPLES;
+        }
+        if config.max_samples == 0 {
+            config.max_samples = 1;
+        }
+        if config.variance_threshold.is_nan() || config.variance_threshold <= 0.0 {
+            log::debug!(
+                "Disabling adaptive sampling with invalid variance threshold {}",
+                config.variance_threshold
+            );
+            config.max_samples = 1;
+        }
+        if !(0.0..=1.0).contains(&config.defensive_mis) {
+            log::debug!("Clamping defensive MIS {}", config.defensive_mis);
+            config.defensive_mis = config.defensive_mis.clamp(0.0, 1.0);
+        }
+        let medium = &mut config.medium;
+        if medium.step_count > MAX_MEDIUM_STEPS {
+            log::debug!(
+                "Clamping medium steps {} to {}",
+                medium.step_count,
+                MAX_MEDIUM_STEPS
+            );
+            medium.step_count = MAX_MEDIUM_STEPS;
+        }
+        if medium.absorption.is_nan() || medium.absorption < 0.0 {
+            log::debug!("Disabling invalid medium absorption {}", medium.absorption);
+            medium.absorption = 0.0;
+        }
+        if medium.scattering.is_nan() || medium.scattering < 0.0 {
+            log::debug!("Disabling invalid medium scattering {}", medium.scattering);
+            medium.scattering = 0.0;
+        }
+        // The phase function degenerates into a delta at the ends
+        medium.anisotropy = medium.anisotropy.clamp(-0.99, 0.99);
+        medium.height_falloff = medium.height_falloff.max(0.0);
+        let environment = &mut config.environment;
+        if environment.intensity.is_nan() || environment.intensity < 0.0 {
+            log::debug!(
+                "Disabling invalid environment intensity {}",
+                environment.intensity
+            );
+            environment.intensity = 0.0;
+        }
+        if !environment.yaw.is_finite() {
+            environment.yaw = 0.0;
+        }
+        config
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
 pub struct DenoiserConfig {
-    pub num_passes: u32,
+    /// Run the denoiser. If disabled, the raw ray traced result is shown.
+    pub enabled: bool,
+    /// Weight of the current frame in the temporal accumulation.
+    /// One disables the temporal filter.
     pub temporal_weight: f32,
+    /// Number of the A-trous spatial filter passes. Can be zero.
+    pub spatial_passes: u32,
+    /// Clamp the accumulated history to this many standard deviations
+    /// around the current frame, reducing the ghosting. Zero disables the clamp.
+    pub history_clamp_sigma: f32,
+}
+impl Default for DenoiserConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            temporal_weight: 0.1,
+            spatial_passes: 3,
+            history_clamp_sigma: 0.0,
+        }
+    }
+}
+
+/// Operator mapping exposed HDR radiance into the displayable range.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, PartialOrd, blade_macros::AsPrimitive, strum::EnumIter,
+)]
+#[repr(u32)]
+pub enum ToneMap {
+    /// Plain clamp after dividing by the white point.
+    Linear = 0,
+    /// Extended Reinhard, mapping the white point to 1.0.
+    #[default]
+    Reinhard = 1,
+    /// Narkowicz fit of the ACES filmic curve.
+    Aces = 2,
+    /// Khronos PBR Neutral, keeping base colors intact under neutral lighting.
+    KhronosPbrNeutral = 3,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct PostProcConfig {
-    //TODO: compute automatically
-    pub average_luminocity: f32,
-    pub exposure_key_value: f32,
-    pub white_level: f32,
+    pub tone_map: ToneMap,
+    /// Exposure in stops, the radiance is scaled by `2^exposure_ev`.
+    pub exposure_ev: f32,
+    /// Exposed radiance that is mapped to white.
+    /// Ignored by `ToneMap::KhronosPbrNeutral`.
+    pub white_point: f32,
+    /// Apply the sRGB transfer function in the shader.
+    /// Only needed if the target surface is not sRGB already.
+    pub encode_srgb: bool,
+    /// Apply the exposure metered by `RayTracer::meter_exposure`,
+    /// with `exposure_ev` becoming the compensation on top of it.
+    pub auto_exposure: bool,
+    /// Composite the bloom produced by `RayTracer::render_bloom`.
+    pub bloom: BloomConfig,
+    /// Strength of the contrast-adaptive sharpening, from 0 to 1,
+    /// applied when upscaling from a lower render scale.
+    /// Zero leaves the bilinear upscaling.
+    pub upscale_sharpness: f32,
 }
 impl Default for PostProcConfig {
     fn default() -> Self {
         Self {
-            average_luminocity: 1.0,
-            exposure_key_value: 1.0,
-            white_level: 1.0,
+            tone_map: ToneMap::default(),
+            exposure_ev: 0.0,
+            white_point: 1.0,
+            encode_srgb: false,
+            auto_exposure: false,
+            bloom: BloomConfig::default(),
+            upscale_sharpness: 0.5,
         }
     }
 }
 
+/// Pending read-back of the accumulated HDR image.
+pub struct HdrReadback {
+    buffer: blade_graphics::Buffer,
+    size: blade_graphics::Extent,
+}
+
+impl HdrReadback {
+    pub fn size(&self) -> blade_graphics::Extent {
+        self.size
+    }
+
+    /// Read the RGBA pixels, row by row.
+    ///
+    /// Has to be called after the sync point of the submission
+    /// in which the read-back was recorded is reached.
+    pub fn into_pixels(self, gpu: &blade_graphics::Context) -> Vec<f32> {
+        let count = self.size.width as usize * self.size.height as usize * 4;
+        let mut pixels = vec![0f32; count];
+        unsafe {
+            ptr::copy_nonoverlapping(self.buffer.data() as *const f32, pixels.as_mut_ptr(), count);
+        }
+        gpu.destroy_buffer(self.buffer);
+        pixels
+    }
+}
+
 pub struct SelectionInfo {
     pub std_deviation: mint::Vector3<f32>,
     pub std_deviation_history: u32,
@@ -172,7 +457,9 @@ impl<const N: usize> RenderTarget<N> {
             dimension: blade_graphics::TextureDimension::D2,
             array_layer_count: N as u32,
             mip_level_count: 1,
-            usage: blade_graphics::TextureUsage::RESOURCE | blade_graphics::TextureUsage::STORAGE,
+            usage: blade_graphics::TextureUsage::RESOURCE
+                | blade_graphics::TextureUsage::STORAGE
+                | blade_graphics::TextureUsage::COPY,
             sample_count: 1,
             external: None,
         });
@@ -213,8 +500,21 @@ struct RestirTargets {
     basis: RenderTarget<2>,
     flat_normal: RenderTarget<2>,
     albedo: RenderTarget<1>,
+    /// Index of the hit entry, for reading the material parameters.
+    hit_entry: RenderTarget<2>,
+    /// Motion to the previous frame pixel, and the previous depth.
     motion: RenderTarget<1>,
+    emission: RenderTarget<1>,
     light_diffuse: RenderTarget<3>,
+    /// Radiance scattered by the medium towards the camera,
+    /// and the transmittance to the visible surface.
+    medium: RenderTarget<2>,
+    /// Number of frames accumulated by the temporal filter.
+    history: RenderTarget<2>,
+    /// Average of the final radiance over the accumulated frames.
+    accumulation: RenderTarget<1>,
+    /// Number of samples per pixel in each tile, for the next frame.
+    tile_samples: RenderTarget<1>,
     camera_params: [CameraParams; 2],
 }
 
@@ -272,14 +572,48 @@ impl RestirTargets {
                 encoder,
                 gpu,
             ),
+            hit_entry: RenderTarget::new(
+                "hit-entry",
+                blade_graphics::TextureFormat::R32Uint,
+                size,
+                encoder,
+                gpu,
+            ),
             motion: RenderTarget::new(
                 "motion",
-                blade_graphics::TextureFormat::Rg8Snorm,
+                blade_graphics::TextureFormat::Rgba16Float,
                 size,
                 encoder,
                 gpu,
             ),
+            emission: RenderTarget::new("emission", RADIANCE_FORMAT, size, encoder, gpu),
             light_diffuse: RenderTarget::new("light-diffuse", RADIANCE_FORMAT, size, encoder, gpu),
+            medium: RenderTarget::new("medium", RADIANCE_FORMAT, size, encoder, gpu),
+            history: RenderTarget::new(
+                "history",
+                blade_graphics::TextureFormat::R32Float,
+                size,
+                encoder,
+                gpu,
+            ),
+            accumulation: RenderTarget::new(
+                "accumulation",
+                blade_graphics::TextureFormat::Rgba32Float,
+                size,
+                encoder,
+                gpu,
+            ),
+            tile_samples: RenderTarget::new(
+                "tile-samples",
+                blade_graphics::TextureFormat::R32Uint,
+                blade_graphics::Extent {
+                    width: size.width.div_ceil(ADAPTIVE_TILE_SIZE),
+                    height: size.height.div_ceil(ADAPTIVE_TILE_SIZE),
+                    depth: 1,
+                },
+                encoder,
+                gpu,
+            ),
             camera_params: [CameraParams::default(); 2],
         }
     }
@@ -293,14 +627,21 @@ impl RestirTargets {
         self.basis.destroy(gpu);
         self.flat_normal.destroy(gpu);
         self.albedo.destroy(gpu);
+        self.hit_entry.destroy(gpu);
         self.motion.destroy(gpu);
+        self.emission.destroy(gpu);
         self.light_diffuse.destroy(gpu);
+        self.medium.destroy(gpu);
+        self.history.destroy(gpu);
+        self.accumulation.destroy(gpu);
+        self.tile_samples.destroy(gpu);
     }
 }
 
 struct Blur {
     temporal_accum_pipeline: blade_graphics::ComputePipeline,
     a_trous_pipeline: blade_graphics::ComputePipeline,
+    tile_priority_pipeline: blade_graphics::ComputePipeline,
 }
 
 /// Blade RayTracer is a comprehensive rendering solution for
@@ -316,8 +657,30 @@ pub struct RayTracer {
     shaders: Shaders,
     targets: RestirTargets,
     post_proc_input_index: usize,
+    /// True if the temporal filter has run for the current frame.
+    is_temporally_accumulated: bool,
+    /// True if the temporal filter has run for the previous frame,
+    /// so its history can be reused.
+    has_prev_history: bool,
+    /// True if the temporal filter has to start over.
+    is_history_reset: bool,
+    /// Last ray configuration requested by the user.
+    requested_ray_config: Option<RayConfig>,
+    /// Ray configuration in use, clamped into the supported ranges.
+    active_ray_config: Option<RayConfig>,
+    /// True if the frames are progressively accumulated.
+    is_accumulating: bool,
+    accumulated_frames: u32,
+    accumulate_pipeline: blade_graphics::ComputePipeline,
+    /// True if the tile sample counts were estimated by a previous frame.
+    has_tile_samples: bool,
+    /// Frame in which the medium was last integrated, for the temporal reuse.
+    medium_frame_index: Option<usize>,
     fill_pipeline: blade_graphics::ComputePipeline,
     main_pipeline: blade_graphics::ComputePipeline,
+    probe_bake_pipeline: blade_graphics::ComputePipeline,
+    /// Irradiance volume providing the indirect diffuse lighting.
+    irradiance_volume: Option<ActiveVolume>,
     post_proc_pipeline: blade_graphics::RenderPipeline,
     blur: Blur,
     acceleration_structure: blade_graphics::AccelerationStructure,
@@ -327,19 +690,103 @@ pub struct RayTracer {
     hit_buffer: blade_graphics::Buffer,
     vertex_buffers: blade_graphics::BufferArray<MAX_RESOURCES>,
     index_buffers: blade_graphics::BufferArray<MAX_RESOURCES>,
+    /// Second sets of the texture coordinates, per geometry.
+    tex_coord_buffers: blade_graphics::BufferArray<MAX_RESOURCES>,
     textures: blade_graphics::TextureArray<MAX_RESOURCES>,
     samplers: Samplers,
     reservoir_size: u32,
     debug: DebugRender,
+    debug_draw: DebugDraw,
+    exposure: Exposure,
+    bloom: Bloom,
+    pass_timings: PassTimings,
     surface_size: blade_graphics::Extent,
     surface_info: blade_graphics::SurfaceInfo,
+    /// Fraction of the surface size that is rendered, before the upscaling.
+    render_scale: f32,
+    render_size: blade_graphics::Extent,
     frame_index: usize,
     frame_scene_built: usize,
     is_frozen: bool,
+    /// Layers of the objects visible to the rays.
+    visible_layers: u32,
+    /// The visible layers were changed since the TLAS was built.
+    is_visibility_changed: bool,
+    /// Culling volume of the last prepared camera.
+    culling: Culling,
+    /// The culling volume excludes different instances than the TLAS.
+    is_culling_changed: bool,
+    /// Mask of every instance in the TLAS, or 0 if it's culled.
+    instance_masks: Vec<u32>,
+    /// Index of the object behind every TLAS instance.
+    tlas_objects: Arc<[u32]>,
+    culling_stats: CullingStats,
+    /// Coarser levels of detail of the models.
+    lod_chains: HashMap<blade_asset::Handle<crate::Model>, Vec<LodLevel>>,
+    /// The LOD chains were changed since the scene was built.
+    are_lod_chains_changed: bool,
+    lod_entries: Vec<LodEntry>,
+    instance_lods: Vec<InstanceLods>,
+    /// Some of the instances switched the level of detail since the TLAS was built.
+    is_lod_changed: bool,
+    /// Camera position of the last prepared frame, for selecting the levels of detail.
+    camera_position: glam::Vec3,
     //TODO: refactor `ResourceArray` to not carry the freelist logic
     // This way we can embed user info into the allocator.
+    /// Texture behind every resource index, with the number of hit entries using it.
     texture_resource_lookup:
-        HashMap<blade_graphics::ResourceIndex, blade_asset::Handle<crate::Texture>>,
+        HashMap<blade_graphics::ResourceIndex, (blade_asset::Handle<crate::Texture>, usize)>,
+    /// Resource indices of the dummy white and black textures.
+    dummy_textures: [blade_graphics::ResourceIndex; 2],
+    // Data of the last built scene, reused by transform updates
+    hit_entries: Vec<HitEntry>,
+    /// Number of hit entries the hit buffer can hold.
+    hit_capacity: usize,
+    /// Ranges of the hit entries left by the removed objects.
+    free_hit_entries: FreeRanges,
+    /// Resources of every object, in the order of the objects.
+    object_entries: Vec<ObjectEntry>,
+    model_entries: HashMap<blade_asset::Handle<crate::Model>, ModelEntry>,
+    /// Number of times the scene was built from scratch.
+    full_builds: u32,
+    instances: Vec<blade_graphics::AccelerationStructureInstance>,
+    /// Radius of the bounding sphere of every instance, before the scaling.
+    instance_radii: Vec<f32>,
+    blases: Vec<blade_graphics::AccelerationStructure>,
+    /// Radius of a sphere around the object origins,
+    /// used to estimate the power of directional lights.
+    scene_radius: f32,
+    lights: Vec<crate::Light>,
+    light_buffer: blade_graphics::Buffer,
+    emissive_triangles: Vec<EmissiveTriangle>,
+    emissive_buffer: blade_graphics::Buffer,
+    skin_pipeline: blade_graphics::ComputePipeline,
+    skinned_instances: Vec<SkinnedInstance>,
+    /// Pool of the skinned vertices, split into regions per instance.
+    skinned_vertex_buffer: blade_graphics::Buffer,
+    /// Size of the vertex pool, in bytes.
+    skinned_vertex_size: u64,
+    /// Regions of the vertex pool left by the removed instances.
+    free_skin_regions: FreeRanges,
+    /// Skinned vertices of the previous frame, for the motion vectors.
+    prev_skinned_vertex_buffer: blade_graphics::Buffer,
+    /// True if the previous skinned vertices differ from the current ones.
+    is_skin_moved: bool,
+    /// True if the previous transforms in the hit entries differ from the current ones.
+    are_objects_moved: bool,
+}
+
+/// Instance of a skinned model, with its own vertices and BLAS.
+struct SkinnedInstance {
+    object_index: usize,
+    vertex_count: u32,
+    /// Offset of the instance region in the vertex pool, in bytes.
+    vertex_offset: u64,
+    region_size: u64,
+    meshes: Vec<blade_graphics::AccelerationStructureMesh>,
+    blas: blade_graphics::AccelerationStructure,
+    scratch_size: u64,
+    buffer_indices: Vec<entries::BufferIndices>,
 }
 
 #[repr(C)]
@@ -366,6 +813,36 @@ struct MainParams {
     use_pairwise_mis: u32,
     defensive_mis: f32,
     use_motion_vectors: u32,
+    num_light_samples: u32,
+    light_count: u32,
+    num_emissive_samples: u32,
+    emissive_count: u32,
+    max_bounces: u32,
+    firefly_clamp: f32,
+    russian_roulette_start: u32,
+    use_adaptive_sampling: u32,
+    environment_yaw: f32,
+    environment_intensity: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct MediumParams {
+    color: [f32; 3],
+    anisotropy: f32,
+    absorption: f32,
+    scattering: f32,
+    base_height: f32,
+    height_falloff: f32,
+    step_count: u32,
+    use_history: u32,
+    pad: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct FillParams {
+    use_motion_vectors: u32,
 }
 
 #[derive(blade_macros::ShaderData)]
@@ -373,10 +850,12 @@ struct FillData<'a> {
     camera: CameraParams,
     prev_camera: CameraParams,
     debug: DebugParams,
+    parameters: FillParams,
     acc_struct: blade_graphics::AccelerationStructure,
     hit_entries: blade_graphics::BufferPiece,
     index_buffers: &'a blade_graphics::BufferArray<MAX_RESOURCES>,
     vertex_buffers: &'a blade_graphics::BufferArray<MAX_RESOURCES>,
+    tex_coord_buffers: &'a blade_graphics::BufferArray<MAX_RESOURCES>,
     textures: &'a blade_graphics::TextureArray<MAX_RESOURCES>,
     sampler_linear: blade_graphics::Sampler,
     debug_buf: blade_graphics::BufferPiece,
@@ -384,36 +863,69 @@ struct FillData<'a> {
     out_basis: blade_graphics::TextureView,
     out_flat_normal: blade_graphics::TextureView,
     out_albedo: blade_graphics::TextureView,
+    out_hit_entry: blade_graphics::TextureView,
     out_motion: blade_graphics::TextureView,
+    out_emission: blade_graphics::TextureView,
     out_debug: blade_graphics::TextureView,
 }
 
 #[derive(blade_macros::ShaderData)]
-struct MainData {
+struct MainData<'a> {
     camera: CameraParams,
     prev_camera: CameraParams,
     debug: DebugParams,
     parameters: MainParams,
+    medium: MediumParams,
+    probes: ProbeParams,
     acc_struct: blade_graphics::AccelerationStructure,
     prev_acc_struct: blade_graphics::AccelerationStructure,
     sampler_linear: blade_graphics::Sampler,
     sampler_nearest: blade_graphics::Sampler,
     env_map: blade_graphics::TextureView,
     env_weights: blade_graphics::TextureView,
+    lights: blade_graphics::BufferPiece,
+    emissive_triangles: blade_graphics::BufferPiece,
+    hit_entries: blade_graphics::BufferPiece,
+    index_buffers: &'a blade_graphics::BufferArray<MAX_RESOURCES>,
+    vertex_buffers: &'a blade_graphics::BufferArray<MAX_RESOURCES>,
+    tex_coord_buffers: &'a blade_graphics::BufferArray<MAX_RESOURCES>,
+    textures: &'a blade_graphics::TextureArray<MAX_RESOURCES>,
     t_depth: blade_graphics::TextureView,
     t_prev_depth: blade_graphics::TextureView,
     t_basis: blade_graphics::TextureView,
     t_prev_basis: blade_graphics::TextureView,
     t_flat_normal: blade_graphics::TextureView,
     t_prev_flat_normal: blade_graphics::TextureView,
+    t_albedo: blade_graphics::TextureView,
+    t_hit_entry: blade_graphics::TextureView,
+    t_prev_hit_entry: blade_graphics::TextureView,
     t_motion: blade_graphics::TextureView,
+    t_tile_samples: blade_graphics::TextureView,
     debug_buf: blade_graphics::BufferPiece,
     reservoirs: blade_graphics::BufferPiece,
     prev_reservoirs: blade_graphics::BufferPiece,
+    t_prev_medium: blade_graphics::TextureView,
+    t_probes: blade_graphics::TextureView,
     out_diffuse: blade_graphics::TextureView,
+    out_medium: blade_graphics::TextureView,
     out_debug: blade_graphics::TextureView,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct SkinParams {
+    vertex_count: u32,
+}
+
+#[derive(blade_macros::ShaderData)]
+struct SkinData {
+    params: SkinParams,
+    joint_matrices: blade_graphics::BufferPiece,
+    rest_vertices: blade_graphics::BufferPiece,
+    skin_vertices: blade_graphics::BufferPiece,
+    out_vertices: blade_graphics::BufferPiece,
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
 struct BlurParams {
@@ -421,7 +933,7 @@ struct BlurParams {
     temporal_weight: f32,
     iteration: i32,
     use_motion_vectors: u32,
-    pad: u32,
+    history_clamp_sigma: f32,
 }
 
 #[derive(blade_macros::ShaderData)]
@@ -435,7 +947,9 @@ struct TemporalAccumData {
     t_flat_normal: blade_graphics::TextureView,
     t_prev_flat_normal: blade_graphics::TextureView,
     t_motion: blade_graphics::TextureView,
+    t_prev_history: blade_graphics::TextureView,
     output: blade_graphics::TextureView,
+    out_history: blade_graphics::TextureView,
 }
 
 #[derive(blade_macros::ShaderData)]
@@ -444,29 +958,86 @@ struct ATrousData {
     input: blade_graphics::TextureView,
     t_depth: blade_graphics::TextureView,
     t_flat_normal: blade_graphics::TextureView,
+    t_basis: blade_graphics::TextureView,
+    output: blade_graphics::TextureView,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct TilePriorityParams {
+    variance_threshold: f32,
+    max_samples: u32,
+    pad: [u32; 2],
+}
+
+#[derive(blade_macros::ShaderData)]
+struct TilePriorityData {
+    params: BlurParams,
+    tile_params: TilePriorityParams,
+    input: blade_graphics::TextureView,
+    out_tile_samples: blade_graphics::TextureView,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct AccumulateParams {
+    frame_count: u32,
+}
+
+#[derive(blade_macros::ShaderData)]
+struct AccumulateData {
+    params: AccumulateParams,
+    t_albedo: blade_graphics::TextureView,
+    light_diffuse: blade_graphics::TextureView,
+    t_emission: blade_graphics::TextureView,
+    t_medium: blade_graphics::TextureView,
     output: blade_graphics::TextureView,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, Default, bytemuck::Zeroable, bytemuck::Pod)]
 struct ToneMapParams {
-    enabled: u32,
-    average_lum: f32,
-    key_value: f32,
-    white_level: f32,
+    mode: u32,
+    exposure: f32,
+    white_point: f32,
+    encode_srgb: u32,
+    auto_exposure: u32,
+    pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default, bytemuck::Zeroable, bytemuck::Pod)]
+struct PostProcParams {
+    is_view_available: u32,
+    use_accumulation: u32,
+    max_samples: u32,
+    bloom_intensity: f32,
+    input_scale: [f32; 2],
+    sharpness: f32,
+    is_upscaled: u32,
 }
 
 #[derive(blade_macros::ShaderData)]
 struct PostProcData {
     t_albedo: blade_graphics::TextureView,
     light_diffuse: blade_graphics::TextureView,
+    t_emission: blade_graphics::TextureView,
+    t_medium: blade_graphics::TextureView,
     t_debug: blade_graphics::TextureView,
+    t_history: blade_graphics::TextureView,
+    t_accumulation: blade_graphics::TextureView,
+    t_tile_samples: blade_graphics::TextureView,
+    t_motion: blade_graphics::TextureView,
     tone_map_params: ToneMapParams,
     debug_params: DebugParams,
+    post_proc_params: PostProcParams,
+    exposure_state: blade_graphics::BufferPiece,
+    t_bloom: blade_graphics::TextureView,
+    bloom_sampler: blade_graphics::Sampler,
 }
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 struct HitEntry {
     index_buf: u32,
     vertex_buf: u32,
@@ -480,6 +1051,162 @@ struct HitEntry {
     base_color_factor: [u8; 4],
     normal_texture: u32,
     normal_scale: f32,
+    emissive_factor: [f32; 3],
+    alpha_mode: u32,
+    alpha_cutoff: f32,
+    transmission: f32,
+    ior: f32,
+    clearcoat: f32,
+    sheen_color: [f32; 3],
+    sheen_roughness: f32,
+    clearcoat_roughness: f32,
+    prev_vertex_buf: u32,
+    base_color_tex_coord: u32,
+    normal_tex_coord: u32,
+    base_color_transform: [[f32; 2]; 3],
+    normal_transform: [[f32; 2]; 3],
+    tex_coords1_buf: u32,
+    lod: u32,
+    pad: [u32; 2],
+}
+
+impl HitEntry {
+    /// Assign the material parameters that objects can override.
+    fn set_material(
+        &mut self,
+        material: &crate::model::Material,
+        overrides: &crate::MaterialOverrides,
+    ) {
+        let c = material.base_color_factor;
+        let f = overrides.base_color_factor;
+        self.base_color_factor = [
+            (c[0] * f[0] * 255.0) as u8,
+            (c[1] * f[1] * 255.0) as u8,
+            (c[2] * f[2] * 255.0) as u8,
+            (c[3] * f[3] * 255.0) as u8,
+        ];
+        self.emissive_factor = overridden_emission(material, overrides);
+        // The path tracer only has roughness on the sheen and clearcoat layers
+        self.sheen_roughness = (material.sheen_roughness * overrides.roughness_factor).min(1.0);
+        self.clearcoat_roughness =
+            (material.clearcoat_roughness * overrides.roughness_factor).min(1.0);
+    }
+}
+
+fn overridden_emission(
+    material: &crate::model::Material,
+    overrides: &crate::MaterialOverrides,
+) -> [f32; 3] {
+    let e = material.emissive_factor;
+    let f = overrides.emissive_factor;
+    [e[0] * f[0], e[1] * f[1], e[2] * f[2]]
+}
+
+// Has to match the shader!
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct EmissiveTriangle {
+    hit_entry: u32,
+    primitive_index: u32,
+    area: f32,
+    /// Probability of choosing this triangle.
+    pdf: f32,
+    radiance: [f32; 3],
+    /// Probability of choosing this triangle or any before it.
+    cdf: f32,
+    object_to_world: mint::ColumnMatrix4<f32>,
+}
+
+/// Compute the probability of choosing each item, and the cumulative
+/// probability of choosing it or any before, proportionally to the power.
+fn compute_pdf_cdf(powers: &[f32]) -> Vec<(f32, f32)> {
+    let total_power = powers.iter().sum::<f32>();
+    let mut cdf = 0.0;
+    let mut result = powers
+        .iter()
+        .map(|&power| {
+            let pdf = if total_power > 0.0 {
+                power / total_power
+            } else {
+                1.0 / powers.len() as f32
+            };
+            cdf += pdf;
+            (pdf, cdf)
+        })
+        .collect::<Vec<_>>();
+    if let Some(last) = result.last_mut() {
+        // Guard the search against the rounding errors
+        last.1 = 1.0;
+    }
+    result
+}
+
+fn luminance(color: [f32; 3]) -> f32 {
+    0.3 * color[0] + 0.4 * color[1] + 0.3 * color[2]
+}
+
+const LIGHT_KIND_POINT: u32 = 0;
+const LIGHT_KIND_SPOT: u32 = 1;
+const LIGHT_KIND_DIRECTIONAL: u32 = 2;
+
+// Has to match the shader!
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Zeroable, bytemuck::Pod)]
+struct AnalyticLight {
+    position: [f32; 3],
+    kind: u32,
+    direction: [f32; 3],
+    cos_inner: f32,
+    radiance: [f32; 3],
+    cos_outer: f32,
+    /// Probability of choosing this light.
+    pdf: f32,
+    /// Probability of choosing this light or any before it.
+    cdf: f32,
+    pad: [u32; 2],
+}
+
+impl AnalyticLight {
+    fn new(light: &crate::Light) -> Self {
+        let radiance = light.color.map(|c| c * light.intensity);
+        let direction = glam::Vec3::from(light.direction).normalize_or_zero();
+        let (kind, cos_inner, cos_outer) = match light.kind {
+            crate::LightKind::Point => (LIGHT_KIND_POINT, -1.0, -1.0),
+            crate::LightKind::Spot {
+                inner_angle,
+                outer_angle,
+            } => (
+                LIGHT_KIND_SPOT,
+                inner_angle.min(outer_angle).cos(),
+                outer_angle.cos(),
+            ),
+            crate::LightKind::Directional { angular_radius } => {
+                (LIGHT_KIND_DIRECTIONAL, 1.0, angular_radius.cos())
+            }
+        };
+        Self {
+            position: light.position.into(),
+            kind,
+            direction: direction.into(),
+            cos_inner,
+            radiance,
+            cos_outer,
+            ..Default::default()
+        }
+    }
+
+    /// Estimate the emitted power, for the purpose of importance sampling.
+    fn power(&self, scene_radius: f32) -> f32 {
+        let luminance = luminance(self.radiance);
+        let area = match self.kind {
+            LIGHT_KIND_POINT => 4.0 * consts::PI,
+            // Count the falloff region as half lit
+            LIGHT_KIND_SPOT => consts::PI * (2.0 - self.cos_inner - self.cos_outer),
+            // Illuminates the whole scene cross-section
+            _ => consts::PI * scene_radius * scene_radius,
+        };
+        luminance.max(0.0) * area
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -489,9 +1216,14 @@ pub struct Shaders {
     pub(crate) ray_trace: blade_asset::Handle<crate::Shader>,
     pub(crate) a_trous: blade_asset::Handle<crate::Shader>,
     pub(crate) post_proc: blade_asset::Handle<crate::Shader>,
+    pub(crate) skin: blade_asset::Handle<crate::Shader>,
+    pub(crate) accumulate: blade_asset::Handle<crate::Shader>,
+    pub(crate) exposure: blade_asset::Handle<crate::Shader>,
+    pub(crate) bloom: blade_asset::Handle<crate::Shader>,
     pub(crate) raster: blade_asset::Handle<crate::Shader>,
     pub(crate) debug_draw: blade_asset::Handle<crate::Shader>,
     pub(crate) debug_blit: blade_asset::Handle<crate::Shader>,
+    pub(crate) pick: blade_asset::Handle<crate::Shader>,
 }
 
 impl Shaders {
@@ -512,9 +1244,14 @@ impl Shaders {
             ray_trace: noop.unwrap_or_else(|| ctx.load_shader("ray-trace.wgsl")),
             a_trous: noop.unwrap_or_else(|| ctx.load_shader("a-trous.wgsl")),
             post_proc: noop.unwrap_or_else(|| ctx.load_shader("post-proc.wgsl")),
+            skin: noop.unwrap_or_else(|| ctx.load_shader("skin.wgsl")),
+            accumulate: noop.unwrap_or_else(|| ctx.load_shader("accumulate.wgsl")),
+            exposure: noop.unwrap_or_else(|| ctx.load_shader("exposure.wgsl")),
+            bloom: noop.unwrap_or_else(|| ctx.load_shader("bloom.wgsl")),
             raster: ctx.load_shader("raster.wgsl"),
             debug_draw: ctx.load_shader("debug-draw.wgsl"),
             debug_blit: ctx.load_shader("debug-blit.wgsl"),
+            pick: noop.unwrap_or_else(|| ctx.load_shader("pick.wgsl")),
         };
         (shaders, ctx.close())
     }
@@ -523,10 +1260,14 @@ impl Shaders {
 struct ShaderPipelines {
     fill: blade_graphics::ComputePipeline,
     main: blade_graphics::ComputePipeline,
+    probe_bake: blade_graphics::ComputePipeline,
     temporal_accum: blade_graphics::ComputePipeline,
     a_trous: blade_graphics::ComputePipeline,
+    tile_priority: blade_graphics::ComputePipeline,
     post_proc: blade_graphics::RenderPipeline,
     env_prepare: blade_graphics::ComputePipeline,
+    skin: blade_graphics::ComputePipeline,
+    accumulate: blade_graphics::ComputePipeline,
     reservoir_size: u32,
 }
 
@@ -537,6 +1278,7 @@ impl ShaderPipelines {
     ) -> blade_graphics::ComputePipeline {
         shader.check_struct_size::<crate::Vertex>();
         shader.check_struct_size::<HitEntry>();
+        shader.check_struct_size::<FillParams>();
         let layout = <FillData as blade_graphics::ShaderData>::layout();
         gpu.create_compute_pipeline(blade_graphics::ComputePipelineDesc {
             name: "fill-gbuf",
@@ -551,6 +1293,9 @@ impl ShaderPipelines {
         shader.check_struct_size::<CameraParams>();
         shader.check_struct_size::<DebugParams>();
         shader.check_struct_size::<MainParams>();
+        shader.check_struct_size::<MediumParams>();
+        shader.check_struct_size::<AnalyticLight>();
+        shader.check_struct_size::<EmissiveTriangle>();
         shader.check_struct_size::<DebugVariance>();
         shader.check_struct_size::<DebugEntry>();
         let layout = <MainData as blade_graphics::ShaderData>::layout();
@@ -585,11 +1330,54 @@ impl ShaderPipelines {
         })
     }
 
+    fn create_tile_priority(
+        shader: &blade_graphics::Shader,
+        gpu: &blade_graphics::Context,
+    ) -> blade_graphics::ComputePipeline {
+        shader.check_struct_size::<TilePriorityParams>();
+        let layout = <TilePriorityData as blade_graphics::ShaderData>::layout();
+        gpu.create_compute_pipeline(blade_graphics::ComputePipelineDesc {
+            name: "tile-priority",
+            data_layouts: &[&layout],
+            compute: shader.at("tile_priority"),
+        })
+    }
+
+    fn create_skin(
+        shader: &blade_graphics::Shader,
+        gpu: &blade_graphics::Context,
+    ) -> blade_graphics::ComputePipeline {
+        shader.check_struct_size::<crate::Vertex>();
+        shader.check_struct_size::<crate::SkinVertex>();
+        shader.check_struct_size::<SkinParams>();
+        let layout = <SkinData as blade_graphics::ShaderData>::layout();
+        gpu.create_compute_pipeline(blade_graphics::ComputePipelineDesc {
+            name: "skin",
+            data_layouts: &[&layout],
+            compute: shader.at("main"),
+        })
+    }
+
+    fn create_accumulate(
+        shader: &blade_graphics::Shader,
+        gpu: &blade_graphics::Context,
+    ) -> blade_graphics::ComputePipeline {
+        shader.check_struct_size::<AccumulateParams>();
+        let layout = <AccumulateData as blade_graphics::ShaderData>::layout();
+        gpu.create_compute_pipeline(blade_graphics::ComputePipelineDesc {
+            name: "accumulate",
+            data_layouts: &[&layout],
+            compute: shader.at("main"),
+        })
+    }
+
     fn create_post_proc(
         shader: &blade_graphics::Shader,
         info: blade_graphics::SurfaceInfo,
         gpu: &blade_graphics::Context,
     ) -> blade_graphics::RenderPipeline {
+        shader.check_struct_size::<ToneMapParams>();
+        shader.check_struct_size::<PostProcParams>();
         let layout = <PostProcData as blade_graphics::ShaderData>::layout();
         gpu.create_render_pipeline(blade_graphics::RenderPipelineDesc {
             name: "main",
@@ -604,6 +1392,7 @@ impl ShaderPipelines {
             color_targets: &[info.format.into()],
             depth_stencil: None,
             multisample_state: blade_graphics::MultisampleState::default(),
+            multiview: None,
         })
     }
 
@@ -618,8 +1407,10 @@ impl ShaderPipelines {
         Ok(Self {
             fill: Self::create_gbuf_fill(shader_man[shaders.fill_gbuf].raw.as_ref().unwrap(), gpu),
             main: Self::create_ray_trace(sh_main, gpu),
+            probe_bake: Self::create_probe_bake(sh_main, gpu),
             temporal_accum: Self::create_temporal_accum(sh_a_trous, gpu),
             a_trous: Self::create_a_trous(sh_a_trous, gpu),
+            tile_priority: Self::create_tile_priority(sh_a_trous, gpu),
             post_proc: Self::create_post_proc(
                 shader_man[shaders.post_proc].raw.as_ref().unwrap(),
                 config.surface_info,
@@ -629,24 +1420,67 @@ impl ShaderPipelines {
                 shader_man[shaders.env_prepare].raw.as_ref().unwrap(),
                 gpu,
             )?,
+            skin: Self::create_skin(shader_man[shaders.skin].raw.as_ref().unwrap(), gpu),
+            accumulate: Self::create_accumulate(
+                shader_man[shaders.accumulate].raw.as_ref().unwrap(),
+                gpu,
+            ),
             reservoir_size: sh_main.get_struct_size("StoredReservoir"),
         })
     }
 }
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy)]
 pub struct FrameConfig {
     pub frozen: bool,
     pub debug_draw: bool,
     pub reset_variance: bool,
     pub reset_reservoirs: bool,
+    /// Progressively accumulate the frames for the offline rendering.
+    /// The spatio-temporal reuse is disabled, and the accumulation
+    /// starts over whenever the camera moves or the reservoirs are reset.
+    pub accumulate: bool,
+    /// Mask of the object layers seen by the rays.
+    /// The objects outside of these layers are masked out of the TLAS,
+    /// which is updated by the next `build_scene` or `update_scene`.
+    pub visible_layers: u32,
+    /// Filtering of the objects against the camera before the TLAS build.
+    pub culling: CullingConfig,
+}
+
+impl Default for FrameConfig {
+    fn default() -> Self {
+        Self {
+            frozen: false,
+            debug_draw: false,
+            reset_variance: false,
+            reset_reservoirs: false,
+            accumulate: false,
+            visible_layers: crate::ALL_LAYERS,
+            culling: CullingConfig::default(),
+        }
+    }
+}
+
+/// Smallest fraction of the surface size that is rendered.
+const MIN_RENDER_SCALE: f32 = 0.25;
+
+/// Size of the surface scaled by the render scale, at least 1 pixel.
+fn scaled_extent(size: blade_graphics::Extent, scale: f32) -> blade_graphics::Extent {
+    blade_graphics::Extent {
+        width: ((size.width as f32 * scale).round() as u32).max(1),
+        height: ((size.height as f32 * scale).round() as u32).max(1),
+        depth: 1,
+    }
 }
 
-/// Temporary resources associated with a GPU frame.
-#[derive(Default)]
-pub struct FrameResources {
-    pub buffers: Vec<blade_graphics::Buffer>,
-    pub acceleration_structures: Vec<blade_graphics::AccelerationStructure>,
+/// TLAS instance mask of an object, which skips it entirely when it's not visible.
+fn instance_mask(layers: u32, visible_layers: u32) -> u32 {
+    if layers & visible_layers != 0 {
+        0xFF
+    } else {
+        0
+    }
 }
 
 impl RayTracer {
@@ -683,6 +1517,19 @@ impl RayTracer {
             )
         };
 
+        let exposure = Exposure::init(
+            encoder,
+            gpu,
+            shader_man[shaders.exposure].raw.as_ref().unwrap(),
+        );
+
+        let bloom = Bloom::init(
+            encoder,
+            gpu,
+            shader_man[shaders.bloom].raw.as_ref().unwrap(),
+            config.surface_size,
+        );
+
         let targets = RestirTargets::new(config.surface_size, sp.reservoir_size, encoder, gpu);
         let dummy = DummyResources::new(encoder, gpu);
 
@@ -709,12 +1556,25 @@ impl RayTracer {
             shaders,
             targets,
             post_proc_input_index: 0,
+            is_temporally_accumulated: false,
+            has_prev_history: false,
+            is_history_reset: false,
+            requested_ray_config: None,
+            active_ray_config: None,
+            is_accumulating: false,
+            accumulated_frames: 0,
+            accumulate_pipeline: sp.accumulate,
+            has_tile_samples: false,
+            medium_frame_index: None,
             fill_pipeline: sp.fill,
             main_pipeline: sp.main,
+            probe_bake_pipeline: sp.probe_bake,
+            irradiance_volume: None,
             post_proc_pipeline: sp.post_proc,
             blur: Blur {
                 temporal_accum_pipeline: sp.temporal_accum,
                 a_trous_pipeline: sp.a_trous,
+                tile_priority_pipeline: sp.tile_priority,
             },
             acceleration_structure: blade_graphics::AccelerationStructure::default(),
             prev_acceleration_structure: blade_graphics::AccelerationStructure::default(),
@@ -723,16 +1583,67 @@ impl RayTracer {
             hit_buffer: blade_graphics::Buffer::default(),
             vertex_buffers: blade_graphics::BufferArray::new(),
             index_buffers: blade_graphics::BufferArray::new(),
+            tex_coord_buffers: blade_graphics::BufferArray::new(),
             textures: blade_graphics::TextureArray::new(),
             samplers,
             reservoir_size: sp.reservoir_size,
             debug,
+            debug_draw: DebugDraw::default(),
+            exposure,
+            bloom,
+            pass_timings: PassTimings::default(),
             surface_size: config.surface_size,
             surface_info: config.surface_info,
+            render_scale: 1.0,
+            render_size: config.surface_size,
             frame_index: 0,
             frame_scene_built: 0,
+            visible_layers: crate::ALL_LAYERS,
+            is_visibility_changed: false,
+            culling: Culling::default(),
+            is_culling_changed: false,
+            instance_masks: Vec::new(),
+            tlas_objects: Arc::new([]),
+            culling_stats: CullingStats::default(),
+            lod_chains: HashMap::default(),
+            are_lod_chains_changed: false,
+            lod_entries: Vec::new(),
+            instance_lods: Vec::new(),
+            is_lod_changed: false,
+            camera_position: glam::Vec3::ZERO,
             is_frozen: false,
             texture_resource_lookup: HashMap::default(),
+            dummy_textures: [0; 2],
+            hit_entries: Vec::new(),
+            hit_capacity: 0,
+            free_hit_entries: FreeRanges::default(),
+            object_entries: Vec::new(),
+            model_entries: HashMap::default(),
+            full_builds: 0,
+            instances: Vec::new(),
+            instance_radii: Vec::new(),
+            blases: Vec::new(),
+            scene_radius: 1.0,
+            lights: Vec::new(),
+            light_buffer: gpu.create_buffer(blade_graphics::BufferDesc {
+                name: "lights",
+                size: mem::size_of::<AnalyticLight>() as u64,
+                memory: blade_graphics::Memory::Device,
+            }),
+            emissive_triangles: Vec::new(),
+            emissive_buffer: gpu.create_buffer(blade_graphics::BufferDesc {
+                name: "emissive triangles",
+                size: mem::size_of::<EmissiveTriangle>() as u64,
+                memory: blade_graphics::Memory::Device,
+            }),
+            skin_pipeline: sp.skin,
+            skinned_instances: Vec::new(),
+            skinned_vertex_buffer: blade_graphics::Buffer::default(),
+            skinned_vertex_size: 0,
+            free_skin_regions: FreeRanges::default(),
+            prev_skinned_vertex_buffer: blade_graphics::Buffer::default(),
+            is_skin_moved: false,
+            are_objects_moved: false,
         }
     }
 
@@ -743,6 +1654,15 @@ impl RayTracer {
         if self.hit_buffer != blade_graphics::Buffer::default() {
             gpu.destroy_buffer(self.hit_buffer);
         }
+        gpu.destroy_buffer(self.light_buffer);
+        gpu.destroy_buffer(self.emissive_buffer);
+        if self.skinned_vertex_buffer != blade_graphics::Buffer::default() {
+            gpu.destroy_buffer(self.skinned_vertex_buffer);
+            gpu.destroy_buffer(self.prev_skinned_vertex_buffer);
+        }
+        for instance in self.skinned_instances.drain(..) {
+            gpu.destroy_acceleration_structure(instance.blas);
+        }
         gpu.destroy_acceleration_structure(self.acceleration_structure);
         if self.prev_acceleration_structure != blade_graphics::AccelerationStructure::default() {
             gpu.destroy_acceleration_structure(self.prev_acceleration_structure);
@@ -751,14 +1671,20 @@ impl RayTracer {
         self.env_map.destroy(gpu);
         self.dummy.destroy(gpu);
         self.debug.destroy(gpu);
+        self.exposure.destroy(gpu);
+        self.bloom.destroy(gpu);
         // samplers
         gpu.destroy_sampler(self.samplers.nearest);
         gpu.destroy_sampler(self.samplers.linear);
         // pipelines
         gpu.destroy_compute_pipeline(&mut self.blur.temporal_accum_pipeline);
         gpu.destroy_compute_pipeline(&mut self.blur.a_trous_pipeline);
+        gpu.destroy_compute_pipeline(&mut self.blur.tile_priority_pipeline);
         gpu.destroy_compute_pipeline(&mut self.fill_pipeline);
         gpu.destroy_compute_pipeline(&mut self.main_pipeline);
+        gpu.destroy_compute_pipeline(&mut self.probe_bake_pipeline);
+        gpu.destroy_compute_pipeline(&mut self.skin_pipeline);
+        gpu.destroy_compute_pipeline(&mut self.accumulate_pipeline);
         gpu.destroy_render_pipeline(&mut self.post_proc_pipeline);
     }
 
@@ -776,6 +1702,10 @@ impl RayTracer {
         tasks.extend(asset_hub.shaders.hot_reload(&mut self.shaders.ray_trace));
         tasks.extend(asset_hub.shaders.hot_reload(&mut self.shaders.a_trous));
         tasks.extend(asset_hub.shaders.hot_reload(&mut self.shaders.post_proc));
+        tasks.extend(asset_hub.shaders.hot_reload(&mut self.shaders.skin));
+        tasks.extend(asset_hub.shaders.hot_reload(&mut self.shaders.accumulate));
+        tasks.extend(asset_hub.shaders.hot_reload(&mut self.shaders.exposure));
+        tasks.extend(asset_hub.shaders.hot_reload(&mut self.shaders.bloom));
         tasks.extend(asset_hub.shaders.hot_reload(&mut self.shaders.debug_draw));
         tasks.extend(asset_hub.shaders.hot_reload(&mut self.shaders.debug_blit));
 
@@ -802,12 +1732,14 @@ impl RayTracer {
                 self.reservoir_size
             );
             self.main_pipeline = ShaderPipelines::create_ray_trace(shader, gpu);
+            self.probe_bake_pipeline = ShaderPipelines::create_probe_bake(shader, gpu);
         }
         if self.shaders.a_trous != old.a_trous
             && let Ok(ref shader) = asset_hub.shaders[self.shaders.a_trous].raw
         {
             self.blur.temporal_accum_pipeline = ShaderPipelines::create_temporal_accum(shader, gpu);
             self.blur.a_trous_pipeline = ShaderPipelines::create_a_trous(shader, gpu);
+            self.blur.tile_priority_pipeline = ShaderPipelines::create_tile_priority(shader, gpu);
         }
         if self.shaders.post_proc != old.post_proc
             && let Ok(ref shader) = asset_hub.shaders[self.shaders.post_proc].raw
@@ -815,6 +1747,26 @@ impl RayTracer {
             self.post_proc_pipeline =
                 ShaderPipelines::create_post_proc(shader, self.surface_info, gpu);
         }
+        if self.shaders.skin != old.skin
+            && let Ok(ref shader) = asset_hub.shaders[self.shaders.skin].raw
+        {
+            self.skin_pipeline = ShaderPipelines::create_skin(shader, gpu);
+        }
+        if self.shaders.accumulate != old.accumulate
+            && let Ok(ref shader) = asset_hub.shaders[self.shaders.accumulate].raw
+        {
+            self.accumulate_pipeline = ShaderPipelines::create_accumulate(shader, gpu);
+        }
+        if self.shaders.exposure != old.exposure
+            && let Ok(ref shader) = asset_hub.shaders[self.shaders.exposure].raw
+        {
+            self.exposure.recreate_pipelines(shader, gpu);
+        }
+        if self.shaders.bloom != old.bloom
+            && let Ok(ref shader) = asset_hub.shaders[self.shaders.bloom].raw
+        {
+            self.bloom.recreate_pipelines(shader, gpu);
+        }
         if self.shaders.debug_draw != old.debug_draw
             && let Ok(ref shader) = asset_hub.shaders[self.shaders.debug_draw].raw
         {
@@ -833,6 +1785,15 @@ impl RayTracer {
         self.surface_size
     }
 
+    /// Size of the internal targets, which `post_proc` upscales to the surface size.
+    pub fn get_render_size(&self) -> blade_graphics::Extent {
+        self.render_size
+    }
+
+    pub fn get_render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
     pub fn view_dummy_white(&self) -> blade_graphics::TextureView {
         self.dummy.white_view
     }
@@ -851,8 +1812,41 @@ impl RayTracer {
         gpu: &blade_graphics::Context,
     ) {
         self.surface_size = size;
+        self.recreate_targets(encoder, gpu);
+    }
+
+    /// Render at a fraction of the surface size, from 0 to 1,
+    /// which `post_proc` upscales with the sharpening filter.
+    ///
+    /// The internal targets are reallocated if the render size changes,
+    /// which also drops the history.
+    #[profiling::function]
+    pub fn set_render_scale(
+        &mut self,
+        scale: f32,
+        encoder: &mut blade_graphics::CommandEncoder,
+        gpu: &blade_graphics::Context,
+    ) {
+        self.render_scale = scale.clamp(MIN_RENDER_SCALE, 1.0);
+        if scaled_extent(self.surface_size, self.render_scale) != self.render_size {
+            self.recreate_targets(encoder, gpu);
+        }
+    }
+
+    fn recreate_targets(
+        &mut self,
+        encoder: &mut blade_graphics::CommandEncoder,
+        gpu: &blade_graphics::Context,
+    ) {
+        let size = scaled_extent(self.surface_size, self.render_scale);
+        self.render_size = size;
         self.targets.destroy(gpu);
         self.targets = RestirTargets::new(size, self.reservoir_size, encoder, gpu);
+        self.bloom.resize(size, encoder, gpu);
+        self.accumulated_frames = 0;
+        self.has_tile_samples = false;
+        // the history is lost with the old targets
+        self.is_temporally_accumulated = false;
     }
 
     #[profiling::function]
@@ -865,151 +1859,894 @@ impl RayTracer {
         gpu: &blade_graphics::Context,
         temp: &mut FrameResources,
     ) {
-        let (env_view, env_extent) = match env_map {
-            Some(handle) => {
-                let asset = &asset_hub.textures[handle];
-                (asset.view, asset.extent)
+        self.assign_environment(command_encoder, env_map, asset_hub, gpu, temp);
+        self.full_builds += 1;
+
+        // The coarser levels get their hit entries after all the objects
+        self.lod_entries.clear();
+        for (object_index, object) in objects.iter().enumerate() {
+            let Some(levels) = self.lod_chains.get(&object.model) else {
+                continue;
+            };
+            if !asset_hub.models[object.model].joints.is_empty() {
+                continue;
             }
-            None => (self.dummy.white_view, blade_graphics::Extent::default()),
-        };
-        self.env_map
-            .assign(env_view, env_extent, command_encoder, gpu);
-
-        if self.prev_acceleration_structure != blade_graphics::AccelerationStructure::default() {
-            temp.acceleration_structures
-                .push(self.prev_acceleration_structure);
+            self.lod_entries.extend(levels.iter().map(|level| LodEntry {
+                object_index,
+                model: level.model,
+                geometry_offset: 0,
+            }));
         }
-        self.prev_acceleration_structure = self.acceleration_structure;
+        self.are_lod_chains_changed = false;
+        let hit_models = lod::hit_entry_models(objects, &self.lod_entries)
+            .map(|(_, model)| model)
+            .collect::<Vec<_>>();
 
-        let geometry_count = objects
+        let geometry_count = hit_models
+            .iter()
+            .map(|&model| asset_hub.models[model].geometries.len())
+            .sum::<usize>();
+        self.hit_capacity = geometry_count;
+        self.create_hit_buffer(gpu, temp);
+        self.free_hit_entries.clear();
+
+        self.vertex_buffers.clear();
+        self.index_buffers.clear();
+        self.tex_coord_buffers.clear();
+        self.textures.clear();
+        self.texture_resource_lookup.clear();
+        self.dummy_textures = [
+            self.textures.alloc(self.dummy.white_view),
+            self.textures.alloc(self.dummy.black_view),
+        ];
+
+        // Every skinned instance gets a region of the vertex pool
+        for instance in self.skinned_instances.drain(..) {
+            temp.acceleration_structures.push(instance.blas);
+        }
+        let mut skinned_vertex_size = 0;
+        let skinned_regions = objects
             .iter()
             .map(|object| {
                 let model = &asset_hub.models[object.model];
-                model.geometries.len()
+                if model.joints.is_empty() {
+                    return None;
+                }
+                let offset = skinned_vertex_size;
+                skinned_vertex_size +=
+                    entries::skinned_region_size(entries::skinned_vertex_count(model));
+                Some(offset)
             })
-            .sum::<usize>();
-        let hit_size = (geometry_count.max(1) * mem::size_of::<HitEntry>()) as u64;
-        //TODO: reuse the hit buffer
-        if self.hit_buffer != blade_graphics::Buffer::default() {
-            temp.buffers.push(self.hit_buffer);
+            .collect::<Vec<_>>();
+        if self.skinned_vertex_buffer != blade_graphics::Buffer::default() {
+            temp.buffers.push(self.skinned_vertex_buffer);
+            temp.buffers.push(self.prev_skinned_vertex_buffer);
+            self.skinned_vertex_buffer = blade_graphics::Buffer::default();
+            self.prev_skinned_vertex_buffer = blade_graphics::Buffer::default();
+        }
+        if skinned_vertex_size != 0 {
+            self.skinned_vertex_buffer = gpu.create_buffer(blade_graphics::BufferDesc {
+                name: "skinned vertices",
+                size: skinned_vertex_size,
+                memory: blade_graphics::Memory::Device,
+            });
+            self.prev_skinned_vertex_buffer = gpu.create_buffer(blade_graphics::BufferDesc {
+                name: "prev skinned vertices",
+                size: skinned_vertex_size,
+                memory: blade_graphics::Memory::Device,
+            });
+        }
+        self.skinned_vertex_size = skinned_vertex_size;
+        self.free_skin_regions.clear();
+
+        let mut geometry_index = 0;
+        self.hit_entries.clear();
+        self.instances.clear();
+        self.instance_radii.clear();
+        self.instance_lods.clear();
+        self.blases.clear();
+        self.object_entries.clear();
+        self.model_entries.clear();
+        let mut texture_indices = HashMap::new();
+
+        for (entry_index, &model_handle) in hit_models.iter().enumerate() {
+            let model = &asset_hub.models[model_handle];
+            // objects come first, followed by their coarser levels
+            let is_lod = entry_index >= objects.len();
+            let object_index = match entry_index.checked_sub(objects.len()) {
+                Some(lod_index) => {
+                    self.lod_entries[lod_index].geometry_offset = geometry_index as u32;
+                    self.lod_entries[lod_index].object_index
+                }
+                None => entry_index,
+            };
+            let object = &objects[object_index];
+            // Instances of the same model share the BLAS and the geometry buffers
+            let (blas_index, buffer_indices) = match skinned_regions[object_index] {
+                Some(region_offset) => {
+                    self.add_skinned_instance(object_index, model, region_offset, gpu)
+                }
+                None => self.acquire_model_entry(model_handle, model),
+            };
+            let lod = if !is_lod {
+                self.instances
+                    .push(blade_graphics::AccelerationStructureInstance {
+                        acceleration_structure_index: blas_index,
+                        transform: object.transform,
+                        mask: instance_mask(object.layers, self.visible_layers),
+                        custom_index: geometry_index as u32,
+                    });
+                self.instance_radii.push(model.radius);
+                self.object_entries.push(entries::ObjectEntry {
+                    handle: None,
+                    model: model_handle,
+                    geometry_offset: geometry_index as u32,
+                    geometry_count: model.geometries.len() as u32,
+                    is_skinned: skinned_regions[object_index].is_some(),
+                });
+                0
+            } else {
+                if self.instance_lods.last().map(|lods| lods.object_index) != Some(object_index) {
+                    let instance = &self.instances[object_index];
+                    self.instance_lods.push(InstanceLods {
+                        object_index,
+                        levels: vec![(
+                            instance.acceleration_structure_index,
+                            instance.custom_index,
+                        )],
+                        distances: self.lod_chains[&object.model]
+                            .iter()
+                            .map(|level| level.distance)
+                            .collect(),
+                        current: 0,
+                    });
+                }
+                let lods = self.instance_lods.last_mut().unwrap();
+                lods.levels.push((blas_index, geometry_index as u32));
+                lods.levels.len() as u32 - 1
+            };
+
+            let hit_entries = self.make_hit_entries(
+                object,
+                model,
+                &buffer_indices,
+                lod,
+                &mut texture_indices,
+                asset_hub,
+            );
+            for hit_entry in hit_entries {
+                log::debug!("Entry[{geometry_index}] = {hit_entry:?}");
+                self.hit_entries.push(hit_entry);
+                geometry_index += 1;
+            }
+        }
+
+        assert_eq!(geometry_index, geometry_count);
+        log::info!(
+            "Preparing ray tracing with {} geometries in total, {} instances of {} models",
+            geometry_count,
+            self.instances.len(),
+            self.blases.len(),
+        );
+
+        self.update_scene_radius();
+        self.select_lods();
+        self.upload_hit_entries(command_encoder, gpu, temp);
+        self.skin_instances(command_encoder, objects, asset_hub, gpu, temp, false);
+        self.build_top_level(command_encoder, gpu, temp);
+        self.is_visibility_changed = false;
+        self.upload_emissive_triangles(command_encoder, objects, asset_hub, gpu, temp);
+        if !self.lights.is_empty() {
+            // Power of the directional lights depends on the scene size
+            self.upload_lights(command_encoder, gpu, temp);
+        }
+    }
+
+    fn create_hit_buffer(&mut self, gpu: &blade_graphics::Context, temp: &mut FrameResources) {
+        if self.hit_buffer != blade_graphics::Buffer::default() {
+            temp.buffers.push(self.hit_buffer);
         }
         self.hit_buffer = gpu.create_buffer(blade_graphics::BufferDesc {
             name: "hit entries",
-            size: hit_size,
+            size: (self.hit_capacity.max(1) * mem::size_of::<HitEntry>()) as u64,
             memory: blade_graphics::Memory::Device,
         });
+    }
+
+    /// Measure the radius of a sphere around the object origins.
+    fn update_scene_radius(&mut self) {
+        let center = self
+            .instances
+            .iter()
+            .map(|instance| mat4_transform(&instance.transform).w_axis.truncate())
+            .sum::<glam::Vec3>()
+            / self.instances.len().max(1) as f32;
+        self.scene_radius = self
+            .instances
+            .iter()
+            .map(|instance| {
+                let origin = mat4_transform(&instance.transform).w_axis.truncate();
+                origin.distance(center)
+            })
+            .fold(1.0, f32::max);
+    }
+
+    /// Create the BLAS of a skinned instance over its region of the vertex pool,
+    /// and allocate the geometry tables of its own.
+    fn add_skinned_instance(
+        &mut self,
+        object_index: usize,
+        model: &crate::Model,
+        region_offset: u64,
+        gpu: &blade_graphics::Context,
+    ) -> (u32, Vec<entries::BufferIndices>) {
+        let vertex_stride = mem::size_of::<crate::Vertex>() as u32;
+        let meshes =
+            model
+                .geometries
+                .iter()
+                .enumerate()
+                .map(|(index, geometry)| {
+                    let material = &model.materials[geometry.material_index];
+                    let transform_offset =
+                        index as u64 * mem::size_of::<blade_graphics::Transform>() as u64;
+                    blade_graphics::AccelerationStructureMesh {
+                        vertex_data: self.skinned_vertex_buffer.at(region_offset
+                            + geometry.vertex_range.start as u64 * vertex_stride as u64),
+                        vertex_format: blade_graphics::VertexFormat::F32Vec3,
+                        vertex_stride,
+                        vertex_count: geometry.vertex_range.end - geometry.vertex_range.start,
+                        index_data: model.index_buffer.at(geometry.index_offset),
+                        index_type: geometry.index_type,
+                        triangle_count: geometry.triangle_count,
+                        transform_data: model.transform_buffer.at(transform_offset),
+                        is_opaque: material.is_opaque(),
+                    }
+                })
+                .collect::<Vec<_>>();
+        let sizes = gpu.get_bottom_level_acceleration_structure_sizes(&meshes);
+        let blas = gpu.create_acceleration_structure(blade_graphics::AccelerationStructureDesc {
+            name: "skinned",
+            ty: blade_graphics::AccelerationStructureType::BottomLevel,
+            size: sizes.data,
+        });
+        let blas_index = self.blases.len() as u32;
+        self.blases.push(blas);
+        let buffer_indices = model
+            .geometries
+            .iter()
+            .map(|geometry| {
+                let vertex_offset = region_offset
+                    + geometry.vertex_range.start as u64 * mem::size_of::<crate::Vertex>() as u64;
+                let index_buf = match geometry.index_type {
+                    Some(_) => self
+                        .index_buffers
+                        .alloc(model.index_buffer.at(geometry.index_offset)),
+                    None => !0,
+                };
+                let vertex_buf = self
+                    .vertex_buffers
+                    .alloc(self.skinned_vertex_buffer.at(vertex_offset));
+                let prev_vertex_buf = self
+                    .vertex_buffers
+                    .alloc(self.prev_skinned_vertex_buffer.at(vertex_offset));
+                let tex_coords1_buf = if geometry.has_tex_coords1 {
+                    self.tex_coord_buffers.alloc(
+                        model
+                            .tex_coords1_buffer
+                            .at(geometry.vertex_range.start as u64
+                                * mem::size_of::<[f32; 2]>() as u64),
+                    )
+                } else {
+                    !0
+                };
+                (index_buf, vertex_buf, prev_vertex_buf, tex_coords1_buf)
+            })
+            .collect::<Vec<_>>();
+        let vertex_count = entries::skinned_vertex_count(model);
+        self.skinned_instances.push(SkinnedInstance {
+            object_index,
+            vertex_count,
+            vertex_offset: region_offset,
+            region_size: entries::skinned_region_size(vertex_count),
+            meshes,
+            blas,
+            scratch_size: sizes.scratch,
+            buffer_indices: buffer_indices.clone(),
+        });
+        (blas_index, buffer_indices)
+    }
+
+    /// Make the hit entries of the model geometries for an object.
+    fn make_hit_entries(
+        &mut self,
+        object: &crate::Object,
+        model: &crate::Model,
+        buffer_indices: &[entries::BufferIndices],
+        lod: u32,
+        texture_indices: &mut HashMap<
+            blade_asset::Handle<crate::Texture>,
+            blade_graphics::ResourceIndex,
+        >,
+        asset_hub: &crate::AssetHub,
+    ) -> Vec<HitEntry> {
+        let [dummy_white, dummy_black] = self.dummy_textures;
+        let mut hit_entries = Vec::with_capacity(model.geometries.len());
+        for (geometry, &(index_buf, vertex_buf, prev_vertex_buf, tex_coords1_buf)) in
+            model.geometries.iter().zip(buffer_indices.iter())
+        {
+            let material = &model.materials[geometry.material_index];
+            let geometry_to_world_rotation =
+                make_normal_rotation(&object.transform, &geometry.transform);
+
+            let mut hit_entry = HitEntry {
+                index_buf,
+                vertex_buf,
+                winding: model.winding,
+                geometry_to_world_rotation,
+                geometry_to_object: mint::ColumnMatrix4::from(mint::RowMatrix4 {
+                    x: geometry.transform.x,
+                    y: geometry.transform.y,
+                    z: geometry.transform.z,
+                    w: [0.0, 0.0, 0.0, 1.0].into(),
+                }),
+                prev_object_to_world: mat4_transform(&object.prev_transform).into(),
+                base_color_texture: match material.base_color_texture {
+                    Some(handle) => self.acquire_texture(handle, texture_indices, asset_hub),
+                    None => dummy_white,
+                },
+                // see `set_material`
+                base_color_factor: [0; 4],
+                normal_texture: match material.normal_texture {
+                    Some(handle) => self.acquire_texture(handle, texture_indices, asset_hub),
+                    None => dummy_black,
+                },
+                normal_scale: material.normal_scale,
+                emissive_factor: [0.0; 3],
+                alpha_mode: material.alpha_mode.to_raw().0,
+                alpha_cutoff: material.alpha_mode.to_raw().1,
+                transmission: material.transmission,
+                ior: material.ior,
+                clearcoat: material.clearcoat,
+                sheen_color: material.sheen_color,
+                sheen_roughness: 0.0,
+                clearcoat_roughness: 0.0,
+                prev_vertex_buf,
+                // without the second set, the first one is used instead
+                base_color_tex_coord: if geometry.has_tex_coords1 {
+                    material.base_color_mapping.tex_coord
+                } else {
+                    0
+                },
+                normal_tex_coord: if geometry.has_tex_coords1 {
+                    material.normal_mapping.tex_coord
+                } else {
+                    0
+                },
+                base_color_transform: material.base_color_mapping.transform,
+                normal_transform: material.normal_mapping.transform,
+                tex_coords1_buf,
+                lod,
+                pad: [0; 2],
+            };
+            hit_entry.set_material(material, &object.material_overrides);
+            hit_entries.push(hit_entry);
+        }
+        hit_entries
+    }
+
+    /// Skin the instances of skinned models with the current joints
+    /// of the objects, and rebuild the top-level acceleration structure.
+    ///
+    /// The objects have to match the last built scene.
+    pub fn update_skins(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        objects: &[crate::Object],
+        asset_hub: &crate::AssetHub,
+        gpu: &blade_graphics::Context,
+        temp: &mut FrameResources,
+    ) {
+        if self.skinned_instances.is_empty() {
+            return;
+        }
+        self.skin_instances(command_encoder, objects, asset_hub, gpu, temp, true);
+        self.build_top_level(command_encoder, gpu, temp);
+    }
+
+    /// Skin the instances into the vertex pool.
+    ///
+    /// If `keep_history` is set, the previous skinned vertices are kept
+    /// for the motion vectors. Otherwise, the skin is considered static.
+    fn skin_instances(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        objects: &[crate::Object],
+        asset_hub: &crate::AssetHub,
+        gpu: &blade_graphics::Context,
+        temp: &mut FrameResources,
+        keep_history: bool,
+    ) {
+        if self.skinned_instances.is_empty() {
+            return;
+        }
+        if keep_history {
+            self.copy_prev_skin(command_encoder);
+        }
+        const MATRIX_SIZE: u64 = mem::size_of::<[f32; 16]>() as u64;
+        let joint_offsets = self
+            .skinned_instances
+            .iter()
+            .scan(0, |offset, instance| {
+                let model = &asset_hub.models[objects[instance.object_index].model];
+                let current = *offset;
+                *offset = crate::util::align_to(
+                    current + model.joints.len() as u64 * MATRIX_SIZE,
+                    blade_graphics::limits::STORAGE_BUFFER_ALIGNMENT,
+                );
+                Some(current)
+            })
+            .collect::<Vec<_>>();
+        let last = self.skinned_instances.last().unwrap();
+        let joint_size = joint_offsets.last().unwrap()
+            + asset_hub.models[objects[last.object_index].model]
+                .joints
+                .len() as u64
+                * MATRIX_SIZE;
+        let joint_buffer = gpu.create_buffer(blade_graphics::BufferDesc {
+            name: "joint matrices",
+            size: joint_size,
+            memory: blade_graphics::Memory::Shared,
+        });
+        temp.buffers.push(joint_buffer);
+
+        for (instance, &joint_offset) in self.skinned_instances.iter().zip(joint_offsets.iter()) {
+            let object = &objects[instance.object_index];
+            let model = &asset_hub.models[object.model];
+            if !object.joints.is_empty() && object.joints.len() != model.joints.len() {
+                log::warn!(
+                    "Object[{}] has {} joints, but the model '{}' has {}",
+                    instance.object_index,
+                    object.joints.len(),
+                    model.name,
+                    model.joints.len()
+                );
+            }
+            let matrices = model
+                .joints
+                .iter()
+                .enumerate()
+                .map(|(index, joint)| {
+                    let transform = object.joints.get(index).unwrap_or(&joint.rest_transform);
+                    (glam::Mat4::from(*transform) * glam::Mat4::from(joint.inverse_bind_matrix))
+                        .to_cols_array()
+                })
+                .collect::<Vec<_>>();
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    matrices.as_ptr(),
+                    joint_buffer.data().add(joint_offset as usize) as *mut [f32; 16],
+                    matrices.len(),
+                );
+            }
+        }
+
+        if let mut pass = command_encoder.compute("skin") {
+            let mut pc = pass.with(&self.skin_pipeline);
+            let wg_size = self.skin_pipeline.get_workgroup_size();
+            for (instance, &joint_offset) in self.skinned_instances.iter().zip(joint_offsets.iter())
+            {
+                let model = &asset_hub.models[objects[instance.object_index].model];
+                pc.bind(
+                    0,
+                    &SkinData {
+                        params: SkinParams {
+                            vertex_count: instance.vertex_count,
+                        },
+                        joint_matrices: joint_buffer.at(joint_offset),
+                        rest_vertices: model.vertex_buffer.at(0),
+                        skin_vertices: model.skin_buffer.at(0),
+                        out_vertices: self.skinned_vertex_buffer.at(instance.vertex_offset),
+                    },
+                );
+                pc.dispatch([instance.vertex_count.div_ceil(wg_size[0]), 1, 1]);
+            }
+        }
+
+        // There is no refitting, the BLAS is rebuilt from the skinned positions
+        if let mut pass = command_encoder.acceleration_structure("skinned BLAS") {
+            for instance in self.skinned_instances.iter() {
+                let scratch = gpu.create_buffer(blade_graphics::BufferDesc {
+                    name: "skinned BLAS scratch",
+                    size: instance.scratch_size,
+                    memory: blade_graphics::Memory::Device,
+                });
+                pass.build_bottom_level(instance.blas, &instance.meshes, scratch.at(0));
+                temp.buffers.push(scratch);
+            }
+        }
+
+        if !keep_history {
+            self.copy_prev_skin(command_encoder);
+        }
+        self.is_skin_moved = keep_history;
+    }
+
+    /// Copy the current skinned vertices into the previous ones.
+    fn copy_prev_skin(&self, command_encoder: &mut blade_graphics::CommandEncoder) {
+        // the regions of the instances are scattered over the pool
+        let mut transfer = command_encoder.transfer("copy-prev-skin");
+        transfer.copy_buffer_to_buffer(
+            self.skinned_vertex_buffer.at(0),
+            self.prev_skinned_vertex_buffer.at(0),
+            self.skinned_vertex_size,
+        );
+    }
+
+    /// Set the analytic lights of the scene.
+    pub fn set_lights(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        lights: &[crate::Light],
+        gpu: &blade_graphics::Context,
+        temp: &mut FrameResources,
+    ) {
+        self.lights.clear();
+        self.lights.extend_from_slice(lights);
+        self.upload_lights(command_encoder, gpu, temp);
+    }
+
+    /// Update the scene from a retained `Scene`, doing only the work needed
+    /// for the changes since the last update.
+    ///
+    /// Adding or removing objects updates the geometry tables in place,
+    /// reusing the hit entries and the skinned vertex regions of the removed objects.
+    /// The scene is only rebuilt from scratch if the objects have levels of detail,
+    /// or the skinned vertex pool runs out of space.
+    /// Moving objects only updates the transforms and rebuilds the top-level
+    /// acceleration structure. Changing the joints re-skins the skinned instances.
+    #[profiling::function]
+    pub fn update_scene(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        scene: &mut crate::Scene,
+        env_map: Option<blade_asset::Handle<crate::Texture>>,
+        asset_hub: &crate::AssetHub,
+        gpu: &blade_graphics::Context,
+        temp: &mut FrameResources,
+    ) {
+        let is_structure_changed = scene.structure_changed
+            || self.are_lod_chains_changed
+            || self.object_entries.len() != scene.objects().len();
+        if is_structure_changed
+            && !self.update_objects(command_encoder, scene, asset_hub, gpu, temp)
+        {
+            self.build_scene(
+                command_encoder,
+                scene.objects(),
+                env_map,
+                asset_hub,
+                gpu,
+                temp,
+            );
+            for (entry, &handle) in self.object_entries.iter_mut().zip(scene.handles()) {
+                entry.handle = Some(handle);
+            }
+        } else {
+            self.assign_environment(command_encoder, env_map, asset_hub, gpu, temp);
+            // Streamed textures change their views as the mips come and go
+            for (&res_id, &(handle, _)) in self.texture_resource_lookup.iter() {
+                self.textures[res_id] = asset_hub.texture_view(handle);
+            }
+            // The objects that stopped still have their old previous transforms
+            let is_transform_refresh = scene.transforms_changed || self.are_objects_moved;
+            let hit_offsets =
+                lod::hit_entry_offsets(&self.object_entries, &self.lod_entries).collect::<Vec<_>>();
+            if scene.overrides_changed {
+                for ((object, model), &offset) in
+                    lod::hit_entry_models(scene.objects(), &self.lod_entries).zip(&hit_offsets)
+                {
+                    let model = &asset_hub.models[model];
+                    for (geometry, hit_entry) in model
+                        .geometries
+                        .iter()
+                        .zip(&mut self.hit_entries[offset as usize..])
+                    {
+                        let material = &model.materials[geometry.material_index];
+                        hit_entry.set_material(material, &object.material_overrides);
+                    }
+                }
+                // Transform changes upload the entries anyway
+                if !is_transform_refresh {
+                    self.upload_hit_entries(command_encoder, gpu, temp);
+                }
+                log::debug!("Material overrides changed, resetting the accumulation");
+                self.reset_history(command_encoder);
+            }
+            if is_transform_refresh {
+                for (object, instance) in scene.objects().iter().zip(self.instances.iter_mut()) {
+                    instance.transform = object.transform;
+                }
+                for ((object, model), &offset) in
+                    lod::hit_entry_models(scene.objects(), &self.lod_entries).zip(&hit_offsets)
+                {
+                    let model = &asset_hub.models[model];
+                    let prev_object_to_world = mat4_transform(&object.prev_transform).into();
+                    for (geometry, hit_entry) in model
+                        .geometries
+                        .iter()
+                        .zip(&mut self.hit_entries[offset as usize..])
+                    {
+                        hit_entry.geometry_to_world_rotation =
+                            make_normal_rotation(&object.transform, &geometry.transform);
+                        hit_entry.prev_object_to_world = prev_object_to_world;
+                    }
+                }
+                self.upload_hit_entries(command_encoder, gpu, temp);
+            }
+            let is_visibility_changed = scene.layers_changed || self.is_visibility_changed;
+            if is_visibility_changed {
+                for (object, instance) in scene.objects().iter().zip(self.instances.iter_mut()) {
+                    instance.mask = instance_mask(object.layers, self.visible_layers);
+                }
+                self.is_visibility_changed = false;
+            }
+            if scene.joints_changed {
+                self.skin_instances(command_encoder, scene.objects(), asset_hub, gpu, temp, true);
+            } else if self.is_skin_moved {
+                // the skin stopped, its previous vertices are the current ones
+                self.copy_prev_skin(command_encoder);
+                self.is_skin_moved = false;
+            }
+            if is_structure_changed
+                || scene.transforms_changed
+                || is_visibility_changed
+                || scene.geometry_changed
+                || (scene.joints_changed && !self.skinned_instances.is_empty())
+                || self.is_culling_changed
+                || self.is_lod_changed
+            {
+                self.build_top_level(command_encoder, gpu, temp);
+            }
+            if is_structure_changed
+                || (scene.transforms_changed && !self.emissive_triangles.is_empty())
+                || (scene.overrides_changed && !self.emissive_triangles.is_empty())
+                || is_visibility_changed
+            {
+                self.upload_emissive_triangles(
+                    command_encoder,
+                    scene.objects(),
+                    asset_hub,
+                    gpu,
+                    temp,
+                );
+            }
+        }
+        self.are_objects_moved = scene.transforms_changed;
+        if scene.lights_changed {
+            let lights = scene.lights().copied().collect::<Vec<_>>();
+            self.set_lights(command_encoder, &lights, gpu, temp);
+        }
+        scene.settle();
+    }
+
+    fn assign_environment(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        env_map: Option<blade_asset::Handle<crate::Texture>>,
+        asset_hub: &crate::AssetHub,
+        gpu: &blade_graphics::Context,
+        temp: &mut FrameResources,
+    ) {
+        let (env_view, env_extent) = match env_map {
+            Some(handle) => {
+                let asset = &asset_hub.textures[handle];
+                (asset.view, asset.extent)
+            }
+            None => (self.dummy.white_view, blade_graphics::Extent::default()),
+        };
+        if self
+            .env_map
+            .assign(env_view, env_extent, command_encoder, gpu, temp)
+        {
+            log::debug!("Environment map changed, resetting the accumulation");
+            self.reset_history(command_encoder);
+        }
+    }
+
+    fn upload_hit_entries(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        gpu: &blade_graphics::Context,
+        temp: &mut FrameResources,
+    ) {
+        let hit_size = (self.hit_entries.len().max(1) * mem::size_of::<HitEntry>()) as u64;
         let hit_staging = gpu.create_buffer(blade_graphics::BufferDesc {
             name: "hit staging",
             size: hit_size,
             memory: blade_graphics::Memory::Upload,
         });
         temp.buffers.push(hit_staging);
-        {
-            let mut transfers = command_encoder.transfer("build-scene");
-            transfers.copy_buffer_to_buffer(hit_staging.at(0), self.hit_buffer.at(0), hit_size);
+        unsafe {
+            ptr::copy_nonoverlapping(
+                self.hit_entries.as_ptr(),
+                hit_staging.data() as *mut HitEntry,
+                self.hit_entries.len(),
+            );
         }
+        let mut transfers = command_encoder.transfer("upload-hit-entries");
+        transfers.copy_buffer_to_buffer(hit_staging.at(0), self.hit_buffer.at(0), hit_size);
+    }
 
-        self.vertex_buffers.clear();
-        self.index_buffers.clear();
-        self.textures.clear();
-        let dummy_white = self.textures.alloc(self.dummy.white_view);
-        let dummy_black = self.textures.alloc(self.dummy.black_view);
+    fn upload_lights(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        gpu: &blade_graphics::Context,
+        temp: &mut FrameResources,
+    ) {
+        let mut records = self
+            .lights
+            .iter()
+            .map(AnalyticLight::new)
+            .collect::<Vec<_>>();
+        let powers = records
+            .iter()
+            .map(|record| record.power(self.scene_radius))
+            .collect::<Vec<_>>();
+        for (record, (pdf, cdf)) in records.iter_mut().zip(compute_pdf_cdf(&powers)) {
+            record.pdf = pdf;
+            record.cdf = cdf;
+        }
 
-        let mut geometry_index = 0;
-        let mut instances = Vec::with_capacity(objects.len());
-        let mut blases = Vec::with_capacity(objects.len());
-        let mut texture_indices = HashMap::new();
+        let light_size = (records.len().max(1) * mem::size_of::<AnalyticLight>()) as u64;
+        temp.buffers.push(self.light_buffer);
+        self.light_buffer = gpu.create_buffer(blade_graphics::BufferDesc {
+            name: "lights",
+            size: light_size,
+            memory: blade_graphics::Memory::Device,
+        });
+        if records.is_empty() {
+            return;
+        }
+        let light_staging = gpu.create_buffer(blade_graphics::BufferDesc {
+            name: "light staging",
+            size: light_size,
+            memory: blade_graphics::Memory::Upload,
+        });
+        temp.buffers.push(light_staging);
+        unsafe {
+            ptr::copy_nonoverlapping(
+                records.as_ptr(),
+                light_staging.data() as *mut AnalyticLight,
+                records.len(),
+            );
+        }
+        let mut transfers = command_encoder.transfer("upload-lights");
+        transfers.copy_buffer_to_buffer(light_staging.at(0), self.light_buffer.at(0), light_size);
+    }
 
-        for object in objects {
-            let m3_object = mat3_transform(&object.transform);
+    /// Gather the triangles of emissive materials, with their world-space
+    /// area and power, and upload them for light sampling.
+    fn upload_emissive_triangles(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        objects: &[crate::Object],
+        asset_hub: &crate::AssetHub,
+        gpu: &blade_graphics::Context,
+        temp: &mut FrameResources,
+    ) {
+        self.emissive_triangles.clear();
+        for (object, entry) in objects.iter().zip(self.object_entries.iter()) {
             let model = &asset_hub.models[object.model];
-            instances.push(blade_graphics::AccelerationStructureInstance {
-                acceleration_structure_index: blases.len() as u32,
-                transform: object.transform,
-                mask: 0xFF,
-                custom_index: geometry_index as u32,
-            });
-            blases.push(model.acceleration_structure);
-
-            for geometry in model.geometries.iter() {
+            // hidden objects don't emit any light
+            if object.layers & self.visible_layers == 0 {
+                continue;
+            }
+            let object_to_world = mat4_transform(&object.transform);
+            for (geometry, geometry_index) in model.geometries.iter().zip(entry.geometry_offset..) {
                 let material = &model.materials[geometry.material_index];
-                let vertex_offset =
-                    geometry.vertex_range.start as u64 * mem::size_of::<crate::Vertex>() as u64;
-                let geometry_to_world_rotation = {
-                    let m3_geo = mat3_transform(&geometry.transform);
-                    let m3_normal = (m3_object * m3_geo).inverse().transpose();
-                    let quat = glam::Quat::from_mat3(&m3_normal);
-                    let qv = glam::Vec4::from(quat) * 127.0;
-                    [qv.x as i8, qv.y as i8, qv.z as i8, qv.w as i8]
-                };
-
-                let hit_entry = HitEntry {
-                    index_buf: match geometry.index_type {
-                        Some(_) => self
-                            .index_buffers
-                            .alloc(model.index_buffer.at(geometry.index_offset)),
-                        None => !0,
-                    },
-                    vertex_buf: self
-                        .vertex_buffers
-                        .alloc(model.vertex_buffer.at(vertex_offset)),
-                    winding: model.winding,
-                    geometry_to_world_rotation,
-                    geometry_to_object: mint::ColumnMatrix4::from(mint::RowMatrix4 {
-                        x: geometry.transform.x,
-                        y: geometry.transform.y,
-                        z: geometry.transform.z,
-                        w: [0.0, 0.0, 0.0, 1.0].into(),
-                    }),
-                    prev_object_to_world: mat4_transform(&object.prev_transform).into(),
-                    base_color_texture: match material.base_color_texture {
-                        Some(handle) => *texture_indices.entry(handle).or_insert_with(|| {
-                            let texture = &asset_hub.textures[handle];
-                            self.textures.alloc(texture.view)
-                        }),
-                        None => dummy_white,
-                    },
-                    base_color_factor: {
-                        let c = material.base_color_factor;
-                        [
-                            (c[0] * 255.0) as u8,
-                            (c[1] * 255.0) as u8,
-                            (c[2] * 255.0) as u8,
-                            (c[3] * 255.0) as u8,
-                        ]
-                    },
-                    normal_texture: match material.normal_texture {
-                        Some(handle) => *texture_indices.entry(handle).or_insert_with(|| {
-                            let texture = &asset_hub.textures[handle];
-                            self.textures.alloc(texture.view)
-                        }),
-                        None => dummy_black,
-                    },
-                    normal_scale: material.normal_scale,
-                };
-
-                log::debug!("Entry[{geometry_index}] = {hit_entry:?}");
-                unsafe {
-                    ptr::write(
-                        (hit_staging.data() as *mut HitEntry).add(geometry_index),
-                        hit_entry,
-                    );
+                let geometry_to_world = object_to_world * mat4_transform(&geometry.transform);
+                for (primitive_index, positions) in geometry.emissive_triangles.iter().enumerate() {
+                    let [a, b, c] = positions.map(|p| geometry_to_world.transform_point3(p.into()));
+                    self.emissive_triangles.push(EmissiveTriangle {
+                        hit_entry: geometry_index,
+                        primitive_index: primitive_index as u32,
+                        area: 0.5 * (b - a).cross(c - a).length(),
+                        pdf: 0.0,
+                        radiance: overridden_emission(material, &object.material_overrides),
+                        cdf: 0.0,
+                        object_to_world: object_to_world.into(),
+                    });
                 }
-                geometry_index += 1;
             }
         }
 
-        self.texture_resource_lookup.clear();
-        for (handle, res_id) in texture_indices {
-            self.texture_resource_lookup.insert(res_id, handle);
+        let powers = self
+            .emissive_triangles
+            .iter()
+            .map(|tri| luminance(tri.radiance).max(0.0) * tri.area)
+            .collect::<Vec<_>>();
+        for (tri, (pdf, cdf)) in self
+            .emissive_triangles
+            .iter_mut()
+            .zip(compute_pdf_cdf(&powers))
+        {
+            tri.pdf = pdf;
+            tri.cdf = cdf;
         }
 
-        assert_eq!(geometry_index, geometry_count);
+        let size =
+            (self.emissive_triangles.len().max(1) * mem::size_of::<EmissiveTriangle>()) as u64;
+        temp.buffers.push(self.emissive_buffer);
+        self.emissive_buffer = gpu.create_buffer(blade_graphics::BufferDesc {
+            name: "emissive triangles",
+            size,
+            memory: blade_graphics::Memory::Device,
+        });
+        if self.emissive_triangles.is_empty() {
+            return;
+        }
         log::info!(
-            "Preparing ray tracing with {} geometries in total",
-            geometry_count
+            "Sampling {} emissive triangles",
+            self.emissive_triangles.len()
         );
+        let staging = gpu.create_buffer(blade_graphics::BufferDesc {
+            name: "emissive staging",
+            size,
+            memory: blade_graphics::Memory::Upload,
+        });
+        temp.buffers.push(staging);
+        unsafe {
+            ptr::copy_nonoverlapping(
+                self.emissive_triangles.as_ptr(),
+                staging.data() as *mut EmissiveTriangle,
+                self.emissive_triangles.len(),
+            );
+        }
+        let mut transfers = command_encoder.transfer("upload-emissive-triangles");
+        transfers.copy_buffer_to_buffer(staging.at(0), self.emissive_buffer.at(0), size);
+    }
+
+    fn build_top_level(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        gpu: &blade_graphics::Context,
+        temp: &mut FrameResources,
+    ) {
+        // The previous structure is kept around for temporal reuse
+        if self.prev_acceleration_structure != blade_graphics::AccelerationStructure::default() {
+            temp.acceleration_structures
+                .push(self.prev_acceleration_structure);
+        }
+        self.prev_acceleration_structure = self.acceleration_structure;
 
+        // Compact the instances that survive the culling
+        self.instance_masks.clear();
+        let mut instances = Vec::with_capacity(self.instances.len());
+        let mut tlas_objects = Vec::with_capacity(self.instances.len());
+        for (object_index, (instance, &radius)) in
+            self.instances.iter().zip(&self.instance_radii).enumerate()
+        {
+            let mask = self.culling.instance_mask(instance, radius);
+            self.instance_masks.push(mask);
+            if mask != 0 {
+                instances.push(blade_graphics::AccelerationStructureInstance { mask, ..*instance });
+                tlas_objects.push(object_index as u32);
+            }
+        }
+        self.culling_stats = CullingStats {
+            instances: self.instances.len() as u32,
+            kept: instances.len() as u32,
+            primary: instances
+                .iter()
+                .filter(|instance| instance.mask & culling::INSTANCE_MASK_PRIMARY != 0)
+                .count() as u32,
+        };
+        self.tlas_objects = tlas_objects.into();
+        self.is_culling_changed = false;
+        self.is_lod_changed = false;
+
+        let blases = &self.blases;
         // Needs to be a separate encoder in order to force synchronization
         let sizes = gpu.get_top_level_acceleration_structure_sizes(instances.len() as u32);
         self.acceleration_structure =
@@ -1018,7 +2755,7 @@ impl RayTracer {
                 ty: blade_graphics::AccelerationStructureType::TopLevel,
                 size: sizes.data,
             });
-        let instance_buf = gpu.create_acceleration_structure_instance_buffer(&instances, &blases);
+        let instance_buf = gpu.create_acceleration_structure_instance_buffer(&instances, blases);
         let scratch_buf = gpu.create_buffer(blade_graphics::BufferDesc {
             name: "TLAS scratch",
             size: sizes.scratch,
@@ -1028,7 +2765,7 @@ impl RayTracer {
         let mut tlas_encoder = command_encoder.acceleration_structure("TLAS");
         tlas_encoder.build_top_level(
             self.acceleration_structure,
-            &blases,
+            blases,
             instances.len() as u32,
             instance_buf.at(0),
             scratch_buf.at(0),
@@ -1039,30 +2776,66 @@ impl RayTracer {
         self.frame_scene_built = self.frame_index + 1;
     }
 
+    /// Check if the buffer behind a debug view is produced in the current configuration.
+    fn is_debug_view_available(&self, mode: DebugMode) -> bool {
+        match mode {
+            DebugMode::Final | DebugMode::Albedo | DebugMode::Motion => true,
+            DebugMode::Variance | DebugMode::AccumulationAge => self.is_temporally_accumulated,
+            DebugMode::AdaptiveSamples => self.has_tile_samples,
+            // the debug image is only written by the debug builds of the shaders
+            _ => cfg!(debug_assertions),
+        }
+    }
+
     fn make_debug_params(&self, config: &DebugConfig) -> DebugParams {
         DebugParams {
             view_mode: config.view_mode as u32,
             draw_flags: config.draw_flags.bits(),
             texture_flags: config.texture_flags.bits(),
             unused: 0,
-            mouse_pos: config.mouse_pos.unwrap_or([-1; 2]),
+            // the mouse is over the surface, which may be upscaled
+            mouse_pos: match config.mouse_pos {
+                Some([x, y]) => [
+                    x * self.render_size.width as i32 / self.surface_size.width.max(1) as i32,
+                    y * self.render_size.height as i32 / self.surface_size.height.max(1) as i32,
+                ],
+                None => [-1; 2],
+            },
         }
     }
 
-    fn make_camera_params(&self, camera: &super::Camera) -> CameraParams {
-        let fov_x = 2.0
-            * ((camera.fov_y * 0.5).tan() * self.surface_size.width as f32
-                / self.surface_size.height as f32)
-                .atan();
-        CameraParams {
-            position: camera.pos.into(),
-            depth: camera.depth,
-            orientation: camera.rot.into(),
-            fov: [fov_x, camera.fov_y],
-            target_size: [self.surface_size.width, self.surface_size.height],
+    /// Make the shader parameters of the ray configuration,
+    /// without the motion vectors and the adaptive sampling.
+    fn make_main_params(&self, ray_config: &RayConfig) -> MainParams {
+        MainParams {
+            frame_index: self.frame_index as u32,
+            num_environment_samples: ray_config.num_environment_samples,
+            environment_importance_sampling: ray_config.environment_importance_sampling as u32,
+            tap_count: ray_config.tap_count,
+            tap_radius: ray_config.tap_radius as f32,
+            tap_confidence_near: ray_config.tap_confidence_near as f32,
+            tap_confidence_far: ray_config.tap_confidence_far as f32,
+            t_start: ray_config.t_start,
+            use_pairwise_mis: ray_config.pairwise_mis as u32,
+            defensive_mis: ray_config.defensive_mis,
+            use_motion_vectors: 0,
+            num_light_samples: ray_config.num_light_samples,
+            light_count: self.lights.len() as u32,
+            num_emissive_samples: ray_config.num_emissive_samples,
+            emissive_count: self.emissive_triangles.len() as u32,
+            max_bounces: ray_config.max_bounces,
+            firefly_clamp: ray_config.firefly_clamp,
+            russian_roulette_start: ray_config.russian_roulette_start,
+            use_adaptive_sampling: 0,
+            environment_yaw: ray_config.environment.yaw,
+            environment_intensity: ray_config.environment.intensity,
         }
     }
 
+    fn make_camera_params(&self, camera: &super::Camera) -> CameraParams {
+        CameraParams::new(camera, self.render_size)
+    }
+
     fn work_indices(&self) -> (usize, usize) {
         let cur = self.frame_index & 1;
         let prev = if cur < self.frame_index { cur ^ 1 } else { cur };
@@ -1077,71 +2850,164 @@ impl RayTracer {
         camera: &crate::Camera,
         config: FrameConfig,
     ) {
-        let mut transfer = command_encoder.transfer("prepare");
+        self.debug.reset_shapes();
+        {
+            let mut transfer = command_encoder.transfer("prepare");
+            if config.debug_draw {
+                self.debug.reset_lines(&mut transfer);
+                self.debug.enable_draw(&mut transfer, true);
+            } else {
+                self.debug.enable_draw(&mut transfer, false);
+            }
 
-        if config.debug_draw {
-            self.debug.reset_lines(&mut transfer);
-            self.debug.enable_draw(&mut transfer, true);
-        } else {
-            self.debug.enable_draw(&mut transfer, false);
-        }
+            if config.reset_reservoirs || config.reset_variance {
+                self.debug.reset_variance(&mut transfer);
+            } else {
+                self.debug.update_variance(&mut transfer);
+            }
+            self.debug.update_entry(&mut transfer);
 
-        if config.reset_reservoirs || config.reset_variance {
-            self.debug.reset_variance(&mut transfer);
-        } else {
-            self.debug.update_variance(&mut transfer);
+            if config.reset_reservoirs {
+                if !config.debug_draw {
+                    self.debug.reset_lines(&mut transfer);
+                }
+                self.reset_reservoirs(&mut transfer);
+            }
         }
-        self.debug.update_entry(&mut transfer);
 
-        if config.reset_reservoirs {
-            if !config.debug_draw {
-                self.debug.reset_lines(&mut transfer);
-            }
-            let total_reservoirs = self.surface_size.width as u64 * self.surface_size.height as u64;
-            for reservoir_buf in self.targets.reservoir_buf.iter() {
-                transfer.fill_buffer(
-                    reservoir_buf.at(0),
-                    total_reservoirs * self.reservoir_size as u64,
-                    0,
-                );
-            }
+        let mut camera_params = self.make_camera_params(camera);
+        // The lens seed changes every frame, so it's not a camera movement.
+        let prev_camera_params = CameraParams {
+            lens_seed: 0,
+            ..self.targets.camera_params[self.frame_index % 2]
+        };
+        let is_camera_moved =
+            bytemuck::bytes_of(&camera_params) != bytemuck::bytes_of(&prev_camera_params);
+        if !config.accumulate || !self.is_accumulating || config.reset_reservoirs || is_camera_moved
+        {
+            self.accumulated_frames = 0;
+        }
+        self.is_accumulating = config.accumulate;
+        // Defocus can't be reprojected, so the history is invalid after a lens change.
+        if camera_params.lens_radius != prev_camera_params.lens_radius
+            || camera_params.focus_distance != prev_camera_params.focus_distance
+            || camera_params.aperture_blades != prev_camera_params.aperture_blades
+            || camera_params.aperture_rotation != prev_camera_params.aperture_rotation
+        {
+            self.is_history_reset = true;
         }
 
         if !config.frozen {
             self.frame_index += 1;
         }
         self.is_frozen = config.frozen;
-        self.targets.camera_params[self.frame_index % 2] = self.make_camera_params(camera);
+        self.pass_timings.update(command_encoder, self.frame_index);
+        if self.visible_layers != config.visible_layers {
+            self.visible_layers = config.visible_layers;
+            self.is_visibility_changed = true;
+        }
+        self.camera_position = camera.pos.into();
+        self.select_lods();
+        let culling = Culling::new(camera, self.render_size, &config.culling);
+        if culling != self.culling {
+            self.culling = culling;
+            self.is_culling_changed |= self
+                .instances
+                .iter()
+                .zip(&self.instance_radii)
+                .zip(&self.instance_masks)
+                .any(|((instance, &radius), &mask)| {
+                    culling.instance_mask(instance, radius) != mask
+                });
+        }
+        camera_params.lens_seed = self.frame_index as u32;
+        self.targets.camera_params[self.frame_index % 2] = camera_params;
         self.post_proc_input_index = self.frame_index % 2;
+        self.has_prev_history = self.is_temporally_accumulated;
+        self.is_temporally_accumulated = false;
+    }
+
+    /// Discard the accumulation and all the temporal history.
+    fn reset_history(&mut self, command_encoder: &mut blade_graphics::CommandEncoder) {
+        let mut transfer = command_encoder.transfer("reset-accumulation");
+        self.reset_reservoirs(&mut transfer);
+        self.is_history_reset = true;
+        self.accumulated_frames = 0;
+        self.has_tile_samples = false;
+        self.medium_frame_index = None;
+    }
+
+    fn reset_reservoirs(&self, transfer: &mut blade_graphics::TransferCommandEncoder) {
+        let total_reservoirs = self.render_size.width as u64 * self.render_size.height as u64;
+        for reservoir_buf in self.targets.reservoir_buf.iter() {
+            transfer.fill_buffer(
+                reservoir_buf.at(0),
+                total_reservoirs * self.reservoir_size as u64,
+                0,
+            );
+        }
     }
 
     /// Ray trace the scene.
     ///
     /// The result is stored internally in an HDR render target.
+    /// Changing the ray configuration resets the accumulated history.
     #[profiling::function]
     pub fn ray_trace(
-        &self,
+        &mut self,
         command_encoder: &mut blade_graphics::CommandEncoder,
         debug_config: DebugConfig,
         ray_config: RayConfig,
     ) {
+        if self.requested_ray_config != Some(ray_config) {
+            if self.requested_ray_config.is_some() {
+                log::debug!("Ray config changed, resetting the accumulation");
+                self.reset_history(command_encoder);
+            }
+            self.requested_ray_config = Some(ray_config);
+            self.active_ray_config = Some(ray_config.clamped());
+        }
+        let mut ray_config = self.active_ray_config.unwrap();
+        if self.is_accumulating {
+            // every accumulated frame has to be an independent estimate
+            ray_config.tap_count = 0;
+        }
+
+        // the tile sample counts are only valid for the frame following their estimation
+        let use_adaptive_sampling = self.has_tile_samples && ray_config.max_samples > 1;
+        self.has_tile_samples = false;
+
         let debug = self.make_debug_params(&debug_config);
         let (cur, prev) = self.work_indices();
         assert_eq!(cur, self.post_proc_input_index);
 
+        let medium = ray_config.medium;
+        // The accumulated frames have to be independent, and the reset history can't be reused
+        let use_medium_history = !self.is_accumulating
+            && !self.is_history_reset
+            && self
+                .medium_frame_index
+                .is_some_and(|index| index + 1 == self.frame_index);
+        self.medium_frame_index = Some(self.frame_index);
+        let (probes, t_probes) = self.make_probe_params();
+
         if let mut pass = command_encoder.compute("fill-gbuf") {
             let mut pc = pass.with(&self.fill_pipeline);
-            let groups = self.fill_pipeline.get_dispatch_for(self.surface_size);
+            let groups = self.fill_pipeline.get_dispatch_for(self.render_size);
             pc.bind(
                 0,
                 &FillData {
                     camera: self.targets.camera_params[cur],
                     prev_camera: self.targets.camera_params[prev],
                     debug,
+                    parameters: FillParams {
+                        use_motion_vectors: (self.frame_scene_built >= self.frame_index) as u32,
+                    },
                     acc_struct: self.acceleration_structure,
                     hit_entries: self.hit_buffer.into(),
                     index_buffers: &self.index_buffers,
                     vertex_buffers: &self.vertex_buffers,
+                    tex_coord_buffers: &self.tex_coord_buffers,
                     textures: &self.textures,
                     sampler_linear: self.samplers.linear,
                     debug_buf: self.debug.buffer_resource(),
@@ -1149,7 +3015,9 @@ impl RayTracer {
                     out_basis: self.targets.basis.views[cur],
                     out_flat_normal: self.targets.flat_normal.views[cur],
                     out_albedo: self.targets.albedo.views[0],
+                    out_hit_entry: self.targets.hit_entry.views[cur],
                     out_motion: self.targets.motion.views[0],
+                    out_emission: self.targets.emission.views[0],
                     out_debug: self.targets.debug.views[0],
                 },
             );
@@ -1158,7 +3026,7 @@ impl RayTracer {
 
         if let mut pass = command_encoder.compute("ray-trace") {
             let mut pc = pass.with(&self.main_pipeline);
-            let groups = self.main_pipeline.get_dispatch_for(self.surface_size);
+            let groups = self.main_pipeline.get_dispatch_for(self.render_size);
             pc.bind(
                 0,
                 &MainData {
@@ -1166,19 +3034,26 @@ impl RayTracer {
                     prev_camera: self.targets.camera_params[prev],
                     debug,
                     parameters: MainParams {
-                        frame_index: self.frame_index as u32,
-                        num_environment_samples: ray_config.num_environment_samples,
-                        environment_importance_sampling: ray_config.environment_importance_sampling
-                            as u32,
-                        tap_count: ray_config.tap_count,
-                        tap_radius: ray_config.tap_radius as f32,
-                        tap_confidence_near: ray_config.tap_confidence_near as f32,
-                        tap_confidence_far: ray_config.tap_confidence_far as f32,
-                        t_start: ray_config.t_start,
-                        use_pairwise_mis: ray_config.pairwise_mis as u32,
-                        defensive_mis: ray_config.defensive_mis,
                         use_motion_vectors: (self.frame_scene_built >= self.frame_index) as u32,
+                        use_adaptive_sampling: use_adaptive_sampling as u32,
+                        ..self.make_main_params(&ray_config)
+                    },
+                    medium: MediumParams {
+                        color: medium.color,
+                        anisotropy: medium.anisotropy,
+                        absorption: medium.absorption,
+                        scattering: medium.scattering,
+                        base_height: medium.base_height,
+                        height_falloff: medium.height_falloff,
+                        step_count: if medium.is_enabled() {
+                            medium.step_count
+                        } else {
+                            0
+                        },
+                        use_history: use_medium_history as u32,
+                        pad: [0; 2],
                     },
+                    probes,
                     acc_struct: self.acceleration_structure,
                     prev_acc_struct: if self.frame_scene_built < self.frame_index
                         || self.prev_acceleration_structure
@@ -1192,39 +3067,128 @@ impl RayTracer {
                     sampler_nearest: self.samplers.nearest,
                     env_map: self.env_map.main_view,
                     env_weights: self.env_map.weight_view,
+                    lights: self.light_buffer.into(),
+                    emissive_triangles: self.emissive_buffer.into(),
+                    hit_entries: self.hit_buffer.into(),
+                    index_buffers: &self.index_buffers,
+                    vertex_buffers: &self.vertex_buffers,
+                    tex_coord_buffers: &self.tex_coord_buffers,
+                    textures: &self.textures,
                     t_depth: self.targets.depth.views[cur],
                     t_prev_depth: self.targets.depth.views[prev],
                     t_basis: self.targets.basis.views[cur],
                     t_prev_basis: self.targets.basis.views[prev],
                     t_flat_normal: self.targets.flat_normal.views[cur],
                     t_prev_flat_normal: self.targets.flat_normal.views[prev],
+                    t_albedo: self.targets.albedo.views[0],
+                    t_hit_entry: self.targets.hit_entry.views[cur],
+                    t_prev_hit_entry: self.targets.hit_entry.views[prev],
                     t_motion: self.targets.motion.views[0],
+                    t_tile_samples: self.targets.tile_samples.views[0],
                     debug_buf: self.debug.buffer_resource(),
                     reservoirs: self.targets.reservoir_buf[cur].into(),
                     prev_reservoirs: self.targets.reservoir_buf[prev].into(),
+                    t_prev_medium: self.targets.medium.views[prev],
+                    t_probes,
                     out_diffuse: self.targets.light_diffuse.views[cur],
+                    out_medium: self.targets.medium.views[cur],
                     out_debug: self.targets.debug.views[0],
                 },
             );
             pc.dispatch(groups);
         }
+
+        if self.is_accumulating {
+            let mut pass = command_encoder.compute("accumulate");
+            let mut pc = pass.with(&self.accumulate_pipeline);
+            let groups = self.accumulate_pipeline.get_dispatch_for(self.render_size);
+            pc.bind(
+                0,
+                &AccumulateData {
+                    params: AccumulateParams {
+                        frame_count: self.accumulated_frames,
+                    },
+                    t_albedo: self.targets.albedo.views[0],
+                    light_diffuse: self.targets.light_diffuse.views[cur],
+                    t_emission: self.targets.emission.views[0],
+                    t_medium: self.targets.medium.views[cur],
+                    output: self.targets.accumulation.views[0],
+                },
+            );
+            pc.dispatch(groups);
+            self.accumulated_frames += 1;
+        }
+    }
+
+    /// Number of frames in the progressive accumulation.
+    ///
+    /// Zero unless `FrameConfig::accumulate` is enabled.
+    pub fn accumulated_frames(&self) -> u32 {
+        self.accumulated_frames
+    }
+
+    /// Instance counts of the last TLAS build, see `FrameConfig::culling`.
+    pub fn culling_stats(&self) -> CullingStats {
+        self.culling_stats
+    }
+
+    /// Record a read-back of the accumulated HDR image.
+    ///
+    /// The image is the average of the linear radiance over the accumulated
+    /// frames, before the tone mapping.
+    pub fn read_back_hdr(
+        &self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        gpu: &blade_graphics::Context,
+    ) -> HdrReadback {
+        if self.accumulated_frames == 0 {
+            log::warn!("Reading back HDR without any accumulated frames");
+        }
+        let bytes_per_row = self.render_size.width * 16;
+        let buffer = gpu.create_buffer(blade_graphics::BufferDesc {
+            name: "hdr read-back",
+            size: bytes_per_row as u64 * self.render_size.height as u64,
+            memory: blade_graphics::Memory::Shared,
+        });
+        let mut transfer = command_encoder.transfer("read-back-hdr");
+        transfer.copy_texture_to_buffer(
+            self.targets.accumulation.texture.into(),
+            buffer.into(),
+            bytes_per_row,
+            self.render_size,
+        );
+        HdrReadback {
+            buffer,
+            size: self.render_size,
+        }
     }
 
     /// Perform noise reduction using SVGF.
+    ///
+    /// Does nothing if the denoiser is disabled. The temporal history
+    /// starts over if it wasn't produced by the previous frame.
     #[profiling::function]
     pub fn denoise(
         &mut self, //TODO: borrow immutably
         command_encoder: &mut blade_graphics::CommandEncoder,
         denoiser_config: DenoiserConfig,
     ) {
+        if !denoiser_config.enabled {
+            return;
+        }
         let mut params = BlurParams {
-            extent: [self.surface_size.width, self.surface_size.height],
+            extent: [self.render_size.width, self.render_size.height],
             temporal_weight: denoiser_config.temporal_weight,
             iteration: 0,
             use_motion_vectors: (self.frame_scene_built >= self.frame_index) as u32,
-            pad: 0,
+            history_clamp_sigma: denoiser_config.history_clamp_sigma.max(0.0),
         };
         let (cur, prev) = self.work_indices();
+        if self.is_history_reset || !self.has_prev_history {
+            // ignore the previous frames, starting the history over
+            params.temporal_weight = 1.0;
+            self.is_history_reset = false;
+        }
 
         if denoiser_config.temporal_weight < 1.0 {
             let mut pass = command_encoder.compute("temporal-accum");
@@ -1232,7 +3196,7 @@ impl RayTracer {
             let groups = self
                 .blur
                 .a_trous_pipeline
-                .get_dispatch_for(self.surface_size);
+                .get_dispatch_for(self.render_size);
             pc.bind(
                 0,
                 &TemporalAccumData {
@@ -1245,21 +3209,52 @@ impl RayTracer {
                     t_flat_normal: self.targets.flat_normal.views[cur],
                     t_prev_flat_normal: self.targets.flat_normal.views[prev],
                     t_motion: self.targets.motion.views[0],
+                    t_prev_history: self.targets.history.views[prev],
                     output: self.targets.light_diffuse.views[cur],
+                    out_history: self.targets.history.views[cur],
                 },
             );
             pc.dispatch(groups);
+            self.is_temporally_accumulated = true;
+        }
+
+        let adaptive_config = self.active_ray_config.filter(|rc| rc.max_samples > 1);
+        if let Some(ray_config) = adaptive_config
+            && self.is_temporally_accumulated
+        {
+            // the moments are estimated by the temporal filter
+            let mut pass = command_encoder.compute("tile-priority");
+            let mut pc = pass.with(&self.blur.tile_priority_pipeline);
+            let groups = self
+                .blur
+                .tile_priority_pipeline
+                .get_dispatch_for(self.render_size);
+            pc.bind(
+                0,
+                &TilePriorityData {
+                    params,
+                    tile_params: TilePriorityParams {
+                        variance_threshold: ray_config.variance_threshold,
+                        max_samples: ray_config.max_samples,
+                        pad: [0; 2],
+                    },
+                    input: self.targets.light_diffuse.views[cur],
+                    out_tile_samples: self.targets.tile_samples.views[0],
+                },
+            );
+            pc.dispatch(groups);
+            self.has_tile_samples = true;
         }
 
         assert_eq!(cur, self.post_proc_input_index);
         let mut ping_pong = [2, if self.is_frozen { cur } else { prev }];
-        for _ in 0..denoiser_config.num_passes {
+        for _ in 0..denoiser_config.spatial_passes {
             let mut pass = command_encoder.compute("a-trous");
             let mut pc = pass.with(&self.blur.a_trous_pipeline);
             let groups = self
                 .blur
                 .a_trous_pipeline
-                .get_dispatch_for(self.surface_size);
+                .get_dispatch_for(self.render_size);
             pc.bind(
                 0,
                 &ATrousData {
@@ -1267,6 +3262,7 @@ impl RayTracer {
                     input: self.targets.light_diffuse.views[self.post_proc_input_index],
                     t_depth: self.targets.depth.views[cur],
                     t_flat_normal: self.targets.flat_normal.views[cur],
+                    t_basis: self.targets.basis.views[cur],
                     output: self.targets.light_diffuse.views[ping_pong[0]],
                 },
             );
@@ -1277,6 +3273,19 @@ impl RayTracer {
         }
     }
 
+    /// Return the batch of debug shapes, to be drawn by `post_proc` of this frame.
+    pub fn debug_draw(&mut self) -> &mut DebugDraw {
+        &mut self.debug_draw
+    }
+
+    /// Upload the debug shapes collected for this frame, and start a new batch.
+    ///
+    /// Has to be called after `prepare`, and before `post_proc`.
+    /// Does nothing if there are no shapes.
+    pub fn flush_debug_draw(&mut self, gpu: &blade_graphics::Context, temp: &mut FrameResources) {
+        self.debug.upload_shapes(&mut self.debug_draw, gpu, temp);
+    }
+
     /// Blit the rendering result into a specified render pass.
     #[profiling::function]
     pub fn post_proc(
@@ -1288,6 +3297,10 @@ impl RayTracer {
         debug_blits: &[DebugBlit],
     ) {
         let cur = self.frame_index % 2;
+        let bloom = self
+            .bloom
+            .output(self.frame_index)
+            .filter(|_| pp_config.bloom.enabled);
         if let mut pc = pass.with(&self.post_proc_pipeline) {
             let debug_params = self.make_debug_params(&debug_config);
             pc.bind(
@@ -1295,14 +3308,38 @@ impl RayTracer {
                 &PostProcData {
                     t_albedo: self.targets.albedo.views[0],
                     light_diffuse: self.targets.light_diffuse.views[self.post_proc_input_index],
+                    t_emission: self.targets.emission.views[0],
+                    t_medium: self.targets.medium.views[cur],
                     t_debug: self.targets.debug.views[0],
+                    t_history: self.targets.history.views[cur],
+                    t_accumulation: self.targets.accumulation.views[0],
+                    t_tile_samples: self.targets.tile_samples.views[0],
+                    t_motion: self.targets.motion.views[0],
                     tone_map_params: ToneMapParams {
-                        enabled: 1,
-                        average_lum: pp_config.average_luminocity,
-                        key_value: pp_config.exposure_key_value,
-                        white_level: pp_config.white_level,
+                        mode: pp_config.tone_map as u32,
+                        exposure: pp_config.exposure_ev.exp2(),
+                        white_point: pp_config.white_point.max(1e-3),
+                        encode_srgb: pp_config.encode_srgb as u32,
+                        auto_exposure: pp_config.auto_exposure as u32,
+                        pad: [0; 3],
                     },
                     debug_params,
+                    post_proc_params: PostProcParams {
+                        is_view_available: self.is_debug_view_available(debug_config.view_mode)
+                            as u32,
+                        use_accumulation: (self.accumulated_frames != 0) as u32,
+                        max_samples: self.active_ray_config.map_or(1, |rc| rc.max_samples),
+                        bloom_intensity: bloom.map_or(0.0, |_| pp_config.bloom.intensity),
+                        input_scale: [
+                            self.render_size.width as f32 / self.surface_size.width.max(1) as f32,
+                            self.render_size.height as f32 / self.surface_size.height.max(1) as f32,
+                        ],
+                        sharpness: pp_config.upscale_sharpness.clamp(0.0, 1.0),
+                        is_upscaled: (self.render_size != self.surface_size) as u32,
+                    },
+                    exposure_state: self.exposure.state_buffer().into(),
+                    t_bloom: bloom.unwrap_or(self.dummy.white_view),
+                    bloom_sampler: self.bloom.sampler(),
                 },
             );
             pc.draw(0, 3, 0, 1);
@@ -1343,11 +3380,11 @@ impl RayTracer {
             base_color_texture: self
                 .texture_resource_lookup
                 .get(&db_e.base_color_texture)
-                .cloned(),
+                .map(|&(handle, _)| handle),
             normal_texture: self
                 .texture_resource_lookup
                 .get(&db_e.normal_texture)
-                .cloned(),
+                .map(|&(handle, _)| handle),
         }
     }
 }
@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+// Keep the slots apart enough to satisfy any storage buffer offset alignment.
+const SLOT_SIZE: usize = 256;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct PickParams {
+    pixel: [i32; 2],
+    pad: [u32; 2],
+}
+
+// Has to match the shader!
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct RawPickResult {
+    hit: u32,
+    instance_index: u32,
+    geometry_index: u32,
+    primitive_index: u32,
+    distance: f32,
+    pad: u32,
+    barycentrics: [f32; 2],
+}
+
+#[derive(blade_macros::ShaderData)]
+struct PickData {
+    camera: super::CameraParams,
+    params: PickParams,
+    acc_struct: blade_graphics::AccelerationStructure,
+    result: blade_graphics::BufferPiece,
+}
+
+/// Reference to a pick request recorded into a command encoder.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PickToken {
+    slot: usize,
+    epoch: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PickResult {
+    /// Index of the object, as passed into `RayTracer::build_scene`.
+    /// Use `Scene::handle_at` to find the handle of a retained object.
+    pub object: usize,
+    /// Index of the geometry within the object's model.
+    pub geometry: u32,
+    /// Index of the triangle within the geometry.
+    pub triangle: u32,
+    /// Barycentric coordinates of the hit point on the triangle.
+    pub barycentrics: mint::Vector2<f32>,
+    /// Distance from the camera along the ray.
+    pub distance: f32,
+}
+
+/// Object picker that traces single rays against the scene
+/// of a `RayTracer` and reads the hits back on the host.
+///
+/// Multiple picks can be in flight at the same time, up to the
+/// capacity given at creation. Older picks get overwritten beyond that.
+pub struct Picker {
+    pipeline: blade_graphics::ComputePipeline,
+    buffer: blade_graphics::Buffer,
+    epochs: Box<[u64]>,
+    /// Objects behind the TLAS instances at the time of every pick.
+    instance_objects: Box<[Arc<[u32]>]>,
+    next_epoch: u64,
+}
+
+impl Picker {
+    pub fn new(
+        shaders: &super::Shaders,
+        shader_man: &blade_asset::AssetManager<crate::shader::Baker>,
+        capacity: usize,
+        gpu: &blade_graphics::Context,
+    ) -> Self {
+        assert!(
+            gpu.capabilities()
+                .ray_query
+                .contains(blade_graphics::ShaderVisibility::COMPUTE)
+        );
+        assert_ne!(capacity, 0);
+        let shader = shader_man[shaders.pick].raw.as_ref().unwrap();
+        shader.check_struct_size::<PickParams>();
+        shader.check_struct_size::<RawPickResult>();
+        let layout = <PickData as blade_graphics::ShaderData>::layout();
+        let pipeline = gpu.create_compute_pipeline(blade_graphics::ComputePipelineDesc {
+            name: "pick",
+            data_layouts: &[&layout],
+            compute: shader.at("main"),
+        });
+        let buffer = gpu.create_buffer(blade_graphics::BufferDesc {
+            name: "pick results",
+            size: (capacity * SLOT_SIZE) as u64,
+            memory: blade_graphics::Memory::Shared,
+        });
+        Self {
+            pipeline,
+            buffer,
+            epochs: vec![0; capacity].into_boxed_slice(),
+            instance_objects: vec![Arc::from([]); capacity].into_boxed_slice(),
+            next_epoch: 0,
+        }
+    }
+
+    pub fn destroy(&mut self, gpu: &blade_graphics::Context) {
+        gpu.destroy_compute_pipeline(&mut self.pipeline);
+        gpu.destroy_buffer(self.buffer);
+    }
+
+    /// Record a pick of the scene under the given pixel of the render target.
+    ///
+    /// The scene has to be built by `RayTracer::build_scene` beforehand.
+    pub fn pick(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        ray_tracer: &super::RayTracer,
+        camera: &crate::Camera,
+        pixel: [i32; 2],
+    ) -> PickToken {
+        self.next_epoch += 1;
+        let slot = (self.next_epoch % self.epochs.len() as u64) as usize;
+        self.epochs[slot] = self.next_epoch;
+        // The culled objects are left out of the TLAS, shifting the instance indices
+        self.instance_objects[slot] = Arc::clone(&ray_tracer.tlas_objects);
+
+        let mut pass = command_encoder.compute("pick");
+        let mut pc = pass.with(&self.pipeline);
+        pc.bind(
+            0,
+            &PickData {
+                camera: ray_tracer.make_camera_params(camera),
+                params: PickParams { pixel, pad: [0; 2] },
+                acc_struct: ray_tracer.acceleration_structure,
+                result: self.buffer.at((slot * SLOT_SIZE) as u64),
+            },
+        );
+        pc.dispatch([1; 3]);
+
+        PickToken {
+            slot,
+            epoch: self.next_epoch,
+        }
+    }
+
+    /// Record a depth probe at the center of the render target,
+    /// for focusing the camera lens.
+    ///
+    /// The `distance` of the result is measured along the central ray,
+    /// so it can be used as `Lens::focus_distance` directly.
+    pub fn probe_focus(
+        &mut self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        ray_tracer: &super::RayTracer,
+        camera: &crate::Camera,
+    ) -> PickToken {
+        let size = ray_tracer.get_render_size();
+        let center = [size.width as i32 / 2, size.height as i32 / 2];
+        self.pick(command_encoder, ray_tracer, camera, center)
+    }
+
+    /// Read the result of a pick.
+    ///
+    /// Has to be called after the sync point of the submission in which
+    /// the pick was recorded is reached. Returns `None` if nothing was hit,
+    /// or if the pick has been overwritten by newer ones.
+    pub fn resolve(&self, token: PickToken) -> Option<PickResult> {
+        if self.epochs[token.slot] != token.epoch {
+            log::warn!("Pick {token:?} has been overwritten by a newer one");
+            return None;
+        }
+        let raw = unsafe {
+            self.buffer
+                .data()
+                .add(token.slot * SLOT_SIZE)
+                .cast::<RawPickResult>()
+                .read()
+        };
+        if raw.hit == 0 {
+            return None;
+        }
+        Some(PickResult {
+            object: self.instance_objects[token.slot][raw.instance_index as usize] as usize,
+            geometry: raw.geometry_index,
+            triangle: raw.primitive_index,
+            barycentrics: raw.barycentrics.into(),
+            distance: raw.distance,
+        })
+    }
+}
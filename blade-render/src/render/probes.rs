@@ -0,0 +1,190 @@
+use super::MAX_RESOURCES;
+use crate::irradiance::{BakedIrradiance, IrradianceVolume, PROBE_COEFFICIENTS, ProbeGrid};
+use std::ptr;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default, bytemuck::Zeroable, bytemuck::Pod)]
+pub(super) struct ProbeParams {
+    origin: [f32; 3],
+    is_enabled: u32,
+    spacing: [f32; 3],
+    rays_per_probe: u32,
+    counts: [u32; 3],
+    pad: u32,
+}
+
+impl ProbeParams {
+    fn new(grid: &ProbeGrid, rays_per_probe: u32) -> Self {
+        Self {
+            origin: grid.origin.into(),
+            is_enabled: 1,
+            spacing: grid.spacing.into(),
+            rays_per_probe,
+            counts: grid.counts,
+            pad: 0,
+        }
+    }
+}
+
+#[derive(blade_macros::ShaderData)]
+struct ProbeBakeData<'a> {
+    camera: crate::CameraParams,
+    parameters: super::MainParams,
+    probes: ProbeParams,
+    acc_struct: blade_graphics::AccelerationStructure,
+    sampler_linear: blade_graphics::Sampler,
+    env_map: blade_graphics::TextureView,
+    env_weights: blade_graphics::TextureView,
+    lights: blade_graphics::BufferPiece,
+    emissive_triangles: blade_graphics::BufferPiece,
+    hit_entries: blade_graphics::BufferPiece,
+    index_buffers: &'a blade_graphics::BufferArray<MAX_RESOURCES>,
+    vertex_buffers: &'a blade_graphics::BufferArray<MAX_RESOURCES>,
+    tex_coord_buffers: &'a blade_graphics::BufferArray<MAX_RESOURCES>,
+    textures: &'a blade_graphics::TextureArray<MAX_RESOURCES>,
+    debug_buf: blade_graphics::BufferPiece,
+    out_probes: blade_graphics::BufferPiece,
+}
+
+/// Irradiance volume attached to the ray tracer.
+pub(super) struct ActiveVolume {
+    grid: ProbeGrid,
+    view: blade_graphics::TextureView,
+}
+
+/// Pending bake of an irradiance volume.
+pub struct IrradianceBake {
+    buffer: blade_graphics::Buffer,
+    grid: ProbeGrid,
+}
+
+impl IrradianceBake {
+    pub fn grid(&self) -> &ProbeGrid {
+        &self.grid
+    }
+
+    /// Read the baked coefficients.
+    ///
+    /// Has to be called after the sync point of the submission
+    /// in which the bake was recorded is reached.
+    pub fn into_baked(self, gpu: &blade_graphics::Context) -> BakedIrradiance {
+        let count = self.grid.probe_count() as usize * PROBE_COEFFICIENTS;
+        let mut coefficients = vec![[0f32; 4]; count];
+        unsafe {
+            ptr::copy_nonoverlapping(
+                self.buffer.data() as *const [f32; 4],
+                coefficients.as_mut_ptr(),
+                count,
+            );
+        }
+        gpu.destroy_buffer(self.buffer);
+        BakedIrradiance {
+            grid: self.grid,
+            coefficients,
+        }
+    }
+}
+
+impl super::ShaderPipelines {
+    pub(super) fn create_probe_bake(
+        shader: &blade_graphics::Shader,
+        gpu: &blade_graphics::Context,
+    ) -> blade_graphics::ComputePipeline {
+        shader.check_struct_size::<ProbeParams>();
+        let layout = <ProbeBakeData as blade_graphics::ShaderData>::layout();
+        gpu.create_compute_pipeline(blade_graphics::ComputePipelineDesc {
+            name: "bake-probes",
+            data_layouts: &[&layout],
+            compute: shader.at("bake_probes"),
+        })
+    }
+}
+
+impl super::RayTracer {
+    pub(super) fn make_probe_params(&self) -> (ProbeParams, blade_graphics::TextureView) {
+        match self.irradiance_volume {
+            Some(ref volume) => (ProbeParams::new(&volume.grid, 0), volume.view),
+            None => (ProbeParams::default(), self.dummy.black_volume_view),
+        }
+    }
+
+    /// Record a bake of the irradiance volume for the current scene.
+    ///
+    /// Every probe traces `rays_per_probe` rays, gathering the environment
+    /// and the direct lighting of the surfaces they hit. The rays reach
+    /// as far as the depth of the last prepared camera.
+    /// The volume in use by the ray tracer doesn't affect the bake.
+    #[profiling::function]
+    pub fn bake_irradiance(
+        &self,
+        command_encoder: &mut blade_graphics::CommandEncoder,
+        grid: &ProbeGrid,
+        rays_per_probe: u32,
+        ray_config: super::RayConfig,
+        gpu: &blade_graphics::Context,
+    ) -> IrradianceBake {
+        let probe_count = grid.probe_count();
+        assert_ne!(probe_count, 0, "Empty probe grid");
+        let buffer = gpu.create_buffer(blade_graphics::BufferDesc {
+            name: "irradiance bake",
+            size: (probe_count as usize * PROBE_COEFFICIENTS * 16) as u64,
+            memory: blade_graphics::Memory::Shared,
+        });
+        let camera = crate::CameraParams {
+            depth: self.targets.camera_params[self.frame_index % 2].depth,
+            ..Default::default()
+        };
+
+        let mut pass = command_encoder.compute("bake-probes");
+        let mut pc = pass.with(&self.probe_bake_pipeline);
+        let groups = self
+            .probe_bake_pipeline
+            .get_dispatch_for(blade_graphics::Extent {
+                width: probe_count,
+                height: 1,
+                depth: 1,
+            });
+        pc.bind(
+            0,
+            &ProbeBakeData {
+                camera,
+                parameters: self.make_main_params(&ray_config.clamped()),
+                probes: ProbeParams::new(grid, rays_per_probe),
+                acc_struct: self.acceleration_structure,
+                sampler_linear: self.samplers.linear,
+                env_map: self.env_map.main_view,
+                env_weights: self.env_map.weight_view,
+                lights: self.light_buffer.into(),
+                emissive_triangles: self.emissive_buffer.into(),
+                hit_entries: self.hit_buffer.into(),
+                index_buffers: &self.index_buffers,
+                vertex_buffers: &self.vertex_buffers,
+                tex_coord_buffers: &self.tex_coord_buffers,
+                textures: &self.textures,
+                debug_buf: self.debug.buffer_resource(),
+                out_probes: buffer.into(),
+            },
+        );
+        pc.dispatch(groups);
+
+        IrradianceBake {
+            buffer,
+            grid: *grid,
+        }
+    }
+
+    /// Use an irradiance volume for the indirect diffuse lighting.
+    ///
+    /// With a volume, only the first bounce is path traced, and the rest
+    /// of the indirect lighting comes from the probes. Without any bounces
+    /// configured, the volume provides all of the indirect diffuse lighting.
+    /// The volume has to stay alive for as long as it's in use.
+    pub fn set_irradiance_volume(&mut self, volume: Option<&IrradianceVolume>) {
+        self.irradiance_volume = volume.map(|volume| ActiveVolume {
+            grid: volume.grid,
+            view: volume.texture.view,
+        });
+        self.is_history_reset = true;
+        self.accumulated_frames = 0;
+    }
+}
@@ -0,0 +1,88 @@
+use std::{collections::VecDeque, time::Duration};
+
+/// Passes recorded by the renderer, which are reported by `RayTracer::timings`.
+const PASS_NAMES: &[&str] = &[
+    "skin",
+    "skinned BLAS",
+    "copy-prev-skin",
+    "upload-hit-entries",
+    "upload-lights",
+    "upload-emissive-triangles",
+    "TLAS",
+    "prepare",
+    "reset-accumulation",
+    "fill-gbuf",
+    "ray-trace",
+    "accumulate",
+    "read-back-hdr",
+    "temporal-accum",
+    "tile-priority",
+    "a-trous",
+    "exposure-histogram",
+    "exposure-adapt",
+    "bloom-downsample-first",
+    "bloom-downsample",
+    "bloom-upsample",
+    "pick",
+    "bake-probes",
+];
+/// Recent frames waiting for the resolved timings. The encoders resolve
+/// them within a few recordings, so the older ones are never matched.
+const MAX_PENDING_FRAMES: usize = 8;
+
+#[derive(Default)]
+pub(super) struct PassTimings {
+    /// Recording index of the encoder and the frame index of the renderer.
+    pending: VecDeque<(u64, usize)>,
+    timings: Vec<(&'static str, Duration)>,
+    frame_index: Option<usize>,
+}
+
+impl PassTimings {
+    /// Pick up the timings resolved by the encoder, and remember the frame
+    /// that is being recorded, until its own timings are resolved.
+    pub(super) fn update(&mut self, encoder: &blade_graphics::CommandEncoder, frame_index: usize) {
+        if let Some(recording) = encoder.timings_recording_index()
+            && let Some(pos) = self.pending.iter().position(|&(r, _)| r == recording)
+        {
+            self.frame_index = Some(self.pending[pos].1);
+            self.pending.drain(..=pos);
+            self.timings.clear();
+            for &(ref name, duration) in encoder.timings() {
+                if let Some(&known) = PASS_NAMES.iter().find(|&&known| known == name) {
+                    self.timings.push((known, duration));
+                }
+            }
+        }
+        let recording = encoder.recording_index();
+        match self.pending.back_mut() {
+            // `prepare` can be called more than once in a recording
+            Some(&mut (r, ref mut index)) if r == recording => *index = frame_index,
+            _ => {
+                if self.pending.len() == MAX_PENDING_FRAMES {
+                    self.pending.pop_front();
+                }
+                self.pending.push_back((recording, frame_index));
+            }
+        }
+    }
+}
+
+impl super::RayTracer {
+    /// GPU time of the renderer passes in the most recent frame with
+    /// the resolved timings, in the recording order. A pass can repeat.
+    ///
+    /// Requires `ContextDesc::timing`, and is empty otherwise.
+    /// Frames are told apart by `prepare`, and the timings lag a few frames behind.
+    /// `post_proc` is recorded into the render pass of the caller,
+    /// so it's timed as a part of that pass.
+    pub fn timings(&self) -> &[(&'static str, Duration)] {
+        &self.pass_timings.timings
+    }
+
+    /// Frame index of `timings`, as counted by `prepare`.
+    /// Frozen frames share the index of the previous one.
+    pub fn timings_frame_index(&self) -> Option<usize> {
+        self.pass_timings.frame_index
+    }
+}
@@ -0,0 +1,302 @@
+/// Handle of an object within a `Scene`.
+///
+/// Slots of removed objects get reused by the new ones, with a different
+/// generation, so the handles of the removed objects stay invalid.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ObjectHandle {
+    index: u32,
+    generation: u32,
+}
+
+/// Handle of a light within a `Scene`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LightHandle {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Default)]
+struct ObjectSlot {
+    generation: u32,
+    /// Packed index of the object, if it's alive.
+    index: Option<usize>,
+}
+
+#[derive(Default)]
+struct LightSlot {
+    generation: u32,
+    light: Option<crate::Light>,
+}
+
+/// Retained set of objects to render.
+///
+/// Keeps the objects packed, so that they can be passed directly
+/// to `RayTracer::build_scene`, and tracks the changes in order
+/// for `RayTracer::update_scene` to only do the necessary work.
+///
+/// The methods taking a handle return `None` if it's stale.
+#[derive(Default)]
+pub struct Scene {
+    objects: Vec<crate::Object>,
+    /// Handle of every packed object.
+    handles: Vec<ObjectHandle>,
+    slots: Vec<ObjectSlot>,
+    free_slots: Vec<u32>,
+    lights: Vec<LightSlot>,
+    free_lights: Vec<u32>,
+    /// Objects were added or removed since the last update.
+    pub(crate) structure_changed: bool,
+    /// Objects were moved since the last update.
+    pub(crate) transforms_changed: bool,
+    /// Joints of skinned objects were moved since the last update.
+    pub(crate) joints_changed: bool,
+    /// Lights were added, removed, or modified since the last update.
+    pub(crate) lights_changed: bool,
+    /// Visibility layers of objects were changed since the last update.
+    pub(crate) layers_changed: bool,
+    /// Vertices of procedural meshes were changed since the last update.
+    pub(crate) geometry_changed: bool,
+    /// Material overrides of objects were changed since the last update.
+    pub(crate) overrides_changed: bool,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn index_of(&self, handle: ObjectHandle) -> Option<usize> {
+        let slot = self.slots.get(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.index
+    }
+
+    pub fn add_object(&mut self, object: crate::Object) -> ObjectHandle {
+        let slot_index = match self.free_slots.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(ObjectSlot::default());
+                self.slots.len() as u32 - 1
+            }
+        };
+        let slot = &mut self.slots[slot_index as usize];
+        slot.index = Some(self.objects.len());
+        let handle = ObjectHandle {
+            index: slot_index,
+            generation: slot.generation,
+        };
+        self.objects.push(object);
+        self.handles.push(handle);
+        self.structure_changed = true;
+        handle
+    }
+
+    pub fn remove_object(&mut self, handle: ObjectHandle) -> Option<crate::Object> {
+        let index = self.index_of(handle)?;
+        let slot = &mut self.slots[handle.index as usize];
+        slot.index = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(handle.index);
+
+        let object = self.objects.swap_remove(index);
+        self.handles.swap_remove(index);
+        if let Some(moved_handle) = self.handles.get(index) {
+            self.slots[moved_handle.index as usize].index = Some(index);
+        }
+        self.structure_changed = true;
+        Some(object)
+    }
+
+    pub fn set_transform(
+        &mut self,
+        handle: ObjectHandle,
+        transform: blade_graphics::Transform,
+    ) -> Option<()> {
+        let index = self.index_of(handle)?;
+        self.objects[index].transform = transform;
+        self.transforms_changed = true;
+        Some(())
+    }
+
+    /// Set the visibility layers of an object.
+    ///
+    /// Only updates the instance, without rebuilding any geometry.
+    pub fn set_layers(&mut self, handle: ObjectHandle, layers: u32) -> Option<()> {
+        let index = self.index_of(handle)?;
+        self.objects[index].layers = layers;
+        self.layers_changed = true;
+        Some(())
+    }
+
+    /// Set the material overrides of an object.
+    ///
+    /// Only updates the material parameters of its geometries,
+    /// but the accumulated lighting is reset.
+    pub fn set_instance_overrides(
+        &mut self,
+        handle: ObjectHandle,
+        overrides: crate::MaterialOverrides,
+    ) -> Option<()> {
+        let index = self.index_of(handle)?;
+        let object = &mut self.objects[index];
+        if object.material_overrides != overrides {
+            object.material_overrides = overrides;
+            self.overrides_changed = true;
+        }
+        Some(())
+    }
+
+    /// Set the model-space joint transforms of a skinned object.
+    pub fn set_joints(
+        &mut self,
+        handle: ObjectHandle,
+        joints: &[mint::ColumnMatrix4<f32>],
+    ) -> Option<()> {
+        let index = self.index_of(handle)?;
+        let object = &mut self.objects[index];
+        object.joints.clear();
+        object.joints.extend_from_slice(joints);
+        self.joints_changed = true;
+        Some(())
+    }
+
+    /// Add an object with a procedural mesh, backed by a model of its own.
+    ///
+    /// The model goes through the same path as the cooked ones,
+    /// including the acceleration structure, which is built by the next `AssetHub::flush`.
+    pub fn add_procedural_mesh(
+        &mut self,
+        asset_hub: &crate::AssetHub,
+        geometry: crate::ProceduralGeometry,
+    ) -> ObjectHandle {
+        let name = geometry.name.clone();
+        let model = asset_hub.models.baker.create_model(&name, vec![geometry]);
+        let handle = asset_hub.models.insert(model);
+        self.add_object(crate::Object::from(handle))
+    }
+
+    /// Replace the vertices of a procedural mesh, keeping the indices.
+    ///
+    /// The acceleration structure is rebuilt by the next `AssetHub::flush`.
+    pub fn update_procedural_mesh(
+        &mut self,
+        asset_hub: &crate::AssetHub,
+        handle: ObjectHandle,
+        vertices: &[crate::Vertex],
+    ) -> Option<()> {
+        let index = self.index_of(handle)?;
+        let model = &asset_hub.models[self.objects[index].model];
+        asset_hub
+            .models
+            .baker
+            .update_model_vertices(model, vertices);
+        self.geometry_changed = true;
+        Some(())
+    }
+
+    /// Remove an object added by `add_procedural_mesh`, releasing its model.
+    ///
+    /// The GPU resources are retired by the next `AssetHub::evict`.
+    pub fn remove_procedural_mesh(
+        &mut self,
+        asset_hub: &crate::AssetHub,
+        handle: ObjectHandle,
+    ) -> Option<()> {
+        let object = self.remove_object(handle)?;
+        asset_hub.models.release(object.model);
+        Some(())
+    }
+
+    pub fn get(&self, handle: ObjectHandle) -> Option<&crate::Object> {
+        let index = self.index_of(handle)?;
+        Some(&self.objects[index])
+    }
+
+    /// Find the object handle by its index in `objects()`,
+    /// such as `PickResult::object`.
+    pub fn handle_at(&self, index: usize) -> Option<ObjectHandle> {
+        self.handles.get(index).copied()
+    }
+
+    pub fn objects(&self) -> &[crate::Object] {
+        &self.objects
+    }
+
+    /// Handles of the objects, in the order of `objects()`.
+    pub(crate) fn handles(&self) -> &[ObjectHandle] {
+        &self.handles
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn add_light(&mut self, light: crate::Light) -> LightHandle {
+        let slot_index = match self.free_lights.pop() {
+            Some(index) => index,
+            None => {
+                self.lights.push(LightSlot::default());
+                self.lights.len() as u32 - 1
+            }
+        };
+        let slot = &mut self.lights[slot_index as usize];
+        slot.light = Some(light);
+        self.lights_changed = true;
+        LightHandle {
+            index: slot_index,
+            generation: slot.generation,
+        }
+    }
+
+    fn light_slot(&mut self, handle: LightHandle) -> Option<&mut LightSlot> {
+        let slot = self.lights.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation || slot.light.is_none() {
+            return None;
+        }
+        Some(slot)
+    }
+
+    pub fn remove_light(&mut self, handle: LightHandle) -> Option<crate::Light> {
+        let slot = self.light_slot(handle)?;
+        let light = slot.light.take();
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_lights.push(handle.index);
+        self.lights_changed = true;
+        light
+    }
+
+    pub fn set_light(&mut self, handle: LightHandle, light: crate::Light) -> Option<()> {
+        let slot = self.light_slot(handle)?;
+        slot.light = Some(light);
+        self.lights_changed = true;
+        Some(())
+    }
+
+    pub fn get_light(&self, handle: LightHandle) -> Option<&crate::Light> {
+        let slot = self.lights.get(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.light.as_ref()
+    }
+
+    pub fn lights(&self) -> impl Iterator<Item = &crate::Light> {
+        self.lights.iter().filter_map(|slot| slot.light.as_ref())
+    }
+
+    /// Mark all the changes as applied.
+    pub(crate) fn settle(&mut self) {
+        for object in self.objects.iter_mut() {
+            object.prev_transform = object.transform;
+        }
+        self.structure_changed = false;
+        self.transforms_changed = false;
+        self.joints_changed = false;
+        self.lights_changed = false;
+        self.layers_changed = false;
+        self.geometry_changed = false;
+        self.overrides_changed = false;
+    }
+}
@@ -1,4 +1,12 @@
-use std::{any, collections::HashMap, fmt, fs, path::Path, str, sync::Arc};
+use std::{
+    any,
+    collections::{HashMap, hash_map::DefaultHasher},
+    fmt, fs,
+    hash::Hash as _,
+    path::Path,
+    str,
+    sync::Arc,
+};
 
 const FAILURE_DUMP_NAME: &str = "_failure.wgsl";
 
@@ -162,4 +170,20 @@ impl blade_asset::Baker for Baker {
         Shader { raw }
     }
     fn delete(&self, _output: Shader) {}
+    fn hash_parameters(&self, hasher: &mut DefaultHasher) {
+        // The expansions are substituted into the cooked shaders
+        let mut expansions = self.expansions.iter().collect::<Vec<_>>();
+        expansions.sort_by_key(|&(name, _)| name);
+        for (name, expansion) in expansions {
+            name.hash(hasher);
+            match *expansion {
+                Expansion::Values(ref map) => {
+                    let mut values = map.iter().collect::<Vec<_>>();
+                    values.sort();
+                    values.hash(hasher);
+                }
+                Expansion::Bool(value) => value.hash(hasher),
+            }
+        }
+    }
 }
@@ -0,0 +1,236 @@
+use std::f32::consts::PI;
+
+/// Angular radius of the sun disc, in radians.
+const SUN_ANGULAR_RADIUS: f32 = 0.00465;
+/// Illuminance of the sun outside of the atmosphere, in kilolux.
+const SUN_ILLUMINANCE: f32 = 128.0;
+/// Brightness of the night sky, relative to the twilight.
+const NIGHT_LEVEL: f32 = 1e-4;
+/// Wavelengths of the red, green, and blue channels, in micrometers.
+const WAVELENGTHS: [f32; 3] = [0.65, 0.55, 0.45];
+
+/// Procedural sky following the analytic model of Preetham et al.
+///
+/// The sky is baked into an equirectangular environment map,
+/// which gets the same importance sampling as any other environment.
+/// The sun disc is not a part of the map. Instead, it's represented
+/// by a directional light from `sun_light`, giving sharp shadows.
+///
+/// The sky radiance is in kilocandela per square meter,
+/// and the sun illuminance is in kilolux, both scaled by the intensity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SkyModel {
+    /// Direction towards the sun in world space, assuming no rotation
+    /// of the environment. Doesn't have to be normalized.
+    pub sun_direction: mint::Vector3<f32>,
+    /// Haziness of the atmosphere, from 2 for a clear sky to 10 for a hazy one.
+    pub turbidity: f32,
+    /// Linear color of the ground below the horizon.
+    pub ground_albedo: [f32; 3],
+    /// Multiplier of both the sky radiance and the sun illuminance.
+    pub intensity: f32,
+}
+
+impl Default for SkyModel {
+    fn default() -> Self {
+        Self {
+            sun_direction: [0.3, 0.6, 0.4].into(),
+            turbidity: 3.0,
+            ground_albedo: [0.3; 3],
+            intensity: 1.0,
+        }
+    }
+}
+
+/// Coefficients of the Perez sky distribution function.
+struct Perez([f32; 5]);
+
+impl Perez {
+    fn new(turbidity: f32, coefficients: [[f32; 2]; 5]) -> Self {
+        Self(coefficients.map(|[a, b]| a * turbidity + b))
+    }
+
+    fn eval(&self, cos_theta: f32, gamma: f32) -> f32 {
+        let [a, b, c, d, e] = self.0;
+        let cos_gamma = gamma.cos();
+        (1.0 + a * (b / cos_theta.max(0.01)).exp())
+            * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+    }
+}
+
+/// Evaluate a polynomial of the sun angle, weighted by the turbidity.
+fn zenith_chromaticity(turbidity: f32, theta: f32, coefficients: [[f32; 4]; 3]) -> f32 {
+    let poly = |[a, b, c, d]: [f32; 4]| ((a * theta + b) * theta + c) * theta + d;
+    let [t2, t1, t0] = coefficients;
+    (poly(t2) * turbidity + poly(t1)) * turbidity + poly(t0)
+}
+
+impl SkyModel {
+    fn sun_dir(&self) -> glam::Vec3 {
+        glam::Vec3::from(self.sun_direction).normalize_or(glam::Vec3::Y)
+    }
+
+    /// Fade the sky out as the sun goes below the horizon.
+    ///
+    /// The night sky is never completely black, which also keeps
+    /// the importance sampling of the environment well defined.
+    fn twilight(&self) -> f32 {
+        (1.0 + 10.0 * self.sun_dir().y).clamp(NIGHT_LEVEL, 1.0)
+    }
+
+    /// Linear color of the sun, after passing through the atmosphere.
+    fn sun_transmittance(&self) -> [f32; 3] {
+        let zenith = self.sun_dir().y.clamp(0.0, 1.0).acos().to_degrees();
+        // Relative optical air mass, by Kasten and Young
+        let air_mass =
+            1.0 / (zenith.to_radians().cos() + 0.50572 * (96.07995 - zenith).powf(-1.6364));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        WAVELENGTHS.map(|lambda| {
+            let rayleigh = 0.008735 * lambda.powf(-4.08);
+            let aerosol = beta * lambda.powf(-1.3);
+            (-(rayleigh + aerosol) * air_mass).exp()
+        })
+    }
+
+    /// Return the directional light of the sun disc.
+    pub fn sun_light(&self) -> crate::Light {
+        let transmittance = self.sun_transmittance();
+        let max = transmittance.iter().fold(0.0f32, |a, &b| a.max(b));
+        let dir = self.sun_dir();
+        let is_up = dir.y > 0.0;
+        crate::Light {
+            kind: crate::LightKind::Directional {
+                angular_radius: SUN_ANGULAR_RADIUS,
+            },
+            position: [0.0; 3].into(),
+            direction: (-dir).into(),
+            color: if is_up {
+                transmittance.map(|t| t / max)
+            } else {
+                [1.0; 3]
+            },
+            intensity: if is_up {
+                SUN_ILLUMINANCE * max * self.intensity
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// Compute the sky radiance in the given direction above the horizon.
+    fn sky_radiance(&self, dir: glam::Vec3) -> [f32; 3] {
+        let t = self.turbidity;
+        let sun = self.sun_dir();
+        // The model is only valid while the sun is above the horizon
+        let theta_s = sun.y.clamp(0.01, 1.0).acos();
+        let cos_theta = dir.y.max(0.0);
+        let gamma = dir.dot(sun).clamp(-1.0, 1.0).acos();
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_y = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let zenith_x = zenith_chromaticity(
+            t,
+            theta_s,
+            [
+                [0.00166, -0.00375, 0.00209, 0.0],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886],
+            ],
+        );
+        let zenith_chroma_y = zenith_chromaticity(
+            t,
+            theta_s,
+            [
+                [0.00275, -0.00610, 0.00317, 0.0],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688],
+            ],
+        );
+
+        let perez_y = Perez::new(
+            t,
+            [
+                [0.1787, -1.4630],
+                [-0.3554, 0.4275],
+                [-0.0227, 5.3251],
+                [0.1206, -2.5771],
+                [-0.0670, 0.3703],
+            ],
+        );
+        let perez_x = Perez::new(
+            t,
+            [
+                [-0.0193, -0.2592],
+                [-0.0665, 0.0008],
+                [-0.0004, 0.2125],
+                [-0.0641, -0.8989],
+                [-0.0033, 0.0452],
+            ],
+        );
+        let perez_chroma_y = Perez::new(
+            t,
+            [
+                [-0.0167, -0.2608],
+                [-0.0950, 0.0092],
+                [-0.0079, 0.2102],
+                [-0.0441, -1.6537],
+                [-0.0109, 0.0529],
+            ],
+        );
+        let relative = |perez: &Perez| perez.eval(cos_theta, gamma) / perez.eval(1.0, theta_s);
+
+        let luminance = zenith_y.max(0.0) * relative(&perez_y);
+        let x = zenith_x * relative(&perez_x);
+        let y = zenith_chroma_y * relative(&perez_chroma_y);
+        // From the xyY color space into the linear sRGB
+        let big_x = x / y * luminance;
+        let big_z = (1.0 - x - y) / y * luminance;
+        let scale = self.intensity * self.twilight();
+        [
+            3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+            -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+            0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+        ]
+        .map(|c| scale * c.max(0.0))
+    }
+
+    /// Bake the sky into an equirectangular environment map of the given size.
+    ///
+    /// The lower half shows the ground, lit by the sun and the sky.
+    pub fn bake(&self, width: u32, height: u32) -> Vec<[f32; 4]> {
+        let mut data = vec![[0.0; 4]; (width * height) as usize];
+        let mut sky_irradiance = [0.0f32; 3];
+        let horizon = height.div_ceil(2);
+        for y in 0..horizon {
+            // Has to match `map_equirect_uv_to_dir` in the shaders
+            let elevation = PI * (0.5 - (y as f32 + 0.5) / height as f32);
+            let solid_angle = (2.0 * PI / width as f32) * (PI / height as f32) * elevation.cos();
+            for x in 0..width {
+                let azimuth = 2.0 * PI * ((x as f32 + 0.5) / width as f32 - 0.5);
+                let dir = glam::Vec3::new(
+                    elevation.cos() * azimuth.sin(),
+                    elevation.sin(),
+                    elevation.cos() * azimuth.cos(),
+                );
+                let radiance = self.sky_radiance(dir);
+                for (irradiance, r) in sky_irradiance.iter_mut().zip(radiance) {
+                    *irradiance += r * dir.y.max(0.0) * solid_angle;
+                }
+                data[(y * width + x) as usize] = [radiance[0], radiance[1], radiance[2], 1.0];
+            }
+        }
+
+        let sun = self.sun_light();
+        let sun_cos = self.sun_dir().y.max(0.0);
+        let mut ground = [0.0; 4];
+        for i in 0..3 {
+            let irradiance = sky_irradiance[i] + sun.color[i] * sun.intensity * sun_cos;
+            ground[i] = self.ground_albedo[i] / PI * irradiance;
+        }
+        ground[3] = 1.0;
+        for texel in data[(horizon * width) as usize..].iter_mut() {
+            *texel = ground;
+        }
+        data
+    }
+}
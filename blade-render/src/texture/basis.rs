@@ -0,0 +1,146 @@
+use basis_universal as bu;
+use blade_graphics::TextureFormat as Tf;
+
+/// Formats to transcode into, in the order of preference,
+/// with their linear and sRGB variants.
+const TARGETS: &[(bu::TranscoderTextureFormat, Tf, Tf)] = &[
+    (
+        bu::TranscoderTextureFormat::BC7_RGBA,
+        Tf::Bc7Unorm,
+        Tf::Bc7UnormSrgb,
+    ),
+    (
+        bu::TranscoderTextureFormat::ASTC_4x4_RGBA,
+        Tf::Astc4x4Unorm,
+        Tf::Astc4x4UnormSrgb,
+    ),
+    (
+        bu::TranscoderTextureFormat::ETC2_RGBA,
+        Tf::Etc2Rgba8Unorm,
+        Tf::Etc2Rgba8UnormSrgb,
+    ),
+    (
+        bu::TranscoderTextureFormat::BC3_RGBA,
+        Tf::Bc3Unorm,
+        Tf::Bc3UnormSrgb,
+    ),
+];
+
+/// Uncompressed format used when none of the `TARGETS` are supported.
+pub(super) fn fallback_format(is_srgb: bool) -> Tf {
+    if is_srgb {
+        Tf::Rgba8UnormSrgb
+    } else {
+        Tf::Rgba8Unorm
+    }
+}
+
+/// Return the extent of the base level of a Basis Universal file.
+pub(super) fn base_extent(data: &[u8]) -> Option<[u32; 2]> {
+    let transcoder = bu::Transcoder::new();
+    if !transcoder.validate_header(data) {
+        return None;
+    }
+    let desc = transcoder.image_level_description(data, 0, 0)?;
+    Some([desc.original_width, desc.original_height])
+}
+
+/// Encode the RGBA texels of an image into a Basis Universal file.
+pub(super) fn encode(
+    texels: &[[u8; 4]],
+    width: u32,
+    height: u32,
+    encoding: super::BasisEncoding,
+    meta: &super::Meta,
+) -> Vec<u8> {
+    let mut params = bu::CompressorParams::new();
+    params.set_basis_format(match encoding {
+        super::BasisEncoding::Etc1s => bu::BasisTextureFormat::ETC1S,
+        super::BasisEncoding::Uastc => bu::BasisTextureFormat::UASTC4x4,
+    });
+    if meta.format.is_srgb() {
+        params.set_color_space(bu::ColorSpace::Srgb);
+    } else if is_two_channel(meta.format) {
+        params.tune_for_normal_maps();
+    } else {
+        params.set_color_space(bu::ColorSpace::Linear);
+    }
+    params.set_generate_mipmaps(meta.generate_mips);
+    params
+        .source_image_mut(0)
+        .init(bytemuck::cast_slice(texels), width, height, 4);
+
+    let mut compressor = bu::Compressor::default();
+    unsafe {
+        assert!(
+            compressor.init(&params),
+            "Unable to initialize the Basis compressor"
+        );
+        if let Err(e) = compressor.process() {
+            panic!("Unable to encode Basis: {e:?}");
+        }
+    }
+    compressor.basis_file().to_vec()
+}
+
+fn is_two_channel(format: Tf) -> bool {
+    matches!(
+        format,
+        Tf::Rg8Unorm | Tf::Rg8Snorm | Tf::Bc5Unorm | Tf::Bc5Snorm
+    )
+}
+
+/// Image transcoded from a Basis Universal file.
+pub(super) struct Transcoded {
+    pub format: Tf,
+    pub mip_offsets: Vec<u64>,
+    pub data: Vec<u8>,
+}
+
+/// Transcode all the mips of a Basis Universal file into the most preferred
+/// of the formats accepted by `is_supported`, falling back to RGBA8.
+pub(super) fn transcode(
+    source: &[u8],
+    is_srgb: bool,
+    is_supported: impl Fn(Tf) -> bool,
+) -> Transcoded {
+    let mut transcoder = bu::Transcoder::new();
+    if transcoder.prepare_transcoding(source).is_err() {
+        panic!("Unable to prepare the Basis transcoding");
+    }
+    let (target, format) = TARGETS
+        .iter()
+        .map(|&(target, linear, srgb)| (target, if is_srgb { srgb } else { linear }))
+        .find(|&(target, format)| {
+            transcoder
+                .basis_texture_format(source)
+                .can_transcode_to_format(target)
+                && is_supported(format)
+        })
+        .unwrap_or((
+            bu::TranscoderTextureFormat::RGBA32,
+            fallback_format(is_srgb),
+        ));
+    log::debug!("Transcoding Basis into {format:?}");
+
+    let level_count = transcoder.image_level_count(source, 0);
+    let mut mip_offsets = Vec::with_capacity(level_count as usize);
+    let mut data = Vec::new();
+    for level in 0..level_count {
+        mip_offsets.push(data.len() as u64);
+        let params = bu::TranscodeParameters {
+            level_index: level,
+            ..Default::default()
+        };
+        match transcoder.transcode_image_level(source, target, params) {
+            Ok(level_data) => data.extend(level_data),
+            Err(e) => panic!("Unable to transcode Basis mip {level}: {e:?}"),
+        }
+    }
+    transcoder.end_transcoding();
+    Transcoded {
+        format,
+        mip_offsets,
+        data,
+    }
+}
@@ -0,0 +1,210 @@
+use blade_graphics::TextureFormat as Tf;
+
+const MAGIC: [u8; 4] = *b"DDS ";
+const HEADER_SIZE: usize = 124;
+const DX10_HEADER_SIZE: usize = 20;
+
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_DEPTH: u32 = 0x80_0000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+const D3D10_RESOURCE_DIMENSION_TEXTURE3D: u32 = 4;
+const D3D10_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+fn map_four_cc(four_cc: [u8; 4]) -> Option<Tf> {
+    Some(match &four_cc {
+        b"DXT1" => Tf::Bc1Unorm,
+        b"DXT2" | b"DXT3" => Tf::Bc2Unorm,
+        b"DXT4" | b"DXT5" => Tf::Bc3Unorm,
+        b"ATI1" | b"BC4U" => Tf::Bc4Unorm,
+        b"BC4S" => Tf::Bc4Snorm,
+        b"ATI2" | b"BC5U" => Tf::Bc5Unorm,
+        b"BC5S" => Tf::Bc5Snorm,
+        // Some of the D3DFORMAT values are stored in place of FourCC
+        _ => match u32::from_le_bytes(four_cc) {
+            111 => Tf::R16Float,
+            112 => Tf::Rg16Float,
+            113 => Tf::Rgba16Float,
+            114 => Tf::R32Float,
+            115 => Tf::Rg32Float,
+            116 => Tf::Rgba32Float,
+            _ => return None,
+        },
+    })
+}
+
+fn map_dxgi_format(format: u32) -> Option<Tf> {
+    Some(match format {
+        2 => Tf::Rgba32Float,
+        10 => Tf::Rgba16Float,
+        16 => Tf::Rg32Float,
+        24 => Tf::Rgb10a2Unorm,
+        26 => Tf::Rg11b10Ufloat,
+        28 => Tf::Rgba8Unorm,
+        29 => Tf::Rgba8UnormSrgb,
+        31 => Tf::Rgba8Snorm,
+        34 => Tf::Rg16Float,
+        41 => Tf::R32Float,
+        49 => Tf::Rg8Unorm,
+        51 => Tf::Rg8Snorm,
+        54 => Tf::R16Float,
+        61 => Tf::R8Unorm,
+        67 => Tf::Rgb9e5Ufloat,
+        71 => Tf::Bc1Unorm,
+        72 => Tf::Bc1UnormSrgb,
+        74 => Tf::Bc2Unorm,
+        75 => Tf::Bc2UnormSrgb,
+        77 => Tf::Bc3Unorm,
+        78 => Tf::Bc3UnormSrgb,
+        80 => Tf::Bc4Unorm,
+        81 => Tf::Bc4Snorm,
+        83 => Tf::Bc5Unorm,
+        84 => Tf::Bc5Snorm,
+        87 => Tf::Bgra8Unorm,
+        91 => Tf::Bgra8UnormSrgb,
+        95 => Tf::Bc6hUfloat,
+        96 => Tf::Bc6hFloat,
+        98 => Tf::Bc7Unorm,
+        99 => Tf::Bc7UnormSrgb,
+        _ => return None,
+    })
+}
+
+/// The legacy header has no notion of sRGB, so it's taken from the requested format.
+fn to_srgb(format: Tf) -> Tf {
+    match format {
+        Tf::Rgba8Unorm => Tf::Rgba8UnormSrgb,
+        Tf::Bgra8Unorm => Tf::Bgra8UnormSrgb,
+        Tf::Bc1Unorm => Tf::Bc1UnormSrgb,
+        Tf::Bc2Unorm => Tf::Bc2UnormSrgb,
+        Tf::Bc3Unorm => Tf::Bc3UnormSrgb,
+        other => other,
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Cook a 2D DDS file with either the legacy or the DX10 header,
+/// keeping the mip chain without recompression.
+///
+/// Only the sRGB-ness of `meta.format` matters, for the legacy formats
+/// that can't express it.
+pub(super) fn cook(source: &[u8], meta: &super::Meta, cooker: &blade_asset::Cooker<super::Baker>) {
+    if source.len() < MAGIC.len() + HEADER_SIZE || source[..4] != MAGIC {
+        panic!("Not a DDS file");
+    }
+    let header = &source[4..4 + HEADER_SIZE];
+    let flags = read_u32(header, 4);
+    let height = read_u32(header, 8);
+    let width = read_u32(header, 12);
+    let depth = read_u32(header, 20);
+    let mip_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        read_u32(header, 24).max(1)
+    } else {
+        1
+    };
+    let pf_flags = read_u32(header, 76);
+    let four_cc: [u8; 4] = header[80..84].try_into().unwrap();
+    let caps2 = read_u32(header, 108);
+    if caps2 & DDSCAPS2_VOLUME != 0 || (flags & DDSD_DEPTH != 0 && depth > 1) {
+        panic!("DDS volume textures are not supported");
+    }
+    if caps2 & DDSCAPS2_CUBEMAP != 0 {
+        panic!("DDS cube maps are not supported yet");
+    }
+
+    let mut offset = 4 + HEADER_SIZE;
+    let format = if pf_flags & DDPF_FOURCC != 0 && &four_cc == b"DX10" {
+        if source.len() < offset + DX10_HEADER_SIZE {
+            panic!("Truncated DX10 header of DDS");
+        }
+        let dx10 = &source[offset..offset + DX10_HEADER_SIZE];
+        offset += DX10_HEADER_SIZE;
+        let dxgi_format = read_u32(dx10, 0);
+        match read_u32(dx10, 4) {
+            D3D10_RESOURCE_DIMENSION_TEXTURE2D => {}
+            D3D10_RESOURCE_DIMENSION_TEXTURE3D => {
+                panic!("DDS volume textures are not supported")
+            }
+            other => panic!("Unsupported DDS resource dimension {other}"),
+        }
+        if read_u32(dx10, 8) & D3D10_RESOURCE_MISC_TEXTURECUBE != 0 {
+            panic!("DDS cube maps are not supported yet");
+        }
+        if read_u32(dx10, 12) > 1 {
+            panic!("DDS texture arrays are not supported yet");
+        }
+        match map_dxgi_format(dxgi_format) {
+            Some(format) => format,
+            None => panic!("Unsupported DXGI format {dxgi_format} of DDS"),
+        }
+    } else if pf_flags & DDPF_FOURCC != 0 {
+        match map_four_cc(four_cc) {
+            Some(format) if meta.format.is_srgb() => to_srgb(format),
+            Some(format) => format,
+            None => panic!(
+                "Unsupported DDS FourCC {:?}",
+                String::from_utf8_lossy(&four_cc)
+            ),
+        }
+    } else {
+        let bit_count = read_u32(header, 84);
+        let masks = [88, 92, 96, 100].map(|offset| read_u32(header, offset));
+        let is_rgba32 =
+            pf_flags & DDPF_RGB != 0 && pf_flags & DDPF_ALPHAPIXELS != 0 && bit_count == 32;
+        let format = match masks {
+            [0xFF, 0xFF00, 0xFF_0000, 0xFF00_0000] if is_rgba32 => Some(Tf::Rgba8Unorm),
+            [0xFF_0000, 0xFF00, 0xFF, 0xFF00_0000] if is_rgba32 => Some(Tf::Bgra8Unorm),
+            _ => None,
+        };
+        match format {
+            Some(format) if meta.format.is_srgb() => to_srgb(format),
+            Some(format) => format,
+            None => panic!(
+                "Unsupported legacy DDS pixel format of {bit_count} bits with flags 0x{pf_flags:x} and masks {masks:x?}, only RGBA8 and BGRA8 are supported"
+            ),
+        }
+    };
+    if format != meta.format {
+        log::warn!(
+            "DDS format {:?} is used instead of the requested {:?}",
+            format,
+            meta.format
+        );
+    }
+    if meta.y_flip {
+        log::warn!("DDS textures can't be flipped, ignoring");
+    }
+
+    let base_extent = blade_graphics::Extent {
+        width,
+        height: height.max(1),
+        depth: 1,
+    };
+    let data = &source[offset..];
+    let mut mip_offsets = Vec::with_capacity(mip_count as usize);
+    let mut end = 0;
+    for i in 0..mip_count {
+        mip_offsets.push(end as u64);
+        end += super::level_size(format, base_extent.at_mip_level(i));
+        assert!(end <= data.len(), "Truncated DDS mip {i}");
+    }
+    if end != data.len() {
+        log::warn!("Ignoring {} bytes after the DDS mips", data.len() - end);
+    }
+
+    cooker.finish(super::CookedImage {
+        name: &[],
+        extent: [base_extent.width, base_extent.height, 1],
+        format: super::TextureFormatWrap(format),
+        mip_offsets,
+        data: &data[..end],
+        is_basis: 0,
+    });
+}
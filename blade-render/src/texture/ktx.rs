@@ -0,0 +1,130 @@
+use blade_graphics::TextureFormat as Tf;
+use std::io::Read as _;
+
+fn map_format(format: ktx2::Format) -> Option<Tf> {
+    use ktx2::Format as F;
+    Some(match format {
+        F::R8_UNORM => Tf::R8Unorm,
+        F::R8G8_UNORM => Tf::Rg8Unorm,
+        F::R8G8_SNORM => Tf::Rg8Snorm,
+        F::R8G8B8A8_UNORM => Tf::Rgba8Unorm,
+        F::R8G8B8A8_SRGB => Tf::Rgba8UnormSrgb,
+        F::R8G8B8A8_SNORM => Tf::Rgba8Snorm,
+        F::B8G8R8A8_UNORM => Tf::Bgra8Unorm,
+        F::B8G8R8A8_SRGB => Tf::Bgra8UnormSrgb,
+        F::R16_SFLOAT => Tf::R16Float,
+        F::R16G16_SFLOAT => Tf::Rg16Float,
+        F::R16G16B16A16_SFLOAT => Tf::Rgba16Float,
+        F::R32_SFLOAT => Tf::R32Float,
+        F::R32G32_SFLOAT => Tf::Rg32Float,
+        F::R32G32B32A32_SFLOAT => Tf::Rgba32Float,
+        F::A2B10G10R10_UNORM_PACK32 => Tf::Rgb10a2Unorm,
+        F::B10G11R11_UFLOAT_PACK32 => Tf::Rg11b10Ufloat,
+        F::E5B9G9R9_UFLOAT_PACK32 => Tf::Rgb9e5Ufloat,
+        // BC1 blocks are the same with or without the alpha
+        F::BC1_RGB_UNORM_BLOCK | F::BC1_RGBA_UNORM_BLOCK => Tf::Bc1Unorm,
+        F::BC1_RGB_SRGB_BLOCK | F::BC1_RGBA_SRGB_BLOCK => Tf::Bc1UnormSrgb,
+        F::BC2_UNORM_BLOCK => Tf::Bc2Unorm,
+        F::BC2_SRGB_BLOCK => Tf::Bc2UnormSrgb,
+        F::BC3_UNORM_BLOCK => Tf::Bc3Unorm,
+        F::BC3_SRGB_BLOCK => Tf::Bc3UnormSrgb,
+        F::BC4_UNORM_BLOCK => Tf::Bc4Unorm,
+        F::BC4_SNORM_BLOCK => Tf::Bc4Snorm,
+        F::BC5_UNORM_BLOCK => Tf::Bc5Unorm,
+        F::BC5_SNORM_BLOCK => Tf::Bc5Snorm,
+        F::BC6H_UFLOAT_BLOCK => Tf::Bc6hUfloat,
+        F::BC6H_SFLOAT_BLOCK => Tf::Bc6hFloat,
+        F::BC7_UNORM_BLOCK => Tf::Bc7Unorm,
+        F::BC7_SRGB_BLOCK => Tf::Bc7UnormSrgb,
+        F::ETC2_R8G8B8_UNORM_BLOCK => Tf::Etc2Rgb8Unorm,
+        F::ETC2_R8G8B8_SRGB_BLOCK => Tf::Etc2Rgb8UnormSrgb,
+        F::ETC2_R8G8B8A8_UNORM_BLOCK => Tf::Etc2Rgba8Unorm,
+        F::ETC2_R8G8B8A8_SRGB_BLOCK => Tf::Etc2Rgba8UnormSrgb,
+        F::ASTC_4x4_UNORM_BLOCK => Tf::Astc4x4Unorm,
+        F::ASTC_4x4_SRGB_BLOCK => Tf::Astc4x4UnormSrgb,
+        _ => return None,
+    })
+}
+
+/// Cook a KTX2 container, taking the mips as they are.
+///
+/// The format of the container is used instead of the one in `meta`,
+/// and no mips are generated, since the data is normally block-compressed.
+pub(super) fn cook(source: &[u8], meta: &super::Meta, cooker: &blade_asset::Cooker<super::Baker>) {
+    let reader = match ktx2::Reader::new(source) {
+        Ok(reader) => reader,
+        Err(e) => panic!("Unable to parse KTX2: {e:?}"),
+    };
+    let header = reader.header();
+    if header.face_count != 1 {
+        panic!("KTX2 cube maps are not supported yet");
+    }
+    if header.layer_count > 1 {
+        panic!("KTX2 texture arrays are not supported yet");
+    }
+    if header.pixel_depth > 1 {
+        panic!("KTX2 3D textures are not supported yet");
+    }
+    let format = match header.format {
+        Some(format) => match map_format(format) {
+            Some(format) => format,
+            None => panic!("Unsupported KTX2 format {format:?}"),
+        },
+        // The format is only described by the DFD, which is the case for Basis Universal
+        None => panic!(
+            "KTX2 with the color model {:?} needs transcoding, which is not supported",
+            reader.color_model()
+        ),
+    };
+    if format != meta.format {
+        log::warn!(
+            "KTX2 format {:?} is used instead of the requested {:?}",
+            format,
+            meta.format
+        );
+    }
+    if meta.y_flip {
+        log::warn!("KTX2 textures can't be flipped, ignoring");
+    }
+
+    let base_extent = blade_graphics::Extent {
+        width: header.pixel_width,
+        height: header.pixel_height.max(1),
+        depth: 1,
+    };
+    let mut mip_offsets = Vec::with_capacity(reader.levels().len());
+    let mut data = Vec::new();
+    for (i, level) in reader.levels().enumerate() {
+        let offset = data.len();
+        mip_offsets.push(offset as u64);
+        match header.supercompression_scheme {
+            None => data.extend_from_slice(level.data),
+            Some(ktx2::SupercompressionScheme::Zstandard) => {
+                profiling::scope!("decompress zstd");
+                let mut decoder = match ruzstd::decoding::StreamingDecoder::new(level.data) {
+                    Ok(decoder) => decoder,
+                    Err(e) => panic!("Unable to decode zstd of mip {i}: {e}"),
+                };
+                if let Err(e) = decoder.read_to_end(&mut data) {
+                    panic!("Unable to decode zstd of mip {i}: {e}");
+                }
+            }
+            Some(other) => panic!("Unsupported KTX2 supercompression {other:?}"),
+        }
+        let expected = super::level_size(format, base_extent.at_mip_level(i as u32));
+        assert_eq!(
+            data.len() - offset,
+            expected,
+            "Unexpected size of KTX2 mip {i}"
+        );
+    }
+
+    cooker.finish(super::CookedImage {
+        name: &[],
+        extent: [base_extent.width, base_extent.height, 1],
+        format: super::TextureFormatWrap(format),
+        mip_offsets,
+        data: &data,
+        is_basis: 0,
+    });
+}
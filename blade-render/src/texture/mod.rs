@@ -1,42 +1,217 @@
 use std::{
+    cmp,
+    collections::HashMap,
     fmt, io, mem, ptr, slice, str,
     sync::{Arc, Mutex},
 };
 
+#[cfg(feature = "asset")]
+mod basis;
+#[cfg(feature = "asset")]
+mod dds;
+#[cfg(feature = "asset")]
+mod ktx;
+
+/// Number of frames a streamed texture stays resident without being requested.
+const STREAM_OUT_DELAY: u64 = 60;
+
 #[repr(transparent)]
 #[derive(Clone, Copy, Debug, blade_macros::Flat)]
 struct TextureFormatWrap(blade_graphics::TextureFormat);
 
-#[derive(blade_macros::Flat)]
-struct CookedMip<'a> {
-    data: &'a [u8],
-}
-
 #[derive(blade_macros::Flat)]
 pub struct CookedImage<'a> {
     name: &'a [u8],
     extent: [u32; 3],
     format: TextureFormatWrap,
-    mips: Vec<CookedMip<'a>>,
+    /// Offset of every mip level in `data`, starting from the base one.
+    mip_offsets: Vec<u64>,
+    data: &'a [u8],
+    /// Non-zero if `data` is a Basis Universal file, which is transcoded
+    /// when served. The `format` is then the uncompressed fallback.
+    is_basis: u32,
+}
+
+impl<'a> CookedImage<'a> {
+    fn mip_data(&self, level: usize) -> &'a [u8] {
+        let start = self.mip_offsets[level] as usize;
+        let end = match self.mip_offsets.get(level + 1) {
+            Some(&offset) => offset as usize,
+            None => self.data.len(),
+        };
+        &self.data[start..end]
+    }
+}
+
+/// Size of a mip level of a 2D texture in bytes.
+#[cfg(feature = "asset")]
+fn level_size(format: blade_graphics::TextureFormat, extent: blade_graphics::Extent) -> usize {
+    let block = format.block_info();
+    let columns = extent.width.div_ceil(block.dimensions.0 as u32);
+    let rows = extent.height.div_ceil(block.dimensions.1 as u32);
+    (columns * rows) as usize * block.size as usize
+}
+
+/// Basis Universal encoding of the cooked textures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BasisEncoding {
+    /// Smaller files of a lower quality.
+    Etc1s,
+    /// Larger files of a higher quality.
+    Uastc,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Meta {
+    /// Format of the cooked texture.
+    ///
+    /// HDR images are cooked into either `Rgba16Float` or `Rgba32Float`,
+    /// trading the precision and range for memory.
     pub format: blade_graphics::TextureFormat,
     pub generate_mips: bool,
     pub y_flip: bool,
+    /// Cook into a Basis Universal file instead of `format`.
+    ///
+    /// The file is transcoded at load time into the best format supported
+    /// by the device, falling back to the uncompressed RGBA8. Only the sRGB-ness
+    /// of `format` is preserved then.
+    pub basis: Option<BasisEncoding>,
 }
 
 impl fmt::Display for Meta {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt::Debug::fmt(&self.format, f)
+        fmt::Debug::fmt(&self.format, f)?;
+        if let Some(encoding) = self.basis {
+            write!(f, "/{encoding:?}")?;
+        }
+        Ok(())
     }
 }
 
 pub struct Texture {
     pub object: blade_graphics::Texture,
     pub view: blade_graphics::TextureView,
+    /// Extent of the base mip level, even if only the mip tail is in `object`.
     pub extent: blade_graphics::Extent,
+    /// Size of the data in `object`.
+    size: u64,
+    stream_source: Option<Arc<StreamSource>>,
+}
+
+impl Texture {
+    /// Return true if the higher mips of this texture are streamed in on demand.
+    pub fn is_streamed(&self) -> bool {
+        self.stream_source.is_some()
+    }
+}
+
+/// Configuration of the texture mip streaming.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StreamingConfig {
+    /// Largest dimension of the mip tail, which is always resident.
+    pub tail_size: u32,
+    /// GPU memory, in bytes, that the streamed mips can occupy.
+    pub budget: u64,
+    /// Maximum number of bytes to upload in a frame.
+    pub upload_limit: u64,
+    /// Bias of the mip levels requested from the distance to the camera.
+    pub mip_bias: f32,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            tail_size: 64,
+            budget: 256 << 20,
+            upload_limit: 16 << 20,
+            mip_bias: 0.0,
+        }
+    }
+}
+
+/// Complete mip chain of a streamed texture, kept in the system memory.
+struct StreamSource {
+    name: String,
+    format: blade_graphics::TextureFormat,
+    extent: blade_graphics::Extent,
+    mip_offsets: Vec<u64>,
+    data: Vec<u8>,
+    /// First mip level of the tail that is always resident.
+    tail_mip: u32,
+}
+
+impl StreamSource {
+    fn image(&self) -> CookedImage<'_> {
+        CookedImage {
+            name: self.name.as_bytes(),
+            extent: [self.extent.width, self.extent.height, self.extent.depth],
+            format: TextureFormatWrap(self.format),
+            mip_offsets: self.mip_offsets.clone(),
+            data: &self.data,
+            is_basis: 0,
+        }
+    }
+
+    /// Size of the mip chain starting from the given level.
+    fn size_from(&self, level: u32) -> u64 {
+        self.data.len() as u64 - self.mip_offsets[level as usize]
+    }
+}
+
+struct ResidentMips {
+    object: blade_graphics::Texture,
+    view: blade_graphics::TextureView,
+    base_mip: u32,
+    size: u64,
+}
+
+struct StreamEntry {
+    source: Arc<StreamSource>,
+    requested_mip: u32,
+    last_request: u64,
+    resident: Option<ResidentMips>,
+}
+
+impl StreamEntry {
+    fn base_mip(&self) -> u32 {
+        match self.resident {
+            Some(ref resident) => resident.base_mip,
+            None => self.source.tail_mip,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Streaming {
+    config: Option<StreamingConfig>,
+    frame_index: u64,
+    entries: HashMap<blade_asset::Handle<Texture>, StreamEntry>,
+    resident_size: u64,
+}
+
+impl Streaming {
+    fn stream_out(
+        &mut self,
+        handle: blade_asset::Handle<Texture>,
+        temp: &mut crate::FrameResources,
+    ) {
+        let entry = self.entries.get_mut(&handle).unwrap();
+        if let Some(resident) = entry.resident.take() {
+            self.resident_size -= resident.size;
+            temp.texture_views.push(resident.view);
+            temp.textures.push(resident.object);
+        }
+    }
+
+    /// Find the resident texture that was requested the longest time ago,
+    /// but before the given frame.
+    fn find_least_recent(&self, before_frame: u64) -> Option<blade_asset::Handle<Texture>> {
+        self.entries
+            .iter()
+            .filter(|&(_, entry)| entry.resident.is_some() && entry.last_request < before_frame)
+            .min_by_key(|&(_, entry)| entry.last_request)
+            .map(|(&handle, _)| handle)
+    }
 }
 
 struct Initialization {
@@ -61,6 +236,7 @@ struct PendingOperations {
 pub struct Baker {
     gpu_context: Arc<blade_graphics::Context>,
     pending_operations: Mutex<PendingOperations>,
+    streaming: Mutex<Streaming>,
 }
 
 impl Baker {
@@ -68,6 +244,7 @@ impl Baker {
         Self {
             gpu_context: Arc::clone(gpu_context),
             pending_operations: Mutex::new(PendingOperations::default()),
+            streaming: Mutex::new(Streaming::default()),
         }
     }
 
@@ -102,26 +279,105 @@ impl Baker {
 }
 
 impl Baker {
+    /// Texture usage for the uploaded contents, which also tells if
+    /// they can be written directly, skipping the staging buffers and copies.
+    fn upload_usage(&self, format: blade_graphics::TextureFormat) -> blade_graphics::TextureUsage {
+        let usage = blade_graphics::TextureUsage::COPY | blade_graphics::TextureUsage::RESOURCE;
+        let host_write = usage | blade_graphics::TextureUsage::HOST_WRITE;
+        if self
+            .gpu_context
+            .supported_texture_usage(format)
+            .contains(host_write)
+        {
+            host_write
+        } else {
+            usage
+        }
+    }
+
     /// Create a texture directly from RGBA u8 pixel data (4 bytes per pixel).
     /// Uses `Rgba8Unorm` format which supports linear filtering on all GPUs.
     pub fn create_texture(&self, name: &str, width: u32, height: u32, data: &[[u8; 4]]) -> Texture {
-        use blade_graphics as gpu;
-
         assert_eq!(data.len(), (width * height) as usize);
-        let format = gpu::TextureFormat::Rgba8Unorm;
-        let extent = gpu::Extent {
-            width,
-            height,
-            depth: 1,
+        let byte_data = unsafe {
+            std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data))
         };
+        self.create_texture_from_bytes(
+            name,
+            blade_graphics::Extent {
+                width,
+                height,
+                depth: 1,
+            },
+            blade_graphics::TextureDimension::D2,
+            blade_graphics::TextureFormat::Rgba8Unorm,
+            byte_data,
+        )
+    }
+
+    /// Create a texture directly from linear RGBA f32 pixel data,
+    /// such as an environment map with a high dynamic range.
+    pub fn create_hdr_texture(
+        &self,
+        name: &str,
+        width: u32,
+        height: u32,
+        data: &[[f32; 4]],
+    ) -> Texture {
+        assert_eq!(data.len(), (width * height) as usize);
+        self.create_texture_from_bytes(
+            name,
+            blade_graphics::Extent {
+                width,
+                height,
+                depth: 1,
+            },
+            blade_graphics::TextureDimension::D2,
+            blade_graphics::TextureFormat::Rgba32Float,
+            bytemuck::cast_slice(data),
+        )
+    }
+
+    /// Create a 3D texture directly from linear RGBA f32 texel data,
+    /// laid out slice by slice.
+    pub fn create_volume_texture(
+        &self,
+        name: &str,
+        extent: blade_graphics::Extent,
+        data: &[[f32; 4]],
+    ) -> Texture {
+        assert_eq!(
+            data.len(),
+            (extent.width * extent.height * extent.depth) as usize
+        );
+        self.create_texture_from_bytes(
+            name,
+            extent,
+            blade_graphics::TextureDimension::D3,
+            blade_graphics::TextureFormat::Rgba32Float,
+            bytemuck::cast_slice(data),
+        )
+    }
+
+    fn create_texture_from_bytes(
+        &self,
+        name: &str,
+        extent: blade_graphics::Extent,
+        dimension: blade_graphics::TextureDimension,
+        format: blade_graphics::TextureFormat,
+        byte_data: &[u8],
+    ) -> Texture {
+        use blade_graphics as gpu;
+
+        let usage = self.upload_usage(format);
         let texture = self.gpu_context.create_texture(gpu::TextureDesc {
             name,
             format,
             size: extent,
             array_layer_count: 1,
             mip_level_count: 1,
-            dimension: gpu::TextureDimension::D2,
-            usage: gpu::TextureUsage::COPY | gpu::TextureUsage::RESOURCE,
+            dimension,
+            usage,
             sample_count: 1,
             external: None,
         });
@@ -130,41 +386,413 @@ impl Baker {
             gpu::TextureViewDesc {
                 name,
                 format,
-                dimension: gpu::ViewDimension::D2,
+                dimension: match dimension {
+                    gpu::TextureDimension::D1 => gpu::ViewDimension::D1,
+                    gpu::TextureDimension::D2 => gpu::ViewDimension::D2,
+                    gpu::TextureDimension::D3 => gpu::ViewDimension::D3,
+                },
                 subresources: &Default::default(),
             },
         );
 
-        let byte_data = unsafe {
-            std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data))
-        };
-        let stage = self.gpu_context.create_buffer(gpu::BufferDesc {
-            name: &format!("{name}/stage"),
+        let bytes_per_row = byte_data.len() as u32 / (extent.height * extent.depth);
+        if usage.contains(gpu::TextureUsage::HOST_WRITE) {
+            self.gpu_context
+                .write_texture(texture.into(), byte_data, bytes_per_row, extent);
+        } else {
+            let stage = self.gpu_context.create_buffer(gpu::BufferDesc {
+                name: &format!("{name}/stage"),
+                size: byte_data.len() as u64,
+                memory: gpu::Memory::Upload,
+            });
+            unsafe {
+                ptr::copy_nonoverlapping(byte_data.as_ptr(), stage.data(), byte_data.len());
+            }
+
+            let mut pending_ops = self.pending_operations.lock().unwrap();
+            pending_ops
+                .initializations
+                .push(Initialization { dst: texture });
+            pending_ops.transfers.push(Transfer {
+                stage,
+                bytes_per_row,
+                dst: texture,
+                extent,
+                mip_level: 0,
+            });
+        }
+
+        Texture {
+            object: texture,
+            view,
+            extent,
             size: byte_data.len() as u64,
-            memory: gpu::Memory::Upload,
-        });
-        unsafe {
-            ptr::copy_nonoverlapping(byte_data.as_ptr(), stage.data(), byte_data.len());
+            stream_source: None,
+        }
+    }
+
+    /// Create a texture with the mip levels of the image starting from `base_mip`,
+    /// and schedule the upload of their contents.
+    ///
+    /// Nothing is left behind if the GPU can't fit the texture or its staging data.
+    fn create_mips(
+        &self,
+        image: &CookedImage<'_>,
+        base_mip: u32,
+    ) -> Result<(blade_graphics::Texture, blade_graphics::TextureView), blade_graphics::ResourceError>
+    {
+        let name = str::from_utf8(image.name).unwrap();
+        let format = image.format.0;
+        let base_extent = blade_graphics::Extent {
+            width: image.extent[0],
+            height: image.extent[1],
+            depth: image.extent[2],
+        };
+        let mip_count = image.mip_offsets.len() as u32;
+        let usage = self.upload_usage(format);
+        let host_write = usage.contains(blade_graphics::TextureUsage::HOST_WRITE);
+        let texture = self
+            .gpu_context
+            .try_create_texture(blade_graphics::TextureDesc {
+                name,
+                format,
+                size: base_extent.at_mip_level(base_mip),
+                array_layer_count: 1,
+                mip_level_count: mip_count - base_mip,
+                dimension: blade_graphics::TextureDimension::D2,
+                usage,
+                sample_count: 1,
+                external: None,
+            })?;
+        let view = self.gpu_context.create_texture_view(
+            texture,
+            blade_graphics::TextureViewDesc {
+                name,
+                format,
+                dimension: blade_graphics::ViewDimension::D2,
+                subresources: &Default::default(),
+            },
+        );
+
+        let mut transfers = Vec::<Transfer>::new();
+        for i in base_mip..mip_count {
+            let data = image.mip_data(i as usize);
+            let block_info = format.block_info();
+            let extent = base_extent.at_mip_level(i);
+            let bytes_per_row =
+                extent.width.div_ceil(block_info.dimensions.0 as u32) * block_info.size as u32;
+            let rows_per_image = extent.height.div_ceil(block_info.dimensions.1 as u32);
+            assert!(
+                data.len() >= rows_per_image as usize * bytes_per_row as usize,
+                "Image mip[{i}] data of size {} is insufficient for {bytes_per_row} bytes per {rows_per_image} rows",
+                data.len()
+            );
+
+            if host_write {
+                let dst = blade_graphics::TexturePiece {
+                    texture,
+                    mip_level: i - base_mip,
+                    array_layer: 0,
+                    origin: [0; 3],
+                };
+                self.gpu_context
+                    .write_texture(dst, data, bytes_per_row, extent);
+                continue;
+            }
+            let stage = match self
+                .gpu_context
+                .try_create_buffer(blade_graphics::BufferDesc {
+                    name: &format!("{name}[{i}]/stage"),
+                    size: data.len() as u64,
+                    memory: blade_graphics::Memory::Upload,
+                }) {
+                Ok(stage) => stage,
+                Err(e) => {
+                    for transfer in transfers {
+                        self.gpu_context.destroy_buffer(transfer.stage);
+                    }
+                    self.gpu_context.destroy_texture_view(view);
+                    self.gpu_context.destroy_texture(texture);
+                    return Err(e);
+                }
+            };
+            unsafe {
+                ptr::copy_nonoverlapping(data.as_ptr(), stage.data(), data.len());
+            }
+            transfers.push(Transfer {
+                stage,
+                bytes_per_row,
+                dst: texture,
+                extent,
+                mip_level: i - base_mip,
+            });
         }
 
-        let bytes_per_row = width * 4;
         let mut pending_ops = self.pending_operations.lock().unwrap();
-        pending_ops
-            .initializations
-            .push(Initialization { dst: texture });
-        pending_ops.transfers.push(Transfer {
-            stage,
-            bytes_per_row,
-            dst: texture,
-            extent,
-            mip_level: 0,
-        });
+        if !host_write {
+            pending_ops
+                .initializations
+                .push(Initialization { dst: texture });
+        }
+        pending_ops.transfers.extend(transfers);
+        Ok((texture, view))
+    }
+
+    /// Create a texture from the cooked image, uploading the mip tail
+    /// if the texture is streamed, or all of the mips otherwise.
+    ///
+    /// The top mips are dropped if the GPU can't fit them, and the image
+    /// is replaced by a white texel if it can't fit any.
+    fn serve_image(&self, image: CookedImage<'_>) -> Texture {
+        let name = str::from_utf8(image.name).unwrap();
+        let base_extent = blade_graphics::Extent {
+            width: image.extent[0],
+            height: image.extent[1],
+            depth: image.extent[2],
+        };
+        let mip_count = image.mip_offsets.len() as u32;
+        // Only the mip tail is uploaded initially, if the texture is streamed.
+        let mut tail_mip = match self.streaming.lock().unwrap().config {
+            Some(config) => (0..mip_count)
+                .find(|&i| {
+                    let extent = base_extent.at_mip_level(i);
+                    extent.width.max(extent.height) <= config.tail_size
+                })
+                .unwrap_or(mip_count - 1),
+            None => 0,
+        };
+        let (texture, view) = loop {
+            match self.create_mips(&image, tail_mip) {
+                Ok(mips) => break mips,
+                Err(e) if tail_mip + 1 < mip_count => {
+                    log::warn!("Dropping mip {tail_mip} of texture '{name}': {e}");
+                    tail_mip += 1;
+                }
+                Err(e) => {
+                    log::error!("Skipping texture '{name}': {e}");
+                    return self.create_texture(name, 1, 1, &[[0xFF; 4]]);
+                }
+            }
+        };
+        let stream_source = if tail_mip != 0 {
+            Some(Arc::new(StreamSource {
+                name: name.to_string(),
+                format: image.format.0,
+                extent: base_extent,
+                mip_offsets: image.mip_offsets.clone(),
+                data: image.data.to_vec(),
+                tail_mip,
+            }))
+        } else {
+            None
+        };
 
         Texture {
             object: texture,
             view,
-            extent,
+            extent: base_extent,
+            size: image.data.len() as u64 - image.mip_offsets[tail_mip as usize],
+            stream_source,
+        }
+    }
+
+    /// Enable streaming of the texture mips, or disable it with `None`.
+    ///
+    /// Only the textures served afterwards are streamed.
+    pub fn set_streaming(&self, config: Option<StreamingConfig>) {
+        self.streaming.lock().unwrap().config = config;
+    }
+
+    pub fn streaming_config(&self) -> Option<StreamingConfig> {
+        self.streaming.lock().unwrap().config
+    }
+
+    /// Return the view of the currently resident mips of a texture.
+    pub fn resident_view(
+        &self,
+        handle: blade_asset::Handle<Texture>,
+        texture: &Texture,
+    ) -> blade_graphics::TextureView {
+        if texture.stream_source.is_none() {
+            return texture.view;
+        }
+        let streaming = self.streaming.lock().unwrap();
+        match streaming.entries.get(&handle) {
+            Some(&StreamEntry {
+                resident: Some(ref resident),
+                ..
+            }) => resident.view,
+            _ => texture.view,
+        }
+    }
+
+    /// Request a mip level of a texture to be resident in the current frame.
+    pub fn request_mip(
+        &self,
+        handle: blade_asset::Handle<Texture>,
+        texture: &Texture,
+        mip_level: u32,
+    ) {
+        let source = match texture.stream_source {
+            Some(ref source) => source,
+            None => return,
+        };
+        let mut streaming = self.streaming.lock().unwrap();
+        let frame_index = streaming.frame_index;
+        let entry = streaming
+            .entries
+            .entry(handle)
+            .or_insert_with(|| StreamEntry {
+                source: Arc::clone(source),
+                requested_mip: source.tail_mip,
+                last_request: frame_index,
+                resident: None,
+            });
+        let mip_level = mip_level.min(source.tail_mip);
+        if entry.last_request != frame_index {
+            entry.requested_mip = mip_level;
+            entry.last_request = frame_index;
+        } else {
+            entry.requested_mip = entry.requested_mip.min(mip_level);
+        }
+    }
+
+    /// Total size of the streamed mips that are currently resident.
+    pub fn streamed_size(&self) -> u64 {
+        self.streaming.lock().unwrap().resident_size
+    }
+
+    /// Stream the texture mips in and out according to the requests of the current frame.
+    ///
+    /// Replaced textures are put into `temp`, to be destroyed once the frame is retired.
+    pub fn stream(
+        &self,
+        encoder: &mut blade_graphics::CommandEncoder,
+        temp: &mut crate::FrameResources,
+    ) {
+        let mut streaming = self.streaming.lock().unwrap();
+        let config = match streaming.config {
+            Some(config) => config,
+            None => return,
+        };
+        let frame_index = streaming.frame_index;
+        streaming.frame_index += 1;
+
+        // Stream out the textures that are no longer used
+        let stale = streaming
+            .entries
+            .iter()
+            .filter(|&(_, entry)| entry.last_request + STREAM_OUT_DELAY <= frame_index)
+            .map(|(&handle, _)| handle)
+            .collect::<Vec<_>>();
+        for handle in stale {
+            streaming.stream_out(handle, temp);
+            streaming.entries.remove(&handle);
+        }
+        while streaming.resident_size > config.budget {
+            let handle = streaming.find_least_recent(frame_index + 1).unwrap();
+            streaming.stream_out(handle, temp);
+        }
+
+        // Stream in the textures that gain the most mip levels first
+        let mut candidates = streaming
+            .entries
+            .iter()
+            .filter(|&(_, entry)| {
+                entry.last_request == frame_index && entry.requested_mip < entry.base_mip()
+            })
+            .map(|(&handle, entry)| (handle, entry.base_mip() - entry.requested_mip))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|&(_, gain)| cmp::Reverse(gain));
+
+        let mut upload_size = 0;
+        for (handle, _) in candidates {
+            if upload_size >= config.upload_limit {
+                break;
+            }
+            let entry = &streaming.entries[&handle];
+            let source = Arc::clone(&entry.source);
+            let base_mip = entry.base_mip();
+            let mut target_mip = entry.requested_mip;
+            let old_size = entry.resident.as_ref().map_or(0, |resident| resident.size);
+            // Make room by streaming out the textures that aren't needed in this frame
+            while streaming.resident_size - old_size + source.size_from(target_mip) > config.budget
+            {
+                match streaming.find_least_recent(frame_index) {
+                    Some(other) => streaming.stream_out(other, temp),
+                    None => target_mip += 1,
+                }
+                if target_mip >= base_mip {
+                    break;
+                }
+            }
+            if target_mip >= base_mip {
+                continue;
+            }
+
+            // Stream in fewer mips if the GPU can't fit them
+            let mut mips = None;
+            while target_mip < base_mip {
+                match self.create_mips(&source.image(), target_mip) {
+                    Ok(result) => {
+                        mips = Some(result);
+                        break;
+                    }
+                    Err(e) => {
+                        log::warn!(
+                            "Unable to stream in mip {target_mip} of texture '{}': {e}",
+                            source.name
+                        );
+                        target_mip += 1;
+                    }
+                }
+            }
+            let Some((object, view)) = mips else {
+                continue;
+            };
+            let size = source.size_from(target_mip);
+            streaming.stream_out(handle, temp);
+            streaming.resident_size += size;
+            streaming.entries.get_mut(&handle).unwrap().resident = Some(ResidentMips {
+                object,
+                view,
+                base_mip: target_mip,
+                size,
+            });
+            upload_size += size;
+        }
+        drop(streaming);
+
+        self.flush(encoder, &mut temp.buffers);
+    }
+
+    /// Stop streaming a texture, which got replaced by a new one.
+    ///
+    /// The streamed mips are put into `temp`.
+    pub fn forget_streamed(
+        &self,
+        handle: blade_asset::Handle<Texture>,
+        temp: &mut crate::FrameResources,
+    ) {
+        let mut streaming = self.streaming.lock().unwrap();
+        if streaming.entries.contains_key(&handle) {
+            streaming.stream_out(handle, temp);
+            streaming.entries.remove(&handle);
+        }
+    }
+
+    /// Destroy all the streamed mips.
+    ///
+    /// Expects the GPU to be done with them.
+    pub fn clear_streaming(&self) {
+        let mut streaming = self.streaming.lock().unwrap();
+        for (_, entry) in streaming.entries.drain() {
+            if let Some(resident) = entry.resident {
+                self.gpu_context.destroy_texture_view(resident.view);
+                self.gpu_context.destroy_texture(resident.object);
+            }
         }
+        streaming.resident_size = 0;
     }
 }
 
@@ -183,7 +811,7 @@ impl blade_asset::Baker for Baker {
         use blade_graphics::TextureFormat as Tf;
 
         type LdrTexel = [u8; 4];
-        type HdrTexel = [f32; 3];
+        type HdrTexel = [f32; 4];
         enum PlainData {
             Ldr(Vec<LdrTexel>),
             Hdr(Vec<HdrTexel>),
@@ -194,6 +822,36 @@ impl blade_asset::Baker for Baker {
             data: PlainData,
         }
 
+        #[cfg(feature = "asset")]
+        if extension == "ktx2" {
+            profiling::scope!("read ktx2");
+            ktx::cook(source, &meta, &cooker);
+            return;
+        }
+        #[cfg(feature = "asset")]
+        if extension == "dds" {
+            profiling::scope!("read dds");
+            dds::cook(source, &meta, &cooker);
+            return;
+        }
+        #[cfg(feature = "asset")]
+        if extension == "basis" {
+            // Already encoded, only needs the transcoding when served
+            let extent = match basis::base_extent(source) {
+                Some(extent) => extent,
+                None => panic!("Unable to parse Basis"),
+            };
+            cooker.finish(CookedImage {
+                name: &[],
+                extent: [extent[0], extent[1], 1],
+                format: TextureFormatWrap(basis::fallback_format(meta.format.is_srgb())),
+                mip_offsets: vec![0],
+                data: source,
+                is_basis: 1,
+            });
+            return;
+        }
+
         let src: PlainImage = match extension {
             #[cfg(feature = "asset")]
             "png" => {
@@ -259,7 +917,9 @@ impl blade_asset::Baker for Baker {
                 PlainImage {
                     width,
                     height,
-                    data: PlainData::Hdr(data),
+                    data: PlainData::Hdr(
+                        data.into_iter().map(|[r, g, b]| [r, g, b, 1.0]).collect(),
+                    ),
                 }
             }
             #[cfg(feature = "asset")]
@@ -270,26 +930,62 @@ impl blade_asset::Baker for Baker {
                     width: usize,
                     data: Vec<HdrTexel>,
                 }
-                let image = exr::image::read::read()
+                let layer_count = match exr::meta::MetaData::read_from_buffered(source, false) {
+                    Ok(meta_data) => meta_data.headers.len(),
+                    Err(e) => panic!("Unable to parse EXR: {e}"),
+                };
+                if layer_count > 1 {
+                    log::warn!("Only the first RGB layer of {layer_count} EXR layers is used");
+                }
+                // Half samples are converted to floats, and the missing alpha is 1.0
+                let image = match exr::image::read::read()
                     .no_deep_data()
                     .largest_resolution_level()
                     .rgba_channels(
                         |size, _| RawImage {
                             width: size.width(),
-                            data: vec![[0f32; 3]; size.width() * size.height()],
+                            data: vec![[0f32; 4]; size.width() * size.height()],
                         },
-                        |image, position, (r, g, b, _): (f32, f32, f32, f32)| {
-                            image.data[position.y() * image.width + position.x()] = [r, g, b];
+                        |image, position, (r, g, b, a): (f32, f32, f32, f32)| {
+                            image.data[position.y() * image.width + position.x()] = [r, g, b, a];
                         },
                     )
                     .first_valid_layer()
                     .all_attributes()
                     .from_buffered(io::Cursor::new(source))
-                    .unwrap();
+                {
+                    Ok(image) => image,
+                    Err(e) => panic!("Unable to decode EXR: {e}"),
+                };
+                // The data window may only cover a part of the display window,
+                // with the rest being transparent black.
+                let display = image.attributes.display_window;
+                let layer = &image.layer_data;
+                let offset = layer.attributes.layer_position - display.position;
+                let (width, height) = (display.size.width(), display.size.height());
+                let mut data = vec![[0f32; 4]; width * height];
+                for (y, row) in layer
+                    .channel_data
+                    .pixels
+                    .data
+                    .chunks(layer.size.width())
+                    .enumerate()
+                {
+                    let dst_y = y as i32 + offset.y();
+                    if dst_y < 0 || dst_y >= height as i32 {
+                        continue;
+                    }
+                    for (x, &texel) in row.iter().enumerate() {
+                        let dst_x = x as i32 + offset.x();
+                        if dst_x >= 0 && dst_x < width as i32 {
+                            data[dst_y as usize * width + dst_x as usize] = texel;
+                        }
+                    }
+                }
                 PlainImage {
-                    width: image.layer_data.size.width(),
-                    height: image.layer_data.size.height(),
-                    data: PlainData::Hdr(image.layer_data.channel_data.pixels.data),
+                    width,
+                    height,
+                    data: PlainData::Hdr(data),
                 }
             }
             other => panic!("Unknown texture extension: {}", other),
@@ -302,6 +998,20 @@ impl blade_asset::Baker for Baker {
                     profiling::scope!("y-flip");
                     zune_imageprocs::flip::vertical_flip(&mut data, src.width);
                 }
+                if let Some(encoding) = meta.basis {
+                    profiling::scope!("encode basis");
+                    let (width, height) = (src.width as u32, src.height as u32);
+                    let encoded = basis::encode(&data, width, height, encoding, &meta);
+                    cooker.finish(CookedImage {
+                        name: &[],
+                        extent: [width, height, 1],
+                        format: TextureFormatWrap(basis::fallback_format(meta.format.is_srgb())),
+                        mip_offsets: vec![0],
+                        data: &encoded,
+                        is_basis: 1,
+                    });
+                    return;
+                }
 
                 let dst_format = match meta.format {
                     Tf::Bc1Unorm | Tf::Bc1UnormSrgb => texpresso::Format::Bc1,
@@ -407,36 +1117,49 @@ impl blade_asset::Baker for Baker {
                 exe_context
                     .fork("finish")
                     .init(move |_| {
+                        let mut mip_offsets = Vec::with_capacity(mips.len());
+                        let mut data = Vec::new();
+                        for mip in mips.iter() {
+                            mip_offsets.push(data.len() as u64);
+                            data.extend_from_slice(mip);
+                        }
                         cooker.finish(CookedImage {
                             name: &[],
                             extent: [base_extent.width, base_extent.height, base_extent.depth],
                             format: TextureFormatWrap(meta.format),
-                            mips: mips.iter().map(|data| CookedMip { data }).collect(),
+                            mip_offsets,
+                            data: &data,
+                            is_basis: 0,
                         });
                     })
                     .depend_on(&compress_task);
             }
             PlainData::Hdr(data) => {
+                assert!(meta.basis.is_none(), "Basis doesn't support HDR textures");
                 //TODO: compress as BC6E
-                //Note: we convert RGB32 to RGBA32 here, for now
-                assert_eq!(meta.format, blade_graphics::TextureFormat::Rgba32Float);
-                let in_texel_elements = data[0].len();
-                let out_texel_size = 4 * mem::size_of::<f32>();
-                let mut buf = vec![0u8; data.len() * out_texel_size];
-                for (slice, texel) in buf.chunks_mut(out_texel_size).zip(data) {
-                    unsafe {
-                        ptr::copy_nonoverlapping(
-                            texel.as_ptr(),
-                            slice.as_mut_ptr() as *mut f32,
-                            in_texel_elements,
-                        )
+                // Half floats take half of the memory, but can't go above 65504
+                let buf = match meta.format {
+                    Tf::Rgba32Float => bytemuck::cast_slice::<_, u8>(&data).to_vec(),
+                    Tf::Rgba16Float => {
+                        profiling::scope!("convert to f16");
+                        let halves = data
+                            .iter()
+                            .flatten()
+                            .map(|&value| exr::prelude::f16::from_f32(value).to_bits())
+                            .collect::<Vec<u16>>();
+                        bytemuck::cast_slice::<_, u8>(&halves).to_vec()
                     }
-                }
+                    other => panic!(
+                        "Unsupported HDR texture format {other:?}, only Rgba16Float and Rgba32Float are supported"
+                    ),
+                };
                 cooker.finish(CookedImage {
                     name: &[],
                     extent: [src.width as u32, src.height as u32, 1],
                     format: TextureFormatWrap(meta.format),
-                    mips: vec![CookedMip { data: &buf }],
+                    mip_offsets: vec![0],
+                    data: &buf,
+                    is_basis: 0,
                 });
             }
         }
@@ -447,75 +1170,40 @@ impl blade_asset::Baker for Baker {
         image: CookedImage<'_>,
         _exe_context: &choir::ExecutionContext,
     ) -> Self::Output {
-        let name = str::from_utf8(image.name).unwrap();
-        let base_extent = blade_graphics::Extent {
-            width: image.extent[0],
-            height: image.extent[1],
-            depth: image.extent[2],
-        };
-        let texture = self
-            .gpu_context
-            .create_texture(blade_graphics::TextureDesc {
-                name,
-                format: image.format.0,
-                size: base_extent,
-                array_layer_count: 1,
-                mip_level_count: image.mips.len() as u32,
-                dimension: blade_graphics::TextureDimension::D2,
-                usage: blade_graphics::TextureUsage::COPY | blade_graphics::TextureUsage::RESOURCE,
-                sample_count: 1,
-                external: None,
-            });
-        let view = self.gpu_context.create_texture_view(
-            texture,
-            blade_graphics::TextureViewDesc {
-                name,
-                format: image.format.0,
-                dimension: blade_graphics::ViewDimension::D2,
-                subresources: &Default::default(),
-            },
-        );
-        self.pending_operations
-            .lock()
-            .unwrap()
-            .initializations
-            .push(Initialization { dst: texture });
-
-        for (i, mip) in image.mips.iter().enumerate() {
-            let stage = self.gpu_context.create_buffer(blade_graphics::BufferDesc {
-                name: &format!("{name}[{i}]/stage"),
-                size: mip.data.len() as u64,
-                memory: blade_graphics::Memory::Upload,
-            });
-            unsafe {
-                ptr::copy_nonoverlapping(mip.data.as_ptr(), stage.data(), mip.data.len());
+        if image.is_basis != 0 {
+            #[cfg(feature = "asset")]
+            {
+                profiling::scope!("transcode basis");
+                let usage =
+                    blade_graphics::TextureUsage::COPY | blade_graphics::TextureUsage::RESOURCE;
+                let transcoded = basis::transcode(image.data, image.format.0.is_srgb(), |format| {
+                    self.gpu_context
+                        .supported_texture_usage(format)
+                        .contains(usage)
+                });
+                return self.serve_image(CookedImage {
+                    format: TextureFormatWrap(transcoded.format),
+                    mip_offsets: transcoded.mip_offsets,
+                    data: &transcoded.data,
+                    is_basis: 0,
+                    ..image
+                });
             }
-
-            let block_info = image.format.0.block_info();
-            let extent = base_extent.at_mip_level(i as u32);
-            let bytes_per_row =
-                extent.width.div_ceil(block_info.dimensions.0 as u32) * block_info.size as u32;
-            let rows_per_image = extent.height.div_ceil(block_info.dimensions.1 as u32);
-            assert!(
-                mip.data.len() >= rows_per_image as usize * bytes_per_row as usize,
-                "Image mip[{i}] data of size {} is insufficient for {bytes_per_row} bytes per {rows_per_image} rows",
-                mip.data.len()
-            );
-
-            let mut pending_ops = self.pending_operations.lock().unwrap();
-            pending_ops.transfers.push(Transfer {
-                stage,
-                bytes_per_row,
-                dst: texture,
-                extent,
-                mip_level: i as u32,
-            });
+            #[cfg(not(feature = "asset"))]
+            panic!("Basis transcoding requires the \"asset\" feature");
         }
+        self.serve_image(image)
+    }
 
-        Texture {
-            object: texture,
-            view,
-            extent: base_extent,
+    /// The streamed mips aren't included, see `streamed_size`.
+    fn footprint(&self, texture: &Self::Output) -> blade_asset::Footprint {
+        let source_size = texture
+            .stream_source
+            .as_ref()
+            .map_or(0, |source| source.data.len());
+        blade_asset::Footprint {
+            cpu: (mem::size_of::<Texture>() + source_size) as u64,
+            gpu: texture.size,
         }
     }
 
@@ -1,4 +1,4 @@
-use crate::render::FrameResources;
+use crate::FrameResources;
 use std::mem;
 
 /// Utility object that encapsulates the logic
@@ -38,6 +38,12 @@ impl FramePacer {
         for accel_structure in self.prev_resources.acceleration_structures.drain(..) {
             context.destroy_acceleration_structure(accel_structure);
         }
+        for view in self.prev_resources.texture_views.drain(..) {
+            context.destroy_texture_view(view);
+        }
+        for texture in self.prev_resources.textures.drain(..) {
+            context.destroy_texture(texture);
+        }
     }
 
     pub fn last_sync_point(&self) -> Option<&blade_graphics::SyncPoint> {
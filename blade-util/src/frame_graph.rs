@@ -0,0 +1,412 @@
+use blade_graphics as gpu;
+use std::mem;
+
+/// Texture declared in a frame graph.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub struct GraphTexture(usize);
+
+/// The way a pass accesses a texture, which decides its usage.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum TextureAccess {
+    /// Sampled or loaded in shaders.
+    Resource,
+    /// Read or written as a storage texture.
+    Storage,
+    /// Rendered into as a color or depth-stencil target.
+    Target,
+    /// Copied from or into by a transfer pass.
+    Copy,
+}
+
+impl TextureAccess {
+    fn usage(self) -> gpu::TextureUsage {
+        match self {
+            Self::Resource => gpu::TextureUsage::RESOURCE,
+            Self::Storage => gpu::TextureUsage::STORAGE,
+            Self::Target => gpu::TextureUsage::TARGET,
+            Self::Copy => gpu::TextureUsage::COPY,
+        }
+    }
+}
+
+/// Configuration of a transient 2D texture, which only lives within a frame.
+#[derive(Clone, Copy, Debug)]
+pub struct TransientTextureDesc<'a> {
+    pub name: &'a str,
+    pub format: gpu::TextureFormat,
+    pub size: gpu::Extent,
+}
+
+/// Textures of the graph, available to the passes during recording.
+pub struct PassResources<'a> {
+    /// Transient textures that no live pass uses are not allocated.
+    textures: &'a [Option<(gpu::Texture, gpu::TextureView)>],
+}
+
+impl PassResources<'_> {
+    fn resolve(&self, texture: GraphTexture) -> (gpu::Texture, gpu::TextureView) {
+        self.textures[texture.0].expect("Texture is not used by any live pass")
+    }
+
+    pub fn texture(&self, texture: GraphTexture) -> gpu::Texture {
+        self.resolve(texture).0
+    }
+
+    pub fn view(&self, texture: GraphTexture) -> gpu::TextureView {
+        self.resolve(texture).1
+    }
+}
+
+type RecordFn<'a> = Box<dyn FnOnce(&mut gpu::CommandEncoder, &PassResources) + 'a>;
+
+struct Pass<'a> {
+    name: String,
+    reads: Vec<(GraphTexture, TextureAccess)>,
+    writes: Vec<(GraphTexture, TextureAccess)>,
+    record: RecordFn<'a>,
+}
+
+enum Declaration {
+    Imported(gpu::Texture, gpu::TextureView),
+    Transient {
+        name: String,
+        format: gpu::TextureFormat,
+        size: gpu::Extent,
+    },
+}
+
+/// Passes and textures of a single frame.
+///
+/// Passes are executed in the order they are added, so a texture
+/// has to be written by one pass before another one can read it.
+#[derive(Default)]
+pub struct FrameGraphBuilder<'a> {
+    textures: Vec<Declaration>,
+    passes: Vec<Pass<'a>>,
+}
+
+impl<'a> FrameGraphBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a texture that lives outside of the graph.
+    ///
+    /// Passes writing into imported textures are never culled.
+    pub fn import_texture(
+        &mut self,
+        texture: gpu::Texture,
+        view: gpu::TextureView,
+    ) -> GraphTexture {
+        self.textures.push(Declaration::Imported(texture, view));
+        GraphTexture(self.textures.len() - 1)
+    }
+
+    /// Declare a transient texture, allocated by the graph.
+    ///
+    /// Its contents are undefined when the first pass accesses it,
+    /// so that pass has to overwrite it, e.g. by clearing.
+    pub fn create_texture(&mut self, desc: TransientTextureDesc) -> GraphTexture {
+        assert_eq!(desc.size.depth, 1, "Transient textures are 2D");
+        self.textures.push(Declaration::Transient {
+            name: desc.name.to_string(),
+            format: desc.format,
+            size: desc.size,
+        });
+        GraphTexture(self.textures.len() - 1)
+    }
+
+    /// Start declaring a new pass.
+    pub fn add_pass<'b>(&'b mut self, name: &str) -> PassBuilder<'b, 'a> {
+        PassBuilder {
+            builder: self,
+            name: name.to_string(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+        }
+    }
+}
+
+/// Declaration of the textures accessed by a pass.
+pub struct PassBuilder<'b, 'a> {
+    builder: &'b mut FrameGraphBuilder<'a>,
+    name: String,
+    reads: Vec<(GraphTexture, TextureAccess)>,
+    writes: Vec<(GraphTexture, TextureAccess)>,
+}
+
+impl<'a> PassBuilder<'_, 'a> {
+    pub fn read(mut self, texture: GraphTexture, access: TextureAccess) -> Self {
+        self.reads.push((texture, access));
+        self
+    }
+
+    pub fn write(mut self, texture: GraphTexture, access: TextureAccess) -> Self {
+        self.writes.push((texture, access));
+        self
+    }
+
+    /// Finish the pass with the function recording it.
+    ///
+    /// The encoder is in the recording state, and every pass
+    /// started by the function is synchronized with the previous ones.
+    pub fn record(self, fun: impl FnOnce(&mut gpu::CommandEncoder, &PassResources) + 'a) {
+        self.builder.passes.push(Pass {
+            name: self.name,
+            reads: self.reads,
+            writes: self.writes,
+            record: Box::new(fun),
+        });
+    }
+}
+
+/// Statistics of the last executed frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameGraphStats {
+    /// Number of recorded passes.
+    pub pass_count: usize,
+    /// Number of passes skipped because nothing used their results.
+    pub culled_pass_count: usize,
+    /// Number of transient textures used by the recorded passes.
+    pub transient_count: usize,
+    /// Number of textures backing the transient ones.
+    pub texture_count: usize,
+    /// Number of textures created for this frame.
+    pub created_texture_count: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TextureKey {
+    format: gpu::TextureFormat,
+    size: gpu::Extent,
+    usage: gpu::TextureUsage,
+}
+
+struct PooledTexture {
+    key: TextureKey,
+    texture: gpu::Texture,
+    view: gpu::TextureView,
+    /// Index of the last pass using it in the current frame.
+    busy_until: Option<usize>,
+}
+
+/// Executor of the frame graphs, keeping the transient textures between frames.
+///
+/// Passes that don't contribute to the imported textures are culled.
+/// Transient textures with the same format, size, and usage are aliased:
+/// once the last pass using one of them is recorded, the same texture
+/// is handed over to the next one. Synchronization between the passes
+/// is the same as for the raw encoders.
+#[derive(Default)]
+pub struct FrameGraph {
+    pool: Vec<PooledTexture>,
+    retired: Vec<PooledTexture>,
+    retired_in_flight: Vec<(PooledTexture, gpu::SyncPoint)>,
+    stats: FrameGraphStats,
+}
+
+impl FrameGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Destroy the graph with all of its textures.
+    pub fn destroy(&mut self, gpu: &gpu::Context) {
+        for pooled in self.pool.drain(..).chain(self.retired.drain(..)) {
+            gpu.destroy_texture_view(pooled.view);
+            gpu.destroy_texture(pooled.texture);
+        }
+        for (pooled, sp) in self.retired_in_flight.drain(..) {
+            let _ = gpu.wait_for(&sp, !0);
+            gpu.destroy_texture_view(pooled.view);
+            gpu.destroy_texture(pooled.texture);
+        }
+    }
+
+    /// Statistics of the last executed frame.
+    pub fn stats(&self) -> FrameGraphStats {
+        self.stats
+    }
+
+    /// Record the passes of the frame that contribute to the imported textures.
+    ///
+    /// The textures that weren't needed by this frame are retired,
+    /// and they have to be flushed after the submission.
+    #[profiling::function]
+    pub fn execute(
+        &mut self,
+        builder: FrameGraphBuilder,
+        encoder: &mut gpu::CommandEncoder,
+        gpu: &gpu::Context,
+    ) {
+        self.retired_in_flight.retain(|(pooled, sp)| {
+            let done = gpu.wait_for(sp, 0).unwrap_or(false);
+            if done {
+                gpu.destroy_texture_view(pooled.view);
+                gpu.destroy_texture(pooled.texture);
+            }
+            !done
+        });
+
+        let FrameGraphBuilder { textures, passes } = builder;
+        let is_live = cull(&textures, &passes);
+
+        // Lifetimes and usages of the transient textures
+        let mut lifetimes = vec![None::<(usize, usize)>; textures.len()];
+        let mut usages = vec![gpu::TextureUsage::empty(); textures.len()];
+        for (index, pass) in passes.iter().enumerate() {
+            if !is_live[index] {
+                continue;
+            }
+            for &(texture, access) in pass.reads.iter().chain(pass.writes.iter()) {
+                let lifetime = lifetimes[texture.0].get_or_insert((index, index));
+                lifetime.1 = index;
+                usages[texture.0] |= access.usage();
+            }
+        }
+
+        // Assign the pooled textures to the transient ones in the order of the first use
+        let mut order = (0..textures.len())
+            .filter(|&i| matches!(textures[i], Declaration::Transient { .. }))
+            .filter_map(|i| lifetimes[i].map(|lifetime| (i, lifetime)))
+            .collect::<Vec<_>>();
+        order.sort_by_key(|&(_, lifetime)| lifetime.0);
+        for pooled in self.pool.iter_mut() {
+            pooled.busy_until = None;
+        }
+        let mut resolved = textures
+            .iter()
+            .map(|declaration| match *declaration {
+                Declaration::Imported(texture, view) => Some((texture, view)),
+                Declaration::Transient { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        let mut created = Vec::new();
+        for &(index, (first, last)) in order.iter() {
+            let Declaration::Transient {
+                ref name,
+                format,
+                size,
+            } = textures[index]
+            else {
+                unreachable!()
+            };
+            let key = TextureKey {
+                format,
+                size,
+                usage: usages[index],
+            };
+            let pool_index =
+                match self.pool.iter().position(|pooled| {
+                    pooled.key == key && pooled.busy_until.is_none_or(|b| b < first)
+                }) {
+                    Some(pool_index) => pool_index,
+                    None => {
+                        let pooled = create_texture(name, key, gpu);
+                        created.push(pooled.texture);
+                        self.pool.push(pooled);
+                        self.pool.len() - 1
+                    }
+                };
+            let pooled = &mut self.pool[pool_index];
+            pooled.busy_until = Some(last);
+            resolved[index] = Some((pooled.texture, pooled.view));
+        }
+
+        // Only keep the textures used by this frame
+        let (used, unused) = mem::take(&mut self.pool)
+            .into_iter()
+            .partition(|pooled| pooled.busy_until.is_some());
+        self.pool = used;
+        self.retired.extend(unused);
+
+        for &texture in created.iter() {
+            encoder.init_texture(texture);
+        }
+        let resources = PassResources {
+            textures: &resolved,
+        };
+        let mut pass_count = 0;
+        for (pass, live) in passes.into_iter().zip(is_live.iter()) {
+            if *live {
+                profiling::scope!("record", &pass.name);
+                (pass.record)(encoder, &resources);
+                pass_count += 1;
+            } else {
+                log::debug!("Culling pass '{}'", pass.name);
+            }
+        }
+
+        self.stats = FrameGraphStats {
+            pass_count,
+            culled_pass_count: is_live.len() - pass_count,
+            transient_count: order.len(),
+            texture_count: self.pool.len(),
+            created_texture_count: created.len(),
+        };
+    }
+
+    /// Mark the retired textures as used by GPU with a given sync point.
+    pub fn flush(&mut self, sp: &gpu::SyncPoint) {
+        self.retired_in_flight
+            .extend(self.retired.drain(..).map(|pooled| (pooled, sp.clone())));
+    }
+}
+
+/// Find the passes contributing to the imported textures,
+/// going from the last pass to the first one.
+///
+/// Passes that don't declare any writes are kept,
+/// since their effects aren't known to the graph.
+fn cull(textures: &[Declaration], passes: &[Pass]) -> Vec<bool> {
+    let mut is_needed = textures
+        .iter()
+        .map(|declaration| matches!(*declaration, Declaration::Imported(..)))
+        .collect::<Vec<_>>();
+    let mut is_live = vec![false; passes.len()];
+    for (index, pass) in passes.iter().enumerate().rev() {
+        let live = pass.writes.is_empty() || pass.writes.iter().any(|write| is_needed[write.0.0]);
+        if live {
+            for read in pass.reads.iter() {
+                is_needed[read.0.0] = true;
+            }
+        }
+        is_live[index] = live;
+    }
+    is_live
+}
+
+fn create_texture(name: &str, key: TextureKey, gpu: &gpu::Context) -> PooledTexture {
+    log::info!(
+        "Creating transient texture '{}' of size {} and format {:?}",
+        name,
+        key.size,
+        key.format
+    );
+    let texture = gpu.create_texture(gpu::TextureDesc {
+        name,
+        format: key.format,
+        size: key.size,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        dimension: gpu::TextureDimension::D2,
+        usage: key.usage,
+        sample_count: 1,
+        external: None,
+    });
+    let view = gpu.create_texture_view(
+        texture,
+        gpu::TextureViewDesc {
+            name,
+            format: key.format,
+            dimension: gpu::ViewDimension::D2,
+            subresources: &Default::default(),
+        },
+    );
+    PooledTexture {
+        key,
+        texture,
+        view,
+        busy_until: None,
+    }
+}
@@ -0,0 +1,164 @@
+use super::BufferBelt;
+use blade_graphics as gpu;
+use std::{mem, ops::Range};
+
+/// A growable array of POD elements in GPU memory, mirrored on CPU.
+///
+/// The elements are changed on CPU, and only the changed range is uploaded
+/// by `upload`. The buffer is reallocated when the array outgrows it,
+/// and the old one is destroyed once the GPU is done with it.
+/// Changes made to the buffer by GPU are not mirrored back.
+pub struct GpuVec<T> {
+    name: String,
+    data: Vec<T>,
+    buffer: gpu::Buffer,
+    capacity: usize,
+    dirty: Range<usize>,
+    retired: Vec<gpu::Buffer>,
+    retired_in_flight: Vec<(gpu::Buffer, gpu::SyncPoint)>,
+}
+
+impl<T: bytemuck::Pod> GpuVec<T> {
+    /// Create a new array, with space for `capacity` elements on GPU.
+    pub fn new(name: &str, capacity: usize, gpu: &gpu::Context) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            name: name.to_string(),
+            data: Vec::new(),
+            buffer: Self::create_buffer(name, capacity, gpu),
+            capacity,
+            dirty: 0..0,
+            retired: Vec::new(),
+            retired_in_flight: Vec::new(),
+        }
+    }
+
+    fn create_buffer(name: &str, capacity: usize, gpu: &gpu::Context) -> gpu::Buffer {
+        gpu.create_buffer(gpu::BufferDesc {
+            name,
+            size: (capacity * mem::size_of::<T>()) as u64,
+            memory: gpu::Memory::Device,
+        })
+    }
+
+    /// Destroy this array.
+    pub fn destroy(&mut self, gpu: &gpu::Context) {
+        gpu.destroy_buffer(self.buffer);
+        for buffer in self.retired.drain(..) {
+            gpu.destroy_buffer(buffer);
+        }
+        for (buffer, sp) in self.retired_in_flight.drain(..) {
+            let _ = gpu.wait_for(&sp, !0);
+            gpu.destroy_buffer(buffer);
+        }
+    }
+
+    /// Number of elements.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Number of elements that fit into the current buffer.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Elements on CPU, including the ones that aren't uploaded yet.
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    fn mark_dirty(&mut self, range: Range<usize>) {
+        self.dirty = if self.dirty.is_empty() {
+            range
+        } else {
+            self.dirty.start.min(range.start)..self.dirty.end.max(range.end)
+        };
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.mark_dirty(self.data.len()..self.data.len() + 1);
+        self.data.push(value);
+    }
+
+    pub fn extend_from_slice(&mut self, values: &[T]) {
+        self.mark_dirty(self.data.len()..self.data.len() + values.len());
+        self.data.extend_from_slice(values);
+    }
+
+    /// Replace the element at `index`.
+    pub fn set(&mut self, index: usize, value: T) {
+        self.data[index] = value;
+        self.mark_dirty(index..index + 1);
+    }
+
+    /// Shorten the array, keeping the buffer.
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(len);
+        self.dirty.end = self.dirty.end.min(len);
+        self.dirty.start = self.dirty.start.min(self.dirty.end);
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Upload the changed elements, growing the buffer if needed.
+    ///
+    /// The data is staged in the `belt`, which needs to be flushed after the submission,
+    /// together with this array.
+    #[profiling::function]
+    pub fn upload(
+        &mut self,
+        encoder: &mut gpu::CommandEncoder,
+        belt: &mut BufferBelt,
+        gpu: &gpu::Context,
+    ) {
+        self.retired_in_flight.retain(|&(buffer, ref sp)| {
+            let done = gpu.wait_for(sp, 0).unwrap_or(false);
+            if done {
+                gpu.destroy_buffer(buffer);
+            }
+            !done
+        });
+
+        if self.data.len() > self.capacity {
+            self.capacity = self.data.len().next_power_of_two();
+            log::info!("Growing '{}' to {} elements", self.name, self.capacity);
+            let buffer = Self::create_buffer(&self.name, self.capacity, gpu);
+            self.retired.push(mem::replace(&mut self.buffer, buffer));
+            self.dirty = 0..self.data.len();
+        }
+        if self.dirty.is_empty() {
+            return;
+        }
+
+        let dirty = mem::replace(&mut self.dirty, 0..0);
+        let staging = belt.alloc_pod(&self.data[dirty.clone()], gpu);
+        let offset = (dirty.start * mem::size_of::<T>()) as u64;
+        let size = (dirty.len() * mem::size_of::<T>()) as u64;
+        let mut transfer = encoder.transfer(&self.name);
+        transfer.copy_buffer_to_buffer(staging, self.buffer.at(offset), size);
+    }
+
+    /// Mark the retired buffers as used by GPU with a given sync point.
+    pub fn flush(&mut self, sp: &gpu::SyncPoint) {
+        self.retired_in_flight
+            .extend(self.retired.drain(..).map(|buffer| (buffer, sp.clone())));
+    }
+
+    /// Buffer with the elements, for binding.
+    /// It changes when the array grows.
+    pub fn buffer(&self) -> gpu::Buffer {
+        self.buffer
+    }
+
+    /// Start of the elements, for binding.
+    pub fn piece(&self) -> gpu::BufferPiece {
+        self.buffer.into()
+    }
+}
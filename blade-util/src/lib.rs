@@ -1,3 +1,14 @@
 mod belt;
+mod frame_graph;
+mod gpu_vec;
+mod offscreen;
+mod ring;
 
 pub use belt::{BufferBelt, BufferBeltDescriptor};
+pub use frame_graph::{
+    FrameGraph, FrameGraphBuilder, FrameGraphStats, GraphTexture, PassBuilder, PassResources,
+    TextureAccess, TransientTextureDesc,
+};
+pub use gpu_vec::GpuVec;
+pub use offscreen::{OffscreenSession, OffscreenSessionDescriptor};
+pub use ring::{MappedRing, MappedRingDescriptor};
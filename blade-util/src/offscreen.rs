@@ -0,0 +1,218 @@
+use blade_graphics as gpu;
+use std::ptr;
+
+/// Configuration of an offscreen session.
+pub struct OffscreenSessionDescriptor<'a> {
+    pub name: &'a str,
+    pub size: gpu::Extent,
+    /// Format of the color target, which is read back.
+    /// It has to be an uncompressed color format.
+    pub color_format: gpu::TextureFormat,
+    /// Format of the optional depth target.
+    pub depth_format: Option<gpu::TextureFormat>,
+}
+
+/// Rendering into textures without a surface, with the results
+/// read back to the CPU. Useful for generating images on headless
+/// machines, and for testing the renderers.
+///
+/// Every frame is submitted and waited upon by `end_frame`,
+/// so there is nothing in flight in between the frames.
+pub struct OffscreenSession {
+    encoder: gpu::CommandEncoder,
+    color: gpu::Texture,
+    color_view: gpu::TextureView,
+    depth: Option<(gpu::Texture, gpu::TextureView)>,
+    readback: gpu::Buffer,
+    size: gpu::Extent,
+    color_format: gpu::TextureFormat,
+    is_initialized: bool,
+}
+
+fn create_target(
+    gpu: &gpu::Context,
+    name: &str,
+    format: gpu::TextureFormat,
+    size: gpu::Extent,
+) -> (gpu::Texture, gpu::TextureView) {
+    let texture = gpu.create_texture(gpu::TextureDesc {
+        name,
+        format,
+        size,
+        dimension: gpu::TextureDimension::D2,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        usage: gpu::TextureUsage::TARGET | gpu::TextureUsage::COPY,
+        sample_count: 1,
+        external: None,
+    });
+    let view = gpu.create_texture_view(
+        texture,
+        gpu::TextureViewDesc {
+            name,
+            format,
+            dimension: gpu::ViewDimension::D2,
+            subresources: &gpu::TextureSubresources::default(),
+        },
+    );
+    (texture, view)
+}
+
+impl OffscreenSession {
+    /// Create the targets and the command encoder of a new session.
+    pub fn new(desc: &OffscreenSessionDescriptor, gpu: &gpu::Context) -> Self {
+        let block_info = desc.color_format.block_info();
+        assert!(
+            desc.color_format.aspects() == gpu::TexelAspects::COLOR
+                && block_info.dimensions == (1, 1),
+            "Format {:?} can't be read back",
+            desc.color_format
+        );
+        let (color, color_view) = create_target(
+            gpu,
+            &format!("{}-color", desc.name),
+            desc.color_format,
+            desc.size,
+        );
+        let depth = desc
+            .depth_format
+            .map(|format| create_target(gpu, &format!("{}-depth", desc.name), format, desc.size));
+        let readback = gpu.create_buffer(gpu::BufferDesc {
+            name: &format!("{}-readback", desc.name),
+            size: (desc.size.width * desc.size.height) as u64 * block_info.size as u64,
+            memory: gpu::Memory::Shared,
+        });
+        let encoder = gpu.create_command_encoder(gpu::CommandEncoderDesc {
+            name: desc.name,
+            buffer_count: 1,
+        });
+        Self {
+            encoder,
+            color,
+            color_view,
+            depth,
+            readback,
+            size: desc.size,
+            color_format: desc.color_format,
+            is_initialized: false,
+        }
+    }
+
+    /// Destroy the session.
+    pub fn destroy(&mut self, gpu: &gpu::Context) {
+        gpu.destroy_command_encoder(&mut self.encoder);
+        gpu.destroy_buffer(self.readback);
+        gpu.destroy_texture_view(self.color_view);
+        gpu.destroy_texture(self.color);
+        if let Some((texture, view)) = self.depth.take() {
+            gpu.destroy_texture_view(view);
+            gpu.destroy_texture(texture);
+        }
+    }
+
+    pub fn size(&self) -> gpu::Extent {
+        self.size
+    }
+
+    pub fn color_format(&self) -> gpu::TextureFormat {
+        self.color_format
+    }
+
+    pub fn color_texture(&self) -> gpu::Texture {
+        self.color
+    }
+
+    pub fn color_view(&self) -> gpu::TextureView {
+        self.color_view
+    }
+
+    pub fn depth_view(&self) -> Option<gpu::TextureView> {
+        self.depth.map(|(_, view)| view)
+    }
+
+    /// Start recording a frame.
+    ///
+    /// The returned encoder can record any work before and after
+    /// the render passes, including the passes on the targets.
+    pub fn begin_frame(&mut self) -> &mut gpu::CommandEncoder {
+        self.encoder.start();
+        if !self.is_initialized {
+            self.encoder.init_texture(self.color);
+            if let Some((texture, _)) = self.depth {
+                self.encoder.init_texture(texture);
+            }
+            self.is_initialized = true;
+        }
+        &mut self.encoder
+    }
+
+    /// Encoder of the current frame.
+    pub fn encoder(&mut self) -> &mut gpu::CommandEncoder {
+        &mut self.encoder
+    }
+
+    /// Record a render pass on the targets, which clears the color to `clear_color`,
+    /// and the depth to one.
+    pub fn render_pass(
+        &mut self,
+        name: &str,
+        clear_color: gpu::TextureColor,
+        draw: impl FnOnce(&mut gpu::RenderCommandEncoder),
+    ) {
+        let mut pass = self.encoder.render(
+            name,
+            gpu::RenderTargetSet {
+                colors: &[gpu::RenderTarget {
+                    view: self.color_view,
+                    init_op: gpu::InitOp::Clear(clear_color),
+                    finish_op: gpu::FinishOp::Store,
+                }],
+                depth_stencil: self.depth.map(|(_, view)| gpu::RenderTarget {
+                    view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::White),
+                    finish_op: gpu::FinishOp::Discard,
+                }),
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        );
+        draw(&mut pass);
+    }
+
+    /// Submit the frame, wait for it, and read back the color target.
+    ///
+    /// The texels are tightly packed in rows, in the color format.
+    #[profiling::function]
+    pub fn end_frame(&mut self, gpu: &gpu::Context) -> Vec<u8> {
+        let bytes_per_row = self.size.width * self.color_format.block_info().size as u32;
+        {
+            let mut transfer = self.encoder.transfer("readback");
+            transfer.copy_texture_to_buffer(
+                self.color.into(),
+                self.readback.into(),
+                bytes_per_row,
+                self.size,
+            );
+        }
+        let sync_point = gpu.submit(&mut self.encoder);
+        let _ = gpu.wait_for(&sync_point, !0);
+        let byte_count = (bytes_per_row * self.size.height) as usize;
+        let mut data = vec![0u8; byte_count];
+        unsafe {
+            ptr::copy_nonoverlapping(self.readback.data(), data.as_mut_ptr(), byte_count);
+        }
+        data
+    }
+
+    /// Render a frame with a single render pass, and read it back.
+    pub fn render_frame(
+        &mut self,
+        gpu: &gpu::Context,
+        clear_color: gpu::TextureColor,
+        draw: impl FnOnce(&mut gpu::RenderCommandEncoder),
+    ) -> Vec<u8> {
+        self.begin_frame();
+        self.render_pass("offscreen", clear_color, draw);
+        self.end_frame(gpu)
+    }
+}
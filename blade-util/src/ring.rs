@@ -0,0 +1,125 @@
+use blade_graphics as gpu;
+use std::{marker::PhantomData, mem};
+
+/// Configuration of a mapped ring.
+pub struct MappedRingDescriptor<'a> {
+    pub name: &'a str,
+    /// Kind of memory to allocate from, has to be mapped.
+    pub memory: gpu::Memory,
+    /// Number of elements that fit into a frame.
+    pub frame_capacity: usize,
+    /// Number of frames that the GPU can use at the same time.
+    pub frame_count: usize,
+    /// Alignment of the frames and the pushed slices, in bytes.
+    pub alignment: u64,
+}
+
+/// A persistently mapped buffer, split into a ring of frames of typed data.
+///
+/// The data is pushed into the current frame, until it's handed over to the GPU
+/// with `advance`, which moves on to the next frame in the ring.
+pub struct MappedRing<T> {
+    buffer: gpu::Buffer,
+    frame_size: u64,
+    frame_stride: u64,
+    alignment: u64,
+    sync_points: Box<[Option<gpu::SyncPoint>]>,
+    current: usize,
+    offset: u64,
+    phantom: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> MappedRing<T> {
+    /// Create a new ring.
+    pub fn new(desc: MappedRingDescriptor, gpu: &gpu::Context) -> Self {
+        assert!(
+            matches!(desc.memory, gpu::Memory::Upload | gpu::Memory::Shared),
+            "Ring memory has to be mapped"
+        );
+        assert_ne!(desc.frame_count, 0);
+        let type_alignment = mem::align_of::<T>() as u64;
+        assert_eq!(
+            desc.alignment % type_alignment,
+            0,
+            "Type alignment {} is too big",
+            type_alignment
+        );
+        let frame_size = (desc.frame_capacity * mem::size_of::<T>()) as u64;
+        let frame_stride = frame_size.next_multiple_of(desc.alignment);
+        let buffer = gpu.create_buffer(gpu::BufferDesc {
+            name: desc.name,
+            size: frame_stride * desc.frame_count as u64,
+            memory: desc.memory,
+        });
+        Self {
+            buffer,
+            frame_size,
+            frame_stride,
+            alignment: desc.alignment,
+            sync_points: vec![None; desc.frame_count].into_boxed_slice(),
+            current: 0,
+            offset: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Destroy this ring.
+    pub fn destroy(&mut self, gpu: &gpu::Context) {
+        for sp in self.sync_points.iter_mut().filter_map(Option::take) {
+            let _ = gpu.wait_for(&sp, !0);
+        }
+        gpu.destroy_buffer(self.buffer);
+    }
+
+    /// Underlying buffer of all the frames.
+    pub fn buffer(&self) -> gpu::Buffer {
+        self.buffer
+    }
+
+    /// Start of the current frame.
+    pub fn frame(&self) -> gpu::BufferPiece {
+        self.buffer.at(self.current as u64 * self.frame_stride)
+    }
+
+    /// Number of elements that can still be pushed into the current frame.
+    pub fn remaining(&self) -> usize {
+        let start = self
+            .offset
+            .next_multiple_of(self.alignment)
+            .min(self.frame_size);
+        ((self.frame_size - start) / mem::size_of::<T>() as u64) as usize
+    }
+
+    /// Write the `data` slice into the current frame, returning its location.
+    pub fn push(&mut self, data: &[T]) -> gpu::BufferPiece {
+        let start = self.offset.next_multiple_of(self.alignment);
+        let end = start + mem::size_of_val(data) as u64;
+        assert!(
+            end <= self.frame_size,
+            "Frame of {} bytes can't fit {} more bytes at {}",
+            self.frame_size,
+            end - start,
+            start
+        );
+        let piece = self
+            .buffer
+            .at(self.current as u64 * self.frame_stride + start);
+        let bytes = bytemuck::cast_slice::<T, u8>(data);
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), piece.data(), bytes.len());
+        }
+        self.offset = end;
+        piece
+    }
+
+    /// Mark the current frame as used by GPU with a given sync point,
+    /// and move to the next frame, waiting for the GPU to be done with it.
+    pub fn advance(&mut self, sp: &gpu::SyncPoint, gpu: &gpu::Context) {
+        self.sync_points[self.current] = Some(sp.clone());
+        self.current = (self.current + 1) % self.sync_points.len();
+        self.offset = 0;
+        if let Some(sp) = self.sync_points[self.current].take() {
+            let _ = gpu.wait_for(&sp, !0);
+        }
+    }
+}
@@ -461,6 +461,7 @@ fn android_main(app: AndroidApp) {
             time_step: 0.01,
             render_backend: blade_engine::config::RenderBackend::Rasterizer,
             gui_enabled: false,
+            texture_budget_mb: 0,
         },
     );
     mark!("XR mark: engine created");
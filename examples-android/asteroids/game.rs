@@ -506,6 +506,9 @@ pub fn setup_game(engine: &mut blade_engine::Engine) -> GameState {
         roughness: 0.7,
         metallic: 0.0,
         space_sky: true,
+        shadows: blade_render::ShadowConfig::default(),
+        environment: blade_render::EnvironmentConfig::default(),
+        visible_layers: blade_render::ALL_LAYERS,
     });
 
     // Gas giant with rings in the distance.
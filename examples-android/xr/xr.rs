@@ -112,6 +112,7 @@ impl Example {
             fragment: Some(shader.at("fs_main")),
             color_targets: &[gpu::ColorTargetState::from(color_format)],
             multisample_state: gpu::MultisampleState::default(),
+            multiview: None,
         });
         let command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
             name: "xr",
@@ -213,6 +214,8 @@ impl Example {
                         init_op: gpu::InitOp::Clear(gpu::TextureColor::White),
                         finish_op: gpu::FinishOp::Discard,
                     }),
+                    depth_stencil_read_only: gpu::TexelAspects::empty(),
+                    multiview: None,
                 },
             );
             let mut rc = pass.with(&mut self.pipeline);
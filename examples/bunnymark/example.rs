@@ -89,8 +89,10 @@ impl Example {
                 format: surface_format,
                 blend: Some(gpu::BlendState::ALPHA_BLENDING),
                 write_mask: gpu::ColorWrites::default(),
+                logic_op: None,
             }],
             multisample_state: gpu::MultisampleState::default(),
+            multiview: None,
         });
 
         let extent = gpu::Extent {
@@ -265,6 +267,8 @@ impl Example {
                     finish_op: gpu::FinishOp::Store,
                 }],
                 depth_stencil: None,
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
             },
         ) {
             let mut rc = pass.with(&self.pipeline);
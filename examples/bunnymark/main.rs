@@ -67,6 +67,9 @@ impl winit::application::ApplicationHandler for App {
         let surface = context
             .create_surface_configured(&window, make_surface_config(window_size))
             .unwrap();
+        if std::env::args().any(|arg| arg == "--verbose") {
+            println!("{}", context.report());
+        }
 
         let screen_size = gpu::Extent {
             width: window_size.width,
@@ -26,4 +26,9 @@ fn main() {
             println!("  dual_source_blending: {}", caps.dual_source_blending);
         }
     }
+
+    if std::env::args().any(|arg| arg == "--verbose") {
+        let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()) }.unwrap();
+        println!("{}", context.report());
+    }
 }
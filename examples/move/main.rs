@@ -46,6 +46,8 @@ impl Game {
                 fov_y: 1.0,
                 depth: 0.0,
                 fov: None,
+                lens: Default::default(),
+                projection: Default::default(),
             },
             fly_speed: 10.0,
         };
@@ -60,6 +62,7 @@ impl Game {
                 time_step: 0.01,
                 render_backend: blade_engine::config::RenderBackend::Rasterizer,
                 gui_enabled: cfg!(debug_assertions),
+                texture_budget_mb: 0,
             },
         );
 
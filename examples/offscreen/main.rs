@@ -0,0 +1,110 @@
+//! Rendering frames into PNG images, without a window or a surface.
+//!
+//! Usage: `cargo run --example offscreen -- [frame count] [output directory]`
+//!
+//! Works on headless machines, as long as there is a GPU device.
+
+use blade_graphics as gpu;
+use std::path::{Path, PathBuf};
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Globals {
+    angle: f32,
+    aspect: f32,
+    pad: [f32; 2],
+}
+
+#[derive(blade_macros::ShaderData)]
+struct Params {
+    globals: Globals,
+}
+
+fn save_png(path: &Path, pixels: &[u8], size: gpu::Extent) {
+    let file = std::fs::File::create(path).unwrap();
+    let mut encoder = png::Encoder::new(file, size.width, size.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(pixels).unwrap();
+}
+
+fn main() {
+    env_logger::init();
+    let mut args = std::env::args().skip(1);
+    let frame_count = args.next().map_or(4, |arg| arg.parse::<u32>().unwrap());
+    let output_dir = args.next().map_or_else(std::env::temp_dir, PathBuf::from);
+
+    let context = unsafe {
+        gpu::Context::init(gpu::ContextDesc {
+            validation: cfg!(debug_assertions),
+            ..Default::default()
+        })
+        .expect("Failed to init GPU context")
+    };
+    println!("Device: {}", context.device_information().device_name);
+
+    let size = gpu::Extent {
+        width: 320,
+        height: 240,
+        depth: 1,
+    };
+    let color_format = gpu::TextureFormat::Rgba8Unorm;
+    let depth_format = gpu::TextureFormat::Depth32Float;
+    let mut session = blade_util::OffscreenSession::new(
+        &blade_util::OffscreenSessionDescriptor {
+            name: "offscreen",
+            size,
+            color_format,
+            depth_format: Some(depth_format),
+        },
+        &context,
+    );
+
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shader.wgsl"),
+        naga_module: None,
+    });
+    let mut pipeline = context.create_render_pipeline(gpu::RenderPipelineDesc {
+        name: "triangles",
+        data_layouts: &[&<Params as gpu::ShaderData>::layout()],
+        vertex: shader.at("vs_main"),
+        vertex_fetches: &[],
+        primitive: gpu::PrimitiveState {
+            topology: gpu::PrimitiveTopology::TriangleList,
+            ..Default::default()
+        },
+        depth_stencil: Some(gpu::DepthStencilState {
+            format: depth_format,
+            depth_write_enabled: true,
+            depth_compare: gpu::CompareFunction::Less,
+            stencil: Default::default(),
+            bias: Default::default(),
+        }),
+        fragment: Some(shader.at("fs_main")),
+        color_targets: &[color_format.into()],
+        multisample_state: Default::default(),
+        multiview: None,
+    });
+
+    for frame in 0..frame_count {
+        let params = Params {
+            globals: Globals {
+                angle: frame as f32 * 0.2,
+                aspect: size.width as f32 / size.height as f32,
+                pad: [0.0; 2],
+            },
+        };
+        let pixels = session.render_frame(&context, gpu::TextureColor::OpaqueBlack, |pass| {
+            let mut pc = pass.with(&pipeline);
+            pc.bind(0, &params);
+            pc.draw(0, 3, 0, 2);
+        });
+        let path = output_dir.join(format!("offscreen-{frame}.png"));
+        save_png(&path, &pixels, size);
+        println!("Saved {}", path.display());
+    }
+
+    context.destroy_render_pipeline(&mut pipeline);
+    session.destroy(&context);
+}
@@ -0,0 +1,32 @@
+struct Globals {
+    angle: f32,
+    aspect: f32,
+    pad: vec2<f32>,
+};
+var<uniform> globals: Globals;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+// Two triangles rotating in the opposite directions,
+// the second one being farther away, and so hidden behind the first one.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, @builtin(instance_index) instance_index: u32) -> VertexOutput {
+    let corner = 2.0943951 * f32(vertex_index);
+    let angle = select(globals.angle, -globals.angle, instance_index == 1u);
+    let pos = 0.8 * vec2<f32>(sin(corner + angle), cos(corner + angle));
+    let depth = 0.25 + 0.5 * f32(instance_index);
+    var color = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    color[vertex_index] = 1.0;
+    if (instance_index == 1u) {
+        color = vec4<f32>(vec3<f32>(0.5), 1.0);
+    }
+    return VertexOutput(vec4<f32>(pos.x / globals.aspect, pos.y, depth, 1.0), color);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
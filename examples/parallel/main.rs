@@ -0,0 +1,238 @@
+//! Parallel recording of draw calls on multiple threads.
+//!
+//! Splits a few thousand draws between command encoders that are recorded
+//! on separate threads, and submits them in order as a single batch.
+//! The draws overlap, so the image is only correct if the order is kept,
+//! which is verified against the single-threaded recording.
+//!
+//! Prints the recording time for every thread count, showing the scaling.
+
+#![allow(irrefutable_let_patterns)]
+
+use blade_graphics as gpu;
+use gpu::ShaderData as _;
+use std::{mem, time};
+
+const DRAW_COUNT: usize = 2000;
+const FRAME_COUNT: usize = 20;
+const SIZE: gpu::Extent = gpu::Extent {
+    width: 256,
+    height: 256,
+    depth: 1,
+};
+const FORMAT: gpu::TextureFormat = gpu::TextureFormat::Rgba8Unorm;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct DrawParams {
+    rect: [f32; 4],
+    color: [f32; 4],
+}
+
+#[derive(blade_macros::ShaderData)]
+struct DrawData {
+    params: DrawParams,
+}
+
+/// Overlapping rectangles spiraling around the center.
+fn make_draws() -> Vec<DrawParams> {
+    (0..DRAW_COUNT)
+        .map(|i| {
+            let t = i as f32 / DRAW_COUNT as f32;
+            let angle = t * 40.0;
+            let radius = 0.8 * (1.0 - t);
+            let [x, y] = [radius * angle.cos(), radius * angle.sin()];
+            let half = 0.05 + 0.1 * t;
+            DrawParams {
+                rect: [x - half, y - half, x + half, y + half],
+                color: [t, (angle * 0.5).sin() * 0.5 + 0.5, 1.0 - t, 1.0],
+            }
+        })
+        .collect()
+}
+
+fn record(
+    encoder: &mut gpu::CommandEncoder,
+    pipeline: &gpu::RenderPipeline,
+    view: gpu::TextureView,
+    draws: &[DrawParams],
+    is_first: bool,
+) {
+    encoder.start();
+    let init_op = if is_first {
+        gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack)
+    } else {
+        gpu::InitOp::Load
+    };
+    if let mut pass = encoder.render(
+        "draws",
+        gpu::RenderTargetSet {
+            colors: &[gpu::RenderTarget {
+                view,
+                init_op,
+                finish_op: gpu::FinishOp::Store,
+            }],
+            depth_stencil: None,
+            depth_stencil_read_only: gpu::TexelAspects::empty(),
+            multiview: None,
+        },
+    ) {
+        let mut rc = pass.with(pipeline);
+        for &params in draws {
+            rc.bind(0, &DrawData { params });
+            rc.draw(0, 4, 0, 1);
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let context = unsafe {
+        gpu::Context::init(gpu::ContextDesc {
+            validation: cfg!(debug_assertions),
+            ..Default::default()
+        })
+        .expect("Failed to init GPU context")
+    };
+    println!("Device: {}", context.device_information().device_name);
+
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shader.wgsl"),
+        naga_module: None,
+    });
+    let mut pipeline = context.create_render_pipeline(gpu::RenderPipelineDesc {
+        name: "draws",
+        data_layouts: &[&DrawData::layout()],
+        vertex: shader.at("vs_main"),
+        vertex_fetches: &[],
+        primitive: gpu::PrimitiveState {
+            topology: gpu::PrimitiveTopology::TriangleStrip,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        fragment: Some(shader.at("fs_main")),
+        color_targets: &[FORMAT.into()],
+        multisample_state: gpu::MultisampleState::default(),
+        multiview: None,
+    });
+
+    let texture = context.create_texture(gpu::TextureDesc {
+        name: "target",
+        format: FORMAT,
+        size: SIZE,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        dimension: gpu::TextureDimension::D2,
+        usage: gpu::TextureUsage::TARGET | gpu::TextureUsage::COPY,
+        sample_count: 1,
+        external: None,
+    });
+    let view = context.create_texture_view(
+        texture,
+        gpu::TextureViewDesc {
+            name: "target",
+            format: FORMAT,
+            dimension: gpu::ViewDimension::D2,
+            subresources: &gpu::TextureSubresources::default(),
+        },
+    );
+    let pixel_count = (SIZE.width * SIZE.height) as usize;
+    let readback = context.create_buffer(gpu::BufferDesc {
+        name: "readback",
+        size: (pixel_count * mem::size_of::<u32>()) as u64,
+        memory: gpu::Memory::Shared,
+    });
+
+    let mut readback_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "readback",
+        buffer_count: 1,
+    });
+    readback_encoder.start();
+    readback_encoder.init_texture(texture);
+    let sync_point = context.submit(&mut readback_encoder);
+    context.wait_for(&sync_point, !0).unwrap();
+
+    let draws = make_draws();
+    let max_threads = num_cpus::get().clamp(1, 16);
+    let mut thread_counts = vec![1];
+    while thread_counts.last().unwrap() * 2 <= max_threads {
+        thread_counts.push(thread_counts.last().unwrap() * 2);
+    }
+
+    let mut reference = None::<Vec<u32>>;
+    let mut single_thread_time = None;
+    println!("Recording {DRAW_COUNT} draws, averaged over {FRAME_COUNT} frames");
+    for thread_count in thread_counts {
+        let mut encoders = (0..thread_count)
+            .map(|_| {
+                context.create_command_encoder(gpu::CommandEncoderDesc {
+                    name: "parallel",
+                    buffer_count: 1,
+                })
+            })
+            .collect::<Vec<_>>();
+        let chunk_size = draws.len().div_ceil(thread_count);
+
+        let mut record_time = time::Duration::ZERO;
+        for _ in 0..FRAME_COUNT {
+            let start = time::Instant::now();
+            std::thread::scope(|scope| {
+                for (index, (encoder, chunk)) in encoders
+                    .iter_mut()
+                    .zip(draws.chunks(chunk_size))
+                    .enumerate()
+                {
+                    let pipeline = &pipeline;
+                    scope.spawn(move || record(encoder, pipeline, view, chunk, index == 0));
+                }
+            });
+            record_time += start.elapsed();
+
+            readback_encoder.start();
+            if let mut transfer = readback_encoder.transfer("readback") {
+                transfer.copy_texture_to_buffer(
+                    texture.into(),
+                    readback.into(),
+                    SIZE.width * 4,
+                    SIZE,
+                );
+            }
+            let mut batch = encoders
+                .iter_mut()
+                .chain(Some(&mut readback_encoder))
+                .collect::<Vec<_>>();
+            let sync_point = context.submit_batch(&mut batch);
+            context.wait_for(&sync_point, !0).unwrap();
+        }
+
+        let pixels = unsafe {
+            std::slice::from_raw_parts(readback.data() as *const u32, pixel_count).to_vec()
+        };
+        match reference {
+            Some(ref expected) => assert!(
+                pixels == *expected,
+                "Image recorded on {thread_count} threads doesn't match"
+            ),
+            None => reference = Some(pixels),
+        }
+
+        let average = record_time / FRAME_COUNT as u32;
+        let speedup =
+            single_thread_time.get_or_insert(average).as_secs_f64() / average.as_secs_f64();
+        println!(
+            "\t{thread_count} thread(s): {:.3} ms, {speedup:.2}x",
+            average.as_secs_f64() * 1000.0
+        );
+
+        for mut encoder in encoders {
+            context.destroy_command_encoder(&mut encoder);
+        }
+    }
+
+    context.destroy_command_encoder(&mut readback_encoder);
+    context.destroy_buffer(readback);
+    context.destroy_texture_view(view);
+    context.destroy_texture(texture);
+    context.destroy_render_pipeline(&mut pipeline);
+}
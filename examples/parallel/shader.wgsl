@@ -0,0 +1,22 @@
+struct DrawParams {
+    rect: vec4<f32>,
+    color: vec4<f32>,
+};
+var<uniform> params: DrawParams;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    let pos = mix(params.rect.xy, params.rect.zw, corner);
+    return VertexOutput(vec4<f32>(pos, 0.0, 1.0), params.color);
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    return vertex.color;
+}
@@ -136,7 +136,8 @@ impl Example {
             .find(|&n| (caps.sample_count_mask & n) != 0)
             .unwrap();
 
-        let gui_painter = blade_egui::GuiPainter::new(surface_info, &context);
+        let mut gui_painter = blade_egui::GuiPainter::new(surface_info, &context);
+        gui_painter.set_dithering(true);
 
         let particle_pipeline = blade_particle::ParticlePipeline::new(
             &context,
@@ -275,6 +276,8 @@ impl Example {
                         finish_op: gpu::FinishOp::Store,
                     }],
                     depth_stencil: None,
+                    depth_stencil_read_only: gpu::TexelAspects::empty(),
+                    multiview: None,
                 },
             ) {
                 self.particle_system
@@ -296,6 +299,8 @@ impl Example {
                         },
                     }],
                     depth_stencil: None,
+                    depth_stencil_read_only: gpu::TexelAspects::empty(),
+                    multiview: None,
                 },
             ) {
                 self.particle_system
@@ -310,6 +315,8 @@ impl Example {
                         finish_op: gpu::FinishOp::Store,
                     }],
                     depth_stencil: None,
+                    depth_stencil_read_only: gpu::TexelAspects::empty(),
+                    multiview: None,
                 },
             ) {
                 self.gui_painter
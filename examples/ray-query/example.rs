@@ -91,6 +91,7 @@ impl Example {
             color_targets: &[surface_format.into()],
             depth_stencil: None,
             multisample_state: Default::default(),
+            multiview: None,
         });
 
         let (indices, vertex_values) =
@@ -172,8 +173,7 @@ impl Example {
             ty: gpu::AccelerationStructureType::TopLevel,
             size: tlas_sizes.data,
         });
-        let tlas_scratch_offset =
-            (blas_sizes.scratch | (gpu::limits::ACCELERATION_STRUCTURE_SCRATCH_ALIGNMENT - 1)) + 1;
+        let tlas_scratch_offset = (blas_sizes.scratch | (tlas_sizes.scratch_alignment - 1)) + 1;
         let scratch_buffer = context.create_buffer(gpu::BufferDesc {
             name: "scratch",
             size: tlas_scratch_offset + tlas_sizes.scratch,
@@ -259,6 +259,8 @@ impl Example {
                     finish_op: gpu::FinishOp::Store,
                 }],
                 depth_stencil: None,
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
             },
         ) && let mut pc = pass.with(&self.draw_pipeline)
         {
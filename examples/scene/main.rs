@@ -12,6 +12,8 @@ use std::{
 
 const RENDER_WHILE_LOADING: bool = true;
 const MAX_DEPTH: f32 = 1e9;
+/// Time for the asset sources to settle before they are reloaded.
+const ASSET_RELOAD_DEBOUNCE: time::Duration = time::Duration::from_millis(300);
 
 #[derive(Clone, Copy, PartialEq, strum::EnumIter)]
 enum DebugBlitInput {
@@ -110,10 +112,6 @@ struct ConfigCamera {
 fn default_transform() -> mint::RowMatrix3x4<f32> {
     gpu::IDENTITY_TRANSFORM
 }
-fn default_luminocity() -> f32 {
-    1.0
-}
-
 #[derive(serde::Deserialize, serde::Serialize)]
 struct ConfigObject {
     path: String,
@@ -126,8 +124,8 @@ struct ConfigScene {
     camera: ConfigCamera,
     #[serde(default)]
     environment_map: String,
-    #[serde(default = "default_luminocity")]
-    average_luminocity: f32,
+    #[serde(default)]
+    exposure_ev: f32,
     objects: Vec<ConfigObject>,
 }
 
@@ -153,12 +151,15 @@ struct Example {
     debug: blade_render::DebugConfig,
     track_hot_reloads: bool,
     need_accumulation_reset: bool,
+    is_accumulating: bool,
     is_point_selected: bool,
     is_file_hovered: bool,
     ray_config: blade_render::RayConfig,
-    denoiser_enabled: bool,
     denoiser_config: blade_render::DenoiserConfig,
     post_proc_config: blade_render::PostProcConfig,
+    render_scale: f32,
+    auto_exposure_config: blade_render::AutoExposureConfig,
+    last_metering: Option<time::Instant>,
     debug_blit: Option<blade_render::DebugBlit>,
     debug_blit_input: DebugBlitInput,
     workers: Vec<choir::WorkerHandle>,
@@ -189,6 +190,7 @@ impl Example {
                 ray_tracing: true,
                 validation: cfg!(debug_assertions),
                 capture: true,
+                timing: true,
                 ..Default::default()
             })
             .unwrap()
@@ -209,6 +211,7 @@ impl Example {
             .collect();
 
         let asset_hub = blade_render::AssetHub::new(Path::new("asset-cache"), &choir, &context);
+        asset_hub.set_watching(Some(ASSET_RELOAD_DEBOUNCE));
         let (shaders, shader_task) =
             blade_render::Shaders::load("blade-render/code/".as_ref(), &asset_hub, true);
 
@@ -229,7 +232,8 @@ impl Example {
             &render_config,
         );
         pacer.end_frame(&context);
-        let gui_painter = blade_egui::GuiPainter::new(surface_info, &context);
+        let mut gui_painter = blade_egui::GuiPainter::new(surface_info, &context);
+        gui_painter.set_dithering(true);
 
         Self {
             scene_path: PathBuf::new(),
@@ -253,19 +257,15 @@ impl Example {
             debug: blade_render::DebugConfig::default(),
             track_hot_reloads: true,
             need_accumulation_reset: true,
+            is_accumulating: false,
             is_point_selected: false,
             is_file_hovered: false,
             ray_config: blade_helpers::default_ray_config(),
-            denoiser_enabled: true,
-            denoiser_config: blade_render::DenoiserConfig {
-                num_passes: 3,
-                temporal_weight: 0.1,
-            },
-            post_proc_config: blade_render::PostProcConfig {
-                average_luminocity: 1.0,
-                exposure_key_value: 1.0 / 9.6,
-                white_level: 1.0,
-            },
+            denoiser_config: blade_render::DenoiserConfig::default(),
+            post_proc_config: blade_render::PostProcConfig::default(),
+            render_scale: 1.0,
+            auto_exposure_config: blade_render::AutoExposureConfig::default(),
+            last_metering: None,
             debug_blit: None,
             debug_blit_input: DebugBlitInput::None,
             workers,
@@ -306,10 +306,12 @@ impl Example {
             fov_y: config_scene.camera.fov_y,
             depth: MAX_DEPTH,
             fov: None,
+            lens: blade_render::Lens::default(),
+            projection: blade_render::Projection::default(),
         };
         self.camera.fly_speed = config_scene.camera.speed;
         self.ray_config.environment_importance_sampling = !config_scene.environment_map.is_empty();
-        self.post_proc_config.average_luminocity = config_scene.average_luminocity;
+        self.post_proc_config.exposure_ev = config_scene.exposure_ev;
 
         self.environment_map = None;
         let parent = scene_path.parent().unwrap();
@@ -320,6 +322,7 @@ impl Example {
                 format: gpu::TextureFormat::Rgba32Float,
                 generate_mips: false,
                 y_flip: false,
+                basis: None,
             };
             let (texture, texture_task) = self
                 .asset_hub
@@ -342,6 +345,9 @@ impl Example {
                 transform: config_object.transform,
                 prev_transform: config_object.transform,
                 color_tint: [1.0; 4],
+                material_overrides: blade_render::MaterialOverrides::default(),
+                joints: Vec::new(),
+                layers: blade_render::DEFAULT_LAYERS,
             });
             self.object_extras.push(ObjectExtra {
                 path: PathBuf::from(config_object.path),
@@ -362,7 +368,7 @@ impl Example {
                 speed: self.camera.fly_speed,
             },
             environment_map: self.scene_environment_map.clone(),
-            average_luminocity: self.post_proc_config.average_luminocity,
+            exposure_ev: self.post_proc_config.exposure_ev,
             objects: self
                 .objects
                 .iter()
@@ -419,10 +425,19 @@ impl Example {
                 .resize_screen(new_render_size, command_encoder, &self.context);
             self.need_accumulation_reset = true;
         }
+        if self.render_scale != self.renderer.get_render_scale() {
+            self.renderer
+                .set_render_scale(self.render_scale, command_encoder, &self.context);
+            self.need_accumulation_reset = true;
+        }
 
         self.gui_painter
             .update_textures(command_encoder, gui_textures, &self.context);
 
+        if !self.asset_hub.take_reloaded(temp).is_empty() {
+            self.have_objects_changed = true;
+            self.need_accumulation_reset = true;
+        }
         self.asset_hub.flush(command_encoder, &mut temp.buffers);
 
         if let Some(ref task) = self.scene_load_task
@@ -460,6 +475,9 @@ impl Example {
                     debug_draw: self.is_point_selected || self.is_file_hovered,
                     reset_variance: self.debug.mouse_pos.is_none(),
                     reset_reservoirs: self.need_accumulation_reset,
+                    accumulate: self.is_accumulating,
+                    visible_layers: blade_render::ALL_LAYERS,
+                    culling: blade_render::CullingConfig::default(),
                 },
             );
             self.need_accumulation_reset = false;
@@ -469,9 +487,23 @@ impl Example {
             if !self.objects.is_empty() {
                 self.renderer
                     .ray_trace(command_encoder, self.debug, self.ray_config);
-                if self.denoiser_enabled {
+                if !self.is_accumulating {
                     self.renderer.denoise(command_encoder, self.denoiser_config);
                 }
+                self.renderer
+                    .render_bloom(command_encoder, self.post_proc_config.bloom);
+                if self.post_proc_config.auto_exposure {
+                    let now = time::Instant::now();
+                    let delta_time = self
+                        .last_metering
+                        .map_or(0.0, |last| (now - last).as_secs_f32());
+                    self.last_metering = Some(now);
+                    self.renderer.meter_exposure(
+                        command_encoder,
+                        self.auto_exposure_config,
+                        delta_time,
+                    );
+                }
             }
         }
 
@@ -487,6 +519,8 @@ impl Example {
                     finish_op: gpu::FinishOp::Store,
                 }],
                 depth_stencil: None,
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
             },
         ) {
             let screen_desc = blade_egui::ScreenDescriptor {
@@ -565,8 +599,16 @@ impl Example {
                 ui.spinner();
             });
             //TODO: seeing GPU Device Lost issues without this
-            for task in self.asset_hub.list_running_tasks() {
-                ui.label(format!("{}", task.as_ref()));
+            let progress = self.asset_hub.progress();
+            ui.add(
+                egui::ProgressBar::new(progress.fraction())
+                    .text(format!("{}/{}", progress.completed, progress.total)),
+            );
+            for name in progress.running {
+                ui.label(name);
+            }
+            for (name, error) in progress.failed {
+                ui.colored_label(egui::Color32::RED, format!("{name}: {error}"));
             }
             return;
         }
@@ -657,13 +699,41 @@ impl Example {
         egui::CollapsingHeader::new("Denoise")
             .default_open(false)
             .show(ui, |ui| {
-                ui.checkbox(&mut self.denoiser_enabled, "Enable");
                 self.denoiser_config.populate_hud(ui);
             });
 
+        egui::CollapsingHeader::new("Accumulate")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.checkbox(&mut self.is_accumulating, "Enable");
+                ui.label(format!("Frames: {}", self.renderer.accumulated_frames()));
+            });
+
         egui::CollapsingHeader::new("Tone Map").show(ui, |ui| {
+            ui.add(egui::Slider::new(&mut self.render_scale, 0.25..=1.0f32).text("Render scale"));
             self.post_proc_config.populate_hud(ui);
+            if self.post_proc_config.auto_exposure {
+                self.auto_exposure_config.populate_hud(ui);
+                ui.label(format!(
+                    "Metered EV: {:.2}",
+                    self.renderer.auto_exposure_ev()
+                ));
+            } else {
+                self.last_metering = None;
+            }
         });
+
+        egui::CollapsingHeader::new("Timings")
+            .default_open(false)
+            .show(ui, |ui| {
+                for &(name, time) in self.renderer.timings() {
+                    let millis = time.as_secs_f32() * 1000.0;
+                    ui.horizontal(|ui| {
+                        ui.label(name);
+                        ui.colored_label(egui::Color32::WHITE, format!("{:.2} ms", millis));
+                    });
+                }
+            });
     }
 
     #[profiling::function]
@@ -786,6 +856,9 @@ impl Example {
             transform,
             prev_transform: transform,
             color_tint: [1.0; 4],
+            material_overrides: blade_render::MaterialOverrides::default(),
+            joints: Vec::new(),
+            layers: blade_render::DEFAULT_LAYERS,
         });
         self.object_extras.push(ObjectExtra {
             path: file_path.to_owned(),
@@ -52,7 +52,8 @@ pub struct Level {
     #[serde(default)]
     pub environment: String,
     pub gravity: f32,
-    pub average_luminocity: f32,
+    #[serde(default)]
+    pub exposure_ev: f32,
     pub spawn_pos: [f32; 3],
     pub ground: blade_engine::config::Object,
 }
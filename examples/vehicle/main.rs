@@ -110,6 +110,7 @@ impl Game {
                 time_step: 0.01,
                 render_backend: blade_engine::config::RenderBackend::RayTracer,
                 gui_enabled: cfg!(debug_assertions),
+                texture_budget_mb: 0,
             },
         );
 
@@ -119,7 +120,7 @@ impl Game {
         .expect("Unable to parse the level config");
         engine.set_environment_map(&lev_config.environment);
         engine.set_gravity(lev_config.gravity);
-        engine.set_average_luminosity(lev_config.average_luminocity);
+        engine.set_exposure(lev_config.exposure_ev);
 
         let ground_handle = engine.add_object(
             &lev_config.ground,
@@ -638,6 +639,8 @@ impl Game {
             blade_engine::FrameCamera {
                 transform: (base * local.inverse()).to_blade(),
                 fov_y: cc.fov,
+                lens: blade_render::Lens::default(),
+                projection: blade_render::Projection::default(),
             }
         };
 
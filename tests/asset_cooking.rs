@@ -0,0 +1,1083 @@
+//! Cooking and streaming of the textures and models.
+#![allow(irrefutable_let_patterns)]
+#![cfg(not(gles))]
+
+use blade_graphics as gpu;
+use std::slice;
+
+#[allow(dead_code)]
+mod common;
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn texture_streaming_budget() {
+    const SIZE: u32 = 256;
+    const TAIL_SIZE: u32 = 64;
+
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => std::sync::Arc::new(c),
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let choir = choir::Choir::new();
+    let _worker = choir.add_worker("worker");
+    let mut asset_hub = blade_render::AssetHub::new(
+        &std::env::temp_dir().join("blade-texture-streaming-test"),
+        &choir,
+        &context,
+    );
+    let config = blade_render::texture::StreamingConfig {
+        tail_size: TAIL_SIZE,
+        ..Default::default()
+    };
+    asset_hub.set_texture_streaming(Some(config));
+
+    // A checkerboard image, encoded as PNG to go through the cooking
+    let mut png_data = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_data, SIZE, SIZE);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let pixels = (0..SIZE * SIZE)
+            .flat_map(|i| {
+                let value = if (i % SIZE / 8 + i / SIZE / 8).is_multiple_of(2) {
+                    255
+                } else {
+                    0
+                };
+                [value, value, value, 255]
+            })
+            .collect::<Vec<u8>>();
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&pixels).unwrap();
+    }
+    let (handle, task) = asset_hub.textures.load_data(
+        "checker.png".as_ref(),
+        &png_data,
+        blade_render::texture::Meta {
+            format: gpu::TextureFormat::Bc1Unorm,
+            generate_mips: true,
+            y_flip: false,
+            basis: None,
+        },
+    );
+    task.clone().join();
+
+    let texture = &asset_hub.textures[handle];
+    assert!(texture.is_streamed());
+    assert_eq!(texture.extent.width, SIZE);
+    let tail_view = texture.view;
+
+    let mut pacer = blade_render::util::FramePacer::new(&context);
+    let mut stream_frame = |asset_hub: &blade_render::AssetHub, mip_level: Option<u32>| {
+        let (command_encoder, temp) = pacer.begin_frame();
+        asset_hub.flush(command_encoder, &mut temp.buffers);
+        if let Some(mip_level) = mip_level {
+            let texture = &asset_hub.textures[handle];
+            asset_hub
+                .textures
+                .baker
+                .request_mip(handle, texture, mip_level);
+        }
+        asset_hub.stream_textures(command_encoder, temp);
+        pacer.end_frame(&context);
+    };
+
+    // Only the mip tail is resident until requested
+    stream_frame(&asset_hub, None);
+    assert_eq!(asset_hub.texture_view(handle), tail_view);
+    assert_eq!(asset_hub.textures.baker.streamed_size(), 0);
+
+    // BC1 takes half a byte per texel, and the mips add up to a third of that
+    stream_frame(&asset_hub, Some(0));
+    assert_ne!(asset_hub.texture_view(handle), tail_view);
+    let full_size = asset_hub.textures.baker.streamed_size();
+    let base_size = (SIZE * SIZE / 2) as u64;
+    assert!(full_size > base_size && full_size < base_size * 3 / 2);
+
+    // A smaller budget only fits the mips starting from the second one
+    asset_hub.set_texture_streaming(Some(blade_render::texture::StreamingConfig {
+        budget: full_size - 1,
+        ..config
+    }));
+    stream_frame(&asset_hub, Some(0));
+    let half_size = asset_hub.textures.baker.streamed_size();
+    assert!(half_size > 0 && half_size < full_size / 3);
+    assert_ne!(asset_hub.texture_view(handle), tail_view);
+
+    // The texture is streamed out when no longer requested
+    for _ in 0..100 {
+        stream_frame(&asset_hub, None);
+    }
+    assert_eq!(asset_hub.textures.baker.streamed_size(), 0);
+    assert_eq!(asset_hub.texture_view(handle), tail_view);
+
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn deterministic_cooking() {
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => std::sync::Arc::new(c),
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    const SIZE: u32 = 64;
+    let mut png_data = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_data, SIZE, SIZE);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let pixels = (0..SIZE * SIZE)
+            .flat_map(|i| [(i % SIZE * 4) as u8, (i / SIZE * 4) as u8, 128, 255])
+            .collect::<Vec<u8>>();
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&pixels).unwrap();
+    }
+
+    let choir = choir::Choir::new();
+    let _workers = (0..4)
+        .map(|i| choir.add_worker(&format!("worker{i}")))
+        .collect::<Vec<_>>();
+    let root = std::env::temp_dir().join(format!("blade-deterministic-{}", std::process::id()));
+    // Cook the same assets from scratch, sequentially and in parallel
+    let cook = |name: &str, concurrency: Option<usize>| {
+        let target = root.join(name);
+        let _ = std::fs::remove_dir_all(&target);
+        let mut asset_hub = blade_render::AssetHub::new(&target, &choir, &context);
+        asset_hub.set_concurrency(concurrency);
+        let (_, texture_task) = asset_hub.textures.load_data(
+            "gradient.png".as_ref(),
+            &png_data,
+            blade_render::texture::Meta {
+                format: gpu::TextureFormat::Bc1Unorm,
+                generate_mips: true,
+                y_flip: false,
+                basis: None,
+            },
+        );
+        let texture_task = texture_task.clone();
+        let (_, model_task) = asset_hub.models.load(
+            "examples/scene/data/monkey.gltf",
+            blade_render::model::Meta {
+                generate_tangents: true,
+                front_face: blade_render::model::FrontFace::CounterClockwise,
+                ..Default::default()
+            },
+        );
+        model_task.clone().join();
+        texture_task.join();
+        asset_hub.destroy();
+
+        let mut files = std::fs::read_dir(&target)
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                (entry.file_name(), std::fs::read(entry.path()).unwrap())
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    };
+
+    let sequential = cook("sequential", Some(1));
+    let parallel = cook("parallel", None);
+    assert_eq!(sequential.len(), 2);
+    assert!(
+        sequential == parallel,
+        "The cooked files differ between the runs"
+    );
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+/// Write a minimal KTX2 container with the given mips, and an empty DFD.
+fn build_ktx2(vk_format: u32, size: u32, face_count: u32, mips: &[Vec<u8>], zstd: bool) -> Vec<u8> {
+    let payloads = mips
+        .iter()
+        .map(|mip| match zstd {
+            true => ruzstd::encoding::compress_to_vec(
+                &mip[..],
+                ruzstd::encoding::CompressionLevel::Fastest,
+            ),
+            false => mip.clone(),
+        })
+        .collect::<Vec<_>>();
+    let dfd_offset = 80 + 24 * mips.len() as u32;
+    let mut bytes = b"\xABKTX 20\xBB\r\n\x1A\n".to_vec();
+    for value in [
+        vk_format,
+        1,
+        size,
+        size,
+        0,
+        0,
+        face_count,
+        mips.len() as u32,
+        if zstd { 2 } else { 0 },
+        dfd_offset,
+        4,
+        0,
+        0,
+    ] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    // no supercompression global data
+    bytes.extend_from_slice(&[0; 16]);
+    let mut offset = dfd_offset as u64 + 4;
+    for (mip, payload) in mips.iter().zip(payloads.iter()) {
+        for value in [offset, payload.len() as u64, mip.len() as u64] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        offset += payload.len() as u64;
+    }
+    bytes.extend_from_slice(&4u32.to_le_bytes());
+    for payload in payloads.iter() {
+        bytes.extend_from_slice(payload);
+    }
+    bytes
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn ktx2_textures() {
+    const VK_FORMAT_BC1_RGBA_SRGB_BLOCK: u32 = 134;
+    const SIZE: u32 = 16;
+
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => std::sync::Arc::new(c),
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let choir = choir::Choir::new();
+    let _worker = choir.add_worker("worker");
+    let mut asset_hub = blade_render::AssetHub::new(
+        &std::env::temp_dir().join("blade-ktx2-test"),
+        &choir,
+        &context,
+    );
+    // BC1 takes 8 bytes per block of 4x4 texels, and the last mips are a single block
+    let mips = [16, 4, 1, 1, 1]
+        .map(|blocks| (0..blocks * 8).map(|i| i as u8).collect::<Vec<u8>>())
+        .to_vec();
+    let meta = blade_render::texture::Meta {
+        format: gpu::TextureFormat::Bc1UnormSrgb,
+        generate_mips: false,
+        y_flip: false,
+        basis: None,
+    };
+    let load = |name: &str, data: &[u8]| {
+        let (handle, task) = asset_hub
+            .textures
+            .load_data(name.as_ref(), data, meta.clone());
+        task.clone().join();
+        handle
+    };
+
+    for (name, zstd) in [("plain.ktx2", false), ("zstd.ktx2", true)] {
+        let handle = load(
+            name,
+            &build_ktx2(VK_FORMAT_BC1_RGBA_SRGB_BLOCK, SIZE, 1, &mips, zstd),
+        );
+        assert_eq!(
+            asset_hub.textures.status(handle),
+            blade_asset::AssetStatus::Ready
+        );
+        let texture = &asset_hub.textures[handle];
+        assert_eq!((texture.extent.width, texture.extent.height), (SIZE, SIZE));
+    }
+
+    // A mip of the wrong size
+    let mut short_mips = mips.clone();
+    short_mips[1].pop();
+    let handle = load(
+        "short.ktx2",
+        &build_ktx2(VK_FORMAT_BC1_RGBA_SRGB_BLOCK, SIZE, 1, &short_mips, false),
+    );
+    match asset_hub.textures.status(handle) {
+        blade_asset::AssetStatus::Failed(error) => assert!(error.contains("mip 1"), "{error}"),
+        other => panic!("Unexpected {other:?}"),
+    }
+
+    let cube_mips = mips.iter().map(|mip| mip.repeat(6)).collect::<Vec<_>>();
+    let handle = load(
+        "cube.ktx2",
+        &build_ktx2(VK_FORMAT_BC1_RGBA_SRGB_BLOCK, SIZE, 6, &cube_mips, false),
+    );
+    match asset_hub.textures.status(handle) {
+        blade_asset::AssetStatus::Failed(error) => assert!(error.contains("cube"), "{error}"),
+        other => panic!("Unexpected {other:?}"),
+    }
+
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn basis_textures() {
+    const SIZE: u32 = 64;
+
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => std::sync::Arc::new(c),
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+    // The uncompressed fallback is always available
+    for format in [
+        gpu::TextureFormat::Rgba8Unorm,
+        gpu::TextureFormat::Rgba8UnormSrgb,
+    ] {
+        assert!(
+            context
+                .supported_texture_usage(format)
+                .contains(gpu::TextureUsage::COPY | gpu::TextureUsage::RESOURCE)
+        );
+    }
+
+    let choir = choir::Choir::new();
+    let _worker = choir.add_worker("worker");
+    let mut asset_hub = blade_render::AssetHub::new(
+        &std::env::temp_dir().join("blade-basis-test"),
+        &choir,
+        &context,
+    );
+
+    let mut png_data = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_data, SIZE, SIZE);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let pixels = (0..SIZE * SIZE)
+            .flat_map(|i| [(i % SIZE * 4) as u8, (i / SIZE * 4) as u8, 128, 255])
+            .collect::<Vec<u8>>();
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&pixels).unwrap();
+    }
+
+    for (name, format, encoding) in [
+        (
+            "color.png",
+            gpu::TextureFormat::Bc1UnormSrgb,
+            blade_render::texture::BasisEncoding::Etc1s,
+        ),
+        (
+            "normal.png",
+            gpu::TextureFormat::Bc5Unorm,
+            blade_render::texture::BasisEncoding::Uastc,
+        ),
+    ] {
+        let (handle, task) = asset_hub.textures.load_data(
+            name.as_ref(),
+            &png_data,
+            blade_render::texture::Meta {
+                format,
+                generate_mips: true,
+                y_flip: false,
+                basis: Some(encoding),
+            },
+        );
+        task.clone().join();
+        assert_eq!(
+            asset_hub.textures.status(handle),
+            blade_asset::AssetStatus::Ready
+        );
+        let texture = &asset_hub.textures[handle];
+        assert_eq!((texture.extent.width, texture.extent.height), (SIZE, SIZE));
+    }
+
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn dds_textures() {
+    // All the fixtures are 8x8 with 4 mips
+    const SIZE: u32 = 8;
+    const HEADER_SIZE: usize = 128;
+    const DX10_HEADER_SIZE: usize = 20;
+
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => std::sync::Arc::new(c),
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let choir = choir::Choir::new();
+    let _worker = choir.add_worker("worker");
+    let mut asset_hub = blade_render::AssetHub::new(
+        &std::env::temp_dir().join("blade-dds-test"),
+        &choir,
+        &context,
+    );
+    let load = |name: &str, data: &[u8], format: gpu::TextureFormat| {
+        let meta = blade_render::texture::Meta {
+            format,
+            generate_mips: false,
+            y_flip: false,
+            basis: None,
+        };
+        let (handle, task) = asset_hub.textures.load_data(name.as_ref(), data, meta);
+        task.clone().join();
+        handle
+    };
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "dds",
+        buffer_count: 1,
+    });
+
+    let fixtures: [(&str, &[u8], gpu::TextureFormat, usize); 4] = [
+        (
+            "bc1.dds",
+            include_bytes!("data/bc1.dds"),
+            gpu::TextureFormat::Bc1UnormSrgb,
+            HEADER_SIZE,
+        ),
+        (
+            "bc3.dds",
+            include_bytes!("data/bc3.dds"),
+            gpu::TextureFormat::Bc3Unorm,
+            HEADER_SIZE,
+        ),
+        (
+            "bc5.dds",
+            include_bytes!("data/bc5.dds"),
+            gpu::TextureFormat::Bc5Unorm,
+            HEADER_SIZE + DX10_HEADER_SIZE,
+        ),
+        (
+            "rgba8.dds",
+            include_bytes!("data/rgba8.dds"),
+            gpu::TextureFormat::Rgba8Unorm,
+            HEADER_SIZE,
+        ),
+    ];
+    for (name, data, format, data_offset) in fixtures {
+        let handle = load(name, data, format);
+        assert_eq!(
+            asset_hub.textures.status(handle),
+            blade_asset::AssetStatus::Ready,
+            "{name}"
+        );
+        let texture = &asset_hub.textures[handle];
+        assert_eq!((texture.extent.width, texture.extent.height), (SIZE, SIZE));
+
+        // Read the base mip back, which has to match the file contents
+        let block_info = format.block_info();
+        let bytes_per_row = SIZE / block_info.dimensions.0 as u32 * block_info.size as u32;
+        let byte_count = (bytes_per_row * SIZE / block_info.dimensions.1 as u32) as usize;
+        let readback = context.create_buffer(gpu::BufferDesc {
+            name: "readback",
+            size: byte_count as u64,
+            memory: gpu::Memory::Shared,
+        });
+        command_encoder.start();
+        let mut temp_buffers = Vec::new();
+        asset_hub.flush(&mut command_encoder, &mut temp_buffers);
+        if let mut transfer = command_encoder.transfer("read-back") {
+            transfer.copy_texture_to_buffer(
+                texture.object.into(),
+                readback.into(),
+                bytes_per_row,
+                texture.extent,
+            );
+        }
+        let sync_point = context.submit(&mut command_encoder);
+        assert!(context.wait_for(&sync_point, 5000).unwrap());
+        let contents = unsafe { slice::from_raw_parts(readback.data(), byte_count) };
+        assert_eq!(
+            contents,
+            &data[data_offset..data_offset + byte_count],
+            "{name}"
+        );
+        context.destroy_buffer(readback);
+        for buffer in temp_buffers {
+            context.destroy_buffer(buffer);
+        }
+    }
+
+    // Volume textures are rejected
+    let mut volume = include_bytes!("data/bc1.dds").to_vec();
+    volume[4 + 108..4 + 112].copy_from_slice(&0x20_0000u32.to_le_bytes());
+    let handle = load("volume.dds", &volume, gpu::TextureFormat::Bc1Unorm);
+    match asset_hub.textures.status(handle) {
+        blade_asset::AssetStatus::Failed(error) => assert!(error.contains("volume"), "{error}"),
+        other => panic!("Unexpected {other:?}"),
+    }
+    // And so are the packed legacy formats, such as R5G6B5
+    let mut packed = include_bytes!("data/rgba8.dds").to_vec();
+    for (offset, value) in [(84, 16u32), (88, 0xF800), (92, 0x7E0), (96, 0x1F), (100, 0)] {
+        packed[4 + offset..4 + offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    let handle = load("packed.dds", &packed, gpu::TextureFormat::Rgba8Unorm);
+    match asset_hub.textures.status(handle) {
+        blade_asset::AssetStatus::Failed(error) => assert!(error.contains("legacy"), "{error}"),
+        other => panic!("Unexpected {other:?}"),
+    }
+
+    context.destroy_command_encoder(&mut command_encoder);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn exr_textures() {
+    use exr::prelude::{
+        Encoding, Image, ImageAttributes, IntegerBounds, Layer, LayerAttributes, SpecificChannels,
+        Vec2, WritableImage as _, f16,
+    };
+    const COLOR: [f32; 3] = [2.0, 0.5, 100.0];
+
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => std::sync::Arc::new(c),
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    // Half RGB channels without alpha, with the data window
+    // covering only the middle of the 8x4 display window.
+    let layer = Layer::new(
+        (4, 2),
+        LayerAttributes::named("main").with_position(Vec2(3, 2)),
+        Encoding::FAST_LOSSLESS,
+        SpecificChannels::rgb(|_: Vec2<usize>| COLOR.map(f16::from_f32).into()),
+    );
+    let image = Image::new(
+        ImageAttributes::new(IntegerBounds::new((1, 1), (8, 4))),
+        layer,
+    );
+    let mut exr_data = Vec::new();
+    image
+        .write()
+        .to_buffered(std::io::Cursor::new(&mut exr_data))
+        .unwrap();
+
+    let choir = choir::Choir::new();
+    let _worker = choir.add_worker("worker");
+    let mut asset_hub = blade_render::AssetHub::new(
+        &std::env::temp_dir().join("blade-exr-test"),
+        &choir,
+        &context,
+    );
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "exr",
+        buffer_count: 1,
+    });
+
+    for format in [
+        gpu::TextureFormat::Rgba16Float,
+        gpu::TextureFormat::Rgba32Float,
+    ] {
+        let (handle, task) = asset_hub.textures.load_data(
+            "sky.exr".as_ref(),
+            &exr_data,
+            blade_render::texture::Meta {
+                format,
+                generate_mips: false,
+                y_flip: false,
+                basis: None,
+            },
+        );
+        task.clone().join();
+        let texture = &asset_hub.textures[handle];
+        assert_eq!((texture.extent.width, texture.extent.height), (8, 4));
+
+        let texel_size = format.block_info().size as u32;
+        let byte_count = (texture.extent.width * texture.extent.height * texel_size) as usize;
+        let readback = context.create_buffer(gpu::BufferDesc {
+            name: "readback",
+            size: byte_count as u64,
+            memory: gpu::Memory::Shared,
+        });
+        command_encoder.start();
+        let mut temp_buffers = Vec::new();
+        asset_hub.flush(&mut command_encoder, &mut temp_buffers);
+        if let mut transfer = command_encoder.transfer("read-back") {
+            transfer.copy_texture_to_buffer(
+                texture.object.into(),
+                readback.into(),
+                texture.extent.width * texel_size,
+                texture.extent,
+            );
+        }
+        let sync_point = context.submit(&mut command_encoder);
+        assert!(context.wait_for(&sync_point, 5000).unwrap());
+        let bytes = unsafe { slice::from_raw_parts(readback.data(), byte_count) };
+        let texels = match format {
+            gpu::TextureFormat::Rgba16Float => bytemuck::cast_slice::<_, half::f16>(bytes)
+                .iter()
+                .map(|value| value.to_f32())
+                .collect::<Vec<_>>(),
+            _ => bytemuck::cast_slice::<_, f32>(bytes).to_vec(),
+        };
+        let at = |x: usize, y: usize| &texels[(y * 8 + x) * 4..][..4];
+        // The missing alpha is opaque inside the data window
+        assert_eq!(at(2, 1), [COLOR[0], COLOR[1], COLOR[2], 1.0]);
+        assert_eq!(at(5, 2), [COLOR[0], COLOR[1], COLOR[2], 1.0]);
+        // And the rest of the display window is transparent black
+        assert_eq!(at(0, 0), [0.0; 4]);
+        assert_eq!(at(6, 1), [0.0; 4]);
+        assert_eq!(at(2, 3), [0.0; 4]);
+        context.destroy_buffer(readback);
+        for buffer in temp_buffers {
+            context.destroy_buffer(buffer);
+        }
+    }
+
+    context.destroy_command_encoder(&mut command_encoder);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn obj_ply_models() {
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => std::sync::Arc::new(c),
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let choir = choir::Choir::new();
+    let _worker = choir.add_worker("worker");
+    let mut asset_hub = blade_render::AssetHub::new(
+        &std::env::temp_dir().join("blade-obj-ply-test"),
+        &choir,
+        &context,
+    );
+    let meta = blade_render::model::Meta {
+        generate_tangents: true,
+        front_face: blade_render::model::FrontFace::CounterClockwise,
+        ..Default::default()
+    };
+    let load = |path: &str| {
+        let (handle, task) = asset_hub.models.load(path, meta.clone());
+        task.clone().join();
+        handle
+    };
+    let obj = load("tests/data/polygons.obj");
+    let ascii_ply = load("tests/data/quad_ascii.ply");
+    let binary_ply = load("tests/data/tetra_binary.ply");
+
+    // Polygons are triangulated, and the vertices are shared between the triangles
+    let model = &asset_hub.models[obj];
+    let geometries = model
+        .geometries
+        .iter()
+        .map(|geo| {
+            (
+                geo.name.as_str(),
+                geo.triangle_count,
+                geo.vertex_range.len(),
+                geo.material_index,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        geometries,
+        [("front", 2, 4, 0), ("back", 3, 5, 1), ("loose", 1, 3, 2)]
+    );
+    let textured = &model.materials[0];
+    assert!(textured.base_color_texture.is_some());
+    assert_eq!(textured.base_color_factor, [1.0, 0.5, 0.25, 1.0]);
+    let glass = &model.materials[1];
+    assert!(glass.base_color_texture.is_none());
+    assert_eq!(glass.base_color_factor, [0.8, 0.9, 1.0, 0.5]);
+    assert_eq!(glass.alpha_mode, blade_render::model::AlphaMode::Blend);
+    assert_eq!(glass.ior, 1.45);
+    // The unknown material falls back to the default one
+    assert_eq!(model.materials[2].base_color_factor, [1.0; 4]);
+
+    // Vertex colors end up in the base color factor
+    let model = &asset_hub.models[ascii_ply];
+    assert_eq!(model.geometries[0].triangle_count, 2);
+    assert_eq!(model.geometries[0].vertex_range.len(), 4);
+    let factor = model.materials[0].base_color_factor;
+    for (actual, expected) in factor.iter().zip([0.5, 0.5, 0.5, 1.0]) {
+        assert!((actual - expected).abs() < 0.01, "{factor:?}");
+    }
+    let model = &asset_hub.models[binary_ply];
+    assert_eq!(model.geometries[0].triangle_count, 4);
+    assert_eq!(model.geometries[0].vertex_range.len(), 4);
+
+    // OBJ indices start from 1, and can't go past the vertices so far
+    let vertices = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+    for (face, error_part) in [("f 0 1 2", "index 0"), ("f 1 2 -4", "out of range")] {
+        let source = format!("{vertices}{face}\n");
+        let (handle, task) =
+            asset_hub
+                .models
+                .load_data("invalid.obj".as_ref(), source.as_bytes(), meta.clone());
+        task.clone().join();
+        match asset_hub.models.status(handle) {
+            blade_asset::AssetStatus::Failed(error) => {
+                assert!(error.contains(error_part), "{error}")
+            }
+            other => panic!("Unexpected {other:?}"),
+        }
+    }
+
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn texture_transform_model() {
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => std::sync::Arc::new(c),
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let choir = choir::Choir::new();
+    let _worker = choir.add_worker("worker");
+    let mut asset_hub = blade_render::AssetHub::new(
+        &std::env::temp_dir().join("blade-texture-transform-test"),
+        &choir,
+        &context,
+    );
+    let meta = blade_render::model::Meta {
+        generate_tangents: true,
+        front_face: blade_render::model::FrontFace::CounterClockwise,
+        ..Default::default()
+    };
+    let (handle, task) = asset_hub
+        .models
+        .load("tests/data/texture_transform.gltf", meta);
+    task.clone().join();
+    let model = &asset_hub.models[handle];
+
+    // The extension overrides the texture coordinate set
+    let transformed = &model.materials[0];
+    assert_eq!(
+        transformed.base_color_mapping,
+        blade_render::model::TextureMapping::new(1, [0.5, 0.25], 0.0, [2.0; 2])
+    );
+    assert_eq!(transformed.normal_mapping.tex_coord, 0);
+    let [c0, c1, offset] = transformed.normal_mapping.transform;
+    for (actual, expected) in [c0, c1, offset]
+        .iter()
+        .flatten()
+        .zip([0.0, -1.0, 1.0, 0.0, 0.0, 0.0])
+    {
+        assert!((actual - expected).abs() < 1e-6, "{c0:?} {c1:?} {offset:?}");
+    }
+    assert_eq!(
+        model.materials[1].base_color_mapping,
+        blade_render::model::TextureMapping::default()
+    );
+
+    // The second set is only kept where it's both present and used
+    let has_tex_coords1 = model
+        .geometries
+        .iter()
+        .map(|geo| geo.has_tex_coords1)
+        .collect::<Vec<_>>();
+    assert_eq!(has_tex_coords1, [true, false, false]);
+    assert_ne!(model.tex_coords1_buffer, gpu::Buffer::default());
+
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn draco_models() {
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-draco-test", true)
+    else {
+        return;
+    };
+    let meta = blade_render::model::Meta {
+        front_face: blade_render::model::FrontFace::CounterClockwise,
+        ..Default::default()
+    };
+    let load = |path: &str| {
+        let (handle, task) = asset_hub.models.load(path, meta.clone());
+        task.clone().join();
+        assert_eq!(
+            asset_hub.models.status(handle),
+            blade_asset::AssetStatus::Ready,
+            "{path}"
+        );
+        handle
+    };
+    // The same grid and cube, uncompressed, then with only the grid compressed
+    // losslessly, then with the quantized attributes in both of them
+    let handles = [
+        load("tests/data/draco_reference.gltf"),
+        load("tests/data/draco_mixed.gltf"),
+        load("tests/data/draco_quantized.gltf"),
+    ];
+
+    let geometries = |handle| {
+        asset_hub.models[handle]
+            .geometries
+            .iter()
+            .map(|geo| {
+                (
+                    geo.triangle_count,
+                    geo.vertex_range.clone(),
+                    geo.material_index,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let reference_geometries = geometries(handles[0]);
+    assert_eq!(reference_geometries.len(), 2);
+    assert_eq!(geometries(handles[1]), reference_geometries);
+    assert_eq!(geometries(handles[2]), reference_geometries);
+    let vertex_count = reference_geometries
+        .iter()
+        .map(|(_, range, _)| range.end as usize)
+        .max()
+        .unwrap();
+
+    let readbacks = handles.map(|_| {
+        context.create_buffer(gpu::BufferDesc {
+            name: "draco-readback",
+            size: (vertex_count * size_of::<blade_render::Vertex>()) as u64,
+            memory: gpu::Memory::Shared,
+        })
+    });
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    if let mut transfer = command_encoder.transfer("draco-readback") {
+        for (&handle, &readback) in handles.iter().zip(&readbacks) {
+            transfer.copy_buffer_to_buffer(
+                asset_hub.models[handle].vertex_buffer.into(),
+                readback.into(),
+                (vertex_count * size_of::<blade_render::Vertex>()) as u64,
+            );
+        }
+    }
+    let sync_point = pacer.end_frame(&context).clone();
+    assert!(context.wait_for(&sync_point, 5000).unwrap());
+    let [reference, mixed, quantized] =
+        readbacks.map(|readback| readback.read_slice::<blade_render::Vertex>(0, vertex_count));
+
+    // Lossless compression produces exactly the same vertices
+    assert_eq!(
+        bytemuck::cast_slice::<_, u8>(&mixed),
+        bytemuck::cast_slice::<_, u8>(&reference)
+    );
+    let radius = asset_hub.models[handles[0]].radius;
+    assert_eq!(asset_hub.models[handles[1]].radius, radius);
+
+    // Quantized attributes are within a step of their grids
+    let max_error = |a: &[f32], b: &[f32]| {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| (x - y).abs())
+            .fold(0.0, f32::max)
+    };
+    for (q, r) in quantized.iter().zip(&reference) {
+        assert!(max_error(&q.position, &r.position) < 1e-3, "{q:?} {r:?}");
+        assert!(
+            max_error(&q.tex_coords, &r.tex_coords) < 1e-3,
+            "{q:?} {r:?}"
+        );
+        for (qn, rn) in q.normal.to_le_bytes().iter().zip(r.normal.to_le_bytes()) {
+            assert!((*qn as i8 - rn as i8).abs() <= 1, "{q:?} {r:?}");
+        }
+    }
+    assert!((asset_hub.models[handles[2]].radius - radius).abs() < 1e-3);
+
+    for readback in readbacks {
+        context.destroy_buffer(readback);
+    }
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn optimized_model_cooking() {
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => std::sync::Arc::new(c),
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let choir = choir::Choir::new();
+    let _worker = choir.add_worker("worker");
+    let mut asset_hub = blade_render::AssetHub::new(
+        &std::env::temp_dir().join("blade-optimize-test"),
+        &choir,
+        &context,
+    );
+    let load = |optimize: bool, quantize: bool| {
+        let (handle, task) = asset_hub.models.load(
+            "examples/scene/data/monkey.gltf",
+            blade_render::model::Meta {
+                generate_tangents: true,
+                optimize,
+                quantize,
+                ..Default::default()
+            },
+        );
+        task.clone().join();
+        handle
+    };
+    let plain = load(false, false);
+    let optimized = load(true, false);
+    let quantized = load(true, true);
+
+    // Reordering keeps all the triangles and the used vertices
+    let counts = |handle| {
+        asset_hub.models[handle]
+            .geometries
+            .iter()
+            .map(|geo| (geo.triangle_count, geo.vertex_range.len()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(counts(plain), counts(optimized));
+    assert_eq!(counts(plain), counts(quantized));
+
+    // Quantized positions are within a step of the 16-bit grid
+    let radius = asset_hub.models[plain].radius;
+    assert_eq!(asset_hub.models[optimized].radius, radius);
+    let error = (asset_hub.models[quantized].radius - radius).abs();
+    assert!(error <= radius * 4.0 / u16::MAX as f32, "{error}");
+
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn procedural_mesh_lifecycle() {
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => std::sync::Arc::new(c),
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let choir = choir::Choir::new();
+    let _worker = choir.add_worker("worker");
+    let mut asset_hub = blade_render::AssetHub::new(
+        &std::env::temp_dir().join("blade-procedural-test"),
+        &choir,
+        &context,
+    );
+    let quad = |height: f32| {
+        [[-1.0, -1.0], [-1.0, 1.0], [1.0, 1.0], [1.0, -1.0]]
+            .map(|[x, z]| {
+                blade_render::Vertex::new(
+                    [x, height, z],
+                    [0.0, 0.0],
+                    [0.0, 1.0, 0.0],
+                    [1.0, 0.0, 0.0, 1.0],
+                )
+            })
+            .to_vec()
+    };
+    let vertex = quad(0.0)[0];
+    // packed snorm of +Y and +X
+    assert_eq!((vertex.normal, vertex.tangent), (0x7F00, 0x7F));
+
+    let mut scene = blade_render::Scene::new();
+    let handle = scene.add_procedural_mesh(
+        &asset_hub,
+        blade_render::ProceduralGeometry {
+            name: "quad".to_string(),
+            vertices: quad(0.0),
+            indices: vec![0, 1, 2, 0, 2, 3],
+            base_color_factor: [1.0; 4],
+        },
+    );
+    let model = scene.get(handle).unwrap().model;
+    let has_ray_query = !context.capabilities().ray_query.is_empty();
+    assert_eq!(
+        asset_hub.models[model].acceleration_structure != gpu::AccelerationStructure::default(),
+        has_ray_query
+    );
+
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "procedural",
+        buffer_count: 1,
+    });
+    let mut temp = blade_render::FrameResources::default();
+    let mut submit = |asset_hub: &blade_render::AssetHub,
+                      temp: &mut blade_render::FrameResources| {
+        command_encoder.start();
+        asset_hub.flush(&mut command_encoder, &mut temp.buffers);
+        let sync_point = context.submit(&mut command_encoder);
+        assert!(context.wait_for(&sync_point, 2000).unwrap());
+    };
+    submit(&asset_hub, &mut temp);
+
+    // The vertices are shared, so the update is visible right away
+    scene.update_procedural_mesh(&asset_hub, handle, &quad(2.0));
+    let vertices = unsafe {
+        std::slice::from_raw_parts(
+            asset_hub.models[model].vertex_buffer.data() as *const blade_render::Vertex,
+            4,
+        )
+    };
+    assert!(vertices.iter().all(|v| v.position[1] == 2.0));
+    submit(&asset_hub, &mut temp);
+
+    scene.remove_procedural_mesh(&asset_hub, handle);
+    assert!(scene.is_empty());
+    assert_eq!(asset_hub.models.ref_count(model), 0);
+    asset_hub.evict(&mut temp);
+    assert_eq!(
+        asset_hub.models.status(model),
+        blade_asset::AssetStatus::Unloaded
+    );
+
+    for buffer in temp.buffers.drain(..) {
+        context.destroy_buffer(buffer);
+    }
+    for acceleration_structure in temp.acceleration_structures.drain(..) {
+        context.destroy_acceleration_structure(acceleration_structure);
+    }
+    context.destroy_command_encoder(&mut command_encoder);
+    asset_hub.destroy();
+}
@@ -0,0 +1,634 @@
+//! Fixtures shared by the GPU integration tests.
+
+use blade_graphics as gpu;
+
+#[path = "../snapshot.rs"]
+pub mod snapshot;
+
+/// Context and assets of a renderer test.
+#[cfg(not(gles))]
+pub struct TestBed {
+    pub context: std::sync::Arc<gpu::Context>,
+    pub asset_hub: blade_render::AssetHub,
+    pub pacer: blade_render::util::FramePacer,
+    pub _worker: choir::WorkerHandle,
+}
+
+#[cfg(not(gles))]
+impl TestBed {
+    /// Initialize a context, with ray queries in compute shaders if `ray_tracing` is set.
+    ///
+    /// Returns `None` if that's not available, so the test can be skipped.
+    pub fn new(name: &str, ray_tracing: bool) -> Option<Self> {
+        // Metal acceleration structure APIs can throw uncatchable ObjC exceptions in CI
+        if ray_tracing && cfg!(target_os = "macos") {
+            println!("Skipping: ray tracing not supported on macOS CI");
+            return None;
+        }
+        let context = match unsafe {
+            gpu::Context::init(gpu::ContextDesc {
+                ray_tracing,
+                ..Default::default()
+            })
+        } {
+            Ok(c) => std::sync::Arc::new(c),
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return None;
+            }
+        };
+        if ray_tracing
+            && !context
+                .capabilities()
+                .ray_query
+                .contains(gpu::ShaderVisibility::COMPUTE)
+        {
+            println!("Skipping: ray_query compute not supported");
+            return None;
+        }
+
+        let choir = choir::Choir::new();
+        let worker = choir.add_worker("worker");
+        let asset_hub =
+            blade_render::AssetHub::new(&std::env::temp_dir().join(name), &choir, &context);
+        let pacer = blade_render::util::FramePacer::new(&context);
+        Some(Self {
+            context,
+            asset_hub,
+            pacer,
+            _worker: worker,
+        })
+    }
+}
+
+/// Renderer configuration of an `Rgba8Unorm` surface.
+#[cfg(not(gles))]
+pub fn test_render_config(size: gpu::Extent) -> blade_render::RenderConfig {
+    blade_render::RenderConfig {
+        surface_size: size,
+        surface_info: gpu::SurfaceInfo {
+            format: gpu::TextureFormat::Rgba8Unorm,
+            alpha: gpu::AlphaMode::Ignored,
+        },
+        max_debug_lines: 1,
+    }
+}
+
+#[cfg(not(gles))]
+pub fn create_ray_tracer(
+    context: &gpu::Context,
+    asset_hub: &blade_render::AssetHub,
+    pacer: &mut blade_render::util::FramePacer,
+    size: gpu::Extent,
+) -> blade_render::RayTracer {
+    let (shaders, shader_task) =
+        blade_render::Shaders::load("blade-render/code/".as_ref(), asset_hub, true);
+    shader_task.join();
+    let (command_encoder, _) = pacer.begin_frame();
+    let ray_tracer = blade_render::RayTracer::new(
+        command_encoder,
+        context,
+        shaders,
+        &asset_hub.shaders,
+        &test_render_config(size),
+    );
+    pacer.end_frame(context);
+    ray_tracer
+}
+
+#[cfg(not(gles))]
+pub fn create_rasterizer(
+    context: &gpu::Context,
+    asset_hub: &blade_render::AssetHub,
+    pacer: &mut blade_render::util::FramePacer,
+    size: gpu::Extent,
+) -> blade_render::Rasterizer {
+    let (shaders, shader_task) =
+        blade_render::Shaders::load("blade-render/code/".as_ref(), asset_hub, false);
+    shader_task.join();
+    let (command_encoder, _) = pacer.begin_frame();
+    let rasterizer = blade_render::Rasterizer::new(
+        command_encoder,
+        context,
+        shaders,
+        &asset_hub.shaders,
+        &test_render_config(size),
+    );
+    pacer.end_frame(context);
+    rasterizer
+}
+
+/// Square in the XZ plane, facing up.
+#[cfg(not(gles))]
+pub fn quad_geometry(
+    name: &str,
+    half_size: f32,
+    base_color_factor: [f32; 4],
+) -> blade_render::ProceduralGeometry {
+    let corners = [[-1.0, -1.0], [-1.0, 1.0], [1.0, 1.0], [1.0, -1.0]];
+    blade_render::ProceduralGeometry {
+        name: name.to_string(),
+        vertices: corners
+            .iter()
+            .map(|&[x, z]| {
+                blade_render::Vertex::new(
+                    [x * half_size, 0.0, z * half_size],
+                    [0.0, 0.0],
+                    [0.0, 1.0, 0.0],
+                    [1.0, 0.0, 0.0, 1.0],
+                )
+            })
+            .collect(),
+        indices: vec![0, 1, 2, 0, 2, 3],
+        base_color_factor,
+    }
+}
+
+/// Camera above the origin, looking straight down.
+#[cfg(not(gles))]
+pub fn top_down_camera(height: f32) -> blade_render::Camera {
+    let half_sqrt = std::f32::consts::FRAC_1_SQRT_2;
+    blade_render::Camera {
+        pos: [0.0, height, 0.0].into(),
+        rot: mint::Quaternion {
+            s: half_sqrt,
+            v: [-half_sqrt, 0.0, 0.0].into(),
+        },
+        fov_y: 1.0,
+        depth: 100.0,
+        fov: None,
+        lens: blade_render::Lens::default(),
+        projection: blade_render::Projection::default(),
+    }
+}
+
+/// Accumulate `frame_count` frames from scratch and read back the HDR image.
+#[cfg(not(gles))]
+pub fn accumulate_hdr(
+    context: &gpu::Context,
+    pacer: &mut blade_render::util::FramePacer,
+    ray_tracer: &mut blade_render::RayTracer,
+    camera: &blade_render::Camera,
+    frame_count: u32,
+) -> Vec<f32> {
+    accumulate_hdr_with(
+        context,
+        pacer,
+        ray_tracer,
+        camera,
+        blade_helpers::default_ray_config(),
+        frame_count,
+    )
+}
+
+/// Same as `accumulate_hdr`, with a custom ray configuration.
+#[cfg(not(gles))]
+pub fn accumulate_hdr_with(
+    context: &gpu::Context,
+    pacer: &mut blade_render::util::FramePacer,
+    ray_tracer: &mut blade_render::RayTracer,
+    camera: &blade_render::Camera,
+    ray_config: blade_render::RayConfig,
+    frame_count: u32,
+) -> Vec<f32> {
+    for i in 0..frame_count {
+        let (command_encoder, _) = pacer.begin_frame();
+        ray_tracer.prepare(
+            command_encoder,
+            camera,
+            blade_render::FrameConfig {
+                reset_reservoirs: i == 0,
+                accumulate: true,
+                ..Default::default()
+            },
+        );
+        ray_tracer.ray_trace(
+            command_encoder,
+            blade_render::DebugConfig::default(),
+            ray_config,
+        );
+        pacer.end_frame(context);
+    }
+    assert_eq!(ray_tracer.accumulated_frames(), frame_count);
+
+    let (command_encoder, _) = pacer.begin_frame();
+    let readback = ray_tracer.read_back_hdr(command_encoder, context);
+    let sync_point = pacer.end_frame(context).clone();
+    assert!(context.wait_for(&sync_point, 5000).unwrap());
+    readback.into_pixels(context)
+}
+
+/// Initialize a context with ray queries in compute shaders,
+/// or return `None` if that's not available, so the test can be skipped.
+#[cfg(not(gles))]
+pub fn ray_tracing_context() -> Option<gpu::Context> {
+    // Metal acceleration structure APIs can throw uncatchable ObjC exceptions in CI
+    if cfg!(target_os = "macos") {
+        println!("Skipping: ray tracing not supported on macOS CI");
+        return None;
+    }
+    let context = match unsafe {
+        gpu::Context::init(gpu::ContextDesc {
+            ray_tracing: true,
+            ..Default::default()
+        })
+    } {
+        Ok(c) => c,
+        Err(e) => {
+            println!("Skipping: GPU context with ray tracing not available: {e:?}");
+            return None;
+        }
+    };
+    if !context
+        .capabilities()
+        .ray_query
+        .contains(gpu::ShaderVisibility::COMPUTE)
+    {
+        println!("Skipping: ray_query compute not supported");
+        return None;
+    }
+    Some(context)
+}
+
+/// Vertices of a single triangle, in a shared buffer.
+#[cfg(not(gles))]
+pub fn create_triangle_buffer(context: &gpu::Context) -> gpu::Buffer {
+    let vertices: [[f32; 3]; 3] = [[-1.0, -1.0, 0.0], [1.0, -1.0, 0.0], [0.0, 1.0, 0.0]];
+    let buffer = context.create_buffer(gpu::BufferDesc {
+        name: "triangle",
+        size: std::mem::size_of_val(&vertices) as u64,
+        memory: gpu::Memory::Shared,
+    });
+    unsafe {
+        std::ptr::copy_nonoverlapping(vertices.as_ptr(), buffer.data() as *mut [f32; 3], 3);
+    }
+    buffer
+}
+
+#[cfg(not(gles))]
+pub fn triangle_mesh(vertex_buffer: gpu::Buffer) -> gpu::AccelerationStructureMesh {
+    gpu::AccelerationStructureMesh {
+        vertex_data: vertex_buffer.at(0),
+        vertex_format: gpu::VertexFormat::F32Vec3,
+        vertex_stride: 12,
+        vertex_count: 3,
+        index_data: gpu::Buffer::default().at(0),
+        index_type: None,
+        triangle_count: 1,
+        transform_data: gpu::Buffer::default().at(0),
+        is_opaque: true,
+    }
+}
+
+/// Mean radiance of the pixels within the rectangle `[x0, y0, x1, y1)` of an HDR image.
+#[cfg(not(gles))]
+pub fn mean_radiance(pixels: &[f32], size: gpu::Extent, rect: [u32; 4]) -> f32 {
+    let [x0, y0, x1, y1] = rect;
+    let mut sum = 0.0;
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let p = &pixels[(y * size.width + x) as usize * 4..][..3];
+            sum += p[0] + p[1] + p[2];
+        }
+    }
+    sum / (3 * (x1 - x0) * (y1 - y0)) as f32
+}
+
+/// Ray configuration without the environment, so that only the lights illuminate the scene.
+#[cfg(not(gles))]
+pub fn dark_ray_config() -> blade_render::RayConfig {
+    blade_render::RayConfig {
+        environment: blade_render::EnvironmentConfig {
+            intensity: 0.0,
+            ..Default::default()
+        },
+        ..blade_helpers::default_ray_config()
+    }
+}
+
+/// Transform that turns a quad of `quad_geometry` upside down, and moves it up.
+#[cfg(not(gles))]
+pub fn flipped_at_height(height: f32) -> gpu::Transform {
+    mint::RowMatrix3x4 {
+        x: [1.0, 0.0, 0.0, 0.0].into(),
+        y: [0.0, -1.0, 0.0, height].into(),
+        z: [0.0, 0.0, -1.0, 0.0].into(),
+    }
+}
+
+#[cfg(not(gles))]
+pub fn translation(offset: [f32; 3]) -> gpu::Transform {
+    mint::RowMatrix3x4 {
+        x: [1.0, 0.0, 0.0, offset[0]].into(),
+        y: [0.0, 1.0, 0.0, offset[1]].into(),
+        z: [0.0, 0.0, 1.0, offset[2]].into(),
+    }
+}
+
+/// Ray trace and denoise a frame, then tone map it into the target,
+/// and read back the pixels.
+#[cfg(not(gles))]
+#[allow(clippy::too_many_arguments)]
+pub fn render_denoised_frame(
+    context: &gpu::Context,
+    pacer: &mut blade_render::util::FramePacer,
+    ray_tracer: &mut blade_render::RayTracer,
+    asset_hub: &blade_render::AssetHub,
+    target: &snapshot::OffscreenTarget,
+    camera: &blade_render::Camera,
+    objects: &[blade_render::Object],
+    reset: bool,
+) -> Vec<u8> {
+    render_denoised_frame_with(
+        context,
+        pacer,
+        ray_tracer,
+        target,
+        camera,
+        reset,
+        |ray_tracer, command_encoder, temp| {
+            ray_tracer.build_scene(command_encoder, objects, None, asset_hub, context, temp)
+        },
+    )
+}
+
+/// Same as `render_denoised_frame`, with the scene set up by `update`.
+#[cfg(not(gles))]
+pub fn render_denoised_frame_with(
+    context: &gpu::Context,
+    pacer: &mut blade_render::util::FramePacer,
+    ray_tracer: &mut blade_render::RayTracer,
+    target: &snapshot::OffscreenTarget,
+    camera: &blade_render::Camera,
+    reset: bool,
+    update: impl FnOnce(
+        &mut blade_render::RayTracer,
+        &mut gpu::CommandEncoder,
+        &mut blade_render::FrameResources,
+    ),
+) -> Vec<u8> {
+    let (command_encoder, temp) = pacer.begin_frame();
+    update(ray_tracer, command_encoder, temp);
+    ray_tracer.prepare(
+        command_encoder,
+        camera,
+        blade_render::FrameConfig {
+            reset_reservoirs: reset,
+            ..Default::default()
+        },
+    );
+    ray_tracer.ray_trace(
+        command_encoder,
+        blade_render::DebugConfig::default(),
+        blade_helpers::default_ray_config(),
+    );
+    ray_tracer.denoise(command_encoder, blade_render::DenoiserConfig::default());
+    if let mut pass = command_encoder.render(
+        "draw",
+        gpu::RenderTargetSet {
+            colors: &[gpu::RenderTarget {
+                view: target.view,
+                init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                finish_op: gpu::FinishOp::Store,
+            }],
+            depth_stencil: None,
+            depth_stencil_read_only: gpu::TexelAspects::empty(),
+            multiview: None,
+        },
+    ) {
+        ray_tracer.post_proc(
+            &mut pass,
+            blade_render::DebugConfig::default(),
+            blade_render::PostProcConfig::default(),
+            &[],
+            &[],
+        );
+    }
+    if let mut transfer = command_encoder.transfer("read-back") {
+        transfer.copy_texture_to_buffer(
+            target.texture.into(),
+            target.readback.into(),
+            target.size.width * 4,
+            target.size,
+        );
+    }
+    let sync_point = pacer.end_frame(context).clone();
+    assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+    let byte_count = (target.size.width * target.size.height * 4) as usize;
+    let mut pixels = vec![0u8; byte_count];
+    unsafe {
+        std::ptr::copy_nonoverlapping(target.readback.data(), pixels.as_mut_ptr(), byte_count);
+    }
+    pixels
+}
+
+/// Largest difference of the mean colors of 8x8 blocks between the images.
+#[cfg(not(gles))]
+pub fn max_block_error(a: &[u8], b: &[u8], size: gpu::Extent) -> f32 {
+    const BLOCK: usize = 8;
+    let width = size.width as usize;
+    let block_mean = |pixels: &[u8], bx: usize, by: usize| {
+        let mut sum = 0.0;
+        for y in by * BLOCK..(by + 1) * BLOCK {
+            for x in bx * BLOCK..(bx + 1) * BLOCK {
+                let p = &pixels[(y * width + x) * 4..][..3];
+                sum += p.iter().map(|&c| c as f32).sum::<f32>();
+            }
+        }
+        sum / (3 * BLOCK * BLOCK) as f32
+    };
+    let mut max_error = 0.0f32;
+    for by in 0..size.height as usize / BLOCK {
+        for bx in 0..width / BLOCK {
+            let error = (block_mean(a, bx, by) - block_mean(b, bx, by)).abs();
+            max_error = max_error.max(error);
+        }
+    }
+    max_error
+}
+
+/// Ray trace a frame showing the debug view, denoised with `denoiser_config`,
+/// and read it back.
+#[cfg(not(gles))]
+#[allow(clippy::too_many_arguments)]
+pub fn render_debug_view(
+    context: &gpu::Context,
+    pacer: &mut blade_render::util::FramePacer,
+    ray_tracer: &mut blade_render::RayTracer,
+    target: &snapshot::OffscreenTarget,
+    camera: &blade_render::Camera,
+    reset: bool,
+    debug_config: blade_render::DebugConfig,
+    ray_config: blade_render::RayConfig,
+    denoiser_config: blade_render::DenoiserConfig,
+) -> Vec<u8> {
+    let (command_encoder, _) = pacer.begin_frame();
+    ray_tracer.prepare(
+        command_encoder,
+        camera,
+        blade_render::FrameConfig {
+            reset_reservoirs: reset,
+            ..Default::default()
+        },
+    );
+    ray_tracer.ray_trace(command_encoder, debug_config, ray_config);
+    ray_tracer.denoise(command_encoder, denoiser_config);
+    if let mut pass = command_encoder.render(
+        "draw",
+        gpu::RenderTargetSet {
+            colors: &[gpu::RenderTarget {
+                view: target.view,
+                init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                finish_op: gpu::FinishOp::Store,
+            }],
+            depth_stencil: None,
+            depth_stencil_read_only: gpu::TexelAspects::empty(),
+            multiview: None,
+        },
+    ) {
+        ray_tracer.post_proc(
+            &mut pass,
+            debug_config,
+            blade_render::PostProcConfig::default(),
+            &[],
+            &[],
+        );
+    }
+    if let mut transfer = command_encoder.transfer("read-back") {
+        transfer.copy_texture_to_buffer(
+            target.texture.into(),
+            target.readback.into(),
+            target.size.width * 4,
+            target.size,
+        );
+    }
+    let sync_point = pacer.end_frame(context).clone();
+    assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+    let byte_count = (target.size.width * target.size.height * 4) as usize;
+    let mut pixels = vec![0u8; byte_count];
+    unsafe {
+        std::ptr::copy_nonoverlapping(target.readback.data(), pixels.as_mut_ptr(), byte_count);
+    }
+    pixels
+}
+
+/// Mean color of the pixels within the rectangle.
+#[cfg(not(gles))]
+pub fn mean_color(pixels: &[u8], size: gpu::Extent, rect: [u32; 4]) -> [f32; 3] {
+    let [x0, y0, x1, y1] = rect;
+    let mut sum = [0.0; 3];
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let p = &pixels[(y * size.width + x) as usize * 4..][..3];
+            for (s, &c) in sum.iter_mut().zip(p) {
+                *s += c as f32;
+            }
+        }
+    }
+    sum.map(|s| s / ((x1 - x0) * (y1 - y0)) as f32)
+}
+
+/// The view is replaced by the checkerboard of unavailable buffers.
+#[cfg(not(gles))]
+pub fn is_checkerboard(pixels: &[u8]) -> bool {
+    pixels
+        .chunks(4)
+        .all(|p| p[0] == p[1] && p[1] == p[2] && (p[0] == 51 || p[0] == 102))
+}
+
+/// Post-process the accumulated image into the target and read it back.
+#[cfg(not(gles))]
+pub fn post_process_accumulated(
+    context: &gpu::Context,
+    pacer: &mut blade_render::util::FramePacer,
+    ray_tracer: &mut blade_render::RayTracer,
+    target: &snapshot::OffscreenTarget,
+    pp_config: blade_render::PostProcConfig,
+) -> Vec<u8> {
+    let (command_encoder, _) = pacer.begin_frame();
+    ray_tracer.render_bloom(command_encoder, pp_config.bloom);
+    if let mut pass = command_encoder.render(
+        "draw",
+        gpu::RenderTargetSet {
+            colors: &[gpu::RenderTarget {
+                view: target.view,
+                init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                finish_op: gpu::FinishOp::Store,
+            }],
+            depth_stencil: None,
+            depth_stencil_read_only: gpu::TexelAspects::empty(),
+            multiview: None,
+        },
+    ) {
+        ray_tracer.post_proc(
+            &mut pass,
+            blade_render::DebugConfig::default(),
+            pp_config,
+            &[],
+            &[],
+        );
+    }
+    if let mut transfer = command_encoder.transfer("read-back") {
+        transfer.copy_texture_to_buffer(
+            target.texture.into(),
+            target.readback.into(),
+            target.size.width * 4,
+            target.size,
+        );
+    }
+    let sync_point = pacer.end_frame(context).clone();
+    assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+    let byte_count = (target.size.width * target.size.height * 4) as usize;
+    let mut pixels = vec![0u8; byte_count];
+    unsafe {
+        std::ptr::copy_nonoverlapping(target.readback.data(), pixels.as_mut_ptr(), byte_count);
+    }
+    pixels
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct QuadParams {
+    pub rect: [f32; 4],
+    pub color: [f32; 4],
+    pub size: [f32; 2],
+    pub depth: f32,
+    pub pad: f32,
+}
+
+#[derive(blade_macros::ShaderData)]
+pub struct QuadData {
+    pub params: QuadParams,
+}
+
+#[derive(Clone, Copy)]
+pub struct DispatchGlobals {
+    pub input: gpu::BufferPiece,
+    pub output: gpu::BufferPiece,
+}
+
+impl gpu::ShaderData for DispatchGlobals {
+    fn layout() -> gpu::ShaderDataLayout {
+        gpu::ShaderDataLayout {
+            bindings: vec![
+                ("input", gpu::ShaderBinding::Buffer),
+                ("output", gpu::ShaderBinding::Buffer),
+            ],
+        }
+    }
+
+    fn fill(&self, mut ctx: gpu::PipelineContext) {
+        use gpu::ShaderBindable as _;
+        self.input.bind_to(&mut ctx, 0);
+        self.output.bind_to(&mut ctx, 1);
+    }
+}
+
+#[derive(blade_macros::ShaderData)]
+pub struct NullableDispatchGlobals {
+    pub input: gpu::Nullable<gpu::BufferPiece>,
+    pub output: gpu::BufferPiece,
+}
@@ -0,0 +1,42 @@
+use blade_graphics as gpu;
+
+fn transform(m: &mint::ColumnMatrix4<f32>, v: [f32; 4]) -> [f32; 4] {
+    let columns = [m.x, m.y, m.z, m.w];
+    let mut out = [0.0; 4];
+    for (column, &value) in columns.iter().zip(v.iter()) {
+        out[0] += column.x * value;
+        out[1] += column.y * value;
+        out[2] += column.z * value;
+        out[3] += column.w * value;
+    }
+    out
+}
+
+#[test]
+fn ndc_corners_to_uv() {
+    // Top left of the viewport is the origin of the texture
+    assert_eq!(gpu::ndc_to_uv([-1.0, 1.0]), [0.0, 0.0]);
+    assert_eq!(gpu::ndc_to_uv([1.0, -1.0]), [1.0, 1.0]);
+    assert_eq!(gpu::ndc_to_uv([0.0, 0.0]), [0.5, 0.5]);
+    for ndc in [[-1.0, 1.0], [1.0, -1.0], [0.5, -0.25]] {
+        assert_eq!(gpu::uv_to_ndc(gpu::ndc_to_uv(ndc)), ndc);
+    }
+}
+
+#[test]
+fn matrices_match_functions() {
+    for point in [[-1.0, 1.0], [1.0, -1.0], [0.25, 0.75]] {
+        let uv = transform(&gpu::NDC_TO_UV, [point[0], point[1], 0.0, 1.0]);
+        assert_eq!([uv[0], uv[1]], gpu::ndc_to_uv(point));
+        let ndc = transform(&gpu::UV_TO_NDC, [point[0], point[1], 0.0, 1.0]);
+        assert_eq!([ndc[0], ndc[1]], gpu::uv_to_ndc(point));
+    }
+}
+
+#[test]
+fn gl_depth_range() {
+    let near = transform(&gpu::GL_CLIP_TO_CLIP, [0.0, 0.0, -1.0, 1.0]);
+    let far = transform(&gpu::GL_CLIP_TO_CLIP, [0.0, 0.0, 1.0, 1.0]);
+    assert_eq!(near[2], gpu::NDC_DEPTH_RANGE.start);
+    assert_eq!(far[2], gpu::NDC_DEPTH_RANGE.end);
+}
@@ -0,0 +1,40 @@
+use blade_graphics::util::fold_group_count;
+
+const LIMITS: [u32; 3] = [65535; 3];
+
+#[test]
+fn linear_dispatch_within_x() {
+    assert_eq!(fold_group_count(0, LIMITS), [0, 1, 1]);
+    assert_eq!(fold_group_count(1, LIMITS), [1, 1, 1]);
+    assert_eq!(fold_group_count(65535, LIMITS), [65535, 1, 1]);
+}
+
+#[test]
+fn linear_dispatch_folds_into_y() {
+    assert_eq!(fold_group_count(65536, LIMITS), [65535, 2, 1]);
+    assert_eq!(fold_group_count(2 * 65535, LIMITS), [65535, 2, 1]);
+    assert_eq!(fold_group_count(2 * 65535 + 1, LIMITS), [65535, 3, 1]);
+    // 40M particles with 64 invocations per group
+    let groups = 40_000_000u32.div_ceil(64);
+    let [x, y, z] = fold_group_count(groups, LIMITS);
+    assert_eq!([x, y, z], [65535, 10, 1]);
+    assert!(x * y * z >= groups && x * (y - 1) < groups);
+}
+
+#[test]
+fn linear_dispatch_folds_into_z() {
+    let limits = [100, 10, 10];
+    assert_eq!(fold_group_count(1000, limits), [100, 10, 1]);
+    assert_eq!(fold_group_count(1001, limits), [100, 10, 2]);
+    assert_eq!(fold_group_count(10000, limits), [100, 10, 10]);
+    assert_eq!(
+        fold_group_count(u32::MAX, [u32::MAX / 2 + 1, 2, 1]),
+        [u32::MAX / 2 + 1, 2, 1]
+    );
+}
+
+#[test]
+#[should_panic]
+fn linear_dispatch_overflow() {
+    fold_group_count(10001, [100, 10, 10]);
+}
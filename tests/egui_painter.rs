@@ -0,0 +1,1044 @@
+//! Texture management and painting of the egui integration.
+#![allow(irrefutable_let_patterns)]
+#![cfg(not(gles))]
+
+use blade_graphics as gpu;
+use std::slice;
+
+#[allow(dead_code)]
+mod common;
+
+use common::snapshot;
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn egui_user_textures() {
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => c,
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let size = gpu::Extent {
+        width: 8,
+        height: 8,
+        depth: 1,
+    };
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut painter = blade_egui::GuiPainter::new(
+        gpu::SurfaceInfo {
+            format,
+            alpha: gpu::AlphaMode::Ignored,
+        },
+        &context,
+    );
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "egui-user-textures",
+        buffer_count: 1,
+    });
+    let sd = blade_egui::ScreenDescriptor {
+        physical_size: (size.width, size.height),
+        scale_factor: 1.0,
+    };
+    let options = egui::TextureOptions::NEAREST;
+    let encoding = blade_egui::TextureEncoding::Srgb;
+
+    // User images of different sizes, as if the viewport got resized
+    let images = [(4, [1.0, 0.0, 0.0, 1.0]), (2, [0.0, 1.0, 0.0, 1.0])].map(|(extent, color)| {
+        let texture = context.create_texture(gpu::TextureDesc {
+            name: "user-image",
+            format,
+            size: gpu::Extent {
+                width: extent,
+                height: extent,
+                depth: 1,
+            },
+            dimension: gpu::TextureDimension::D2,
+            array_layer_count: 1,
+            mip_level_count: 1,
+            usage: gpu::TextureUsage::TARGET | gpu::TextureUsage::RESOURCE,
+            sample_count: 1,
+            external: None,
+        });
+        let view = context.create_texture_view(
+            texture,
+            gpu::TextureViewDesc {
+                name: "user-image",
+                format,
+                dimension: gpu::ViewDimension::D2,
+                subresources: &gpu::TextureSubresources::default(),
+            },
+        );
+        (texture, view, color)
+    });
+    command_encoder.start();
+    for &(texture, view, color) in images.iter() {
+        command_encoder.init_texture(texture);
+        let _pass = command_encoder.render(
+            "user-image",
+            gpu::RenderTargetSet {
+                colors: &[gpu::RenderTarget {
+                    view,
+                    init_op: gpu::InitOp::ClearColor(gpu::ClearColor::Float(color)),
+                    finish_op: gpu::FinishOp::Store,
+                }],
+                depth_stencil: None,
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        );
+    }
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+    // Paint a texture over the whole target, and return the pixel colors
+    let mut paint =
+        |painter: &mut blade_egui::GuiPainter, texture_id: egui::TextureId| -> Vec<u8> {
+            let rect = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(8.0, 8.0));
+            let mut mesh = egui::Mesh::with_texture(texture_id);
+            mesh.add_rect_with_uv(
+                rect,
+                egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0)),
+                egui::Color32::WHITE,
+            );
+            let primitives = [egui::ClippedPrimitive {
+                clip_rect: rect,
+                primitive: egui::epaint::Primitive::Mesh(mesh),
+            }];
+            command_encoder.start();
+            painter.update_textures(
+                &mut command_encoder,
+                &egui::TexturesDelta::default(),
+                &context,
+            );
+            command_encoder.init_texture(target.texture);
+            if let mut pass = command_encoder.render(
+                "egui",
+                gpu::RenderTargetSet {
+                    colors: &[gpu::RenderTarget {
+                        view: target.view,
+                        init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                        finish_op: gpu::FinishOp::Store,
+                    }],
+                    depth_stencil: None,
+                    depth_stencil_read_only: gpu::TexelAspects::empty(),
+                    multiview: None,
+                },
+            ) {
+                painter.paint(&mut pass, &primitives, &sd, &context);
+            }
+            let pixels = target.read_pixels(&context, &mut command_encoder);
+            painter.after_submit(&context.submit(&mut command_encoder));
+            pixels
+        };
+
+    let (_, red_view, _) = images[0];
+    let (_, green_view, _) = images[1];
+    let texture_id = painter.register_texture(red_view, options, encoding, &context);
+    assert!(matches!(texture_id, egui::TextureId::User(_)));
+    let pixels = paint(&mut painter, texture_id);
+    assert!(
+        pixels.chunks(4).all(|p| p == [255, 0, 0, 255]),
+        "Registered texture is not painted: {pixels:?}"
+    );
+
+    // The same id shows the new view after an update
+    painter
+        .update_texture(texture_id, green_view, options, encoding, &context)
+        .unwrap();
+    let pixels = paint(&mut painter, texture_id);
+    assert!(
+        pixels.chunks(4).all(|p| p == [0, 255, 0, 255]),
+        "Updated texture is not painted: {pixels:?}"
+    );
+
+    // Every registration gets a new id
+    let other_id = painter.register_texture(red_view, options, encoding, &context);
+    assert_ne!(other_id, texture_id);
+
+    // The textures of egui can't be touched
+    let managed_id = egui::TextureId::Managed(0);
+    assert_eq!(
+        painter.update_texture(managed_id, red_view, options, encoding, &context),
+        Err(blade_egui::TextureError::Managed(managed_id))
+    );
+    assert_eq!(
+        painter.unregister_texture(managed_id),
+        Err(blade_egui::TextureError::Managed(managed_id))
+    );
+
+    // An unregistered id is forgotten
+    assert_eq!(painter.unregister_texture(texture_id), Ok(()));
+    assert_eq!(
+        painter.unregister_texture(texture_id),
+        Err(blade_egui::TextureError::Unknown(texture_id))
+    );
+    assert_eq!(
+        painter.update_texture(texture_id, red_view, options, encoding, &context),
+        Err(blade_egui::TextureError::Unknown(texture_id))
+    );
+    let pixels = paint(&mut painter, other_id);
+    assert!(pixels.chunks(4).all(|p| p == [255, 0, 0, 255]));
+
+    painter.destroy(&context);
+    context.destroy_command_encoder(&mut command_encoder);
+    target.destroy(&context);
+    for (texture, view, _) in images {
+        context.destroy_texture_view(view);
+        context.destroy_texture(texture);
+    }
+}
+
+struct QuadCallback {
+    pipeline: std::sync::Arc<gpu::RenderPipeline>,
+    rect: egui::Rect,
+    color: [f32; 4],
+    prepared: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl blade_egui::CallbackTrait for QuadCallback {
+    fn prepare(
+        &self,
+        _command_encoder: &mut gpu::CommandEncoder,
+        _sd: &blade_egui::ScreenDescriptor,
+        _context: &gpu::Context,
+    ) {
+        self.prepared
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn paint(
+        &self,
+        info: egui::epaint::PaintCallbackInfo,
+        pass: &mut gpu::RenderCommandEncoder,
+        sd: &blade_egui::ScreenDescriptor,
+    ) {
+        assert_eq!(info.viewport, self.rect);
+        assert_eq!(info.pixels_per_point, sd.scale_factor);
+        // The quad covers the whole viewport of the callback
+        let mut pc = pass.with(&self.pipeline);
+        pc.bind(
+            0,
+            &common::QuadData {
+                params: common::QuadParams {
+                    rect: [-1.0, -1.0, 1.0, 1.0],
+                    color: self.color,
+                    size: [0.0; 2],
+                    depth: 0.0,
+                    pad: 0.0,
+                },
+            },
+        );
+        pc.draw(0, 6, 0, 1);
+    }
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn egui_paint_callback() {
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => c,
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let size = gpu::Extent {
+        width: 16,
+        height: 16,
+        depth: 1,
+    };
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut painter = blade_egui::GuiPainter::new(
+        gpu::SurfaceInfo {
+            format,
+            alpha: gpu::AlphaMode::Ignored,
+        },
+        &context,
+    );
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/golden.wgsl"),
+        naga_module: None,
+    });
+    let layout = <common::QuadData as gpu::ShaderData>::layout();
+    let pipeline = std::sync::Arc::new(context.create_render_pipeline(gpu::RenderPipelineDesc {
+        name: "callback",
+        data_layouts: &[&layout],
+        vertex: shader.at("vs_quad"),
+        vertex_fetches: &[],
+        primitive: Default::default(),
+        depth_stencil: None,
+        fragment: Some(shader.at("fs_color")),
+        color_targets: &[format.into()],
+        multisample_state: Default::default(),
+        multiview: None,
+    }));
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "egui-paint-callback",
+        buffer_count: 1,
+    });
+    let sd = blade_egui::ScreenDescriptor {
+        physical_size: (size.width, size.height),
+        scale_factor: 1.0,
+    };
+    let texture_id = egui::TextureId::Managed(0);
+    let white = egui::TexturesDelta {
+        set: vec![(
+            texture_id,
+            egui::epaint::ImageDelta::full(
+                egui::ColorImage::filled([1, 1], egui::Color32::WHITE),
+                egui::TextureOptions::NEAREST,
+            ),
+        )],
+        free: Vec::new(),
+    };
+    let mesh_primitive = |texture_id, rect: egui::Rect, color| {
+        let mut mesh = egui::Mesh::with_texture(texture_id);
+        mesh.add_rect_with_uv(
+            rect,
+            egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0)),
+            color,
+        );
+        egui::ClippedPrimitive {
+            clip_rect: rect,
+            primitive: egui::epaint::Primitive::Mesh(mesh),
+        }
+    };
+
+    // The callback is clipped to its left half, and it's followed
+    // by a mesh with an unknown texture, and a mesh on the right side.
+    let full_rect = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(16.0, 16.0));
+    let callback_rect = egui::Rect::from_min_max(egui::pos2(4.0, 4.0), egui::pos2(12.0, 12.0));
+    let prepared = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let primitives = vec![
+        mesh_primitive(texture_id, full_rect, egui::Color32::BLUE),
+        egui::ClippedPrimitive {
+            clip_rect: egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(8.0, 16.0)),
+            primitive: egui::epaint::Primitive::Callback(blade_egui::Callback::new_paint_callback(
+                callback_rect,
+                QuadCallback {
+                    pipeline: std::sync::Arc::clone(&pipeline),
+                    rect: callback_rect,
+                    color: [0.0, 1.0, 0.0, 1.0],
+                    prepared: std::sync::Arc::clone(&prepared),
+                },
+            )),
+        },
+        mesh_primitive(egui::TextureId::User(7), full_rect, egui::Color32::WHITE),
+        mesh_primitive(
+            texture_id,
+            egui::Rect::from_min_max(egui::pos2(12.0, 0.0), egui::pos2(16.0, 16.0)),
+            egui::Color32::RED,
+        ),
+    ];
+
+    command_encoder.start();
+    painter.update_textures(&mut command_encoder, &white, &context);
+    painter.prepare_callbacks(&mut command_encoder, &primitives, &sd, &context);
+    assert!(prepared.load(std::sync::atomic::Ordering::Relaxed));
+    command_encoder.init_texture(target.texture);
+    if let mut pass = command_encoder.render(
+        "egui",
+        gpu::RenderTargetSet {
+            colors: &[gpu::RenderTarget {
+                view: target.view,
+                init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                finish_op: gpu::FinishOp::Store,
+            }],
+            depth_stencil: None,
+            depth_stencil_read_only: gpu::TexelAspects::empty(),
+            multiview: None,
+        },
+    ) {
+        painter.paint(&mut pass, &primitives, &sd, &context);
+    }
+    let pixels = target.read_pixels(&context, &mut command_encoder);
+
+    for (i, pixel) in pixels.chunks(4).enumerate() {
+        let (x, y) = (i as u32 % size.width, i as u32 / size.width);
+        let expected = if x >= 12 {
+            [255, 0, 0, 255]
+        } else if (4..8).contains(&x) && (4..12).contains(&y) {
+            [0, 255, 0, 255]
+        } else {
+            [0, 0, 255, 255]
+        };
+        assert_eq!(pixel, expected, "Pixel at {x}x{y}");
+    }
+
+    drop(primitives);
+    painter.destroy(&context);
+    let mut pipeline = std::sync::Arc::into_inner(pipeline).unwrap();
+    context.destroy_render_pipeline(&mut pipeline);
+    context.destroy_command_encoder(&mut command_encoder);
+    target.destroy(&context);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn egui_texture_deltas() {
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => c,
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let size = gpu::Extent {
+        width: 8,
+        height: 8,
+        depth: 1,
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut painter = blade_egui::GuiPainter::new(
+        gpu::SurfaceInfo {
+            format,
+            alpha: gpu::AlphaMode::Ignored,
+        },
+        &context,
+    );
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "egui-texture-deltas",
+        buffer_count: 1,
+    });
+    let sd = blade_egui::ScreenDescriptor {
+        physical_size: (size.width, size.height),
+        scale_factor: 1.0,
+    };
+    let texture_id = egui::TextureId::Managed(0);
+    let screen_rect = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(8.0, 8.0));
+    let uv_rect = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+    let mut mesh = egui::Mesh::with_texture(texture_id);
+    mesh.add_rect_with_uv(screen_rect, uv_rect, egui::Color32::WHITE);
+    let primitives = [egui::ClippedPrimitive {
+        clip_rect: screen_rect,
+        primitive: egui::epaint::Primitive::Mesh(mesh),
+    }];
+    let options = egui::TextureOptions::NEAREST;
+
+    // Paint the texture over the whole target, and return the pixel colors
+    let mut paint = |delta: egui::TexturesDelta| -> Vec<[u8; 4]> {
+        command_encoder.start();
+        painter.update_textures(&mut command_encoder, &delta, &context);
+        command_encoder.init_texture(target.texture);
+        if let mut pass = command_encoder.render(
+            "egui",
+            gpu::RenderTargetSet {
+                colors: &[gpu::RenderTarget {
+                    view: target.view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                    finish_op: gpu::FinishOp::Store,
+                }],
+                depth_stencil: None,
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        ) {
+            painter.paint(&mut pass, &primitives, &sd, &context);
+        }
+        if let mut transfer = command_encoder.transfer("read-back") {
+            transfer.copy_texture_to_buffer(
+                target.texture.into(),
+                target.readback.into(),
+                size.width * 4,
+                size,
+            );
+        }
+        let sync_point = context.submit(&mut command_encoder);
+        painter.after_submit(&sync_point);
+        assert!(context.wait_for(&sync_point, 5000).unwrap());
+        let bytes = unsafe {
+            slice::from_raw_parts(
+                target.readback.data(),
+                (size.width * size.height * 4) as usize,
+            )
+        };
+        bytes.chunks(4).map(|p| [p[0], p[1], p[2], p[3]]).collect()
+    };
+    let red = [255, 0, 0, 255];
+    let green = [0, 255, 0, 255];
+    let blue = [0, 0, 255, 255];
+    let at = |pixels: &[[u8; 4]], x: u32, y: u32| pixels[(y * size.width + x) as usize];
+
+    // The first full image creates the texture
+    let pixels = paint(egui::TexturesDelta {
+        set: vec![(
+            texture_id,
+            egui::epaint::ImageDelta::full(
+                egui::ColorImage::filled([4, 4], egui::Color32::RED),
+                options,
+            ),
+        )],
+        free: Vec::new(),
+    });
+    assert!(pixels.iter().all(|&p| p == red), "{pixels:?}");
+
+    // A partial update only touches the bottom right quarter
+    let pixels = paint(egui::TexturesDelta {
+        set: vec![(
+            texture_id,
+            egui::epaint::ImageDelta::partial(
+                [2, 2],
+                egui::ColorImage::filled([2, 2], egui::Color32::GREEN),
+                options,
+            ),
+        )],
+        free: Vec::new(),
+    });
+    assert_eq!(at(&pixels, 1, 1), red);
+    assert_eq!(at(&pixels, 6, 1), red);
+    assert_eq!(at(&pixels, 1, 6), red);
+    assert_eq!(at(&pixels, 6, 6), green);
+
+    // The atlas grows, followed by a partial update of the new area
+    let pixels = paint(egui::TexturesDelta {
+        set: vec![
+            (
+                texture_id,
+                egui::epaint::ImageDelta::full(
+                    egui::ColorImage::filled([8, 8], egui::Color32::BLUE),
+                    options,
+                ),
+            ),
+            (
+                texture_id,
+                egui::epaint::ImageDelta::partial(
+                    [4, 0],
+                    egui::ColorImage::filled([4, 8], egui::Color32::RED),
+                    options,
+                ),
+            ),
+        ],
+        free: Vec::new(),
+    });
+    assert_eq!(at(&pixels, 1, 1), blue);
+    assert_eq!(at(&pixels, 1, 6), blue);
+    assert_eq!(at(&pixels, 6, 1), red);
+    assert_eq!(at(&pixels, 6, 6), red);
+
+    // A freed texture can still be painted in the same frame
+    let pixels = paint(egui::TexturesDelta {
+        set: Vec::new(),
+        free: vec![texture_id],
+    });
+    assert_eq!(at(&pixels, 1, 1), blue);
+
+    // The texture can be created again after it's freed
+    let pixels = paint(egui::TexturesDelta {
+        set: vec![(
+            texture_id,
+            egui::epaint::ImageDelta::full(
+                egui::ColorImage::filled([2, 2], egui::Color32::GREEN),
+                options,
+            ),
+        )],
+        free: Vec::new(),
+    });
+    assert!(pixels.iter().all(|&p| p == green), "{pixels:?}");
+
+    painter.destroy(&context);
+    context.destroy_command_encoder(&mut command_encoder);
+    target.destroy(&context);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn egui_color_modes() {
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => c,
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let size = gpu::Extent {
+        width: 4,
+        height: 4,
+        depth: 1,
+    };
+    let sd = blade_egui::ScreenDescriptor {
+        physical_size: (size.width, size.height),
+        scale_factor: 1.0,
+    };
+    let texture_id = egui::TextureId::Managed(0);
+    let screen_rect = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(4.0, 4.0));
+    let mut mesh = egui::Mesh::with_texture(texture_id);
+    // Half transparent white, premultiplied in gamma space
+    mesh.add_rect_with_uv(
+        screen_rect,
+        egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0)),
+        egui::Color32::from_white_alpha(128),
+    );
+    let primitives = [egui::ClippedPrimitive {
+        clip_rect: screen_rect,
+        primitive: egui::epaint::Primitive::Mesh(mesh),
+    }];
+    let white = egui::TexturesDelta {
+        set: vec![(
+            texture_id,
+            egui::epaint::ImageDelta::full(
+                egui::ColorImage::filled([1, 1], egui::Color32::WHITE),
+                egui::TextureOptions::NEAREST,
+            ),
+        )],
+        free: Vec::new(),
+    };
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "egui-color-modes",
+        buffer_count: 1,
+    });
+
+    // Blending over black in gamma space keeps the value of the vertex color,
+    // and blending in linear space makes it brighter once encoded into sRGB.
+    for (format, color_mode, expected) in [
+        (
+            gpu::TextureFormat::Rgba8Unorm,
+            blade_egui::UiColorMode::Gamma,
+            128,
+        ),
+        (
+            gpu::TextureFormat::Rgba8UnormSrgb,
+            blade_egui::UiColorMode::Gamma,
+            128,
+        ),
+        (
+            gpu::TextureFormat::Rgba8UnormSrgb,
+            blade_egui::UiColorMode::Linear,
+            188,
+        ),
+        (
+            gpu::TextureFormat::Rgba8Unorm,
+            blade_egui::UiColorMode::Linear,
+            128,
+        ),
+    ] {
+        let target = snapshot::OffscreenTarget::new(&context, size, format);
+        let mut painter = blade_egui::GuiPainter::new(
+            gpu::SurfaceInfo {
+                format,
+                alpha: gpu::AlphaMode::Ignored,
+            },
+            &context,
+        );
+        painter.set_color_mode(color_mode);
+
+        command_encoder.start();
+        painter.update_textures(&mut command_encoder, &white, &context);
+        command_encoder.init_texture(target.texture);
+        if let mut pass = command_encoder.render(
+            "egui",
+            gpu::RenderTargetSet {
+                colors: &[gpu::RenderTarget {
+                    view: target.view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                    finish_op: gpu::FinishOp::Store,
+                }],
+                depth_stencil: None,
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        ) {
+            painter.paint(&mut pass, &primitives, &sd, &context);
+        }
+        let pixels = target.read_pixels(&context, &mut command_encoder);
+        println!("{format:?} in {color_mode:?}: {:?}", &pixels[..4]);
+        for channel in &pixels[..3] {
+            assert!(
+                channel.abs_diff(expected) <= 2,
+                "{format:?} in {color_mode:?} has {channel}, expected {expected}"
+            );
+        }
+
+        painter.destroy(&context);
+        target.destroy(&context);
+    }
+    context.destroy_command_encoder(&mut command_encoder);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn egui_multi_viewport() {
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => c,
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let mut painter = blade_egui::GuiPainter::new(
+        gpu::SurfaceInfo {
+            format,
+            alpha: gpu::AlphaMode::Ignored,
+        },
+        &context,
+    );
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "egui-multi-viewport",
+        buffer_count: 1,
+    });
+    let texture_id = egui::TextureId::Managed(0);
+    let textures_delta = egui::TexturesDelta {
+        set: vec![(
+            texture_id,
+            egui::epaint::ImageDelta::full(
+                egui::ColorImage::filled([1, 1], egui::Color32::WHITE),
+                egui::TextureOptions::NEAREST,
+            ),
+        )],
+        free: Vec::new(),
+    };
+    let second_id = egui::ViewportId::from_hash_of("second");
+    // The viewports have different sizes, and each one is covered by a mesh of its own color
+    let viewports = [
+        (egui::ViewportId::ROOT, 8, egui::Color32::RED),
+        (second_id, 4, egui::Color32::GREEN),
+    ]
+    .map(|(id, extent, color)| {
+        let size = gpu::Extent {
+            width: extent,
+            height: extent,
+            depth: 1,
+        };
+        let rect =
+            egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(extent as f32, extent as f32));
+        let mut mesh = egui::Mesh::with_texture(texture_id);
+        mesh.add_rect_with_uv(
+            rect,
+            egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0)),
+            color,
+        );
+        let primitives = vec![egui::ClippedPrimitive {
+            clip_rect: rect,
+            primitive: egui::epaint::Primitive::Mesh(mesh),
+        }];
+        let sd = blade_egui::ScreenDescriptor {
+            physical_size: (extent, extent),
+            scale_factor: 1.0,
+        };
+        let target = snapshot::OffscreenTarget::new(&context, size, format);
+        (id, primitives, sd, target, color)
+    });
+
+    for frame in 0..2 {
+        command_encoder.start();
+        if frame == 0 {
+            painter.update_textures(&mut command_encoder, &textures_delta, &context);
+        }
+        for &(id, ref primitives, ref sd, ref target, _) in viewports.iter() {
+            // The second viewport is closed after the first frame
+            if frame == 1 && id == second_id {
+                continue;
+            }
+            command_encoder.init_texture(target.texture);
+            if let mut pass = command_encoder.render(
+                "egui",
+                gpu::RenderTargetSet {
+                    colors: &[gpu::RenderTarget {
+                        view: target.view,
+                        init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                        finish_op: gpu::FinishOp::Store,
+                    }],
+                    depth_stencil: None,
+                    depth_stencil_read_only: gpu::TexelAspects::empty(),
+                    multiview: None,
+                },
+            ) {
+                painter.paint_viewport(&mut pass, primitives, id, sd, &context);
+            }
+            if let mut transfer = command_encoder.transfer("read-back") {
+                transfer.copy_texture_to_buffer(
+                    target.texture.into(),
+                    target.readback.into(),
+                    target.size.width * 4,
+                    target.size,
+                );
+            }
+        }
+        let sync_point = context.submit(&mut command_encoder);
+        painter.after_submit(&sync_point);
+        if frame == 0 {
+            painter.remove_viewport(second_id);
+        }
+        assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+        for &(id, _, _, ref target, color) in viewports.iter() {
+            if frame == 1 && id == second_id {
+                continue;
+            }
+            let byte_count = (target.size.width * target.size.height * 4) as usize;
+            let pixels = unsafe { slice::from_raw_parts(target.readback.data(), byte_count) };
+            assert!(
+                pixels.chunks(4).all(|p| p == color.to_array()),
+                "Viewport {id:?} in frame {frame} is not filled"
+            );
+        }
+    }
+
+    painter.destroy(&context);
+    context.destroy_command_encoder(&mut command_encoder);
+    for (_, _, _, target, _) in viewports {
+        target.destroy(&context);
+    }
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn egui_dithering() {
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => c,
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 4,
+        depth: 1,
+    };
+    let sd = blade_egui::ScreenDescriptor {
+        physical_size: (size.width, size.height),
+        scale_factor: 1.0,
+    };
+    let texture_id = egui::TextureId::Managed(0);
+    let uv = egui::pos2(0.5, 0.5);
+    let mut mesh = egui::Mesh::with_texture(texture_id);
+    // A subtle gradient in the top half
+    for (x, y, gray) in [
+        (0.0, 0.0, 20),
+        (64.0, 0.0, 24),
+        (0.0, 2.0, 20),
+        (64.0, 2.0, 24),
+    ] {
+        mesh.vertices.push(egui::epaint::Vertex {
+            pos: egui::pos2(x, y),
+            uv,
+            color: egui::Color32::from_gray(gray),
+        });
+    }
+    mesh.add_triangle(0, 1, 2);
+    mesh.add_triangle(2, 1, 3);
+    // A flat color in the bottom half
+    mesh.add_rect_with_uv(
+        egui::Rect::from_min_max(egui::pos2(0.0, 2.0), egui::pos2(64.0, 4.0)),
+        egui::Rect::from_min_max(uv, uv),
+        egui::Color32::from_gray(32),
+    );
+    let primitives = [egui::ClippedPrimitive {
+        clip_rect: egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(64.0, 4.0)),
+        primitive: egui::epaint::Primitive::Mesh(mesh),
+    }];
+    let white = egui::TexturesDelta {
+        set: vec![(
+            texture_id,
+            egui::epaint::ImageDelta::full(
+                egui::ColorImage::filled([1, 1], egui::Color32::WHITE),
+                egui::TextureOptions::NEAREST,
+            ),
+        )],
+        free: Vec::new(),
+    };
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "egui-dithering",
+        buffer_count: 1,
+    });
+
+    for format in [
+        gpu::TextureFormat::Rgba8Unorm,
+        gpu::TextureFormat::Rgba8UnormSrgb,
+    ] {
+        let [plain, dithered] = [false, true].map(|dithering| {
+            let target = snapshot::OffscreenTarget::new(&context, size, format);
+            let mut painter = blade_egui::GuiPainter::new(
+                gpu::SurfaceInfo {
+                    format,
+                    alpha: gpu::AlphaMode::Ignored,
+                },
+                &context,
+            );
+            painter.set_dithering(dithering);
+            command_encoder.start();
+            painter.update_textures(&mut command_encoder, &white, &context);
+            command_encoder.init_texture(target.texture);
+            if let mut pass = command_encoder.render(
+                "egui",
+                gpu::RenderTargetSet {
+                    colors: &[gpu::RenderTarget {
+                        view: target.view,
+                        init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                        finish_op: gpu::FinishOp::Store,
+                    }],
+                    depth_stencil: None,
+                    depth_stencil_read_only: gpu::TexelAspects::empty(),
+                    multiview: None,
+                },
+            ) {
+                painter.paint(&mut pass, &primitives, &sd, &context);
+            }
+            let pixels = target.read_pixels(&context, &mut command_encoder);
+            painter.destroy(&context);
+            target.destroy(&context);
+            pixels
+        });
+
+        let half = plain.len() / 2;
+        assert_eq!(
+            plain[half..],
+            dithered[half..],
+            "{format:?}: dithering changed a flat color"
+        );
+        let mut changed = 0;
+        for (a, b) in plain[..half].iter().zip(&dithered[..half]) {
+            assert!(a.abs_diff(*b) <= 1, "{format:?}: dither is too strong");
+            if a != b {
+                changed += 1;
+            }
+        }
+        assert_ne!(changed, 0, "{format:?}: gradient is not dithered");
+    }
+    context.destroy_command_encoder(&mut command_encoder);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn egui_texture_soak() {
+    const FRAME_COUNT: u64 = 2000;
+    const IMAGE_SIZE: usize = 64;
+
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc::default()) {
+            Ok(c) => c,
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return;
+            }
+        }
+    };
+
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let size = gpu::Extent {
+        width: 8,
+        height: 8,
+        depth: 1,
+    };
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut painter = blade_egui::GuiPainter::new(
+        gpu::SurfaceInfo {
+            format,
+            alpha: gpu::AlphaMode::Ignored,
+        },
+        &context,
+    );
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "egui-soak",
+        buffer_count: 2,
+    });
+    let rect = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(8.0, 8.0));
+    let sd = blade_egui::ScreenDescriptor {
+        physical_size: (size.width, size.height),
+        scale_factor: 1.0,
+    };
+    let image_memory = (IMAGE_SIZE * IMAGE_SIZE * 4) as u64;
+
+    // Every frame opens a window with a new image, and closes the previous one
+    let mut prev_sync_point = None;
+    let mut max_memory = 0;
+    for frame in 0..FRAME_COUNT {
+        let texture_id = egui::TextureId::Managed(frame);
+        let textures_delta = egui::TexturesDelta {
+            set: vec![(
+                texture_id,
+                egui::epaint::ImageDelta::full(
+                    egui::ColorImage::filled([IMAGE_SIZE; 2], egui::Color32::WHITE),
+                    egui::TextureOptions::LINEAR,
+                ),
+            )],
+            free: match frame {
+                0 => Vec::new(),
+                _ => vec![egui::TextureId::Managed(frame - 1)],
+            },
+        };
+        let mut mesh = egui::Mesh::with_texture(texture_id);
+        mesh.add_rect_with_uv(
+            rect,
+            egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0)),
+            egui::Color32::WHITE,
+        );
+        let primitives = [egui::ClippedPrimitive {
+            clip_rect: rect,
+            primitive: egui::epaint::Primitive::Mesh(mesh),
+        }];
+
+        command_encoder.start();
+        painter.update_textures(&mut command_encoder, &textures_delta, &context);
+        command_encoder.init_texture(target.texture);
+        if let mut pass = command_encoder.render(
+            "egui",
+            gpu::RenderTargetSet {
+                colors: &[gpu::RenderTarget {
+                    view: target.view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                    finish_op: gpu::FinishOp::Store,
+                }],
+                depth_stencil: None,
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        ) {
+            painter.paint(&mut pass, &primitives, &sd, &context);
+        }
+        let sync_point = context.submit(&mut command_encoder);
+        painter.after_submit(&sync_point);
+        // Keep one frame in flight
+        if let Some(sp) = prev_sync_point.replace(sync_point) {
+            assert!(context.wait_for(&sp, 5000).unwrap());
+        }
+        max_memory = max_memory.max(painter.texture_memory());
+    }
+    println!("Peak texture memory: {max_memory}");
+    // The image of the current frame, the freed one, and the ones in flight
+    assert!(
+        max_memory <= 4 * image_memory,
+        "Texture memory grows up to {max_memory}"
+    );
+
+    let sync_point = prev_sync_point.unwrap();
+    assert!(context.wait_for(&sync_point, 5000).unwrap());
+    // Nothing new is set, so only the last image is left after the deletions
+    command_encoder.start();
+    painter.update_textures(
+        &mut command_encoder,
+        &egui::TexturesDelta::default(),
+        &context,
+    );
+    assert_eq!(painter.texture_memory(), image_memory);
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+    painter.destroy(&context);
+    context.destroy_command_encoder(&mut command_encoder);
+    target.destroy(&context);
+}
@@ -0,0 +1,701 @@
+//! Golden image tests of the backends.
+//!
+//! Every scene is rendered offscreen on the available backend, and compared
+//! against the reference image in `tests/reference/golden/`, allowing
+//! for the precision differences of the backend.
+//! Run with `BLADE_UPDATE_SNAPSHOTS=1` to regenerate the references.
+#![allow(irrefutable_let_patterns)]
+
+use blade_graphics as gpu;
+use std::path::Path;
+
+#[allow(dead_code)]
+mod snapshot;
+
+const REFERENCE_DIR: &str = "tests/reference/golden";
+const SIZE: gpu::Extent = gpu::Extent {
+    width: 64,
+    height: 64,
+    depth: 1,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Backend {
+    Vulkan,
+    Metal,
+    Gles,
+}
+
+impl Backend {
+    fn current() -> Self {
+        if cfg!(gles) {
+            Self::Gles
+        } else if cfg!(any(target_os = "macos", target_os = "ios")) {
+            Self::Metal
+        } else {
+            Self::Vulkan
+        }
+    }
+}
+
+/// Allowed difference with the reference image.
+#[derive(Clone, Copy, Debug)]
+struct Tolerance {
+    /// Largest difference of a channel that is still considered a match.
+    max_channel_error: u8,
+    /// Fraction of the pixels that can differ more than that.
+    max_outlier_fraction: f32,
+}
+
+const EXACT: Tolerance = Tolerance {
+    max_channel_error: 0,
+    max_outlier_fraction: 0.0,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    rect: [f32; 4],
+    color: [f32; 4],
+    size: [f32; 2],
+    depth: f32,
+    pad: f32,
+}
+
+impl Params {
+    const FULL_SCREEN: [f32; 4] = [-1.0, -1.0, 1.0, 1.0];
+
+    fn new(rect: [f32; 4], color: [f32; 4], depth: f32) -> Self {
+        Self {
+            rect,
+            color,
+            size: [SIZE.width as f32, SIZE.height as f32],
+            depth,
+            pad: 0.0,
+        }
+    }
+}
+
+#[derive(blade_macros::ShaderData)]
+struct DrawData {
+    params: Params,
+}
+
+#[derive(blade_macros::ShaderData)]
+struct SampleData {
+    params: Params,
+    source: gpu::TextureView,
+    source_sampler: gpu::Sampler,
+}
+
+#[derive(blade_macros::ShaderData)]
+struct PatternData {
+    output: gpu::TextureView,
+}
+
+struct Harness {
+    context: gpu::Context,
+    shader: gpu::Shader,
+    backend: Backend,
+}
+
+impl Harness {
+    fn new() -> Option<Self> {
+        let context = match unsafe { gpu::Context::init(gpu::ContextDesc::default()) } {
+            Ok(c) => c,
+            Err(e) => {
+                println!("Skipping: GPU context not available: {e:?}");
+                return None;
+            }
+        };
+        let shader = context.create_shader(gpu::ShaderDesc {
+            source: include_str!("shaders/golden.wgsl"),
+            naga_module: None,
+        });
+        Some(Self {
+            context,
+            shader,
+            backend: Backend::current(),
+        })
+    }
+
+    fn session(&self, color_format: gpu::TextureFormat) -> blade_util::OffscreenSession {
+        self.session_with_depth(color_format, None)
+    }
+
+    fn session_with_depth(
+        &self,
+        color_format: gpu::TextureFormat,
+        depth_format: Option<gpu::TextureFormat>,
+    ) -> blade_util::OffscreenSession {
+        blade_util::OffscreenSession::new(
+            &blade_util::OffscreenSessionDescriptor {
+                name: "golden",
+                size: SIZE,
+                color_format,
+                depth_format,
+            },
+            &self.context,
+        )
+    }
+
+    fn pipeline(
+        &self,
+        vertex: &str,
+        fragment: &str,
+        layout: &gpu::ShaderDataLayout,
+        color_target: gpu::ColorTargetState,
+        depth_format: Option<gpu::TextureFormat>,
+    ) -> gpu::RenderPipeline {
+        self.context
+            .create_render_pipeline(gpu::RenderPipelineDesc {
+                name: fragment,
+                data_layouts: &[layout],
+                vertex: self.shader.at(vertex),
+                vertex_fetches: &[],
+                primitive: gpu::PrimitiveState {
+                    topology: gpu::PrimitiveTopology::TriangleList,
+                    ..Default::default()
+                },
+                depth_stencil: depth_format.map(|format| gpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: true,
+                    depth_compare: gpu::CompareFunction::Less,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                fragment: Some(self.shader.at(fragment)),
+                color_targets: &[color_target],
+                multisample_state: Default::default(),
+                multiview: None,
+            })
+    }
+
+    /// Compare the pixels with the reference image of the scene,
+    /// or replace the reference if requested.
+    fn check(&self, name: &str, pixels: &[u8], tolerance: Tolerance) {
+        let dir = Path::new(REFERENCE_DIR);
+        let reference_path = dir.join(format!("{name}.png"));
+        if std::env::var("BLADE_UPDATE_SNAPSHOTS").is_ok() {
+            snapshot::save_image(&reference_path, pixels, SIZE);
+            println!("Updated reference image: {}", reference_path.display());
+            return;
+        }
+
+        let (reference, size) = snapshot::load_reference(&reference_path);
+        assert_eq!(size, SIZE, "Reference image of {name} has a wrong size");
+        let outliers = pixels
+            .chunks(4)
+            .zip(reference.chunks(4))
+            .filter(|&(actual, expected)| {
+                actual
+                    .iter()
+                    .zip(expected)
+                    .any(|(&a, &e)| a.abs_diff(e) > tolerance.max_channel_error)
+            })
+            .count();
+        let fraction = outliers as f32 / (SIZE.width * SIZE.height) as f32;
+        println!("{name} on {:?}: {outliers} pixels differ", self.backend);
+        if fraction > tolerance.max_outlier_fraction {
+            let actual_path = dir.join(format!("{name}_actual.png"));
+            snapshot::save_image(&actual_path, pixels, SIZE);
+            panic!(
+                "{name} differs from the reference in {outliers} pixels on {:?}, \
+                 with {tolerance:?}\nActual output saved to: {}",
+                self.backend,
+                actual_path.display()
+            );
+        }
+    }
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn golden_solid_triangle() {
+    let Some(harness) = Harness::new() else {
+        return;
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let mut session = harness.session(format);
+    let mut pipeline = harness.pipeline(
+        "vs_triangle",
+        "fs_color",
+        &<DrawData as gpu::ShaderData>::layout(),
+        format.into(),
+        None,
+    );
+
+    let params = Params::new(Params::FULL_SCREEN, [1.0, 0.0, 0.0, 1.0], 0.0);
+    let pixels = session.render_frame(&harness.context, gpu::TextureColor::OpaqueBlack, |pass| {
+        let mut pc = pass.with(&pipeline);
+        pc.bind(0, &DrawData { params });
+        pc.draw(0, 3, 0, 1);
+    });
+    harness.check("solid-triangle", &pixels, EXACT);
+
+    harness.context.destroy_render_pipeline(&mut pipeline);
+    session.destroy(&harness.context);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn golden_alpha_blend() {
+    let Some(harness) = Harness::new() else {
+        return;
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let mut session = harness.session(format);
+    let mut pipeline = harness.pipeline(
+        "vs_quad",
+        "fs_alpha_ramp",
+        &<DrawData as gpu::ShaderData>::layout(),
+        gpu::ColorTargetState {
+            format,
+            blend: Some(gpu::BlendState::ALPHA_BLENDING),
+            write_mask: gpu::ColorWrites::all(),
+            logic_op: None,
+        },
+        None,
+    );
+
+    // Red with the opacity growing from left to right, over white
+    let params = Params::new(Params::FULL_SCREEN, [1.0, 0.0, 0.0, 1.0], 0.0);
+    let pixels = session.render_frame(&harness.context, gpu::TextureColor::White, |pass| {
+        let mut pc = pass.with(&pipeline);
+        pc.bind(0, &DrawData { params });
+        pc.draw(0, 6, 0, 1);
+    });
+    let max_channel_error = match harness.backend {
+        Backend::Vulkan | Backend::Metal => 1,
+        Backend::Gles => 2,
+    };
+    harness.check(
+        "alpha-blend",
+        &pixels,
+        Tolerance {
+            max_channel_error,
+            max_outlier_fraction: 0.0,
+        },
+    );
+
+    harness.context.destroy_render_pipeline(&mut pipeline);
+    session.destroy(&harness.context);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn golden_srgb_gradient() {
+    let Some(harness) = Harness::new() else {
+        return;
+    };
+    let format = gpu::TextureFormat::Rgba8UnormSrgb;
+    let mut session = harness.session(format);
+    let mut pipeline = harness.pipeline(
+        "vs_quad",
+        "fs_gradient",
+        &<DrawData as gpu::ShaderData>::layout(),
+        format.into(),
+        None,
+    );
+
+    // Linear values are encoded by the target
+    let params = Params::new(Params::FULL_SCREEN, [0.0; 4], 0.0);
+    let pixels = session.render_frame(&harness.context, gpu::TextureColor::OpaqueBlack, |pass| {
+        let mut pc = pass.with(&pipeline);
+        pc.bind(0, &DrawData { params });
+        pc.draw(0, 6, 0, 1);
+    });
+    let max_channel_error = match harness.backend {
+        Backend::Vulkan | Backend::Metal => 1,
+        Backend::Gles => 2,
+    };
+    harness.check(
+        "srgb-gradient",
+        &pixels,
+        Tolerance {
+            max_channel_error,
+            max_outlier_fraction: 0.0,
+        },
+    );
+
+    harness.context.destroy_render_pipeline(&mut pipeline);
+    session.destroy(&harness.context);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn golden_bc_texture() {
+    let Some(harness) = Harness::new() else {
+        return;
+    };
+    let context = &harness.context;
+    let bc_format = gpu::TextureFormat::Bc1Unorm;
+    if !context
+        .supported_texture_usage(bc_format)
+        .contains(gpu::TextureUsage::RESOURCE | gpu::TextureUsage::COPY)
+    {
+        println!("Skipping: BC1 is not supported");
+        return;
+    }
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let mut session = harness.session(format);
+    let mut pipeline = harness.pipeline(
+        "vs_quad",
+        "fs_sample",
+        &<SampleData as gpu::ShaderData>::layout(),
+        format.into(),
+        None,
+    );
+
+    // 2x2 blocks of red, green, blue and gray, with every texel taking the first color.
+    // The gray is far from its sRGB decoding, which catches a mixed up format.
+    let block_colors: [u16; 4] = [0xF800, 0x07E0, 0x001F, 0x8410];
+    let blocks = block_colors
+        .iter()
+        .flat_map(|&color| {
+            let mut block = [0u8; 8];
+            block[..2].copy_from_slice(&color.to_le_bytes());
+            block
+        })
+        .collect::<Vec<u8>>();
+    let texture_size = gpu::Extent {
+        width: 8,
+        height: 8,
+        depth: 1,
+    };
+    let texture = context.create_texture(gpu::TextureDesc {
+        name: "golden-bc1",
+        format: bc_format,
+        size: texture_size,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        dimension: gpu::TextureDimension::D2,
+        usage: gpu::TextureUsage::RESOURCE | gpu::TextureUsage::COPY,
+        sample_count: 1,
+        external: None,
+    });
+    let view = context.create_texture_view(
+        texture,
+        gpu::TextureViewDesc {
+            name: "golden-bc1",
+            format: bc_format,
+            dimension: gpu::ViewDimension::D2,
+            subresources: &gpu::TextureSubresources::default(),
+        },
+    );
+    let sampler = context.create_sampler(gpu::SamplerDesc {
+        name: "golden-nearest",
+        ..Default::default()
+    });
+    let upload = context.create_buffer(gpu::BufferDesc {
+        name: "golden-bc1-upload",
+        size: blocks.len() as u64,
+        memory: gpu::Memory::Shared,
+    });
+    unsafe {
+        std::ptr::copy_nonoverlapping(blocks.as_ptr(), upload.data(), blocks.len());
+    }
+
+    let encoder = session.begin_frame();
+    encoder.init_texture(texture);
+    if let mut transfer = encoder.transfer("upload") {
+        // Every row of blocks has 2 blocks
+        transfer.copy_buffer_to_texture(upload.into(), 2 * 8, texture.into(), texture_size);
+    }
+    let params = Params::new(Params::FULL_SCREEN, [0.0; 4], 0.0);
+    session.render_pass("sample", gpu::TextureColor::OpaqueBlack, |pass| {
+        let mut pc = pass.with(&pipeline);
+        pc.bind(
+            0,
+            &SampleData {
+                params,
+                source: view,
+                source_sampler: sampler,
+            },
+        );
+        pc.draw(0, 6, 0, 1);
+    });
+    let pixels = session.end_frame(context);
+    // Expansion of the gray from 5 and 6 bits can round either way
+    harness.check(
+        "bc-texture",
+        &pixels,
+        Tolerance {
+            max_channel_error: 1,
+            max_outlier_fraction: 0.0,
+        },
+    );
+
+    context.destroy_buffer(upload);
+    context.destroy_sampler(sampler);
+    context.destroy_texture_view(view);
+    context.destroy_texture(texture);
+    context.destroy_render_pipeline(&mut pipeline);
+    session.destroy(context);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn golden_depth_test() {
+    let Some(harness) = Harness::new() else {
+        return;
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let depth_format = gpu::TextureFormat::Depth32Float;
+    let mut session = harness.session_with_depth(format, Some(depth_format));
+    let mut pipeline = harness.pipeline(
+        "vs_quad",
+        "fs_color",
+        &<DrawData as gpu::ShaderData>::layout(),
+        format.into(),
+        Some(depth_format),
+    );
+
+    // Green in the middle, red hidden behind it, and blue in front of the left half
+    let quads = [
+        Params::new(Params::FULL_SCREEN, [0.0, 1.0, 0.0, 1.0], 0.5),
+        Params::new(Params::FULL_SCREEN, [1.0, 0.0, 0.0, 1.0], 0.75),
+        Params::new([-1.0, -1.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0], 0.25),
+    ];
+    let pixels = session.render_frame(&harness.context, gpu::TextureColor::OpaqueBlack, |pass| {
+        let mut pc = pass.with(&pipeline);
+        for params in quads {
+            pc.bind(0, &DrawData { params });
+            pc.draw(0, 6, 0, 1);
+        }
+    });
+    harness.check("depth-test", &pixels, EXACT);
+
+    harness.context.destroy_render_pipeline(&mut pipeline);
+    session.destroy(&harness.context);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn golden_storage_texture() {
+    let Some(harness) = Harness::new() else {
+        return;
+    };
+    let context = &harness.context;
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let mut session = harness.session(format);
+    let mut pipeline = harness.pipeline(
+        "vs_quad",
+        "fs_load",
+        &<SampleData as gpu::ShaderData>::layout(),
+        format.into(),
+        None,
+    );
+    let mut compute_pipeline = context.create_compute_pipeline(gpu::ComputePipelineDesc {
+        name: "pattern",
+        data_layouts: &[&<PatternData as gpu::ShaderData>::layout()],
+        compute: harness.shader.at("cs_pattern"),
+    });
+
+    let texture = context.create_texture(gpu::TextureDesc {
+        name: "golden-storage",
+        format,
+        size: SIZE,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        dimension: gpu::TextureDimension::D2,
+        usage: gpu::TextureUsage::RESOURCE | gpu::TextureUsage::STORAGE,
+        sample_count: 1,
+        external: None,
+    });
+    let view = context.create_texture_view(
+        texture,
+        gpu::TextureViewDesc {
+            name: "golden-storage",
+            format,
+            dimension: gpu::ViewDimension::D2,
+            subresources: &gpu::TextureSubresources::default(),
+        },
+    );
+    let sampler = context.create_sampler(gpu::SamplerDesc {
+        name: "golden-nearest",
+        ..Default::default()
+    });
+
+    let encoder = session.begin_frame();
+    encoder.init_texture(texture);
+    if let mut pass = encoder.compute("pattern") {
+        let mut pc = pass.with(&compute_pipeline);
+        pc.bind(0, &PatternData { output: view });
+        pc.dispatch(compute_pipeline.get_dispatch_for(SIZE));
+    }
+    let params = Params::new(Params::FULL_SCREEN, [0.0; 4], 0.0);
+    session.render_pass("load", gpu::TextureColor::OpaqueBlack, |pass| {
+        let mut pc = pass.with(&pipeline);
+        pc.bind(
+            0,
+            &SampleData {
+                params,
+                source: view,
+                source_sampler: sampler,
+            },
+        );
+        pc.draw(0, 6, 0, 1);
+    });
+    let pixels = session.end_frame(context);
+    harness.check("storage-texture", &pixels, EXACT);
+
+    context.destroy_sampler(sampler);
+    context.destroy_texture_view(view);
+    context.destroy_texture(texture);
+    context.destroy_compute_pipeline(&mut compute_pipeline);
+    context.destroy_render_pipeline(&mut pipeline);
+    session.destroy(context);
+}
+
+/// Checks the coordinate conventions: the texture origin, the winding of the front faces,
+/// and the depth range are expected to be the same on every backend.
+#[test]
+#[ignore = "requires a working GPU context"]
+fn golden_textured_triangle() {
+    let Some(harness) = Harness::new() else {
+        return;
+    };
+    let context = &harness.context;
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let depth_format = gpu::TextureFormat::Depth32Float;
+    let mut session = harness.session_with_depth(format, Some(depth_format));
+    let culled_pipeline = |name, fragment, layout: &gpu::ShaderDataLayout| {
+        context.create_render_pipeline(gpu::RenderPipelineDesc {
+            name,
+            data_layouts: &[layout],
+            vertex: harness.shader.at("vs_textured_triangle"),
+            vertex_fetches: &[],
+            primitive: gpu::PrimitiveState {
+                topology: gpu::PrimitiveTopology::TriangleList,
+                front_face: gpu::FrontFace::Ccw,
+                cull_mode: Some(gpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(gpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: true,
+                depth_compare: gpu::CompareFunction::Less,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            fragment: Some(harness.shader.at(fragment)),
+            color_targets: &[format.into()],
+            multisample_state: Default::default(),
+            multiview: None,
+        })
+    };
+    let mut textured_pipeline = culled_pipeline(
+        "textured",
+        "fs_textured",
+        &<SampleData as gpu::ShaderData>::layout(),
+    );
+    let mut solid_pipeline = culled_pipeline(
+        "solid",
+        "fs_color",
+        &<DrawData as gpu::ShaderData>::layout(),
+    );
+    let mut quad_pipeline = harness.pipeline(
+        "vs_quad",
+        "fs_color",
+        &<DrawData as gpu::ShaderData>::layout(),
+        format.into(),
+        Some(depth_format),
+    );
+
+    // Red, yellow, blue, and white quadrants, starting from the top left
+    let texels: [[u8; 4]; 4] = [
+        [0xFF, 0, 0, 0xFF],
+        [0xFF, 0xFF, 0, 0xFF],
+        [0, 0, 0xFF, 0xFF],
+        [0xFF; 4],
+    ];
+    let texture_size = gpu::Extent {
+        width: 2,
+        height: 2,
+        depth: 1,
+    };
+    let texture = context.create_texture(gpu::TextureDesc {
+        name: "golden-quadrants",
+        format,
+        size: texture_size,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        dimension: gpu::TextureDimension::D2,
+        usage: gpu::TextureUsage::RESOURCE | gpu::TextureUsage::COPY,
+        sample_count: 1,
+        external: None,
+    });
+    let view = context.create_texture_view(
+        texture,
+        gpu::TextureViewDesc {
+            name: "golden-quadrants",
+            format,
+            dimension: gpu::ViewDimension::D2,
+            subresources: &gpu::TextureSubresources::default(),
+        },
+    );
+    let sampler = context.create_sampler(gpu::SamplerDesc {
+        name: "golden-nearest",
+        ..Default::default()
+    });
+    let upload = context.create_buffer(gpu::BufferDesc {
+        name: "golden-quadrants-upload",
+        size: 16,
+        memory: gpu::Memory::Shared,
+    });
+    upload.write_slice(0, &texels);
+
+    let encoder = session.begin_frame();
+    encoder.init_texture(texture);
+    if let mut transfer = encoder.transfer("upload") {
+        transfer.copy_buffer_to_texture(upload.into(), 2 * 4, texture.into(), texture_size);
+    }
+    let mut back_facing = Params::new(Params::FULL_SCREEN, [1.0, 0.0, 1.0, 1.0], 0.25);
+    back_facing.pad = 1.0;
+    session.render_pass("draw", gpu::TextureColor::OpaqueBlack, |pass| {
+        // Front-facing textured triangle in the lower left half
+        if let mut pc = pass.with(&textured_pipeline) {
+            pc.bind(
+                0,
+                &SampleData {
+                    params: Params::new(Params::FULL_SCREEN, [0.0; 4], 0.5),
+                    source: view,
+                    source_sampler: sampler,
+                },
+            );
+            pc.draw(0, 3, 0, 1);
+        }
+        // Green quad behind it, only visible in the upper right half
+        if let mut pc = pass.with(&quad_pipeline) {
+            pc.bind(
+                0,
+                &DrawData {
+                    params: Params::new(Params::FULL_SCREEN, [0.0, 1.0, 0.0, 1.0], 0.75),
+                },
+            );
+            pc.draw(0, 6, 0, 1);
+        }
+        // Back-facing magenta triangle in front of everything, which is culled
+        if let mut pc = pass.with(&solid_pipeline) {
+            pc.bind(
+                0,
+                &DrawData {
+                    params: back_facing,
+                },
+            );
+            pc.draw(0, 3, 0, 1);
+        }
+    });
+    let pixels = session.end_frame(context);
+    harness.check("textured-triangle", &pixels, EXACT);
+
+    context.destroy_buffer(upload);
+    context.destroy_sampler(sampler);
+    context.destroy_texture_view(view);
+    context.destroy_texture(texture);
+    context.destroy_render_pipeline(&mut quad_pipeline);
+    context.destroy_render_pipeline(&mut solid_pipeline);
+    context.destroy_render_pipeline(&mut textured_pipeline);
+    session.destroy(context);
+}
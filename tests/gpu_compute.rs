@@ -0,0 +1,641 @@
+//! Compute dispatch, bindings, and buffer addressing.
+#![allow(irrefutable_let_patterns)]
+
+use blade_graphics as gpu;
+use blade_graphics::ShaderData;
+use std::slice;
+
+#[allow(dead_code)]
+mod common;
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn redundant_binding_skip() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let [input, output_a, output_b] =
+        ["skip-input", "skip-output-a", "skip-output-b"].map(|name| {
+            context.create_buffer(gpu::BufferDesc {
+                name,
+                size: 16,
+                memory: gpu::Memory::Shared,
+            })
+        });
+    unsafe {
+        slice::from_raw_parts_mut(input.data() as *mut u32, 4).copy_from_slice(&[1, 2, 3, 4]);
+    }
+    context.sync_buffer(input);
+
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/dispatch.wgsl"),
+        naga_module: None,
+    });
+    let mut pipeline = context.create_compute_pipeline(gpu::ComputePipelineDesc {
+        name: "skip-test",
+        data_layouts: &[&common::DispatchGlobals::layout()],
+        compute: shader.at("main"),
+    });
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "skip-test",
+        buffer_count: 1,
+    });
+
+    let globals_a = common::DispatchGlobals {
+        input: input.into(),
+        output: output_a.into(),
+    };
+    let globals_b = common::DispatchGlobals {
+        input: input.into(),
+        output: output_b.into(),
+    };
+    command_encoder.start();
+    if let mut compute = command_encoder.compute("dispatch") {
+        if let mut pass = compute.with(&pipeline) {
+            for globals in [&globals_a, &globals_a, &globals_b] {
+                pass.bind(0, globals);
+                pass.dispatch([1, 1, 1]);
+            }
+        }
+        // A new pipeline scope has nothing bound yet
+        if let mut pass = compute.with(&pipeline) {
+            pass.bind(0, &globals_a);
+            pass.dispatch([1, 1, 1]);
+        }
+    }
+    let stats = command_encoder.binding_stats();
+    assert_eq!(stats.issued + stats.skipped, 4);
+    assert!(
+        stats.issued >= 3,
+        "Changed bindings were skipped: {stats:?}"
+    );
+
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+    for output in [output_a, output_b] {
+        let actual = unsafe { slice::from_raw_parts(output.data() as *const u32, 4) };
+        assert_eq!(actual, [3, 5, 7, 9]);
+    }
+
+    command_encoder.start();
+    assert_eq!(
+        command_encoder.binding_stats(),
+        gpu::BindingStats::default()
+    );
+
+    context.destroy_command_encoder(&mut command_encoder);
+    context.destroy_compute_pipeline(&mut pipeline);
+    for buffer in [input, output_a, output_b] {
+        context.destroy_buffer(buffer);
+    }
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn descriptor_pool_trim() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let buffer = context.create_buffer(gpu::BufferDesc {
+        name: "trim-data",
+        size: 0x200000,
+        memory: gpu::Memory::Device,
+    });
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/dispatch.wgsl"),
+        naga_module: None,
+    });
+    let mut pipeline = context.create_compute_pipeline(gpu::ComputePipelineDesc {
+        name: "trim-test",
+        data_layouts: &[&common::DispatchGlobals::layout()],
+        compute: shader.at("main"),
+    });
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "trim-test",
+        buffer_count: 1,
+    });
+    let baseline = context.memory_stats().descriptor_pools;
+    if baseline.pool_count == 0 {
+        println!("Skipping: shader data is bound without descriptor pools");
+        context.destroy_command_encoder(&mut command_encoder);
+        context.destroy_compute_pipeline(&mut pipeline);
+        context.destroy_buffer(buffer);
+        return;
+    }
+
+    // Bind thousands of unique data sets, growing the pools
+    const SET_COUNT: u64 = 5000;
+    command_encoder.start();
+    if let mut compute = command_encoder.compute("bind")
+        && let mut pass = compute.with(&pipeline)
+    {
+        for i in 0..SET_COUNT {
+            pass.bind(
+                0,
+                &common::DispatchGlobals {
+                    input: buffer.at(i * 256),
+                    output: buffer.at(0x180000),
+                },
+            );
+        }
+    }
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+    let grown = context.memory_stats().descriptor_pools;
+    assert!(grown.allocated_sets >= SET_COUNT);
+    assert!(grown.set_capacity >= SET_COUNT);
+    assert!(grown.peak_pool_count > baseline.pool_count);
+
+    // Without a trim, the largest pool is kept around
+    command_encoder.start();
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+    let kept = context.memory_stats().descriptor_pools;
+    assert_eq!(kept.allocated_sets, 0);
+    assert!(kept.set_capacity > baseline.set_capacity);
+
+    context.trim();
+    command_encoder.start();
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+    let trimmed = context.memory_stats().descriptor_pools;
+    assert_eq!(trimmed.pool_count, baseline.pool_count);
+    assert_eq!(trimmed.set_capacity, baseline.set_capacity);
+    assert_eq!(trimmed.peak_set_capacity, grown.peak_set_capacity);
+
+    context.destroy_command_encoder(&mut command_encoder);
+    context.destroy_compute_pipeline(&mut pipeline);
+    context.destroy_buffer(buffer);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn null_buffer_binding() {
+    let context = unsafe {
+        gpu::Context::init(gpu::ContextDesc {
+            robustness: true,
+            ..Default::default()
+        })
+        .unwrap()
+    };
+    if !context.capabilities().robustness {
+        println!("Skipping: robustness is not supported");
+        return;
+    }
+    let output = context.create_buffer(gpu::BufferDesc {
+        name: "null-output",
+        size: 16,
+        memory: gpu::Memory::Shared,
+    });
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/dispatch.wgsl"),
+        naga_module: None,
+    });
+    let mut pipeline = context.create_compute_pipeline(gpu::ComputePipelineDesc {
+        name: "null-binding",
+        data_layouts: &[&common::NullableDispatchGlobals::layout()],
+        compute: shader.at("main"),
+    });
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "null-binding",
+        buffer_count: 1,
+    });
+    command_encoder.start();
+    if let mut compute = command_encoder.compute("dispatch")
+        && let mut pass = compute.with(&pipeline)
+    {
+        pass.bind(
+            0,
+            &common::NullableDispatchGlobals {
+                input: None.into(),
+                output: output.into(),
+            },
+        );
+        pass.dispatch([1, 1, 1]);
+    }
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    // Reads from the null buffer return zeros
+    let actual = unsafe { slice::from_raw_parts(output.data() as *const u32, 4) };
+    assert_eq!(actual, [1; 4]);
+
+    context.destroy_command_encoder(&mut command_encoder);
+    context.destroy_compute_pipeline(&mut pipeline);
+    context.destroy_buffer(output);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn binding_array_limit() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let limit = context.capabilities().max_binding_array_size;
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/dispatch.wgsl"),
+        naga_module: None,
+    });
+    let layout = gpu::ShaderDataLayout {
+        bindings: vec![(
+            "textures",
+            gpu::ShaderBinding::TextureArray { count: limit + 1 },
+        )],
+    };
+
+    let result = context.try_create_compute_pipeline(gpu::ComputePipelineDesc {
+        name: "binding-array-limit",
+        data_layouts: &[&layout],
+        compute: shader.at("main"),
+    });
+    match result {
+        Err(gpu::PipelineError::TooManyBindingArrayTextures { count, limit: l }) => {
+            assert_eq!((count, l), (limit + 1, limit));
+        }
+        Err(other) => panic!("Unexpected error: {other}"),
+        Ok(_) => panic!("Pipeline creation should fail"),
+    }
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn indirect_dispatch() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+
+    let input = context.create_buffer(gpu::BufferDesc {
+        name: "indirect-input",
+        size: 32,
+        memory: gpu::Memory::Shared,
+    });
+    input.write_slice(0, &[1u32, 2, 3, 4, 5, 6, 7, 8]);
+    context.sync_buffer(input);
+    let output = context.create_buffer(gpu::BufferDesc {
+        name: "indirect-output",
+        size: 32,
+        memory: gpu::Memory::Shared,
+    });
+    let args = context.create_indirect_buffer(
+        "indirect-args",
+        &[
+            gpu::DispatchIndirectArgs { x: 1, y: 1, z: 1 },
+            gpu::DispatchIndirectArgs { x: 2, y: 1, z: 1 },
+        ],
+    );
+
+    let args_size = size_of::<gpu::DispatchIndirectArgs>() as u64;
+    assert_eq!(
+        args.at(0)
+            .check_indirect::<gpu::DispatchIndirectArgs>(2, args_size),
+        Ok(())
+    );
+    assert_eq!(
+        args.at(2)
+            .check_indirect::<gpu::DispatchIndirectArgs>(1, args_size),
+        Err(gpu::IndirectError::MisalignedOffset { offset: 2 })
+    );
+    assert_eq!(
+        args.at(0)
+            .check_indirect::<gpu::DispatchIndirectArgs>(2, args_size - 4),
+        Err(gpu::IndirectError::InvalidStride {
+            stride: args_size - 4,
+            size: args_size,
+        })
+    );
+    assert_eq!(
+        args.at(args_size)
+            .check_indirect::<gpu::DispatchIndirectArgs>(2, args_size),
+        Err(gpu::IndirectError::OutOfBounds {
+            end: 3 * args_size,
+            buffer_size: 2 * args_size,
+        })
+    );
+
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/dispatch.wgsl"),
+        naga_module: None,
+    });
+    let global_layout = common::DispatchGlobals::layout();
+    let mut pipeline = context.create_compute_pipeline(gpu::ComputePipelineDesc {
+        name: "indirect-dispatch",
+        data_layouts: &[&global_layout],
+        compute: shader.at("main"),
+    });
+
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "indirect-dispatch",
+        buffer_count: 1,
+    });
+    command_encoder.start();
+    if let mut compute = command_encoder.compute("indirect-dispatch")
+        && let mut pass = compute.with(&pipeline)
+    {
+        pass.bind(
+            0,
+            &common::DispatchGlobals {
+                input: input.into(),
+                output: output.into(),
+            },
+        );
+        // Two groups of 4 invocations cover the whole buffer
+        pass.dispatch_indirect(args.at(args_size));
+    }
+
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+    assert_eq!(output.read_slice::<u32>(0, 8), [3, 5, 7, 9, 11, 13, 15, 17]);
+
+    context.destroy_command_encoder(&mut command_encoder);
+    context.destroy_compute_pipeline(&mut pipeline);
+    context.destroy_buffer(args);
+    context.destroy_buffer(output);
+    context.destroy_buffer(input);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn buffer_device_address() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    if !context.capabilities().buffer_device_address {
+        println!("Buffer device addresses are not supported, skipping");
+        return;
+    }
+    let buffers = [
+        ("address-device", gpu::Memory::Device),
+        ("address-shared", gpu::Memory::Shared),
+        ("address-upload", gpu::Memory::Upload),
+    ]
+    .map(|(name, memory)| {
+        context.create_buffer(gpu::BufferDesc {
+            name,
+            size: 256,
+            memory,
+        })
+    });
+
+    let mut ranges = buffers.map(|buffer| {
+        let piece = gpu::BufferPiece::from(buffer);
+        assert_eq!(buffer.at(64).device_address(), piece.device_address() + 64);
+        piece.device_address()..piece.device_address() + 256
+    });
+    // The buffers are alive at the same time, so they can't overlap
+    ranges.sort_by_key(|range| range.start);
+    for pair in ranges.windows(2) {
+        assert!(pair[0].end <= pair[1].start, "Overlapping {pair:?}");
+    }
+
+    for buffer in buffers {
+        context.destroy_buffer(buffer);
+    }
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn plain_data_limit() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let limit = context.capabilities().max_plain_data_size;
+    // Plain data of exactly `size` bytes, made of 16-byte elements
+    let try_create = |size: u32| {
+        let count = size / 16;
+        let source = format!(
+            "struct Lights {{ data: array<vec4<u32>, {count}> }}
+            var<uniform> lights: Lights;
+            var<storage, read_write> output: array<vec4<u32>>;
+            @compute @workgroup_size(1)
+            fn main() {{ output[0] = lights.data[{count} - 1]; }}"
+        );
+        let shader = context.create_shader(gpu::ShaderDesc {
+            source: &source,
+            naga_module: None,
+        });
+        let layout = gpu::ShaderDataLayout {
+            bindings: vec![
+                ("lights", gpu::ShaderBinding::Plain { size }),
+                ("output", gpu::ShaderBinding::Buffer),
+            ],
+        };
+        context.try_create_compute_pipeline(gpu::ComputePipelineDesc {
+            name: "plain-data-limit",
+            data_layouts: &[&layout],
+            compute: shader.at("main"),
+        })
+    };
+
+    let mut pipeline = try_create(limit).unwrap();
+    context.destroy_compute_pipeline(&mut pipeline);
+    match try_create(limit + 16) {
+        Err(gpu::PipelineError::PlainDataTooLarge {
+            binding,
+            size,
+            limit: l,
+        }) => {
+            assert_eq!((binding.as_str(), size, l), ("lights", limit + 16, limit));
+        }
+        Err(other) => panic!("Unexpected error: {other}"),
+        Ok(_) => panic!("Pipeline creation should fail"),
+    }
+}
+
+#[cfg(not(gles))]
+#[derive(blade_macros::ShaderData)]
+struct DynamicOffsetData {
+    #[shader_data(dynamic)]
+    params: gpu::BufferPiece,
+    output: gpu::BufferPiece,
+}
+
+// GLES binds the dynamic buffers as storage blocks only
+#[cfg(not(gles))]
+#[test]
+#[ignore = "requires a working GPU context"]
+fn dynamic_offsets() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let alignment = context.capabilities().dynamic_offset_alignment;
+    if alignment == 0 {
+        println!("Skipping: dynamic buffers are not supported");
+        return;
+    }
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: "struct Params { index: u32, value: u32 }
+            var<uniform> params: Params;
+            var<storage, read_write> output: array<u32>;
+            @compute @workgroup_size(1)
+            fn main() { output[params.index] = params.value; }",
+        naga_module: None,
+    });
+    let layout = <DynamicOffsetData as gpu::ShaderData>::layout();
+    let mut pipeline = context.create_compute_pipeline(gpu::ComputePipelineDesc {
+        name: "dynamic-offsets",
+        data_layouts: &[&layout],
+        compute: shader.at("main"),
+    });
+
+    // One `Params` per dispatch, strided by the offset alignment
+    let values = [5u32, 7, 11, 13];
+    let stride = alignment.max(8);
+    let params = context.create_buffer(gpu::BufferDesc {
+        name: "dynamic-params",
+        size: (stride * values.len() as u32) as u64,
+        memory: gpu::Memory::Shared,
+    });
+    for (index, &value) in values.iter().enumerate() {
+        params.write_slice(index * stride as usize / 4, &[index as u32, value]);
+    }
+    context.sync_buffer(params);
+    let output = context.create_buffer(gpu::BufferDesc {
+        name: "dynamic-output",
+        size: 4 * values.len() as u64,
+        memory: gpu::Memory::Shared,
+    });
+
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "dynamic-offsets",
+        buffer_count: 1,
+    });
+    command_encoder.start();
+    if let mut compute = command_encoder.compute("dynamic-offsets")
+        && let mut pass = compute.with(&pipeline)
+    {
+        pass.bind(
+            0,
+            &DynamicOffsetData {
+                params: params.into(),
+                output: output.into(),
+            },
+        );
+        for index in 0..values.len() as u32 {
+            pass.set_dynamic_offsets(0, &[index * stride]);
+            pass.dispatch([1, 1, 1]);
+        }
+    }
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    let actual = unsafe { slice::from_raw_parts(output.data() as *const u32, values.len()) };
+    assert_eq!(actual, values);
+
+    context.destroy_command_encoder(&mut command_encoder);
+    context.destroy_compute_pipeline(&mut pipeline);
+    context.destroy_buffer(output);
+    context.destroy_buffer(params);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn null_buffer_binding_without_robustness() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let output = context.create_buffer(gpu::BufferDesc {
+        name: "null-output",
+        size: 16,
+        memory: gpu::Memory::Shared,
+    });
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/dispatch.wgsl"),
+        naga_module: None,
+    });
+    let mut pipeline = context.create_compute_pipeline(gpu::ComputePipelineDesc {
+        name: "null-binding",
+        data_layouts: &[&common::NullableDispatchGlobals::layout()],
+        compute: shader.at("main"),
+    });
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "null-binding",
+        buffer_count: 1,
+    });
+    command_encoder.start();
+    if let mut compute = command_encoder.compute("dispatch")
+        && let mut pass = compute.with(&pipeline)
+    {
+        pass.bind(
+            0,
+            &common::NullableDispatchGlobals {
+                input: None.into(),
+                output: output.into(),
+            },
+        );
+        pass.dispatch([1, 1, 1]);
+    }
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    // The dummy buffer is bound instead, which is zeroed
+    let actual = unsafe { slice::from_raw_parts(output.data() as *const u32, 4) };
+    assert_eq!(actual, [1; 4]);
+
+    context.destroy_command_encoder(&mut command_encoder);
+    context.destroy_compute_pipeline(&mut pipeline);
+    context.destroy_buffer(output);
+}
+
+#[derive(blade_macros::ShaderData)]
+struct DummyReadData {
+    white: gpu::TextureView,
+    black: gpu::TextureView,
+    normal: gpu::TextureView,
+    zeros: gpu::BufferPiece,
+    output: gpu::BufferPiece,
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn dummy_resources() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    assert_eq!(context.dummy_white(), context.dummy_white());
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: "var white: texture_2d<f32>;
+            var black: texture_2d<f32>;
+            var normal: texture_2d<f32>;
+            var<storage, read> zeros: array<u32>;
+            var<storage, read_write> output: array<vec4<f32>>;
+            @compute @workgroup_size(1)
+            fn main() {
+                output[0] = textureLoad(white, vec2<i32>(0), 0);
+                output[1] = textureLoad(black, vec2<i32>(0), 0);
+                output[2] = textureLoad(normal, vec2<i32>(0), 0);
+                output[3] = vec4<f32>(f32(zeros[0]), f32(zeros[255]), 0.0, 0.0);
+            }",
+        naga_module: None,
+    });
+    let mut pipeline = context.create_compute_pipeline(gpu::ComputePipelineDesc {
+        name: "dummy-read",
+        data_layouts: &[&DummyReadData::layout()],
+        compute: shader.at("main"),
+    });
+    let output = context.create_buffer(gpu::BufferDesc {
+        name: "dummy-output",
+        size: 64,
+        memory: gpu::Memory::Shared,
+    });
+
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "dummy-read",
+        buffer_count: 1,
+    });
+    command_encoder.start();
+    if let mut compute = command_encoder.compute("dummy-read")
+        && let mut pass = compute.with(&pipeline)
+    {
+        pass.bind(
+            0,
+            &DummyReadData {
+                white: context.dummy_white(),
+                black: context.dummy_black(),
+                normal: context.dummy_normal(),
+                zeros: context.dummy_buffer().into(),
+                output: output.into(),
+            },
+        );
+        pass.dispatch([1, 1, 1]);
+    }
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    let actual = unsafe { slice::from_raw_parts(output.data() as *const [f32; 4], 4) };
+    let half = 128.0 / 255.0;
+    let expected = [[1.0; 4], [0.0; 4], [half, half, 1.0, 1.0], [0.0; 4]];
+    for (texel, expected_texel) in actual.iter().zip(expected) {
+        for (&value, expected_value) in texel.iter().zip(expected_texel) {
+            assert!(
+                (value - expected_value).abs() < 1e-3,
+                "{texel:?} != {expected_texel:?}"
+            );
+        }
+    }
+
+    context.destroy_command_encoder(&mut command_encoder);
+    context.destroy_compute_pipeline(&mut pipeline);
+    context.destroy_buffer(output);
+}
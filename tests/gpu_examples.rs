@@ -8,15 +8,17 @@ unsafe extern "C" {}
 
 use blade_graphics as gpu;
 use blade_graphics::ShaderData;
+use common::{DispatchGlobals, snapshot};
 use std::slice;
 
 #[allow(dead_code)]
 #[path = "../examples/bunnymark/example.rs"]
 mod bunnymark_example;
+#[allow(dead_code)]
+mod common;
 #[cfg(not(gles))]
 #[path = "../examples/ray-query/example.rs"]
 mod ray_query_example;
-mod snapshot;
 
 // --- Sky snapshot test structs ---
 
@@ -39,29 +41,6 @@ struct SkyTestData {
     env_map: gpu::TextureView,
 }
 
-#[derive(Clone, Copy)]
-struct DispatchGlobals {
-    input: gpu::BufferPiece,
-    output: gpu::BufferPiece,
-}
-
-impl gpu::ShaderData for DispatchGlobals {
-    fn layout() -> gpu::ShaderDataLayout {
-        gpu::ShaderDataLayout {
-            bindings: vec![
-                ("input", gpu::ShaderBinding::Buffer),
-                ("output", gpu::ShaderBinding::Buffer),
-            ],
-        }
-    }
-
-    fn fill(&self, mut ctx: gpu::PipelineContext) {
-        use gpu::ShaderBindable as _;
-        self.input.bind_to(&mut ctx, 0);
-        self.output.bind_to(&mut ctx, 1);
-    }
-}
-
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
 struct EnvSampleParams {
@@ -123,8 +102,10 @@ impl EnvMapSampler {
                 format,
                 blend: None,
                 write_mask: gpu::ColorWrites::ALL,
+                logic_op: None,
             }],
             multisample_state: gpu::MultisampleState::default(),
+            multiview: None,
         });
         let accum_pipeline = context.create_render_pipeline(gpu::RenderPipelineDesc {
             name: "env-accum",
@@ -141,8 +122,10 @@ impl EnvMapSampler {
                 format,
                 blend: Some(gpu::BlendState::ADDITIVE),
                 write_mask: gpu::ColorWrites::RED,
+                logic_op: None,
             }],
             multisample_state: gpu::MultisampleState::default(),
+            multiview: None,
         });
 
         Self {
@@ -172,6 +155,8 @@ impl EnvMapSampler {
                     finish_op: gpu::FinishOp::Store,
                 }],
                 depth_stencil: None,
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
             },
         );
         if let mut encoder = pass.with(&self.init_pipeline) {
@@ -206,11 +191,7 @@ impl EnvMapSampler {
     }
 }
 
-#[test]
-#[ignore = "requires a working GPU context"]
-fn dispatch_gpu_test() {
-    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
-
+fn run_dispatch(context: &gpu::Context) {
     let input = context.create_buffer(gpu::BufferDesc {
         name: "dispatch-input",
         size: 16,
@@ -270,6 +251,36 @@ fn dispatch_gpu_test() {
     context.destroy_buffer(input);
 }
 
+#[test]
+#[ignore = "requires a working GPU context"]
+fn dispatch_gpu_test() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    run_dispatch(&context);
+}
+
+/// Compute-only context, which has to work without a display server.
+#[test]
+#[ignore = "requires a working GPU context"]
+fn headless_compute() {
+    let context = unsafe {
+        gpu::Context::init(gpu::ContextDesc {
+            presentation: false,
+            ..Default::default()
+        })
+        .unwrap()
+    };
+    let report = context.report();
+    assert!(
+        !report
+            .extensions
+            .iter()
+            .any(|name| name.contains("surface")),
+        "surface extensions are enabled: {:?}",
+        report.extensions
+    );
+    run_dispatch(&context);
+}
+
 #[test]
 #[ignore = "requires a working GPU context"]
 fn env_map_gpu_test() {
@@ -292,7 +303,14 @@ fn env_map_gpu_test() {
 
     let mut dummy = blade_render::DummyResources::new(&mut command_encoder, &context);
     let mut env_map = blade_render::EnvironmentMap::new(&shader_prepare, &dummy, &context);
-    env_map.assign(dummy.white_view, dummy.size, &mut command_encoder, &context);
+    let mut temp = blade_render::FrameResources::default();
+    env_map.assign(
+        dummy.white_view,
+        dummy.size,
+        &mut command_encoder,
+        &context,
+        &mut temp,
+    );
 
     let env_sampler = EnvMapSampler::new(dummy.size, &shader_sample, &context);
     env_sampler.accumulate(
@@ -339,7 +357,22 @@ fn env_map_gpu_test() {
 #[test]
 #[ignore = "requires a working GPU context"]
 fn snapshot_bunnymark() {
-    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    render_bunnymark_snapshot(gpu::ContextDesc::default());
+}
+
+/// Same scene as `snapshot_bunnymark`, rendered through the fallback
+/// for the drivers without dynamic rendering.
+#[test]
+#[ignore = "requires a working GPU context"]
+fn snapshot_bunnymark_legacy_render_passes() {
+    render_bunnymark_snapshot(gpu::ContextDesc {
+        legacy_render_passes: true,
+        ..Default::default()
+    });
+}
+
+fn render_bunnymark_snapshot(desc: gpu::ContextDesc) {
+    let context = unsafe { gpu::Context::init(desc).unwrap() };
     let size = gpu::Extent {
         width: 400,
         height: 300,
@@ -347,7 +380,15 @@ fn snapshot_bunnymark() {
     };
     let format = gpu::TextureFormat::Rgba8Unorm;
 
-    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut session = blade_util::OffscreenSession::new(
+        &blade_util::OffscreenSessionDescriptor {
+            name: "snapshot-bunnymark",
+            size,
+            color_format: format,
+            depth_format: None,
+        },
+        &context,
+    );
     let mut example = bunnymark_example::Example::new(&context, size, format);
 
     // Add bunnies and step the simulation for a deterministic scene
@@ -356,20 +397,14 @@ fn snapshot_bunnymark() {
         example.step(0.01);
     }
 
-    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
-        name: "snapshot-bunnymark",
-        buffer_count: 1,
-    });
-    command_encoder.start();
-    command_encoder.init_texture(target.texture);
-    example.render(&mut command_encoder, target.view);
+    let view = session.color_view();
+    example.render(session.begin_frame(), view);
 
-    let pixels = target.read_pixels(&context, &mut command_encoder);
+    let pixels = session.end_frame(&context);
     snapshot::check("bunnymark", &pixels, size);
 
     example.deinit(&context);
-    context.destroy_command_encoder(&mut command_encoder);
-    target.destroy(&context);
+    session.destroy(&context);
 }
 
 #[cfg(not(gles))]
@@ -411,24 +446,26 @@ fn snapshot_ray_query() {
     };
     let format = gpu::TextureFormat::Rgba8Unorm;
 
-    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut session = blade_util::OffscreenSession::new(
+        &blade_util::OffscreenSessionDescriptor {
+            name: "snapshot-ray-query",
+            size,
+            color_format: format,
+            depth_format: None,
+        },
+        &context,
+    );
     let mut example = ray_query_example::Example::new(&context, size, format);
 
-    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
-        name: "snapshot-ray-query",
-        buffer_count: 1,
-    });
-    command_encoder.start();
-    command_encoder.init_texture(target.texture);
+    let view = session.color_view();
     // Fixed rotation angle for deterministic output
-    example.render(&mut command_encoder, target.view, 1.0);
+    example.render(session.begin_frame(), view, 1.0);
 
-    let pixels = target.read_pixels(&context, &mut command_encoder);
+    let pixels = session.end_frame(&context);
     snapshot::check("ray-query", &pixels, size);
 
     example.deinit(&context);
-    context.destroy_command_encoder(&mut command_encoder);
-    target.destroy(&context);
+    session.destroy(&context);
 }
 
 #[test]
@@ -442,7 +479,15 @@ fn snapshot_particle() {
     };
     let format = gpu::TextureFormat::Rgba8Unorm;
 
-    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut session = blade_util::OffscreenSession::new(
+        &blade_util::OffscreenSessionDescriptor {
+            name: "snapshot-particle",
+            size,
+            color_format: format,
+            depth_format: None,
+        },
+        &context,
+    );
     let mut pipeline = blade_particle::ParticlePipeline::new(
         &context,
         blade_particle::PipelineDesc {
@@ -469,14 +514,10 @@ fn snapshot_particle() {
     };
     let mut particle_system = pipeline.create_system(&context, "snapshot particle", &effect);
 
-    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
-        name: "snapshot-particle",
-        buffer_count: 1,
-    });
-    command_encoder.start();
+    let command_encoder = session.begin_frame();
     // Run several update cycles to emit and move particles
     for _ in 0..20 {
-        particle_system.update(&pipeline, &mut command_encoder, 0.01);
+        particle_system.update(&pipeline, command_encoder, 0.01);
     }
 
     let camera = {
@@ -496,28 +537,16 @@ fn snapshot_particle() {
         }
     };
 
-    command_encoder.init_texture(target.texture);
-    if let mut pass = command_encoder.render(
-        "draw particles",
-        gpu::RenderTargetSet {
-            colors: &[gpu::RenderTarget {
-                view: target.view,
-                init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
-                finish_op: gpu::FinishOp::Store,
-            }],
-            depth_stencil: None,
-        },
-    ) {
-        particle_system.draw(&pipeline, &mut pass, &camera);
-    }
+    session.render_pass("draw particles", gpu::TextureColor::OpaqueBlack, |pass| {
+        particle_system.draw(&pipeline, pass, &camera)
+    });
 
-    let pixels = target.read_pixels(&context, &mut command_encoder);
+    let pixels = session.end_frame(&context);
     snapshot::check("particle", &pixels, size);
 
     particle_system.destroy(&context);
     pipeline.destroy(&context);
-    context.destroy_command_encoder(&mut command_encoder);
-    target.destroy(&context);
+    session.destroy(&context);
 }
 
 #[test]
@@ -587,6 +616,7 @@ fn snapshot_space_sky() {
         fragment: Some(shader.at("raster_sky_fs")),
         color_targets: &[format.into()],
         multisample_state: gpu::MultisampleState::default(),
+        multiview: None,
     });
 
     // Build camera: look along +Z from origin
@@ -627,6 +657,8 @@ fn snapshot_space_sky() {
                 finish_op: gpu::FinishOp::Store,
             }],
             depth_stencil: None,
+            depth_stencil_read_only: gpu::TexelAspects::empty(),
+            multiview: None,
         },
     ) && let mut pc = pass.with(&sky_pipeline)
     {
@@ -665,3 +697,24 @@ fn snapshot_space_sky() {
     context.destroy_command_encoder(&mut command_encoder);
     target.destroy(&context);
 }
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn init_report() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let report = context.report();
+    assert!(!report.backend.is_empty());
+    assert!(!report.api_version.is_empty());
+    assert_eq!(
+        report.device.device_name,
+        context.device_information().device_name
+    );
+    assert_eq!(
+        report.features.iter().any(|name| name == "robustness"),
+        context.capabilities().robustness
+    );
+    assert!(report.surface.is_none());
+    let text = report.to_string();
+    assert!(text.contains(&report.backend));
+    assert!(text.contains("Surface: not configured"));
+}
@@ -0,0 +1,256 @@
+//! Acceleration structures and ray queries of the backends.
+#![allow(irrefutable_let_patterns)]
+#![cfg(not(gles))]
+
+use blade_graphics as gpu;
+use blade_graphics::ShaderData;
+use std::slice;
+
+#[allow(dead_code)]
+mod common;
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn motion_acceleration_structures() {
+    let Some(context) = common::ray_tracing_context() else {
+        return;
+    };
+    let vertex_buffer = common::create_triangle_buffer(&context);
+    let meshes = [common::triangle_mesh(vertex_buffer)];
+    let blas_sizes = context.get_bottom_level_acceleration_structure_sizes(&meshes);
+    let blas = context.create_acceleration_structure(gpu::AccelerationStructureDesc {
+        name: "triangle",
+        ty: gpu::AccelerationStructureType::BottomLevel,
+        size: blas_sizes.data,
+    });
+    // The triangle slides along X during the frame
+    let instances = [gpu::AccelerationStructureMotionInstance {
+        transform_end: [
+            [1.0, 0.0, 0.0, 1.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ]
+        .into(),
+        ..Default::default()
+    }];
+
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "motion",
+        buffer_count: 1,
+    });
+    command_encoder.start();
+    let mut buffers = vec![vertex_buffer];
+    let mut acceleration_structures = vec![blas];
+    if context.capabilities().acceleration_structure_motion {
+        let tlas_sizes = context
+            .get_top_level_motion_acceleration_structure_sizes(instances.len() as u32)
+            .unwrap();
+        let instance_buffer = context
+            .create_acceleration_structure_motion_instance_buffer(&instances, &[blas])
+            .unwrap();
+        let tlas = context.create_acceleration_structure(gpu::AccelerationStructureDesc {
+            name: "motion TLAS",
+            ty: gpu::AccelerationStructureType::TopLevelMotion,
+            size: tlas_sizes.data,
+        });
+        let tlas_scratch_offset = blas_sizes
+            .scratch
+            .next_multiple_of(tlas_sizes.scratch_alignment);
+        let scratch_buffer = context.create_buffer(gpu::BufferDesc {
+            name: "scratch",
+            size: tlas_scratch_offset + tlas_sizes.scratch,
+            memory: gpu::Memory::Device,
+        });
+        if let mut pass = command_encoder.acceleration_structure("BLAS") {
+            pass.build_bottom_level(blas, &meshes, scratch_buffer.at(0));
+        }
+        if let mut pass = command_encoder.acceleration_structure("TLAS") {
+            pass.build_top_level_motion(
+                tlas,
+                instances.len() as u32,
+                instance_buffer.at(0),
+                scratch_buffer.at(tlas_scratch_offset),
+            )
+            .unwrap();
+        }
+        buffers.extend([instance_buffer, scratch_buffer]);
+        acceleration_structures.push(tlas);
+    } else {
+        println!("Motion instances are not supported, checking the errors");
+        let error = gpu::ResourceError::MotionNotSupported;
+        assert_eq!(
+            context.get_top_level_motion_acceleration_structure_sizes(1),
+            Err(error.clone())
+        );
+        assert_eq!(
+            context.create_acceleration_structure_motion_instance_buffer(&instances, &[blas]),
+            Err(error.clone())
+        );
+        let mut pass = command_encoder.acceleration_structure("TLAS");
+        assert_eq!(
+            pass.build_top_level_motion(blas, 1, vertex_buffer.at(0), vertex_buffer.at(0)),
+            Err(error)
+        );
+    }
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+    context.destroy_command_encoder(&mut command_encoder);
+    for acceleration_structure in acceleration_structures {
+        context.destroy_acceleration_structure(acceleration_structure);
+    }
+    for buffer in buffers {
+        context.destroy_buffer(buffer);
+    }
+}
+
+#[derive(blade_macros::ShaderData)]
+struct RayHitData {
+    acc_struct: gpu::AccelerationStructure,
+    hits: gpu::BufferPiece,
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn shared_scratch_builds() {
+    const TRIANGLE_COUNT: usize = 4;
+    // Every other ray goes between the triangles
+    const RAY_COUNT: usize = TRIANGLE_COUNT * 2;
+
+    let Some(context) = common::ray_tracing_context() else {
+        return;
+    };
+    // Triangles of different sizes, 4 units apart along X
+    let vertex_buffers = (0..TRIANGLE_COUNT)
+        .map(|index| {
+            let x = index as f32 * 4.0;
+            let half = 0.5 + index as f32 * 0.25;
+            let vertices: [[f32; 3]; 3] =
+                [[x - half, -1.0, 0.0], [x + half, -1.0, 0.0], [x, 1.0, 0.0]];
+            let buffer = context.create_buffer(gpu::BufferDesc {
+                name: "triangle",
+                size: std::mem::size_of_val(&vertices) as u64,
+                memory: gpu::Memory::Shared,
+            });
+            unsafe {
+                std::ptr::copy_nonoverlapping(vertices.as_ptr(), buffer.data() as *mut [f32; 3], 3);
+            }
+            buffer
+        })
+        .collect::<Vec<_>>();
+    let meshes = vertex_buffers
+        .iter()
+        .map(|&buffer| [common::triangle_mesh(buffer)])
+        .collect::<Vec<_>>();
+
+    let mut scratch_size = 0;
+    let blases = meshes
+        .iter()
+        .map(|mesh| {
+            let sizes = context.get_bottom_level_acceleration_structure_sizes(mesh);
+            assert!(sizes.scratch_alignment.is_power_of_two(), "{sizes:?}");
+            // an update never needs more scratch than a full build
+            assert!(sizes.update_scratch <= sizes.scratch, "{sizes:?}");
+            scratch_size = scratch_size.max(sizes.scratch);
+            context.create_acceleration_structure(gpu::AccelerationStructureDesc {
+                name: "triangle",
+                ty: gpu::AccelerationStructureType::BottomLevel,
+                size: sizes.data,
+            })
+        })
+        .collect::<Vec<_>>();
+    let tlas_sizes = context.get_top_level_acceleration_structure_sizes(TRIANGLE_COUNT as u32);
+    assert!(tlas_sizes.scratch_alignment.is_power_of_two());
+    assert!(tlas_sizes.update_scratch <= tlas_sizes.scratch);
+    // The TLAS goes at the first aligned offset past the start,
+    // overlapping the scratch of the BLASes
+    let tlas_scratch_offset = tlas_sizes.scratch_alignment;
+    scratch_size = scratch_size.max(tlas_scratch_offset + tlas_sizes.scratch);
+    let scratch_buffer = context.create_buffer(gpu::BufferDesc {
+        name: "scratch",
+        size: scratch_size,
+        memory: gpu::Memory::Device,
+    });
+    let tlas = context.create_acceleration_structure(gpu::AccelerationStructureDesc {
+        name: "TLAS",
+        ty: gpu::AccelerationStructureType::TopLevel,
+        size: tlas_sizes.data,
+    });
+    let instances = (0..TRIANGLE_COUNT)
+        .map(|index| gpu::AccelerationStructureInstance {
+            acceleration_structure_index: index as u32,
+            custom_index: index as u32,
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    let instance_buffer =
+        context.create_acceleration_structure_instance_buffer(&instances, &blases);
+    let hit_buffer = context.create_buffer(gpu::BufferDesc {
+        name: "hits",
+        size: (RAY_COUNT * std::mem::size_of::<u32>()) as u64,
+        memory: gpu::Memory::Shared,
+    });
+
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/ray_hits.wgsl"),
+        naga_module: None,
+    });
+    let mut pipeline = context.create_compute_pipeline(gpu::ComputePipelineDesc {
+        name: "ray-hits",
+        data_layouts: &[&RayHitData::layout()],
+        compute: shader.at("main"),
+    });
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "shared scratch",
+        buffer_count: 1,
+    });
+    command_encoder.start();
+    // All the builds go back to back through the same scratch memory
+    if let mut pass = command_encoder.acceleration_structure("BLAS") {
+        for (&blas, mesh) in blases.iter().zip(meshes.iter()) {
+            pass.build_bottom_level(blas, mesh, scratch_buffer.at(0));
+        }
+    }
+    if let mut pass = command_encoder.acceleration_structure("TLAS") {
+        pass.build_top_level(
+            tlas,
+            &blases,
+            TRIANGLE_COUNT as u32,
+            instance_buffer.at(0),
+            scratch_buffer.at(tlas_scratch_offset),
+        );
+    }
+    if let mut compute = command_encoder.compute("ray-hits")
+        && let mut pass = compute.with(&pipeline)
+    {
+        pass.bind(
+            0,
+            &RayHitData {
+                acc_struct: tlas,
+                hits: hit_buffer.into(),
+            },
+        );
+        pass.dispatch([1, 1, 1]);
+    }
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+    let hits = unsafe { slice::from_raw_parts(hit_buffer.data() as *const u32, RAY_COUNT) };
+    let expected = (0..RAY_COUNT)
+        .map(|ray| if ray % 2 == 0 { ray as u32 / 2 + 1 } else { 0 })
+        .collect::<Vec<_>>();
+    assert_eq!(hits, expected);
+
+    context.destroy_command_encoder(&mut command_encoder);
+    context.destroy_compute_pipeline(&mut pipeline);
+    context.destroy_acceleration_structure(tlas);
+    for blas in blases {
+        context.destroy_acceleration_structure(blas);
+    }
+    for buffer in vertex_buffers {
+        context.destroy_buffer(buffer);
+    }
+    context.destroy_buffer(instance_buffer);
+    context.destroy_buffer(scratch_buffer);
+    context.destroy_buffer(hit_buffer);
+}
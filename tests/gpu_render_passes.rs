@@ -0,0 +1,1179 @@
+//! Render pass features: depth-only and read-only attachments, clears, multiview, and predication.
+#![allow(irrefutable_let_patterns)]
+
+use blade_graphics as gpu;
+use std::slice;
+
+#[allow(dead_code)]
+mod common;
+
+#[cfg(not(gles))]
+#[test]
+#[ignore = "requires a working GPU context"]
+fn depth_only_passes() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let caps = context.capabilities();
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/golden.wgsl"),
+        naga_module: None,
+    });
+    shader.check_struct_size::<common::QuadParams>();
+    let layout = <common::QuadData as gpu::ShaderData>::layout();
+    let color_format = gpu::TextureFormat::Rgba8Unorm;
+    let depth_format = gpu::TextureFormat::Depth32Float;
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let quad = |rect: [f32; 4], color: [f32; 4], depth: f32| common::QuadData {
+        params: common::QuadParams {
+            rect,
+            color,
+            size: [size.width as f32, size.height as f32],
+            depth,
+            pad: 0.0,
+        },
+    };
+
+    // Writing colors without any color targets is rejected
+    let result = context.try_create_render_pipeline(gpu::RenderPipelineDesc {
+        name: "colorless",
+        data_layouts: &[&layout],
+        vertex: shader.at("vs_quad"),
+        vertex_fetches: &[],
+        primitive: Default::default(),
+        depth_stencil: Some(gpu::DepthStencilState {
+            format: depth_format,
+            depth_write_enabled: true,
+            depth_compare: gpu::CompareFunction::Less,
+            stencil: Default::default(),
+            bias: Default::default(),
+        }),
+        fragment: Some(shader.at("fs_color")),
+        color_targets: &[],
+        multisample_state: Default::default(),
+        multiview: None,
+    });
+    match result {
+        Err(gpu::PipelineError::ColorOutputsWithoutTargets { fragment, count }) => {
+            assert_eq!((fragment.as_str(), count), ("fs_color", 1));
+        }
+        Err(other) => panic!("Unexpected error: {other}"),
+        Ok(_) => panic!("Pipeline creation should fail"),
+    }
+
+    let depth_only_pipeline = |sample_count| {
+        context.create_render_pipeline(gpu::RenderPipelineDesc {
+            name: "depth-only",
+            data_layouts: &[&layout],
+            vertex: shader.at("vs_quad"),
+            vertex_fetches: &[],
+            primitive: Default::default(),
+            depth_stencil: Some(gpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: true,
+                depth_compare: gpu::CompareFunction::Less,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            fragment: None,
+            color_targets: &[],
+            multisample_state: gpu::MultisampleState {
+                sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        })
+    };
+    let mut depth_pipeline = depth_only_pipeline(1);
+    let mut color_pipeline = context.create_render_pipeline(gpu::RenderPipelineDesc {
+        name: "depth-tested",
+        data_layouts: &[&layout],
+        vertex: shader.at("vs_quad"),
+        vertex_fetches: &[],
+        primitive: Default::default(),
+        depth_stencil: Some(gpu::DepthStencilState {
+            format: depth_format,
+            depth_write_enabled: false,
+            depth_compare: gpu::CompareFunction::Less,
+            stencil: Default::default(),
+            bias: Default::default(),
+        }),
+        fragment: Some(shader.at("fs_color")),
+        color_targets: &[color_format.into()],
+        multisample_state: Default::default(),
+        multiview: None,
+    });
+
+    let msaa_samples = 4;
+    let msaa = (caps.depth_resolve && caps.sample_count_mask & msaa_samples != 0).then(|| {
+        let texture = context.create_texture(gpu::TextureDesc {
+            name: "depth-msaa",
+            format: depth_format,
+            size,
+            array_layer_count: 1,
+            mip_level_count: 1,
+            dimension: gpu::TextureDimension::D2,
+            usage: gpu::TextureUsage::TARGET,
+            sample_count: msaa_samples,
+            external: None,
+        });
+        let view = context.create_texture_view(
+            texture,
+            gpu::TextureViewDesc {
+                name: "depth-msaa",
+                format: depth_format,
+                dimension: gpu::ViewDimension::D2,
+                subresources: &Default::default(),
+            },
+        );
+        (texture, view, depth_only_pipeline(msaa_samples))
+    });
+    if msaa.is_none() {
+        println!("Skipping the depth resolve: not supported");
+    }
+
+    let mut session = blade_util::OffscreenSession::new(
+        &blade_util::OffscreenSessionDescriptor {
+            name: "depth-only",
+            size,
+            color_format,
+            depth_format: Some(depth_format),
+        },
+        &context,
+    );
+    let color_view = session.color_view();
+    let depth_view = session.depth_view().unwrap();
+    let resolve_mode = if caps.depth_resolve_min_max {
+        gpu::DepthResolveMode::Min
+    } else {
+        gpu::DepthResolveMode::SampleZero
+    };
+    let resolve_variants: &[bool] = match msaa {
+        Some(_) => &[false, true],
+        None => &[false],
+    };
+    for &resolve in resolve_variants {
+        let encoder = session.begin_frame();
+        if let Some(&(texture, _, _)) = msaa.as_ref() {
+            encoder.init_texture(texture);
+        }
+        // The left half is occluded by the depth-only pass
+        let occluder = quad([-1.0, -1.0, 0.0, 1.0], [0.0; 4], 0.25);
+        let (view, finish_op, pipeline) = match msaa {
+            Some((_, msaa_view, ref msaa_pipeline)) if resolve => (
+                msaa_view,
+                gpu::FinishOp::ResolveDepthTo(depth_view, resolve_mode),
+                msaa_pipeline,
+            ),
+            _ => (depth_view, gpu::FinishOp::Store, &depth_pipeline),
+        };
+        if let mut pass = encoder.render(
+            "depth-only",
+            gpu::RenderTargetSet {
+                colors: &[],
+                depth_stencil: Some(gpu::RenderTarget {
+                    view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::White),
+                    finish_op,
+                }),
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        ) {
+            let mut pc = pass.with(pipeline);
+            pc.bind(0, &occluder);
+            pc.draw(0, 6, 0, 1);
+        }
+        let fill = quad([-1.0, -1.0, 1.0, 1.0], [0.0, 1.0, 0.0, 1.0], 0.5);
+        if let mut pass = encoder.render(
+            "depth-tested",
+            gpu::RenderTargetSet {
+                colors: &[gpu::RenderTarget {
+                    view: color_view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                    finish_op: gpu::FinishOp::Store,
+                }],
+                depth_stencil: Some(gpu::RenderTarget {
+                    view: depth_view,
+                    init_op: gpu::InitOp::Load,
+                    finish_op: gpu::FinishOp::Discard,
+                }),
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        ) {
+            let mut pc = pass.with(&color_pipeline);
+            pc.bind(0, &fill);
+            pc.draw(0, 6, 0, 1);
+        }
+        let pixels = session.end_frame(&context);
+
+        for (i, pixel) in pixels.chunks(4).enumerate() {
+            let x = i as u32 % size.width;
+            let expected = if x < size.width / 2 {
+                [0, 0, 0, 255]
+            } else {
+                [0, 255, 0, 255]
+            };
+            assert_eq!(pixel, expected, "Pixel {i} with resolve: {resolve}");
+        }
+    }
+
+    if let Some((texture, view, mut pipeline)) = msaa {
+        context.destroy_render_pipeline(&mut pipeline);
+        context.destroy_texture_view(view);
+        context.destroy_texture(texture);
+    }
+    session.destroy(&context);
+    context.destroy_render_pipeline(&mut color_pipeline);
+    context.destroy_render_pipeline(&mut depth_pipeline);
+}
+
+#[cfg(not(gles))]
+#[derive(blade_macros::ShaderData)]
+struct AspectsData {
+    params: common::QuadParams,
+    depth_source: gpu::TextureView,
+    stencil_source: gpu::TextureView,
+}
+
+#[cfg(not(gles))]
+#[test]
+#[ignore = "requires a working GPU context"]
+fn depth_stencil_read_only() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/depth_read_only.wgsl"),
+        naga_module: None,
+    });
+    shader.check_struct_size::<common::QuadParams>();
+    let quad_layout = <common::QuadData as gpu::ShaderData>::layout();
+    let aspects_layout = <AspectsData as gpu::ShaderData>::layout();
+    let color_format = gpu::TextureFormat::Rgba8Unorm;
+    let ds_format = gpu::TextureFormat::Depth32FloatStencil8Uint;
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let params = |rect: [f32; 4], depth: f32| common::QuadParams {
+        rect,
+        color: [0.0; 4],
+        size: [size.width as f32, size.height as f32],
+        depth,
+        pad: 0.0,
+    };
+
+    let ds_texture = context.create_texture(gpu::TextureDesc {
+        name: "depth-stencil",
+        format: ds_format,
+        size,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        dimension: gpu::TextureDimension::D2,
+        usage: gpu::TextureUsage::TARGET | gpu::TextureUsage::RESOURCE,
+        sample_count: 1,
+        external: None,
+    });
+    let view = |name, format| {
+        context.create_texture_view(
+            ds_texture,
+            gpu::TextureViewDesc {
+                name,
+                format,
+                dimension: gpu::ViewDimension::D2,
+                subresources: &Default::default(),
+            },
+        )
+    };
+    let ds_view = view("depth-stencil", ds_format);
+    let depth_view = view("depth-aspect", gpu::TextureFormat::Depth32Float);
+    let stencil_view = view("stencil-aspect", gpu::TextureFormat::Stencil8Uint);
+
+    let stencil_face = |compare, pass_op| gpu::StencilFaceState {
+        compare,
+        fail_op: gpu::StencilOperation::Keep,
+        depth_fail_op: gpu::StencilOperation::Keep,
+        pass_op,
+    };
+    let stencil_state = |face: gpu::StencilFaceState| gpu::StencilState {
+        front: face,
+        back: face,
+        read_mask: 0xFF,
+        write_mask: 0xFF,
+    };
+    let mut prepass_pipeline = context.create_render_pipeline(gpu::RenderPipelineDesc {
+        name: "prepass",
+        data_layouts: &[&quad_layout],
+        vertex: shader.at("vs_quad"),
+        vertex_fetches: &[],
+        primitive: Default::default(),
+        depth_stencil: Some(gpu::DepthStencilState {
+            format: ds_format,
+            depth_write_enabled: true,
+            depth_compare: gpu::CompareFunction::Less,
+            stencil: stencil_state(stencil_face(
+                gpu::CompareFunction::Always,
+                gpu::StencilOperation::Replace,
+            )),
+            bias: Default::default(),
+        }),
+        fragment: None,
+        color_targets: &[],
+        multisample_state: Default::default(),
+        multiview: None,
+    });
+    let masked_desc = gpu::DepthStencilState {
+        format: ds_format,
+        depth_write_enabled: false,
+        depth_compare: gpu::CompareFunction::Always,
+        stencil: stencil_state(stencil_face(
+            gpu::CompareFunction::Equal,
+            gpu::StencilOperation::Keep,
+        )),
+        bias: Default::default(),
+    };
+    let mut masked_pipeline = context.create_render_pipeline(gpu::RenderPipelineDesc {
+        name: "masked",
+        data_layouts: &[&aspects_layout],
+        vertex: shader.at("vs_quad"),
+        vertex_fetches: &[],
+        primitive: Default::default(),
+        depth_stencil: Some(masked_desc),
+        fragment: Some(shader.at("fs_aspects")),
+        color_targets: &[color_format.into()],
+        multisample_state: Default::default(),
+        multiview: None,
+    });
+
+    let mut session = blade_util::OffscreenSession::new(
+        &blade_util::OffscreenSessionDescriptor {
+            name: "depth-stencil-read-only",
+            size,
+            color_format,
+            depth_format: None,
+        },
+        &context,
+    );
+    let color_view = session.color_view();
+    let encoder = session.begin_frame();
+    encoder.init_texture(ds_texture);
+    // The left half gets depth 0.25 and stencil 1
+    if let mut pass = encoder.render(
+        "prepass",
+        gpu::RenderTargetSet {
+            colors: &[],
+            depth_stencil: Some(gpu::RenderTarget {
+                view: ds_view,
+                init_op: gpu::InitOp::Clear(gpu::TextureColor::White),
+                finish_op: gpu::FinishOp::Store,
+            }),
+            depth_stencil_read_only: gpu::TexelAspects::empty(),
+            multiview: None,
+        },
+    ) {
+        let mut pc = pass.with(&prepass_pipeline);
+        pc.set_stencil_reference(1);
+        pc.bind(
+            0,
+            &common::QuadData {
+                params: params([-1.0, -1.0, 0.0, 1.0], 0.25),
+            },
+        );
+        pc.draw(0, 6, 0, 1);
+    }
+    // Both aspects are sampled while masking by the stencil
+    if let mut pass = encoder.render(
+        "read-only",
+        gpu::RenderTargetSet {
+            colors: &[gpu::RenderTarget {
+                view: color_view,
+                init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                finish_op: gpu::FinishOp::Store,
+            }],
+            depth_stencil: Some(gpu::RenderTarget {
+                view: ds_view,
+                init_op: gpu::InitOp::Load,
+                finish_op: gpu::FinishOp::Store,
+            }),
+            depth_stencil_read_only: gpu::TexelAspects::DEPTH | gpu::TexelAspects::STENCIL,
+            multiview: None,
+        },
+    ) {
+        let mut pc = pass.with(&masked_pipeline);
+        pc.set_stencil_reference(1);
+        pc.bind(
+            0,
+            &AspectsData {
+                params: params([-1.0, -1.0, 1.0, 1.0], 0.5),
+                depth_source: depth_view,
+                stencil_source: stencil_view,
+            },
+        );
+        pc.draw(0, 6, 0, 1);
+    }
+    let pixels = session.end_frame(&context);
+
+    for (i, pixel) in pixels.chunks(4).enumerate() {
+        let x = i as u32 % size.width;
+        if x < size.width / 2 {
+            assert!(
+                pixel[0].abs_diff(64) <= 1 && pixel[1..] == [255, 0, 255],
+                "Pixel {i} inside the mask: {pixel:?}"
+            );
+        } else {
+            assert_eq!(pixel, [0, 0, 0, 255], "Pixel {i} outside the mask");
+        }
+    }
+
+    session.destroy(&context);
+    context.destroy_render_pipeline(&mut masked_pipeline);
+    context.destroy_render_pipeline(&mut prepass_pipeline);
+    context.destroy_texture_view(stencil_view);
+    context.destroy_texture_view(depth_view);
+    context.destroy_texture_view(ds_view);
+    context.destroy_texture(ds_texture);
+}
+
+#[cfg(not(gles))]
+#[test]
+#[ignore = "requires a working GPU context"]
+fn partial_depth_stencil_clears() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/depth_read_only.wgsl"),
+        naga_module: None,
+    });
+    shader.check_struct_size::<common::QuadParams>();
+    let aspects_layout = <AspectsData as gpu::ShaderData>::layout();
+    let color_format = gpu::TextureFormat::Rgba8Unorm;
+    let ds_format = gpu::TextureFormat::Depth32FloatStencil8Uint;
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+
+    let ds_texture = context.create_texture(gpu::TextureDesc {
+        name: "depth-stencil",
+        format: ds_format,
+        size,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        dimension: gpu::TextureDimension::D2,
+        usage: gpu::TextureUsage::TARGET | gpu::TextureUsage::RESOURCE,
+        sample_count: 1,
+        external: None,
+    });
+    let view = |name, format| {
+        context.create_texture_view(
+            ds_texture,
+            gpu::TextureViewDesc {
+                name,
+                format,
+                dimension: gpu::ViewDimension::D2,
+                subresources: &Default::default(),
+            },
+        )
+    };
+    let ds_view = view("depth-stencil", ds_format);
+    let depth_view = view("depth-aspect", gpu::TextureFormat::Depth32Float);
+    let stencil_view = view("stencil-aspect", gpu::TextureFormat::Stencil8Uint);
+
+    let stencil_face = gpu::StencilFaceState {
+        compare: gpu::CompareFunction::Equal,
+        fail_op: gpu::StencilOperation::Keep,
+        depth_fail_op: gpu::StencilOperation::Keep,
+        pass_op: gpu::StencilOperation::Keep,
+    };
+    let mut masked_pipeline = context.create_render_pipeline(gpu::RenderPipelineDesc {
+        name: "masked",
+        data_layouts: &[&aspects_layout],
+        vertex: shader.at("vs_quad"),
+        vertex_fetches: &[],
+        primitive: Default::default(),
+        depth_stencil: Some(gpu::DepthStencilState {
+            format: ds_format,
+            depth_write_enabled: false,
+            depth_compare: gpu::CompareFunction::Always,
+            stencil: gpu::StencilState {
+                front: stencil_face,
+                back: stencil_face,
+                read_mask: 0xFF,
+                write_mask: 0xFF,
+            },
+            bias: Default::default(),
+        }),
+        fragment: Some(shader.at("fs_aspects")),
+        color_targets: &[color_format.into()],
+        multisample_state: Default::default(),
+        multiview: None,
+    });
+
+    let mut session = blade_util::OffscreenSession::new(
+        &blade_util::OffscreenSessionDescriptor {
+            name: "partial-depth-stencil-clears",
+            size,
+            color_format,
+            depth_format: None,
+        },
+        &context,
+    );
+    let color_view = session.color_view();
+    let encoder = session.begin_frame();
+    encoder.init_texture(ds_texture);
+    let clear = |encoder: &mut gpu::CommandEncoder, name, depth, stencil| {
+        let _ = encoder.render(
+            name,
+            gpu::RenderTargetSet {
+                colors: &[],
+                depth_stencil: Some(gpu::RenderTarget {
+                    view: ds_view,
+                    init_op: gpu::InitOp::ClearDepthStencil { depth, stencil },
+                    finish_op: gpu::FinishOp::Store,
+                }),
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        );
+    };
+    clear(encoder, "clear-both", Some(0.5), Some(3));
+    // Clearing only the depth keeps the stencil of 3
+    clear(encoder, "clear-depth", Some(0.25), None);
+    // Both aspects are sampled by a quad that only passes the stencil test with the reference
+    let draw_masked = |encoder: &mut gpu::CommandEncoder, name, color_init_op, rect, reference| {
+        if let mut pass = encoder.render(
+            name,
+            gpu::RenderTargetSet {
+                colors: &[gpu::RenderTarget {
+                    view: color_view,
+                    init_op: color_init_op,
+                    finish_op: gpu::FinishOp::Store,
+                }],
+                depth_stencil: Some(gpu::RenderTarget {
+                    view: ds_view,
+                    init_op: gpu::InitOp::Load,
+                    finish_op: gpu::FinishOp::Store,
+                }),
+                depth_stencil_read_only: gpu::TexelAspects::DEPTH | gpu::TexelAspects::STENCIL,
+                multiview: None,
+            },
+        ) {
+            let mut pc = pass.with(&masked_pipeline);
+            pc.set_stencil_reference(reference);
+            pc.bind(
+                0,
+                &AspectsData {
+                    params: common::QuadParams {
+                        rect,
+                        color: [0.0; 4],
+                        size: [size.width as f32, size.height as f32],
+                        depth: 0.5,
+                        pad: 0.0,
+                    },
+                    depth_source: depth_view,
+                    stencil_source: stencil_view,
+                },
+            );
+            pc.draw(0, 6, 0, 1);
+        }
+    };
+    draw_masked(
+        encoder,
+        "read-left",
+        gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+        [-1.0, -1.0, 0.0, 1.0],
+        3,
+    );
+    // Clearing only the stencil keeps the depth of 0.25
+    clear(encoder, "clear-stencil", None, Some(1));
+    draw_masked(
+        encoder,
+        "read-right",
+        gpu::InitOp::Load,
+        [0.0, -1.0, 1.0, 1.0],
+        1,
+    );
+    let pixels = session.end_frame(&context);
+
+    for (i, pixel) in pixels.chunks(4).enumerate() {
+        assert!(
+            pixel[0].abs_diff(64) <= 1 && pixel[1..] == [255, 0, 255],
+            "Pixel {i}: {pixel:?}"
+        );
+    }
+
+    session.destroy(&context);
+    context.destroy_render_pipeline(&mut masked_pipeline);
+    context.destroy_texture_view(stencil_view);
+    context.destroy_texture_view(depth_view);
+    context.destroy_texture_view(ds_view);
+    context.destroy_texture(ds_texture);
+}
+
+#[cfg(not(gles))]
+#[test]
+#[ignore = "requires a working GPU context"]
+fn logic_op_accumulation() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/golden.wgsl"),
+        naga_module: None,
+    });
+    shader.check_struct_size::<common::QuadParams>();
+    let layout = <common::QuadData as gpu::ShaderData>::layout();
+    let flags_format = gpu::TextureFormat::R32Uint;
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let try_create_pipeline = |color_targets: &[gpu::ColorTargetState]| {
+        context.try_create_render_pipeline(gpu::RenderPipelineDesc {
+            name: "flags",
+            data_layouts: &[&layout],
+            vertex: shader.at("vs_quad"),
+            vertex_fetches: &[],
+            primitive: Default::default(),
+            depth_stencil: None,
+            fragment: Some(shader.at("fs_flags")),
+            color_targets,
+            multisample_state: Default::default(),
+            multiview: None,
+        })
+    };
+    let or_target = gpu::ColorTargetState {
+        logic_op: Some(gpu::LogicOp::Or),
+        ..flags_format.into()
+    };
+
+    if !context.capabilities().logic_op {
+        let result = try_create_pipeline(&[or_target]);
+        assert_eq!(result.err(), Some(gpu::PipelineError::LogicOpNotSupported));
+        println!("Skipping the accumulation: logic operations are not supported");
+        return;
+    }
+
+    let float_target = gpu::ColorTargetState {
+        logic_op: Some(gpu::LogicOp::Or),
+        ..gpu::TextureFormat::R32Float.into()
+    };
+    let result = try_create_pipeline(&[float_target]);
+    assert_eq!(
+        result.err(),
+        Some(gpu::PipelineError::LogicOpOnNonIntegerTarget {
+            index: 0,
+            format: gpu::TextureFormat::R32Float,
+        })
+    );
+    let result = try_create_pipeline(&[or_target.clone(), gpu::TextureFormat::Rg32Uint.into()]);
+    assert_eq!(result.err(), Some(gpu::PipelineError::MismatchedLogicOps));
+
+    let mut pipeline = try_create_pipeline(&[or_target]).unwrap();
+    let mut session = blade_util::OffscreenSession::new(
+        &blade_util::OffscreenSessionDescriptor {
+            name: "logic-op",
+            size,
+            color_format: flags_format,
+            depth_format: None,
+        },
+        &context,
+    );
+    let color_view = session.color_view();
+    let encoder = session.begin_frame();
+    if let mut pass = encoder.render(
+        "flags",
+        gpu::RenderTargetSet {
+            colors: &[gpu::RenderTarget {
+                view: color_view,
+                init_op: gpu::InitOp::Clear(gpu::TextureColor::TransparentBlack),
+                finish_op: gpu::FinishOp::Store,
+            }],
+            depth_stencil: None,
+            depth_stencil_read_only: gpu::TexelAspects::empty(),
+            multiview: None,
+        },
+    ) {
+        let mut pc = pass.with(&pipeline);
+        // The left and the right 3/4 overlap in the middle half
+        for (rect, flag) in [([-1.0, -1.0, 0.5, 1.0], 1.0), ([-0.5, -1.0, 1.0, 1.0], 2.0)] {
+            pc.bind(
+                0,
+                &common::QuadData {
+                    params: common::QuadParams {
+                        rect,
+                        color: [flag, 0.0, 0.0, 0.0],
+                        size: [size.width as f32, size.height as f32],
+                        depth: 0.0,
+                        pad: 0.0,
+                    },
+                },
+            );
+            pc.draw(0, 6, 0, 1);
+        }
+    }
+    let texels = session.end_frame(&context);
+
+    for (i, texel) in texels.chunks(4).enumerate() {
+        let x = i as u32 % size.width;
+        let expected = match x * 4 / size.width {
+            0 => 1,
+            3 => 2,
+            _ => 3,
+        };
+        let value = u32::from_ne_bytes(texel.try_into().unwrap());
+        assert_eq!(value, expected, "Texel {i}");
+    }
+
+    session.destroy(&context);
+    context.destroy_render_pipeline(&mut pipeline);
+}
+
+#[cfg(not(gles))]
+#[test]
+#[ignore = "requires a working GPU context"]
+fn multiview_layers() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/multiview.wgsl"),
+        naga_module: None,
+    });
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let try_create_pipeline = |view_count: u32| {
+        context.try_create_render_pipeline(gpu::RenderPipelineDesc {
+            name: "multiview",
+            data_layouts: &[],
+            vertex: shader.at("vs_fullscreen"),
+            vertex_fetches: &[],
+            primitive: Default::default(),
+            depth_stencil: None,
+            fragment: Some(shader.at("fs_view_index")),
+            color_targets: &[format.into()],
+            multisample_state: Default::default(),
+            multiview: std::num::NonZeroU32::new(view_count),
+        })
+    };
+    let limit = context.capabilities().max_view_count;
+    assert_eq!(
+        try_create_pipeline(limit + 1).err(),
+        Some(gpu::PipelineError::TooManyViews {
+            count: limit + 1,
+            limit,
+        })
+    );
+    if limit < 2 {
+        println!("Skipping the rendering: multiview is not supported");
+        return;
+    }
+
+    // Both eyes of a stereo view are rendered by a single draw
+    let view_count = 2;
+    let size = gpu::Extent {
+        width: 16,
+        height: 16,
+        depth: 1,
+    };
+    let mut pipeline = try_create_pipeline(view_count).unwrap();
+    let texture = context.create_texture(gpu::TextureDesc {
+        name: "multiview",
+        format,
+        size,
+        array_layer_count: view_count,
+        mip_level_count: 1,
+        dimension: gpu::TextureDimension::D2,
+        usage: gpu::TextureUsage::TARGET | gpu::TextureUsage::COPY,
+        sample_count: 1,
+        external: None,
+    });
+    let view = context.create_texture_view(
+        texture,
+        gpu::TextureViewDesc {
+            name: "multiview",
+            format,
+            dimension: gpu::ViewDimension::D2Array,
+            subresources: &gpu::TextureSubresources::default(),
+        },
+    );
+    let layer_bytes = size.width * size.height * 4;
+    let buffer = context.create_buffer(gpu::BufferDesc {
+        name: "multiview-readback",
+        size: (layer_bytes * view_count) as u64,
+        memory: gpu::Memory::Shared,
+    });
+
+    let mut encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "multiview",
+        buffer_count: 1,
+    });
+    encoder.start();
+    encoder.init_texture(texture);
+    if let mut pass = encoder.render(
+        "multiview",
+        gpu::RenderTargetSet {
+            colors: &[gpu::RenderTarget {
+                view,
+                init_op: gpu::InitOp::Clear(gpu::TextureColor::TransparentBlack),
+                finish_op: gpu::FinishOp::Store,
+            }],
+            depth_stencil: None,
+            depth_stencil_read_only: gpu::TexelAspects::empty(),
+            multiview: std::num::NonZeroU32::new(view_count),
+        },
+    ) {
+        pass.with(&pipeline).draw(0, 3, 0, 1);
+    }
+    if let mut transfer = encoder.transfer("readback") {
+        for layer in 0..view_count {
+            transfer.copy_texture_to_buffer(
+                gpu::TexturePiece {
+                    texture,
+                    mip_level: 0,
+                    array_layer: layer,
+                    origin: [0; 3],
+                },
+                buffer.at((layer * layer_bytes) as u64),
+                size.width * 4,
+                size,
+            );
+        }
+    }
+    let sync_point = context.submit(&mut encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    let texels = unsafe {
+        slice::from_raw_parts(
+            buffer.data() as *const [u8; 4],
+            (size.width * size.height * view_count) as usize,
+        )
+    };
+    for (i, texel) in texels.iter().enumerate() {
+        let layer = i as u32 / (size.width * size.height);
+        assert_eq!(*texel, [layer as u8 + 1, 0, 0, 255], "Texel {i}");
+    }
+
+    context.destroy_command_encoder(&mut encoder);
+    context.destroy_buffer(buffer);
+    context.destroy_texture_view(view);
+    context.destroy_texture(texture);
+    context.destroy_render_pipeline(&mut pipeline);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn conditional_rendering() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    if !context.capabilities().conditional_rendering {
+        println!("Skipping: conditional rendering is not supported");
+        return;
+    }
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/golden.wgsl"),
+        naga_module: None,
+    });
+    shader.check_struct_size::<common::QuadParams>();
+    let layout = <common::QuadData as gpu::ShaderData>::layout();
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let mut pipeline = context.create_render_pipeline(gpu::RenderPipelineDesc {
+        name: "conditional",
+        data_layouts: &[&layout],
+        vertex: shader.at("vs_quad"),
+        vertex_fetches: &[],
+        primitive: Default::default(),
+        depth_stencil: None,
+        fragment: Some(shader.at("fs_color")),
+        color_targets: &[format.into()],
+        multisample_state: Default::default(),
+        multiview: None,
+    });
+    // Any non-zero value executes the draws, not only 1
+    let predicates = [0u32, 7];
+    let predicate_buffer = context.create_buffer(gpu::BufferDesc {
+        name: "predicates",
+        size: std::mem::size_of_val(&predicates) as u64,
+        memory: gpu::Memory::Shared,
+    });
+    unsafe {
+        std::ptr::copy_nonoverlapping(
+            predicates.as_ptr(),
+            predicate_buffer.data() as *mut u32,
+            predicates.len(),
+        );
+    }
+
+    let mut session = blade_util::OffscreenSession::new(
+        &blade_util::OffscreenSessionDescriptor {
+            name: "conditional",
+            size,
+            color_format: format,
+            depth_format: None,
+        },
+        &context,
+    );
+    let color_view = session.color_view();
+    let encoder = session.begin_frame();
+    if let mut pass = encoder.render(
+        "conditional",
+        gpu::RenderTargetSet {
+            colors: &[gpu::RenderTarget {
+                view: color_view,
+                init_op: gpu::InitOp::Clear(gpu::TextureColor::TransparentBlack),
+                finish_op: gpu::FinishOp::Store,
+            }],
+            depth_stencil: None,
+            depth_stencil_read_only: gpu::TexelAspects::empty(),
+            multiview: None,
+        },
+    ) {
+        // The left half is predicated by zero, and the right half by non-zero
+        for (i, rect) in [[-1.0, -1.0, 0.0, 1.0], [0.0, -1.0, 1.0, 1.0]]
+            .into_iter()
+            .enumerate()
+        {
+            pass.begin_conditional(predicate_buffer.at(i as u64 * 4));
+            {
+                let mut pc = pass.with(&pipeline);
+                pc.bind(
+                    0,
+                    &common::QuadData {
+                        params: common::QuadParams {
+                            rect,
+                            color: [1.0; 4],
+                            size: [size.width as f32, size.height as f32],
+                            depth: 0.0,
+                            pad: 0.0,
+                        },
+                    },
+                );
+                pc.draw(0, 6, 0, 1);
+            }
+            pass.end_conditional();
+        }
+    }
+    let texels = session.end_frame(&context);
+
+    for (i, texel) in texels.chunks(4).enumerate() {
+        let x = i as u32 % size.width;
+        let expected = if x < size.width / 2 { 0 } else { 255 };
+        assert_eq!(texel, [expected; 4], "Texel {i}");
+    }
+
+    session.destroy(&context);
+    context.destroy_buffer(predicate_buffer);
+    context.destroy_render_pipeline(&mut pipeline);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn per_target_clear_values() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let size = gpu::Extent {
+        width: 4,
+        height: 4,
+        depth: 1,
+    };
+    // An ID buffer with "no object" everywhere, next to an explicit and a named float clear
+    let targets = [
+        (
+            gpu::TextureFormat::R32Uint,
+            gpu::InitOp::ClearColor(gpu::ClearColor::Uint([!0, 0, 0, 0])),
+            (!0u32).to_ne_bytes().to_vec(),
+        ),
+        (
+            gpu::TextureFormat::Rgba8Unorm,
+            gpu::InitOp::ClearColor(gpu::ClearColor::Float([0.0, 1.0, 0.0, 1.0])),
+            vec![0, 255, 0, 255],
+        ),
+        (
+            gpu::TextureFormat::Rgba8Unorm,
+            gpu::InitOp::Clear(gpu::TextureColor::White),
+            vec![255; 4],
+        ),
+    ];
+    let create_target = |format| {
+        let texture = context.create_texture(gpu::TextureDesc {
+            name: "clear",
+            format,
+            size,
+            array_layer_count: 1,
+            mip_level_count: 1,
+            dimension: gpu::TextureDimension::D2,
+            usage: gpu::TextureUsage::TARGET | gpu::TextureUsage::COPY,
+            sample_count: 1,
+            external: None,
+        });
+        let view = context.create_texture_view(
+            texture,
+            gpu::TextureViewDesc {
+                name: "clear",
+                format,
+                dimension: gpu::ViewDimension::D2,
+                subresources: &gpu::TextureSubresources::default(),
+            },
+        );
+        (texture, view)
+    };
+    let color_targets = targets
+        .iter()
+        .map(|&(format, _, _)| create_target(format))
+        .collect::<Vec<_>>();
+    let (ds_texture, ds_view) = create_target(gpu::TextureFormat::Depth32FloatStencil8Uint);
+    let texel_bytes = 4;
+    let target_bytes = size.width * size.height * texel_bytes;
+    let buffer = context.create_buffer(gpu::BufferDesc {
+        name: "clear-readback",
+        size: (target_bytes * targets.len() as u32) as u64,
+        memory: gpu::Memory::Shared,
+    });
+
+    let mut encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "clear",
+        buffer_count: 1,
+    });
+    encoder.start();
+    for &(texture, _) in color_targets.iter() {
+        encoder.init_texture(texture);
+    }
+    encoder.init_texture(ds_texture);
+    let colors = targets
+        .iter()
+        .zip(color_targets.iter())
+        .map(|(&(_, init_op, _), &(_, view))| gpu::RenderTarget {
+            view,
+            init_op,
+            finish_op: gpu::FinishOp::Store,
+        })
+        .collect::<Vec<_>>();
+    // The pass only clears the targets
+    let _ = encoder.render(
+        "clear",
+        gpu::RenderTargetSet {
+            colors: &colors,
+            depth_stencil: Some(gpu::RenderTarget {
+                view: ds_view,
+                init_op: gpu::InitOp::ClearDepthStencil {
+                    depth: Some(0.5),
+                    stencil: Some(3),
+                },
+                finish_op: gpu::FinishOp::Store,
+            }),
+            depth_stencil_read_only: gpu::TexelAspects::empty(),
+            multiview: None,
+        },
+    );
+    if let mut transfer = encoder.transfer("readback") {
+        for (i, &(texture, _)) in color_targets.iter().enumerate() {
+            transfer.copy_texture_to_buffer(
+                texture.into(),
+                buffer.at((i as u32 * target_bytes) as u64),
+                size.width * texel_bytes,
+                size,
+            );
+        }
+    }
+    let sync_point = context.submit(&mut encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    let data = unsafe { slice::from_raw_parts(buffer.data(), buffer.size() as usize) };
+    for (&(format, _, ref expected), bytes) in
+        targets.iter().zip(data.chunks(target_bytes as usize))
+    {
+        for texel in bytes.chunks(texel_bytes as usize) {
+            assert_eq!(texel, &expected[..], "{format:?}");
+        }
+    }
+
+    context.destroy_command_encoder(&mut encoder);
+    context.destroy_buffer(buffer);
+    for (texture, view) in color_targets.into_iter().chain([(ds_texture, ds_view)]) {
+        context.destroy_texture_view(view);
+        context.destroy_texture(texture);
+    }
+}
+
+/// Clear a single target of the given format in an otherwise empty pass.
+fn clear_single_target(format: gpu::TextureFormat, init_op: gpu::InitOp) {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let texture = context.create_texture(gpu::TextureDesc {
+        name: "clear",
+        format,
+        size: gpu::Extent {
+            width: 4,
+            height: 4,
+            depth: 1,
+        },
+        array_layer_count: 1,
+        mip_level_count: 1,
+        dimension: gpu::TextureDimension::D2,
+        usage: gpu::TextureUsage::TARGET,
+        sample_count: 1,
+        external: None,
+    });
+    let view = context.create_texture_view(
+        texture,
+        gpu::TextureViewDesc {
+            name: "clear",
+            format,
+            dimension: gpu::ViewDimension::D2,
+            subresources: &gpu::TextureSubresources::default(),
+        },
+    );
+    let target = gpu::RenderTarget {
+        view,
+        init_op,
+        finish_op: gpu::FinishOp::Store,
+    };
+    let (colors, depth_stencil) = if format.aspects().contains(gpu::TexelAspects::COLOR) {
+        (vec![target], None)
+    } else {
+        (Vec::new(), Some(target))
+    };
+
+    let mut encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "clear",
+        buffer_count: 1,
+    });
+    encoder.start();
+    encoder.init_texture(texture);
+    let _ = encoder.render(
+        "clear",
+        gpu::RenderTargetSet {
+            colors: &colors,
+            depth_stencil,
+            depth_stencil_read_only: gpu::TexelAspects::empty(),
+            multiview: None,
+        },
+    );
+    let sync_point = context.submit(&mut encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    context.destroy_command_encoder(&mut encoder);
+    context.destroy_texture_view(view);
+    context.destroy_texture(texture);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+#[should_panic(expected = "R32Uint")]
+fn mismatched_color_clear_value() {
+    clear_single_target(
+        gpu::TextureFormat::R32Uint,
+        gpu::InitOp::ClearColor(gpu::ClearColor::Float([1.0; 4])),
+    );
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+#[should_panic(expected = "Depth32FloatStencil8Uint")]
+fn mismatched_depth_stencil_clear_value() {
+    clear_single_target(
+        gpu::TextureFormat::Depth32FloatStencil8Uint,
+        gpu::InitOp::ClearColor(gpu::ClearColor::Uint([0; 4])),
+    );
+}
@@ -0,0 +1,583 @@
+//! Creation, destruction, and memory management of the resources.
+#![allow(irrefutable_let_patterns)]
+
+use blade_graphics as gpu;
+use blade_graphics::ShaderData;
+use std::slice;
+
+#[allow(dead_code)]
+mod common;
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn concurrent_resource_creation() {
+    const WORKER_COUNT: usize = 4;
+    const ITERATIONS: u32 = 200;
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+
+    std::thread::scope(|scope| {
+        let mut workers = Vec::with_capacity(WORKER_COUNT);
+        for worker in 0..WORKER_COUNT {
+            let context = &context;
+            workers.push(scope.spawn(move || {
+                for i in 0..ITERATIONS {
+                    let buffer = context.create_buffer(gpu::BufferDesc {
+                        name: "stress",
+                        size: 256,
+                        memory: gpu::Memory::Shared,
+                    });
+                    unsafe {
+                        let data = slice::from_raw_parts_mut(buffer.data() as *mut u32, 64);
+                        data.fill(worker as u32 * ITERATIONS + i);
+                    }
+                    let texture = context.create_texture(gpu::TextureDesc {
+                        name: "stress",
+                        format: gpu::TextureFormat::Rgba8Unorm,
+                        size: gpu::Extent {
+                            width: 16,
+                            height: 16,
+                            depth: 1,
+                        },
+                        array_layer_count: 1,
+                        mip_level_count: 1,
+                        dimension: gpu::TextureDimension::D2,
+                        usage: gpu::TextureUsage::RESOURCE | gpu::TextureUsage::COPY,
+                        sample_count: 1,
+                        external: None,
+                    });
+                    let view = context.create_texture_view(
+                        texture,
+                        gpu::TextureViewDesc {
+                            name: "stress",
+                            format: gpu::TextureFormat::Rgba8Unorm,
+                            dimension: gpu::ViewDimension::D2,
+                            subresources: &gpu::TextureSubresources::default(),
+                        },
+                    );
+                    let sampler = context.create_sampler(gpu::SamplerDesc {
+                        name: "stress",
+                        ..Default::default()
+                    });
+                    context.destroy_sampler(sampler);
+                    context.destroy_texture_view(view);
+                    context.destroy_texture(texture);
+                    context.destroy_buffer(buffer);
+                }
+            }));
+        }
+
+        // Keep recording and submitting on this thread meanwhile
+        let src = context.create_buffer(gpu::BufferDesc {
+            name: "stress-src",
+            size: 16,
+            memory: gpu::Memory::Shared,
+        });
+        let dst = context.create_buffer(gpu::BufferDesc {
+            name: "stress-dst",
+            size: 16,
+            memory: gpu::Memory::Shared,
+        });
+        let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+            name: "stress",
+            buffer_count: 2,
+        });
+        let mut frame = 0u32;
+        while !workers.iter().all(|worker| worker.is_finished()) {
+            unsafe {
+                slice::from_raw_parts_mut(src.data() as *mut u32, 4).fill(frame);
+            }
+            context.sync_buffer(src);
+            command_encoder.start();
+            if let mut transfer = command_encoder.transfer("stress") {
+                transfer.copy_buffer_to_buffer(src.into(), dst.into(), 16);
+            }
+            let sync_point = context.submit(&mut command_encoder);
+            assert!(context.wait_for(&sync_point, 2000).unwrap());
+            let actual = unsafe { slice::from_raw_parts(dst.data() as *const u32, 4) };
+            assert_eq!(actual, [frame; 4]);
+            frame += 1;
+        }
+
+        context.destroy_command_encoder(&mut command_encoder);
+        context.destroy_buffer(dst);
+        context.destroy_buffer(src);
+    });
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn mapped_ring() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let mut ring = blade_util::MappedRing::<u32>::new(
+        blade_util::MappedRingDescriptor {
+            name: "ring",
+            memory: gpu::Memory::Upload,
+            frame_capacity: 8,
+            frame_count: 2,
+            alignment: 16,
+        },
+        &context,
+    );
+    let readback = context.create_buffer(gpu::BufferDesc {
+        name: "ring-readback",
+        size: 32,
+        memory: gpu::Memory::Shared,
+    });
+    let mut encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "ring",
+        buffer_count: 1,
+    });
+
+    for frame in 0..3u32 {
+        assert_eq!(ring.frame().offset, (frame as u64 % 2) * 32);
+        let first = ring.push(&[frame, frame + 1]);
+        // The next slice starts at the alignment
+        let second = ring.push(&[frame + 2]);
+        assert_eq!(second.offset - first.offset, 16);
+        assert_eq!(ring.remaining(), 3);
+
+        encoder.start();
+        if let mut transfer = encoder.transfer("readback") {
+            transfer.copy_buffer_to_buffer(ring.frame(), readback.into(), 32);
+        }
+        let sync_point = context.submit(&mut encoder);
+        ring.advance(&sync_point, &context);
+        assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+        assert_eq!(readback.read_slice::<u32>(0, 2), [frame, frame + 1]);
+        assert_eq!(readback.read_slice::<u32>(4, 1), [frame + 2]);
+    }
+
+    readback.write_slice(6, &[7u32, 8]);
+    assert_eq!(readback.read_slice::<u32>(6, 2), [7, 8]);
+    let result = std::panic::catch_unwind(|| readback.write_slice(7, &[9u32, 10]));
+    assert!(result.is_err(), "Out of bounds write was not caught");
+
+    context.destroy_command_encoder(&mut encoder);
+    context.destroy_buffer(readback);
+    ring.destroy(&context);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn gpu_vec_growth() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let mut belt = blade_util::BufferBelt::new(blade_util::BufferBeltDescriptor {
+        memory: gpu::Memory::Upload,
+        min_chunk_size: 0x100,
+        alignment: 4,
+    });
+    let mut vec = blade_util::GpuVec::<u32>::new("gpu-vec", 4, &context);
+    let readback = context.create_buffer(gpu::BufferDesc {
+        name: "gpu-vec-readback",
+        size: 64,
+        memory: gpu::Memory::Shared,
+    });
+    let mut encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "gpu-vec",
+        buffer_count: 1,
+    });
+
+    let mut check = |vec: &mut blade_util::GpuVec<u32>| {
+        encoder.start();
+        vec.upload(&mut encoder, &mut belt, &context);
+        if let mut transfer = encoder.transfer("readback") {
+            let size = (vec.len() * 4) as u64;
+            transfer.copy_buffer_to_buffer(vec.piece(), readback.into(), size);
+        }
+        let sync_point = context.submit(&mut encoder);
+        belt.flush(&sync_point);
+        vec.flush(&sync_point);
+        assert!(context.wait_for(&sync_point, 2000).unwrap());
+        assert_eq!(readback.read_slice::<u32>(0, vec.len()), vec.as_slice());
+    };
+
+    vec.extend_from_slice(&[1, 2, 3]);
+    check(&mut vec);
+    let first_buffer = vec.buffer();
+
+    // Only the changed element and the appended ones are uploaded
+    vec.set(1, 20);
+    vec.extend_from_slice(&[4, 5, 6, 7, 8, 9, 10]);
+    check(&mut vec);
+    assert_eq!(vec.len(), 10);
+    assert_eq!(vec.capacity(), 16);
+    assert_ne!(vec.buffer(), first_buffer);
+
+    vec.truncate(2);
+    vec.push(30);
+    check(&mut vec);
+    assert_eq!(vec.as_slice(), [1, 20, 30]);
+
+    context.destroy_command_encoder(&mut encoder);
+    context.destroy_buffer(readback);
+    vec.destroy(&context);
+    belt.destroy(&context);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn frame_graph_culling_and_aliasing() {
+    use blade_util::TextureAccess as Ta;
+
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let size = gpu::Extent {
+        width: 4,
+        height: 4,
+        depth: 1,
+    };
+    let upload = context.create_buffer(gpu::BufferDesc {
+        name: "graph-upload",
+        size: 16 * 4,
+        memory: gpu::Memory::Shared,
+    });
+    let readback = context.create_buffer(gpu::BufferDesc {
+        name: "graph-readback",
+        size: 16 * 4,
+        memory: gpu::Memory::Shared,
+    });
+    let texels = (0..16u32).map(|i| i * 0x01020304).collect::<Vec<_>>();
+    unsafe {
+        slice::from_raw_parts_mut(upload.data() as *mut u32, 16).copy_from_slice(&texels);
+    }
+
+    let mut graph = blade_util::FrameGraph::new();
+    let mut encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "graph",
+        buffer_count: 1,
+    });
+    for frame in 0..2 {
+        unsafe {
+            slice::from_raw_parts_mut(readback.data() as *mut u32, 16).fill(0);
+        }
+        let mut builder = blade_util::FrameGraphBuilder::new();
+        let desc = blade_util::TransientTextureDesc {
+            name: "graph-transient",
+            format: gpu::TextureFormat::Rgba8Unorm,
+            size,
+        };
+        let first = builder.create_texture(desc);
+        let second = builder.create_texture(desc);
+        let unused = builder.create_texture(desc);
+        let last = builder.create_texture(desc);
+        let copy = move |src, dst| {
+            move |encoder: &mut gpu::CommandEncoder, res: &blade_util::PassResources| {
+                let mut transfer = encoder.transfer("copy");
+                transfer.copy_texture_to_texture(
+                    res.texture(src).into(),
+                    res.texture(dst).into(),
+                    size,
+                );
+            }
+        };
+        builder
+            .add_pass("upload")
+            .write(first, Ta::Copy)
+            .record(move |encoder, res| {
+                let mut transfer = encoder.transfer("upload");
+                transfer.copy_buffer_to_texture(
+                    upload.into(),
+                    4 * 4,
+                    res.texture(first).into(),
+                    size,
+                );
+            });
+        builder
+            .add_pass("first-to-second")
+            .read(first, Ta::Copy)
+            .write(second, Ta::Copy)
+            .record(copy(first, second));
+        builder
+            .add_pass("unused")
+            .read(first, Ta::Copy)
+            .write(unused, Ta::Copy)
+            .record(|_, _| panic!("Unused pass is recorded"));
+        // Reuses the texture of the first one, which is no longer needed
+        builder
+            .add_pass("second-to-last")
+            .read(second, Ta::Copy)
+            .write(last, Ta::Copy)
+            .record(copy(second, last));
+        builder
+            .add_pass("readback")
+            .read(last, Ta::Copy)
+            .record(move |encoder, res| {
+                let mut transfer = encoder.transfer("readback");
+                transfer.copy_texture_to_buffer(
+                    res.texture(last).into(),
+                    readback.into(),
+                    4 * 4,
+                    size,
+                );
+            });
+
+        encoder.start();
+        graph.execute(builder, &mut encoder, &context);
+        let sync_point = context.submit(&mut encoder);
+        graph.flush(&sync_point);
+        assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+        let actual = unsafe { slice::from_raw_parts(readback.data() as *const u32, 16) };
+        assert_eq!(actual, &texels[..]);
+        assert_eq!(
+            graph.stats(),
+            blade_util::FrameGraphStats {
+                pass_count: 4,
+                culled_pass_count: 1,
+                transient_count: 3,
+                texture_count: 2,
+                created_texture_count: if frame == 0 { 2 } else { 0 },
+            }
+        );
+    }
+
+    context.destroy_command_encoder(&mut encoder);
+    context.destroy_buffer(upload);
+    context.destroy_buffer(readback);
+    graph.destroy(&context);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn deferred_destruction() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/dispatch.wgsl"),
+        naga_module: None,
+    });
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "deferred",
+        buffer_count: 2,
+    });
+
+    // Replace every resource on each frame without waiting for the GPU
+    const FRAME_COUNT: u32 = 100;
+    let mut last_sync_point = None::<gpu::SyncPoint>;
+    let mut last_output = None;
+    for frame in 0..FRAME_COUNT {
+        let [input, output] = ["deferred-input", "deferred-output"].map(|name| {
+            context.create_buffer(gpu::BufferDesc {
+                name,
+                size: 16,
+                memory: gpu::Memory::Shared,
+            })
+        });
+        unsafe {
+            slice::from_raw_parts_mut(input.data() as *mut u32, 4).fill(frame);
+        }
+        context.sync_buffer(input);
+        let texture = context.create_texture(gpu::TextureDesc {
+            name: "deferred-texture",
+            format: gpu::TextureFormat::Rgba8Unorm,
+            size: gpu::Extent {
+                width: 4,
+                height: 4,
+                depth: 1,
+            },
+            array_layer_count: 1,
+            mip_level_count: 1,
+            dimension: gpu::TextureDimension::D2,
+            usage: gpu::TextureUsage::COPY,
+            sample_count: 1,
+            external: None,
+        });
+        let pipeline = context.create_compute_pipeline(gpu::ComputePipelineDesc {
+            name: "deferred",
+            data_layouts: &[&common::DispatchGlobals::layout()],
+            compute: shader.at("main"),
+        });
+
+        command_encoder.start();
+        command_encoder.init_texture(texture);
+        if let mut compute = command_encoder.compute("dispatch")
+            && let mut pass = compute.with(&pipeline)
+        {
+            pass.bind(
+                0,
+                &common::DispatchGlobals {
+                    input: input.into(),
+                    output: output.into(),
+                },
+            );
+            pass.dispatch([1, 1, 1]);
+        }
+        let sync_point = context.submit(&mut command_encoder);
+        context.destroy_buffer_after(input, &sync_point);
+        context.destroy_texture_after(texture, &sync_point);
+        context.destroy_compute_pipeline_after(pipeline, &sync_point);
+        if let Some(output) = last_output.replace(output) {
+            context.destroy_buffer_after(output, &sync_point);
+        }
+        // Only one frame is in flight at a time
+        if let Some(sp) = last_sync_point.replace(sync_point) {
+            assert!(context.wait_for(&sp, 2000).unwrap());
+        }
+        assert!(context.pending_destruction_count() <= 8);
+    }
+
+    let sync_point = last_sync_point.unwrap();
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+    assert_eq!(context.pending_destruction_count(), 0);
+    let output = last_output.unwrap();
+    let actual = unsafe { slice::from_raw_parts(output.data() as *const u32, 4) };
+    let expected = 2 * (FRAME_COUNT - 1) + 1;
+    assert_eq!(actual, [expected; 4]);
+
+    context.destroy_buffer(output);
+    context.destroy_command_encoder(&mut command_encoder);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn fallible_resource_creation() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let texture_desc = |format, size, usage| gpu::TextureDesc {
+        name: "fallible",
+        format,
+        size,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        dimension: gpu::TextureDimension::D2,
+        usage,
+        sample_count: 1,
+        external: None,
+    };
+    let small = gpu::Extent {
+        width: 4,
+        height: 4,
+        depth: 1,
+    };
+
+    let huge = gpu::Extent {
+        width: 1 << 20,
+        height: 4,
+        depth: 1,
+    };
+    match context.try_create_texture(texture_desc(
+        gpu::TextureFormat::Rgba8Unorm,
+        huge,
+        gpu::TextureUsage::RESOURCE,
+    )) {
+        Err(gpu::ResourceError::TooLarge { limit }) => assert!(limit < huge.width as u64),
+        other => panic!("Unexpected result {other:?}"),
+    }
+
+    let format = gpu::TextureFormat::Bc1Unorm;
+    let supported = context.supported_texture_usage(format);
+    if !supported.contains(gpu::TextureUsage::STORAGE) {
+        let result =
+            context.try_create_texture(texture_desc(format, small, gpu::TextureUsage::STORAGE));
+        assert!(matches!(
+            result,
+            Err(gpu::ResourceError::UnsupportedUsage(_) | gpu::ResourceError::UnsupportedFormat(_))
+        ));
+    }
+
+    let texture = context
+        .try_create_texture(texture_desc(
+            gpu::TextureFormat::Rgba8Unorm,
+            small,
+            gpu::TextureUsage::RESOURCE | gpu::TextureUsage::COPY,
+        ))
+        .unwrap();
+    context.destroy_texture(texture);
+
+    let buffer = context
+        .try_create_buffer(gpu::BufferDesc {
+            name: "fallible",
+            size: 16,
+            memory: gpu::Memory::Device,
+        })
+        .unwrap();
+    context.destroy_buffer(buffer);
+}
+
+#[cfg(not(gles))]
+#[test]
+#[ignore = "requires a working GPU context"]
+fn sparse_texture_residency() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let tile = match context.sparse_tile_extent(format) {
+        Some(tile) if context.capabilities().sparse_tile_size != 0 => tile,
+        _ => {
+            println!("Sparse textures are not supported, skipping");
+            return;
+        }
+    };
+    assert!(
+        context
+            .supported_texture_usage(format)
+            .contains(gpu::TextureUsage::SPARSE)
+    );
+
+    // Only the first of the 2x2 tiles is ever made resident
+    let texture = context.create_texture(gpu::TextureDesc {
+        name: "sparse",
+        format,
+        size: gpu::Extent {
+            width: tile.width * 2,
+            height: tile.height * 2,
+            depth: 1,
+        },
+        array_layer_count: 1,
+        mip_level_count: 1,
+        dimension: gpu::TextureDimension::D2,
+        usage: gpu::TextureUsage::SPARSE | gpu::TextureUsage::COPY | gpu::TextureUsage::RESOURCE,
+        sample_count: 1,
+        external: None,
+    });
+    let region = gpu::TextureRegion {
+        mip_level: 0,
+        array_layer: 0,
+        origin: [0; 3],
+        size: tile,
+    };
+    context
+        .update_texture_residency(texture, &[region], gpu::Residency::Bind)
+        .unwrap();
+    // Binding an already resident tile is a no-op
+    context
+        .update_texture_residency(texture, &[region], gpu::Residency::Bind)
+        .unwrap();
+
+    let texel_count = (tile.width * tile.height) as usize;
+    let buffer = context.create_buffer(gpu::BufferDesc {
+        name: "sparse-data",
+        size: texel_count as u64 * 4,
+        memory: gpu::Memory::Shared,
+    });
+    let texels = unsafe { slice::from_raw_parts_mut(buffer.data() as *mut [u8; 4], texel_count) };
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = [i as u8, (i >> 8) as u8, 0x5A, 0xFF];
+    }
+    let expected = texels.to_vec();
+
+    let mut encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "sparse",
+        buffer_count: 1,
+    });
+    encoder.start();
+    encoder.init_texture(texture);
+    if let mut transfer = encoder.transfer("upload") {
+        transfer.copy_buffer_to_texture(buffer.into(), tile.width * 4, texture.into(), tile);
+    }
+    if let mut transfer = encoder.transfer("clear") {
+        transfer.fill_buffer(buffer.into(), texel_count as u64 * 4, 0);
+    }
+    if let mut transfer = encoder.transfer("readback") {
+        transfer.copy_texture_to_buffer(texture.into(), buffer.into(), tile.width * 4, tile);
+    }
+    let sync_point = context.submit(&mut encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    let actual = unsafe { slice::from_raw_parts(buffer.data() as *const [u8; 4], texel_count) };
+    assert_eq!(actual, &expected[..]);
+
+    context
+        .update_texture_residency(texture, &[region], gpu::Residency::Unbind)
+        .unwrap();
+    context.destroy_command_encoder(&mut encoder);
+    context.destroy_buffer(buffer);
+    context.destroy_texture(texture);
+}
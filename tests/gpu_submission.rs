@@ -0,0 +1,298 @@
+//! Command encoders and submission.
+#![allow(irrefutable_let_patterns)]
+
+use blade_graphics as gpu;
+use std::{alloc, cell::Cell, slice};
+
+#[allow(dead_code)]
+mod common;
+
+use common::snapshot;
+
+/// Allocator that counts the allocations of the threads that opted in.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATION_COUNT: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+fn count_allocation() {
+    let _ = ALLOCATION_COUNT.try_with(|count| {
+        if let Some(value) = count.get() {
+            count.set(Some(value + 1));
+        }
+    });
+}
+
+unsafe impl alloc::GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: alloc::Layout) -> *mut u8 {
+        count_allocation();
+        unsafe { alloc::System.alloc(layout) }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: alloc::Layout) {
+        unsafe { alloc::System.dealloc(ptr, layout) }
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: alloc::Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        unsafe { alloc::System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Count the allocations made by the current thread while running `fun`.
+fn count_allocations(fun: impl FnOnce()) -> usize {
+    ALLOCATION_COUNT.with(|count| count.set(Some(0)));
+    fun();
+    ALLOCATION_COUNT.with(|count| count.take()).unwrap()
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn batched_submission() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let buffers = ["batch-first", "batch-second"].map(|name| {
+        context.create_buffer(gpu::BufferDesc {
+            name,
+            size: 16,
+            memory: gpu::Memory::Shared,
+        })
+    });
+    let mut encoders = ["batch-fill", "batch-copy"].map(|name| {
+        context.create_command_encoder(gpu::CommandEncoderDesc {
+            name,
+            buffer_count: 1,
+        })
+    });
+
+    // Record the encoders on separate threads, the second one using the results of the first
+    std::thread::scope(|scope| {
+        let [ref mut fill, ref mut copy] = encoders;
+        scope.spawn(|| {
+            fill.start();
+            if let mut transfer = fill.transfer("fill") {
+                transfer.fill_buffer(buffers[0].into(), 16, 0x5A);
+            }
+        });
+        scope.spawn(|| {
+            copy.start();
+            if let mut transfer = copy.transfer("copy") {
+                transfer.copy_buffer_to_buffer(buffers[0].into(), buffers[1].into(), 16);
+            }
+        });
+    });
+    let [ref mut fill, ref mut copy] = encoders;
+    let sync_point = context.submit_batch(&mut [fill, copy]);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    let actual = unsafe { slice::from_raw_parts(buffers[1].data(), 16) };
+    assert_eq!(actual, [0x5A; 16]);
+
+    for mut encoder in encoders {
+        context.destroy_command_encoder(&mut encoder);
+    }
+    for buffer in buffers {
+        context.destroy_buffer(buffer);
+    }
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn reusable_command_encoder() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    if !context.capabilities().reusable_command_encoders {
+        println!("Skipping: reusable command encoders not supported");
+        return;
+    }
+    let buffers = ["reuse-src", "reuse-dst"].map(|name| {
+        context.create_buffer(gpu::BufferDesc {
+            name,
+            size: 16,
+            memory: gpu::Memory::Shared,
+        })
+    });
+    let mut encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "reuse",
+        buffer_count: 1,
+    });
+
+    // Record the copy once, and submit it with different source contents
+    encoder.start_reusable();
+    if let mut transfer = encoder.transfer("copy") {
+        transfer.copy_buffer_to_buffer(buffers[0].into(), buffers[1].into(), 16);
+    }
+    for value in [1u8, 2, 3] {
+        unsafe { slice::from_raw_parts_mut(buffers[0].data(), 16).fill(value) };
+        let sync_point = context.submit(&mut encoder);
+        assert!(context.wait_for(&sync_point, 2000).unwrap());
+        let actual = unsafe { slice::from_raw_parts(buffers[1].data(), 16) };
+        assert_eq!(actual, [value; 16]);
+    }
+
+    encoder.invalidate();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        context.submit(&mut encoder);
+    }));
+    assert!(result.is_err(), "Invalidated encoder was submitted");
+
+    // Re-recording makes the encoder usable again
+    encoder.start();
+    if let mut transfer = encoder.transfer("fill") {
+        transfer.fill_buffer(buffers[1].into(), 16, 0x5A);
+    }
+    let sync_point = context.submit(&mut encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+    let actual = unsafe { slice::from_raw_parts(buffers[1].data(), 16) };
+    assert_eq!(actual, [0x5A; 16]);
+
+    context.destroy_command_encoder(&mut encoder);
+    for buffer in buffers {
+        context.destroy_buffer(buffer);
+    }
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn steady_state_encoder_allocations() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut pipeline = blade_particle::ParticlePipeline::new(
+        &context,
+        blade_particle::PipelineDesc {
+            name: "steady particle",
+            draw_format: format,
+            depth_format: None,
+            sample_count: 1,
+        },
+    );
+    let effect = blade_particle::ParticleEffect {
+        capacity: 1000,
+        emitter: blade_particle::Emitter {
+            rate: 1000.0,
+            burst_count: 0,
+            shape: blade_particle::EmitterShape::Point,
+            cone_angle: std::f32::consts::PI,
+        },
+        particle: blade_particle::ParticleConfig {
+            life: [0.5, 1.0],
+            speed: [10.0, 50.0],
+            scale: [1.0, 5.0],
+            color: blade_particle::ColorConfig::Solid([255, 255, 255, 255]),
+        },
+    };
+    let mut particle_system = pipeline.create_system(&context, "steady particle", &effect);
+    let camera = blade_particle::CameraParams {
+        view_proj: glam::Mat4::orthographic_rh(-100.0, 100.0, -100.0, 100.0, -100.0, 100.0)
+            .to_cols_array(),
+        camera_right: [1.0, 0.0, 0.0, 0.0],
+        camera_up: [0.0, 1.0, 0.0, 0.0],
+    };
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "steady-particle",
+        buffer_count: 1,
+    });
+    command_encoder.start();
+    command_encoder.init_texture(target.texture);
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    let mut run_frame = || {
+        command_encoder.start();
+        particle_system.update(&pipeline, &mut command_encoder, 0.016);
+        if let mut pass = command_encoder.render(
+            "draw particles",
+            gpu::RenderTargetSet {
+                colors: &[gpu::RenderTarget {
+                    view: target.view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                    finish_op: gpu::FinishOp::Store,
+                }],
+                depth_stencil: None,
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        ) {
+            particle_system.draw(&pipeline, &mut pass, &camera);
+        }
+        let sync_point = context.submit(&mut command_encoder);
+        assert!(context.wait_for(&sync_point, 2000).unwrap());
+    };
+
+    // Let the internal containers grow to their working size
+    for _ in 0..5 {
+        run_frame();
+    }
+    const FRAME_COUNT: usize = 20;
+    let allocations = count_allocations(|| {
+        for _ in 0..FRAME_COUNT {
+            run_frame();
+        }
+    });
+    println!("{allocations} allocations in {FRAME_COUNT} frames");
+    assert!(
+        allocations <= FRAME_COUNT,
+        "Too many allocations in the steady state: {allocations}"
+    );
+    let stats = command_encoder.memory_stats();
+    assert_ne!(stats.retained_bytes, 0);
+    assert!(stats.peak_retained_bytes >= stats.retained_bytes);
+
+    particle_system.destroy(&context);
+    pipeline.destroy(&context);
+    context.destroy_command_encoder(&mut command_encoder);
+    target.destroy(&context);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn batched_submission_with_empty_encoder() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let buffers = ["batch-source", "batch-copy"].map(|name| {
+        context.create_buffer(gpu::BufferDesc {
+            name,
+            size: 16,
+            memory: gpu::Memory::Shared,
+        })
+    });
+    let mut encoders = ["batch-fill", "batch-empty", "batch-copy", "batch-refill"].map(|name| {
+        context.create_command_encoder(gpu::CommandEncoderDesc {
+            name,
+            buffer_count: 1,
+        })
+    });
+    for encoder in encoders.iter_mut() {
+        encoder.start();
+    }
+    let [ref mut fill, ref mut empty, ref mut copy, ref mut refill] = encoders;
+    if let mut transfer = fill.transfer("fill") {
+        transfer.fill_buffer(buffers[0].into(), 16, 0x5A);
+    }
+    if let mut transfer = copy.transfer("copy") {
+        transfer.copy_buffer_to_buffer(buffers[0].into(), buffers[1].into(), 16);
+    }
+    if let mut transfer = refill.transfer("refill") {
+        transfer.fill_buffer(buffers[0].into(), 16, 0x33);
+    }
+    // The copy sees the first fill through the empty encoder, but not the last one
+    let sync_point = context.submit_batch(&mut [fill, empty, copy, refill]);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    let source = unsafe { slice::from_raw_parts(buffers[0].data(), 16) };
+    assert_eq!(source, [0x33; 16]);
+    let copied = unsafe { slice::from_raw_parts(buffers[1].data(), 16) };
+    assert_eq!(copied, [0x5A; 16]);
+
+    for mut encoder in encoders {
+        context.destroy_command_encoder(&mut encoder);
+    }
+    for buffer in buffers {
+        context.destroy_buffer(buffer);
+    }
+}
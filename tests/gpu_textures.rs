@@ -0,0 +1,704 @@
+//! Texture transfers, formats, and samplers.
+#![allow(irrefutable_let_patterns)]
+
+use blade_graphics as gpu;
+use blade_graphics::ShaderData;
+use std::slice;
+
+#[allow(dead_code)]
+mod common;
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn texture_blit() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let make_texture = |name, format, size| {
+        context.create_texture(gpu::TextureDesc {
+            name,
+            format,
+            size: gpu::Extent {
+                width: size,
+                height: size,
+                depth: 1,
+            },
+            array_layer_count: 1,
+            mip_level_count: 1,
+            dimension: gpu::TextureDimension::D2,
+            usage: gpu::TextureUsage::COPY
+                | gpu::TextureUsage::RESOURCE
+                | gpu::TextureUsage::TARGET,
+            sample_count: 1,
+            external: None,
+        })
+    };
+    let src = make_texture("blit-src", gpu::TextureFormat::Rgba8Unorm, 4);
+    let dst = make_texture("blit-dst", gpu::TextureFormat::Bgra8Unorm, 2);
+    let buffer = context.create_buffer(gpu::BufferDesc {
+        name: "blit-data",
+        size: 4 * 4 * 4,
+        memory: gpu::Memory::Shared,
+    });
+
+    // Every quadrant of the source has a solid color, which any filter keeps
+    let colors = [
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [0, 0, 255, 255],
+        [255, 255, 0, 255],
+    ];
+    let texels = unsafe { slice::from_raw_parts_mut(buffer.data() as *mut [u8; 4], 16) };
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        *texel = colors[(y / 2) * 2 + x / 2];
+    }
+
+    let mut encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "blit",
+        buffer_count: 1,
+    });
+    encoder.start();
+    encoder.init_texture(src);
+    encoder.init_texture(dst);
+    let full = |size| gpu::Extent {
+        width: size,
+        height: size,
+        depth: 1,
+    };
+    // Separate passes, to have barriers between the operations
+    if let mut transfer = encoder.transfer("upload") {
+        transfer.copy_buffer_to_texture(buffer.into(), 4 * 4, src.into(), full(4));
+    }
+    if let mut transfer = encoder.transfer("blit") {
+        transfer.blit(
+            src.into(),
+            full(4),
+            dst.into(),
+            full(2),
+            gpu::FilterMode::Linear,
+        );
+    }
+    if let mut transfer = encoder.transfer("readback") {
+        transfer.copy_texture_to_buffer(dst.into(), buffer.into(), 2 * 4, full(2));
+    }
+    let sync_point = context.submit(&mut encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    let actual = unsafe { slice::from_raw_parts(buffer.data() as *const [u8; 4], 4) };
+    let expected = colors.map(|[r, g, b, a]| [b, g, r, a]);
+    assert_eq!(actual, expected);
+
+    context.destroy_command_encoder(&mut encoder);
+    context.destroy_buffer(buffer);
+    context.destroy_texture(src);
+    context.destroy_texture(dst);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn host_texture_write() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    if !context
+        .supported_texture_usage(format)
+        .contains(gpu::TextureUsage::HOST_WRITE)
+    {
+        println!("Skipping: host texture writes are not supported");
+        return;
+    }
+
+    let texture = context.create_texture(gpu::TextureDesc {
+        name: "host-write",
+        format,
+        size: gpu::Extent {
+            width: 4,
+            height: 4,
+            depth: 1,
+        },
+        array_layer_count: 1,
+        mip_level_count: 2,
+        dimension: gpu::TextureDimension::D2,
+        usage: gpu::TextureUsage::COPY | gpu::TextureUsage::HOST_WRITE,
+        sample_count: 1,
+        external: None,
+    });
+    let texels = (0..16u8)
+        .map(|i| [i, 2 * i, 3 * i, 255])
+        .collect::<Vec<_>>();
+    context.write_texture(
+        texture.into(),
+        bytemuck::cast_slice(&texels),
+        4 * 4,
+        gpu::Extent {
+            width: 4,
+            height: 4,
+            depth: 1,
+        },
+    );
+    // Write a part of the second mip, with padded rows
+    let padded = [[7u8; 4], [8; 4], [0; 4], [9; 4], [10; 4], [0; 4]];
+    context.write_texture(
+        gpu::TexturePiece {
+            texture,
+            mip_level: 1,
+            array_layer: 0,
+            origin: [0; 3],
+        },
+        bytemuck::cast_slice(&padded),
+        3 * 4,
+        gpu::Extent {
+            width: 2,
+            height: 2,
+            depth: 1,
+        },
+    );
+
+    let buffer = context.create_buffer(gpu::BufferDesc {
+        name: "host-write-readback",
+        size: (16 + 4) * 4,
+        memory: gpu::Memory::Shared,
+    });
+    let mut encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "host-write",
+        buffer_count: 1,
+    });
+    encoder.start();
+    if let mut transfer = encoder.transfer("readback") {
+        transfer.copy_texture_to_buffer(
+            texture.into(),
+            buffer.into(),
+            4 * 4,
+            gpu::Extent {
+                width: 4,
+                height: 4,
+                depth: 1,
+            },
+        );
+        transfer.copy_texture_to_buffer(
+            gpu::TexturePiece {
+                texture,
+                mip_level: 1,
+                array_layer: 0,
+                origin: [0; 3],
+            },
+            buffer.at(16 * 4),
+            2 * 4,
+            gpu::Extent {
+                width: 2,
+                height: 2,
+                depth: 1,
+            },
+        );
+    }
+    let sync_point = context.submit(&mut encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    let actual = unsafe { slice::from_raw_parts(buffer.data() as *const [u8; 4], 20) };
+    assert_eq!(actual[..16], texels[..]);
+    assert_eq!(actual[16..], [[7; 4], [8; 4], [9; 4], [10; 4]]);
+
+    context.destroy_command_encoder(&mut encoder);
+    context.destroy_buffer(buffer);
+    context.destroy_texture(texture);
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn texture_region_validation() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let extent = |width, height| gpu::Extent {
+        width,
+        height,
+        depth: 1,
+    };
+    assert_eq!(extent(3, 8).min(extent(5, 2)), extent(3, 2));
+    let bc1 = gpu::TextureFormat::Bc1Unorm;
+    assert!(extent(8, 4).is_block_aligned(bc1));
+    assert!(!extent(6, 4).is_block_aligned(bc1));
+    assert!(extent(6, 4).is_block_aligned(gpu::TextureFormat::Rgba8Unorm));
+
+    let format = if context.supported_texture_usage(bc1).is_empty() {
+        println!("Skipping block checks: BC1 is not supported");
+        None
+    } else {
+        Some(bc1)
+    };
+    let texture = context.create_texture(gpu::TextureDesc {
+        name: "regions",
+        format: format.unwrap_or(gpu::TextureFormat::Rgba8Unorm),
+        size: extent(18, 16),
+        array_layer_count: 1,
+        mip_level_count: 2,
+        dimension: gpu::TextureDimension::D2,
+        usage: gpu::TextureUsage::COPY,
+        sample_count: 1,
+        external: None,
+    });
+    let base = gpu::TexturePiece::from(texture);
+    let mip = gpu::TexturePiece {
+        mip_level: 1,
+        ..base
+    };
+    assert_eq!(mip.mip_size(), extent(9, 8));
+
+    let piece = base.subregion([4, 8, 0], extent(8, 8)).unwrap();
+    assert_eq!(piece.origin, [4, 8, 0]);
+    assert_eq!(
+        piece.subregion([8, 0, 0], extent(8, 4)).map(|_| ()),
+        Err(gpu::RegionError::OutOfBounds {
+            origin: [12, 8, 0],
+            extent: extent(8, 4),
+            mip_size: extent(18, 16),
+        })
+    );
+    assert!(base.subregion([u32::MAX, 0, 0], extent(1, 1)).is_err());
+    assert!(mip.validate(extent(9, 8)).is_ok());
+    assert!(mip.validate(extent(9, 9)).is_err());
+
+    if format.is_some() {
+        // Partial blocks are fine at the edge only
+        assert!(base.validate(extent(18, 16)).is_ok());
+        assert!(base.subregion([16, 12, 0], extent(2, 4)).is_ok());
+        assert!(mip.subregion([4, 4, 0], extent(5, 4)).is_ok());
+        assert_eq!(
+            base.subregion([4, 0, 0], extent(6, 4)).map(|_| ()),
+            Err(gpu::RegionError::PartialBlock {
+                origin: [4, 0, 0],
+                extent: extent(6, 4),
+                block_size: (4, 4),
+            })
+        );
+        assert!(base.subregion([2, 0, 0], extent(4, 4)).is_err());
+    }
+
+    context.destroy_texture(texture);
+}
+
+#[derive(blade_macros::ShaderData)]
+struct SrgbGradientData {
+    source: gpu::TextureView,
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Renders a known gradient and compares it against the sRGB transfer function,
+/// which every backend has to follow when decoding and encoding sRGB formats.
+#[test]
+#[ignore = "requires a working GPU context"]
+fn srgb_decode_and_encode() {
+    const WIDTH: u32 = 16;
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let srgb_format = gpu::TextureFormat::Rgba8UnormSrgb;
+    let linear_format = gpu::TextureFormat::Rgba8Unorm;
+    assert!(srgb_format.is_srgb() && !linear_format.is_srgb());
+    if !context
+        .supported_texture_usage(srgb_format)
+        .contains(gpu::TextureUsage::TARGET | gpu::TextureUsage::RESOURCE)
+    {
+        println!("Skipping: sRGB render targets are not supported");
+        return;
+    }
+
+    let size = gpu::Extent {
+        width: WIDTH,
+        height: 1,
+        depth: 1,
+    };
+    let create_texture = |name, format, usage| {
+        let texture = context.create_texture(gpu::TextureDesc {
+            name,
+            format,
+            size,
+            array_layer_count: 1,
+            mip_level_count: 1,
+            dimension: gpu::TextureDimension::D2,
+            usage: usage | gpu::TextureUsage::COPY,
+            sample_count: 1,
+            external: None,
+        });
+        let view = context.create_texture_view(
+            texture,
+            gpu::TextureViewDesc {
+                name,
+                format,
+                dimension: gpu::ViewDimension::D2,
+                subresources: &gpu::TextureSubresources::default(),
+            },
+        );
+        (texture, view)
+    };
+    let (source, source_view) =
+        create_texture("srgb-source", srgb_format, gpu::TextureUsage::RESOURCE);
+    let (decoded, decoded_view) =
+        create_texture("srgb-decoded", linear_format, gpu::TextureUsage::TARGET);
+    let (encoded, encoded_view) =
+        create_texture("srgb-encoded", srgb_format, gpu::TextureUsage::TARGET);
+
+    let row_size = WIDTH * 4;
+    let buffer = context.create_buffer(gpu::BufferDesc {
+        name: "srgb",
+        size: 3 * row_size as u64,
+        memory: gpu::Memory::Shared,
+    });
+    let source_texels = (0..WIDTH)
+        .map(|i| [(i * 17) as u8, 0, 0, 0xFF])
+        .collect::<Vec<_>>();
+    unsafe {
+        slice::from_raw_parts_mut(buffer.data() as *mut [u8; 4], WIDTH as usize)
+            .copy_from_slice(&source_texels);
+    }
+
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: include_str!("shaders/srgb_gradient.wgsl"),
+        naga_module: None,
+    });
+    let layout = <SrgbGradientData as gpu::ShaderData>::layout();
+    let mut pipeline = context.create_render_pipeline(gpu::RenderPipelineDesc {
+        name: "srgb-gradient",
+        data_layouts: &[&layout],
+        vertex: shader.at("vs_main"),
+        vertex_fetches: &[],
+        fragment: Some(shader.at("fs_main")),
+        primitive: gpu::PrimitiveState {
+            topology: gpu::PrimitiveTopology::TriangleStrip,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        color_targets: &[linear_format.into(), srgb_format.into()],
+        multisample_state: gpu::MultisampleState::default(),
+        multiview: None,
+    });
+
+    let mut encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "srgb",
+        buffer_count: 1,
+    });
+    encoder.start();
+    encoder.init_texture(source);
+    encoder.init_texture(decoded);
+    encoder.init_texture(encoded);
+    if let mut transfer = encoder.transfer("upload") {
+        transfer.copy_buffer_to_texture(buffer.into(), row_size, source.into(), size);
+    }
+    if let mut pass = encoder.render(
+        "gradient",
+        gpu::RenderTargetSet {
+            colors: &[
+                gpu::RenderTarget {
+                    view: decoded_view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::TransparentBlack),
+                    finish_op: gpu::FinishOp::Store,
+                },
+                gpu::RenderTarget {
+                    view: encoded_view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::TransparentBlack),
+                    finish_op: gpu::FinishOp::Store,
+                },
+            ],
+            depth_stencil: None,
+            depth_stencil_read_only: gpu::TexelAspects::empty(),
+            multiview: None,
+        },
+    ) {
+        let mut pe = pass.with(&pipeline);
+        pe.bind(
+            0,
+            &SrgbGradientData {
+                source: source_view,
+            },
+        );
+        pe.draw(0, 4, 0, 1);
+    }
+    if let mut transfer = encoder.transfer("readback") {
+        transfer.copy_texture_to_buffer(decoded.into(), buffer.at(row_size as u64), row_size, size);
+        transfer.copy_texture_to_buffer(
+            encoded.into(),
+            buffer.at(2 * row_size as u64),
+            row_size,
+            size,
+        );
+    }
+    let sync_point = context.submit(&mut encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    let results = unsafe {
+        slice::from_raw_parts(
+            (buffer.data() as *const [u8; 4]).add(WIDTH as usize),
+            2 * WIDTH as usize,
+        )
+    };
+    let (actual_decoded, actual_encoded) = results.split_at(WIDTH as usize);
+    for i in 0..WIDTH as usize {
+        let expected = srgb_to_linear(source_texels[i][0] as f32 / 255.0) * 255.0;
+        let actual = actual_decoded[i][0] as f32;
+        assert!(
+            (actual - expected).abs() <= 1.5,
+            "Decoded texel {i} is {actual}, expected {expected}"
+        );
+        let expected = linear_to_srgb(i as f32 / (WIDTH - 1) as f32) * 255.0;
+        let actual = actual_encoded[i][0] as f32;
+        assert!(
+            (actual - expected).abs() <= 1.5,
+            "Encoded texel {i} is {actual}, expected {expected}"
+        );
+    }
+
+    context.destroy_command_encoder(&mut encoder);
+    context.destroy_render_pipeline(&mut pipeline);
+    context.destroy_buffer(buffer);
+    for (texture, view) in [
+        (source, source_view),
+        (decoded, decoded_view),
+        (encoded, encoded_view),
+    ] {
+        context.destroy_texture_view(view);
+        context.destroy_texture(texture);
+    }
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn all_texture_formats() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    for &format in gpu::TextureFormat::ALL {
+        let usage = context.supported_texture_usage(format);
+        if usage.is_empty() {
+            continue;
+        }
+        let (block_width, block_height) = format.block_info().dimensions;
+        let texture = context
+            .try_create_texture(gpu::TextureDesc {
+                name: "format",
+                format,
+                size: gpu::Extent {
+                    width: block_width as u32,
+                    height: block_height as u32,
+                    depth: 1,
+                },
+                array_layer_count: 1,
+                mip_level_count: 1,
+                dimension: gpu::TextureDimension::D2,
+                usage,
+                sample_count: 1,
+                external: None,
+            })
+            .unwrap_or_else(|e| panic!("Unable to create a {format:?} texture: {e}"));
+        assert_eq!(texture.format(), format);
+        let view = context.create_texture_view(
+            texture,
+            gpu::TextureViewDesc {
+                name: "format",
+                format,
+                dimension: gpu::ViewDimension::D2,
+                subresources: &gpu::TextureSubresources::default(),
+            },
+        );
+        context.destroy_texture_view(view);
+        context.destroy_texture(texture);
+    }
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn sampler_deduplication() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let linear = |name| gpu::SamplerDesc {
+        name,
+        address_modes: [gpu::AddressMode::Repeat; 3],
+        mag_filter: gpu::FilterMode::Linear,
+        min_filter: gpu::FilterMode::Linear,
+        ..Default::default()
+    };
+
+    let first = context.create_sampler(linear("first"));
+    let second = context.create_sampler(linear("second"));
+    let nearest = context.create_sampler(gpu::SamplerDesc {
+        name: "nearest",
+        ..Default::default()
+    });
+    assert_eq!(first, second);
+    assert_ne!(first, nearest);
+    let stats = context.sampler_cache_stats();
+    assert_eq!((stats.hits, stats.misses, stats.cached_count), (1, 2, 2));
+
+    // The shared sampler stays alive until both users are done
+    context.destroy_sampler(first);
+    assert_eq!(context.sampler_cache_stats().cached_count, 2);
+    context.destroy_sampler(second);
+    context.destroy_sampler(nearest);
+    assert_eq!(context.sampler_cache_stats().cached_count, 0);
+}
+
+#[derive(blade_macros::ShaderData)]
+struct LodSampleData {
+    mips: gpu::TextureView,
+    max_clamped: gpu::Sampler,
+    min_clamped: gpu::Sampler,
+    biased: gpu::Sampler,
+    output: gpu::BufferPiece,
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn sampler_lod_controls() {
+    let context = unsafe { gpu::Context::init(gpu::ContextDesc::default()).unwrap() };
+    let shader = context.create_shader(gpu::ShaderDesc {
+        source: "var mips: texture_2d<f32>;
+            var max_clamped: sampler;
+            var min_clamped: sampler;
+            var biased: sampler;
+            var<storage, read_write> output: array<f32>;
+            @compute @workgroup_size(1)
+            fn main() {
+                let uv = vec2<f32>(0.5);
+                output[0] = textureSampleLevel(mips, max_clamped, uv, 2.0).r;
+                output[1] = textureSampleLevel(mips, min_clamped, uv, 0.0).r;
+                output[2] = textureSampleLevel(mips, biased, uv, 0.0).r;
+            }",
+        naga_module: None,
+    });
+    let mut pipeline = context.create_compute_pipeline(gpu::ComputePipelineDesc {
+        name: "lod-sample",
+        data_layouts: &[&LodSampleData::layout()],
+        compute: shader.at("main"),
+    });
+
+    // 4x4 texture with 3 mips, each filled with its own value
+    let mip_values = [0x40u8, 0x80, 0xC0];
+    let texture = context.create_texture(gpu::TextureDesc {
+        name: "lod-mips",
+        format: gpu::TextureFormat::Rgba8Unorm,
+        size: gpu::Extent {
+            width: 4,
+            height: 4,
+            depth: 1,
+        },
+        array_layer_count: 1,
+        mip_level_count: 3,
+        dimension: gpu::TextureDimension::D2,
+        usage: gpu::TextureUsage::RESOURCE | gpu::TextureUsage::COPY,
+        sample_count: 1,
+        external: None,
+    });
+    let view = context.create_texture_view(
+        texture,
+        gpu::TextureViewDesc {
+            name: "lod-mips",
+            format: gpu::TextureFormat::Rgba8Unorm,
+            dimension: gpu::ViewDimension::D2,
+            subresources: &gpu::TextureSubresources::default(),
+        },
+    );
+    let texels = [16, 4, 1]
+        .iter()
+        .zip(mip_values)
+        .flat_map(|(&count, value)| vec![[value; 4]; count])
+        .collect::<Vec<_>>();
+    let upload = context.create_buffer(gpu::BufferDesc {
+        name: "lod-upload",
+        size: 4 * texels.len() as u64,
+        memory: gpu::Memory::Shared,
+    });
+    upload.write_slice(0, &texels);
+
+    let max_clamped = context.create_sampler(gpu::SamplerDesc {
+        name: "max-clamped",
+        lod_max_clamp: Some(1.0),
+        ..Default::default()
+    });
+    let min_clamped = context.create_sampler(gpu::SamplerDesc {
+        name: "min-clamped",
+        lod_min_clamp: 1.0,
+        ..Default::default()
+    });
+    let biased = context.create_sampler(gpu::SamplerDesc {
+        name: "biased",
+        lod_bias: 1.0,
+        ..Default::default()
+    });
+    assert_ne!(biased, min_clamped);
+    let output = context.create_buffer(gpu::BufferDesc {
+        name: "lod-output",
+        size: 12,
+        memory: gpu::Memory::Shared,
+    });
+
+    let mut command_encoder = context.create_command_encoder(gpu::CommandEncoderDesc {
+        name: "lod-sample",
+        buffer_count: 1,
+    });
+    command_encoder.start();
+    command_encoder.init_texture(texture);
+    if let mut transfer = command_encoder.transfer("upload") {
+        let mut offset = 0;
+        for mip_level in 0..3 {
+            let size = 4 >> mip_level;
+            transfer.copy_buffer_to_texture(
+                upload.at(offset),
+                4 * size,
+                gpu::TexturePiece {
+                    texture,
+                    mip_level,
+                    array_layer: 0,
+                    origin: [0; 3],
+                },
+                gpu::Extent {
+                    width: size,
+                    height: size,
+                    depth: 1,
+                },
+            );
+            offset += 4 * (size * size) as u64;
+        }
+    }
+    if let mut compute = command_encoder.compute("lod-sample")
+        && let mut pass = compute.with(&pipeline)
+    {
+        pass.bind(
+            0,
+            &LodSampleData {
+                mips: view,
+                max_clamped,
+                min_clamped,
+                biased,
+                output: output.into(),
+            },
+        );
+        pass.dispatch([1, 1, 1]);
+    }
+    let sync_point = context.submit(&mut command_encoder);
+    assert!(context.wait_for(&sync_point, 2000).unwrap());
+
+    let actual = output.read_slice::<f32>(0, 3);
+    let expected = mip_values[1] as f32 / 255.0;
+    assert!((actual[0] - expected).abs() < 1e-3, "max clamp: {actual:?}");
+    assert!((actual[1] - expected).abs() < 1e-3, "min clamp: {actual:?}");
+    if context.capabilities().max_sampler_lod_bias >= 1.0 {
+        assert!((actual[2] - expected).abs() < 1e-3, "bias: {actual:?}");
+    } else {
+        println!("Skipping the LOD bias check: not supported");
+    }
+
+    context.destroy_command_encoder(&mut command_encoder);
+    context.destroy_compute_pipeline(&mut pipeline);
+    context.destroy_buffer(output);
+    context.destroy_sampler(biased);
+    context.destroy_sampler(min_clamped);
+    context.destroy_sampler(max_clamped);
+    context.destroy_buffer(upload);
+    context.destroy_texture_view(view);
+    context.destroy_texture(texture);
+}
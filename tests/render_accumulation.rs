@@ -0,0 +1,539 @@
+//! Progressive accumulation and camera models of the ray tracer.
+#![allow(irrefutable_let_patterns)]
+#![cfg(not(gles))]
+
+use blade_graphics as gpu;
+
+#[allow(dead_code)]
+mod common;
+
+use common::snapshot;
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn accumulation_convergence() {
+    // Mean radiance of the blocks of the two runs has to match within this fraction.
+    const TOLERANCE: f32 = 0.05;
+    const FRAME_COUNT: u32 = 256;
+    const BLOCK: usize = 8;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-accumulation-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // A floor facing up, lit by a point light above it
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 2.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let objects = [blade_render::Object::from(asset_hub.models.insert(floor))];
+    let light = blade_render::Light {
+        kind: blade_render::LightKind::Point,
+        position: [0.5, 1.0, 0.0].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 10.0,
+    };
+
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[light], &context, temp);
+    pacer.end_frame(&context);
+
+    // Looking down at the floor
+    let camera = common::top_down_camera(3.0);
+
+    let first = common::accumulate_hdr(&context, &mut pacer, &mut ray_tracer, &camera, FRAME_COUNT);
+    let second =
+        common::accumulate_hdr(&context, &mut pacer, &mut ray_tracer, &camera, FRAME_COUNT);
+
+    let width = size.width as usize;
+    let block_mean = |pixels: &[f32], bx: usize, by: usize| {
+        let mut sum = 0.0;
+        for y in by * BLOCK..(by + 1) * BLOCK {
+            for x in bx * BLOCK..(bx + 1) * BLOCK {
+                let p = &pixels[(y * width + x) * 4..][..3];
+                sum += p[0] + p[1] + p[2];
+            }
+        }
+        sum / (3 * BLOCK * BLOCK) as f32
+    };
+    let mut max_error = 0.0f32;
+    for by in 0..size.height as usize / BLOCK {
+        for bx in 0..width / BLOCK {
+            let a = block_mean(&first, bx, by);
+            let b = block_mean(&second, bx, by);
+            assert!(a.is_finite() && b.is_finite());
+            max_error = max_error.max((a - b).abs() / a.max(b).max(1e-3));
+        }
+    }
+    println!("Max block error: {max_error}");
+    assert!(
+        max_error < TOLERANCE,
+        "Independent accumulations diverge by {max_error}"
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn ray_config_bounces() {
+    const FRAME_COUNT: u32 = 32;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-bounces-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 32,
+        height: 32,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // A wall on the right side of the floor, reflecting the light back onto it
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 3.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let wall = asset_hub.models.baker.create_model(
+        "wall",
+        vec![common::quad_geometry("wall", 1.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let mut wall_object = blade_render::Object::from(asset_hub.models.insert(wall));
+    // Rotated to face the light
+    wall_object.transform = mint::RowMatrix3x4 {
+        x: [0.0, -1.0, 0.0, 1.0].into(),
+        y: [1.0, 0.0, 0.0, 1.0].into(),
+        z: [0.0, 0.0, 1.0, 0.0].into(),
+    };
+    wall_object.prev_transform = wall_object.transform;
+    let objects = [
+        blade_render::Object::from(asset_hub.models.insert(floor)),
+        wall_object,
+    ];
+    let light = blade_render::Light {
+        kind: blade_render::LightKind::Point,
+        position: [0.0, 0.5, 0.0].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 5.0,
+    };
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[light], &context, temp);
+    pacer.end_frame(&context);
+
+    let camera = common::top_down_camera(3.0);
+    let direct = blade_render::RayConfig {
+        max_bounces: 0,
+        firefly_clamp: 0.0,
+        ..common::dark_ray_config()
+    };
+    let indirect = blade_render::RayConfig {
+        max_bounces: 2,
+        ..direct
+    };
+    let clamped = blade_render::RayConfig {
+        firefly_clamp: 1e-4,
+        ..indirect
+    };
+    // The floor is lit evenly on both sides, unless the wall bounces the light
+    let mut wall_ratio = |ray_config| {
+        let pixels = common::accumulate_hdr_with(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &camera,
+            ray_config,
+            FRAME_COUNT,
+        );
+        assert!(pixels.iter().all(|value| value.is_finite()));
+        let near = common::mean_radiance(&pixels, size, [22, 14, 25, 18]);
+        let far = common::mean_radiance(&pixels, size, [7, 14, 10, 18]);
+        assert!(far > 0.0);
+        near / far
+    };
+    let [direct_ratio, indirect_ratio, clamped_ratio] =
+        [direct, indirect, clamped].map(&mut wall_ratio);
+    println!(
+        "Floor next to the wall relative to the far side: direct {direct_ratio}, \
+        indirect {indirect_ratio}, clamped {clamped_ratio}"
+    );
+    assert!(
+        (0.95..1.05).contains(&direct_ratio),
+        "The direct lighting includes the bounced light"
+    );
+    assert!(
+        indirect_ratio > 1.1,
+        "The bounces don't bring the light from the wall"
+    );
+    assert!(
+        clamped_ratio < 1.05,
+        "The firefly clamp doesn't limit the indirect lighting"
+    );
+
+    // Out of range values are clamped instead of hanging the GPU
+    let extreme = blade_render::RayConfig {
+        max_bounces: u32::MAX,
+        firefly_clamp: f32::NAN,
+        russian_roulette_start: u32::MAX,
+        ..indirect
+    };
+    wall_ratio(extreme);
+
+    // Changing the config restarts the accumulation
+    let mut trace_frame = |ray_config| {
+        let (command_encoder, _) = pacer.begin_frame();
+        ray_tracer.prepare(
+            command_encoder,
+            &camera,
+            blade_render::FrameConfig {
+                accumulate: true,
+                ..Default::default()
+            },
+        );
+        ray_tracer.ray_trace(
+            command_encoder,
+            blade_render::DebugConfig::default(),
+            ray_config,
+        );
+        pacer.end_frame(&context);
+        ray_tracer.accumulated_frames()
+    };
+    assert_eq!(trace_frame(indirect), 1);
+    assert_eq!(trace_frame(indirect), 2);
+    assert_eq!(trace_frame(direct), 1);
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn adaptive_sampling() {
+    const FRAME_COUNT: u32 = 8;
+    const MAX_SAMPLES: u32 = 4;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-adaptive-sampling-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 32,
+        height: 32,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // The right half of the floor is in the shadow of a wide plate above the camera,
+    // and only gets the noisy light from the environment and the bounces.
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 3.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let plate = asset_hub.models.baker.create_model(
+        "plate",
+        vec![common::quad_geometry("plate", 3.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let mut plate_object = blade_render::Object::from(asset_hub.models.insert(plate));
+    plate_object.transform = mint::RowMatrix3x4 {
+        x: [1.0, 0.0, 0.0, 3.2].into(),
+        y: [0.0, -1.0, 0.0, 6.0].into(),
+        z: [0.0, 0.0, -1.0, 0.0].into(),
+    };
+    plate_object.prev_transform = plate_object.transform;
+    let objects = [
+        blade_render::Object::from(asset_hub.models.insert(floor)),
+        plate_object,
+    ];
+    let sun = blade_render::Light {
+        kind: blade_render::LightKind::Directional {
+            angular_radius: 0.0,
+        },
+        position: [0.0; 3].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 5.0,
+    };
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[sun], &context, temp);
+    pacer.end_frame(&context);
+
+    let camera = common::top_down_camera(3.0);
+    let target = snapshot::OffscreenTarget::new(&context, size, gpu::TextureFormat::Rgba8Unorm);
+    // The tile samples of every frame are estimated by the denoiser of the previous one
+    let mut render = |view_mode, max_samples| {
+        let ray_config = blade_render::RayConfig {
+            max_bounces: 2,
+            max_samples,
+            variance_threshold: 0.05,
+            ..blade_helpers::default_ray_config()
+        };
+        let mut pixels = Vec::new();
+        for frame in 0..FRAME_COUNT {
+            pixels = common::render_debug_view(
+                &context,
+                &mut pacer,
+                &mut ray_tracer,
+                &target,
+                &camera,
+                frame == 0,
+                blade_render::DebugConfig {
+                    view_mode,
+                    ..Default::default()
+                },
+                ray_config,
+                blade_render::DenoiserConfig::default(),
+            );
+        }
+        pixels
+    };
+    // Heat of the heatmap colors, growing from blue to red
+    let heat = |pixels: &[u8], rect| {
+        let [r, _, b] = common::mean_color(pixels, size, rect);
+        (r - b) / 255.0
+    };
+    let lit_tiles = [0, 0, 8, 32];
+    let shadow_tiles = [24, 0, 32, 32];
+
+    let uniform = render(blade_render::DebugMode::AdaptiveSamples, 1);
+    assert!(
+        common::is_checkerboard(&uniform),
+        "Tile samples are shown without the adaptive sampling"
+    );
+
+    let adaptive = render(blade_render::DebugMode::AdaptiveSamples, MAX_SAMPLES);
+    let lit = heat(&adaptive, lit_tiles);
+    let shadow = heat(&adaptive, shadow_tiles);
+    println!("Extra samples heat in the lit tiles {lit}, in the shadow {shadow}");
+    assert!(lit < -0.5, "The converged tiles get many extra samples");
+    assert!(
+        shadow > lit,
+        "The noisy tiles don't get more samples than the converged ones"
+    );
+
+    // The ray count is written by the debug builds of the shaders only
+    if cfg!(debug_assertions) {
+        let uniform_rays = heat(&render(blade_render::DebugMode::RayCount, 1), shadow_tiles);
+        let adaptive_rays = heat(
+            &render(blade_render::DebugMode::RayCount, MAX_SAMPLES),
+            shadow_tiles,
+        );
+        println!("Ray count heat in the shadow {uniform_rays}, adaptive {adaptive_rays}");
+        assert!(
+            adaptive_rays > uniform_rays,
+            "No extra rays are traced in the noisy tiles"
+        );
+    }
+    target.destroy(&context);
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn depth_of_field_blurs_edges() {
+    const FRAME_COUNT: u32 = 64;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-dof-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // A lit floor with sharp edges against the black background
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 2.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let objects = [blade_render::Object::from(asset_hub.models.insert(floor))];
+    let light = blade_render::Light {
+        kind: blade_render::LightKind::Point,
+        position: [0.0, 2.0, 0.0].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 10.0,
+    };
+
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[light], &context, temp);
+    pacer.end_frame(&context);
+
+    // Looking down at the floor from far enough to see its edges
+    let pinhole = common::top_down_camera(8.0);
+    // Wide open aperture focused far in front of the floor
+    let defocused = blade_render::Camera {
+        lens: blade_render::Lens {
+            f_stop: 0.1,
+            focus_distance: 1.0,
+            blade_count: 6,
+            blade_rotation: 0.0,
+        },
+        ..pinhole
+    };
+
+    // Largest luminance step between neighbors on the middle row
+    let width = size.width as usize;
+    let max_step = |pixels: &[f32]| {
+        let row = &pixels[width * size.height as usize / 2 * 4..][..width * 4];
+        let luminance = |x: usize| row[x * 4] + row[x * 4 + 1] + row[x * 4 + 2];
+        (1..width)
+            .map(|x| (luminance(x) - luminance(x - 1)).abs())
+            .fold(0.0f32, f32::max)
+    };
+    let sharp =
+        common::accumulate_hdr(&context, &mut pacer, &mut ray_tracer, &pinhole, FRAME_COUNT);
+    let blurred = common::accumulate_hdr(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &defocused,
+        FRAME_COUNT,
+    );
+    let sharp_step = max_step(&sharp);
+    let blurred_step = max_step(&blurred);
+    println!("Max step: pinhole {sharp_step}, defocused {blurred_step}");
+    assert!(sharp_step > 0.0);
+    assert!(
+        blurred_step < 0.5 * sharp_step,
+        "The defocused edges are too sharp: {blurred_step} vs {sharp_step}"
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn orthographic_projection() {
+    const FRAME_COUNT: u32 = 4;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-ortho-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // A lit floor against the black background
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 2.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let objects = [blade_render::Object::from(asset_hub.models.insert(floor))];
+    let light = blade_render::Light {
+        kind: blade_render::LightKind::Point,
+        position: [0.0, 2.0, 0.0].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 10.0,
+    };
+
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[light], &context, temp);
+    pacer.end_frame(&context);
+
+    // Looking down at the floor, with the view twice as wide as the floor
+    let orthographic = blade_render::Camera {
+        projection: blade_render::Projection::Orthographic {
+            width: 8.0,
+            height: 8.0,
+        },
+        ..common::top_down_camera(8.0)
+    };
+    // The same projection, given by the raw matrix
+    let custom = blade_render::Camera {
+        projection: blade_render::Projection::Matrix(
+            glam::Mat4::orthographic_rh(-4.0, 4.0, -4.0, 4.0, 0.01, 100.0).into(),
+        ),
+        ..orthographic
+    };
+
+    let width = size.width as usize;
+    for camera in [orthographic, custom] {
+        let pixels =
+            common::accumulate_hdr(&context, &mut pacer, &mut ray_tracer, &camera, FRAME_COUNT);
+        // The floor covers exactly the middle half of the view, regardless of the distance
+        let row = &pixels[width * size.height as usize / 2 * 4..][..width * 4];
+        for x in 0..width {
+            let is_lit = row[x * 4] + row[x * 4 + 1] + row[x * 4 + 2] > 0.0;
+            let is_floor = (width / 4..width * 3 / 4).contains(&x);
+            assert_eq!(
+                is_lit, is_floor,
+                "Column {x} mismatches the floor with {:?}",
+                camera.projection
+            );
+        }
+    }
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
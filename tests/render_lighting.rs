@@ -0,0 +1,1070 @@
+//! Lights, materials, and participating media of the ray tracer.
+#![allow(irrefutable_let_patterns)]
+#![cfg(not(gles))]
+
+use blade_graphics as gpu;
+
+#[allow(dead_code)]
+mod common;
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn analytic_lights() {
+    const FRAME_COUNT: u32 = 16;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-lights-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 32,
+        height: 32,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // A floor facing up, and a plate facing down above the camera,
+    // which is only seen by the shadow rays.
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 2.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let plate = asset_hub.models.baker.create_model(
+        "plate",
+        vec![common::quad_geometry("plate", 0.5, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let mut plate_object = blade_render::Object::from(asset_hub.models.insert(plate));
+    plate_object.transform = common::flipped_at_height(4.0);
+    plate_object.prev_transform = plate_object.transform;
+    let objects = [
+        blade_render::Object::from(asset_hub.models.insert(floor)),
+        plate_object,
+    ];
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    pacer.end_frame(&context);
+
+    let camera = common::top_down_camera(3.0);
+    let center = [14, 14, 18, 18];
+    let corner = [0, 0, 4, 4];
+    let mut render = |kind, height: f32| {
+        let light = blade_render::Light {
+            kind,
+            position: [0.0, height, 0.0].into(),
+            direction: [0.0, -1.0, 0.0].into(),
+            color: [1.0; 3],
+            intensity: 1.0,
+        };
+        let (command_encoder, temp) = pacer.begin_frame();
+        ray_tracer.set_lights(command_encoder, &[light], &context, temp);
+        pacer.end_frame(&context);
+        let pixels = common::accumulate_hdr_with(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &camera,
+            common::dark_ray_config(),
+            FRAME_COUNT,
+        );
+        let radiance = [center, corner].map(|rect| common::mean_radiance(&pixels, size, rect));
+        println!("{kind:?} at {height}: center and corner radiance {radiance:?}");
+        radiance
+    };
+
+    // The point light falls off with the squared distance
+    let [near, _] = render(blade_render::LightKind::Point, 1.0);
+    let [far, _] = render(blade_render::LightKind::Point, 2.0);
+    assert!(
+        (3.4..4.4).contains(&(near / far)),
+        "Point light falloff {near} to {far} isn't quadratic"
+    );
+
+    // The spot light is as bright as the point light inside the cone, and dark outside
+    let [spot_center, spot_corner] = render(
+        blade_render::LightKind::Spot {
+            inner_angle: 0.2,
+            outer_angle: 0.3,
+        },
+        1.0,
+    );
+    assert!(
+        (0.8..1.2).contains(&(spot_center / near)),
+        "Spot light {spot_center} doesn't match the point light {near}"
+    );
+    assert!(
+        spot_corner < 0.01 * spot_center,
+        "Spot light leaks out of the cone"
+    );
+
+    // The plate casts a hard shadow of the sun, which gets soft with the angular radius
+    let [hard_center, hard_corner] = render(
+        blade_render::LightKind::Directional {
+            angular_radius: 0.0,
+        },
+        0.0,
+    );
+    assert!(hard_corner > 0.0);
+    assert!(
+        hard_center < 0.01 * hard_corner,
+        "Hard shadow isn't dark: {hard_center} against {hard_corner}"
+    );
+    let [soft_center, soft_corner] = render(
+        blade_render::LightKind::Directional {
+            angular_radius: 0.2,
+        },
+        0.0,
+    );
+    assert!(
+        soft_center > 0.2 * soft_corner,
+        "Soft shadow doesn't let the light through: {soft_center} against {soft_corner}"
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn emissive_triangle_sampling() {
+    const FRAME_COUNT: u32 = 64;
+    // Mean radiance of the two runs has to match within this fraction.
+    const TOLERANCE: f32 = 0.05;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-emissive-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 32,
+        height: 32,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // The Cornell box is only lit by the emissive panel under the ceiling
+    let (model, model_task) = asset_hub.models.load(
+        "examples/scene/data/cornell-box.gltf",
+        blade_render::model::Meta {
+            generate_tangents: true,
+            front_face: blade_render::model::FrontFace::CounterClockwise,
+            ..Default::default()
+        },
+    );
+    model_task.clone().join();
+    {
+        let model = &asset_hub.models[model];
+        for geometry in model.geometries.iter() {
+            let material = &model.materials[geometry.material_index];
+            if geometry.name.contains("light") {
+                assert!(material.is_emissive());
+                assert_eq!(
+                    geometry.emissive_triangles.len(),
+                    geometry.triangle_count as usize
+                );
+            } else {
+                assert!(!material.is_emissive());
+                assert!(geometry.emissive_triangles.is_empty());
+            }
+        }
+    }
+    let objects = [blade_render::Object::from(model)];
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    pacer.end_frame(&context);
+
+    // Looking at the back wall from the open side
+    let camera = blade_render::Camera {
+        pos: [0.0, 1.0, 3.4].into(),
+        rot: mint::Quaternion {
+            s: 1.0,
+            v: [0.0; 3].into(),
+        },
+        fov_y: 0.8,
+        depth: 100.0,
+        fov: None,
+        lens: blade_render::Lens::default(),
+        projection: blade_render::Projection::default(),
+    };
+    let back_wall = [12, 10, 20, 18];
+    let mut render = |num_emissive_samples| {
+        let pixels = common::accumulate_hdr_with(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &camera,
+            blade_render::RayConfig {
+                num_emissive_samples,
+                ..common::dark_ray_config()
+            },
+            FRAME_COUNT,
+        );
+        common::mean_radiance(&pixels, size, back_wall)
+    };
+
+    // Without the indirect bounces, the walls only see the panel through the light sampling
+    let unsampled = render(0);
+    let first = render(1);
+    let second = render(1);
+    println!("Back wall radiance: {unsampled} unsampled, {first} and {second} sampled");
+    assert!(first > 0.0);
+    assert!(
+        unsampled < 0.01 * first,
+        "The wall is lit without sampling the emissive triangles"
+    );
+    let error = (first - second).abs() / first.max(second);
+    assert!(
+        error < TOLERANCE,
+        "Independent accumulations diverge by {error}"
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn alpha_modes_in_shadows() {
+    const FRAME_COUNT: u32 = 16;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-alpha-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 32,
+        height: 32,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // Plates facing down, with the alpha below the cutoff, above it, and blended
+    let (plates, plates_task) = asset_hub.models.load(
+        "tests/data/alpha_modes.gltf",
+        blade_render::model::Meta {
+            generate_tangents: true,
+            front_face: blade_render::model::FrontFace::CounterClockwise,
+            ..Default::default()
+        },
+    );
+    plates_task.clone().join();
+    let alpha_modes = asset_hub.models[plates]
+        .materials
+        .iter()
+        .map(|material| (material.alpha_mode, material.base_color_factor[3]))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        alpha_modes,
+        [
+            (blade_render::model::AlphaMode::Mask { cutoff: 0.5 }, 0.3),
+            (blade_render::model::AlphaMode::Mask { cutoff: 0.5 }, 0.8),
+            (blade_render::model::AlphaMode::Blend, 0.5),
+        ]
+    );
+
+    // The plates are above the camera, so they are only seen by the shadow rays
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 3.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let mut plates_object = blade_render::Object::from(plates);
+    plates_object.transform = common::translation([0.0, 8.0, 0.0]);
+    plates_object.prev_transform = plates_object.transform;
+    let objects = [
+        blade_render::Object::from(asset_hub.models.insert(floor)),
+        plates_object,
+    ];
+    let sun = blade_render::Light {
+        kind: blade_render::LightKind::Directional {
+            angular_radius: 0.0,
+        },
+        position: [0.0; 3].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 1.0,
+    };
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[sun], &context, temp);
+    pacer.end_frame(&context);
+
+    let camera = common::top_down_camera(5.0);
+    let pixels = common::accumulate_hdr_with(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &camera,
+        common::dark_ray_config(),
+        FRAME_COUNT,
+    );
+    // The shadows are in a row across the middle, and the floor is lit next to them
+    let lit = common::mean_radiance(&pixels, size, [14, 26, 18, 30]);
+    let [mask_low, mask_high, blend] = [[5, 14, 9, 18], [14, 14, 18, 18], [23, 14, 27, 18]]
+        .map(|rect| common::mean_radiance(&pixels, size, rect) / lit);
+    println!("Shadows relative to the lit floor: {mask_low}, {mask_high}, {blend}");
+    assert!(lit > 0.0);
+    assert!(mask_low > 0.9, "The cut out plate casts a shadow");
+    assert!(mask_high < 0.05, "The masked plate doesn't cast a shadow");
+    assert!(
+        (0.3..0.7).contains(&blend),
+        "The blended plate doesn't let half of the light through"
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn skinned_shadows() {
+    const FRAME_COUNT: u32 = 16;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-skinning-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 32,
+        height: 32,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // Plate facing down, fully weighted to a single joint
+    let (plate, plate_task) = asset_hub.models.load(
+        "tests/data/skinned_plate.gltf",
+        blade_render::model::Meta {
+            generate_tangents: true,
+            front_face: blade_render::model::FrontFace::CounterClockwise,
+            ..Default::default()
+        },
+    );
+    plate_task.clone().join();
+    {
+        let model = &asset_hub.models[plate];
+        let names = model
+            .joints
+            .iter()
+            .map(|joint| joint.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["bone"]);
+        assert!(model.geometries.iter().all(|geometry| geometry.skinned));
+    }
+
+    // The plate is above the camera, so it's only seen by the shadow rays
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 3.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let mut scene = blade_render::Scene::new();
+    scene.add_object(blade_render::Object::from(asset_hub.models.insert(floor)));
+    let mut plate_object = blade_render::Object::from(plate);
+    plate_object.transform = common::translation([0.0, 8.0, 0.0]);
+    plate_object.prev_transform = plate_object.transform;
+    let plate_handle = scene.add_object(plate_object);
+    scene.add_light(blade_render::Light {
+        kind: blade_render::LightKind::Directional {
+            angular_radius: 0.0,
+        },
+        position: [0.0; 3].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 1.0,
+    });
+
+    let camera = common::top_down_camera(5.0);
+    let mut render = |scene: &mut blade_render::Scene, ray_tracer: &mut blade_render::RayTracer| {
+        let (command_encoder, temp) = pacer.begin_frame();
+        asset_hub.flush(command_encoder, &mut temp.buffers);
+        ray_tracer.update_scene(command_encoder, scene, None, &asset_hub, &context, temp);
+        pacer.end_frame(&context);
+        let pixels = common::accumulate_hdr_with(
+            &context,
+            &mut pacer,
+            ray_tracer,
+            &camera,
+            common::dark_ray_config(),
+            FRAME_COUNT,
+        );
+        let lit = common::mean_radiance(&pixels, size, [14, 26, 18, 30]);
+        assert!(lit > 0.0);
+        [[14, 14, 18, 18], [23, 14, 27, 18]]
+            .map(|rect| common::mean_radiance(&pixels, size, rect) / lit)
+    };
+
+    // The rest pose keeps the plate in place
+    let [center, side] = render(&mut scene, &mut ray_tracer);
+    println!("Rest pose shadow: {center}, floor on the side: {side}");
+    assert!(
+        center < 0.05,
+        "The plate doesn't cast a shadow in the rest pose"
+    );
+    assert!(side > 0.9, "The floor on the side isn't lit");
+
+    // Moving the joint drags the plate, and its shadow, to the side
+    let moved = glam::Mat4::from_translation(glam::Vec3::new(1.5, 0.0, 0.0));
+    scene.set_joints(plate_handle, &[moved.into()]).unwrap();
+    let [center, side] = render(&mut scene, &mut ray_tracer);
+    println!("Moved pose floor in the center: {center}, shadow: {side}");
+    assert!(center > 0.9, "The shadow stays in the rest pose");
+    assert!(side < 0.05, "The skinned plate doesn't cast a shadow");
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn layered_materials() {
+    const FRAME_COUNT: u32 = 16;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-layered-materials-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 32,
+        height: 32,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // Plates facing down, made of glass, clear coated paint, and velvet
+    let (plates, plates_task) = asset_hub.models.load(
+        "tests/data/layered_materials.gltf",
+        blade_render::model::Meta {
+            generate_tangents: true,
+            front_face: blade_render::model::FrontFace::CounterClockwise,
+            ..Default::default()
+        },
+    );
+    plates_task.clone().join();
+    {
+        let [glass, paint, velvet] = &asset_hub.models[plates].materials[..] else {
+            panic!("Expected 3 materials");
+        };
+        assert_eq!((glass.transmission, glass.ior), (1.0, 1.5));
+        assert!(!glass.is_opaque());
+        assert_eq!((paint.clearcoat, paint.clearcoat_roughness), (1.0, 0.1));
+        assert_eq!(paint.transmission, 0.0);
+        assert_eq!(velvet.sheen_color, [1.0, 0.5, 0.25]);
+        assert_eq!(velvet.sheen_roughness, 0.5);
+        assert_eq!(velvet.clearcoat, 0.0);
+    }
+
+    // The plates are above the camera, so they are only seen by the shadow rays
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 3.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let mut plates_object = blade_render::Object::from(plates);
+    plates_object.transform = common::translation([0.0, 8.0, 0.0]);
+    plates_object.prev_transform = plates_object.transform;
+    let objects = [
+        blade_render::Object::from(asset_hub.models.insert(floor)),
+        plates_object,
+    ];
+    let sun = blade_render::Light {
+        kind: blade_render::LightKind::Directional {
+            angular_radius: 0.0,
+        },
+        position: [0.0; 3].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 1.0,
+    };
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[sun], &context, temp);
+    pacer.end_frame(&context);
+
+    let camera = common::top_down_camera(5.0);
+    let pixels = common::accumulate_hdr_with(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &camera,
+        common::dark_ray_config(),
+        FRAME_COUNT,
+    );
+    assert!(pixels.iter().all(|value| value.is_finite()));
+    let lit = common::mean_radiance(&pixels, size, [14, 26, 18, 30]);
+    let [glass, paint, velvet] = [[5, 14, 9, 18], [14, 14, 18, 18], [23, 14, 27, 18]]
+        .map(|rect| common::mean_radiance(&pixels, size, rect) / lit);
+    println!("Shadows relative to the lit floor: {glass}, {paint}, {velvet}");
+    assert!(lit > 0.0);
+    // Only the Fresnel reflection of the thin glass is missing
+    assert!(
+        (0.85..1.05).contains(&glass),
+        "The glass doesn't let the light through"
+    );
+    assert!(paint < 0.05, "The clear coat lets the light through");
+    assert!(velvet < 0.05, "The sheen lets the light through");
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn medium_attenuates_and_scatters() {
+    const FRAME_COUNT: u32 = 64;
+    const ABSORPTION: f32 = 0.2;
+    const CAMERA_HEIGHT: f32 = 3.0;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-medium-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // A floor facing up, lit by a point light above it
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 4.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let objects = [blade_render::Object::from(asset_hub.models.insert(floor))];
+    let light = blade_render::Light {
+        kind: blade_render::LightKind::Point,
+        position: [0.0, 1.0, 0.0].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 10.0,
+    };
+
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[light], &context, temp);
+    pacer.end_frame(&context);
+
+    // Looking down at the floor
+    let camera = blade_render::Camera {
+        fov_y: 0.2,
+        ..common::top_down_camera(CAMERA_HEIGHT)
+    };
+
+    let mean = |pixels: &[f32]| {
+        let sum = pixels.chunks(4).map(|p| p[0] + p[1] + p[2]).sum::<f32>();
+        sum / (3 * pixels.len() / 4) as f32
+    };
+    let mut ray_config = blade_helpers::default_ray_config();
+    let clear = mean(&common::accumulate_hdr_with(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &camera,
+        ray_config,
+        FRAME_COUNT,
+    ));
+    ray_config.medium = blade_render::Medium {
+        absorption: ABSORPTION,
+        ..Default::default()
+    };
+    let absorbed = mean(&common::accumulate_hdr_with(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &camera,
+        ray_config,
+        FRAME_COUNT,
+    ));
+    ray_config.medium = blade_render::Medium {
+        absorption: ABSORPTION,
+        scattering: ABSORPTION,
+        ..Default::default()
+    };
+    let scattered = mean(&common::accumulate_hdr_with(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &camera,
+        ray_config,
+        FRAME_COUNT,
+    ));
+    println!("Mean radiance: clear {clear}, absorbed {absorbed}, scattered {scattered}");
+
+    assert!(clear.is_finite() && absorbed.is_finite() && scattered.is_finite());
+    // The narrow view sees the floor at about the camera height
+    let expected = (-ABSORPTION * CAMERA_HEIGHT).exp();
+    let ratio = absorbed / clear;
+    assert!(
+        (ratio - expected).abs() < 0.05,
+        "Transmittance {ratio} doesn't match the expected {expected}"
+    );
+    // The medium is lit by the point light, adding radiance over the attenuated floor
+    let ratio = (scattered / clear) / (-2.0 * ABSORPTION * CAMERA_HEIGHT).exp();
+    assert!(ratio > 1.0, "The medium doesn't scatter any light");
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn environment_rotation_and_intensity() {
+    const FRAME_COUNT: u32 = 4;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-environment-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 16,
+        height: 16,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // A small floor far below the view, so that only the sky is visible
+    let corners = [[-1.0, -1.0], [-1.0, 1.0], [1.0, 1.0], [1.0, -1.0]];
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![blade_render::ProceduralGeometry {
+            name: "floor".to_string(),
+            vertices: corners
+                .iter()
+                .map(|&[x, z]| {
+                    blade_render::Vertex::new(
+                        [x, -100.0, z],
+                        [0.0, 0.0],
+                        [0.0, 1.0, 0.0],
+                        [1.0, 0.0, 0.0, 1.0],
+                    )
+                })
+                .collect(),
+            indices: vec![0, 1, 2, 0, 2, 3],
+            base_color_factor: [0.8, 0.8, 0.8, 1.0],
+        }],
+    );
+    let objects = [blade_render::Object::from(asset_hub.models.insert(floor))];
+    // Half of the sky is red, and the other half is blue
+    let red = [0xFF, 0, 0, 0xFF];
+    let blue = [0, 0, 0xFF, 0xFF];
+    let two_tone = asset_hub.textures.baker.create_texture(
+        "two-tone-sky",
+        4,
+        2,
+        &[red, red, blue, blue, red, red, blue, blue],
+    );
+    let two_tone = asset_hub.textures.insert(two_tone);
+    let green =
+        asset_hub
+            .textures
+            .baker
+            .create_texture("green-sky", 4, 2, &[[0, 0xFF, 0, 0xFF]; 8]);
+    let green = asset_hub.textures.insert(green);
+
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(
+        command_encoder,
+        &objects,
+        Some(two_tone),
+        &asset_hub,
+        &context,
+        temp,
+    );
+    pacer.end_frame(&context);
+
+    // Looking at the horizon along X, into the middle of one of the halves
+    let half_sqrt = std::f32::consts::FRAC_1_SQRT_2;
+    let camera = blade_render::Camera {
+        pos: [0.0; 3].into(),
+        rot: mint::Quaternion {
+            s: half_sqrt,
+            v: [0.0, -half_sqrt, 0.0].into(),
+        },
+        fov_y: 0.2,
+        depth: 1000.0,
+        fov: None,
+        lens: blade_render::Lens::default(),
+        projection: blade_render::Projection::default(),
+    };
+
+    let center = |pixels: &[f32]| {
+        let offset = ((size.height / 2 * size.width + size.width / 2) * 4) as usize;
+        [pixels[offset], pixels[offset + 1], pixels[offset + 2]]
+    };
+    let mut ray_config = blade_helpers::default_ray_config();
+    let base = center(&common::accumulate_hdr_with(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &camera,
+        ray_config,
+        FRAME_COUNT,
+    ));
+    ray_config.environment.intensity = 2.0;
+    let bright = center(&common::accumulate_hdr_with(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &camera,
+        ray_config,
+        FRAME_COUNT,
+    ));
+    ray_config.environment = blade_render::EnvironmentConfig {
+        yaw: std::f32::consts::PI,
+        intensity: 1.0,
+    };
+    let rotated = center(&common::accumulate_hdr_with(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &camera,
+        ray_config,
+        FRAME_COUNT,
+    ));
+    println!("Sky radiance: base {base:?}, bright {bright:?}, rotated {rotated:?}");
+
+    let (base_main, base_other) = if base[0] > base[2] { (0, 2) } else { (2, 0) };
+    assert!(base[base_main] > 0.9 && base[base_other] < 0.1);
+    assert!(
+        (bright[base_main] - 2.0 * base[base_main]).abs() < 0.05,
+        "The intensity doesn't scale the environment"
+    );
+    assert!(
+        rotated[base_other] > 0.9 && rotated[base_main] < 0.1,
+        "The rotation doesn't turn the environment around"
+    );
+
+    // Swapping the environment map restarts the accumulation
+    let (command_encoder, temp) = pacer.begin_frame();
+    ray_tracer.build_scene(
+        command_encoder,
+        &objects,
+        Some(green),
+        &asset_hub,
+        &context,
+        temp,
+    );
+    ray_tracer.prepare(
+        command_encoder,
+        &camera,
+        blade_render::FrameConfig {
+            accumulate: true,
+            ..Default::default()
+        },
+    );
+    ray_tracer.ray_trace(
+        command_encoder,
+        blade_render::DebugConfig::default(),
+        ray_config,
+    );
+    pacer.end_frame(&context);
+    assert_eq!(ray_tracer.accumulated_frames(), 1);
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn procedural_sky() {
+    const FRAME_COUNT: u32 = 4;
+
+    let noon = blade_render::SkyModel {
+        sun_direction: [0.2, 1.0, 0.1].into(),
+        ..Default::default()
+    };
+    let sunset = blade_render::SkyModel {
+        sun_direction: [1.0, 0.05, 0.0].into(),
+        ..noon
+    };
+    let noon_sun = noon.sun_light();
+    let sunset_sun = sunset.sun_light();
+    assert!(noon_sun.intensity > sunset_sun.intensity);
+    assert!(
+        sunset_sun.color[2] < sunset_sun.color[0],
+        "The setting sun isn't reddened by the atmosphere"
+    );
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-sky-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 16,
+        height: 16,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // A small floor far below the view, so that only the sky is visible
+    let corners = [[-1.0, -1.0], [-1.0, 1.0], [1.0, 1.0], [1.0, -1.0]];
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![blade_render::ProceduralGeometry {
+            name: "floor".to_string(),
+            vertices: corners
+                .iter()
+                .map(|&[x, z]| {
+                    blade_render::Vertex::new(
+                        [x, -100.0, z],
+                        [0.0, 0.0],
+                        [0.0, 1.0, 0.0],
+                        [1.0, 0.0, 0.0, 1.0],
+                    )
+                })
+                .collect(),
+            indices: vec![0, 1, 2, 0, 2, 3],
+            base_color_factor: [0.8, 0.8, 0.8, 1.0],
+        }],
+    );
+    let objects = [blade_render::Object::from(asset_hub.models.insert(floor))];
+    let sky = asset_hub.create_sky(&noon);
+
+    // Looking up at the zenith
+    let half_sqrt = std::f32::consts::FRAC_1_SQRT_2;
+    let camera = blade_render::Camera {
+        pos: [0.0; 3].into(),
+        rot: mint::Quaternion {
+            s: half_sqrt,
+            v: [half_sqrt, 0.0, 0.0].into(),
+        },
+        fov_y: 0.2,
+        depth: 1000.0,
+        fov: None,
+        lens: blade_render::Lens::default(),
+        projection: blade_render::Projection::default(),
+    };
+    let center = |pixels: &[f32]| {
+        let offset = ((size.height / 2 * size.width + size.width / 2) * 4) as usize;
+        [pixels[offset], pixels[offset + 1], pixels[offset + 2]]
+    };
+
+    let mut zenith = Vec::new();
+    for model in [noon, sunset] {
+        let (command_encoder, temp) = pacer.begin_frame();
+        if model != noon {
+            asset_hub.update_sky(sky, &model, temp);
+        }
+        asset_hub.flush(command_encoder, &mut temp.buffers);
+        ray_tracer.build_scene(
+            command_encoder,
+            &objects,
+            Some(sky),
+            &asset_hub,
+            &context,
+            temp,
+        );
+        ray_tracer.set_lights(command_encoder, &[model.sun_light()], &context, temp);
+        pacer.end_frame(&context);
+        zenith.push(center(&common::accumulate_hdr(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &camera,
+            FRAME_COUNT,
+        )));
+    }
+    println!(
+        "Zenith radiance at noon {:?}, at sunset {:?}",
+        zenith[0], zenith[1]
+    );
+
+    assert!(
+        zenith[0][2] > zenith[0][0] && zenith[0][0] > 0.0,
+        "The sky at noon isn't blue"
+    );
+    assert!(
+        zenith[1][2] < zenith[0][2],
+        "The sky doesn't darken at sunset"
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn irradiance_volume_bake() {
+    const FRAME_COUNT: u32 = 16;
+    const NAME: &str = "blade-irradiance-test";
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new(NAME, true)
+    else {
+        return;
+    };
+    let temp_dir = std::env::temp_dir().join(NAME);
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // A floor facing up, lit by a point light above it
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 4.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let objects = [blade_render::Object::from(asset_hub.models.insert(floor))];
+    let light = blade_render::Light {
+        kind: blade_render::LightKind::Point,
+        position: [0.0, 1.0, 0.0].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 10.0,
+    };
+
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[light], &context, temp);
+    pacer.end_frame(&context);
+
+    // Looking down at the floor
+    let camera = common::top_down_camera(3.0);
+    let mut ray_config = blade_helpers::default_ray_config();
+    ray_config.max_bounces = 0;
+    let mean = |pixels: &[f32]| {
+        let sum = pixels.chunks(4).map(|p| p[0] + p[1] + p[2]).sum::<f32>();
+        sum / (3 * pixels.len() / 4) as f32
+    };
+    let direct = mean(&common::accumulate_hdr_with(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &camera,
+        ray_config,
+        FRAME_COUNT,
+    ));
+
+    // Probes hovering above the floor see it below, and the white environment above
+    let grid = blade_render::ProbeGrid {
+        origin: [-2.0, 0.5, -2.0].into(),
+        spacing: [2.0, 1.0, 2.0].into(),
+        counts: [3, 2, 3],
+    };
+    let (command_encoder, _) = pacer.begin_frame();
+    let bake = ray_tracer.bake_irradiance(command_encoder, &grid, 256, ray_config, &context);
+    let sync_point = pacer.end_frame(&context).clone();
+    assert!(context.wait_for(&sync_point, 5000).unwrap());
+    let baked = bake.into_baked(&context);
+    assert_eq!(baked.grid, grid);
+    let down = baked.probe_irradiance([1, 0, 1], [0.0, -1.0, 0.0].into());
+    let up = baked.probe_irradiance([1, 0, 1], [0.0, 1.0, 0.0].into());
+    println!("Probe irradiance: down {down:?}, up {up:?}");
+    for value in down.iter().chain(up.iter()) {
+        assert!(value.is_finite() && *value > 0.0);
+    }
+
+    // Serialized volume survives a round trip through the asset hub
+    assert_eq!(
+        blade_render::BakedIrradiance::from_bytes(&baked.to_bytes()).unwrap(),
+        baked
+    );
+    std::fs::create_dir_all(&temp_dir).unwrap();
+    let path = temp_dir.join("probes.irradiance");
+    baked.save(&path).unwrap();
+    let (handle, task) = asset_hub
+        .irradiance
+        .load(&path, blade_render::irradiance::Meta);
+    task.clone().join();
+    assert_eq!(asset_hub.irradiance[handle].grid, grid);
+
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    pacer.end_frame(&context);
+    ray_tracer.set_irradiance_volume(Some(&asset_hub.irradiance[handle]));
+    let with_volume = mean(&common::accumulate_hdr_with(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &camera,
+        ray_config,
+        FRAME_COUNT,
+    ));
+    println!("Mean radiance: direct {direct}, with volume {with_volume}");
+    assert!(with_volume.is_finite());
+    assert!(
+        with_volume > direct,
+        "The volume doesn't add any indirect lighting"
+    );
+
+    ray_tracer.set_irradiance_volume(None);
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
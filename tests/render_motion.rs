@@ -0,0 +1,513 @@
+//! Motion vectors, temporal reuse, and denoising of the ray tracer.
+#![allow(irrefutable_let_patterns)]
+#![cfg(not(gles))]
+
+use blade_graphics as gpu;
+
+#[allow(dead_code)]
+mod common;
+
+use common::snapshot;
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn moving_object_no_trailing() {
+    // Mean color of the blocks has to match the static reference within this range.
+    const TOLERANCE: f32 = 24.0;
+    const FRAME_COUNT: u32 = 64;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-motion-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    let make_quad = |name: &str, half_size: f32, color: [f32; 4]| {
+        asset_hub
+            .models
+            .baker
+            .create_model(name, vec![common::quad_geometry(name, half_size, color)])
+    };
+    // A dark floor with a bright plate floating above, casting a shadow
+    let floor = asset_hub
+        .models
+        .insert(make_quad("floor", 2.0, [0.2, 0.2, 0.2, 1.0]));
+    let plate = asset_hub
+        .models
+        .insert(make_quad("plate", 0.3, [1.0, 1.0, 1.0, 1.0]));
+    let light = blade_render::Light {
+        kind: blade_render::LightKind::Point,
+        position: [0.0, 2.0, 0.0].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 10.0,
+    };
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.set_lights(command_encoder, &[light], &context, temp);
+    pacer.end_frame(&context);
+
+    // Looking down at the floor from a static position
+    let camera = common::top_down_camera(3.0);
+    let plate_position = |frame: u32| [(frame as f32 * 0.3).sin(), 0.5, 0.0];
+    let make_objects = |position: [f32; 3], prev_position: [f32; 3]| {
+        let mut plate_object = blade_render::Object::from(plate);
+        plate_object.transform = common::translation(position);
+        plate_object.prev_transform = common::translation(prev_position);
+        [blade_render::Object::from(floor), plate_object]
+    };
+
+    // The plate oscillates under the static camera
+    let mut moving = Vec::new();
+    for frame in 0..FRAME_COUNT {
+        let objects = make_objects(
+            plate_position(frame),
+            plate_position(frame.saturating_sub(1)),
+        );
+        moving = common::render_denoised_frame(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &asset_hub,
+            &target,
+            &camera,
+            &objects,
+            frame == 0,
+        );
+    }
+
+    // The plate stays at the last position from the start
+    let last_position = plate_position(FRAME_COUNT - 1);
+    let mut reference = Vec::new();
+    for frame in 0..FRAME_COUNT {
+        let objects = make_objects(last_position, last_position);
+        reference = common::render_denoised_frame(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &asset_hub,
+            &target,
+            &camera,
+            &objects,
+            frame == 0,
+        );
+    }
+
+    let max_error = common::max_block_error(&moving, &reference, size);
+    println!("Max block error: {max_error}");
+    assert!(
+        max_error < TOLERANCE,
+        "The moving plate leaves a trail, differing by {max_error}"
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    target.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn stopped_object_no_trailing() {
+    // Mean color of the blocks has to match the static reference within this range.
+    const TOLERANCE: f32 = 24.0;
+    const MOVING_FRAMES: u32 = 16;
+    const FRAME_COUNT: u32 = 64;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-motion-stop-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 2.0, [0.2, 0.2, 0.2, 1.0])],
+    );
+    let plate = asset_hub.models.baker.create_model(
+        "plate",
+        vec![common::quad_geometry("plate", 0.3, [1.0, 1.0, 1.0, 1.0])],
+    );
+    let floor = asset_hub.models.insert(floor);
+    let plate = asset_hub.models.insert(plate);
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    pacer.end_frame(&context);
+
+    let mut scene = blade_render::Scene::new();
+    scene.add_object(blade_render::Object::from(floor));
+    let plate_handle = scene.add_object(blade_render::Object::from(plate));
+    scene.add_light(blade_render::Light {
+        kind: blade_render::LightKind::Point,
+        position: [0.0, 2.0, 0.0].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 10.0,
+    });
+    let camera = common::top_down_camera(3.0);
+    let plate_position = |frame: u32| [(frame as f32 * 0.3).sin(), 0.5, 0.0];
+
+    // The plate moves for a while through the retained scene, then stops,
+    // leaving the motion vectors of the last move behind if they are not refreshed
+    let mut stopped = Vec::new();
+    for frame in 0..FRAME_COUNT {
+        if frame < MOVING_FRAMES {
+            scene.set_transform(plate_handle, common::translation(plate_position(frame)));
+        }
+        stopped = common::render_denoised_frame_with(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &target,
+            &camera,
+            frame == 0,
+            |ray_tracer, command_encoder, temp| {
+                ray_tracer.update_scene(
+                    command_encoder,
+                    &mut scene,
+                    None,
+                    &asset_hub,
+                    &context,
+                    temp,
+                )
+            },
+        );
+    }
+
+    // The plate stays at the last position from the start
+    let mut plate_object = blade_render::Object::from(plate);
+    plate_object.transform = common::translation(plate_position(MOVING_FRAMES - 1));
+    plate_object.prev_transform = plate_object.transform;
+    let objects = [blade_render::Object::from(floor), plate_object];
+    let mut reference = Vec::new();
+    for frame in 0..FRAME_COUNT {
+        reference = common::render_denoised_frame(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &asset_hub,
+            &target,
+            &camera,
+            &objects,
+            frame == 0,
+        );
+    }
+
+    let max_error = common::max_block_error(&stopped, &reference, size);
+    println!("Max block error: {max_error}");
+    assert!(
+        max_error < TOLERANCE,
+        "The stopped plate leaves a trail, differing by {max_error}"
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    target.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn debug_views() {
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-debug-view-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 32,
+        height: 32,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // A blue plate above an orange floor, casting a shadow to the side
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 3.0, [0.8, 0.6, 0.4, 1.0])],
+    );
+    let plate = asset_hub.models.baker.create_model(
+        "plate",
+        vec![common::quad_geometry("plate", 0.3, [0.2, 0.4, 0.9, 1.0])],
+    );
+    let mut plate_object = blade_render::Object::from(asset_hub.models.insert(plate));
+    plate_object.transform = common::translation([-0.8, 1.0, 0.0]);
+    plate_object.prev_transform = plate_object.transform;
+    let objects = [
+        blade_render::Object::from(asset_hub.models.insert(floor)),
+        plate_object,
+    ];
+    let sun = blade_render::Light {
+        kind: blade_render::LightKind::Directional {
+            angular_radius: 0.0,
+        },
+        position: [0.0; 3].into(),
+        direction: [1.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 1.0,
+    };
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[sun], &context, temp);
+    pacer.end_frame(&context);
+
+    // The linear depth is relative to the camera depth
+    let camera = blade_render::Camera {
+        depth: 5.0,
+        ..common::top_down_camera(3.0)
+    };
+    let target = snapshot::OffscreenTarget::new(&context, size, gpu::TextureFormat::Rgba8Unorm);
+    let mut render = |view_mode, denoiser_config| {
+        common::render_debug_view(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &target,
+            &camera,
+            true,
+            blade_render::DebugConfig {
+                view_mode,
+                ..Default::default()
+            },
+            common::dark_ray_config(),
+            denoiser_config,
+        )
+    };
+    let plate_rect = [2, 14, 7, 18];
+    let shadow_rect = [16, 14, 20, 18];
+    let lit_rect = [24, 14, 28, 18];
+    let no_denoiser = blade_render::DenoiserConfig {
+        enabled: false,
+        ..Default::default()
+    };
+
+    let final_image = render(blade_render::DebugMode::Final, no_denoiser);
+    let shadow = common::mean_color(&final_image, size, shadow_rect);
+    let lit = common::mean_color(&final_image, size, lit_rect);
+    println!("Final shadow {shadow:?}, lit {lit:?}");
+    assert!(shadow[0] < 0.5 * lit[0], "The shadow is missing");
+
+    // The albedo is not affected by the lighting
+    let albedo = render(blade_render::DebugMode::Albedo, no_denoiser);
+    let [plate, shadow, lit] =
+        [plate_rect, shadow_rect, lit_rect].map(|rect| common::mean_color(&albedo, size, rect));
+    println!("Albedo of the plate {plate:?}, shadow {shadow:?}, lit {lit:?}");
+    assert!(lit[0] > lit[1] && lit[1] > lit[2], "The floor isn't orange");
+    assert!(
+        plate[2] > plate[1] && plate[1] > plate[0],
+        "The plate isn't blue"
+    );
+    assert!(
+        shadow.iter().zip(&lit).all(|(s, l)| (s - l).abs() <= 2.0),
+        "The albedo is shadowed"
+    );
+
+    // The linear depth is written by the debug builds of the shaders only
+    let depth = render(blade_render::DebugMode::LinearDepth, no_denoiser);
+    if cfg!(debug_assertions) {
+        let plate = common::mean_color(&depth, size, plate_rect)[0];
+        let floor = common::mean_color(&depth, size, lit_rect)[0];
+        println!("Linear depth of the plate {plate}, floor {floor}");
+        // Slightly more than 2 and 3 units away, out of 5
+        assert!((100.0..120.0).contains(&plate), "Wrong depth of the plate");
+        assert!((150.0..175.0).contains(&floor), "Wrong depth of the floor");
+    } else {
+        assert!(common::is_checkerboard(&depth));
+    }
+
+    // The accumulation age only exists after the temporal filter
+    let age = render(blade_render::DebugMode::AccumulationAge, no_denoiser);
+    assert!(
+        common::is_checkerboard(&age),
+        "Stale accumulation age is shown"
+    );
+    let age = render(
+        blade_render::DebugMode::AccumulationAge,
+        blade_render::DenoiserConfig::default(),
+    );
+    assert!(
+        !common::is_checkerboard(&age),
+        "The accumulation age is missing"
+    );
+    target.destroy(&context);
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn denoiser_configs() {
+    // Mean color of the blocks has to match the raw image within this range.
+    const TOLERANCE: f32 = 8.0;
+    const FRAME_COUNT: u32 = 8;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-denoiser-config-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 32,
+        height: 32,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // A plate above the camera, casting a shadow in the middle of the floor
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 3.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let plate = asset_hub.models.baker.create_model(
+        "plate",
+        vec![common::quad_geometry("plate", 1.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let mut plate_object = blade_render::Object::from(asset_hub.models.insert(plate));
+    plate_object.transform = common::flipped_at_height(6.0);
+    plate_object.prev_transform = plate_object.transform;
+    let objects = [
+        blade_render::Object::from(asset_hub.models.insert(floor)),
+        plate_object,
+    ];
+    let sun = |direction: [f32; 3]| blade_render::Light {
+        kind: blade_render::LightKind::Directional {
+            angular_radius: 0.0,
+        },
+        position: [0.0; 3].into(),
+        direction: direction.into(),
+        color: [1.0; 3],
+        intensity: 1.0,
+    };
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[sun([0.0, -1.0, 0.0])], &context, temp);
+    pacer.end_frame(&context);
+
+    let camera = common::top_down_camera(3.0);
+    let target = snapshot::OffscreenTarget::new(&context, size, gpu::TextureFormat::Rgba8Unorm);
+    let render = |pacer: &mut blade_render::util::FramePacer,
+                  ray_tracer: &mut blade_render::RayTracer,
+                  reset,
+                  denoiser_config| {
+        common::render_debug_view(
+            &context,
+            pacer,
+            ray_tracer,
+            &target,
+            &camera,
+            reset,
+            blade_render::DebugConfig::default(),
+            common::dark_ray_config(),
+            denoiser_config,
+        )
+    };
+    let disabled = blade_render::DenoiserConfig {
+        enabled: false,
+        ..Default::default()
+    };
+
+    // Every combination produces the same image as the raw ray traced one
+    let raw = render(&mut pacer, &mut ray_tracer, true, disabled);
+    let shadow = common::mean_color(&raw, size, [12, 12, 20, 20]);
+    let lit = common::mean_color(&raw, size, [0, 0, 4, 4]);
+    assert!(shadow[0] < 0.5 * lit[0], "The shadow is missing");
+    let configs = [
+        blade_render::DenoiserConfig::default(),
+        blade_render::DenoiserConfig {
+            spatial_passes: 0,
+            ..Default::default()
+        },
+        blade_render::DenoiserConfig {
+            temporal_weight: 1.0,
+            ..Default::default()
+        },
+        blade_render::DenoiserConfig {
+            temporal_weight: 1.0,
+            spatial_passes: 0,
+            ..Default::default()
+        },
+        blade_render::DenoiserConfig {
+            history_clamp_sigma: 1.0,
+            ..Default::default()
+        },
+    ];
+    for denoiser_config in configs {
+        let mut pixels = Vec::new();
+        for frame in 0..FRAME_COUNT {
+            pixels = render(&mut pacer, &mut ray_tracer, frame == 0, denoiser_config);
+        }
+        let error = common::max_block_error(&raw, &pixels, size);
+        println!("{denoiser_config:?} differs from the raw image by {error}");
+        assert!(error < TOLERANCE, "{denoiser_config:?} changes the image");
+    }
+
+    // The history is not updated while the denoiser is disabled,
+    // so it can't be reused when enabling it again.
+    let (command_encoder, temp) = pacer.begin_frame();
+    ray_tracer.set_lights(command_encoder, &[sun([1.0, -1.0, 0.0])], &context, temp);
+    pacer.end_frame(&context);
+    let mut moved = Vec::new();
+    for _ in 0..FRAME_COUNT {
+        moved = render(&mut pacer, &mut ray_tracer, false, disabled);
+    }
+    let shadow = common::mean_color(&moved, size, [12, 12, 20, 20]);
+    assert!(shadow[0] > 0.5 * lit[0], "The shadow didn't move");
+    let resumed = render(
+        &mut pacer,
+        &mut ray_tracer,
+        false,
+        blade_render::DenoiserConfig::default(),
+    );
+    let error = common::max_block_error(&moved, &resumed, size);
+    println!("Re-enabled denoiser differs from the raw image by {error}");
+    assert!(error < TOLERANCE, "The stale history is reused");
+    target.destroy(&context);
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
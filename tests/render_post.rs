@@ -0,0 +1,734 @@
+//! Tone mapping, bloom, exposure, and upscaling of the post-processing.
+#![allow(irrefutable_let_patterns)]
+#![cfg(not(gles))]
+
+use blade_graphics as gpu;
+
+#[allow(dead_code)]
+mod common;
+
+use common::snapshot;
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn bloom_spreads_bright_areas() {
+    const FRAME_COUNT: u32 = 16;
+    // Mean difference of the channels that the bloom may cause at the default intensity.
+    const TOLERANCE: f32 = 16.0;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-bloom-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    let make_quad = |name: &str, half_size: f32, color: [f32; 4]| {
+        asset_hub
+            .models
+            .baker
+            .create_model(name, vec![common::quad_geometry(name, half_size, color)])
+    };
+    // A dark floor with a white plate right under a strong light
+    let floor = asset_hub
+        .models
+        .insert(make_quad("floor", 4.0, [0.05, 0.05, 0.05, 1.0]));
+    let plate = asset_hub
+        .models
+        .insert(make_quad("plate", 0.3, [1.0, 1.0, 1.0, 1.0]));
+    let mut plate_object = blade_render::Object::from(plate);
+    plate_object.transform = common::translation([0.0, 0.5, 0.0]);
+    let objects = [blade_render::Object::from(floor), plate_object];
+    let light = blade_render::Light {
+        kind: blade_render::LightKind::Point,
+        position: [0.0, 1.0, 0.0].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 100.0,
+    };
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[light], &context, temp);
+    pacer.end_frame(&context);
+
+    // Looking down at the plate
+    let camera = common::top_down_camera(3.0);
+    let plain = blade_render::PostProcConfig::default();
+    let bloom = blade_render::PostProcConfig {
+        bloom: blade_render::BloomConfig {
+            enabled: true,
+            ..Default::default()
+        },
+        ..plain
+    };
+    let strong_bloom = blade_render::PostProcConfig {
+        bloom: blade_render::BloomConfig {
+            intensity: 0.3,
+            ..bloom.bloom
+        },
+        ..plain
+    };
+    let brightness = |pixels: &[u8]| -> Vec<f32> {
+        pixels
+            .chunks(4)
+            .map(|p| p[..3].iter().map(|&c| c as f32).sum::<f32>() / 3.0)
+            .collect()
+    };
+
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    common::accumulate_hdr(&context, &mut pacer, &mut ray_tracer, &camera, FRAME_COUNT);
+    let without = brightness(&common::post_process_accumulated(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &target,
+        plain,
+    ));
+    let with = brightness(&common::post_process_accumulated(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &target,
+        strong_bloom,
+    ));
+    target.destroy(&context);
+    let min = |values: &[f32]| values.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = |values: &[f32]| values.iter().cloned().fold(0.0, f32::max);
+    println!(
+        "Brightness range without bloom {}..{}, with {}..{}",
+        min(&without),
+        max(&without),
+        min(&with),
+        max(&with)
+    );
+    assert!(
+        min(&with) > min(&without),
+        "The bloom doesn't reach the dark floor"
+    );
+    assert!(
+        max(&with) <= max(&without),
+        "The bloom adds energy to the bright plate"
+    );
+
+    // Odd and tiny resolutions, with the chain shrinking down to nothing
+    for (width, height) in [(50, 37), (5, 3), (1, 1)] {
+        let size = gpu::Extent {
+            width,
+            height,
+            depth: 1,
+        };
+        let (command_encoder, _) = pacer.begin_frame();
+        ray_tracer.resize_screen(size, command_encoder, &context);
+        pacer.end_frame(&context);
+        let target = snapshot::OffscreenTarget::new(&context, size, format);
+        common::accumulate_hdr(&context, &mut pacer, &mut ray_tracer, &camera, FRAME_COUNT);
+        let without = brightness(&common::post_process_accumulated(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &target,
+            plain,
+        ));
+        let with = brightness(&common::post_process_accumulated(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &target,
+            bloom,
+        ));
+        target.destroy(&context);
+        let error = without
+            .iter()
+            .zip(with.iter())
+            .map(|(a, b)| (a - b).abs())
+            .sum::<f32>()
+            / without.len() as f32;
+        println!("Bloom difference at {width}x{height}: {error}");
+        assert!(
+            error < TOLERANCE,
+            "The bloom at {width}x{height} differs by {error}"
+        );
+    }
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+/// Tone map an HDR color on the CPU, following the post-processing shader.
+fn tone_map_reference(color: [f32; 3], pp_config: &blade_render::PostProcConfig) -> [f32; 3] {
+    let aces = |x: f32| (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+    let exposure = pp_config.exposure_ev.exp2();
+    let white = pp_config.white_point;
+    let exposed = color.map(|c| (exposure * c).max(0.0));
+    let mapped = match pp_config.tone_map {
+        blade_render::ToneMap::Linear => exposed.map(|c| c / white),
+        blade_render::ToneMap::Reinhard => {
+            exposed.map(|c| c * (1.0 + c / (white * white)) / (1.0 + c))
+        }
+        blade_render::ToneMap::Aces => exposed.map(|c| aces(c) / aces(white)),
+        blade_render::ToneMap::KhronosPbrNeutral => {
+            const START_COMPRESSION: f32 = 0.8 - 0.04;
+            const DESATURATION: f32 = 0.15;
+            let x = exposed.iter().cloned().fold(f32::INFINITY, f32::min);
+            let offset = if x < 0.08 { x - 6.25 * x * x } else { 0.04 };
+            let color = exposed.map(|c| c - offset);
+            let peak = color.iter().cloned().fold(0.0, f32::max);
+            if peak < START_COMPRESSION {
+                color
+            } else {
+                let d = 1.0 - START_COMPRESSION;
+                let new_peak = 1.0 - d * d / (peak + d - START_COMPRESSION);
+                let g = 1.0 - 1.0 / (DESATURATION * (peak - new_peak) + 1.0);
+                color.map(|c| c * new_peak / peak * (1.0 - g) + new_peak * g)
+            }
+        }
+    };
+    mapped.map(|c| {
+        let c = c.clamp(0.0, 1.0);
+        if !pp_config.encode_srgb {
+            c
+        } else if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    })
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn tone_mapping_operators() {
+    const FRAME_COUNT: u32 = 16;
+    // Difference of the 8-bit channels allowed by the rounding.
+    const TOLERANCE: u8 = 2;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-tone-map-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 32,
+        height: 32,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // A floor under a strong light, covering a wide range of radiance
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 4.0, [0.8, 0.6, 0.4, 1.0])],
+    );
+    let objects = [blade_render::Object::from(asset_hub.models.insert(floor))];
+    let light = blade_render::Light {
+        kind: blade_render::LightKind::Point,
+        position: [0.0, 0.5, 0.0].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 20.0,
+    };
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[light], &context, temp);
+    pacer.end_frame(&context);
+
+    let camera = common::top_down_camera(3.0);
+    let hdr = common::accumulate_hdr_with(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &camera,
+        common::dark_ray_config(),
+        FRAME_COUNT,
+    );
+    let (min, max) = hdr
+        .chunks(4)
+        .flat_map(|p| p[..3].iter().cloned())
+        .fold((f32::INFINITY, 0.0f32), |(lo, hi), c| {
+            (lo.min(c), hi.max(c))
+        });
+    println!("Radiance range {min}..{max}");
+    assert!(min < 0.5 && max > 2.0, "The radiance range is too narrow");
+
+    let target = snapshot::OffscreenTarget::new(&context, size, gpu::TextureFormat::Rgba8Unorm);
+    let base = blade_render::PostProcConfig {
+        upscale_sharpness: 0.0,
+        ..Default::default()
+    };
+    let configs = [
+        blade_render::PostProcConfig {
+            tone_map: blade_render::ToneMap::Linear,
+            ..base
+        },
+        blade_render::PostProcConfig {
+            tone_map: blade_render::ToneMap::Linear,
+            exposure_ev: -2.0,
+            ..base
+        },
+        blade_render::PostProcConfig {
+            tone_map: blade_render::ToneMap::Reinhard,
+            white_point: 4.0,
+            ..base
+        },
+        blade_render::PostProcConfig {
+            tone_map: blade_render::ToneMap::Aces,
+            exposure_ev: 1.0,
+            white_point: 8.0,
+            ..base
+        },
+        blade_render::PostProcConfig {
+            tone_map: blade_render::ToneMap::KhronosPbrNeutral,
+            ..base
+        },
+        blade_render::PostProcConfig {
+            tone_map: blade_render::ToneMap::Reinhard,
+            encode_srgb: true,
+            ..base
+        },
+    ];
+    for pp_config in configs {
+        let ldr = common::post_process_accumulated(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &target,
+            pp_config,
+        );
+        for (hdr_pixel, ldr_pixel) in hdr.chunks(4).zip(ldr.chunks(4)) {
+            let expected =
+                tone_map_reference([hdr_pixel[0], hdr_pixel[1], hdr_pixel[2]], &pp_config)
+                    .map(|c| (c * 255.0).round() as u8);
+            assert!(
+                expected
+                    .iter()
+                    .zip(ldr_pixel)
+                    .all(|(&e, &a)| e.abs_diff(a) <= TOLERANCE),
+                "{pp_config:?} maps {hdr_pixel:?} to {ldr_pixel:?} instead of {expected:?}"
+            );
+        }
+    }
+    target.destroy(&context);
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+/// Meter the exposure of the current image and read the result back.
+fn meter_exposure(
+    context: &gpu::Context,
+    pacer: &mut blade_render::util::FramePacer,
+    ray_tracer: &mut blade_render::RayTracer,
+    config: blade_render::AutoExposureConfig,
+    delta_time: f32,
+) -> f32 {
+    let (command_encoder, _) = pacer.begin_frame();
+    ray_tracer.meter_exposure(command_encoder, config, delta_time);
+    let sync_point = pacer.end_frame(context).clone();
+    assert!(context.wait_for(&sync_point, 5000).unwrap());
+    ray_tracer.auto_exposure_ev()
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn auto_exposure() {
+    const FRAME_COUNT: u32 = 4;
+    // Metered EV difference accepted as equal, in stops.
+    const TOLERANCE: f32 = 0.01;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-exposure-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 16,
+        height: 16,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    // A small floor far below the view, so that only the sky is visible
+    let corners = [[-1.0, -1.0], [-1.0, 1.0], [1.0, 1.0], [1.0, -1.0]];
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![blade_render::ProceduralGeometry {
+            name: "floor".to_string(),
+            vertices: corners
+                .iter()
+                .map(|&[x, z]| {
+                    blade_render::Vertex::new(
+                        [x, -100.0, z],
+                        [0.0, 0.0],
+                        [0.0, 1.0, 0.0],
+                        [1.0, 0.0, 0.0, 1.0],
+                    )
+                })
+                .collect(),
+            indices: vec![0, 1, 2, 0, 2, 3],
+            base_color_factor: [0.8, 0.8, 0.8, 1.0],
+        }],
+    );
+    let objects = [blade_render::Object::from(asset_hub.models.insert(floor))];
+    let noon = blade_render::SkyModel {
+        sun_direction: [0.2, 1.0, 0.1].into(),
+        ..Default::default()
+    };
+    let sunset = blade_render::SkyModel {
+        sun_direction: [1.0, 0.05, 0.0].into(),
+        ..noon
+    };
+    let sky = asset_hub.create_sky(&noon);
+
+    // Looking up at the zenith
+    let half_sqrt = std::f32::consts::FRAC_1_SQRT_2;
+    let camera = blade_render::Camera {
+        pos: [0.0; 3].into(),
+        rot: mint::Quaternion {
+            s: half_sqrt,
+            v: [half_sqrt, 0.0, 0.0].into(),
+        },
+        fov_y: 0.2,
+        depth: 1000.0,
+        fov: None,
+        lens: blade_render::Lens::default(),
+        projection: blade_render::Projection::default(),
+    };
+
+    let config = blade_render::AutoExposureConfig::default();
+
+    let mut evs = Vec::new();
+    for model in [noon, sunset] {
+        let (command_encoder, temp) = pacer.begin_frame();
+        if model != noon {
+            asset_hub.update_sky(sky, &model, temp);
+        }
+        asset_hub.flush(command_encoder, &mut temp.buffers);
+        ray_tracer.build_scene(
+            command_encoder,
+            &objects,
+            Some(sky),
+            &asset_hub,
+            &context,
+            temp,
+        );
+        pacer.end_frame(&context);
+        common::accumulate_hdr(&context, &mut pacer, &mut ray_tracer, &camera, FRAME_COUNT);
+
+        // The first metering jumps to the target, the later ones are limited by the speed
+        let first = meter_exposure(&context, &mut pacer, &mut ray_tracer, config, 0.0);
+        let ev = meter_exposure(&context, &mut pacer, &mut ray_tracer, config, 1.0e3);
+        if model == noon {
+            assert!(
+                (ev - first).abs() < TOLERANCE,
+                "Exposure moved from {first} to {ev} without the image changing"
+            );
+        } else {
+            assert_eq!(first, evs[0], "Exposure moved with no time passing");
+        }
+        evs.push(ev);
+    }
+    println!("Metered EV at noon {}, at sunset {}", evs[0], evs[1]);
+    assert!(
+        evs[1] > evs[0] + TOLERANCE,
+        "Exposure didn't increase for the darker sky"
+    );
+
+    let clamped = meter_exposure(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        blade_render::AutoExposureConfig {
+            max_ev: evs[0],
+            ..config
+        },
+        1.0e3,
+    );
+    assert!(
+        (clamped - evs[0]).abs() < TOLERANCE,
+        "Exposure {clamped} isn't clamped to {}",
+        evs[0]
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn render_scale_upscaling() {
+    const FRAME_COUNT: u32 = 16;
+    // Mean color of the blocks has to match the native rendering within this range.
+    const TOLERANCE: f32 = 24.0;
+    const BLOCK: usize = 8;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-render-scale-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    let make_quad = |name: &str, half_size: f32, color: [f32; 4]| {
+        asset_hub
+            .models
+            .baker
+            .create_model(name, vec![common::quad_geometry(name, half_size, color)])
+    };
+    // A dark floor with a bright plate, giving sharp edges
+    let floor = asset_hub
+        .models
+        .insert(make_quad("floor", 4.0, [0.2, 0.2, 0.2, 1.0]));
+    let plate = asset_hub
+        .models
+        .insert(make_quad("plate", 0.5, [1.0, 1.0, 1.0, 1.0]));
+    let mut plate_object = blade_render::Object::from(plate);
+    plate_object.transform = common::translation([0.0, 0.5, 0.0]);
+    let objects = [blade_render::Object::from(floor), plate_object];
+    let light = blade_render::Light {
+        kind: blade_render::LightKind::Point,
+        position: [0.0, 2.0, 0.0].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 10.0,
+    };
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    ray_tracer.set_lights(command_encoder, &[light], &context, temp);
+    pacer.end_frame(&context);
+
+    let camera = common::top_down_camera(3.0);
+
+    common::accumulate_hdr(&context, &mut pacer, &mut ray_tracer, &camera, FRAME_COUNT);
+    let native = common::post_process_accumulated(
+        &context,
+        &mut pacer,
+        &mut ray_tracer,
+        &target,
+        blade_render::PostProcConfig::default(),
+    );
+
+    let (command_encoder, _) = pacer.begin_frame();
+    ray_tracer.set_render_scale(0.5, command_encoder, &context);
+    pacer.end_frame(&context);
+    assert_eq!(ray_tracer.get_surface_size(), size);
+    assert_eq!(
+        ray_tracer.get_render_size(),
+        gpu::Extent {
+            width: 32,
+            height: 32,
+            depth: 1,
+        }
+    );
+    common::accumulate_hdr(&context, &mut pacer, &mut ray_tracer, &camera, FRAME_COUNT);
+    let upscaled = [0.0, 1.0].map(|upscale_sharpness| {
+        common::post_process_accumulated(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &target,
+            blade_render::PostProcConfig {
+                upscale_sharpness,
+                ..Default::default()
+            },
+        )
+    });
+
+    let width = size.width as usize;
+    let block_mean = |pixels: &[u8], bx: usize, by: usize| {
+        let mut sum = 0.0;
+        for y in by * BLOCK..(by + 1) * BLOCK {
+            for x in bx * BLOCK..(bx + 1) * BLOCK {
+                let p = &pixels[(y * width + x) * 4..][..3];
+                sum += p.iter().map(|&c| c as f32).sum::<f32>();
+            }
+        }
+        sum / (3 * BLOCK * BLOCK) as f32
+    };
+    for pixels in upscaled.iter() {
+        let mut max_error = 0.0f32;
+        for by in 0..size.height as usize / BLOCK {
+            for bx in 0..width / BLOCK {
+                let error = (block_mean(pixels, bx, by) - block_mean(&native, bx, by)).abs();
+                max_error = max_error.max(error);
+            }
+        }
+        println!("Max block error of the upscaled image: {max_error}");
+        assert!(
+            max_error < TOLERANCE,
+            "The upscaled image differs by {max_error}"
+        );
+    }
+    assert_ne!(upscaled[0], upscaled[1], "The sharpening has no effect");
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    target.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn renderer_pass_timings() {
+    const FRAME_COUNT: usize = 12;
+
+    if cfg!(target_os = "macos") {
+        println!("Skipping: ray tracing not supported on macOS CI");
+        return;
+    }
+    let context = unsafe {
+        match gpu::Context::init(gpu::ContextDesc {
+            ray_tracing: true,
+            timing: true,
+            ..Default::default()
+        }) {
+            Ok(c) => std::sync::Arc::new(c),
+            Err(e) => {
+                println!("Skipping: GPU context with ray tracing not available: {e:?}");
+                return;
+            }
+        }
+    };
+    if !context
+        .capabilities()
+        .ray_query
+        .contains(gpu::ShaderVisibility::COMPUTE)
+    {
+        println!("Skipping: ray_query compute not supported");
+        return;
+    }
+
+    let choir = choir::Choir::new();
+    let _worker = choir.add_worker("worker");
+    let mut asset_hub = blade_render::AssetHub::new(
+        &std::env::temp_dir().join("blade-render-timings-test"),
+        &choir,
+        &context,
+    );
+    let mut pacer = blade_render::util::FramePacer::new(&context);
+
+    let size = gpu::Extent {
+        width: 32,
+        height: 32,
+        depth: 1,
+    };
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 1.0, [0.5, 0.5, 0.5, 1.0])],
+    );
+    let objects = [blade_render::Object::from(asset_hub.models.insert(floor))];
+    let (command_encoder, temp) = pacer.begin_frame();
+    asset_hub.flush(command_encoder, &mut temp.buffers);
+    ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+    pacer.end_frame(&context);
+
+    let camera = blade_render::Camera {
+        pos: [0.0, 1.0, 2.0].into(),
+        rot: [0.0, 0.0, 0.0, 1.0].into(),
+        fov_y: 1.0,
+        depth: 100.0,
+        fov: None,
+        lens: blade_render::Lens::default(),
+        projection: blade_render::Projection::default(),
+    };
+    // Only the odd frames are denoised, so the passes tell the frames apart
+    let mut denoised_frames = Vec::new();
+    for _ in 0..FRAME_COUNT {
+        let (command_encoder, _) = pacer.begin_frame();
+        ray_tracer.prepare(
+            command_encoder,
+            &camera,
+            blade_render::FrameConfig::default(),
+        );
+        ray_tracer.ray_trace(
+            command_encoder,
+            blade_render::DebugConfig::default(),
+            blade_helpers::default_ray_config(),
+        );
+        let frame_index = denoised_frames.len();
+        let denoise = frame_index % 2 == 1;
+        ray_tracer.denoise(
+            command_encoder,
+            blade_render::DenoiserConfig {
+                enabled: denoise,
+                ..Default::default()
+            },
+        );
+        denoised_frames.push(denoise);
+        pacer.end_frame(&context);
+
+        let count = |name: &str| {
+            ray_tracer
+                .timings()
+                .iter()
+                .filter(|&&(pass, _)| pass == name)
+                .count()
+        };
+        match ray_tracer.timings_frame_index() {
+            Some(timed) => {
+                // The first frame after the scene build has index 1
+                assert!(timed <= frame_index + 1);
+                assert_eq!(count("ray-trace"), 1);
+                assert_eq!(count("temporal-accum"), denoised_frames[timed - 1] as usize);
+            }
+            None => assert!(ray_tracer.timings().is_empty()),
+        }
+    }
+    if ray_tracer.timings_frame_index().is_none() {
+        println!("Skipping: GPU timestamps are not supported");
+    } else {
+        assert!(ray_tracer.timings_frame_index().unwrap() + 4 > FRAME_COUNT);
+        for &(name, time) in ray_tracer.timings() {
+            println!("{name}: {time:?}");
+        }
+    }
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
@@ -0,0 +1,573 @@
+//! Lighting, shadows, and debug drawing of the rasterizer.
+#![allow(irrefutable_let_patterns)]
+#![cfg(not(gles))]
+
+use blade_graphics as gpu;
+use std::slice;
+
+#[allow(dead_code)]
+mod common;
+
+use common::snapshot;
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn raster_environment_ambient() {
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-raster-env-test", false)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut rasterizer = common::create_rasterizer(&context, &asset_hub, &mut pacer, size);
+
+    // A floor facing up, with a uniformly white sky around
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 2.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let objects = [blade_render::Object::from(asset_hub.models.insert(floor))];
+    let sky = asset_hub
+        .textures
+        .baker
+        .create_texture("white-sky", 4, 2, &[[0xFF; 4]; 8]);
+    let sky = asset_hub.textures.insert(sky);
+
+    // Looking down at the floor, which is only lit by the environment
+    let camera = common::top_down_camera(3.0);
+    let config = blade_render::RasterConfig {
+        light_color: [0.0; 3].into(),
+        ambient_color: [0.0; 3].into(),
+        ..Default::default()
+    };
+
+    let mut center_values = Vec::new();
+    for environment_map in [None, Some(sky)] {
+        let (command_encoder, temp) = pacer.begin_frame();
+        asset_hub.flush(command_encoder, &mut temp.buffers);
+        rasterizer.prepare(
+            command_encoder,
+            &camera,
+            &objects,
+            &asset_hub,
+            environment_map,
+            config,
+            &context,
+        );
+        command_encoder.init_texture(rasterizer.depth_texture());
+        if let mut pass = command_encoder.render(
+            "raster",
+            gpu::RenderTargetSet {
+                colors: &[gpu::RenderTarget {
+                    view: target.view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                    finish_op: gpu::FinishOp::Store,
+                }],
+                depth_stencil: Some(gpu::RenderTarget {
+                    view: rasterizer.depth_view(),
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::White),
+                    finish_op: gpu::FinishOp::Store,
+                }),
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        ) {
+            rasterizer.render(
+                &mut pass,
+                &camera,
+                &objects,
+                &asset_hub,
+                environment_map,
+                config,
+            );
+        }
+        if let mut transfer = command_encoder.transfer("read-back") {
+            transfer.copy_texture_to_buffer(
+                target.texture.into(),
+                target.readback.into(),
+                size.width * 4,
+                size,
+            );
+        }
+        let sync_point = pacer.end_frame(&context).clone();
+        assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+        let center = ((size.height / 2 * size.width + size.width / 2) * 4) as usize;
+        let value = unsafe { *target.readback.data().add(center) };
+        center_values.push(value);
+    }
+    println!("Floor brightness without and with the environment: {center_values:?}");
+    assert!(center_values[0] < 8, "The floor is lit without any light");
+    assert!(
+        center_values[1] > 100,
+        "The floor isn't lit by the environment"
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    rasterizer.destroy(&context);
+    pacer.destroy(&context);
+    target.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn raster_cascaded_shadows() {
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-raster-shadow-test", false)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut rasterizer = common::create_rasterizer(&context, &asset_hub, &mut pacer, size);
+
+    // A floor, and a plate hovering over it to the side
+    let make_quad = |name: &str, min: [f32; 2], max: [f32; 2], height: f32| {
+        let corners = [[min[0], min[1]], [min[0], max[1]], max, [max[0], min[1]]];
+        blade_render::ProceduralGeometry {
+            name: name.to_string(),
+            vertices: corners
+                .iter()
+                .map(|&[x, z]| {
+                    blade_render::Vertex::new(
+                        [x, height, z],
+                        [0.0, 0.0],
+                        [0.0, 1.0, 0.0],
+                        [1.0, 0.0, 0.0, 1.0],
+                    )
+                })
+                .collect(),
+            indices: vec![0, 1, 2, 0, 2, 3],
+            base_color_factor: [0.8, 0.8, 0.8, 1.0],
+        }
+    };
+    let scene = asset_hub.models.baker.create_model(
+        "scene",
+        vec![
+            make_quad("floor", [-2.0, -2.0], [2.0, 2.0], 0.0),
+            make_quad("plate", [0.5, -0.5], [1.5, 0.5], 1.0),
+        ],
+    );
+    let objects = [blade_render::Object::from(asset_hub.models.insert(scene))];
+
+    // Looking down at the floor center, where the plate casts its shadow
+    let camera = common::top_down_camera(3.0);
+
+    let mut center_values = Vec::new();
+    for cascade_count in [0, 3] {
+        let config = blade_render::RasterConfig {
+            light_dir: [1.0, 1.0, 0.0].into(),
+            ambient_color: [0.0; 3].into(),
+            shadows: blade_render::ShadowConfig {
+                cascade_count,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (command_encoder, temp) = pacer.begin_frame();
+        asset_hub.flush(command_encoder, &mut temp.buffers);
+        rasterizer.prepare(
+            command_encoder,
+            &camera,
+            &objects,
+            &asset_hub,
+            None,
+            config,
+            &context,
+        );
+        command_encoder.init_texture(rasterizer.depth_texture());
+        if let mut pass = command_encoder.render(
+            "raster",
+            gpu::RenderTargetSet {
+                colors: &[gpu::RenderTarget {
+                    view: target.view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                    finish_op: gpu::FinishOp::Store,
+                }],
+                depth_stencil: Some(gpu::RenderTarget {
+                    view: rasterizer.depth_view(),
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::White),
+                    finish_op: gpu::FinishOp::Store,
+                }),
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        ) {
+            rasterizer.render(&mut pass, &camera, &objects, &asset_hub, None, config);
+        }
+        if let mut transfer = command_encoder.transfer("read-back") {
+            transfer.copy_texture_to_buffer(
+                target.texture.into(),
+                target.readback.into(),
+                size.width * 4,
+                size,
+            );
+        }
+        let sync_point = pacer.end_frame(&context).clone();
+        assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+        let center = ((size.height / 2 * size.width + size.width / 2) * 4) as usize;
+        let value = unsafe { *target.readback.data().add(center) };
+        center_values.push(value);
+    }
+    println!("Floor brightness without and with the shadows: {center_values:?}");
+    assert!(center_values[0] > 100, "The floor isn't lit");
+    assert!(
+        center_values[1] < 8,
+        "The floor isn't shadowed by the plate"
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    rasterizer.destroy(&context);
+    pacer.destroy(&context);
+    target.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn raster_debug_draw() {
+    const RED: u32 = 0xFF0000FF;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-debug-draw-test", false)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    // The shapes are not limited by the capacity of the debug lines
+    let mut rasterizer = common::create_rasterizer(&context, &asset_hub, &mut pacer, size);
+
+    let camera = blade_render::Camera {
+        pos: [0.0, 0.0, 5.0].into(),
+        rot: mint::Quaternion {
+            s: 1.0,
+            v: [0.0; 3].into(),
+        },
+        fov_y: 1.0,
+        depth: 100.0,
+        fov: None,
+        lens: blade_render::Lens::default(),
+        projection: blade_render::Projection::default(),
+    };
+    let config = blade_render::RasterConfig {
+        space_sky: true,
+        ..Default::default()
+    };
+
+    let mut red_counts = Vec::new();
+    for with_shapes in [true, false] {
+        if with_shapes {
+            let debug_draw = rasterizer.debug_draw();
+            debug_draw.aabb([-1.0; 3].into(), [1.0; 3].into(), RED);
+            debug_draw.sphere([0.0; 3].into(), 1.5, RED);
+            debug_draw.line([-2.0, 0.0, 0.0].into(), [2.0, 0.0, 0.0].into(), RED);
+            assert_eq!(debug_draw.lines().len(), 12 + 3 * 32 + 1);
+        }
+        let (command_encoder, temp) = pacer.begin_frame();
+        asset_hub.flush(command_encoder, &mut temp.buffers);
+        rasterizer.prepare(
+            command_encoder,
+            &camera,
+            &[],
+            &asset_hub,
+            None,
+            config,
+            &context,
+        );
+        rasterizer.flush_debug_draw(&context, temp);
+        assert!(rasterizer.debug_draw().is_empty());
+        command_encoder.init_texture(rasterizer.depth_texture());
+        if let mut pass = command_encoder.render(
+            "raster",
+            gpu::RenderTargetSet {
+                colors: &[gpu::RenderTarget {
+                    view: target.view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                    finish_op: gpu::FinishOp::Store,
+                }],
+                depth_stencil: Some(gpu::RenderTarget {
+                    view: rasterizer.depth_view(),
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::White),
+                    finish_op: gpu::FinishOp::Store,
+                }),
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        ) {
+            rasterizer.render(&mut pass, &camera, &[], &asset_hub, None, config);
+            rasterizer.render_debug_lines(&mut pass, &camera, &[]);
+        }
+        if let mut transfer = command_encoder.transfer("read-back") {
+            transfer.copy_texture_to_buffer(
+                target.texture.into(),
+                target.readback.into(),
+                size.width * 4,
+                size,
+            );
+        }
+        let sync_point = pacer.end_frame(&context).clone();
+        assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+        let pixels = unsafe {
+            slice::from_raw_parts(
+                target.readback.data(),
+                (size.width * size.height * 4) as usize,
+            )
+        };
+        let red_count = pixels
+            .chunks(4)
+            .filter(|p| p[0] > 40 && p[1] < 20 && p[2] < 20)
+            .count();
+        red_counts.push(red_count);
+    }
+    println!("Red pixels with and without the shapes: {red_counts:?}");
+    assert!(red_counts[0] > 100, "The debug shapes are not drawn");
+    assert_eq!(red_counts[1], 0, "The debug shapes are not cleared");
+
+    pacer.wait_for_previous_frame(&context);
+    rasterizer.destroy(&context);
+    pacer.destroy(&context);
+    target.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn raster_visibility_layers() {
+    const FLOOR_LAYER: u32 = 1 << 2;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-layers-test", false)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut rasterizer = common::create_rasterizer(&context, &asset_hub, &mut pacer, size);
+
+    // A lit floor facing up, assigned to a non-default layer
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 2.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let mut floor_object = blade_render::Object::from(asset_hub.models.insert(floor));
+    assert_eq!(floor_object.layers, blade_render::DEFAULT_LAYERS);
+    floor_object.layers = FLOOR_LAYER;
+    let objects = [floor_object];
+
+    let camera = common::top_down_camera(3.0);
+
+    let mut center_values = Vec::new();
+    for visible_layers in [
+        blade_render::ALL_LAYERS,
+        FLOOR_LAYER | 1,
+        blade_render::DEFAULT_LAYERS,
+    ] {
+        let config = blade_render::RasterConfig {
+            visible_layers,
+            ..Default::default()
+        };
+        let (command_encoder, temp) = pacer.begin_frame();
+        asset_hub.flush(command_encoder, &mut temp.buffers);
+        rasterizer.prepare(
+            command_encoder,
+            &camera,
+            &objects,
+            &asset_hub,
+            None,
+            config,
+            &context,
+        );
+        command_encoder.init_texture(rasterizer.depth_texture());
+        if let mut pass = command_encoder.render(
+            "raster",
+            gpu::RenderTargetSet {
+                colors: &[gpu::RenderTarget {
+                    view: target.view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                    finish_op: gpu::FinishOp::Store,
+                }],
+                depth_stencil: Some(gpu::RenderTarget {
+                    view: rasterizer.depth_view(),
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::White),
+                    finish_op: gpu::FinishOp::Store,
+                }),
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        ) {
+            rasterizer.render(&mut pass, &camera, &objects, &asset_hub, None, config);
+        }
+        if let mut transfer = command_encoder.transfer("read-back") {
+            transfer.copy_texture_to_buffer(
+                target.texture.into(),
+                target.readback.into(),
+                size.width * 4,
+                size,
+            );
+        }
+        let sync_point = pacer.end_frame(&context).clone();
+        assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+        let center = ((size.height / 2 * size.width + size.width / 2) * 4) as usize;
+        let value = unsafe { *target.readback.data().add(center) };
+        center_values.push(value);
+    }
+    println!("Floor brightness with all, matching, and other layers: {center_values:?}");
+    assert!(center_values[0] > 40, "The floor isn't drawn");
+    assert_eq!(
+        center_values[1], center_values[0],
+        "A matching layer changes the floor"
+    );
+    assert_eq!(center_values[2], 0, "The hidden floor is drawn");
+
+    pacer.wait_for_previous_frame(&context);
+    rasterizer.destroy(&context);
+    pacer.destroy(&context);
+    target.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context"]
+fn raster_material_overrides() {
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-overrides-test", false)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut rasterizer = common::create_rasterizer(&context, &asset_hub, &mut pacer, size);
+
+    // A lit floor facing up
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 2.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let mut floor_object = blade_render::Object::from(asset_hub.models.insert(floor));
+
+    let camera = common::top_down_camera(3.0);
+
+    let mut center_values = Vec::new();
+    let darker = blade_render::MaterialOverrides {
+        base_color_factor: [0.5, 0.5, 0.5, 1.0],
+        ..Default::default()
+    };
+    for overrides in [blade_render::MaterialOverrides::default(), darker] {
+        floor_object.material_overrides = overrides;
+        let objects = std::slice::from_ref(&floor_object);
+        let config = blade_render::RasterConfig::default();
+        let (command_encoder, temp) = pacer.begin_frame();
+        asset_hub.flush(command_encoder, &mut temp.buffers);
+        rasterizer.prepare(
+            command_encoder,
+            &camera,
+            objects,
+            &asset_hub,
+            None,
+            config,
+            &context,
+        );
+        command_encoder.init_texture(rasterizer.depth_texture());
+        if let mut pass = command_encoder.render(
+            "raster",
+            gpu::RenderTargetSet {
+                colors: &[gpu::RenderTarget {
+                    view: target.view,
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::OpaqueBlack),
+                    finish_op: gpu::FinishOp::Store,
+                }],
+                depth_stencil: Some(gpu::RenderTarget {
+                    view: rasterizer.depth_view(),
+                    init_op: gpu::InitOp::Clear(gpu::TextureColor::White),
+                    finish_op: gpu::FinishOp::Store,
+                }),
+                depth_stencil_read_only: gpu::TexelAspects::empty(),
+                multiview: None,
+            },
+        ) {
+            rasterizer.render(&mut pass, &camera, objects, &asset_hub, None, config);
+        }
+        if let mut transfer = command_encoder.transfer("read-back") {
+            transfer.copy_texture_to_buffer(
+                target.texture.into(),
+                target.readback.into(),
+                size.width * 4,
+                size,
+            );
+        }
+        let sync_point = pacer.end_frame(&context).clone();
+        assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+        let center = ((size.height / 2 * size.width + size.width / 2) * 4) as usize;
+        let value = unsafe { *target.readback.data().add(center) };
+        center_values.push(value);
+    }
+    println!("Floor brightness without and with the overrides: {center_values:?}");
+    assert!(center_values[0] > 40, "The floor isn't drawn");
+    assert!(
+        center_values[1] < center_values[0],
+        "The base color override has no effect"
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    rasterizer.destroy(&context);
+    pacer.destroy(&context);
+    target.destroy(&context);
+    asset_hub.destroy();
+}
@@ -0,0 +1,754 @@
+//! Scene management of the renderer: picking, culling, and LOD selection.
+#![allow(irrefutable_let_patterns)]
+#![cfg(not(gles))]
+
+use blade_graphics as gpu;
+
+#[allow(dead_code)]
+mod common;
+
+use common::snapshot;
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn object_picking() {
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-picking-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 16,
+        height: 16,
+        depth: 1,
+    };
+    let (shaders, shader_task) =
+        blade_render::Shaders::load("blade-render/code/".as_ref(), &asset_hub, true);
+    shader_task.join();
+    let (command_encoder, _) = pacer.begin_frame();
+    let mut picker = blade_render::Picker::new(&shaders, &asset_hub.shaders, 3, &context);
+    let mut ray_tracer = blade_render::RayTracer::new(
+        command_encoder,
+        &context,
+        shaders,
+        &asset_hub.shaders,
+        &common::test_render_config(size),
+    );
+    pacer.end_frame(&context);
+
+    // The first object is too far, so the TLAS instances of the others don't match their indices
+    let quad = asset_hub.models.baker.create_model(
+        "quad",
+        vec![common::quad_geometry("quad", 1.0, [0.8, 0.8, 0.8, 1.0])],
+    );
+    let quad = asset_hub.models.insert(quad);
+    let objects = [[0.0, -500.0, 0.0], [-2.0, 0.0, 0.0], [2.0, 0.0, 0.0]].map(|offset| {
+        let mut object = blade_render::Object::from(quad);
+        object.transform = common::translation(offset);
+        object.prev_transform = object.transform;
+        object
+    });
+    let camera = common::top_down_camera(10.0);
+    let build = |pacer: &mut blade_render::util::FramePacer,
+                 ray_tracer: &mut blade_render::RayTracer,
+                 culling: blade_render::CullingConfig| {
+        // The culling of one frame is applied by the scene build of the next one
+        for _ in 0..2 {
+            let (command_encoder, temp) = pacer.begin_frame();
+            asset_hub.flush(command_encoder, &mut temp.buffers);
+            ray_tracer.build_scene(command_encoder, &objects, None, &asset_hub, &context, temp);
+            ray_tracer.prepare(
+                command_encoder,
+                &camera,
+                blade_render::FrameConfig {
+                    culling,
+                    ..Default::default()
+                },
+            );
+            pacer.end_frame(&context);
+        }
+    };
+    build(
+        &mut pacer,
+        &mut ray_tracer,
+        blade_render::CullingConfig {
+            max_distance: 100.0,
+            frustum: false,
+        },
+    );
+
+    // More picks than the capacity, so the first one gets overwritten
+    let (command_encoder, _) = pacer.begin_frame();
+    let tokens = [[4, 8], [4, 8], [11, 8], [8, 8]]
+        .map(|pixel| picker.pick(command_encoder, &ray_tracer, &camera, pixel));
+    let sync_point = pacer.end_frame(&context).clone();
+    assert!(context.wait_for(&sync_point, 5000).unwrap());
+
+    // The picks refer to the TLAS they were recorded with
+    build(
+        &mut pacer,
+        &mut ray_tracer,
+        blade_render::CullingConfig::default(),
+    );
+    pacer.wait_for_previous_frame(&context);
+
+    let results = tokens.map(|token| picker.resolve(token));
+    println!("Picks: {results:?}");
+    assert_eq!(results[0], None, "The pick has to be overwritten");
+    for (result, expected_object) in results[1..3].iter().zip([1, 2]) {
+        let result = result.expect("The quad is missed");
+        assert_eq!(result.object, expected_object);
+        assert_eq!(result.geometry, 0);
+        assert!(result.triangle < 2);
+        assert!(
+            (10.0..10.5).contains(&result.distance),
+            "Distance {} is off",
+            result.distance
+        );
+    }
+    assert_eq!(results[3], None, "The gap between the quads is hit");
+
+    picker.destroy(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+fn scene_light_handles() {
+    let light = |intensity| blade_render::Light {
+        kind: blade_render::LightKind::Point,
+        position: [0.0; 3].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity,
+    };
+    let mut scene = blade_render::Scene::new();
+    let first = scene.add_light(light(1.0));
+    let second = scene.add_light(light(2.0));
+    assert_eq!(scene.remove_light(first).map(|l| l.intensity), Some(1.0));
+    // The slot is reused, but the old handle stays stale
+    let third = scene.add_light(light(3.0));
+    assert_ne!(first, third);
+    assert!(scene.get_light(first).is_none());
+    assert!(scene.set_light(first, light(4.0)).is_none());
+    assert!(scene.remove_light(first).is_none());
+    assert_eq!(scene.get_light(third).map(|l| l.intensity), Some(3.0));
+    assert!(scene.set_light(second, light(5.0)).is_some());
+    let mut intensities = scene.lights().map(|l| l.intensity).collect::<Vec<_>>();
+    intensities.sort_by(f32::total_cmp);
+    assert_eq!(intensities, [3.0, 5.0]);
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn spawn_and_delete_objects() {
+    // Mean color of the blocks has to match the scene built from scratch within this range.
+    const TOLERANCE: f32 = 24.0;
+    const SPAWN_INTERVAL: u32 = 4;
+    const SPAWN_FRAMES: u32 = 96;
+    const SETTLE_FRAMES: u32 = 32;
+    const MAX_ALIVE: usize = 3;
+    // Allowance for the allocator keeping some of the freed blocks around.
+    const MEMORY_TOLERANCE: u64 = 1 << 20;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-spawn-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+
+    let floor = asset_hub.models.baker.create_model(
+        "floor",
+        vec![common::quad_geometry("floor", 2.0, [0.2, 0.2, 0.2, 1.0])],
+    );
+    let plate = asset_hub.models.baker.create_model(
+        "plate",
+        vec![common::quad_geometry("plate", 0.2, [0.0, 0.0, 1.0, 1.0])],
+    );
+    let floor = asset_hub.models.insert(floor);
+    let plate = asset_hub.models.insert(plate);
+
+    let mut scene = blade_render::Scene::new();
+    scene.add_object(blade_render::Object::from(floor));
+    scene.add_light(blade_render::Light {
+        kind: blade_render::LightKind::Point,
+        position: [0.0, 2.0, 0.0].into(),
+        direction: [0.0, -1.0, 0.0].into(),
+        color: [1.0; 3],
+        intensity: 10.0,
+    });
+    let camera = common::top_down_camera(3.0);
+    let spawn_position = |index: u32| {
+        let angle = index as f32 * 1.3;
+        [angle.cos(), 0.5, angle.sin()]
+    };
+
+    // Every spawn is a procedural mesh of its own, and an instance of a shared model
+    let mut alive = std::collections::VecDeque::new();
+    let mut removed = Vec::new();
+    let mut steady_stats = None;
+    let mut steady_usage = 0;
+    let mut image = Vec::new();
+    for frame in 0..SPAWN_FRAMES + SETTLE_FRAMES {
+        if frame < SPAWN_FRAMES && frame % SPAWN_INTERVAL == 0 {
+            let index = frame / SPAWN_INTERVAL;
+            if alive.len() == MAX_ALIVE {
+                let (mesh, instance) = alive.pop_front().unwrap();
+                assert!(scene.remove_procedural_mesh(&asset_hub, mesh).is_some());
+                assert!(scene.remove_object(instance).is_some());
+                removed.push(mesh);
+                removed.push(instance);
+            }
+            let [x, y, z] = spawn_position(index);
+            let mesh = scene.add_procedural_mesh(
+                &asset_hub,
+                common::quad_geometry("spawned", 0.2, [1.0, 0.0, 0.0, 1.0]),
+            );
+            scene.set_transform(mesh, common::translation([x, y, z]));
+            let instance = scene.add_object(blade_render::Object::from(plate));
+            scene.set_transform(instance, common::translation([-x, y, -z]));
+            alive.push_back((mesh, instance));
+        }
+        image = common::render_denoised_frame_with(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &target,
+            &camera,
+            frame == 0,
+            |ray_tracer, command_encoder, temp| {
+                asset_hub.flush(command_encoder, &mut temp.buffers);
+                ray_tracer.update_scene(
+                    command_encoder,
+                    &mut scene,
+                    None,
+                    &asset_hub,
+                    &context,
+                    temp,
+                );
+                asset_hub.evict(temp);
+            },
+        );
+        // Check after the first objects got removed and their resources reused
+        if frame == (MAX_ALIVE as u32 + 2) * SPAWN_INTERVAL {
+            pacer.wait_for_previous_frame(&context);
+            steady_stats = Some(ray_tracer.scene_stats());
+            steady_usage = context.memory_stats().usage;
+        }
+    }
+
+    // The handles of the removed objects stay stale, even with the slots reused
+    for handle in removed {
+        assert!(scene.get(handle).is_none());
+        assert!(
+            scene
+                .set_transform(handle, common::translation([0.0; 3]))
+                .is_none()
+        );
+        assert!(scene.remove_object(handle).is_none());
+    }
+
+    pacer.wait_for_previous_frame(&context);
+    let stats = ray_tracer.scene_stats();
+    println!("Scene stats: {stats:?}");
+    assert_eq!(
+        stats.full_builds, 1,
+        "Adding or removing objects rebuilt the scene"
+    );
+    assert_eq!(stats.objects, 1 + 2 * MAX_ALIVE as u32);
+    assert_eq!(stats.hit_entries, stats.objects);
+    assert_eq!(Some(stats), steady_stats, "Freed hit entries aren't reused");
+    let usage = context.memory_stats().usage;
+    println!("Memory usage: {steady_usage} -> {usage}");
+    assert!(
+        usage <= steady_usage + MEMORY_TOLERANCE,
+        "GPU memory grew from {steady_usage} to {usage}"
+    );
+
+    // The scene built from scratch has the same objects
+    let objects = scene
+        .objects()
+        .iter()
+        .map(|object| blade_render::Object {
+            transform: object.transform,
+            prev_transform: object.transform,
+            ..blade_render::Object::from(object.model)
+        })
+        .collect::<Vec<_>>();
+    let mut reference = Vec::new();
+    for frame in 0..SETTLE_FRAMES {
+        reference = common::render_denoised_frame(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &asset_hub,
+            &target,
+            &camera,
+            &objects,
+            frame == 0,
+        );
+    }
+    let max_error = common::max_block_error(&image, &reference, size);
+    println!("Max block error: {max_error}");
+    assert!(
+        max_error < TOLERANCE,
+        "The updated scene differs from the built one by {max_error}"
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    target.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn instanced_model_shares_blas() {
+    const INSTANCE_COUNT: usize = 500;
+    const FRAME_COUNT: u32 = 16;
+
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-instancing-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 64,
+        height: 64,
+        depth: 1,
+    };
+    let format = gpu::TextureFormat::Rgba8Unorm;
+    let target = snapshot::OffscreenTarget::new(&context, size, format);
+    let mut ray_tracer = common::create_ray_tracer(&context, &asset_hub, &mut pacer, size);
+    let mut build = |ray_tracer: &mut blade_render::RayTracer,
+                     asset_hub: &blade_render::AssetHub,
+                     objects: &[blade_render::Object]| {
+        let (command_encoder, temp) = pacer.begin_frame();
+        asset_hub.flush(command_encoder, &mut temp.buffers);
+        ray_tracer.build_scene(command_encoder, objects, None, asset_hub, &context, temp);
+        pacer.end_frame(&context);
+        pacer.wait_for_previous_frame(&context);
+        ray_tracer.scene_stats()
+    };
+    let spread = |index: usize| {
+        common::translation([
+            (index % 25) as f32 * 0.1 - 1.2,
+            0.0,
+            (index / 25) as f32 * 0.1 - 1.0,
+        ])
+    };
+
+    // Every model gets a copy of the vertices and a BLAS of its own
+    let usage_before = context.memory_stats().usage;
+    let separate_models = (0..INSTANCE_COUNT)
+        .map(|_| {
+            let model = asset_hub.models.baker.create_model(
+                "separate",
+                vec![common::quad_geometry("separate", 0.04, [1.0; 4])],
+            );
+            asset_hub.models.insert(model)
+        })
+        .collect::<Vec<_>>();
+    let objects = separate_models
+        .iter()
+        .enumerate()
+        .map(|(index, &model)| blade_render::Object {
+            transform: spread(index),
+            ..blade_render::Object::from(model)
+        })
+        .collect::<Vec<_>>();
+    let separate_stats = build(&mut ray_tracer, &asset_hub, &objects);
+    let separate_usage = context.memory_stats().usage.saturating_sub(usage_before);
+    assert_eq!(separate_stats.bottom_levels, INSTANCE_COUNT as u32);
+
+    let model = asset_hub.models.baker.create_model(
+        "instanced",
+        vec![common::quad_geometry("instanced", 0.04, [1.0; 4])],
+    );
+    let model = asset_hub.models.insert(model);
+    for &model in separate_models.iter() {
+        asset_hub.models.release(model);
+    }
+    let objects = (0..INSTANCE_COUNT)
+        .map(|index| blade_render::Object {
+            transform: spread(index),
+            ..blade_render::Object::from(model)
+        })
+        .collect::<Vec<_>>();
+    let usage_before = context.memory_stats().usage;
+    let shared_stats = build(&mut ray_tracer, &asset_hub, &objects);
+    let (_, temp) = pacer.begin_frame();
+    asset_hub.evict(temp);
+    pacer.end_frame(&context);
+    pacer.wait_for_previous_frame(&context);
+    let shared_usage = context.memory_stats().usage;
+    println!(
+        "Memory of {INSTANCE_COUNT} models: {separate_usage} bytes, \
+        after switching to the instances of one: {} bytes",
+        shared_usage as i64 - usage_before as i64
+    );
+    // The instances still get their own hit entries, for the transforms and the overrides
+    assert_eq!(shared_stats.bottom_levels, 1);
+    assert_eq!(shared_stats.hit_entries, INSTANCE_COUNT as u32);
+    if usage_before != 0 {
+        assert!(
+            shared_usage < usage_before,
+            "Instancing doesn't save memory"
+        );
+    }
+
+    // Instances of the same model are shaded with their own material overrides
+    let tinted = |x: f32, color: [f32; 3]| blade_render::Object {
+        transform: common::translation([x, 0.0, 0.0]),
+        material_overrides: blade_render::MaterialOverrides {
+            base_color_factor: [color[0], color[1], color[2], 1.0],
+            ..Default::default()
+        },
+        ..blade_render::Object::from(model)
+    };
+    let objects = [tinted(-0.5, [1.0, 0.0, 0.0]), tinted(0.5, [0.0, 0.0, 1.0])];
+    let camera = common::top_down_camera(3.0);
+    let mut image = Vec::new();
+    for frame in 0..FRAME_COUNT {
+        image = common::render_denoised_frame(
+            &context,
+            &mut pacer,
+            &mut ray_tracer,
+            &asset_hub,
+            &target,
+            &camera,
+            &objects,
+            frame == 0,
+        );
+    }
+    assert_eq!(ray_tracer.scene_stats().bottom_levels, 1);
+    // Sum of the red and blue channels over the left and the right halves
+    let width = size.width as usize;
+    let mut sums = [[0u64; 2]; 2];
+    for (index, pixel) in image.chunks(4).enumerate() {
+        let half = (index % width >= width / 2) as usize;
+        sums[half][0] += pixel[0] as u64;
+        sums[half][1] += pixel[2] as u64;
+    }
+    println!("Red and blue of the halves: {sums:?}");
+    assert!(sums[0][0] > sums[0][1], "Left instance isn't red");
+    assert!(sums[1][1] > sums[1][0], "Right instance isn't blue");
+
+    pacer.wait_for_previous_frame(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    target.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn instance_culling() {
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-culling-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 16,
+        height: 16,
+        depth: 1,
+    };
+    let (shaders, shader_task) =
+        blade_render::Shaders::load("blade-render/code/".as_ref(), &asset_hub, true);
+    shader_task.join();
+    let (command_encoder, _) = pacer.begin_frame();
+    let mut picker = blade_render::Picker::new(&shaders, &asset_hub.shaders, 1, &context);
+    let mut ray_tracer = blade_render::RayTracer::new(
+        command_encoder,
+        &context,
+        shaders,
+        &asset_hub.shaders,
+        &blade_render::RenderConfig {
+            surface_size: size,
+            surface_info: gpu::SurfaceInfo {
+                format: gpu::TextureFormat::Rgba8Unorm,
+                alpha: gpu::AlphaMode::Ignored,
+            },
+            max_debug_lines: 1,
+        },
+    );
+    pacer.end_frame(&context);
+
+    // A wall facing the camera, placed behind it, in front of it, and far away
+    let corners = [[-1.0, -1.0], [-1.0, 1.0], [1.0, 1.0], [1.0, -1.0]];
+    let wall = asset_hub.models.baker.create_model(
+        "wall",
+        vec![blade_render::ProceduralGeometry {
+            name: "wall".to_string(),
+            vertices: corners
+                .iter()
+                .map(|&[x, y]| {
+                    blade_render::Vertex::new(
+                        [x, y, 0.0],
+                        [0.0, 0.0],
+                        [0.0, 0.0, 1.0],
+                        [1.0, 0.0, 0.0, 1.0],
+                    )
+                })
+                .collect(),
+            indices: vec![0, 1, 2, 0, 2, 3],
+            base_color_factor: [0.8, 0.8, 0.8, 1.0],
+        }],
+    );
+    let wall = asset_hub.models.insert(wall);
+    let mut scene = blade_render::Scene::new();
+    for z in [5.0, -500.0, -5.0] {
+        let handle = scene.add_object(blade_render::Object::from(wall));
+        scene.set_transform(
+            handle,
+            gpu::Transform {
+                x: [1.0, 0.0, 0.0, 0.0].into(),
+                y: [0.0, 1.0, 0.0, 0.0].into(),
+                z: [0.0, 0.0, 1.0, z].into(),
+            },
+        );
+    }
+
+    // Looking along -Z from the origin
+    let camera = blade_render::Camera {
+        pos: [0.0; 3].into(),
+        rot: mint::Quaternion {
+            s: 1.0,
+            v: [0.0; 3].into(),
+        },
+        fov_y: 1.0,
+        depth: 1000.0,
+        fov: None,
+        lens: blade_render::Lens::default(),
+        projection: blade_render::Projection::default(),
+    };
+    let mut stats = Vec::new();
+    let mut picks = Vec::new();
+    for culling in [
+        blade_render::CullingConfig::default(),
+        blade_render::CullingConfig {
+            max_distance: 100.0,
+            frustum: true,
+        },
+    ] {
+        // The culling of one frame is applied by the scene update of the next one
+        for _ in 0..2 {
+            let (command_encoder, temp) = pacer.begin_frame();
+            asset_hub.flush(command_encoder, &mut temp.buffers);
+            ray_tracer.update_scene(
+                command_encoder,
+                &mut scene,
+                None,
+                &asset_hub,
+                &context,
+                temp,
+            );
+            ray_tracer.prepare(
+                command_encoder,
+                &camera,
+                blade_render::FrameConfig {
+                    culling,
+                    ..Default::default()
+                },
+            );
+            pacer.end_frame(&context);
+        }
+        let (command_encoder, _) = pacer.begin_frame();
+        let token = picker.probe_focus(command_encoder, &ray_tracer, &camera);
+        let sync_point = pacer.end_frame(&context).clone();
+        assert!(context.wait_for(&sync_point, 5000).unwrap());
+        stats.push(ray_tracer.culling_stats());
+        picks.push(picker.resolve(token).map(|result| result.object));
+    }
+    println!("Culling stats without and with the culling: {stats:?}");
+    assert_eq!(
+        stats[0],
+        blade_render::CullingStats {
+            instances: 3,
+            kept: 3,
+            primary: 3,
+        }
+    );
+    assert_eq!(
+        stats[1],
+        blade_render::CullingStats {
+            instances: 3,
+            kept: 2,
+            primary: 1,
+        },
+        "The far wall has to be removed, and the one behind hidden from the camera"
+    );
+    assert_eq!(
+        picks,
+        [Some(2), Some(2)],
+        "The picks don't match the objects"
+    );
+
+    pacer.wait_for_previous_frame(&context);
+    picker.destroy(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
+
+#[test]
+#[ignore = "requires a working GPU context with ray tracing"]
+fn lod_selection() {
+    let Some(common::TestBed {
+        context,
+        mut asset_hub,
+        mut pacer,
+        _worker,
+    }) = common::TestBed::new("blade-lod-test", true)
+    else {
+        return;
+    };
+
+    let size = gpu::Extent {
+        width: 16,
+        height: 16,
+        depth: 1,
+    };
+    let (shaders, shader_task) =
+        blade_render::Shaders::load("blade-render/code/".as_ref(), &asset_hub, true);
+    shader_task.join();
+    let (command_encoder, _) = pacer.begin_frame();
+    let mut picker = blade_render::Picker::new(&shaders, &asset_hub.shaders, 1, &context);
+    let mut ray_tracer = blade_render::RayTracer::new(
+        command_encoder,
+        &context,
+        shaders,
+        &asset_hub.shaders,
+        &blade_render::RenderConfig {
+            surface_size: size,
+            surface_info: gpu::SurfaceInfo {
+                format: gpu::TextureFormat::Rgba8Unorm,
+                alpha: gpu::AlphaMode::Ignored,
+            },
+            max_debug_lines: 1,
+        },
+    );
+    pacer.end_frame(&context);
+
+    // The coarse level is a wall further away, to tell it apart by the hit distance
+    let corners = [[-1.0, -1.0], [-1.0, 1.0], [1.0, 1.0], [1.0, -1.0]];
+    let make_wall = |name: &str, z: f32| {
+        let model = asset_hub.models.baker.create_model(
+            name,
+            vec![blade_render::ProceduralGeometry {
+                name: name.to_string(),
+                vertices: corners
+                    .iter()
+                    .map(|&[x, y]| {
+                        blade_render::Vertex::new(
+                            [x, y, z],
+                            [0.0, 0.0],
+                            [0.0, 0.0, 1.0],
+                            [1.0, 0.0, 0.0, 1.0],
+                        )
+                    })
+                    .collect(),
+                indices: vec![0, 1, 2, 0, 2, 3],
+                base_color_factor: [0.8, 0.8, 0.8, 1.0],
+            }],
+        );
+        asset_hub.models.insert(model)
+    };
+    let fine = make_wall("fine", 0.0);
+    let coarse = make_wall("coarse", -1.0);
+    ray_tracer.set_lod_chain(
+        fine,
+        &[blade_render::LodLevel {
+            model: coarse,
+            distance: 20.0,
+        }],
+    );
+    let mut scene = blade_render::Scene::new();
+    let handle = scene.add_object(blade_render::Object::from(fine));
+    scene.set_transform(
+        handle,
+        gpu::Transform {
+            x: [1.0, 0.0, 0.0, 0.0].into(),
+            y: [0.0, 1.0, 0.0, 0.0].into(),
+            z: [0.0, 0.0, 1.0, -10.0].into(),
+        },
+    );
+
+    // Moving away past the switch, back into the hysteresis margin, and closer
+    let mut distances = Vec::new();
+    for camera_z in [0.0, 15.0, 11.0, 5.0] {
+        let camera = blade_render::Camera {
+            pos: [0.0, 0.0, camera_z].into(),
+            rot: mint::Quaternion {
+                s: 1.0,
+                v: [0.0; 3].into(),
+            },
+            fov_y: 1.0,
+            depth: 1000.0,
+            fov: None,
+            lens: blade_render::Lens::default(),
+            projection: blade_render::Projection::default(),
+        };
+        let (command_encoder, temp) = pacer.begin_frame();
+        asset_hub.flush(command_encoder, &mut temp.buffers);
+        ray_tracer.prepare(command_encoder, &camera, Default::default());
+        ray_tracer.update_scene(
+            command_encoder,
+            &mut scene,
+            None,
+            &asset_hub,
+            &context,
+            temp,
+        );
+        let token = picker.probe_focus(command_encoder, &ray_tracer, &camera);
+        let sync_point = pacer.end_frame(&context).clone();
+        assert!(context.wait_for(&sync_point, 5000).unwrap());
+        let result = picker.resolve(token).expect("The wall isn't hit");
+        assert_eq!(result.object, 0);
+        distances.push(result.distance);
+    }
+    println!("Hit distances: {distances:?}");
+    for (distance, expected) in distances.iter().zip([10.0, 26.0, 22.0, 15.0]) {
+        assert!(
+            (distance - expected).abs() < 0.01,
+            "Wrong level of detail at {distances:?}"
+        );
+    }
+
+    pacer.wait_for_previous_frame(&context);
+    picker.destroy(&context);
+    ray_tracer.destroy(&context);
+    pacer.destroy(&context);
+    asset_hub.destroy();
+}
@@ -0,0 +1,34 @@
+struct Params {
+    // Rectangle in NDC: min x, min y, max x, max y
+    rect: vec4<f32>,
+    color: vec4<f32>,
+    size: vec2<f32>,
+    depth: f32,
+    pad: f32,
+};
+var<uniform> params: Params;
+var depth_source: texture_depth_2d;
+var stencil_source: texture_2d<u32>;
+
+@vertex
+fn vs_quad(@builtin(vertex_index) vi: u32) -> @builtin(position) vec4<f32> {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let pos = mix(params.rect.xy, params.rect.zw, corners[vi]);
+    return vec4<f32>(pos, params.depth, 1.0);
+}
+
+// Reads both aspects of the depth-stencil target bound to the pass.
+@fragment
+fn fs_aspects(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(position.xy);
+    let depth = textureLoad(depth_source, coord, 0);
+    let stencil = textureLoad(stencil_source, coord, 0).r;
+    return vec4<f32>(depth, f32(stencil), 0.0, 1.0);
+}
@@ -0,0 +1,100 @@
+struct Params {
+    // Rectangle in NDC: min x, min y, max x, max y
+    rect: vec4<f32>,
+    color: vec4<f32>,
+    size: vec2<f32>,
+    depth: f32,
+    pad: f32,
+};
+var<uniform> params: Params;
+var source: texture_2d<f32>;
+var source_sampler: sampler;
+var output: texture_storage_2d<rgba8unorm, write>;
+
+@vertex
+fn vs_quad(@builtin(vertex_index) vi: u32) -> @builtin(position) vec4<f32> {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let pos = mix(params.rect.xy, params.rect.zw, corners[vi]);
+    return vec4<f32>(pos, params.depth, 1.0);
+}
+
+// The long edge passes in between the pixel centers of a 64x64 target,
+// so the coverage doesn't depend on the tie-breaking rules.
+@vertex
+fn vs_triangle(@builtin(vertex_index) vi: u32) -> @builtin(position) vec4<f32> {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -63.0 / 64.0),
+        vec2<f32>(-63.0 / 64.0, 1.0),
+    );
+    return vec4<f32>(positions[vi], 0.0, 1.0);
+}
+
+struct TexturedVertex {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Same triangle as `vs_triangle`, covering the pixels on and below the diagonal,
+// in counter-clockwise order, or clockwise if `params.pad` is set.
+// The texture coordinates follow `blade_graphics::NDC_TO_UV`.
+@vertex
+fn vs_textured_triangle(@builtin(vertex_index) vi: u32) -> TexturedVertex {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -63.0 / 64.0),
+        vec2<f32>(-63.0 / 64.0, 1.0),
+    );
+    let pos = positions[select(vi, 2u - vi, params.pad != 0.0)];
+    let uv = pos * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5);
+    return TexturedVertex(vec4<f32>(pos, params.depth, 1.0), uv);
+}
+
+@fragment
+fn fs_textured(input: TexturedVertex) -> @location(0) vec4<f32> {
+    return textureSampleLevel(source, source_sampler, input.uv, 0.0);
+}
+
+@fragment
+fn fs_color() -> @location(0) vec4<f32> {
+    return params.color;
+}
+
+@fragment
+fn fs_flags() -> @location(0) vec4<u32> {
+    return vec4<u32>(params.color);
+}
+
+@fragment
+fn fs_alpha_ramp(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return vec4<f32>(params.color.rgb, position.x / params.size.x);
+}
+
+@fragment
+fn fs_gradient(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let value = position.x / params.size.x;
+    return vec4<f32>(vec3<f32>(value), 1.0);
+}
+
+@fragment
+fn fs_sample(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureSampleLevel(source, source_sampler, position.xy / params.size, 0.0);
+}
+
+@fragment
+fn fs_load(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureLoad(source, vec2<i32>(position.xy), 0);
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_pattern(@builtin(global_invocation_id) id: vec3<u32>) {
+    let value = vec3<u32>(id.x * 4u, id.y * 4u, (id.x ^ id.y) * 4u) & vec3<u32>(0xFFu);
+    textureStore(output, id.xy, vec4<f32>(vec3<f32>(value) / 255.0, 1.0));
+}
@@ -0,0 +1,12 @@
+// Covers the whole target with a single triangle.
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vi: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vi << 1u) & 2u), f32(vi & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Writes the index of the view into the red channel.
+@fragment
+fn fs_view_index(@builtin(view_index) view_index: u32) -> @location(0) vec4<f32> {
+    return vec4<f32>(f32(view_index + 1u) / 255.0, 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,21 @@
+enable wgpu_ray_query;
+
+var acc_struct: acceleration_structure;
+var<storage, read_write> hits: array<u32>;
+
+// Casts the rays down the Z axis, two units apart along X,
+// recording 1 + custom index of the hit instance, or 0 on a miss.
+@compute
+@workgroup_size(8)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let origin = vec3<f32>(f32(gid.x) * 2.0, 0.0, 1.0);
+    var rq: ray_query;
+    rayQueryInitialize(&rq, acc_struct, RayDesc(RAY_FLAG_NONE, 0xFFu, 0.0, 10.0, origin, vec3<f32>(0.0, 0.0, -1.0)));
+    rayQueryProceed(&rq);
+    let intersection = rayQueryGetCommittedIntersection(&rq);
+    if (intersection.kind == RAY_QUERY_INTERSECTION_TRIANGLE) {
+        hits[gid.x] = intersection.instance_custom_data + 1u;
+    } else {
+        hits[gid.x] = 0u;
+    }
+}
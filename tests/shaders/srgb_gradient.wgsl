@@ -0,0 +1,24 @@
+var source: texture_2d<f32>;
+
+struct Output {
+    @location(0) decoded: vec4<f32>,
+    @location(1) encoded: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vi: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(2.0 * f32(vi & 1u), f32(vi & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> Output {
+    let x = i32(position.x);
+    let width = textureDimensions(source).x;
+    var output: Output;
+    // sampling an sRGB texture returns linear values
+    output.decoded = vec4<f32>(textureLoad(source, vec2<i32>(x, 0), 0).r, 0.0, 0.0, 1.0);
+    // rendering linear values into an sRGB target encodes them
+    output.encoded = vec4<f32>(f32(x) / f32(width - 1u), 0.0, 0.0, 1.0);
+    return output;
+}
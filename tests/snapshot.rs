@@ -175,7 +175,7 @@ pub fn check(name: &str, pixels: &[u8], size: gpu::Extent) {
     }
 }
 
-fn load_reference(path: &Path) -> (Vec<u8>, gpu::Extent) {
+pub fn load_reference(path: &Path) -> (Vec<u8>, gpu::Extent) {
     let file = std::fs::File::open(path).unwrap_or_else(|e| {
         panic!(
             "Failed to open reference image '{}': {}. \
@@ -197,7 +197,7 @@ fn load_reference(path: &Path) -> (Vec<u8>, gpu::Extent) {
     (buf, size)
 }
 
-fn save_image(path: &Path, data: &[u8], size: gpu::Extent) {
+pub fn save_image(path: &Path, data: &[u8], size: gpu::Extent) {
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent).unwrap();
     }
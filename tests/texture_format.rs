@@ -0,0 +1,86 @@
+use blade_graphics::{ClearColor, TexelAspects, TextureFormat};
+use std::collections::HashSet;
+
+#[test]
+fn all_formats_are_unique() {
+    let unique = TextureFormat::ALL.iter().collect::<HashSet<_>>();
+    assert_eq!(unique.len(), TextureFormat::ALL.len());
+    assert!(TextureFormat::ALL.is_sorted());
+}
+
+#[test]
+fn format_properties_agree() {
+    for &format in TextureFormat::ALL {
+        let info = format.block_info();
+        let aspects = format.aspects();
+        let name = format!("{format:?}");
+
+        assert_ne!(info.size, 0, "{format:?} has empty blocks");
+        assert!(!aspects.is_empty(), "{format:?} has no aspects");
+        assert!(format.is_copy_compatible(format));
+        assert_eq!(
+            format.is_srgb(),
+            name.ends_with("Srgb"),
+            "{format:?} is named inconsistently with its encoding"
+        );
+
+        if info.dimensions != (1, 1) {
+            assert_eq!(info.dimensions, (4, 4), "{format:?} has odd blocks");
+            assert!(
+                info.size == 8 || info.size == 16,
+                "{format:?} has odd block size {}",
+                info.size
+            );
+        }
+        if info.dimensions != (1, 1) || format.is_srgb() {
+            assert_eq!(aspects, TexelAspects::COLOR, "{format:?} isn't color");
+        }
+        if aspects != TexelAspects::COLOR {
+            assert!(!aspects.contains(TexelAspects::COLOR));
+            assert_eq!(info.dimensions, (1, 1));
+            assert!(!format.is_copy_compatible(TextureFormat::R32Float));
+        }
+    }
+}
+
+#[test]
+fn srgb_formats_have_linear_pairs() {
+    for &format in TextureFormat::ALL.iter().filter(|format| format.is_srgb()) {
+        let name = format!("{format:?}");
+        let linear_name = name.strip_suffix("Srgb").unwrap();
+        let linear = TextureFormat::ALL
+            .iter()
+            .find(|other| format!("{other:?}") == linear_name)
+            .unwrap_or_else(|| panic!("{format:?} has no linear pair"));
+        assert!(!linear.is_srgb());
+        assert!(format.is_copy_compatible(*linear));
+        assert_eq!(
+            linear.block_info().dimensions,
+            format.block_info().dimensions
+        );
+    }
+}
+
+#[test]
+fn clear_colors_fit_texel_kinds() {
+    let float = ClearColor::Float([0.5; 4]);
+    let uint = ClearColor::Uint([!0; 4]);
+    for &format in TextureFormat::ALL {
+        let is_color = format.aspects() == TexelAspects::COLOR;
+        assert_eq!(
+            float.fits(format),
+            is_color && !format.is_integer(),
+            "{format:?}"
+        );
+        assert_eq!(
+            uint.fits(format),
+            is_color && format.is_integer(),
+            "{format:?}"
+        );
+    }
+    assert!(float.fits(TextureFormat::Rg8Snorm));
+    assert!(!float.fits(TextureFormat::R32Uint));
+    assert!(!float.fits(TextureFormat::Depth32Float));
+    assert!(uint.fits(TextureFormat::R32Uint));
+    assert!(!uint.fits(TextureFormat::Stencil8Uint));
+}